@@ -18,6 +18,10 @@ impl Agent {
             content: text.to_string(),
         })
     }
+    /// address other agents reach this agent's node under
+    pub fn address(&self) -> String {
+        self.identity.content.clone()
+    }
 }
 
 #[cfg(test)]
@@ -33,5 +37,6 @@ mod tests {
 
         let agent = Agent::from_string("jane");
         assert_eq!(agent.identity.content, "jane".to_string());
+        assert_eq!(agent.address(), "jane".to_string());
     }
 }
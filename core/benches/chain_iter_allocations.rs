@@ -0,0 +1,58 @@
+//! counts heap allocations while iterating a chain of 100k entries, with an allocator of its own
+//! so the test binary of core keeps the system allocator
+//! run with `cargo bench -p holochain_core --bench chain_iter_allocations`
+
+extern crate holochain_core;
+
+use holochain_core::{
+    chain::{Chain, ChainWrite}, hash_table::{entry::Entry, memory::MemTable},
+};
+use std::{
+    alloc::{GlobalAlloc, Layout, System}, rc::Rc,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+const ENTRIES: usize = 100_000;
+
+/// counts every heap allocation made by the bench
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn main() {
+    let mut chain = Chain::new(Rc::new(MemTable::new()));
+    for i in 0..ENTRIES {
+        chain
+            .push(&Entry::new("testEntryType", &format!("entry {}", i)))
+            .unwrap();
+    }
+
+    let before = ALLOCATIONS.load(Ordering::SeqCst);
+    let bytes: usize = chain
+        .iter()
+        .map(|p| p.entry().content().len() + p.header().entry_type().len())
+        .sum();
+    let allocations = ALLOCATIONS.load(Ordering::SeqCst) - before;
+
+    assert!(bytes > 0);
+    println!(
+        "iterating {} entries: {} allocations ({} per entry)",
+        ENTRIES,
+        allocations,
+        allocations as f64 / ENTRIES as f64
+    );
+}
@@ -0,0 +1,57 @@
+//! cold vs. warm zome call latency: compiling the module for every call against taking it from
+//! the ModuleCache
+//! run with `cargo bench -p holochain_core --bench module_cache`
+
+#[macro_use]
+extern crate criterion;
+extern crate holochain_core;
+extern crate wabt;
+
+use criterion::Criterion;
+use holochain_core::{
+    instance::Observer, nucleus::{module_cache::ModuleCache, ribosome::{self, HostContext}},
+    state::ActionWrapper,
+};
+use std::sync::mpsc::channel;
+
+/// a module exporting memory and a `test_dispatch` function that returns 0
+fn module_code() -> Vec<u8> {
+    wabt::wat2wasm(
+        r#"
+(module
+    (memory (export "memory") 1)
+    (func (export "test_dispatch") (param i32 i32) (result i32)
+        (i32.const 0)
+    )
+)
+"#,
+    ).unwrap()
+}
+
+fn module_cache(c: &mut Criterion) {
+    let (action_channel, _) = channel::<ActionWrapper>();
+    let (observer_channel, _) = channel::<Observer>();
+    let code = module_code();
+    let host = HostContext::default();
+
+    c.bench_function("cold call", |b| {
+        b.iter(|| {
+            let module = ModuleCache::default().get_or_compile(&code).unwrap();
+            ribosome::call_module(&action_channel, &observer_channel, &module, "test", None, &host)
+                .unwrap();
+        })
+    });
+
+    let cache = ModuleCache::default();
+    cache.precompile(&code).unwrap();
+    c.bench_function("warm call", |b| {
+        b.iter(|| {
+            let module = cache.get_or_compile(&code).unwrap();
+            ribosome::call_module(&action_channel, &observer_channel, &module, "test", None, &host)
+                .unwrap();
+        })
+    });
+}
+
+criterion_group!(benches, module_cache);
+criterion_main!(benches);
@@ -0,0 +1,10 @@
+extern crate prost_build;
+extern crate protoc_bin_vendored;
+
+fn main() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc for this platform");
+    std::env::set_var("PROTOC", protoc);
+    println!("cargo:rerun-if-changed=proto/network.proto");
+    prost_build::compile_protos(&["proto/network.proto"], &["proto"])
+        .expect("proto/network.proto compiles");
+}
@@ -0,0 +1,60 @@
+//! agents block other agents at the app layer with the block_agent and unblock_agent host
+//! functions, which record it on the chain as entries holding the blocked address, the last one
+//! for an address says whether it is blocked
+//! the entries are private, they are kept on the chain but not published
+//! direct messages from blocked agents are dropped, see network::direct_message, and reads that
+//! ask to exclude_blocked leave out what blocked agents authored
+
+use hash_table::entry::Entry;
+use std::collections::BTreeSet;
+
+/// entry type of the system entry blocking the agent at the address it holds
+pub const BLOCK_AGENT_ENTRY_TYPE: &str = "%block_agent";
+/// entry type of the system entry lifting the block of the agent at the address it holds
+pub const UNBLOCK_AGENT_ENTRY_TYPE: &str = "%unblock_agent";
+
+pub fn block_entry(address: &str) -> Entry {
+    Entry::new(BLOCK_AGENT_ENTRY_TYPE, address)
+}
+
+pub fn unblock_entry(address: &str) -> Entry {
+    Entry::new(UNBLOCK_AGENT_ENTRY_TYPE, address)
+}
+
+/// true for the entries that stay on the chain without being published
+pub fn is_private(entry: &Entry) -> bool {
+    entry.entry_type() == BLOCK_AGENT_ENTRY_TYPE || entry.entry_type() == UNBLOCK_AGENT_ENTRY_TYPE
+}
+
+/// update the blocked agents with a committed entry, other entries change nothing
+pub fn apply(blocked: &mut BTreeSet<String>, entry: &Entry) {
+    match entry.entry_type() {
+        BLOCK_AGENT_ENTRY_TYPE => {
+            blocked.insert(entry.content().to_string());
+        }
+        UNBLOCK_AGENT_ENTRY_TYPE => {
+            blocked.remove(entry.content());
+        }
+        _ => (),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::entry::tests::test_entry;
+
+    #[test]
+    /// the last entry for an address decides
+    fn applied_in_order() {
+        let mut blocked = BTreeSet::new();
+        for entry in &[block_entry("bob"), block_entry("carol"), test_entry()] {
+            apply(&mut blocked, entry);
+        }
+        apply(&mut blocked, &unblock_entry("bob"));
+        assert_eq!(vec!["carol"], blocked.iter().collect::<Vec<_>>());
+        assert!(is_private(&block_entry("bob")));
+        assert!(is_private(&unblock_entry("bob")));
+        assert!(!is_private(&test_entry()));
+    }
+}
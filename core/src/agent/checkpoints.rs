@@ -0,0 +1,235 @@
+//! checkpoints attest the head of an agent's chain: while the network config sets
+//! checkpoint_interval_secs, an instance publishes the head, its index and the time to the
+//! neighborhood of its agent every interval the head moved, see dht::activity
+//! holders keep every checkpoint, so rewriting history later shows as the chain no longer holding
+//! the head of the freshest checkpoint at its index, see verify_agent_head()
+//! checkpoints aren't signed yet, so unlike the provenances of headers they are taken at the word
+//! of the agent publishing them
+
+use dht::{aspect::Aspect, DhtState};
+use hash_table::provenance::Provenance;
+use network::outbox::Outgoing;
+use nucleus::scheduler::unix_now;
+use state::State;
+use std::{
+    fmt, sync::{Arc, Mutex},
+};
+
+/// the head of the chain of an agent as it attested it at a time
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub agent: String,
+    /// address of the header at the head
+    pub head: String,
+    /// index of the head on the chain, 0 for the first header
+    pub index: u64,
+    /// seconds since the unix epoch when the checkpoint was made
+    pub time: u64,
+    /// the agent vouching for the checkpoint
+    pub provenance: Provenance,
+}
+
+impl Checkpoint {
+    pub fn new(agent: &str, head: &str, index: u64, time: u64) -> Checkpoint {
+        Checkpoint {
+            agent: agent.to_string(),
+            head: head.to_string(),
+            index,
+            time,
+            // @TODO implement signatures
+            // https://github.com/holochain/holochain-rust/issues/71
+            provenance: Provenance::new(agent, ""),
+        }
+    }
+
+    /// true if the agent vouches for the checkpoint
+    pub fn is_signed(&self) -> bool {
+        self.provenance.source() == self.agent
+    }
+}
+
+/// what comparing the freshest checkpoint of an agent to its chain as held found
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HeadVerification {
+    /// no checkpoint of the agent is held
+    Unchecked,
+    /// the chain holds the head of the checkpoint at its index
+    Verified(Checkpoint),
+    /// the chain held doesn't reach the index of the checkpoint, e.g. its headers haven't
+    /// arrived yet or the agent claims a shorter chain
+    Behind(Checkpoint),
+    /// the chain holds other headers than the head of the checkpoint at its index, the history
+    /// was rewritten since
+    Rewritten(Checkpoint),
+}
+
+/// the checkpoints held of the agent, the freshest last
+pub fn checkpoints(dht: &DhtState, agent: &str) -> Vec<Checkpoint> {
+    let mut checkpoints: Vec<Checkpoint> = dht
+        .aspects(agent)
+        .into_iter()
+        .filter_map(|aspect| match aspect {
+            Aspect::Checkpoint(checkpoint) => Some(checkpoint),
+            _ => None,
+        })
+        .collect();
+    checkpoints.sort_by_key(|checkpoint| (checkpoint.time, checkpoint.index));
+    checkpoints
+}
+
+/// compare the freshest checkpoint held of the agent to the chain its activity holds
+pub fn verify_agent_head(dht: &DhtState, agent: &str) -> HeadVerification {
+    let checkpoint = match checkpoints(dht, agent).pop() {
+        Some(checkpoint) => checkpoint,
+        None => return HeadVerification::Unchecked,
+    };
+    let index = checkpoint.index;
+    let activity = dht.get_agent_activity(agent, &(index..index + 1));
+    if activity.headers.iter().any(|header| header.hash == checkpoint.head) {
+        HeadVerification::Verified(checkpoint)
+    } else if activity.headers.is_empty() {
+        HeadVerification::Behind(checkpoint)
+    } else {
+        HeadVerification::Rewritten(checkpoint)
+    }
+}
+
+/// the checkpoints an instance published
+/// checkpoints are a cheap handle, clones share the same last checkpoint
+#[derive(Clone, Default)]
+pub struct Checkpoints {
+    last: Arc<Mutex<Option<Checkpoint>>>,
+}
+
+impl PartialEq for Checkpoints {
+    fn eq(&self, other: &Checkpoints) -> bool {
+        Arc::ptr_eq(&self.last, &other.last)
+    }
+}
+
+impl fmt::Debug for Checkpoints {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Checkpoints")
+            .field("last", &*self.last.lock().unwrap())
+            .finish()
+    }
+}
+
+impl Checkpoints {
+    /// the checkpoint published last, if any
+    pub fn last(&self) -> Option<Checkpoint> {
+        self.last.lock().unwrap().clone()
+    }
+
+    /// true if a checkpoint of head is due at now
+    fn due(&self, now: u64, interval_secs: u64, head: &str) -> bool {
+        match *self.last.lock().unwrap() {
+            Some(ref last) => last.head != head && now.saturating_sub(last.time) >= interval_secs,
+            None => true,
+        }
+    }
+}
+
+/// queue a checkpoint of the head of the chain of the instance with the state for its
+/// neighborhood if checkpoints are on and one is due, returns the checkpoint queued
+pub fn publish(state: &State) -> Option<Checkpoint> {
+    let nucleus = state.nucleus();
+    let interval_secs = nucleus.messenger().config().checkpoint_interval_secs?;
+    let me = nucleus.messenger().address()?;
+    let agent = state.agent();
+    let head = agent.head()?;
+    let now = unix_now();
+    if agent.is_staging() || !nucleus.checkpoints().due(now, interval_secs, &head) {
+        return None;
+    }
+    let checkpoint = Checkpoint::new(&me, &head, agent.chain_length() - 1, now);
+    let aspects = vec![Aspect::Checkpoint(checkpoint.clone())];
+    // a checkpoint that can't be persisted still goes out while the instance runs
+    let _ = nucleus.outbox().queue(Outgoing::Publish(me, aspects));
+    *nucleus.checkpoints().last.lock().unwrap() = Some(checkpoint.clone());
+    Some(checkpoint)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        activity::tests::test_activity_headers, tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::{
+        header::Header, provenance::tests::{test_agent_address, test_signing_keys},
+    };
+
+    fn hold(dht: DhtState, aspect: Aspect) -> DhtState {
+        test_reduce(dht, Action::HoldAspect(test_agent_address("alice"), aspect))
+    }
+
+    /// hold the headers as alice's activity, signed by her
+    fn hold_activity(dht: DhtState, headers: &[Header]) -> DhtState {
+        headers.iter().fold(dht, |dht, header| {
+            hold(dht, Aspect::Activity(header.sign(&test_signing_keys("alice"))))
+        })
+    }
+
+    #[test]
+    /// the freshest checkpoint is compared to the chain held
+    fn verify() {
+        let alice = test_agent_address("alice");
+        let headers = test_activity_headers(3);
+        let dht = test_dht_state();
+        assert_eq!(HeadVerification::Unchecked, verify_agent_head(&dht, &alice));
+
+        let old = Checkpoint::new(&alice, &headers[0].hash(), 0, 100);
+        let fresh = Checkpoint::new(&alice, &headers[2].hash(), 2, 200);
+        let dht = hold(dht, Aspect::Checkpoint(fresh.clone()));
+        let dht = hold(dht, Aspect::Checkpoint(old.clone()));
+        assert_eq!(vec![old, fresh.clone()], checkpoints(&dht, &alice));
+        let dht = hold_activity(dht, &headers[..2]);
+        assert_eq!(
+            HeadVerification::Behind(fresh.clone()),
+            verify_agent_head(&dht, &alice)
+        );
+        let verified = hold_activity(dht.clone(), &headers[2..]);
+        assert_eq!(
+            HeadVerification::Verified(fresh.clone()),
+            verify_agent_head(&verified, &alice)
+        );
+
+        // another header at the index of the head
+        let other = Header::link(
+            &::hash_table::entry::Entry::new("post", "rewritten"),
+            Some(headers[1].hash()),
+            None,
+        );
+        let rewritten = hold_activity(dht, &[other]);
+        assert_eq!(
+            HeadVerification::Rewritten(fresh),
+            verify_agent_head(&rewritten, &alice)
+        );
+    }
+
+    #[test]
+    /// checkpoints are held at the agent vouching for them
+    fn held_at_agent() {
+        let checkpoint = Checkpoint::new("alice", "head", 0, 100);
+        assert!(Aspect::Checkpoint(checkpoint.clone()).belongs_at("alice"));
+        assert!(!Aspect::Checkpoint(checkpoint.clone()).belongs_at("bob"));
+        let vouched_by_bob = Checkpoint {
+            provenance: Provenance::new("bob", ""),
+            ..checkpoint
+        };
+        assert!(!Aspect::Checkpoint(vouched_by_bob).belongs_at("alice"));
+    }
+
+    #[test]
+    /// checkpoints are due every interval the head moved
+    fn due() {
+        let checkpoints = Checkpoints::default();
+        assert!(checkpoints.due(100, 60, "a"));
+        *checkpoints.last.lock().unwrap() = Some(Checkpoint::new("alice", "a", 0, 100));
+        assert!(!checkpoints.due(150, 60, "b"));
+        assert!(!checkpoints.due(200, 60, "a"));
+        assert!(checkpoints.due(200, 60, "b"));
+    }
+}
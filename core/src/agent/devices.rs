@@ -0,0 +1,408 @@
+//! an agent's identity is a root key kept offline, e.g. in a keystore recovered from its phrase
+//! only when needed, see Keystore::from_mnemonic(), its devices sign with keys of their own,
+//! derived from the root keystore for each device, see Keystore::device_keystore()
+//! the root key signs a binding for the signing key of each device, which the device commits to
+//! the chain, and a revocation for the key of a device that was lost or compromised, so the key
+//! stops being trusted while the agent keeps its identity and its other devices
+//! bindings and revocations are checked as they are committed: the root key has to have signed
+//! them, the same root key as the first binding on the chain, a revoked key can't be bound again
+//! and only bound keys can be revoked
+//! they are published to the neighborhood of the root key too, which holds the activity of the
+//! agent only signed by the root key or a device key bound to it and not revoked, see
+//! dht::aspect and verify_header()
+
+use agent::keystore::Keystore;
+use error::HolochainError;
+use hash_table::{entry::Entry, header::Header};
+use network::sealing::SigningPublicKey;
+use rust_base58::{FromBase58, ToBase58};
+use serde_json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// path the root signing key is derived along in the root keystore
+pub const ROOT_KEY_PATH: &str = "root";
+/// path the signing key of a device is derived along in the keystore of the device
+pub const DEVICE_SIGNING_KEY_PATH: &str = "signing";
+/// prefix of the paths the seeds of device keystores are derived along, see device_path()
+pub const DEVICE_PATH_PREFIX: &str = "device/";
+/// entry type of the system entry binding the signing key of a device to the root key
+pub const DEVICE_BINDING_ENTRY_TYPE: &str = "%device_binding";
+/// entry type of the system entry revoking the signing key of a device
+pub const DEVICE_REVOCATION_ENTRY_TYPE: &str = "%device_revocation";
+
+/// the path the seed of the named device's keystore is derived along from the root keystore
+pub fn device_path(name: &str) -> String {
+    format!("{}{}", DEVICE_PATH_PREFIX, name)
+}
+
+fn verify(root: &str, message: &str, signature: &str) -> bool {
+    match (SigningPublicKey::from_base58(root), signature.from_base58()) {
+        (Ok(root), Ok(signature)) => root.verify(message.as_bytes(), &signature),
+        _ => false,
+    }
+}
+
+/// the root key's word that the signing key is the named device's
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceBinding {
+    /// base58 root signing key
+    pub root: String,
+    /// base58 signing key of the device
+    pub device: String,
+    pub name: String,
+    /// base58 signature of the root key, see message()
+    pub signature: String,
+}
+
+impl DeviceBinding {
+    /// the binding of the device key signed by the root key of the root keystore, an error while
+    /// it is locked
+    pub fn new(
+        root_keystore: &Keystore,
+        name: &str,
+        device: &SigningPublicKey,
+    ) -> Result<DeviceBinding, HolochainError> {
+        let mut binding = DeviceBinding {
+            root: root_keystore.signing_key(ROOT_KEY_PATH)?.to_base58(),
+            device: device.to_base58(),
+            name: name.to_string(),
+            signature: String::new(),
+        };
+        binding.signature = root_keystore
+            .sign(ROOT_KEY_PATH, binding.message().as_bytes())?
+            .to_base58();
+        Ok(binding)
+    }
+
+    /// what the root key signs
+    fn message(&self) -> String {
+        format!("{}bind:{}:{}", DEVICE_PATH_PREFIX, self.device, self.name)
+    }
+
+    /// true if the root key signed the binding
+    pub fn verify(&self) -> bool {
+        verify(&self.root, &self.message(), &self.signature)
+    }
+
+    pub fn to_entry(&self) -> Entry {
+        let content = serde_json::to_string(self).expect("DeviceBinding should serialize");
+        Entry::new(DEVICE_BINDING_ENTRY_TYPE, &content)
+    }
+
+    /// the binding held by a binding entry, None for any other entry
+    pub fn from_entry(entry: &Entry) -> Option<DeviceBinding> {
+        if entry.entry_type() != DEVICE_BINDING_ENTRY_TYPE {
+            return None;
+        }
+        serde_json::from_str(entry.content()).ok()
+    }
+}
+
+/// the root key's word that the signing key of a device is no longer to be trusted
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DeviceRevocation {
+    /// base58 root signing key
+    pub root: String,
+    /// base58 signing key of the device
+    pub device: String,
+    /// base58 signature of the root key, see message()
+    pub signature: String,
+}
+
+impl DeviceRevocation {
+    /// the revocation of the device key signed by the root key of the root keystore, an error
+    /// while it is locked
+    pub fn new(
+        root_keystore: &Keystore,
+        device: &SigningPublicKey,
+    ) -> Result<DeviceRevocation, HolochainError> {
+        let mut revocation = DeviceRevocation {
+            root: root_keystore.signing_key(ROOT_KEY_PATH)?.to_base58(),
+            device: device.to_base58(),
+            signature: String::new(),
+        };
+        revocation.signature = root_keystore
+            .sign(ROOT_KEY_PATH, revocation.message().as_bytes())?
+            .to_base58();
+        Ok(revocation)
+    }
+
+    /// what the root key signs
+    fn message(&self) -> String {
+        format!("{}revoke:{}", DEVICE_PATH_PREFIX, self.device)
+    }
+
+    /// true if the root key signed the revocation
+    pub fn verify(&self) -> bool {
+        verify(&self.root, &self.message(), &self.signature)
+    }
+
+    pub fn to_entry(&self) -> Entry {
+        let content = serde_json::to_string(self).expect("DeviceRevocation should serialize");
+        Entry::new(DEVICE_REVOCATION_ENTRY_TYPE, &content)
+    }
+
+    /// the revocation held by a revocation entry, None for any other entry
+    pub fn from_entry(entry: &Entry) -> Option<DeviceRevocation> {
+        if entry.entry_type() != DEVICE_REVOCATION_ENTRY_TYPE {
+            return None;
+        }
+        serde_json::from_str(entry.content()).ok()
+    }
+}
+
+/// the root key that signed the binding or revocation entry, None for other entries and ones it
+/// didn't sign
+pub fn root_of(entry: &Entry) -> Option<String> {
+    match (DeviceBinding::from_entry(entry), DeviceRevocation::from_entry(entry)) {
+        (Some(binding), _) if binding.verify() => Some(binding.root),
+        (_, Some(revocation)) if revocation.verify() => Some(revocation.root),
+        _ => None,
+    }
+}
+
+/// the device keys bound and revoked by the entries committed
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Devices {
+    /// the root key of the first binding, None until a device is bound
+    root: Option<String>,
+    /// names of the device keys bound and not revoked, by key
+    bound: BTreeMap<String, String>,
+    revoked: BTreeSet<String>,
+}
+
+impl Devices {
+    /// check an entry about to be committed can be applied, other entries always can
+    pub fn check(&self, entry: &Entry) -> Result<(), String> {
+        let (root, device, verified) = match entry.entry_type() {
+            DEVICE_BINDING_ENTRY_TYPE => match DeviceBinding::from_entry(entry) {
+                Some(binding) => {
+                    let verified = binding.verify();
+                    (binding.root, binding.device, verified)
+                }
+                None => return Err("the device binding can't be read".to_string()),
+            },
+            DEVICE_REVOCATION_ENTRY_TYPE => match DeviceRevocation::from_entry(entry) {
+                Some(revocation) => {
+                    let verified = revocation.verify();
+                    (revocation.root, revocation.device, verified)
+                }
+                None => return Err("the device revocation can't be read".to_string()),
+            },
+            _ => return Ok(()),
+        };
+        if !verified {
+            return Err(format!("the root key didn't sign for the device key {}", device));
+        }
+        if let Some(ref bound) = self.root {
+            if *bound != root {
+                return Err(format!("{} isn't the root key of the chain", root));
+            }
+        }
+        if self.revoked.contains(&device) {
+            return Err(format!("the device key {} was revoked", device));
+        }
+        if entry.entry_type() == DEVICE_REVOCATION_ENTRY_TYPE && !self.bound.contains_key(&device) {
+            return Err(format!("the device key {} isn't bound", device));
+        }
+        Ok(())
+    }
+
+    /// update the devices with a committed entry, other entries change nothing
+    /// revoked keys stay revoked whatever order the entries come in, e.g. as the DHT holds them
+    pub fn apply(&mut self, entry: &Entry) {
+        if let Some(binding) = DeviceBinding::from_entry(entry) {
+            self.root.get_or_insert(binding.root);
+            if !self.revoked.contains(&binding.device) {
+                self.bound.insert(binding.device, binding.name);
+            }
+        } else if let Some(revocation) = DeviceRevocation::from_entry(entry) {
+            self.bound.remove(&revocation.device);
+            self.revoked.insert(revocation.device);
+        }
+    }
+
+    /// base58 root key of the agent, None until a device is bound
+    pub fn root(&self) -> Option<&str> {
+        self.root.as_deref()
+    }
+
+    /// the name the device key was bound with, None unless it is bound and not revoked
+    pub fn name(&self, device: &str) -> Option<&str> {
+        self.bound.get(device).map(String::as_str)
+    }
+
+    /// true if the device key is bound and not revoked
+    pub fn is_trusted(&self, device: &str) -> bool {
+        self.bound.contains_key(device)
+    }
+
+    /// true if the device key was revoked
+    pub fn is_revoked(&self, device: &str) -> bool {
+        self.revoked.contains(device)
+    }
+
+    /// true if signatures of the key are the agent's: the agent's own key, or a device key bound
+    /// to it as the root key and not revoked
+    pub fn signs_for(&self, agent: &str, key: &str) -> bool {
+        if key == agent {
+            !self.is_revoked(key)
+        } else {
+            self.root() == Some(agent) && self.is_trusted(key)
+        }
+    }
+
+    /// true if the agent vouches for the header: every provenance verifies, none is of a revoked
+    /// device key, and the first, the author's, is signed by a key of the agent
+    pub fn verify_header(&self, agent: &str, header: &Header) -> bool {
+        header
+            .provenances()
+            .first()
+            .map(|author| self.signs_for(agent, author.source()))
+            .unwrap_or(false)
+            && header
+                .provenances()
+                .iter()
+                .all(|provenance| !self.is_revoked(provenance.source()))
+            && header.verify_provenances()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::{
+        entry::tests::test_entry, header::tests::test_header,
+        provenance::tests::{test_agent_address, test_signing_keys},
+    };
+
+    fn device_key(root: &Keystore, name: &str) -> SigningPublicKey {
+        root.device_keystore(name)
+            .unwrap()
+            .signing_key(DEVICE_SIGNING_KEY_PATH)
+            .unwrap()
+    }
+
+    #[test]
+    fn entries() {
+        let root = Keystore::default();
+        let phone = device_key(&root, "phone");
+        let binding = DeviceBinding::new(&root, "phone", &phone).unwrap();
+        assert!(binding.verify());
+        assert_eq!(Some(binding.clone()), DeviceBinding::from_entry(&binding.to_entry()));
+        assert_eq!(None, DeviceBinding::from_entry(&test_entry()));
+
+        let revocation = DeviceRevocation::new(&root, &phone).unwrap();
+        assert!(revocation.verify());
+        assert_eq!(
+            Some(revocation.clone()),
+            DeviceRevocation::from_entry(&revocation.to_entry())
+        );
+
+        let mut renamed = binding.clone();
+        renamed.name = "laptop".to_string();
+        assert!(!renamed.verify());
+    }
+
+    #[test]
+    /// a revoked key stops being trusted, the other devices of the root key are kept
+    fn bind_and_revoke() {
+        let root = Keystore::default();
+        let phone = device_key(&root, "phone");
+        let laptop = device_key(&root, "laptop");
+        let mut devices = Devices::default();
+        for (name, key) in &[("phone", phone), ("laptop", laptop)] {
+            let entry = DeviceBinding::new(&root, name, key).unwrap().to_entry();
+            assert_eq!(Ok(()), devices.check(&entry));
+            devices.apply(&entry);
+        }
+        assert!(devices.is_trusted(&phone.to_base58()));
+        assert_eq!(Some("laptop"), devices.name(&laptop.to_base58()));
+        let root_key = root.signing_key(ROOT_KEY_PATH).unwrap().to_base58();
+        assert_eq!(Some(root_key.as_str()), devices.root());
+
+        let revocation = DeviceRevocation::new(&root, &phone).unwrap().to_entry();
+        assert_eq!(Ok(()), devices.check(&revocation));
+        devices.apply(&revocation);
+        assert!(!devices.is_trusted(&phone.to_base58()));
+        assert!(devices.is_revoked(&phone.to_base58()));
+        assert!(devices.is_trusted(&laptop.to_base58()));
+
+        // the revoked key can't come back, and is revoked once
+        let rebinding = DeviceBinding::new(&root, "phone", &phone).unwrap().to_entry();
+        assert!(devices.check(&rebinding).is_err());
+        assert!(devices.check(&revocation).is_err());
+        assert_eq!(Ok(()), devices.check(&test_entry()));
+    }
+
+    #[test]
+    /// only the root key of the chain binds and revokes, and only what it bound
+    fn checked() {
+        let root = Keystore::default();
+        let phone = device_key(&root, "phone");
+        let mut devices = Devices::default();
+        let unbound = DeviceRevocation::new(&root, &phone).unwrap().to_entry();
+        assert!(devices.check(&unbound).is_err());
+        devices.apply(&DeviceBinding::new(&root, "phone", &phone).unwrap().to_entry());
+
+        let other = Keystore::default();
+        let laptop = device_key(&other, "laptop");
+        let foreign = DeviceBinding::new(&other, "laptop", &laptop).unwrap();
+        assert!(devices.check(&foreign.to_entry()).is_err());
+        let foreign_revocation = DeviceRevocation::new(&other, &phone).unwrap().to_entry();
+        assert!(devices.check(&foreign_revocation).is_err());
+
+        let mut forged = DeviceBinding::new(&root, "laptop", &laptop).unwrap();
+        forged.device = device_key(&root, "tablet").to_base58();
+        assert!(devices.check(&forged.to_entry()).is_err());
+        let garbled = Entry::new(DEVICE_BINDING_ENTRY_TYPE, "not a binding");
+        assert!(devices.check(&garbled).is_err());
+    }
+
+    #[test]
+    /// the root key vouches for headers signed by the keys it bound until it revokes them
+    fn verify_header() {
+        let root = Keystore::default();
+        let agent = root.signing_key(ROOT_KEY_PATH).unwrap().to_base58();
+        let phone = root.device_keystore("phone").unwrap();
+        let phone_key = phone.signing_key(DEVICE_SIGNING_KEY_PATH).unwrap();
+        let header = test_header();
+        let by_phone = phone.sign_header(DEVICE_SIGNING_KEY_PATH, &header).unwrap();
+        let by_root = root.sign_header(ROOT_KEY_PATH, &header).unwrap();
+
+        let mut devices = Devices::default();
+        assert!(!devices.verify_header(&agent, &by_phone));
+        assert!(devices.verify_header(&agent, &by_root));
+        // unsigned headers are nobody's
+        assert!(!devices.verify_header(&agent, &header));
+
+        let binding = DeviceBinding::new(&root, "phone", &phone_key).unwrap().to_entry();
+        assert_eq!(Some(agent.clone()), root_of(&binding));
+        devices.apply(&binding);
+        assert!(devices.verify_header(&agent, &by_phone));
+        // bound to the root key, not to other agents
+        let alice = test_agent_address("alice");
+        assert!(!devices.verify_header(&alice, &by_phone));
+        let by_alice = header.sign(&test_signing_keys("alice"));
+        assert!(Devices::default().verify_header(&alice, &by_alice));
+
+        let revocation = DeviceRevocation::new(&root, &phone_key).unwrap().to_entry();
+        devices.apply(&revocation);
+        assert!(!devices.verify_header(&agent, &by_phone));
+        // nor countersigned by the revoked key
+        let countersigned = by_root.with_provenance(by_phone.provenances()[0].clone());
+        assert!(!devices.verify_header(&agent, &countersigned));
+        assert!(devices.verify_header(&agent, &by_root));
+
+        // revoked whatever order they come in
+        let mut held = Devices::default();
+        held.apply(&revocation);
+        held.apply(&binding);
+        assert!(!held.verify_header(&agent, &by_phone));
+
+        let mut forged = DeviceBinding::from_entry(&binding).unwrap();
+        forged.name = "laptop".to_string();
+        assert_eq!(None, root_of(&forged.to_entry()));
+        assert_eq!(None, root_of(&test_entry()));
+    }
+}
@@ -0,0 +1,276 @@
+//! group secrets let a group of agents publish entries anyone can hold but only the group can
+//! read: an agent creates a secret keyed to the group, which goes to every other member in a
+//! direct message, sealed to the member like every direct message, see network::sealing
+//! members encrypt entry payloads with the secret before committing them and decrypt what they
+//! get, with XSalsa20-Poly1305, the ciphertext goes as base58
+//! the secrets an agent knows are kept in a store by id, which can be persisted to a JSON file
+//! rewritten every time a secret is added, the file holds the keys in the clear
+//! agents have no keys to sign with yet, see agent::keys, so like other direct messages a secret
+//! is taken at the word of the agent sending it, and the first secret known under an id is kept
+
+use agent::secbuf::{self, SecBuf};
+use error::HolochainError;
+use network::{
+    direct_message::DirectMessage, outbox::{Outbox, Outgoing, Priority}, sealing,
+};
+use rand::{self, Rng};
+use rust_base58::{FromBase58, ToBase58};
+use serde_json;
+use std::{
+    collections::BTreeMap, fmt, fs, path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// a secret key shared by a group of agents
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct GroupSecret {
+    /// random, unique among the secrets of the agents knowing it
+    pub id: String,
+    /// address of the agent that created the secret and shared it
+    pub creator: String,
+    /// addresses of the agents the secret is for, the creator among them, sorted
+    pub members: Vec<String>,
+    #[serde(deserialize_with = "secbuf::deserialize_key")]
+    pub(crate) key: SecBuf,
+}
+
+/// the key doesn't show
+impl fmt::Debug for GroupSecret {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("GroupSecret")
+            .field("id", &self.id)
+            .field("creator", &self.creator)
+            .field("members", &self.members)
+            .finish()
+    }
+}
+
+impl GroupSecret {
+    /// a fresh secret the creator shares with the members
+    pub fn new(creator: &str, members: &[String]) -> GroupSecret {
+        let mut members = members.to_vec();
+        members.push(creator.to_string());
+        members.sort();
+        members.dedup();
+        let mut rng = rand::thread_rng();
+        GroupSecret {
+            id: rng.gen::<[u8; 16]>().to_base58(),
+            creator: creator.to_string(),
+            members,
+            key: SecBuf::random(32),
+        }
+    }
+
+    pub fn is_member(&self, agent: &str) -> bool {
+        self.members.binary_search(&agent.to_string()).is_ok()
+    }
+
+    /// the payload encrypted with the secret, in base58
+    pub fn encrypt(&self, payload: &str) -> String {
+        sealing::encrypt(payload.as_bytes(), self.key.as_key()).to_base58()
+    }
+
+    /// the payload encrypted with the secret, an error if it was encrypted with another one
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String, HolochainError> {
+        let encrypted = ciphertext.from_base58().map_err(|_| {
+            HolochainError::ErrorGeneric("the ciphertext isn't base58".to_string())
+        })?;
+        let payload = sealing::decrypt(&encrypted, self.key.as_key())?;
+        String::from_utf8(payload).map_err(|e| HolochainError::ErrorGeneric(e.to_string()))
+    }
+
+    /// queue the secret in the outbox for every member but the creator, so members offline get
+    /// it once they can be reached, nothing is queued unless there is room for every member
+    pub fn share(&self, outbox: &Outbox) -> Result<(), HolochainError> {
+        let members: Vec<&String> =
+            self.members.iter().filter(|member| **member != self.creator).collect();
+        outbox
+            .room(Priority::Normal, members.len())
+            .map_err(|exceeded| HolochainError::ErrorGeneric(exceeded.to_string()))?;
+        for member in members {
+            let message = DirectMessage::GroupSecret(self.clone());
+            outbox.queue(Outgoing::Message(member.clone(), message))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Secrets {
+    by_id: BTreeMap<String, GroupSecret>,
+    /// file the secrets are persisted to, if any
+    path: Option<PathBuf>,
+}
+
+impl Secrets {
+    /// keep the secret unless one is known under its id already, true if it was kept
+    fn add(&mut self, secret: GroupSecret) -> bool {
+        if self.by_id.contains_key(&secret.id) {
+            return false;
+        }
+        self.by_id.insert(secret.id.clone(), secret);
+        true
+    }
+
+    fn save(&self) -> Result<(), HolochainError> {
+        match self.path {
+            Some(ref path) => {
+                let json = serde_json::to_string(&self.by_id)
+                    .map_err(|e| HolochainError::new(&e.to_string()))?;
+                fs::write(path, json).map_err(|e| HolochainError::new(&e.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// the group secrets an agent created or was given
+/// the store is a cheap handle, clones share the same secrets
+#[derive(Clone, Default)]
+pub struct GroupSecrets {
+    secrets: Arc<Mutex<Secrets>>,
+}
+
+impl PartialEq for GroupSecrets {
+    fn eq(&self, other: &GroupSecrets) -> bool {
+        Arc::ptr_eq(&self.secrets, &other.secrets)
+    }
+}
+
+impl fmt::Debug for GroupSecrets {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let secrets = self.secrets.lock().unwrap();
+        f.debug_struct("GroupSecrets")
+            .field("secrets", &secrets.by_id.len())
+            .field("path", &secrets.path)
+            .finish()
+    }
+}
+
+impl GroupSecrets {
+    /// persist the secrets to the file from now on, adding the ones kept in it
+    pub fn persist(&self, path: &Path) -> Result<(), HolochainError> {
+        let persisted: BTreeMap<String, GroupSecret> = if path.exists() {
+            let json = fs::read_to_string(path).map_err(|e| HolochainError::new(&e.to_string()))?;
+            serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))?
+        } else {
+            BTreeMap::new()
+        };
+        let mut secrets = self.secrets.lock().unwrap();
+        for secret in persisted.into_values() {
+            secrets.add(secret);
+        }
+        secrets.path = Some(path.to_path_buf());
+        secrets.save()
+    }
+
+    /// keep a secret unless one is known under its id already, it is kept even if it can't be
+    /// persisted, the error says why it wasn't
+    pub fn add(&self, secret: GroupSecret) -> Result<(), HolochainError> {
+        let mut secrets = self.secrets.lock().unwrap();
+        if secrets.add(secret) {
+            secrets.save()
+        } else {
+            Ok(())
+        }
+    }
+
+    /// the secret with the id, if known
+    pub fn get(&self, id: &str) -> Option<GroupSecret> {
+        self.secrets.lock().unwrap().by_id.get(id).cloned()
+    }
+
+    /// the ids of the secrets known, sorted
+    pub fn ids(&self) -> Vec<String> {
+        self.secrets.lock().unwrap().by_id.keys().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::{env, process};
+
+    /// secret alice shares with bob and carol
+    pub fn test_group_secret() -> GroupSecret {
+        GroupSecret::new("alice", &["carol".to_string(), "bob".to_string()])
+    }
+
+    #[test]
+    /// the creator is a member, members are sorted
+    fn new() {
+        let secret = test_group_secret();
+        assert_eq!(vec!["alice", "bob", "carol"], secret.members);
+        assert!(secret.is_member("alice"));
+        assert!(!secret.is_member("dave"));
+        assert_ne!(secret.id, test_group_secret().id);
+        assert!(!format!("{:?}", secret).contains("key"));
+    }
+
+    #[test]
+    /// only the secret encrypted with decrypts
+    fn encrypt_decrypt() {
+        let secret = test_group_secret();
+        let ciphertext = secret.encrypt("the plan");
+        assert!(!ciphertext.contains("plan"));
+        assert_eq!(Ok("the plan".to_string()), secret.decrypt(&ciphertext));
+        assert!(test_group_secret().decrypt(&ciphertext).is_err());
+        assert!(secret.decrypt("not base58!").is_err());
+    }
+
+    #[test]
+    /// every member but the creator gets the secret through the outbox
+    fn share() {
+        let secret = test_group_secret();
+        let outbox = Outbox::new();
+        secret.share(&outbox).unwrap();
+        let message = |member: &str| {
+            Outgoing::Message(member.to_string(), DirectMessage::GroupSecret(secret.clone()))
+        };
+        assert_eq!(vec![message("bob"), message("carol")], outbox.pending());
+
+        // a secret only some members would get isn't shared at all
+        let full = Outbox::new();
+        full.set_max_depth(Some(1));
+        assert!(secret.share(&full).is_err());
+        assert!(full.pending().is_empty());
+    }
+
+    #[test]
+    /// the first secret known under an id is kept
+    fn add() {
+        let secrets = GroupSecrets::default();
+        let secret = test_group_secret();
+        secrets.add(secret.clone()).unwrap();
+        let impostor = GroupSecret {
+            id: secret.id.clone(),
+            ..GroupSecret::new("dave", &[])
+        };
+        secrets.add(impostor).unwrap();
+        assert_eq!(Some(secret.clone()), secrets.get(&secret.id));
+        assert_eq!(vec![secret.id], secrets.ids());
+        assert_eq!(None, secrets.get("unknown"));
+    }
+
+    #[test]
+    /// the secrets survive in their file
+    fn persist() {
+        let path = env::temp_dir().join(format!(
+            "holochain_group_secrets_test_{}.json",
+            process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let secrets = GroupSecrets::default();
+        secrets.persist(&path).unwrap();
+        let secret = test_group_secret();
+        secrets.add(secret.clone()).unwrap();
+
+        let restarted = GroupSecrets::default();
+        restarted.persist(&path).unwrap();
+        assert_eq!(Some(secret), restarted.get(&restarted.ids()[0]));
+
+        fs::write(&path, "not json").unwrap();
+        assert!(GroupSecrets::default().persist(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,593 @@
+//! the keystore keeps the secret keys of an agent out of the zomes: X25519 key pairs are derived
+//! from a random seed along paths zomes name, e.g. "chat/bob", and zomes only get their public
+//! keys and the keys they agree with other agents' keys, to build their own secure channels
+//! the same seed and path always give the same key pair, so a keystore persisted to a JSON file
+//! gives zomes their keys back after a restart, the file holds the seed in the clear unless it is
+//! encrypted with a passphrase of the agent's own, see persist_encrypted()
+//! keystores kept in a file are locked while their instance isn't running: the seed is zeroized
+//! when it stops and read back from the file when it starts, see unlock() and lock(), the
+//! passphrase of an encrypted file is only used while reading or writing it and never kept, so
+//! those only unlock with it again, see unlock_with()
+//! seed files are only readable by their owner and replaced whole, never left half written
+//! the seed, the passphrase, the plaintext of seed files and the keys stretched and derived from
+//! them are kept in SecBufs, see agent::secbuf
+//! the seed can also come from a BIP39 phrase the agent backed its keys up with, see
+//! from_mnemonic() and agent::mnemonic
+//! Ed25519 keys to sign with are derived the same way, see signing_key(), the agent's root key
+//! signs for the keys of its devices, each of which gets a keystore of its own, see
+//! agent::devices, the headers an instance publishes are signed by its device key, see
+//! sign_header(), the keys of the keystore don't seal direct messages yet, see network::sealing
+
+use agent::{devices, mnemonic, secbuf::SecBuf};
+use error::HolochainError;
+use hash_table::header::Header;
+use network::sealing::{
+    self, KeyPair, PublicKey, SigningKeyPair, SigningPublicKey, StretchParams,
+};
+use rand::{OsRng, Rng};
+use rust_base58::{FromBase58, ToBase58};
+use serde_json;
+use std::{
+    fmt, fs, io::{self, ErrorKind, Write}, path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// the function passphrases of keystore files are stretched with, see sealing::stretch()
+const KEYSTORE_KDF: &str = "argon2id";
+
+/// what a keystore file encrypted with a passphrase holds: a header telling how the passphrase
+/// is stretched into the key, then the seed
+#[derive(Serialize, Deserialize)]
+struct EncryptedSeed {
+    /// the function the passphrase is stretched with, KEYSTORE_KDF
+    kdf: String,
+    /// the cost it is stretched with, whatever the defaults were when the file was written
+    params: StretchParams,
+    /// base58 salt the passphrase is stretched with
+    salt: String,
+    /// base58 seed encrypted with the stretched passphrase
+    seed: String,
+}
+
+struct Seed {
+    /// None while the keystore is locked
+    seed: Option<SecBuf>,
+    /// file the seed is persisted to, if any
+    path: Option<PathBuf>,
+    /// whether the seed is encrypted with a passphrase in the file
+    encrypted: bool,
+}
+
+impl Seed {
+    /// zeroize the seed, unless there is no file to read it back from
+    fn lock(&mut self) {
+        if self.path.is_some() {
+            self.seed = None;
+        }
+    }
+
+    fn unlocked(&self) -> Result<&SecBuf, HolochainError> {
+        self.seed
+            .as_ref()
+            .ok_or_else(|| HolochainError::ErrorGeneric("the keystore is locked".to_string()))
+    }
+
+    fn path(&self) -> Result<&Path, HolochainError> {
+        self.path
+            .as_deref()
+            .ok_or_else(|| HolochainError::new("the keystore has no file to unlock"))
+    }
+}
+
+fn to_error<E: ToString>(error: E) -> HolochainError {
+    HolochainError::new(&error.to_string())
+}
+
+fn no_seed(path: &Path) -> HolochainError {
+    HolochainError::ErrorGeneric(format!("{} holds no keystore seed", path.display()))
+}
+
+/// the plaintext of a seed file, the base58 of the seed as a JSON string
+fn seed_to_json(seed: &SecBuf) -> SecBuf {
+    let encoded = seed.encode_base58();
+    let mut json = SecBuf::new(encoded.len() + 2);
+    let end = json.len() - 1;
+    json[0] = b'"';
+    json[1..end].copy_from_slice(&encoded);
+    json[end] = b'"';
+    json
+}
+
+/// the seed of the plaintext of a seed file, None unless it is a JSON string of base58
+fn seed_from_json(json: &[u8]) -> Option<SecBuf> {
+    let start = json.iter().position(|c| !c.is_ascii_whitespace())?;
+    let end = json.iter().rposition(|c| !c.is_ascii_whitespace())?;
+    if end <= start || json[start] != b'"' || json[end] != b'"' {
+        return None;
+    }
+    SecBuf::decode_base58(&json[start + 1..end])
+}
+
+/// the seed kept in the file, encrypted with the passphrase if there is one
+fn read_seed(path: &Path, passphrase: Option<&SecBuf>) -> Result<SecBuf, HolochainError> {
+    let json = SecBuf::from_vec(fs::read(path).map_err(to_error)?);
+    let seed = match passphrase {
+        Some(passphrase) => {
+            let encrypted: EncryptedSeed = serde_json::from_slice(&json).map_err(to_error)?;
+            if encrypted.kdf != KEYSTORE_KDF {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "{} is stretched with {}, only {} is supported",
+                    path.display(),
+                    encrypted.kdf,
+                    KEYSTORE_KDF
+                )));
+            }
+            let salt = encrypted.salt.from_base58().map_err(|_| no_seed(path))?;
+            let sealed = encrypted.seed.from_base58().map_err(|_| no_seed(path))?;
+            let key = sealing::stretch(passphrase, &salt, &encrypted.params)?;
+            let decrypted = sealing::decrypt(&sealed, key.as_key()).map_err(|_| {
+                HolochainError::ErrorGeneric(format!(
+                    "the passphrase doesn't unlock the keystore in {}",
+                    path.display()
+                ))
+            })?;
+            SecBuf::from_vec(decrypted)
+        }
+        None => seed_from_json(&json).ok_or_else(|| no_seed(path))?,
+    };
+    if seed.len() == 32 {
+        Ok(seed)
+    } else {
+        Err(no_seed(path))
+    }
+}
+
+/// keep the seed in the file, encrypted with the passphrase stretched at the given cost if there
+/// is one
+fn write_seed(
+    path: &Path,
+    seed: &SecBuf,
+    passphrase: Option<(&SecBuf, &StretchParams)>,
+) -> Result<(), HolochainError> {
+    let json = match passphrase {
+        Some((passphrase, params)) => {
+            let salt = OsRng::new().map_err(to_error)?.gen::<[u8; 16]>();
+            let key = sealing::stretch(passphrase, &salt, params)?;
+            let encrypted = EncryptedSeed {
+                kdf: KEYSTORE_KDF.to_string(),
+                params: *params,
+                salt: salt.to_base58(),
+                seed: sealing::encrypt(seed, key.as_key()).to_base58(),
+            };
+            SecBuf::from_vec(serde_json::to_vec(&encrypted).map_err(to_error)?)
+        }
+        None => seed_to_json(seed),
+    };
+    write_private(path, &json).map_err(to_error)
+}
+
+/// replace the file with the contents, readable by the owner only, through a file next to it so
+/// it is never left half written
+fn write_private(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    let temp = PathBuf::from(temp);
+    // a file left by a write that failed could have been opened by others meanwhile
+    if let Err(e) = fs::remove_file(&temp) {
+        if e.kind() != ErrorKind::NotFound {
+            return Err(e);
+        }
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let written = options.open(&temp).and_then(|mut file| {
+        file.write_all(contents)?;
+        file.sync_all()
+    });
+    match written.and_then(|_| fs::rename(&temp, path)) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = fs::remove_file(&temp);
+            Err(e)
+        }
+    }
+}
+
+/// the keys of the agent, derived from a seed of its own
+/// the keystore is a cheap handle, clones share the same seed
+#[derive(Clone)]
+pub struct Keystore {
+    seed: Arc<Mutex<Seed>>,
+}
+
+/// a keystore with a fresh random seed
+impl Default for Keystore {
+    fn default() -> Keystore {
+        Keystore {
+            seed: Arc::new(Mutex::new(Seed {
+                seed: Some(SecBuf::random(32)),
+                path: None,
+                encrypted: false,
+            })),
+        }
+    }
+}
+
+impl PartialEq for Keystore {
+    fn eq(&self, other: &Keystore) -> bool {
+        Arc::ptr_eq(&self.seed, &other.seed)
+    }
+}
+
+/// the seed doesn't show
+impl fmt::Debug for Keystore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let seed = self.seed.lock().unwrap();
+        f.debug_struct("Keystore")
+            .field("path", &seed.path)
+            .field("locked", &seed.seed.is_none())
+            .finish()
+    }
+}
+
+impl Keystore {
+    /// the keystore with the seed derived along the path from the BIP39 phrase and passphrase,
+    /// the same keys for the same phrase, e.g. along mnemonic::dna_path() for the keys of a DNA
+    /// an error if the phrase isn't valid
+    pub fn from_mnemonic(
+        phrase: &str,
+        passphrase: &str,
+        path: &str,
+    ) -> Result<Keystore, HolochainError> {
+        Ok(Keystore {
+            seed: Arc::new(Mutex::new(Seed {
+                seed: Some(mnemonic::derive_seed(phrase, passphrase, path)?),
+                path: None,
+                encrypted: false,
+            })),
+        })
+    }
+
+    /// take the seed kept in the file, or keep the seed in it if there is no file yet, so keys
+    /// derived before and after a restart are the same
+    pub fn persist(&self, path: &Path) -> Result<(), HolochainError> {
+        let mut seed = self.seed.lock().unwrap();
+        if path.exists() {
+            let read = read_seed(path, None)?;
+            seed.seed = Some(read);
+        } else {
+            write_seed(path, seed.unlocked()?, None)?;
+        }
+        seed.path = Some(path.to_path_buf());
+        seed.encrypted = false;
+        Ok(())
+    }
+
+    /// keep the seed in the file encrypted with the passphrase, or take the one kept in it,
+    /// failing if the passphrase doesn't decrypt it
+    /// the keystore stays unlocked until locked, the passphrase isn't kept, see unlock_with()
+    /// the passphrase is stretched with Argon2id at the default cost, see sealing::StretchParams,
+    /// which goes in the header of the file with the salt
+    pub fn persist_encrypted(&self, path: &Path, passphrase: &str) -> Result<(), HolochainError> {
+        let mut seed = self.seed.lock().unwrap();
+        let passphrase = SecBuf::from_slice(passphrase.as_bytes());
+        if path.exists() {
+            let read = read_seed(path, Some(&passphrase))?;
+            seed.seed = Some(read);
+        } else {
+            let params = StretchParams::default();
+            write_seed(path, seed.unlocked()?, Some((&passphrase, &params)))?;
+        }
+        seed.path = Some(path.to_path_buf());
+        seed.encrypted = true;
+        Ok(())
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.seed.lock().unwrap().encrypted
+    }
+
+    /// read the seed from the file, e.g. as the instance starts, nothing to do unless locked
+    /// keystores encrypted in their file only unlock with the passphrase, see unlock_with()
+    pub fn unlock(&self) -> Result<(), HolochainError> {
+        let mut seed = self.seed.lock().unwrap();
+        if seed.seed.is_some() {
+            return Ok(());
+        }
+        if seed.encrypted {
+            return Err(HolochainError::new(
+                "the keystore is encrypted, it only unlocks with the agent's passphrase",
+            ));
+        }
+        let read = read_seed(seed.path()?, None)?;
+        seed.seed = Some(read);
+        Ok(())
+    }
+
+    /// read the seed from the file decrypting it with the passphrase, which isn't kept, nothing
+    /// to do unless locked
+    /// an error for keystores not encrypted in their file, a passphrase given for those is a
+    /// mistake, see unlock()
+    pub fn unlock_with(&self, passphrase: &str) -> Result<(), HolochainError> {
+        let mut seed = self.seed.lock().unwrap();
+        if !seed.encrypted {
+            return Err(HolochainError::new(
+                "the keystore isn't encrypted, it unlocks without a passphrase",
+            ));
+        }
+        if seed.seed.is_some() {
+            return Ok(());
+        }
+        let passphrase = SecBuf::from_slice(passphrase.as_bytes());
+        let read = read_seed(seed.path()?, Some(&passphrase))?;
+        seed.seed = Some(read);
+        Ok(())
+    }
+
+    /// zeroize the seed until the keystore is unlocked again, e.g. as the instance stops
+    /// keystores not kept in a file stay unlocked, their seed couldn't be read back
+    pub fn lock(&self) {
+        self.seed.lock().unwrap().lock();
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.seed.lock().unwrap().seed.is_none()
+    }
+
+    fn key_pair(&self, path: &str) -> Result<KeyPair, HolochainError> {
+        Ok(KeyPair::derive(
+            self.seed.lock().unwrap().unlocked()?.as_key(),
+            path,
+        ))
+    }
+
+    /// the public key of the key pair derived along the path, an error while locked
+    pub fn derive_key(&self, path: &str) -> Result<PublicKey, HolochainError> {
+        Ok(self.key_pair(path)?.public())
+    }
+
+    /// the key the key pair derived along the path agrees with the other key, its holder gets
+    /// the same one, see KeyPair::agree(), an error while locked
+    pub fn agree(&self, path: &str, other: &PublicKey) -> Result<SecBuf, HolochainError> {
+        self.key_pair(path)?.agree(other)
+    }
+
+    fn signing_key_pair(&self, path: &str) -> Result<SigningKeyPair, HolochainError> {
+        Ok(SigningKeyPair::derive(
+            self.seed.lock().unwrap().unlocked()?.as_key(),
+            path,
+        ))
+    }
+
+    /// the public key of the signing key pair derived along the path, an error while locked
+    pub fn signing_key(&self, path: &str) -> Result<SigningPublicKey, HolochainError> {
+        Ok(self.signing_key_pair(path)?.public())
+    }
+
+    /// the signature of the message by the signing key pair derived along the path, an error
+    /// while locked
+    pub fn sign(&self, path: &str, message: &[u8]) -> Result<Vec<u8>, HolochainError> {
+        Ok(self.signing_key_pair(path)?.sign(message))
+    }
+
+    /// a copy of the header with the provenance of the signing key pair derived along the path,
+    /// e.g. devices::DEVICE_SIGNING_KEY_PATH, see Header::sign(), an error while locked
+    pub fn sign_header(&self, path: &str, header: &Header) -> Result<Header, HolochainError> {
+        Ok(header.sign(&self.signing_key_pair(path)?))
+    }
+
+    /// a keystore for the named device, with a seed derived from this one along
+    /// devices::device_path(), so the device never holds the seed of the root key and the root
+    /// keystore can bind, revoke and recreate its keys, an error while locked
+    pub fn device_keystore(&self, name: &str) -> Result<Keystore, HolochainError> {
+        let seed = sealing::derive_secret(
+            self.seed.lock().unwrap().unlocked()?,
+            &devices::device_path(name),
+        );
+        Ok(Keystore {
+            seed: Arc::new(Mutex::new(Seed {
+                seed: Some(seed),
+                path: None,
+                encrypted: false,
+            })),
+        })
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use network::sealing::tests::test_stretch_params;
+    use serde_json::Value;
+    use std::{env, process};
+
+    #[test]
+    /// keys are the same along the same path only
+    fn derive_key() {
+        let keystore = Keystore::default();
+        let key = keystore.derive_key("chat/bob").unwrap();
+        assert_eq!(Ok(key), keystore.clone().derive_key("chat/bob"));
+        assert_ne!(Ok(key), keystore.derive_key("chat/carol"));
+        assert_ne!(Ok(key), Keystore::default().derive_key("chat/bob"));
+        assert!(!format!("{:?}", keystore).contains("seed"));
+    }
+
+    #[test]
+    /// alice and bob agree on a key from each other's public key
+    fn agree() {
+        let alice = Keystore::default();
+        let bob = Keystore::default();
+        let bob_key = bob.derive_key("chat/alice").unwrap();
+        let key = alice.agree("chat/bob", &bob_key).unwrap();
+        assert_eq!(
+            Ok(key.clone()),
+            bob.agree("chat/alice", &alice.derive_key("chat/bob").unwrap())
+        );
+        assert_ne!(Ok(key), alice.agree("chat/carol", &bob_key));
+    }
+
+    #[test]
+    /// the keys survive a restart with the seed in its file
+    fn persist() {
+        let path = env::temp_dir().join(format!("holochain_keystore_test_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let keystore = Keystore::default();
+        keystore.persist(&path).unwrap();
+        let key = keystore.derive_key("chat/bob");
+
+        let restarted = Keystore::default();
+        restarted.persist(&path).unwrap();
+        assert_eq!(key, restarted.derive_key("chat/bob"));
+
+        // locked while stopped, the seed is read back as it starts again
+        restarted.lock();
+        assert!(restarted.is_locked());
+        assert!(restarted.derive_key("chat/bob").is_err());
+        restarted.unlock().unwrap();
+        assert_eq!(key, restarted.derive_key("chat/bob"));
+
+        // the file holds the base58 seed as a JSON string, as it always did
+        let persisted: String = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(32, persisted.from_base58().unwrap().len());
+        fs::write(&path, format!(" {:?}\n", persisted)).unwrap();
+        let reread = Keystore::default();
+        reread.persist(&path).unwrap();
+        assert_eq!(key, reread.derive_key("chat/bob"));
+
+        // a passphrase for a keystore that has none is a mistake
+        restarted.lock();
+        assert!(restarted.unlock_with("bob's passphrase").is_err());
+        assert!(restarted.is_locked());
+
+        fs::write(&path, "\"not a seed\"").unwrap();
+        assert!(Keystore::default().persist(&path).is_err());
+        fs::remove_file(&path).unwrap();
+
+        // keystores in no file can't be locked
+        let unpersisted = Keystore::default();
+        unpersisted.lock();
+        assert!(!unpersisted.is_locked());
+        assert_eq!(Ok(()), unpersisted.unlock());
+    }
+
+    #[test]
+    /// keys are recovered from the phrase, for one DNA at a time
+    fn from_mnemonic() {
+        let phrase = mnemonic::generate(24).unwrap();
+        let phrase = phrase.as_str().unwrap();
+        let chat = mnemonic::dna_path("QmChat");
+        let keystore = Keystore::from_mnemonic(phrase, "", &chat).unwrap();
+        let key = keystore.derive_key("chat/bob").unwrap();
+        let recovered = Keystore::from_mnemonic(phrase, "", &chat).unwrap();
+        assert_eq!(Ok(key), recovered.derive_key("chat/bob"));
+
+        let blog = Keystore::from_mnemonic(phrase, "", &mnemonic::dna_path("QmBlog")).unwrap();
+        assert_ne!(Ok(key), blog.derive_key("chat/bob"));
+        assert!(Keystore::from_mnemonic("not a phrase", "", &chat).is_err());
+    }
+
+    #[test]
+    /// encrypted keystores unlock with their own passphrase only
+    fn encrypted() {
+        let path = env::temp_dir().join(format!("holochain_keystore_enc_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let keystore = Keystore::default();
+        let key = keystore.derive_key("chat/bob").unwrap();
+        keystore.persist_encrypted(&path, "bob's passphrase").unwrap();
+        assert!(keystore.is_encrypted());
+        assert!(fs::read_to_string(&path).unwrap().contains("salt"));
+
+        // once locked, only the passphrase brings the seed back
+        keystore.lock();
+        assert!(keystore.derive_key("chat/bob").is_err());
+        assert!(keystore.unlock().is_err());
+        assert!(keystore.unlock_with("carol's passphrase").is_err());
+        assert!(keystore.is_locked());
+        keystore.unlock_with("bob's passphrase").unwrap();
+        assert_eq!(Ok(key), keystore.derive_key("chat/bob"));
+
+        let restarted = Keystore::default();
+        restarted.persist_encrypted(&path, "bob's passphrase").unwrap();
+        assert_eq!(Ok(key), restarted.derive_key("chat/bob"));
+
+        let guessed = Keystore::default();
+        assert!(guessed.persist_encrypted(&path, "carol's passphrase").is_err());
+        assert!(!guessed.is_encrypted());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    /// only the owner can read the seed, no temporary file is left
+    fn private_file() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = env::temp_dir().join(format!("holochain_keystore_mode_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        Keystore::default().persist(&path).unwrap();
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(0o600, mode & 0o777);
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        assert!(!Path::new(&temp).exists());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// the cost and salt of stretching the passphrase are read from the header of the file
+    fn encrypted_header() {
+        let path = env::temp_dir().join(format!("holochain_keystore_hdr_{}.json", process::id()));
+        let seed = SecBuf::random(32);
+        let passphrase = SecBuf::from_slice(b"bob's passphrase");
+        write_seed(&path, &seed, Some((&passphrase, &test_stretch_params()))).unwrap();
+        let mut header: Value = serde_json::from_str(&fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(json!("argon2id"), header["kdf"]);
+        assert_eq!(json!(test_stretch_params()), header["params"]);
+        assert_eq!(Ok(seed.clone()), read_seed(&path, Some(&passphrase)));
+
+        // another cost stretches the passphrase into another key
+        header["params"]["iterations"] = json!(2);
+        fs::write(&path, header.to_string()).unwrap();
+        assert!(read_seed(&path, Some(&passphrase)).is_err());
+
+        header["kdf"] = json!("blake2b");
+        fs::write(&path, header.to_string()).unwrap();
+        match read_seed(&path, Some(&passphrase)) {
+            Err(HolochainError::ErrorGeneric(message)) => assert!(message.contains("blake2b")),
+            result => panic!("expected an unsupported kdf, got {:?}", result.is_ok()),
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// signatures verify with the signing key of the same path only
+    fn sign() {
+        let keystore = Keystore::default();
+        let key = keystore.signing_key("chat").unwrap();
+        let signature = keystore.sign("chat", b"hello").unwrap();
+        assert!(key.verify(b"hello", &signature));
+        assert!(!key.verify(b"hullo", &signature));
+        assert!(!keystore.signing_key("blog").unwrap().verify(b"hello", &signature));
+    }
+
+    #[test]
+    /// devices get keys of their own, the same for the same name
+    fn device_keystore() {
+        let root = Keystore::default();
+        let phone = root.device_keystore("phone").unwrap();
+        let key = phone.signing_key(devices::DEVICE_SIGNING_KEY_PATH).unwrap();
+        assert_eq!(
+            Ok(key),
+            root.device_keystore("phone")
+                .unwrap()
+                .signing_key(devices::DEVICE_SIGNING_KEY_PATH)
+        );
+        assert_ne!(Ok(key), root.signing_key(devices::DEVICE_SIGNING_KEY_PATH));
+        let laptop = root.device_keystore("laptop").unwrap();
+        assert_ne!(Ok(key), laptop.signing_key(devices::DEVICE_SIGNING_KEY_PATH));
+    }
+}
@@ -0,0 +1,134 @@
+//! membranes gate who may join the network of a DNA: a DNA with a zome defining validate_agent in
+//! its lifecycle capability only lets in the agents whose membrane proof every such zome accepts
+//! the proof is supplied when the instance is created, genesis fails unless it is accepted, and
+//! the AgentId entry carrying it is committed first so it is published along with the chain
+//! the nodes the AgentId entry is published to run validate_agent on it when they first see it
+//! and hold it as invalid if they refuse it
+
+use dht::{self, HoldingValidation};
+use hash_table::entry::Entry;
+use holochain_dna::{
+    zome::capabilities::{ReservedCapabilityNames, ReservedFunctionNames}, Dna,
+};
+use instance::Observer;
+use nucleus::{call_lifecycle_function, traits};
+use platform;
+use serde_json;
+use state;
+use std::sync::mpsc::Sender;
+
+/// entry type of the system entry an agent of a gated DNA commits first, holding its AgentId
+pub const AGENT_ID_ENTRY_TYPE: &str = "%agent_id";
+
+/// the agent an instance runs for and the proof it joins the network with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentId {
+    pub address: String,
+    #[serde(default)]
+    pub membrane_proof: Option<String>,
+}
+
+impl AgentId {
+    pub fn new(address: &str, membrane_proof: Option<&str>) -> AgentId {
+        AgentId {
+            address: address.to_string(),
+            membrane_proof: membrane_proof.map(|proof| proof.to_string()),
+        }
+    }
+
+    /// the AgentId entry for it
+    pub fn to_entry(&self) -> Entry {
+        let content = serde_json::to_string(self).expect("AgentId should serialize");
+        Entry::new(AGENT_ID_ENTRY_TYPE, &content)
+    }
+
+    /// the AgentId held by an AgentId entry, None for any other entry
+    pub fn from_entry(entry: &Entry) -> Option<AgentId> {
+        if entry.entry_type() != AGENT_ID_ENTRY_TYPE {
+            return None;
+        }
+        serde_json::from_str(entry.content()).ok()
+    }
+}
+
+/// true if a zome of the DNA defines validate_agent, so agents need their proof accepted
+pub fn is_gated(dna: &Dna) -> bool {
+    let export = format!("{}_dispatch", ReservedFunctionNames::ValidateAgent.as_str());
+    dna.zomes
+        .iter()
+        .flat_map(|zome| zome.capabilities.iter())
+        .filter(|capability| capability.name == ReservedCapabilityNames::LifeCycle.as_str())
+        .filter_map(|capability| traits::wasm_exports(&capability.code.code).ok())
+        .any(|exports| exports.contains(&export))
+}
+
+/// ask validate_agent of every zome of the DNA whether the agent may join
+/// zomes without validate_agent don't object, the first refusal is the error
+pub fn validate_agent(
+    dna: &Dna,
+    agent_id: &AgentId,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) -> Result<(), String> {
+    let parameters = serde_json::to_string(agent_id).expect("AgentId should serialize");
+    for zome in &dna.zomes {
+        call_lifecycle_function(
+            &zome.name,
+            &ReservedFunctionNames::ValidateAgent,
+            &parameters,
+            action_channel,
+            observer_channel,
+        ).map_err(|refusal| format!("agent {} refused: {}", agent_id.address, refusal))?;
+    }
+    Ok(())
+}
+
+/// check an AgentId entry published to the node holding it at address, in a thread of its own as
+/// validate_agent is a zome call, then record whether it was accepted as the validation held
+pub fn verify_published(
+    dna: Dna,
+    agent_id: AgentId,
+    address: String,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) {
+    let action_channel = action_channel.clone();
+    let observer_channel = observer_channel.clone();
+    platform::spawn("validate_agent", move || {
+        let validation = match validate_agent(&dna, &agent_id, &action_channel, &observer_channel)
+        {
+            Ok(()) => HoldingValidation::Valid,
+            Err(refusal) => HoldingValidation::Invalid(refusal),
+        };
+        ::instance::dispatch_action(
+            &action_channel,
+            state::Action::Dht(dht::Action::SetValidation(address, validation)),
+        );
+    });
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::entry::tests::test_entry;
+
+    #[test]
+    /// AgentIds round trip through their entry, other entries hold none
+    fn entry() {
+        let agent_id = AgentId::new("alice", Some("invite code"));
+        let entry = agent_id.to_entry();
+        assert_eq!(AGENT_ID_ENTRY_TYPE, entry.entry_type());
+        assert_eq!(Some(agent_id), AgentId::from_entry(&entry));
+        assert_eq!(None, AgentId::from_entry(&test_entry()));
+        assert_eq!(
+            Some(AgentId::new("bob", None)),
+            AgentId::from_entry(&Entry::new(AGENT_ID_ENTRY_TYPE, r#"{"address":"bob"}"#))
+        );
+    }
+
+    #[test]
+    /// DNAs without validate_agent don't gate anyone
+    fn ungated() {
+        assert!(!is_gated(&Dna::new()));
+    }
+}
@@ -0,0 +1,269 @@
+//! mnemonics back the keys of an agent up as a phrase of words instead of a file: BIP39 phrases
+//! of 12 to 24 words from the English wordlist, whose last word carries a checksum, stretched
+//! into a 64 byte seed with PBKDF2-HMAC-SHA512 and an optional passphrase, see to_seed()
+//! the BIP39 seed isn't the seed of a keystore itself, every DNA gets a keystore seed of its own
+//! derived along its standard path, see dna_path(), so one phrase recovers the keys of every DNA
+//! the agent joined while the keys of one DNA give nothing away about another's
+//! phrases and passphrases are taken as they are, without the NFKD normalization of BIP39, which
+//! leaves ASCII as is
+
+use agent::secbuf::SecBuf;
+use error::HolochainError;
+use network::sealing;
+use sha2::{Digest, Sha256, Sha512};
+
+/// the BIP39 English wordlist, one word per line
+const ENGLISH: &str = include_str!("bip39_english.txt");
+
+/// prefix of the standard paths keystore seeds of DNAs are derived along, see dna_path()
+pub const DNA_PATH_PREFIX: &str = "holochain/dna/";
+
+/// PBKDF2 rounds of BIP39
+const SEED_ROUNDS: u32 = 2048;
+
+/// bytes of SHA-512, the PBKDF2 block and the BIP39 seed
+const SEED_BYTES: usize = 64;
+
+/// the numbers of words a phrase can have
+const PHRASE_WORDS: [usize; 5] = [12, 15, 18, 21, 24];
+
+/// the standard path the keystore seed for the DNA is derived along, see Keystore::from_mnemonic()
+pub fn dna_path(dna_hash: &str) -> String {
+    format!("{}{}", DNA_PATH_PREFIX, dna_hash)
+}
+
+fn words() -> Vec<&'static str> {
+    ENGLISH.lines().collect()
+}
+
+fn invalid(reason: &str) -> HolochainError {
+    HolochainError::ErrorGeneric(format!("invalid mnemonic: {}", reason))
+}
+
+/// a fresh random phrase of 12, 15, 18, 21 or 24 words
+pub fn generate(words: usize) -> Result<SecBuf, HolochainError> {
+    if !PHRASE_WORDS.contains(&words) {
+        return Err(invalid("a phrase has 12, 15, 18, 21 or 24 words"));
+    }
+    from_entropy(&SecBuf::random(words / 3 * 4))
+}
+
+/// the phrase of 16 to 32 bytes of entropy, a multiple of 4, and their checksum
+pub fn from_entropy(entropy: &[u8]) -> Result<SecBuf, HolochainError> {
+    if !PHRASE_WORDS.iter().any(|words| words / 3 * 4 == entropy.len()) {
+        return Err(invalid("the entropy has to be 16 to 32 bytes, a multiple of 4"));
+    }
+    let mut bits = SecBuf::new(entropy.len() * 8 + entropy.len() / 4);
+    let checksum = Sha256::digest(entropy);
+    for i in 0..bits.len() {
+        let byte = if i < entropy.len() * 8 {
+            entropy[i / 8]
+        } else {
+            checksum[(i - entropy.len() * 8) / 8]
+        };
+        bits[i] = (byte >> (7 - i % 8)) & 1;
+    }
+    let words = words();
+    let mut phrase = Vec::new();
+    for chunk in bits.chunks(11) {
+        let index = chunk
+            .iter()
+            .fold(0, |index, bit| (index << 1) | *bit as usize);
+        if !phrase.is_empty() {
+            phrase.push(b' ');
+        }
+        phrase.extend_from_slice(words[index].as_bytes());
+    }
+    Ok(SecBuf::from_vec(phrase))
+}
+
+/// the entropy of the phrase, an error if a word isn't in the wordlist, the number of words is
+/// off or the checksum doesn't match
+pub fn to_entropy(phrase: &str) -> Result<SecBuf, HolochainError> {
+    let words = words();
+    let phrase: Vec<&str> = phrase.split_whitespace().collect();
+    if !PHRASE_WORDS.contains(&phrase.len()) {
+        return Err(invalid("a phrase has 12, 15, 18, 21 or 24 words"));
+    }
+    let mut bits = SecBuf::new(phrase.len() * 11);
+    for (i, word) in phrase.iter().enumerate() {
+        let index = words
+            .binary_search(word)
+            .map_err(|_| invalid(&format!("'{}' isn't a word of the wordlist", word)))?;
+        for bit in 0..11 {
+            bits[i * 11 + bit] = ((index >> (10 - bit)) & 1) as u8;
+        }
+    }
+    let checksum_bits = bits.len() / 33;
+    let mut entropy = SecBuf::new((bits.len() - checksum_bits) / 8);
+    for (i, bit) in bits[..entropy.len() * 8].iter().enumerate() {
+        entropy[i / 8] |= bit << (7 - i % 8);
+    }
+    let checksum = Sha256::digest(&entropy);
+    let matches = bits[entropy.len() * 8..]
+        .iter()
+        .enumerate()
+        .all(|(i, bit)| (checksum[i / 8] >> (7 - i % 8)) & 1 == *bit);
+    if matches {
+        Ok(entropy)
+    } else {
+        Err(invalid("the checksum doesn't match"))
+    }
+}
+
+/// the 64 byte BIP39 seed of the phrase and passphrase, an error if the phrase isn't valid
+pub fn to_seed(phrase: &str, passphrase: &str) -> Result<SecBuf, HolochainError> {
+    to_entropy(phrase)?;
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    let normalized = SecBuf::from_vec(words.join(" ").into_bytes());
+    let mut salt = SecBuf::new(8 + passphrase.len());
+    salt[..8].copy_from_slice(b"mnemonic");
+    salt[8..].copy_from_slice(passphrase.as_bytes());
+    Ok(pbkdf2(&normalized, &salt))
+}
+
+/// the keystore seed along the path, for the seed of the phrase and passphrase
+pub fn derive_seed(phrase: &str, passphrase: &str, path: &str) -> Result<SecBuf, HolochainError> {
+    Ok(sealing::derive_secret(&to_seed(phrase, passphrase)?, path))
+}
+
+fn hmac_sha512(key: &[u8], message: &[u8]) -> SecBuf {
+    let mut block = SecBuf::new(128);
+    if key.len() > block.len() {
+        block[..SEED_BYTES].copy_from_slice(&SecBuf::from_slice(&Sha512::digest(key)));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut pad = SecBuf::new(block.len());
+    for (padded, byte) in pad.iter_mut().zip(block.iter()) {
+        *padded = byte ^ 0x36;
+    }
+    let mut inner = Sha512::default();
+    inner.input(&pad);
+    inner.input(message);
+    let inner = SecBuf::from_slice(&inner.result());
+    for (padded, byte) in pad.iter_mut().zip(block.iter()) {
+        *padded = byte ^ 0x5c;
+    }
+    let mut outer = Sha512::default();
+    outer.input(&pad);
+    outer.input(&inner);
+    SecBuf::from_slice(&outer.result())
+}
+
+/// PBKDF2-HMAC-SHA512 for the one block of a BIP39 seed
+fn pbkdf2(password: &[u8], salt: &[u8]) -> SecBuf {
+    let mut input = SecBuf::new(salt.len() + 4);
+    input[..salt.len()].copy_from_slice(salt);
+    input[salt.len()..].copy_from_slice(&[0, 0, 0, 1]);
+    let mut round = hmac_sha512(password, &input);
+    let mut seed = round.clone();
+    for _ in 1..SEED_ROUNDS {
+        round = hmac_sha512(password, &round);
+        for (byte, xor) in seed.iter_mut().zip(round.iter()) {
+            *byte ^= xor;
+        }
+    }
+    seed
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len() / 2)
+            .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    /// the wordlist is the sorted 2048 words of BIP39, told apart by their first 4 letters
+    fn wordlist() {
+        let words = words();
+        assert_eq!(2048, words.len());
+        assert!(words.windows(2).all(|pair| pair[0] < pair[1]));
+        let mut prefixes: Vec<&str> = words.iter().map(|word| &word[..word.len().min(4)]).collect();
+        prefixes.dedup();
+        assert_eq!(2048, prefixes.len());
+    }
+
+    #[test]
+    /// entropy and phrases of the BIP39 test vectors
+    fn vectors() {
+        let vectors = [
+            (
+                "00000000000000000000000000000000",
+                "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                 abandon about",
+            ),
+            (
+                "80808080808080808080808080808080",
+                "letter advice cage absurd amount doctor acoustic avoid letter advice cage above",
+            ),
+            (
+                "9e885d952ad362caeb4efe34a8e91bd2",
+                "ozone drill grab fiber curtain grace pudding thank cruise elder eight picnic",
+            ),
+            (
+                "f30f8c1da665478f49b001d94c5fc452",
+                "vessel ladder alter error federal sibling chat ability sun glass valve picture",
+            ),
+            (
+                "f585c11aec520db57dd353c69554b21a89b20fb0650966fa0a9d6f74fd989d8f",
+                "void come effort suffer camp survey warrior heavy shoot primary clutch crush open \
+                 amazing screen patrol group space point ten exist slush involve unfold",
+            ),
+        ];
+        for (entropy, phrase) in vectors.iter() {
+            let entropy = unhex(entropy);
+            assert_eq!(Some(*phrase), from_entropy(&entropy).unwrap().as_str());
+            assert_eq!(SecBuf::from_vec(entropy), to_entropy(phrase).unwrap());
+        }
+    }
+
+    #[test]
+    /// the seed of the first BIP39 test vector with the passphrase TREZOR
+    fn seed_vector() {
+        let seed = to_seed(
+            "abandon abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+             abandon about",
+            "TREZOR",
+        ).unwrap();
+        assert_eq!(
+            unhex(
+                "c55257c360c07c72029aebc1b53c05ed0362ada38ead3e3e9efa3708e53495531f09a6987599d1826\
+                 4c1e1c92f2cf141630c7a3c4ab7c81b2f001698e7463b04"
+            ),
+            seed.to_vec()
+        );
+    }
+
+    #[test]
+    fn invalid_phrases() {
+        let phrase = "abandon abandon abandon abandon abandon abandon abandon abandon abandon \
+                      abandon abandon";
+        assert!(to_entropy(phrase).is_err());
+        assert!(to_entropy(&format!("{} abandon", phrase)).is_err());
+        assert!(to_entropy(&format!("{} holochain", phrase)).is_err());
+        assert!(to_seed(&format!("{} abandon", phrase), "").is_err());
+        assert!(generate(13).is_err());
+        assert!(from_entropy(&[0; 15]).is_err());
+    }
+
+    #[test]
+    /// every DNA has a seed of its own, the same for the same phrase and passphrase
+    fn generate_and_derive() {
+        let phrase = generate(24).unwrap();
+        let phrase = phrase.as_str().unwrap();
+        assert_eq!(24, phrase.split_whitespace().count());
+        assert!(to_entropy(phrase).is_ok());
+        assert_eq!(12, generate(12).unwrap().as_str().unwrap().split(' ').count());
+
+        let seed = derive_seed(phrase, "", &dna_path("QmChat")).unwrap();
+        assert_eq!(32, seed.len());
+        assert_eq!(seed, derive_seed(phrase, "", &dna_path("QmChat")).unwrap());
+        assert_ne!(seed, derive_seed(phrase, "", &dna_path("QmBlog")).unwrap());
+        assert_ne!(seed, derive_seed(phrase, "secret", &dna_path("QmChat")).unwrap());
+    }
+}
@@ -1,20 +1,73 @@
+pub mod blocks;
+pub mod checkpoints;
+pub mod devices;
+pub mod groups;
 pub mod keys;
+pub mod keystore;
+pub mod membrane;
+pub mod mnemonic;
+pub mod secbuf;
+pub mod transaction;
 
-use agent::keys::Keys;
-use chain::Chain;
-use hash_table::{entry::Entry, memory::MemTable, pair::Pair};
+use agent::{devices::Devices, keys::Keys, transaction::Transaction};
+use hash_table::{entry::Entry, header::Header, pair::Pair};
+use limits::{self, LimitExceeded, Resource};
+use nucleus::scheduler::unix_now;
 use state;
 use std::{
-    rc::Rc, sync::{mpsc::Sender, Arc},
+    collections::{BTreeMap, BTreeSet}, sync::{mpsc::Sender, Arc},
 };
+use validation::{
+    rates::{BucketUse, Buckets}, timestamps::{self, DEFAULT_MAX_CLOCK_SKEW_SECS},
+};
+
+/// entry type of the system marker committed once every zome's init callback has succeeded
+pub const INIT_COMPLETE_ENTRY_TYPE: &str = "%init_complete";
+
+/// why a commit expecting a head the chain has moved on from failed
+pub const HEAD_MOVED: &str = "the chain head moved since it was read";
 
-#[derive(Clone, Debug, PartialEq, Default)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AgentState {
+    #[serde(skip)]
     keys: Option<Keys>,
     // @TODO how should this work with chains/HTs?
     // @see https://github.com/holochain/holochain-rust/issues/137
     // @see https://github.com/holochain/holochain-rust/issues/135
     top_pair: Option<Pair>,
+    /// address of the latest header of each entry type on the chain, staged ones left out
+    type_tops: BTreeMap<String, String>,
+    /// the pairs pushed by the last commit, in push order, or why nothing was
+    last_commit: Result<Vec<Pair>, String>,
+    /// pairs committed since BeginStaging, held back from top_pair until CommitStaged
+    /// None unless staging
+    staged: Option<Vec<Pair>>,
+    /// true once the InitComplete marker is committed
+    init_complete: bool,
+    /// the pairs committed up to the InitComplete marker, see validation::packages
+    genesis: Vec<Pair>,
+    /// agents blocked by the entries committed, see blocks
+    blocked: BTreeSet<String>,
+    /// device keys bound to the root key and revoked by the entries committed, see devices
+    devices: Devices,
+    /// pairs on the chain, staged ones left out
+    chain_length: u64,
+    /// bytes of entry content committed, staged commits included
+    chain_bytes: u64,
+    /// commits that would take chain_bytes over this fail, see limits
+    max_chain_bytes: Option<u64>,
+    /// the rate buckets the entry types of the DNA declare, see validation::rates
+    rate_buckets: Buckets,
+    /// what the commits took from the rate buckets lately
+    bucket_use: BucketUse,
+    /// seconds a header can be timed before the one it follows, see validation::timestamps
+    max_clock_skew_secs: u64,
+}
+
+impl Default for AgentState {
+    fn default() -> Self {
+        AgentState::new()
+    }
 }
 
 impl AgentState {
@@ -23,6 +76,19 @@ impl AgentState {
         AgentState {
             keys: None,
             top_pair: None,
+            type_tops: BTreeMap::new(),
+            last_commit: Ok(Vec::new()),
+            staged: None,
+            init_complete: false,
+            genesis: Vec::new(),
+            blocked: BTreeSet::new(),
+            devices: Devices::default(),
+            chain_length: 0,
+            chain_bytes: 0,
+            max_chain_bytes: None,
+            rate_buckets: Buckets::new(),
+            bucket_use: BucketUse::default(),
+            max_clock_skew_secs: DEFAULT_MAX_CLOCK_SKEW_SECS,
         }
     }
 
@@ -36,29 +102,330 @@ impl AgentState {
     pub fn top_pair(&self) -> Option<Pair> {
         self.top_pair.clone()
     }
+
+    /// address of the header at the head of the chain, staged commits included, None while
+    /// nothing is committed
+    pub fn head(&self) -> Option<String> {
+        self.staged
+            .as_ref()
+            .and_then(|staged| staged.last())
+            .or(self.top_pair.as_ref())
+            .map(|pair| pair.header().hash())
+    }
+
+    /// address of the latest header of the entry type, staged commits included
+    fn type_top(&self, entry_type: &str) -> Option<String> {
+        let staged = self.staged.iter().flatten().rev();
+        match staged
+            .map(|pair| pair.header())
+            .find(|header| header.entry_type() == entry_type)
+        {
+            Some(header) => Some(header.hash()),
+            None => self.type_tops.get(entry_type).cloned(),
+        }
+    }
+
+    /// getter for a copy of self.last_commit
+    pub fn last_commit(&self) -> Result<Vec<Pair>, String> {
+        self.last_commit.clone()
+    }
+
+    /// seconds clocks are allowed to be apart, for the times of the headers committed and
+    /// published, see validation::timestamps
+    pub fn max_clock_skew_secs(&self) -> u64 {
+        self.max_clock_skew_secs
+    }
+
+    /// how many pairs are on the chain, staged ones left out
+    pub fn chain_length(&self) -> u64 {
+        self.chain_length
+    }
+
+    /// bytes of entry content committed so far
+    pub fn chain_bytes(&self) -> u64 {
+        self.chain_bytes
+    }
+
+    /// true between BeginStaging and CommitStaged or AbortStaged
+    pub fn is_staging(&self) -> bool {
+        self.staged.is_some()
+    }
+
+    /// true once init has run for every zome, so it won't run again
+    pub fn init_complete(&self) -> bool {
+        self.init_complete
+    }
+
+    /// the pairs committed during genesis, up to and including the InitComplete marker
+    pub fn genesis(&self) -> &[Pair] {
+        &self.genesis
+    }
+
+    /// the agents blocked, sorted
+    pub fn blocked(&self) -> &BTreeSet<String> {
+        &self.blocked
+    }
+
+    pub fn is_blocked(&self, address: &str) -> bool {
+        self.blocked.contains(address)
+    }
+
+    /// the device keys bound to the agent's root key and revoked
+    pub fn devices(&self) -> &Devices {
+        &self.devices
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Action {
     Commit(Entry),
+    /// commit the entry only if the head of the chain is still the one given, else fail with
+    /// HEAD_MOVED
+    CommitOnHead(Entry, Option<String>),
+    /// commit every staged entry or none of them
+    /// the transaction is expected to have been validated already
+    CommitTransaction(Transaction),
+    /// hold commits back from the top pair until CommitStaged or AbortStaged, e.g. during genesis
+    BeginStaging,
+    /// move the top pair onto everything committed since BeginStaging
+    CommitStaged,
+    /// forget everything committed since BeginStaging, as if it never was
+    AbortStaged,
+    /// limit the bytes of entry content committed, None for no limit
+    SetChainLimit(Option<u64>),
+    /// limit the commits of entry types by the rate buckets they declare
+    SetRateBuckets(Buckets),
+    /// allow clocks to be apart by the seconds, see validation::timestamps
+    SetClockSkew(u64),
+}
+
+/// bytes of entry content the entries take on the chain
+fn content_bytes<'a, I: IntoIterator<Item = &'a Entry>>(entries: I) -> u64 {
+    entries
+        .into_iter()
+        .map(|entry| entry.content().len() as u64)
+        .sum()
+}
+
+/// check committing the entries keeps the chain within its limit
+/// going over it fails the commit and reports the exceeded limit
+fn check_chain_limit<'a, I: IntoIterator<Item = &'a Entry>>(
+    state: &mut AgentState,
+    entries: I,
+    action_channel: &Sender<state::ActionWrapper>,
+) -> bool {
+    let requested = state.chain_bytes + content_bytes(entries);
+    match limits::check(Resource::ChainBytes, state.max_chain_bytes, requested) {
+        Ok(()) => true,
+        Err(exceeded) => {
+            state.last_commit = Err(exceeded.to_string());
+            report_limit_exceeded(exceeded, action_channel);
+            false
+        }
+    }
+}
+
+/// check the chain is still at the expected head
+/// a moved head fails the commit with HEAD_MOVED
+fn check_head(state: &mut AgentState, expected: &Option<String>) -> bool {
+    if state.head() == *expected {
+        true
+    } else {
+        state.last_commit = Err(HEAD_MOVED.to_string());
+        false
+    }
+}
+
+/// check the headers of the pairs follow the head of the chain and each other in time
+/// a header timed before the one it follows by more than the skew fails the commit
+fn check_times(state: &mut AgentState, pairs: &[Pair]) -> bool {
+    let mut previous = state
+        .staged
+        .as_ref()
+        .and_then(|staged| staged.last())
+        .or(state.top_pair.as_ref())
+        .map(|pair| pair.header().clone());
+    for pair in pairs {
+        if let Some(ref previous) = previous {
+            let skew_secs = state.max_clock_skew_secs;
+            if let Err(reason) = timestamps::check_order(pair.header(), previous, skew_secs) {
+                state.last_commit = Err(reason);
+                return false;
+            }
+        }
+        previous = Some(pair.header().clone());
+    }
+    true
+}
+
+/// check the device bindings and revocations among the pairs, in order, see devices
+/// one the devices, staged commits included, reject fails the commit
+fn check_devices(state: &mut AgentState, pairs: &[Pair]) -> bool {
+    let mut devices = state.devices.clone();
+    for pair in state.staged.iter().flatten() {
+        devices.apply(pair.entry());
+    }
+    for pair in pairs {
+        if let Err(reason) = devices.check(pair.entry()) {
+            state.last_commit = Err(reason);
+            return false;
+        }
+        devices.apply(pair.entry());
+    }
+    true
+}
+
+/// take the entries from the rate buckets of their types at now
+/// using a bucket up fails the commit and takes nothing
+fn take_buckets<'a, I: IntoIterator<Item = &'a Entry>>(
+    state: &mut AgentState,
+    entries: I,
+    now: u64,
+) -> bool {
+    let mut bucket_use = state.bucket_use.clone();
+    for entry in entries {
+        if let Err(reason) = bucket_use.take(entry.entry_type(), &state.rate_buckets, now) {
+            state.last_commit = Err(reason);
+            return false;
+        }
+    }
+    state.bucket_use = bucket_use;
+    true
+}
+
+fn report_limit_exceeded(exceeded: LimitExceeded, action_channel: &Sender<state::ActionWrapper>) {
+    ::instance::dispatch_action(
+        action_channel,
+        state::Action::Nucleus(::nucleus::Action::ReportLimitExceeded(exceeded)),
+    );
+}
+
+/// count the bytes of newly pushed pairs and record them as committed
+fn push_commit(state: &mut AgentState, pairs: Vec<Pair>) {
+    state.chain_bytes += content_bytes(pairs.iter().map(|pair| pair.entry()));
+    apply_commit(state, pairs);
+}
+
+/// record pairs as committed, or as staged while staging
+fn apply_commit(state: &mut AgentState, pairs: Vec<Pair>) {
+    state.last_commit = Ok(pairs.clone());
+    if let Some(ref mut staged) = state.staged {
+        staged.extend(pairs);
+        return;
+    }
+    if let Some(pair) = pairs.last() {
+        state.top_pair = Some(pair.clone());
+    }
+    state.chain_length += pairs.len() as u64;
+    if !state.init_complete {
+        state.genesis.extend(pairs.iter().cloned());
+    }
+    if pairs
+        .iter()
+        .any(|pair| pair.entry().entry_type() == INIT_COMPLETE_ENTRY_TYPE)
+    {
+        state.init_complete = true;
+    }
+    for pair in &pairs {
+        let entry_type = pair.header().entry_type().to_string();
+        state.type_tops.insert(entry_type, pair.key());
+        blocks::apply(&mut state.blocked, pair.entry());
+        state.devices.apply(pair.entry());
+    }
+}
+
+/// pairs of the entries, each header on top of the head of the chain, staged commits included,
+/// or the one before it, and of the latest header of its entry type
+fn link<'a, I: IntoIterator<Item = &'a Entry>>(state: &AgentState, entries: I) -> Vec<Pair> {
+    let mut pairs: Vec<Pair> = Vec::new();
+    for entry in entries {
+        let next = match pairs.last() {
+            Some(pair) => Some(pair.key()),
+            None => state.head(),
+        };
+        let type_next = match pairs
+            .iter()
+            .rev()
+            .find(|pair| pair.header().entry_type() == entry.entry_type())
+        {
+            Some(pair) => Some(pair.key()),
+            None => state.type_top(entry.entry_type()),
+        };
+        pairs.push(Pair::from_header(Header::link(entry, next, type_next), entry));
+    }
+    pairs
+}
+
+/// commit a single entry, unless it takes the chain over its limit or a rate bucket
+fn commit(state: &mut AgentState, entry: &Entry, action_channel: &Sender<state::ActionWrapper>) {
+    if !check_chain_limit(state, Some(entry), action_channel) {
+        return;
+    }
+    let pairs = link(state, Some(entry));
+    if check_times(state, &pairs)
+        && check_devices(state, &pairs)
+        && take_buckets(state, Some(entry), unix_now())
+    {
+        push_commit(state, pairs);
+    }
 }
 
 /// Reduce Agent's state according to provided Action
 pub fn reduce(
     old_state: Arc<AgentState>,
     action: &state::Action,
-    _action_channel: &Sender<state::ActionWrapper>,
+    action_channel: &Sender<state::ActionWrapper>,
 ) -> Arc<AgentState> {
     match *action {
         state::Action::Agent(ref agent_action) => {
             let mut new_state: AgentState = (*old_state).clone();
             match *agent_action {
-                Action::Commit(ref entry) => {
-                    // add entry to source chain
-                    // @TODO this does nothing! it isn't exactly clear what it should do either
-                    // @see https://github.com/holochain/holochain-rust/issues/148
-                    let mut chain = Chain::new(Rc::new(MemTable::new()));
-                    chain.push(&entry).unwrap();
+                Action::Commit(ref entry) => commit(&mut new_state, entry, action_channel),
+                Action::CommitOnHead(ref entry, ref head) => {
+                    if check_head(&mut new_state, head) {
+                        commit(&mut new_state, entry, action_channel);
+                    }
+                }
+                Action::CommitTransaction(ref transaction) => {
+                    if transaction
+                        .expected_head()
+                        .map(|head| check_head(&mut new_state, head))
+                        .unwrap_or(true)
+                        && check_chain_limit(&mut new_state, transaction.entries(), action_channel)
+                    {
+                        let pairs = link(&new_state, transaction.entries());
+                        if check_times(&mut new_state, &pairs)
+                            && check_devices(&mut new_state, &pairs)
+                            && take_buckets(&mut new_state, transaction.entries(), unix_now())
+                        {
+                            push_commit(&mut new_state, pairs)
+                        }
+                    }
+                }
+                Action::BeginStaging => {
+                    if new_state.staged.is_none() {
+                        new_state.staged = Some(Vec::new());
+                    }
+                }
+                Action::CommitStaged => {
+                    if let Some(pairs) = new_state.staged.take() {
+                        apply_commit(&mut new_state, pairs);
+                    }
+                }
+                Action::AbortStaged => {
+                    if let Some(pairs) = new_state.staged.take() {
+                        new_state.chain_bytes -=
+                            content_bytes(pairs.iter().map(|pair| pair.entry()));
+                    }
+                }
+                Action::SetChainLimit(limit) => {
+                    new_state.max_chain_bytes = limit;
+                }
+                Action::SetRateBuckets(ref buckets) => {
+                    new_state.rate_buckets = buckets.clone();
+                }
+                Action::SetClockSkew(secs) => {
+                    new_state.max_clock_skew_secs = secs;
                 }
             }
             Arc::new(new_state)
@@ -69,7 +436,31 @@ pub fn reduce(
 
 #[cfg(test)]
 pub mod tests {
-    use super::AgentState;
+    use super::{
+        blocks::{block_entry, unblock_entry}, check_times,
+        devices::{DeviceBinding, DeviceRevocation, DEVICE_SIGNING_KEY_PATH}, keystore::Keystore,
+        reduce, transaction::tests::test_transaction, Action, AgentState, Buckets, HEAD_MOVED,
+        INIT_COMPLETE_ENTRY_TYPE,
+    };
+    use agent::transaction::Transaction;
+    use dht::{
+        activity::get_agent_activity, aspect::Aspect, tests::{test_dht_state, test_reduce},
+        Action::HoldAspect,
+    };
+    use hash_table::{
+        entry::{
+            tests::{test_entry, test_type}, Entry,
+        },
+        header::{tests::test_untimed_header, Header}, pair::Pair,
+        provenance::tests::{test_agent_address, test_signing_keys},
+    };
+    use holochain_dna::zome::entry_types::RateBucket;
+    use limits::Resource;
+    use nucleus::Action::ReportLimitExceeded;
+    use state;
+    use std::{
+        slice, sync::{mpsc::channel, Arc},
+    };
 
     /// builds a dummy agent state for testing
     pub fn test_agent_state() -> AgentState {
@@ -93,4 +484,330 @@ pub mod tests {
     fn agent_state_top_pair() {
         assert_eq!(None, test_agent_state().top_pair());
     }
+
+    #[test]
+    /// committing the InitComplete marker marks init as complete and ends genesis
+    fn agent_state_init_complete() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let commit = |agent_state, entry| {
+            reduce(
+                agent_state,
+                &state::Action::Agent(Action::Commit(entry)),
+                &sender,
+            )
+        };
+
+        let agent_state = commit(Arc::new(test_agent_state()), test_entry());
+        assert!(!agent_state.init_complete());
+        assert_eq!(
+            Some(&test_entry()),
+            agent_state.top_pair().as_ref().map(|pair| pair.entry())
+        );
+
+        let agent_state = commit(agent_state, Entry::new(INIT_COMPLETE_ENTRY_TYPE, ""));
+        assert!(agent_state.init_complete());
+        assert_eq!(2, agent_state.genesis().len());
+
+        // what is committed after genesis isn't part of it
+        let agent_state = commit(agent_state, test_entry());
+        assert_eq!(2, agent_state.genesis().len());
+    }
+
+    #[test]
+    /// block entries block agents once they are on the chain
+    fn agent_state_blocked() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let agent_state = [Action::BeginStaging, Action::Commit(block_entry("bob"))]
+            .iter()
+            .fold(Arc::new(test_agent_state()), |agent_state, action| {
+                reduce(agent_state, &state::Action::Agent(action.clone()), &sender)
+            });
+        assert!(!agent_state.is_blocked("bob"));
+
+        let agent_state = reduce(agent_state, &state::Action::Agent(Action::CommitStaged), &sender);
+        assert!(agent_state.is_blocked("bob"));
+        let unblock = state::Action::Agent(Action::Commit(unblock_entry("bob")));
+        assert!(reduce(agent_state, &unblock, &sender).blocked().is_empty());
+    }
+
+    #[test]
+    /// device keys are trusted once bound, revocations the root key didn't sign fail
+    fn agent_state_devices() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let commit = |agent_state, entry| {
+            reduce(
+                agent_state,
+                &state::Action::Agent(Action::Commit(entry)),
+                &sender,
+            )
+        };
+        let root = Keystore::default();
+        let phone = root
+            .device_keystore("phone")
+            .unwrap()
+            .signing_key(DEVICE_SIGNING_KEY_PATH)
+            .unwrap();
+        let binding = DeviceBinding::new(&root, "phone", &phone).unwrap();
+        let agent_state = commit(Arc::new(test_agent_state()), binding.to_entry());
+        assert!(agent_state.devices().is_trusted(&phone.to_base58()));
+
+        let forged = DeviceRevocation::new(&Keystore::default(), &phone).unwrap();
+        let agent_state = commit(agent_state, forged.to_entry());
+        assert!(agent_state.last_commit().is_err());
+        assert!(agent_state.devices().is_trusted(&phone.to_base58()));
+
+        let revocation = DeviceRevocation::new(&root, &phone).unwrap();
+        let agent_state = commit(agent_state, revocation.to_entry());
+        assert!(agent_state.last_commit().is_ok());
+        assert!(agent_state.devices().is_revoked(&phone.to_base58()));
+    }
+
+    #[test]
+    /// a transaction is committed as contiguous pairs, the last one becoming the top pair
+    fn agent_state_commit_transaction() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let transaction = test_transaction();
+        let agent_state = reduce(
+            Arc::new(test_agent_state()),
+            &state::Action::Agent(Action::CommitTransaction(transaction.clone())),
+            &sender,
+        );
+
+        let pairs = agent_state.last_commit().unwrap();
+        assert_eq!(3, pairs.len());
+        for (pair, entry) in pairs.iter().zip(transaction.entries()) {
+            assert_eq!(entry, pair.entry());
+        }
+        assert_eq!(Some(pairs[0].key().as_str()), pairs[1].header().next());
+        assert_eq!(Some(pairs[1].key().as_str()), pairs[2].header().next());
+        assert_eq!(pairs.last().cloned(), agent_state.top_pair());
+    }
+
+    #[test]
+    /// every header follows the head of the chain and the latest header of its entry type
+    fn agent_state_links_commits() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let apply =
+            |agent_state, action| reduce(agent_state, &state::Action::Agent(action), &sender);
+        let post = |content: &str| Entry::new("post", content);
+        let agent_state = apply(Arc::new(test_agent_state()), Action::Commit(post("first")));
+        let first = agent_state.top_pair().unwrap();
+        assert_eq!(None, first.header().next());
+
+        let agent_state = apply(agent_state, Action::Commit(Entry::new("comment", "hi")));
+        let comment = agent_state.top_pair().unwrap();
+        assert_eq!(Some(first.key().as_str()), comment.header().next());
+        assert_eq!(None, comment.header().type_next());
+
+        let agent_state = apply(agent_state, Action::BeginStaging);
+        let agent_state = apply(agent_state, Action::Commit(post("staged")));
+        let staged = agent_state.last_commit().unwrap().remove(0);
+        assert_eq!(Some(comment.key().as_str()), staged.header().next());
+        assert_eq!(Some(first.key().as_str()), staged.header().type_next());
+
+        let mut transaction = Transaction::new();
+        transaction.stage(&post("second"));
+        transaction.stage(&post("third"));
+        let agent_state = apply(agent_state, Action::CommitTransaction(transaction));
+        let pairs = agent_state.last_commit().unwrap();
+        assert_eq!(Some(staged.key().as_str()), pairs[0].header().next());
+        assert_eq!(Some(staged.key().as_str()), pairs[0].header().type_next());
+        assert_eq!(Some(pairs[0].key().as_str()), pairs[1].header().type_next());
+
+        let agent_state = apply(agent_state, Action::CommitStaged);
+        assert_eq!(Some(pairs[1].clone()), agent_state.top_pair());
+        let agent_state = apply(agent_state, Action::Commit(post("fourth")));
+        let fourth = agent_state.top_pair().unwrap();
+        assert_eq!(Some(pairs[1].key().as_str()), fourth.header().type_next());
+    }
+
+    #[test]
+    /// the headers committed line up as the activity of the agent, without forks
+    fn agent_state_activity() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let alice = test_agent_address("alice");
+        let mut agent_state = Arc::new(test_agent_state());
+        let mut dht = test_dht_state();
+        for content in &["first", "second", "third"] {
+            let commit = Action::Commit(Entry::new("post", content));
+            agent_state = reduce(agent_state, &state::Action::Agent(commit), &sender);
+            let header = agent_state.top_pair().unwrap().header().sign(&test_signing_keys("alice"));
+            dht = test_reduce(dht, HoldAspect(alice.clone(), Aspect::Activity(header)));
+        }
+        let activity = get_agent_activity(&dht, &alice, &(0..10));
+        let indices: Vec<u64> = activity.headers.iter().map(|header| header.index).collect();
+        assert_eq!(vec![0, 1, 2], indices);
+        assert_eq!(Some(2), activity.highest);
+        assert!(activity.forks.is_empty());
+    }
+
+    #[test]
+    /// staged commits only reach the top pair once the staging is committed
+    fn agent_state_staging() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let apply =
+            |agent_state, action| reduce(agent_state, &state::Action::Agent(action), &sender);
+        let marker = Entry::new(INIT_COMPLETE_ENTRY_TYPE, "");
+
+        let committed = apply(Arc::new(test_agent_state()), Action::Commit(test_entry()));
+        let staging = apply(committed.clone(), Action::BeginStaging);
+        assert!(staging.is_staging());
+        let staging = apply(staging, Action::Commit(marker.clone()));
+        assert_eq!(committed.top_pair(), staging.top_pair());
+        assert_eq!(1, staging.chain_length());
+        assert!(!staging.init_complete());
+        assert_eq!(
+            Some(&marker),
+            staging.last_commit().unwrap().last().map(|pair| pair.entry())
+        );
+
+        let aborted = apply(staging.clone(), Action::AbortStaged);
+        assert!(!aborted.is_staging());
+        assert_eq!(committed.top_pair(), aborted.top_pair());
+        assert!(!aborted.init_complete());
+        let aborted = apply(aborted, Action::CommitStaged);
+        assert_eq!(committed.top_pair(), aborted.top_pair());
+
+        let done = apply(staging, Action::CommitStaged);
+        assert!(!done.is_staging());
+        assert_eq!(Some(&marker), done.top_pair().as_ref().map(|pair| pair.entry()));
+        assert!(done.init_complete());
+        assert_eq!(2, done.chain_length());
+    }
+
+    #[test]
+    /// commits going over the chain limit fail and are reported, staged bytes count until aborted
+    fn agent_state_chain_limit() {
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let apply =
+            |agent_state, action| reduce(agent_state, &state::Action::Agent(action), &sender);
+        let bytes = test_entry().content().len() as u64;
+
+        let agent_state = apply(
+            Arc::new(test_agent_state()),
+            Action::SetChainLimit(Some(2 * bytes)),
+        );
+        let agent_state = apply(agent_state, Action::BeginStaging);
+        let agent_state = apply(agent_state, Action::Commit(test_entry()));
+        let agent_state = apply(agent_state, Action::Commit(test_entry()));
+        assert_eq!(2 * bytes, agent_state.chain_bytes());
+        assert!(receiver.try_recv().is_err());
+
+        let exceeded = apply(agent_state.clone(), Action::Commit(test_entry()));
+        assert_eq!(2 * bytes, exceeded.chain_bytes());
+        assert!(exceeded.last_commit().is_err());
+        match receiver.try_recv().unwrap().action {
+            state::Action::Nucleus(ReportLimitExceeded(exceeded)) => {
+                assert_eq!(Resource::ChainBytes, exceeded.resource);
+                assert_eq!(3 * bytes, exceeded.requested);
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+
+        let aborted = apply(agent_state, Action::AbortStaged);
+        assert_eq!(0, aborted.chain_bytes());
+        let committed = apply(aborted, Action::Commit(test_entry()));
+        assert_eq!(bytes, committed.chain_bytes());
+        assert!(committed.last_commit().is_ok());
+    }
+
+    #[test]
+    /// commits using up the rate bucket of their entry type fail, failed transactions take nothing
+    fn agent_state_rate_buckets() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let apply =
+            |agent_state, action| reduce(agent_state, &state::Action::Agent(action), &sender);
+        let bucket = RateBucket {
+            capacity: 2,
+            interval_secs: 3600,
+        };
+        let mut buckets = Buckets::new();
+        buckets.insert(test_type(), (1, bucket));
+
+        let agent_state = apply(Arc::new(test_agent_state()), Action::SetRateBuckets(buckets));
+        let agent_state = apply(agent_state, Action::Commit(test_entry()));
+        assert!(agent_state.last_commit().is_ok());
+
+        let mut transaction = Transaction::new();
+        transaction.stage(&test_entry());
+        transaction.stage(&test_entry());
+        let refused = apply(agent_state, Action::CommitTransaction(transaction));
+        assert_eq!(
+            Err(format!(
+                "'{}' entries are over their rate bucket of 2 per 3600 seconds",
+                test_type()
+            )),
+            refused.last_commit()
+        );
+
+        let agent_state = apply(refused, Action::Commit(test_entry()));
+        assert!(agent_state.last_commit().is_ok());
+        let agent_state = apply(agent_state, Action::Commit(test_entry()));
+        assert!(agent_state.last_commit().is_err());
+        let other = Entry::new("other", "unlimited");
+        assert!(apply(agent_state, Action::Commit(other)).last_commit().is_ok());
+    }
+
+    #[test]
+    /// commits expecting a head only go through while the chain is still at it
+    fn agent_state_commit_on_head() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let apply =
+            |agent_state, action| reduce(agent_state, &state::Action::Agent(action), &sender);
+        let agent_state = Arc::new(test_agent_state());
+        assert_eq!(None, agent_state.head());
+
+        let agent_state = apply(agent_state, Action::CommitOnHead(test_entry(), None));
+        let head = agent_state.head();
+        assert!(head.is_some());
+        assert_eq!(
+            head,
+            agent_state.top_pair().map(|pair| pair.header().hash())
+        );
+
+        let refused = apply(agent_state.clone(), Action::CommitOnHead(test_entry(), None));
+        assert_eq!(Err(HEAD_MOVED.to_string()), refused.last_commit());
+        assert_eq!(head, refused.head());
+
+        let mut transaction = test_transaction();
+        transaction.expect_head(None);
+        let refused = apply(agent_state.clone(), Action::CommitTransaction(transaction));
+        assert_eq!(Err(HEAD_MOVED.to_string()), refused.last_commit());
+
+        let mut transaction = test_transaction();
+        transaction.expect_head(head.clone());
+        let committed = apply(agent_state, Action::CommitTransaction(transaction));
+        assert_eq!(3, committed.last_commit().unwrap().len());
+        assert_ne!(head, committed.head());
+    }
+
+    #[test]
+    /// commits timed before the head of the chain by more than the skew fail
+    fn agent_state_times() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let timed = |content: &str, next: Option<String>, time: u64| {
+            let entry = Entry::new("post", content);
+            Pair::from_header(Header::link(&entry, next, None).with_time(time), &entry)
+        };
+        let agent_state = reduce(
+            Arc::new(test_agent_state()),
+            &state::Action::Agent(Action::SetClockSkew(60)),
+            &sender,
+        );
+        assert_eq!(60, agent_state.max_clock_skew_secs());
+        let mut agent_state = (*agent_state).clone();
+        let head = timed("first", None, 1000);
+        agent_state.top_pair = Some(head.clone());
+
+        let within = timed("second", Some(head.header().hash()), 940);
+        assert!(check_times(&mut agent_state, slice::from_ref(&within)));
+        let before = timed("third", Some(within.header().hash()), 879);
+        assert!(!check_times(&mut agent_state, &[within, before]));
+        assert!(agent_state.last_commit().is_err());
+        // commits are timed now, long after the head
+        let now = Pair::new(&::chain::tests::test_chain(), &test_entry());
+        assert!(check_times(&mut agent_state, &[now]));
+        let untimed = Pair::from_header(test_untimed_header(), &test_entry());
+        assert!(!check_times(&mut agent_state, &[untimed]));
+    }
 }
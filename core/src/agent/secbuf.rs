@@ -0,0 +1,410 @@
+//! secret key material, the keystore's seed and passphrase, the secret keys derived from it and
+//! group secrets, is kept in SecBufs: the bytes are locked in memory where the platform lets them
+//! be, so they aren't swapped to disk, zeroized when dropped and never shown by Debug
+//! locking can fail, e.g. past RLIMIT_MEMLOCK, the bytes are kept regardless, see
+//! is_memory_locked()
+//! like sodium_malloc, every buffer gets pages of its own between two guard pages, the bytes
+//! ending where the second one starts, so running past them faults and unlocking them when the
+//! buffer is dropped leaves the pages of other buffers locked
+//! where pages can't be mapped the bytes are on the heap, unlocked
+
+use rand::{OsRng, Rng};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{
+    convert::TryFrom, fmt, ops::{Deref, DerefMut}, ptr, slice, str, sync::atomic,
+};
+
+/// secret bytes, locked in memory and zeroized on drop
+pub struct SecBuf {
+    bytes: *mut u8,
+    len: usize,
+    /// the pages mapped for the bytes alone, None where they are on the heap
+    mapping: Option<Mapping>,
+    /// whether the bytes were locked in memory
+    locked: bool,
+}
+
+// the bytes are owned by the buffer alone, like those of a Box
+unsafe impl Send for SecBuf {}
+unsafe impl Sync for SecBuf {}
+
+impl SecBuf {
+    /// len zero bytes
+    pub fn new(len: usize) -> SecBuf {
+        match map(len) {
+            Some((mapping, bytes)) => {
+                let locked = mlock(mapping.data(), mapping.data_len);
+                SecBuf {
+                    bytes,
+                    len,
+                    mapping: Some(mapping),
+                    locked,
+                }
+            }
+            None => SecBuf {
+                bytes: Box::into_raw(vec![0; len].into_boxed_slice()) as *mut u8,
+                len,
+                mapping: None,
+                locked: false,
+            },
+        }
+    }
+
+    /// a copy of the bytes, zeroizing the original is up to the caller
+    pub fn from_slice(bytes: &[u8]) -> SecBuf {
+        let mut buf = SecBuf::new(bytes.len());
+        buf.copy_from_slice(bytes);
+        buf
+    }
+
+    /// the bytes, zeroized where they were
+    pub fn from_vec(mut bytes: Vec<u8>) -> SecBuf {
+        let buf = SecBuf::from_slice(&bytes);
+        zeroize(&mut bytes);
+        buf
+    }
+
+    /// len random bytes from the OS, like nucleus::random
+    /// panics if the OS has no randomness to give, no secret can be made without it
+    pub fn random(len: usize) -> SecBuf {
+        let mut buf = SecBuf::new(len);
+        OsRng::new()
+            .expect("the OS has no randomness for secrets")
+            .fill_bytes(&mut buf);
+        buf
+    }
+
+    /// whether the bytes are locked in memory, false where the platform didn't let them be
+    pub fn is_memory_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// the bytes of a 32 byte key
+    /// panics unless there are 32, keys are only ever made with 32
+    pub fn as_key(&self) -> &[u8; 32] {
+        <&[u8; 32]>::try_from(&self[..]).expect("keys have 32 bytes")
+    }
+
+    /// the bytes as text, e.g. of a passphrase, None unless they are UTF-8
+    pub fn as_str(&self) -> Option<&str> {
+        str::from_utf8(self).ok()
+    }
+
+    /// the base58 of the bytes, the same as rust_base58's, with every step of the way in SecBufs
+    pub fn encode_base58(&self) -> SecBuf {
+        // base58 digits of the number, least significant first
+        let mut digits = SecBuf::new(self.len * 138 / 100 + 1);
+        let mut len = 0;
+        for &byte in self.iter() {
+            let mut carry = byte as usize;
+            for digit in digits[..len].iter_mut() {
+                carry += (*digit as usize) << 8;
+                *digit = (carry % 58) as u8;
+                carry /= 58;
+            }
+            while carry > 0 {
+                digits[len] = (carry % 58) as u8;
+                len += 1;
+                carry /= 58;
+            }
+        }
+        let zeros = self.iter().take_while(|&&byte| byte == 0).count();
+        let mut encoded = SecBuf::new(zeros + len);
+        for (i, character) in encoded.iter_mut().enumerate() {
+            *character = if i < zeros {
+                BASE58[0]
+            } else {
+                BASE58[digits[zeros + len - 1 - i] as usize]
+            };
+        }
+        encoded
+    }
+
+    /// the bytes of the base58, like encode_base58() with every step of the way in SecBufs, None
+    /// if a character isn't base58
+    pub fn decode_base58(encoded: &[u8]) -> Option<SecBuf> {
+        // bytes of the number, least significant first
+        let mut bytes = SecBuf::new(encoded.len() * 733 / 1000 + 1);
+        let mut len = 0;
+        for character in encoded {
+            let mut carry = BASE58.iter().position(|c| c == character)?;
+            for byte in bytes[..len].iter_mut() {
+                carry += *byte as usize * 58;
+                *byte = carry as u8;
+                carry >>= 8;
+            }
+            while carry > 0 {
+                bytes[len] = carry as u8;
+                len += 1;
+                carry >>= 8;
+            }
+        }
+        let zeros = encoded.iter().take_while(|&&c| c == BASE58[0]).count();
+        let mut decoded = SecBuf::new(zeros + len);
+        for i in 0..len {
+            decoded[zeros + i] = bytes[len - 1 - i];
+        }
+        Some(decoded)
+    }
+}
+
+/// the bitcoin alphabet, the one of rust_base58
+const BASE58: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+impl Deref for SecBuf {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.bytes, self.len) }
+    }
+}
+
+impl DerefMut for SecBuf {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { slice::from_raw_parts_mut(self.bytes, self.len) }
+    }
+}
+
+impl Clone for SecBuf {
+    fn clone(&self) -> SecBuf {
+        SecBuf::from_slice(self)
+    }
+}
+
+/// in constant time for buffers of the same length
+impl PartialEq for SecBuf {
+    fn eq(&self, other: &SecBuf) -> bool {
+        self.len == other.len
+            && self
+                .iter()
+                .zip(other.iter())
+                .fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+/// only the length shows
+impl fmt::Debug for SecBuf {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecBuf({} bytes)", self.len)
+    }
+}
+
+impl Drop for SecBuf {
+    fn drop(&mut self) {
+        zeroize(self);
+        match self.mapping.take() {
+            Some(mapping) => {
+                if self.locked {
+                    munlock(mapping.data(), mapping.data_len);
+                }
+                unmap(&mapping);
+            }
+            None => unsafe {
+                drop(Box::from_raw(ptr::slice_from_raw_parts_mut(self.bytes, self.len)));
+            },
+        }
+    }
+}
+
+/// as bytes, an array of numbers in JSON, for secrets persisted or sent on purpose
+impl Serialize for SecBuf {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for SecBuf {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<SecBuf, D::Error> {
+        Vec::<u8>::deserialize(deserializer).map(SecBuf::from_vec)
+    }
+}
+
+/// deserialize a SecBuf that has to be a key, see as_key(), for #[serde(deserialize_with)]
+pub fn deserialize_key<'de, D: Deserializer<'de>>(deserializer: D) -> Result<SecBuf, D::Error> {
+    let key = SecBuf::deserialize(deserializer)?;
+    if key.len() == 32 {
+        Ok(key)
+    } else {
+        Err(de::Error::invalid_length(key.len(), &"a 32 byte key"))
+    }
+}
+
+/// overwrite secret bytes with zeros in a way the compiler doesn't optimize out
+pub fn zeroize(bytes: &mut [u8]) {
+    for byte in bytes.iter_mut() {
+        // a plain write to memory about to be freed is dead to the optimizer
+        unsafe { ptr::write_volatile(byte, 0) };
+    }
+    atomic::compiler_fence(atomic::Ordering::SeqCst);
+}
+
+/// pages mapped for the bytes of one SecBuf: a guard page, the data pages, a guard page
+struct Mapping {
+    start: *mut u8,
+    page: usize,
+    data_len: usize,
+}
+
+impl Mapping {
+    /// start of the data pages
+    fn data(&self) -> *mut u8 {
+        unsafe { self.start.add(self.page) }
+    }
+}
+
+#[cfg(unix)]
+fn page_size() -> usize {
+    unsafe { ::libc::sysconf(::libc::_SC_PAGESIZE) as usize }
+}
+
+/// fresh zeroed pages for len bytes between two inaccessible guard pages, and where the bytes
+/// start so they end at the second guard page, None if they can't be mapped
+#[cfg(unix)]
+fn map(len: usize) -> Option<(Mapping, *mut u8)> {
+    use libc::{
+        c_void, mmap, mprotect, munmap, MAP_ANON, MAP_FAILED, MAP_PRIVATE, PROT_NONE, PROT_READ,
+        PROT_WRITE,
+    };
+    if len == 0 {
+        return None;
+    }
+    let page = page_size();
+    let data_len = len.div_ceil(page) * page;
+    let total = data_len + 2 * page;
+    unsafe {
+        let start = mmap(
+            ptr::null_mut(),
+            total,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANON,
+            -1,
+            0,
+        );
+        if start == MAP_FAILED {
+            return None;
+        }
+        let start = start as *mut u8;
+        let guarded = mprotect(start as *mut c_void, page, PROT_NONE) == 0
+            && mprotect(start.add(page + data_len) as *mut c_void, page, PROT_NONE) == 0;
+        if !guarded {
+            munmap(start as *mut c_void, total);
+            return None;
+        }
+        let bytes = start.add(page + data_len - len);
+        Some((
+            Mapping {
+                start,
+                page,
+                data_len,
+            },
+            bytes,
+        ))
+    }
+}
+
+#[cfg(unix)]
+fn unmap(mapping: &Mapping) {
+    let total = mapping.data_len + 2 * mapping.page;
+    unsafe { ::libc::munmap(mapping.start as *mut ::libc::c_void, total) };
+}
+
+#[cfg(unix)]
+fn mlock(data: *mut u8, len: usize) -> bool {
+    unsafe { ::libc::mlock(data as *const ::libc::c_void, len) == 0 }
+}
+
+#[cfg(unix)]
+fn munlock(data: *mut u8, len: usize) {
+    unsafe { ::libc::munlock(data as *const ::libc::c_void, len) };
+}
+
+#[cfg(not(unix))]
+fn map(_len: usize) -> Option<(Mapping, *mut u8)> {
+    None
+}
+
+#[cfg(not(unix))]
+fn unmap(_mapping: &Mapping) {}
+
+#[cfg(not(unix))]
+fn mlock(_data: *mut u8, _len: usize) -> bool {
+    false
+}
+
+#[cfg(not(unix))]
+fn munlock(_data: *mut u8, _len: usize) {}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use rust_base58::ToBase58;
+    use serde_json;
+
+    #[test]
+    fn zeroize_bytes() {
+        let mut secret = vec![7; 32];
+        zeroize(&mut secret);
+        assert_eq!(vec![0; 32], secret);
+    }
+
+    #[test]
+    /// the bytes are there to use but don't show
+    fn secbuf() {
+        let key = SecBuf::from_vec(vec![7; 32]);
+        assert_eq!(&[7; 32], key.as_key());
+        assert_eq!(key, key.clone());
+        assert_ne!(key, SecBuf::random(32));
+        assert_ne!(key, SecBuf::from_slice(&[7; 16]));
+        assert_eq!("SecBuf(32 bytes)", format!("{:?}", key));
+        assert_eq!(Some("passphrase"), SecBuf::from_slice(b"passphrase").as_str());
+        assert_eq!(0, SecBuf::new(0).len());
+    }
+
+    #[test]
+    /// the base58 is the one of rust_base58, leading zeros included
+    fn base58() {
+        for bytes in vec![vec![], vec![0], vec![0, 0, 1, 255], vec![255; 32], vec![7; 32]] {
+            let secret = SecBuf::from_slice(&bytes);
+            let encoded = secret.encode_base58();
+            assert_eq!(Some(bytes.to_base58().as_str()), encoded.as_str());
+            assert_eq!(Some(secret), SecBuf::decode_base58(&encoded));
+        }
+        assert_eq!(None, SecBuf::decode_base58(b"0OIl"));
+    }
+
+    #[test]
+    #[cfg(unix)]
+    /// buffers get pages of their own, the bytes ending at the guard page after them
+    fn own_pages() {
+        let page = page_size();
+        let a = SecBuf::from_slice(&[1; 32]);
+        let b = SecBuf::from_slice(&[2; 32]);
+        for buf in &[&a, &b] {
+            let end = buf.as_ptr() as usize + buf.len();
+            assert_eq!(0, end % page);
+            let mapping = buf.mapping.as_ref().unwrap();
+            assert_eq!(mapping.data() as usize + mapping.data_len, end);
+        }
+        assert_ne!(a.as_ptr() as usize / page, b.as_ptr() as usize / page);
+
+        let large = SecBuf::random(page + 1);
+        assert_eq!(2 * page, large.mapping.as_ref().unwrap().data_len);
+        assert_eq!(large, large.clone());
+
+        // dropping one buffer leaves the other whole
+        drop(a);
+        assert_eq!(&[2; 32], b.as_key());
+    }
+
+    #[test]
+    /// keys go as the arrays of bytes they were before they were SecBufs
+    fn serialize_keys() {
+        let key = SecBuf::from_slice(&[7; 32]);
+        let json = serde_json::to_string(&key).unwrap();
+        assert_eq!(serde_json::to_string(&[7; 32]).unwrap(), json);
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        assert_eq!(key, deserialize_key(&mut deserializer).unwrap());
+        let mut deserializer = serde_json::Deserializer::from_str("[7, 7]");
+        assert!(deserialize_key(&mut deserializer).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+//! a transaction stages several entries and links for a zome to commit all at once
+//! either every staged entry ends up on the chain under contiguous headers or none does, and the
+//! links are validated against the staged set as a whole so they can link entries staged
+//! alongside them
+
+use hash_table::entry::Entry;
+use holochain_dna::Dna;
+use validation::links::{validate_link, Link, LINK_ENTRY_TYPE};
+
+/// entries staged to be committed together, in commit order
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Transaction {
+    entries: Vec<Entry>,
+    /// the head the chain has to be at for the transaction to be committed, if it matters
+    expected_head: Option<Option<String>>,
+}
+
+impl Transaction {
+    pub fn new() -> Transaction {
+        Transaction::default()
+    }
+
+    /// stage an entry, returning its key
+    pub fn stage(&mut self, entry: &Entry) -> String {
+        self.entries.push(entry.clone());
+        entry.key()
+    }
+
+    /// stage a link, returning the key of the link entry
+    pub fn stage_link(&mut self, link: &Link) -> String {
+        self.stage(&link.to_entry())
+    }
+
+    /// only commit the transaction if the chain is still at head, see agent::HEAD_MOVED
+    pub fn expect_head(&mut self, head: Option<String>) {
+        self.expected_head = Some(head);
+    }
+
+    pub fn expected_head(&self) -> Option<&Option<String>> {
+        self.expected_head.as_ref()
+    }
+
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// the staged entry with the given key, if any
+    pub fn staged(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().find(|entry| entry.key() == key)
+    }
+
+    /// check the staged links are declared in the DNA
+    /// link ends are looked up among the staged entries first, then with lookup, e.g. in what
+    /// the DHT holds
+    pub fn validate<F>(&self, dna: &Dna, lookup: F) -> Result<(), String>
+    where
+        F: Fn(&str) -> Option<Entry>,
+    {
+        for entry in &self.entries {
+            if entry.entry_type() != LINK_ENTRY_TYPE {
+                continue;
+            }
+            let link = Link::from_entry(entry)
+                .ok_or_else(|| format!("malformed link entry {}", entry.key()))?;
+            validate_link(dna, &link, |key| {
+                self.staged(key).cloned().or_else(|| lookup(key))
+            })?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Transaction;
+    use validation::links::{
+        tests::{test_comment, test_link_dna, test_post}, Link,
+    };
+
+    /// transaction staging test_post() and test_comment() with the "comments" link between them
+    pub fn test_transaction() -> Transaction {
+        let mut transaction = Transaction::new();
+        let post = transaction.stage(&test_post());
+        let comment = transaction.stage(&test_comment());
+        transaction.stage_link(&Link::new(&post, &comment, "comments"));
+        transaction
+    }
+
+    #[test]
+    /// entries are staged in order
+    fn stage() {
+        let transaction = test_transaction();
+        assert_eq!(3, transaction.entries().len());
+        assert_eq!(test_post(), transaction.entries()[0]);
+        assert_eq!(Some(&test_comment()), transaction.staged(&test_comment().key()));
+        assert!(Transaction::new().is_empty());
+    }
+
+    #[test]
+    /// links are validated against the staged entries, then the lookup
+    fn validate() {
+        let dna = test_link_dna();
+        assert_eq!(Ok(()), test_transaction().validate(&dna, |_| None));
+
+        let mut transaction = Transaction::new();
+        let comment = transaction.stage(&test_comment());
+        transaction.stage_link(&Link::new(&test_post().key(), &comment, "comments"));
+        assert!(transaction.validate(&dna, |_| None).is_err());
+        assert_eq!(
+            Ok(()),
+            transaction.validate(&dna, |key| Some(test_post()).filter(|post| post.key() == key))
+        );
+
+        let mut transaction = test_transaction();
+        transaction.stage_link(&Link::new(&test_comment().key(), &test_post().key(), "comments"));
+        assert!(transaction.validate(&dna, |_| None).is_err());
+    }
+}
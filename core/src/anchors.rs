@@ -0,0 +1,240 @@
+//! anchors are well known entries that index other entries through links, e.g. linking posts to
+//! the anchor at path "posts/2018/03" lets anyone find every post from March 2018
+//! paths form a tree, every anchor is linked from its parent anchor so the tree can be walked down
+//! from the root, and entries linked from a leaf anchor stay spread across the DHT instead of all
+//! being links from a single hot address
+//! an anchor is addressed by its path alone, so every agent committing the same path commits the
+//! same entries and committing it again is harmless
+
+use dht::DhtState;
+use hash_table::entry::Entry;
+use serde_json;
+use std::fmt;
+use validation::links::Link;
+
+/// entry type anchors are committed as
+pub const ANCHOR_ENTRY_TYPE: &str = "%anchor";
+
+/// tag of the links from an anchor to its children
+pub const ANCHOR_CHILD_TAG: &str = "%anchor_child";
+
+/// where an anchor sits in the tree, the root anchor has no components
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Path(Vec<String>);
+
+impl Path {
+    pub fn root() -> Path {
+        Path(Vec::new())
+    }
+
+    /// path from its string form, components separated by '/', empty components are ignored
+    pub fn parse(path: &str) -> Path {
+        Path(
+            path.split('/')
+                .map(|component| component.trim())
+                .filter(|component| !component.is_empty())
+                .map(|component| component.to_string())
+                .collect(),
+        )
+    }
+
+    pub fn components(&self) -> &[String] {
+        &self.0
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// None for the root
+    pub fn parent(&self) -> Option<Path> {
+        if self.is_root() {
+            None
+        } else {
+            Some(Path(self.0[..self.0.len() - 1].to_vec()))
+        }
+    }
+
+    pub fn child(&self, component: &str) -> Path {
+        let mut components = self.0.clone();
+        components.push(component.to_string());
+        Path(components)
+    }
+
+    /// the anchor as an entry to commit
+    pub fn to_entry(&self) -> Entry {
+        Entry::new(
+            ANCHOR_ENTRY_TYPE,
+            &serde_json::to_string(self).expect("Path should serialize"),
+        )
+    }
+
+    /// the path of an anchor entry, None if the entry is not a well formed anchor
+    pub fn from_entry(entry: &Entry) -> Option<Path> {
+        if entry.entry_type() != ANCHOR_ENTRY_TYPE {
+            return None;
+        }
+        serde_json::from_str(entry.content()).ok()
+    }
+
+    /// key of the anchor entry, what to link indexed entries from
+    pub fn address(&self) -> String {
+        self.to_entry().key()
+    }
+
+    /// everything to commit for the anchor to be reachable from the root: the anchors along the
+    /// path, root first, each followed by the link to it from its parent
+    pub fn index_entries(&self) -> Vec<Entry> {
+        let mut path = Path::root();
+        let mut entries = vec![path.to_entry()];
+        for component in &self.0 {
+            let child = path.child(component);
+            let link = Link::new(&path.address(), &child.address(), ANCHOR_CHILD_TAG);
+            entries.push(child.to_entry());
+            entries.push(link.to_entry());
+            path = child;
+        }
+        entries
+    }
+
+    /// link indexing the entry at target under the anchor
+    /// the target's entry type has to declare it is linked from anchors with the tag
+    pub fn link_to(&self, target: &str, tag: &str) -> Link {
+        Link::new(&self.address(), target, tag)
+    }
+}
+
+impl fmt::Display for Path {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join("/"))
+    }
+}
+
+/// check a link from an anchor to another anchor links a parent to its child
+pub fn validate_child_link(base: &Entry, target: &Entry) -> Result<(), String> {
+    let base = Path::from_entry(base).ok_or_else(|| "malformed anchor".to_string())?;
+    let target = Path::from_entry(target).ok_or_else(|| "malformed anchor".to_string())?;
+    if target.parent() == Some(base.clone()) {
+        Ok(())
+    } else {
+        Err(format!("anchor '{}' is not a child of '{}'", target, base))
+    }
+}
+
+/// the child anchors of an anchor the DHT holds links to, sorted
+pub fn children(dht: &DhtState, path: &Path) -> Vec<Path> {
+    let mut children = dht
+        .links_from(&path.address(), ANCHOR_CHILD_TAG)
+        .iter()
+        .filter_map(|link| dht.holding(&link.target))
+        .filter_map(|entry| Path::from_entry(&entry))
+        .collect::<Vec<Path>>();
+    children.sort();
+    children
+}
+
+/// keys of the entries the DHT holds links to from an anchor with the tag, sorted
+pub fn targets(dht: &DhtState, path: &Path, tag: &str) -> Vec<String> {
+    let mut targets = dht
+        .links_from(&path.address(), tag)
+        .into_iter()
+        .map(|link| link.target)
+        .collect::<Vec<String>>();
+    targets.sort();
+    targets
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{tests::test_reduce, Action};
+    use validation::links::tests::test_post;
+
+    pub fn test_path() -> Path {
+        Path::parse("posts/2018/03")
+    }
+
+    /// dht state holding everything committed for the given paths, plus the links from the
+    /// leaf anchors to test_post() with the tag "posts"
+    pub fn test_anchor_dht(paths: &[Path]) -> DhtState {
+        let mut entries = vec![test_post()];
+        for path in paths {
+            entries.extend(path.index_entries());
+            entries.push(path.link_to(&test_post().key(), "posts").to_entry());
+        }
+        entries.into_iter().fold(DhtState::new(), |dht, entry| {
+            test_reduce(dht, Action::Hold(entry))
+        })
+    }
+
+    #[test]
+    /// paths parse and walk up and down the tree
+    fn path() {
+        let path = test_path();
+        assert_eq!(path, Path::parse("/posts//2018/ 03 /"));
+        assert_eq!("posts/2018/03", path.to_string());
+        assert_eq!(3, path.components().len());
+        assert_eq!(Some(Path::parse("posts/2018")), path.parent());
+        assert_eq!(path, Path::parse("posts/2018").child("03"));
+        assert_eq!(None, Path::root().parent());
+        assert!(Path::parse("/").is_root());
+    }
+
+    #[test]
+    /// anchors are addressed by their path
+    fn anchor_entry() {
+        let path = test_path();
+        assert_eq!(Some(path.clone()), Path::from_entry(&path.to_entry()));
+        assert_eq!(None, Path::from_entry(&test_post()));
+        assert_eq!(Path::parse("posts/2018/03").address(), path.address());
+        assert_ne!(Path::parse("posts/2018").address(), path.address());
+        assert_ne!(Path::root().address(), path.address());
+    }
+
+    #[test]
+    /// the index reaches the anchor from the root
+    fn index_entries() {
+        let entries = Path::parse("posts/2018").index_entries();
+        assert_eq!(5, entries.len());
+        assert_eq!(Path::root().to_entry(), entries[0]);
+        assert_eq!(
+            Some(Link::new(
+                &Path::parse("posts").address(),
+                &Path::parse("posts/2018").address(),
+                ANCHOR_CHILD_TAG
+            )),
+            Link::from_entry(&entries[4])
+        );
+        assert_eq!(1, Path::root().index_entries().len());
+    }
+
+    #[test]
+    /// only links from parents to their children are valid anchor links
+    fn child_links() {
+        let parent = Path::parse("posts/2018").to_entry();
+        let child = test_path().to_entry();
+        assert_eq!(Ok(()), validate_child_link(&parent, &child));
+        assert!(validate_child_link(&child, &parent).is_err());
+        assert!(validate_child_link(&Path::root().to_entry(), &child).is_err());
+        assert!(validate_child_link(&test_post(), &child).is_err());
+    }
+
+    #[test]
+    /// the tree can be walked down to the entries indexed
+    fn query() {
+        let march = test_path();
+        let april = Path::parse("posts/2018/04");
+        let dht = test_anchor_dht(&[march.clone(), april.clone()]);
+
+        assert_eq!(vec![Path::parse("posts")], children(&dht, &Path::root()));
+        assert_eq!(
+            vec![march.clone(), april.clone()],
+            children(&dht, &Path::parse("posts/2018"))
+        );
+        assert!(children(&dht, &march).is_empty());
+
+        assert_eq!(vec![test_post().key()], targets(&dht, &march, "posts"));
+        assert!(targets(&dht, &march, "comments").is_empty());
+        assert!(targets(&dht, &Path::parse("posts"), "posts").is_empty());
+    }
+}
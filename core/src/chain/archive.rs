@@ -0,0 +1,436 @@
+use agent::keys::Keys;
+use chain::Chain;
+use error::HolochainError;
+use hash_table::{header::Header, pair::Pair, pair_meta::PairMeta, HashTable};
+use miniz_oxide::{deflate::compress_to_vec, inflate::decompress_to_vec};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
+use std::{collections::HashMap, rc::Rc};
+
+/// DEFLATE level of what goes into cold storage, 6 is the zlib default
+pub const ARCHIVE_COMPRESSION_LEVEL: u8 = 6;
+
+/// serialized and compressed bytes of a value going into cold storage
+pub fn pack<T: Serialize>(value: &T) -> Result<Vec<u8>, HolochainError> {
+    let json = serde_json::to_vec(value).map_err(|e| HolochainError::new(&e.to_string()))?;
+    Ok(compress_to_vec(&json, ARCHIVE_COMPRESSION_LEVEL))
+}
+
+/// the value packed into bytes by pack()
+pub fn unpack<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, HolochainError> {
+    let json = decompress_to_vec(bytes)
+        .map_err(|e| HolochainError::new(&format!("corrupt cold storage: {:?}", e.status)))?;
+    serde_json::from_slice(&json).map_err(|e| HolochainError::new(&e.to_string()))
+}
+
+/// trait that defines the cold storage that archived Pairs are moved into
+/// cold storage is expected to be slow and cheap, it is only hit when an archived Pair is read
+/// the Headers of archived Pairs are kept there too, so an ArchiveTable can be restored
+pub trait ColdStore {
+    /// move a Pair into cold storage
+    fn put(&mut self, pair: &Pair) -> Result<(), HolochainError>;
+    /// lookup an archived Pair by Pair/Header key
+    fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError>;
+    /// drop an archived Pair from cold storage
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError>;
+    /// keep the Header of an archived Pair
+    fn put_header(&mut self, header: &Header) -> Result<(), HolochainError>;
+    /// every Header kept
+    fn headers(&self) -> Result<Vec<Header>, HolochainError>;
+    /// drop a kept Header by its key
+    fn remove_header(&mut self, key: &str) -> Result<(), HolochainError>;
+}
+
+/// in memory ColdStore that holds archived Pairs and Headers as compressed bytes, see pack()
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemColdStore {
+    pairs: HashMap<String, Vec<u8>>,
+    headers: HashMap<String, Vec<u8>>,
+}
+
+impl MemColdStore {
+    pub fn new() -> MemColdStore {
+        MemColdStore {
+            pairs: HashMap::new(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+impl ColdStore for MemColdStore {
+    fn put(&mut self, pair: &Pair) -> Result<(), HolochainError> {
+        self.pairs.insert(pair.key(), pack(pair)?);
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError> {
+        match self.pairs.get(key) {
+            None => Ok(None),
+            Some(bytes) => unpack(bytes).map(Some),
+        }
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError> {
+        self.pairs.remove(key);
+        Ok(())
+    }
+
+    fn put_header(&mut self, header: &Header) -> Result<(), HolochainError> {
+        self.headers.insert(header.key(), pack(header)?);
+        Ok(())
+    }
+
+    fn headers(&self) -> Result<Vec<Header>, HolochainError> {
+        self.headers.values().map(|bytes| unpack(bytes)).collect()
+    }
+
+    fn remove_header(&mut self, key: &str) -> Result<(), HolochainError> {
+        self.headers.remove(key);
+        Ok(())
+    }
+}
+
+/// HashTable that can move Pairs out of a hot table and into a ColdStore
+/// the Header of every archived Pair is retained in memory so chain integrity can be checked
+/// without touching cold storage, and so that anything read back from cold storage can be
+/// verified against what was archived
+/// retained Headers are kept in the ColdStore as well and restored from it by new()
+pub struct ArchiveTable<T: HashTable, C: ColdStore> {
+    hot: T,
+    cold: C,
+    headers: HashMap<String, Header>,
+}
+
+impl<T: HashTable, C: ColdStore> ArchiveTable<T, C> {
+    /// the archive of a hot table and a cold store, retaining the Headers kept in the cold store
+    /// Headers are retained under their own hash, whatever the cold store filed them under
+    pub fn new(hot: T, cold: C) -> Result<ArchiveTable<T, C>, HolochainError> {
+        let headers = cold
+            .headers()?
+            .into_iter()
+            .map(|header| (header.key(), header))
+            .collect();
+        Ok(ArchiveTable { hot, cold, headers })
+    }
+
+    /// move the Pair for the given key from the hot table into cold storage
+    /// returns false if there is no Pair in the hot table for the key
+    pub fn archive(&mut self, key: &str) -> Result<bool, HolochainError> {
+        let pair = match self.hot.get(key)? {
+            Some(pair) => pair,
+            None => return Ok(false),
+        };
+
+        // the pair must be safely in cold storage before it leaves the hot table
+        self.cold.put(&pair)?;
+        self.cold.put_header(pair.header())?;
+        self.headers.insert(pair.key(), pair.header().clone());
+        self.hot.remove(key)?;
+        Ok(true)
+    }
+
+    /// returns true if the Pair for the given key has been archived
+    pub fn is_archived(&self, key: &str) -> bool {
+        self.headers.contains_key(key)
+    }
+
+    /// the retained Header of an archived Pair
+    pub fn archived_header(&self, key: &str) -> Option<Header> {
+        self.headers.get(key).cloned()
+    }
+
+    /// number of archived Pairs
+    pub fn archived_count(&self) -> usize {
+        self.headers.len()
+    }
+}
+
+impl<T: HashTable, C: ColdStore> HashTable for ArchiveTable<T, C> {
+    fn setup(&mut self) -> Result<(), HolochainError> {
+        self.hot.setup()
+    }
+
+    fn teardown(&mut self) -> Result<(), HolochainError> {
+        self.hot.teardown()
+    }
+
+    fn commit(&mut self, pair: &Pair) -> Result<(), HolochainError> {
+        self.hot.commit(pair)
+    }
+
+    fn commit_batch(&mut self, pairs: &[Pair]) -> Result<(), HolochainError> {
+        self.hot.commit_batch(pairs)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError> {
+        if let Some(pair) = self.hot.get(key)? {
+            return Ok(Some(pair));
+        }
+
+        let header = match self.headers.get(key) {
+            Some(header) => header,
+            None => return Ok(None),
+        };
+
+        // fall back to cold storage, the retained header vouches for what comes back
+        match self.cold.get(key)? {
+            Some(ref pair) if pair.header() != header || !pair.validate() => Err(
+                HolochainError::new(&format!("archived pair {} failed integrity check", key)),
+            ),
+            Some(pair) => Ok(Some(pair)),
+            None => Err(HolochainError::new(&format!(
+                "archived pair {} missing from cold storage",
+                key
+            ))),
+        }
+    }
+
+    fn modify(
+        &mut self,
+        keys: &Keys,
+        old_pair: &Pair,
+        new_pair: &Pair,
+    ) -> Result<(), HolochainError> {
+        self.hot.modify(keys, old_pair, new_pair)
+    }
+
+    fn retract(&mut self, keys: &Keys, pair: &Pair) -> Result<(), HolochainError> {
+        self.hot.retract(keys, pair)
+    }
+
+    fn keys(&self) -> Result<Vec<String>, HolochainError> {
+        let mut keys = self.hot.keys()?;
+        keys.extend(self.headers.keys().cloned());
+        Ok(keys)
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError> {
+        if self.headers.remove(key).is_some() {
+            self.cold.remove(key)?;
+            self.cold.remove_header(key)?;
+        }
+        self.hot.remove(key)
+    }
+
+    fn assert_meta(&mut self, meta: &PairMeta) -> Result<(), HolochainError> {
+        self.hot.assert_meta(meta)
+    }
+
+    fn get_meta(&mut self, key: &str) -> Result<Option<PairMeta>, HolochainError> {
+        self.hot.get_meta(key)
+    }
+
+    fn get_pair_meta(&mut self, pair: &Pair) -> Result<Vec<PairMeta>, HolochainError> {
+        self.hot.get_pair_meta(pair)
+    }
+}
+
+impl<T: HashTable, C: ColdStore> Chain<ArchiveTable<T, C>> {
+    /// archive every Pair of the given entry types that is more than `horizon` Pairs below the top
+    /// this is intended for private entries, public entries can always be refetched from the DHT
+    /// returns the number of newly archived Pairs
+    pub fn archive(
+        &mut self,
+        horizon: usize,
+        entry_types: &[String],
+    ) -> Result<usize, HolochainError> {
+        // collect first so the iterator releases its reference to the table
+        let keys = self
+            .iter()
+            .skip(horizon)
+            .filter(|p| entry_types.iter().any(|t| t == p.header().entry_type()))
+            .map(|p| p.key())
+            .collect::<Vec<String>>();
+
+        let table = Rc::get_mut(&mut self.table).ok_or_else(|| {
+            HolochainError::new("cannot archive a chain whose table is borrowed elsewhere")
+        })?;
+
+        let mut archived = 0;
+        for key in keys {
+            if table.archive(&key)? {
+                archived += 1;
+            }
+        }
+        Ok(archived)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{pack, unpack, ArchiveTable, ColdStore, MemColdStore};
+    use chain::{Chain, ChainRead, ChainWrite};
+    use hash_table::{
+        entry::{
+            tests::{test_entry_a, test_entry_b, test_type_a, test_type_b}, Entry,
+        },
+        memory::{tests::test_table, MemTable}, pair::tests::{test_pair_a, test_pair_b},
+        HashTable,
+    };
+    use serde_json;
+    use std::rc::Rc;
+
+    /// builds a dummy archive table for testing
+    pub fn test_archive_table() -> ArchiveTable<MemTable, MemColdStore> {
+        ArchiveTable::new(test_table(), MemColdStore::new()).unwrap()
+    }
+
+    /// builds a dummy chain backed by an archive table for testing
+    pub fn test_archive_chain() -> Chain<ArchiveTable<MemTable, MemColdStore>> {
+        Chain::new(Rc::new(test_archive_table()))
+    }
+
+    #[test]
+    /// Pairs can round trip through the cold store
+    fn cold_store_round_trip() {
+        let mut cold = MemColdStore::new();
+        let p = test_pair_a();
+
+        assert_eq!(None, cold.get(&p.key()).unwrap());
+
+        cold.put(&p).unwrap();
+        assert_eq!(Some(p.clone()), cold.get(&p.key()).unwrap());
+
+        cold.remove(&p.key()).unwrap();
+        assert_eq!(None, cold.get(&p.key()).unwrap());
+
+        assert!(cold.headers().unwrap().is_empty());
+        cold.put_header(p.header()).unwrap();
+        assert_eq!(vec![p.header().clone()], cold.headers().unwrap());
+        cold.remove_header(&p.key()).unwrap();
+        assert!(cold.headers().unwrap().is_empty());
+    }
+
+    #[test]
+    /// what goes into cold storage is compressed, corrupt bytes are an error
+    fn cold_store_compression() {
+        let mut cold = MemColdStore::new();
+        let mut chain = test_archive_chain();
+        let p = chain.push(&Entry::new(&test_type_a(), &"a".repeat(10_000))).unwrap();
+
+        cold.put(&p).unwrap();
+        let json = serde_json::to_vec(&p).unwrap();
+        assert!(cold.pairs[&p.key()].len() * 10 < json.len());
+        assert_eq!(Ok(p.clone()), unpack(&pack(&p).unwrap()));
+
+        cold.pairs.insert(p.key(), json);
+        assert!(cold.get(&p.key()).is_err());
+    }
+
+    #[test]
+    /// archived Pairs leave the hot table but can still be read
+    fn archive() {
+        let mut ht = test_archive_table();
+        let p = test_pair_a();
+
+        ht.commit(&p).unwrap();
+        assert!(!ht.is_archived(&p.key()));
+
+        assert_eq!(Ok(true), ht.archive(&p.key()));
+        assert!(ht.is_archived(&p.key()));
+        assert_eq!(None, ht.hot.get(&p.key()).unwrap());
+        assert_eq!(Some(p.header().clone()), ht.archived_header(&p.key()));
+        assert_eq!(Some(p.clone()), ht.get(&p.key()).unwrap());
+
+        // nothing left in the hot table to archive
+        assert_eq!(Ok(false), ht.archive(&p.key()));
+        assert_eq!(1, ht.archived_count());
+    }
+
+    #[test]
+    /// reads from cold storage are checked against the retained header
+    fn archive_integrity() {
+        let mut ht = test_archive_table();
+        let p1 = test_pair_a();
+        let p2 = test_pair_b();
+
+        ht.commit(&p1).unwrap();
+        ht.archive(&p1.key()).unwrap();
+
+        // swap out what is in cold storage behind the table's back
+        ht.cold.pairs.insert(p1.key(), pack(&p2).unwrap());
+
+        assert!(ht.get(&p1.key()).is_err());
+
+        // cold storage losing the pair is also an error rather than a silent None
+        ht.cold.remove(&p1.key()).unwrap();
+        assert!(ht.get(&p1.key()).is_err());
+    }
+
+    #[test]
+    /// removing an archived pair clears it from cold storage too
+    fn archive_remove() {
+        let mut ht = test_archive_table();
+        let p = test_pair_a();
+
+        ht.commit(&p).unwrap();
+        ht.archive(&p.key()).unwrap();
+        ht.remove(&p.key()).unwrap();
+
+        assert!(!ht.is_archived(&p.key()));
+        assert_eq!(None, ht.cold.get(&p.key()).unwrap());
+        assert!(ht.cold.headers().unwrap().is_empty());
+        assert_eq!(None, ht.get(&p.key()).unwrap());
+    }
+
+    #[test]
+    /// retained headers survive the archive table, restored from the cold store
+    fn archive_restore() {
+        let mut ht = test_archive_table();
+        let p1 = test_pair_a();
+        let p2 = test_pair_b();
+        ht.commit(&p1).unwrap();
+        ht.commit(&p2).unwrap();
+        ht.archive(&p1.key()).unwrap();
+
+        let restored = ArchiveTable::new(ht.hot.clone(), ht.cold.clone()).unwrap();
+        assert!(restored.is_archived(&p1.key()));
+        assert_eq!(Some(p1.header().clone()), restored.archived_header(&p1.key()));
+        assert_eq!(Some(p1.clone()), restored.get(&p1.key()).unwrap());
+        assert_eq!(Some(p2.clone()), restored.get(&p2.key()).unwrap());
+
+        // headers are retained under their own hash, not the key they were kept under
+        let mut cold = MemColdStore::new();
+        cold.headers.insert(p1.key(), pack(p2.header()).unwrap());
+        let restored = ArchiveTable::new(test_table(), cold).unwrap();
+        assert!(!restored.is_archived(&p1.key()));
+        assert!(restored.is_archived(&p2.key()));
+    }
+
+    #[test]
+    /// chain.archive() only archives pairs of the given types beyond the horizon
+    fn chain_archive() {
+        let mut chain = test_archive_chain();
+
+        let p1 = chain
+            .push(&Entry::new(&test_type_a(), "archived content"))
+            .unwrap();
+        let p2 = chain.push(&test_entry_b()).unwrap();
+        let p3 = chain.push(&test_entry_a()).unwrap();
+        let p4 = chain.push(&test_entry_b()).unwrap();
+
+        // p4 and p3 are inside the horizon, p2 is the wrong type
+        assert_eq!(Ok(1), chain.archive(2, &[test_type_a()]));
+        assert!(chain.table().is_archived(&p1.key()));
+        assert!(!chain.table().is_archived(&p2.key()));
+        assert!(!chain.table().is_archived(&p3.key()));
+
+        // archiving again is a noop for what has already been archived
+        assert_eq!(Ok(1), chain.archive(2, &[test_type_a(), test_type_b()]));
+        assert!(chain.table().is_archived(&p2.key()));
+        assert!(!chain.table().is_archived(&p4.key()));
+
+        // the chain is still whole and lookups fall back to the cold store transparently
+        assert!(chain.validate());
+        assert_eq!(
+            vec![p4, p3, p2.clone(), p1.clone()],
+            chain.iter().collect::<Vec<_>>()
+        );
+        assert_eq!(Some(p1.clone()), chain.get(&p1.key()).unwrap());
+        assert_eq!(
+            Some(p1.clone()),
+            chain.get_entry(&p1.entry().key()).unwrap()
+        );
+    }
+}
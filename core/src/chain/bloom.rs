@@ -0,0 +1,155 @@
+use std::f64::consts::LN_2;
+
+/// default number of entries a chain bloom filter is sized for
+pub const BLOOM_DEFAULT_CAPACITY: usize = 10_000;
+/// default false positive rate a chain bloom filter is sized for
+pub const BLOOM_DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.01;
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// 64 bit FNV-1a, seeded so we can derive two independent hashes for double hashing
+/// the hash must be stable across builds and platforms as filters are persisted
+fn fnv1a(seed: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(FNV_OFFSET_BASIS ^ seed, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// BloomFilter over addresses, used to answer "definitely not present" without hitting storage
+/// false positives are possible, false negatives are not
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+    count: usize,
+    capacity: usize,
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        BloomFilter::new(BLOOM_DEFAULT_CAPACITY, BLOOM_DEFAULT_FALSE_POSITIVE_RATE)
+    }
+}
+
+impl BloomFilter {
+    /// build a new, empty BloomFilter sized for the expected number of items and false positive
+    /// rate
+    pub fn new(capacity: usize, false_positive_rate: f64) -> BloomFilter {
+        let n = capacity.max(1) as f64;
+        // round the optimal number of bits up to whole words
+        let num_words = (-(n * false_positive_rate.ln()) / (LN_2 * LN_2) / 64.0)
+            .ceil()
+            .max(1.0) as u64;
+        let num_bits = num_words * 64;
+        let num_hashes = ((num_bits as f64 / n) * LN_2).round().max(1.0) as u32;
+        BloomFilter {
+            bits: vec![0; num_words as usize],
+            num_bits,
+            num_hashes,
+            count: 0,
+            capacity: capacity.max(1),
+        }
+    }
+
+    /// the bit indexes for an address
+    fn indexes(&self, address: &str) -> Vec<u64> {
+        let h1 = fnv1a(0, address.as_bytes());
+        let h2 = fnv1a(1, address.as_bytes()) | 1;
+        (0..u64::from(self.num_hashes))
+            .map(|i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+            .collect()
+    }
+
+    /// add an address to the filter
+    pub fn insert(&mut self, address: &str) {
+        for i in self.indexes(address) {
+            self.bits[(i / 64) as usize] |= 1 << (i % 64);
+        }
+        self.count += 1;
+    }
+
+    /// returns false if the address was definitely never inserted
+    pub fn may_contain(&self, address: &str) -> bool {
+        self.indexes(address)
+            .iter()
+            .all(|i| self.bits[(i / 64) as usize] & (1 << (i % 64)) != 0)
+    }
+
+    /// number of insertions made into the filter
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    /// true once more items have been inserted than the filter was sized for
+    /// a saturated filter still works but its false positive rate degrades, so it should be
+    /// rebuilt with a larger capacity
+    pub fn is_saturated(&self) -> bool {
+        self.count > self.capacity
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::BloomFilter;
+
+    /// builds a small dummy bloom filter for testing
+    pub fn test_bloom() -> BloomFilter {
+        BloomFilter::new(100, 0.01)
+    }
+
+    #[test]
+    /// smoke test
+    fn new() {
+        let bloom = test_bloom();
+        assert_eq!(0, bloom.count());
+        assert!(!bloom.is_saturated());
+        assert!(bloom.num_hashes > 0);
+        assert!(bloom.num_bits >= 64);
+    }
+
+    #[test]
+    /// inserted addresses are always reported as possibly present
+    fn no_false_negatives() {
+        let mut bloom = test_bloom();
+        for i in 0..100 {
+            bloom.insert(&format!("address {}", i));
+        }
+        for i in 0..100 {
+            assert!(bloom.may_contain(&format!("address {}", i)));
+        }
+        assert_eq!(100, bloom.count());
+        assert!(!bloom.is_saturated());
+    }
+
+    #[test]
+    /// addresses that were never inserted are mostly reported as absent
+    fn false_positive_rate() {
+        let mut bloom = test_bloom();
+        for i in 0..100 {
+            bloom.insert(&format!("address {}", i));
+        }
+        let false_positives = (0..1000)
+            .filter(|i| bloom.may_contain(&format!("missing {}", i)))
+            .count();
+        // sized for 1%, leave plenty of room for variance
+        assert!(false_positives < 50);
+    }
+
+    #[test]
+    /// filters are saturated once past capacity
+    fn saturated() {
+        let mut bloom = BloomFilter::new(1, 0.01);
+        bloom.insert("a");
+        assert!(!bloom.is_saturated());
+        bloom.insert("b");
+        assert!(bloom.is_saturated());
+    }
+
+    #[test]
+    /// filters hash the same way everywhere so they can be persisted
+    fn stable_hashing() {
+        assert_eq!(0xaf63_dc4c_8601_ec8c, super::fnv1a(0, b"a"));
+    }
+}
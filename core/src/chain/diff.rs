@@ -0,0 +1,157 @@
+//! comparing two chains, e.g. a chain and its replica or its backup, entry by entry
+//! this builds without the native feature
+
+use chain::ChainRead;
+use hash_table::{header::Header, pair::Pair};
+use std::collections::{HashMap, HashSet};
+
+/// an entry both chains hold under different headers, e.g. linking to different previous headers
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Divergence {
+    /// hash of the entry
+    pub entry: String,
+    pub a: Header,
+    pub b: Header,
+}
+
+/// what diff() found, bottom to top of the chain the pairs are from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ChainDiff {
+    pub only_a: Vec<Pair>,
+    pub only_b: Vec<Pair>,
+    pub divergent: Vec<Divergence>,
+}
+
+impl ChainDiff {
+    /// true if both chains hold the same entries under the same headers
+    pub fn is_empty(&self) -> bool {
+        self.only_a.is_empty() && self.only_b.is_empty() && self.divergent.is_empty()
+    }
+
+    /// one line per difference
+    pub fn summary(&self) -> Vec<String> {
+        let mut lines = Vec::new();
+        for pair in &self.only_a {
+            lines.push(format!("only in a: {} {}", pair.key(), pair.entry().entry_type()));
+        }
+        for pair in &self.only_b {
+            lines.push(format!("only in b: {} {}", pair.key(), pair.entry().entry_type()));
+        }
+        for divergence in &self.divergent {
+            let link = |link: Option<&str>| link.unwrap_or("none").to_string();
+            lines.push(format!(
+                "divergent: {} is {} (next {}, type_next {}) in a and {} (next {}, type_next {}) \
+                 in b",
+                divergence.entry,
+                divergence.a.key(),
+                link(divergence.a.next()),
+                link(divergence.a.type_next()),
+                divergence.b.key(),
+                link(divergence.b.next()),
+                link(divergence.b.type_next()),
+            ));
+        }
+        lines
+    }
+}
+
+/// the pairs of the chain bottom to top
+fn pairs<C: ChainRead + ?Sized>(chain: &C) -> Vec<Pair> {
+    let mut pairs: Vec<Pair> = chain.pairs().collect();
+    pairs.reverse();
+    pairs
+}
+
+/// compare the entries of a and b
+/// an entry committed more than once is matched up in order, extra commits count as only in one
+pub fn diff<A: ChainRead + ?Sized, B: ChainRead + ?Sized>(a: &A, b: &B) -> ChainDiff {
+    let mut result = ChainDiff::default();
+    let mut in_b: HashMap<String, Vec<Pair>> = HashMap::new();
+    for pair in pairs(b) {
+        in_b.entry(pair.entry().hash()).or_default().push(pair);
+    }
+    // reversed so matching pairs are popped bottom up
+    for matches in in_b.values_mut() {
+        matches.reverse();
+    }
+    for pair in pairs(a) {
+        let entry = pair.entry().hash();
+        match in_b.get_mut(&entry).and_then(Vec::pop) {
+            None => result.only_a.push(pair),
+            Some(ref other) if other.header() != pair.header() => {
+                result.divergent.push(Divergence {
+                    entry,
+                    a: pair.header().clone(),
+                    b: other.header().clone(),
+                })
+            }
+            Some(_) => (),
+        }
+    }
+    let left: HashSet<String> = in_b
+        .values()
+        .flat_map(|matches| matches.iter().map(Pair::key))
+        .collect();
+    result.only_b = pairs(b)
+        .into_iter()
+        .filter(|pair| left.contains(&pair.key()))
+        .collect();
+    result
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chain::{tests::test_chain, Chain, ChainWrite};
+    use hash_table::{entry::Entry, memory::MemTable};
+    use std::rc::Rc;
+
+    #[test]
+    /// a chain and its copy differ in what was pushed on each after copying
+    fn diff_copies() {
+        let mut a = test_chain();
+        a.push_batch(&[Entry::new("post", "a"), Entry::new("post", "b")])
+            .unwrap();
+        let mut b = Chain::from_json(Rc::new(MemTable::new()), &a.to_json().unwrap());
+        assert!(diff(&a, &b).is_empty());
+
+        let only_a = a.push(&Entry::new("post", "c")).unwrap();
+        let only_b = b.push(&Entry::new("comment", "d")).unwrap();
+        let result = diff(&a, &b);
+        assert_eq!(vec![only_a.clone()], result.only_a);
+        assert_eq!(vec![only_b.clone()], result.only_b);
+        assert!(result.divergent.is_empty());
+        assert_eq!(
+            vec![
+                format!("only in a: {} post", only_a.key()),
+                format!("only in b: {} comment", only_b.key()),
+            ],
+            result.summary()
+        );
+    }
+
+    #[test]
+    /// the same entry linked to different previous headers diverges
+    fn diff_divergent() {
+        let mut a = test_chain();
+        let pairs = a
+            .push_batch(&[Entry::new("post", "a"), Entry::new("post", "b")])
+            .unwrap();
+        let mut b = test_chain();
+        let other = b.push(&Entry::new("post", "b")).unwrap();
+
+        let result = diff(&a, &b);
+        assert_eq!(vec![pairs[0].clone()], result.only_a);
+        assert!(result.only_b.is_empty());
+        assert_eq!(
+            vec![Divergence {
+                entry: pairs[1].entry().hash(),
+                a: pairs[1].header().clone(),
+                b: other.header().clone(),
+            }],
+            result.divergent
+        );
+        assert!(result.summary()[1].contains(&format!("(next {}", pairs[0].key())));
+        assert!(result.summary()[1].contains("(next none, type_next none) in b"));
+    }
+}
@@ -0,0 +1,314 @@
+//! interactive explorer for debugging chains that are corrupted or hold surprises
+//! commands move a cursor around the chain and print what is there, see EXPLORER_HELP
+//! the explorer only reads, chains are loaded as they are without being validated so broken
+//! ones can be looked into
+
+use chain::{verify, ChainRead};
+use error::HolochainError;
+use hash_table::pair::Pair;
+use serde_json;
+use validation::links::Link;
+
+/// number of headers log prints unless told otherwise
+pub const EXPLORER_DEFAULT_LOG_LENGTH: usize = 10;
+
+pub const EXPLORER_HELP: &str = "\
+top                  go to the top of the chain
+next                 go to the previous header
+type_next            go to the previous header of the same entry type
+goto <key>           go to the header with the key, or for the entry with the hash
+header               print the header
+entry                print the entry, pretty printed if it is JSON
+log [n]              list n headers down from here
+links                list the links committed from this entry
+follow <n>           go to the target of the nth link listed by links, or of this link entry
+verify               check the hashes and header links of the whole chain
+grep <text>          list the entries with content containing text
+help                 print this
+quit                 stop exploring";
+
+/// walks a chain on behalf of someone typing commands
+/// any chain will do, whatever HashTable it is kept in
+pub struct Explorer {
+    chain: Box<dyn ChainRead>,
+    /// the pair commands are about, None on an empty chain
+    cursor: Option<Pair>,
+}
+
+impl Explorer {
+    /// load a chain from its JSON, as written by Chain::save_json() or Chain::to_json()
+    pub fn from_json(json: &str) -> Result<Explorer, HolochainError> {
+        Ok(Explorer::new(verify::chain_from_json(json)?))
+    }
+
+    /// start exploring at the top of the chain
+    pub fn new<C: ChainRead + 'static>(chain: C) -> Explorer {
+        let cursor = chain.top();
+        Explorer {
+            chain: Box::new(chain),
+            cursor,
+        }
+    }
+
+    pub fn cursor(&self) -> Option<Pair> {
+        self.cursor.clone()
+    }
+
+    /// run a command line, returning what to print
+    pub fn run(&mut self, line: &str) -> Result<String, HolochainError> {
+        let mut words = line.split_whitespace();
+        let command = words.next().unwrap_or_default();
+        let argument = words.collect::<Vec<&str>>().join(" ");
+        match command {
+            "" => Ok(String::new()),
+            "help" => Ok(EXPLORER_HELP.to_string()),
+            "top" => {
+                self.cursor = self.chain.top();
+                self.header()
+            }
+            "next" => {
+                let next = self.current()?.header().next().map(str::to_string);
+                self.go(next, "the genesis header has no next")
+            }
+            "type_next" => {
+                let type_next = self.current()?.header().type_next().map(str::to_string);
+                self.go(type_next, "first header of its entry type")
+            }
+            "goto" => self.goto(&argument),
+            "header" => self.header(),
+            "entry" => self.entry(),
+            "log" => self.log(&argument),
+            "links" => self.links(),
+            "follow" => self.follow(&argument),
+            "verify" => Ok(self.verify()),
+            "grep" => self.grep(&argument),
+            _ => Err(HolochainError::ErrorGeneric(format!(
+                "unknown command {}, try help",
+                command
+            ))),
+        }
+    }
+
+    fn current(&self) -> Result<Pair, HolochainError> {
+        self.cursor
+            .clone()
+            .ok_or_else(|| HolochainError::new("the chain is empty"))
+    }
+
+    fn lookup(&self, key: &str) -> Result<Pair, HolochainError> {
+        self.chain
+            .get(key)?
+            .ok_or_else(|| HolochainError::ErrorGeneric(format!("no header {} in the chain", key)))
+    }
+
+    /// move to the header with the key, if there is one
+    fn go(&mut self, key: Option<String>, otherwise: &str) -> Result<String, HolochainError> {
+        let key = key.ok_or_else(|| HolochainError::new(otherwise))?;
+        self.cursor = Some(self.lookup(&key)?);
+        self.header()
+    }
+
+    fn goto(&mut self, key: &str) -> Result<String, HolochainError> {
+        if key.is_empty() {
+            return Err(HolochainError::new("goto needs a key"));
+        }
+        let pair = match self.chain.get(key)? {
+            Some(pair) => Some(pair),
+            None => self.chain.get_entry(key)?,
+        };
+        self.go(pair.map(|pair| pair.key()), &format!("nothing in the chain has key {}", key))
+    }
+
+    fn header(&self) -> Result<String, HolochainError> {
+        let pair = self.current()?;
+        let header = pair.header();
+        let signed_by: Vec<&str> = header.provenances().iter().map(|p| p.source()).collect();
+        Ok(format!(
+            "header     {}\nentry_type {}\ntime       {}\nnext       {}\nentry      {}\n\
+             type_next  {}\nsigned by  {}",
+            pair.key(),
+            header.entry_type(),
+            header.time(),
+            header.next().unwrap_or("-"),
+            header.entry(),
+            header.type_next().unwrap_or("-"),
+            if signed_by.is_empty() {
+                "-".to_string()
+            } else {
+                signed_by.join(", ")
+            }
+        ))
+    }
+
+    fn entry(&self) -> Result<String, HolochainError> {
+        let pair = self.current()?;
+        let content = pair.entry().content();
+        let pretty = serde_json::from_str::<serde_json::Value>(content)
+            .ok()
+            .and_then(|json| serde_json::to_string_pretty(&json).ok())
+            .unwrap_or_else(|| content.to_string());
+        Ok(format!("{} {}\n{}", pair.entry().entry_type(), pair.entry().hash(), pretty))
+    }
+
+    fn log(&self, n: &str) -> Result<String, HolochainError> {
+        let n = if n.is_empty() {
+            EXPLORER_DEFAULT_LOG_LENGTH
+        } else {
+            n.parse()
+                .map_err(|_| HolochainError::ErrorGeneric(format!("{} is not a number", n)))?
+        };
+        let log = self
+            .chain
+            .pairs()
+            .skip_while(|pair| Some(pair) != self.cursor.as_ref())
+            .take(n)
+            .map(|pair| format!("{} {}", pair.key(), pair.header().entry_type()))
+            .collect::<Vec<String>>();
+        Ok(log.join("\n"))
+    }
+
+    /// the links committed from the entry at the cursor, oldest first
+    fn links_from_cursor(&self) -> Result<Vec<Link>, HolochainError> {
+        let base = self.current()?.entry().hash();
+        let mut links = self
+            .chain
+            .pairs()
+            .filter_map(|pair| Link::from_entry(pair.entry()))
+            .filter(|link| link.base == base)
+            .collect::<Vec<Link>>();
+        links.reverse();
+        Ok(links)
+    }
+
+    fn links(&self) -> Result<String, HolochainError> {
+        let links = self.links_from_cursor()?;
+        if links.is_empty() {
+            return Ok("no links from this entry".to_string());
+        }
+        Ok(links
+            .iter()
+            .enumerate()
+            .map(|(i, link)| format!("{} {} -> {}", i, link.tag, link.target))
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+
+    fn follow(&mut self, n: &str) -> Result<String, HolochainError> {
+        let target = if n.is_empty() {
+            Link::from_entry(self.current()?.entry())
+                .ok_or_else(|| HolochainError::new("this is not a link entry, follow <n>"))?
+                .target
+        } else {
+            let i: usize = n
+                .parse()
+                .map_err(|_| HolochainError::ErrorGeneric(format!("{} is not a number", n)))?;
+            self.links_from_cursor()?
+                .get(i)
+                .ok_or_else(|| HolochainError::ErrorGeneric(format!("there is no link {}", i)))?
+                .target
+                .clone()
+        };
+        let pair = self.chain.get_entry(&target)?;
+        self.go(
+            pair.map(|pair| pair.key()),
+            &format!("the target {} is not in the chain", target),
+        )
+    }
+
+    /// everything wrong with the chain, one problem per line
+    fn verify(&self) -> String {
+        let verification = verify::verify(&*self.chain);
+        let mut lines = verification.problems;
+        lines.push(format!(
+            "{} headers checked, {} unsigned",
+            verification.checked, verification.unsigned
+        ));
+        lines.join("\n")
+    }
+
+    fn grep(&self, text: &str) -> Result<String, HolochainError> {
+        if text.is_empty() {
+            return Err(HolochainError::new("grep needs some text to look for"));
+        }
+        Ok(self
+            .chain
+            .pairs()
+            .filter(|pair| pair.entry().content().contains(text))
+            .map(|pair| format!("{} {}", pair.key(), pair.entry().entry_type()))
+            .collect::<Vec<String>>()
+            .join("\n"))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chain::{tests::test_chain, ChainWrite};
+    use hash_table::entry::Entry;
+
+    /// a chain of a post, a comment on it and a link from the post to the comment
+    fn test_explorer() -> (Explorer, Vec<Pair>) {
+        let mut chain = test_chain();
+        let post = Entry::new("post", "{\"title\":\"hello\"}");
+        let comment = Entry::new("comment", "nice");
+        let link = Link::new(&post.hash(), &comment.hash(), "comments");
+        let pairs = chain.push_batch(&[post, comment, link.to_entry()]).unwrap();
+        (Explorer::new(chain), pairs)
+    }
+
+    #[test]
+    /// headers are walked with next and goto
+    fn walk() {
+        let (mut explorer, pairs) = test_explorer();
+        assert_eq!(Some(pairs[2].clone()), explorer.cursor());
+        let header = explorer.run("next").unwrap();
+        assert!(header.starts_with(&format!("header     {}", pairs[1].key())));
+        explorer.run("next").unwrap();
+        assert!(explorer.run("next").is_err());
+        assert_eq!(Some(pairs[0].clone()), explorer.cursor());
+
+        explorer.run(&format!("goto {}", pairs[1].entry().hash())).unwrap();
+        assert_eq!(Some(pairs[1].clone()), explorer.cursor());
+        assert!(explorer.run("goto nowhere").is_err());
+        explorer.run("top").unwrap();
+        assert_eq!(Some(pairs[2].clone()), explorer.cursor());
+        assert_eq!(2, explorer.run("log 2").unwrap().lines().count());
+        assert!(explorer.run("fly").is_err());
+    }
+
+    #[test]
+    /// entries are pretty printed and links followed
+    fn entries_and_links() {
+        let (mut explorer, pairs) = test_explorer();
+        explorer.run(&format!("goto {}", pairs[0].key())).unwrap();
+        assert!(explorer.run("entry").unwrap().ends_with("{\n  \"title\": \"hello\"\n}"));
+        assert!(explorer.run("links").unwrap().starts_with("0 comments -> "));
+
+        explorer.run("follow 0").unwrap();
+        assert_eq!(Some(pairs[1].clone()), explorer.cursor());
+        assert_eq!("no links from this entry", explorer.run("links").unwrap());
+        explorer.run("top").unwrap();
+        explorer.run("follow").unwrap();
+        assert_eq!(Some(pairs[1].clone()), explorer.cursor());
+    }
+
+    #[test]
+    /// verify finds broken header links, grep finds content
+    fn verify_and_grep() {
+        let (explorer, pairs) = test_explorer();
+        assert_eq!("3 headers checked, 3 unsigned", explorer.verify());
+        assert_eq!(
+            format!("{} post", pairs[0].key()),
+            explorer.grep("hello").unwrap()
+        );
+
+        let json = serde_json::to_string(&vec![pairs[2].clone(), pairs[0].clone()]).unwrap();
+        let broken = Explorer::from_json(&json).unwrap();
+        assert!(broken.verify().starts_with(&format!(
+            "{} links to missing next {}",
+            pairs[2].key(),
+            pairs[1].key()
+        )));
+        assert!(Explorer::from_json("not a chain").is_err());
+    }
+}
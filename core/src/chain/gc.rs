@@ -0,0 +1,260 @@
+use chain::{Chain, ChainRead};
+use error::HolochainError;
+use hash_table::{status::LINK_NAME, HashTable};
+use std::{collections::HashSet, rc::Rc};
+
+/// default number of keys a single gc step marks or sweeps
+pub const GC_DEFAULT_STEP_BUDGET: usize = 100;
+
+/// where a GarbageCollector is at
+#[derive(Clone, Debug, PartialEq)]
+pub enum GcPhase {
+    /// walking out from the roots to find every live Pair
+    Mark,
+    /// removing every Pair that was not found live
+    Sweep,
+    Done,
+}
+
+/// incremental mark-and-sweep garbage collector for Pairs in a HashTable
+/// Pairs are live if reachable from a root through header links (next and type_next) or LINK
+/// meta (e.g. from a modified Pair to its replacement); everything else, e.g. left over from
+/// aborted commits or dropped DHT holdings, is removed
+/// work is done in budgeted steps so collection can be interleaved with other work rather than
+/// stopping the world
+/// only Pairs that already existed when collection started are candidates for removal, so
+/// anything committed while a collection is in progress is safe
+pub struct GarbageCollector {
+    frontier: Vec<String>,
+    live: HashSet<String>,
+    /// snapshot of the table keys taken when the collection started
+    candidates: Vec<String>,
+    phase: GcPhase,
+    removed: usize,
+}
+
+impl GarbageCollector {
+    /// start a collection of the table from the given root Pair keys, the candidates for removal
+    /// are the keys of the table now
+    pub fn new<T: HashTable>(
+        table: &T,
+        roots: Vec<String>,
+    ) -> Result<GarbageCollector, HolochainError> {
+        Ok(GarbageCollector {
+            frontier: roots,
+            live: HashSet::new(),
+            candidates: table.keys()?,
+            phase: GcPhase::Mark,
+            removed: 0,
+        })
+    }
+
+    /// start a collection of the table rooted at the top of a chain plus any Pairs held for the
+    /// DHT
+    pub fn for_chain<T: HashTable, C: ChainRead + ?Sized>(
+        chain: &C,
+        table: &T,
+        held: &[String],
+    ) -> Result<GarbageCollector, HolochainError> {
+        let mut roots = held.to_vec();
+        if let Some(top) = chain.top() {
+            roots.push(top.key());
+        }
+        GarbageCollector::new(table, roots)
+    }
+
+    pub fn phase(&self) -> GcPhase {
+        self.phase.clone()
+    }
+
+    /// number of Pairs removed so far
+    pub fn removed(&self) -> usize {
+        self.removed
+    }
+
+    /// true if the key has been found live so far
+    pub fn is_live(&self, key: &str) -> bool {
+        self.live.contains(key)
+    }
+
+    /// mark or sweep up to budget keys
+    pub fn step<T: HashTable>(
+        &mut self,
+        table: &mut T,
+        budget: usize,
+    ) -> Result<GcPhase, HolochainError> {
+        let mut budget = budget.max(1);
+        while budget > 0 && self.phase != GcPhase::Done {
+            match self.phase {
+                GcPhase::Mark => match self.frontier.pop() {
+                    Some(key) => {
+                        budget -= 1;
+                        self.mark(table, key)?;
+                    }
+                    None => self.phase = GcPhase::Sweep,
+                },
+                GcPhase::Sweep => match self.candidates.pop() {
+                    Some(key) => {
+                        budget -= 1;
+                        if !self.live.contains(&key) {
+                            table.remove(&key)?;
+                            self.removed += 1;
+                        }
+                    }
+                    None => self.phase = GcPhase::Done,
+                },
+                GcPhase::Done => {}
+            }
+        }
+        Ok(self.phase())
+    }
+
+    /// step until done, returning the number of removed Pairs
+    pub fn run<T: HashTable>(&mut self, table: &mut T) -> Result<usize, HolochainError> {
+        while self.step(table, GC_DEFAULT_STEP_BUDGET)? != GcPhase::Done {}
+        Ok(self.removed)
+    }
+
+    fn mark<T: HashTable>(&mut self, table: &mut T, key: String) -> Result<(), HolochainError> {
+        if self.live.contains(&key) {
+            return Ok(());
+        }
+        let pair = match table.get(&key)? {
+            Some(pair) => pair,
+            None => return Ok(()),
+        };
+        self.live.insert(key);
+
+        self.frontier
+            .extend(pair.header().next().map(|next| next.to_string()));
+        self.frontier.extend(
+            pair.header()
+                .type_next()
+                .map(|type_next| type_next.to_string()),
+        );
+        for meta in table.get_pair_meta(&pair)? {
+            if meta.attribute() == LINK_NAME {
+                self.frontier.push(meta.value());
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<T: HashTable> Chain<T> {
+    /// run a budgeted step of garbage collection against the chain's table
+    pub fn collect_garbage(
+        &mut self,
+        gc: &mut GarbageCollector,
+        budget: usize,
+    ) -> Result<GcPhase, HolochainError> {
+        let table = Rc::get_mut(&mut self.table).ok_or_else(|| {
+            HolochainError::new("cannot collect garbage for a chain whose table is borrowed elsewhere")
+        })?;
+        gc.step(table, budget)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{GarbageCollector, GcPhase};
+    use agent::keys::tests::test_keys;
+    use chain::{tests::test_chain, ChainRead, ChainWrite};
+    use hash_table::{
+        entry::{tests::test_type, Entry}, memory::tests::test_table, pair::Pair, HashTable,
+    };
+
+    #[test]
+    /// everything reachable from the chain top survives, orphans do not
+    fn collect() {
+        let mut chain = test_chain();
+        let p1 = chain.push(&Entry::new(&test_type(), "1")).unwrap();
+
+        // an aborted commit, generated against the chain but never pushed
+        let orphan = Pair::new(&chain, &Entry::new(&test_type(), "orphan"));
+        let p2 = chain.push(&Entry::new(&test_type(), "2")).unwrap();
+
+        let mut table = test_table();
+        for pair in chain.iter() {
+            table.commit(&pair).unwrap();
+        }
+        table.commit(&orphan).unwrap();
+
+        let mut gc = GarbageCollector::for_chain(&chain, &table, &[]).unwrap();
+        assert_eq!(Ok(1), gc.run(&mut table));
+        assert_eq!(GcPhase::Done, gc.phase());
+        assert!(gc.is_live(&p1.key()));
+        assert!(gc.is_live(&p2.key()));
+        assert_eq!(None, table.get(&orphan.key()).unwrap());
+        assert_eq!(Some(p1.clone()), table.get(&p1.key()).unwrap());
+    }
+
+    #[test]
+    /// held pairs and the targets of LINK meta are roots too
+    fn roots() {
+        let chain = test_chain();
+        let held = Pair::new(&chain, &Entry::new(&test_type(), "held"));
+        let old = Pair::new(&chain, &Entry::new(&test_type(), "old"));
+        let new = Pair::new(&chain, &Entry::new(&test_type(), "new"));
+        let orphan = Pair::new(&chain, &Entry::new(&test_type(), "orphan"));
+
+        let mut table = test_table();
+        for pair in &[&held, &old, &orphan] {
+            table.commit(pair).unwrap();
+        }
+        table.modify(&test_keys(), &old, &new).unwrap();
+
+        let mut gc = GarbageCollector::new(&table, vec![held.key(), old.key()]).unwrap();
+        assert_eq!(Ok(1), gc.run(&mut table));
+        assert!(table.get(&held.key()).unwrap().is_some());
+        assert!(table.get(&old.key()).unwrap().is_some());
+        assert!(table.get(&new.key()).unwrap().is_some());
+        assert!(table.get(&orphan.key()).unwrap().is_none());
+    }
+
+    #[test]
+    /// collection can be done a little at a time, interleaved with commits
+    fn incremental() {
+        let mut chain = test_chain();
+        for i in 0..10 {
+            chain.push(&Entry::new(&test_type(), &i.to_string())).unwrap();
+        }
+
+        let mut gc = GarbageCollector::for_chain(&chain, &*chain.table(), &[]).unwrap();
+        assert_eq!(Ok(GcPhase::Mark), chain.collect_garbage(&mut gc, 3));
+
+        // committed mid collection, after the snapshot of candidates
+        let late = chain.push(&Entry::new(&test_type(), "late")).unwrap();
+
+        let mut steps = 1;
+        while chain.collect_garbage(&mut gc, 3).unwrap() != GcPhase::Done {
+            steps += 1;
+        }
+        assert!(steps > 2);
+        assert_eq!(0, gc.removed());
+        assert!(chain.get(&late.key()).unwrap().is_some());
+        assert!(chain.validate());
+        assert_eq!(11, chain.iter().count());
+    }
+
+    #[test]
+    /// Pairs committed between starting a collection and its first step are not candidates
+    fn snapshot_at_start() {
+        let mut chain = test_chain();
+        chain.push(&Entry::new(&test_type(), "1")).unwrap();
+        let mut table = test_table();
+        for pair in chain.iter() {
+            table.commit(&pair).unwrap();
+        }
+
+        let mut gc = GarbageCollector::for_chain(&chain, &table, &[]).unwrap();
+        // unreachable from the roots, but not there when the collection started
+        let late = Pair::new(&chain, &Entry::new(&test_type(), "late"));
+        table.commit(&late).unwrap();
+
+        assert_eq!(Ok(0), gc.run(&mut table));
+        assert!(!gc.is_live(&late.key()));
+        assert!(table.get(&late.key()).unwrap().is_some());
+    }
+}
@@ -1,9 +1,29 @@
 // pub mod memory;
+pub mod archive;
+pub mod bloom;
+pub mod diff;
+#[cfg(feature = "native")]
+pub mod explorer;
+pub mod gc;
+pub mod verify;
+
+use chain::bloom::BloomFilter;
 use error::HolochainError;
-use hash_table::{entry::Entry, pair::Pair, HashTable};
+use hash_table::{
+    entry::{Entry, EntryMeta}, field_index::{IndexQuery, IndexedFields}, header::Header, pair::Pair,
+    HashTable,
+};
 use serde_json;
 use std::{fmt, rc::Rc};
 
+/// a chain as Chain::save_json() writes it, top to bottom with the bloom filter of its entries,
+/// so loading it doesn't walk the chain to build one, see verify::chain_from_json()
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SavedChain {
+    pub pairs: Vec<Pair>,
+    pub bloom: BloomFilter,
+}
+
 #[derive(Clone)]
 pub struct ChainIterator<T: HashTable> {
     // @TODO thread safe table references
@@ -31,20 +51,126 @@ impl<T: HashTable> Iterator for ChainIterator<T> {
 
     fn next(&mut self) -> Option<Pair> {
         let ret = self.current();
-        self.current = ret.clone()
+        self.current = ret.as_ref()
                         .and_then(|p| p.header().next())
                         // @TODO should this panic?
                         // @see https://github.com/holochain/holochain-rust/issues/146
-                        .and_then(|h| self.table.get(&h).unwrap());
+                        .and_then(|h| self.table.get(h).unwrap());
         ret
     }
 }
 
+/// reading a chain, which is all validation and read_only zome calls get of one
+/// nothing here changes the chain, so what only has a ChainRead cannot push, see ChainWrite
+///
+/// ```compile_fail
+/// # extern crate holochain_core;
+/// # use holochain_core::{chain::ChainRead, hash_table::entry::Entry};
+/// fn validate(chain: &dyn ChainRead, entry: &Entry) {
+///     chain.push(entry);
+/// }
+/// # fn main() {}
+/// ```
+pub trait ChainRead {
+    /// returns a clone of the top Pair
+    fn top(&self) -> Option<Pair>;
+
+    /// the Pairs of the chain, top to bottom
+    fn pairs(&self) -> Box<dyn Iterator<Item = Pair> + '_>;
+
+    /// get a Pair by Pair/Header key from the HashTable if it exists
+    fn get(&self, k: &str) -> Result<Option<Pair>, HolochainError>;
+
+    /// returns true if an Entry with the given Entry key has been pushed onto the chain
+    /// negative lookups are answered by the bloom filter without touching the HashTable
+    fn contains(&self, entry_hash: &str) -> Result<bool, HolochainError>;
+
+    /// get an Entry by Entry key from the HashTable if it exists
+    fn get_entry(&self, entry_hash: &str) -> Result<Option<Pair>, HolochainError>;
+
+    /// true between begin() and commit() or abort()
+    fn is_staging(&self) -> bool;
+
+    /// the Pairs pushed since begin(), in push order
+    fn staged(&self) -> Vec<Pair>;
+
+    /// the Pairs of entry_type with a value at path matching the query, by value then key, see
+    /// hash_table::field_index
+    fn query_index(
+        &self,
+        entry_type: &str,
+        path: &str,
+        query: &IndexQuery,
+    ) -> Result<Vec<Pair>, HolochainError>;
+
+    /// returns true if all pairs in the chain pass validation
+    fn validate(&self) -> bool {
+        self.pairs().all(|p| p.validate())
+    }
+
+    /// what is known about the Entry with the given Entry key short of its content, from the
+    /// Header it was last pushed under
+    fn get_entry_meta(&self, entry_hash: &str) -> Result<Option<EntryMeta>, HolochainError> {
+        Ok(self
+            .get_entry(entry_hash)?
+            .map(|pair| EntryMeta::new(pair.entry(), &[pair.header().clone()], None)))
+    }
+
+    /// get the top Pair by Entry type
+    fn top_type(&self, t: &str) -> Result<Option<Pair>, HolochainError> {
+        Ok(self.pairs().find(|p| p.header().entry_type() == t))
+    }
+
+    /// get the entire chain, top to bottom as a JSON array
+    fn to_json(&self) -> Result<String, serde_json::Error> {
+        let as_seq = self.pairs().collect::<Vec<Pair>>();
+        serde_json::to_string(&as_seq)
+    }
+}
+
+/// pushing onto a chain, which only the agent committing to its own chain gets to do
+/// neither trait borrows from or is generic over the HashTable, so chains kept in different ones
+/// can be held as Box<dyn ChainWrite> alike
+pub trait ChainWrite: ChainRead {
+    /// push a new Entry on to the top of the Chain
+    /// the Pair for the new Entry is automatically generated and validated against the current top
+    /// Pair to ensure the chain links up correctly across the underlying table data
+    /// the newly created and pushed Pair is returned in the fn Result
+    /// while staging the Pair is staged instead, see begin()
+    fn push(&mut self, entry: &Entry) -> Result<Pair, HolochainError>;
+
+    /// push many Entries on to the top of the Chain in one all-or-nothing transaction
+    /// Pairs are generated against each other in order, written with a single
+    /// table.commit_batch() and the top only moves once everything is committed
+    /// if anything fails nothing is pushed and the chain is left as it was
+    /// the pushed Pairs are returned in push order (i.e. the last one is the new top)
+    /// while staging the Pairs are staged instead, see begin()
+    fn push_batch(&mut self, entries: &[Entry]) -> Result<Vec<Pair>, HolochainError>;
+
+    /// start staging: from now on pushed Pairs are held back until commit() writes them all at
+    /// once, or abort() drops them
+    /// the top, iteration and lookups only see what is committed, so nothing refers to staged
+    /// Pairs before they are, e.g. validated
+    fn begin(&mut self) -> Result<(), HolochainError>;
+
+    /// write every staged Pair and stop staging, returning them in push order
+    /// if writing fails nothing is written, staging stops all the same
+    fn commit(&mut self) -> Result<Vec<Pair>, HolochainError>;
+
+    /// drop every staged Pair and stop staging, returning how many were dropped
+    fn abort(&mut self) -> usize;
+}
+
 pub struct Chain<T: HashTable> {
     // @TODO thread safe table references
     // @see https://github.com/holochain/holochain-rust/issues/135
     table: Rc<T>,
     top: Option<Pair>,
+    /// index of every entry address pushed onto this chain for fast negative lookups
+    bloom: BloomFilter,
+    /// Pairs pushed since begin(), only written to the table by commit()
+    /// None unless staging
+    staged: Option<Vec<Pair>>,
 }
 
 impl<T: HashTable> PartialEq for Chain<T> {
@@ -80,12 +206,74 @@ impl<T: HashTable> Chain<T> {
         Chain {
             top: None,
             table: Rc::clone(&table),
+            bloom: BloomFilter::default(),
+            staged: None,
         }
     }
 
-    /// returns a clone of the top Pair
-    pub fn top(&self) -> Option<Pair> {
-        self.top.clone()
+    /// restore a Chain from a HashTable that already holds its Pairs, e.g. after a restart
+    /// the persisted bloom filter is reused if available, otherwise (or if it has saturated) it is
+    /// rebuilt by walking the chain
+    pub fn load(table: Rc<T>, top: Option<Pair>, bloom: Option<BloomFilter>) -> Chain<T> {
+        let mut chain = Chain {
+            top,
+            table: Rc::clone(&table),
+            bloom: BloomFilter::default(),
+            staged: None,
+        };
+        match bloom {
+            Some(ref bloom) if !bloom.is_saturated() => chain.bloom = bloom.clone(),
+            _ => chain.rebuild_bloom(),
+        }
+        chain
+    }
+
+    /// rebuild the bloom filter from the chain contents, sized for the current chain length
+    pub fn rebuild_bloom(&mut self) {
+        let hashes = self
+            .iter()
+            .map(|p| p.header().entry().to_string())
+            .collect::<Vec<String>>();
+        let mut bloom = BloomFilter::new(
+            (hashes.len() * 2).max(bloom::BLOOM_DEFAULT_CAPACITY),
+            bloom::BLOOM_DEFAULT_FALSE_POSITIVE_RATE,
+        );
+        for hash in hashes {
+            bloom.insert(&hash);
+        }
+        self.bloom = bloom;
+    }
+
+    /// returns a clone of the bloom filter, e.g. for persisting alongside the chain
+    pub fn bloom(&self) -> BloomFilter {
+        self.bloom.clone()
+    }
+
+    /// the entire chain, top to bottom, and its bloom filter as JSON, see SavedChain
+    pub fn save_json(&self) -> Result<String, serde_json::Error> {
+        serde_json::to_string(&SavedChain {
+            pairs: self.pairs().collect(),
+            bloom: self.bloom(),
+        })
+    }
+
+    /// add an entry address to the bloom filter, rebuilding it bigger once it is saturated
+    fn index_entry(&mut self, entry_hash: &str) {
+        self.bloom.insert(entry_hash);
+        if self.bloom.is_saturated() {
+            self.rebuild_bloom();
+        }
+    }
+
+    /// index the fields of the content the DNA declares for lookups with query_index(), see
+    /// HashTable::set_indexed_fields()
+    pub fn set_indexed_fields(&mut self, fields: &IndexedFields) -> Result<(), HolochainError> {
+        // @TODO implement incubator for thread safety
+        // @see https://github.com/holochain/holochain-rust/issues/135
+        let table = Rc::get_mut(&mut self.table).ok_or_else(|| {
+            HolochainError::new("cannot index a chain whose table is borrowed elsewhere")
+        })?;
+        table.set_indexed_fields(fields)
     }
 
     /// returns a reference to the underlying HashTable
@@ -102,7 +290,7 @@ impl<T: HashTable> Chain<T> {
         }
 
         let top_pair = self.top().and_then(|p| Some(p.key()));
-        let next_pair = pair.header().next();
+        let next_pair = pair.header().next().map(|next| next.to_string());
 
         if top_pair != next_pair {
             return Err(HolochainError::new(&format!(
@@ -118,6 +306,7 @@ impl<T: HashTable> Chain<T> {
         let result = table.commit(&pair);
         if result.is_ok() {
             self.top = Some(pair.clone());
+            self.index_entry(pair.header().entry());
         }
         match result {
             Ok(_) => Ok(pair),
@@ -125,18 +314,26 @@ impl<T: HashTable> Chain<T> {
         }
     }
 
-    /// push a new Entry on to the top of the Chain
-    /// the Pair for the new Entry is automatically generated and validated against the current top
-    /// Pair to ensure the chain links up correctly across the underlying table data
-    /// the newly created and pushed Pair is returned in the fn Result
-    pub fn push(&mut self, entry: &Entry) -> Result<Pair, HolochainError> {
-        let pair = Pair::new(self, entry);
-        self.push_pair(pair)
-    }
+    /// write Pairs linked on top of each other and the current top in one table.commit_batch()
+    /// the top only moves once they all are
+    fn write_pairs(&mut self, pairs: &[Pair]) -> Result<(), HolochainError> {
+        let new_top = match pairs.last() {
+            Some(pair) => pair.clone(),
+            None => return Ok(()),
+        };
 
-    /// returns true if all pairs in the chain pass validation
-    pub fn validate(&self) -> bool {
-        self.iter().all(|p| p.validate())
+        // @TODO implement incubator for thread safety
+        // @see https://github.com/holochain/holochain-rust/issues/135
+        let table = Rc::get_mut(&mut self.table).ok_or_else(|| {
+            HolochainError::new("cannot push to a chain whose table is borrowed elsewhere")
+        })?;
+        table.commit_batch(pairs)?;
+
+        self.top = Some(new_top);
+        for pair in pairs {
+            self.index_entry(pair.header().entry());
+        }
+        Ok(())
     }
 
     /// returns a ChainIterator that provides cloned Pairs from the underlying HashTable
@@ -144,13 +341,44 @@ impl<T: HashTable> Chain<T> {
         ChainIterator::new(self.table(), &self.top())
     }
 
-    /// get a Pair by Pair/Header key from the HashTable if it exists
-    pub fn get(&self, k: &str) -> Result<Option<Pair>, HolochainError> {
+    /// restore a valid JSON chain
+    pub fn from_json(table: Rc<T>, s: &str) -> Self {
+        // @TODO inappropriate unwrap?
+        let mut as_seq: Vec<Pair> = serde_json::from_str(s).unwrap();
+        as_seq.reverse();
+
+        let mut chain = Chain::new(table);
+        for p in as_seq {
+            chain.push_pair(p).unwrap();
+        }
+        chain
+    }
+}
+
+impl<T: HashTable> ChainRead for Chain<T> {
+    fn top(&self) -> Option<Pair> {
+        self.top.clone()
+    }
+
+    fn pairs(&self) -> Box<dyn Iterator<Item = Pair> + '_> {
+        Box::new(self.iter())
+    }
+
+    fn get(&self, k: &str) -> Result<Option<Pair>, HolochainError> {
         self.table.get(k)
     }
 
-    /// get an Entry by Entry key from the HashTable if it exists
-    pub fn get_entry(&self, entry_hash: &str) -> Result<Option<Pair>, HolochainError> {
+    fn contains(&self, entry_hash: &str) -> Result<bool, HolochainError> {
+        if !self.bloom.may_contain(entry_hash) {
+            return Ok(false);
+        }
+        Ok(self.get_entry(entry_hash)?.is_some())
+    }
+
+    fn get_entry(&self, entry_hash: &str) -> Result<Option<Pair>, HolochainError> {
+        if !self.bloom.may_contain(entry_hash) {
+            return Ok(None);
+        }
         // @TODO - this is a slow way to do a lookup
         // @see https://github.com/holochain/holochain-rust/issues/50
         Ok(self
@@ -160,38 +388,108 @@ impl<T: HashTable> Chain<T> {
                 .find(|p| p.entry().hash() == entry_hash))
     }
 
-    /// get the top Pair by Entry type
-    pub fn top_type(&self, t: &str) -> Result<Option<Pair>, HolochainError> {
-        Ok(self.iter().find(|p| p.header().entry_type() == t))
+    /// answered by the HashTable, from its index for the fields declared, see set_indexed_fields()
+    fn query_index(
+        &self,
+        entry_type: &str,
+        path: &str,
+        query: &IndexQuery,
+    ) -> Result<Vec<Pair>, HolochainError> {
+        let mut pairs = Vec::new();
+        for key in self.table.query_index(entry_type, path, query)? {
+            pairs.extend(self.table.get(&key)?);
+        }
+        Ok(pairs)
     }
 
-    /// get the entire chain, top to bottom as a JSON array
-    pub fn to_json(&self) -> Result<String, serde_json::Error> {
-        let as_seq = self.iter().collect::<Vec<Pair>>();
-        serde_json::to_string(&as_seq)
+    fn is_staging(&self) -> bool {
+        self.staged.is_some()
     }
 
-    /// restore a valid JSON chain
-    pub fn from_json(table: Rc<T>, s: &str) -> Self {
-        // @TODO inappropriate unwrap?
-        let mut as_seq: Vec<Pair> = serde_json::from_str(s).unwrap();
-        as_seq.reverse();
+    fn staged(&self) -> Vec<Pair> {
+        self.staged.clone().unwrap_or_default()
+    }
+}
 
-        let mut chain = Chain::new(table);
-        for p in as_seq {
-            chain.push_pair(p).unwrap();
+impl<T: HashTable> ChainWrite for Chain<T> {
+    fn push(&mut self, entry: &Entry) -> Result<Pair, HolochainError> {
+        if self.staged.is_some() {
+            let mut pairs = self.push_batch(::std::slice::from_ref(entry))?;
+            return Ok(pairs.remove(0));
         }
-        chain
+        let pair = Pair::new(self, entry);
+        self.push_pair(pair)
+    }
+
+    fn push_batch(&mut self, entries: &[Entry]) -> Result<Vec<Pair>, HolochainError> {
+        let pending = self.staged.clone().unwrap_or_default();
+        let mut pairs: Vec<Pair> = Vec::with_capacity(entries.len());
+        for entry in entries {
+            let previous = || pending.iter().chain(pairs.iter()).rev();
+            let next = match previous().next() {
+                Some(pair) => Some(pair.key()),
+                None => self.top().map(|p| p.key()),
+            };
+            let type_next = match previous().find(|p| p.header().entry_type() == entry.entry_type())
+            {
+                Some(pair) => Some(pair.key()),
+                None => self.top_type(entry.entry_type())?.map(|p| p.key()),
+            };
+            pairs.push(Pair::from_header(
+                Header::link(entry, next, type_next),
+                entry,
+            ));
+        }
+
+        if let Some(ref mut staged) = self.staged {
+            staged.extend(pairs.iter().cloned());
+            return Ok(pairs);
+        }
+        self.write_pairs(&pairs)?;
+        Ok(pairs)
+    }
+
+    fn begin(&mut self) -> Result<(), HolochainError> {
+        if self.staged.is_some() {
+            return Err(HolochainError::new("chain is already staging"));
+        }
+        self.staged = Some(Vec::new());
+        Ok(())
+    }
+
+    fn commit(&mut self) -> Result<Vec<Pair>, HolochainError> {
+        let pairs = self
+            .staged
+            .take()
+            .ok_or_else(|| HolochainError::new("chain is not staging"))?;
+        self.write_pairs(&pairs)?;
+        Ok(pairs)
+    }
+
+    fn abort(&mut self) -> usize {
+        self.staged.take().map(|pairs| pairs.len()).unwrap_or_default()
     }
 }
 
 #[cfg(test)]
 pub mod tests {
 
-    use super::Chain;
+    use super::{
+        archive::tests::test_archive_chain, bloom::BloomFilter, verify::verify, Chain, ChainRead,
+        ChainWrite,
+    };
+    use agent::keys::Keys;
+    use error::HolochainError;
     use hash_table::{
-        entry::tests::{test_entry, test_entry_a, test_entry_b, test_type_a, test_type_b},
-        memory::{tests::test_table, MemTable}, pair::Pair, HashTable,
+        entry::{
+            tests::{test_entry, test_entry_a, test_entry_b, test_type_a, test_type_b},
+            Entry,
+        },
+        field_index::{
+            extract, tests::{test_indexed_fields, test_profile}, IndexQuery, IndexValue,
+        },
+        header::tests::test_timed_header, memory::{tests::test_table, MemTable}, pair::Pair,
+        pair_meta::PairMeta, HashTable,
     };
     use std::rc::Rc;
 
@@ -216,9 +514,14 @@ pub mod tests {
         let e1 = test_entry_a();
         let e2 = test_entry_b();
 
-        c1.push(&e1).unwrap();
-        c2.push(&e1).unwrap();
-        c3.push(&e2).unwrap();
+        // pushed at the same time, the clock could tick between two push() calls
+        let push = |chain: &mut Chain<MemTable>, entry: &Entry| {
+            let pair = Pair::from_header(test_timed_header(chain, entry), entry);
+            chain.push_pair(pair).unwrap();
+        };
+        push(&mut c1, &e1);
+        push(&mut c2, &e1);
+        push(&mut c3, &e2);
 
         assert_eq!(c1.top(), c2.top());
         assert_eq!(c1, c2);
@@ -268,7 +571,7 @@ pub mod tests {
         let p1 = chain.push(&e1).unwrap();
 
         assert_eq!(Some(p1.clone()), chain.top());
-        assert_eq!(e1, p1.entry());
+        assert_eq!(&e1, p1.entry());
         assert_eq!(e1.hash(), p1.header().entry());
 
         // we should be able to do it again
@@ -276,7 +579,7 @@ pub mod tests {
         let p2 = chain.push(&e2).unwrap();
 
         assert_eq!(Some(p2.clone()), chain.top());
-        assert_eq!(e2, p2.entry());
+        assert_eq!(&e2, p2.entry());
         assert_eq!(e2.hash(), p2.header().entry());
     }
 
@@ -392,6 +695,78 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// test chain.contains()
+    fn contains() {
+        let mut chain = test_chain();
+
+        let e1 = test_entry_a();
+        let e2 = test_entry_b();
+
+        assert_eq!(Ok(false), chain.contains(&e1.key()));
+
+        chain.push(&e1).unwrap();
+        assert_eq!(Ok(true), chain.contains(&e1.key()));
+        assert_eq!(Ok(false), chain.contains(&e2.key()));
+        assert!(!chain.bloom().may_contain(&e2.key()));
+
+        chain.push(&e2).unwrap();
+        assert_eq!(Ok(true), chain.contains(&e2.key()));
+    }
+
+    #[test]
+    /// test Chain::load() reuses or rebuilds the bloom filter
+    fn load() {
+        let mut chain = test_chain();
+
+        let e1 = test_entry_a();
+        let e2 = test_entry_b();
+
+        chain.push(&e1).unwrap();
+        chain.push(&e2).unwrap();
+
+        // persisted bloom filter is reused as is
+        let bloom = chain.bloom();
+        let loaded = Chain::load(chain.table(), chain.top(), Some(bloom.clone()));
+        assert_eq!(chain, loaded);
+        assert_eq!(bloom, loaded.bloom());
+
+        // missing bloom filter is rebuilt from the chain
+        let loaded = Chain::load(chain.table(), chain.top(), None);
+        assert_eq!(Ok(true), loaded.contains(&e1.key()));
+        assert_eq!(Ok(true), loaded.contains(&e2.key()));
+        assert_eq!(2, loaded.bloom().count());
+
+        // saturated bloom filter is rebuilt bigger
+        let mut saturated = BloomFilter::new(1, 0.01);
+        saturated.insert("a");
+        saturated.insert("b");
+        let loaded = Chain::load(chain.table(), chain.top(), Some(saturated));
+        assert!(!loaded.bloom().is_saturated());
+        assert_eq!(Ok(true), loaded.contains(&e1.key()));
+    }
+
+    #[test]
+    /// the bloom filter is rebuilt bigger once pushes saturate it
+    fn bloom_resizes() {
+        let mut chain = Chain::load(
+            Rc::new(test_table()),
+            None,
+            Some(BloomFilter::new(1, 0.01)),
+        );
+        let e1 = test_entry_a();
+        let e2 = test_entry_b();
+        chain.push(&e1).unwrap();
+        chain.push(&e2).unwrap();
+        assert!(!chain.bloom().is_saturated());
+        assert_eq!(2, chain.bloom().count());
+
+        chain.push_batch(&[Entry::new("post", "a"), Entry::new("post", "b")]).unwrap();
+        assert!(!chain.bloom().is_saturated());
+        assert_eq!(4, chain.bloom().count());
+        assert_eq!(Ok(true), chain.contains(&e1.key()));
+    }
+
     #[test]
     /// test chain.top_type()
     fn top_type() {
@@ -458,11 +833,280 @@ pub mod tests {
         chain.push(&e2).unwrap();
         chain.push(&e3).unwrap();
 
-        let expected_json = "[{\"header\":{\"entry_type\":\"testEntryType\",\"time\":\"\",\"next\":\"QmPT5HXvyv54Dg36YSK1A2rYvoPCNWoqpLzzZnHnQBcU6x\",\"entry\":\"QmbXSE38SN3SuJDmHKSSw5qWWegvU7oTxrLDRavWjyxMrT\",\"type_next\":\"QmawqBCVVap9KdaakqEHF4JzUjjLhmR7DpM5jgJko8j1rA\",\"signature\":\"\"},\"entry\":{\"content\":\"test entry content\",\"entry_type\":\"testEntryType\"}},{\"header\":{\"entry_type\":\"testEntryTypeB\",\"time\":\"\",\"next\":\"QmawqBCVVap9KdaakqEHF4JzUjjLhmR7DpM5jgJko8j1rA\",\"entry\":\"QmPz5jKXsxq7gPVAbPwx5gD2TqHfqB8n25feX5YH18JXrT\",\"type_next\":null,\"signature\":\"\"},\"entry\":{\"content\":\"other test entry content\",\"entry_type\":\"testEntryTypeB\"}},{\"header\":{\"entry_type\":\"testEntryType\",\"time\":\"\",\"next\":null,\"entry\":\"QmbXSE38SN3SuJDmHKSSw5qWWegvU7oTxrLDRavWjyxMrT\",\"type_next\":null,\"signature\":\"\"},\"entry\":{\"content\":\"test entry content\",\"entry_type\":\"testEntryType\"}}]";
-        assert_eq!(expected_json, chain.to_json().unwrap());
+        let expected_json = "[{\"header\":{\"entry_type\":\"testEntryType\",\"time\":\"\",\"next\":\"QmPT5HXvyv54Dg36YSK1A2rYvoPCNWoqpLzzZnHnQBcU6x\",\"entry\":\"QmbXSE38SN3SuJDmHKSSw5qWWegvU7oTxrLDRavWjyxMrT\",\"type_next\":\"QmawqBCVVap9KdaakqEHF4JzUjjLhmR7DpM5jgJko8j1rA\",\"provenances\":[]},\"entry\":{\"content\":\"test entry content\",\"entry_type\":\"testEntryType\"}},{\"header\":{\"entry_type\":\"testEntryTypeB\",\"time\":\"\",\"next\":\"QmawqBCVVap9KdaakqEHF4JzUjjLhmR7DpM5jgJko8j1rA\",\"entry\":\"QmPz5jKXsxq7gPVAbPwx5gD2TqHfqB8n25feX5YH18JXrT\",\"type_next\":null,\"provenances\":[]},\"entry\":{\"content\":\"other test entry content\",\"entry_type\":\"testEntryTypeB\"}},{\"header\":{\"entry_type\":\"testEntryType\",\"time\":\"\",\"next\":null,\"entry\":\"QmbXSE38SN3SuJDmHKSSw5qWWegvU7oTxrLDRavWjyxMrT\",\"type_next\":null,\"provenances\":[]},\"entry\":{\"content\":\"test entry content\",\"entry_type\":\"testEntryType\"}}]";
+        let json = chain.to_json().unwrap();
+        assert_eq!(chain, Chain::from_json(Rc::new(test_table()), &json));
+
+        // headers pushed are timed by the clock, these ones as they were written before
+        let untimed = Chain::from_json(Rc::new(test_table()), expected_json);
+        assert_eq!(3, untimed.iter().count());
+        assert_eq!(expected_json, untimed.to_json().unwrap());
+    }
+
+    /// MemTable that starts failing commits after a set number of successful ones
+    struct FailingTable {
+        table: MemTable,
+        commits_left: usize,
+    }
 
-        let table = test_table();
-        assert_eq!(chain, Chain::from_json(Rc::new(table), expected_json));
+    impl HashTable for FailingTable {
+        fn setup(&mut self) -> Result<(), HolochainError> {
+            self.table.setup()
+        }
+
+        fn teardown(&mut self) -> Result<(), HolochainError> {
+            self.table.teardown()
+        }
+
+        fn commit(&mut self, pair: &Pair) -> Result<(), HolochainError> {
+            if self.commits_left == 0 {
+                return Err(HolochainError::new("commit failed"));
+            }
+            self.commits_left -= 1;
+            self.table.commit(pair)
+        }
+
+        fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError> {
+            self.table.get(key)
+        }
+
+        fn modify(
+            &mut self,
+            keys: &Keys,
+            old_pair: &Pair,
+            new_pair: &Pair,
+        ) -> Result<(), HolochainError> {
+            self.table.modify(keys, old_pair, new_pair)
+        }
+
+        fn retract(&mut self, keys: &Keys, pair: &Pair) -> Result<(), HolochainError> {
+            self.table.retract(keys, pair)
+        }
+
+        fn keys(&self) -> Result<Vec<String>, HolochainError> {
+            self.table.keys()
+        }
+
+        fn remove(&mut self, key: &str) -> Result<(), HolochainError> {
+            self.table.remove(key)
+        }
+
+        fn assert_meta(&mut self, meta: &PairMeta) -> Result<(), HolochainError> {
+            self.table.assert_meta(meta)
+        }
+
+        fn get_meta(&mut self, key: &str) -> Result<Option<PairMeta>, HolochainError> {
+            self.table.get_meta(key)
+        }
+
+        fn get_pair_meta(&mut self, pair: &Pair) -> Result<Vec<PairMeta>, HolochainError> {
+            self.table.get_pair_meta(pair)
+        }
     }
 
+    #[test]
+    /// push_batch() links pairs up exactly as pushing one at a time would
+    fn push_batch() {
+        let mut batched = test_chain();
+        let mut pushed = test_chain();
+        let entries = vec![test_entry_a(), test_entry_b(), test_entry_a()];
+
+        batched.push(&test_entry_b()).unwrap();
+        pushed.push(&test_entry_b()).unwrap();
+
+        let pairs = batched.push_batch(&entries).unwrap();
+        for entry in &entries {
+            pushed.push(entry).unwrap();
+        }
+
+        assert_eq!(3, pairs.len());
+        assert_eq!(pairs.last().cloned(), batched.top());
+        assert_eq!(batched, pushed);
+        assert!(batched.validate());
+        assert_eq!(
+            pushed.iter().collect::<Vec<Pair>>(),
+            batched.iter().collect::<Vec<Pair>>()
+        );
+        assert!(batched.contains(&test_entry_a().key()).unwrap());
+
+        // an empty batch is a noop
+        assert_eq!(Ok(Vec::new()), batched.push_batch(&[]));
+        assert_eq!(pushed.top(), batched.top());
+    }
+
+    #[test]
+    /// push_batch() is all or nothing
+    fn push_batch_rollback() {
+        let mut chain = Chain::new(Rc::new(FailingTable {
+            table: test_table(),
+            commits_left: 2,
+        }));
+        let p1 = chain.push(&test_entry_a()).unwrap();
+
+        // the first pair of the batch commits, the second fails
+        assert!(
+            chain
+                .push_batch(&[test_entry_b(), Entry::new(&test_type_b(), "never pushed")])
+                .is_err()
+        );
+
+        assert_eq!(Some(p1.clone()), chain.top());
+        assert_eq!(vec![p1], chain.iter().collect::<Vec<Pair>>());
+        // the pair that did commit was removed again
+        let mut expected = test_chain();
+        expected.push(&test_entry_a()).unwrap();
+        let p2 = expected.push(&test_entry_b()).unwrap();
+        assert_eq!(None, chain.get(&p2.key()).unwrap());
+        assert!(!chain.contains(&test_entry_b().key()).unwrap());
+    }
+
+    #[test]
+    /// staged pairs are only written and become the top on commit()
+    fn staging() {
+        let mut chain = test_chain();
+        let p1 = chain.push(&test_entry_a()).unwrap();
+        assert!(!chain.is_staging());
+
+        chain.begin().unwrap();
+        assert!(chain.begin().is_err());
+        let p2 = chain.push(&test_entry_b()).unwrap();
+        let batch = chain.push_batch(&[test_entry_a()]).unwrap();
+        assert!(chain.is_staging());
+        assert_eq!(vec![p2.clone(), batch[0].clone()], chain.staged());
+
+        // nothing refers to staged pairs until they are committed
+        assert_eq!(Some(p1.clone()), chain.top());
+        assert_eq!(None, chain.get(&p2.key()).unwrap());
+        assert!(!chain.contains(&test_entry_b().key()).unwrap());
+
+        assert_eq!(Ok(vec![p2.clone(), batch[0].clone()]), chain.commit());
+        assert!(!chain.is_staging());
+        assert_eq!(Some(batch[0].clone()), chain.top());
+        assert!(chain.contains(&test_entry_b().key()).unwrap());
+        assert!(chain.validate());
+
+        // staged pairs link up exactly as pushed ones do
+        let mut pushed = test_chain();
+        for entry in &[test_entry_a(), test_entry_b(), test_entry_a()] {
+            pushed.push(entry).unwrap();
+        }
+        assert_eq!(pushed, chain);
+
+        assert!(chain.commit().is_err());
+    }
+
+    #[test]
+    /// aborted pairs leave no trace
+    fn staging_abort() {
+        let mut chain = test_chain();
+        let p1 = chain.push(&test_entry_a()).unwrap();
+
+        chain.begin().unwrap();
+        let p2 = chain.push(&test_entry_b()).unwrap();
+        assert_eq!(1, chain.abort());
+        assert!(!chain.is_staging());
+        assert_eq!(0, chain.abort());
+
+        assert_eq!(Some(p1.clone()), chain.top());
+        assert_eq!(vec![p1], chain.iter().collect::<Vec<Pair>>());
+        assert_eq!(None, chain.get(&p2.key()).unwrap());
+        assert!(!chain.contains(&test_entry_b().key()).unwrap());
+    }
+
+    #[test]
+    /// a staged set that fails to be written is not written at all
+    fn staging_commit_fails() {
+        let mut chain = Chain::new(Rc::new(FailingTable {
+            table: test_table(),
+            commits_left: 2,
+        }));
+        let p1 = chain.push(&test_entry_a()).unwrap();
+
+        chain.begin().unwrap();
+        chain.push(&test_entry_b()).unwrap();
+        chain.push(&test_entry_a()).unwrap();
+        assert!(chain.commit().is_err());
+        assert!(!chain.is_staging());
+        assert_eq!(vec![p1], chain.iter().collect::<Vec<Pair>>());
+    }
+
+    #[test]
+    /// what only has a ChainRead reads the chain as it is
+    fn read_only() {
+        let mut chain = test_chain();
+        let p1 = chain.push(&test_entry_a()).unwrap();
+        let p2 = chain.push(&test_entry_b()).unwrap();
+
+        let view: &dyn ChainRead = &chain;
+        assert_eq!(Some(p2.clone()), view.top());
+        assert_eq!(vec![p2.clone(), p1.clone()], view.pairs().collect::<Vec<Pair>>());
+        assert_eq!(Ok(Some(p1.clone())), view.get(&p1.key()));
+        assert_eq!(Ok(Some(p1.clone())), view.get_entry(&test_entry_a().key()));
+        assert_eq!(Ok(Some(p2.clone())), view.top_type(&test_type_b()));
+        assert!(view.validate());
+        assert_eq!(chain.to_json().unwrap(), view.to_json().unwrap());
+        assert_eq!(
+            test_timed_header(&chain, &test_entry_a()),
+            test_timed_header(view, &test_entry_a())
+        );
+    }
+
+    #[test]
+    /// metadata of pushed entries comes from the header they were last pushed under
+    fn get_entry_meta() {
+        let mut chain = test_chain();
+        assert_eq!(Ok(None), chain.get_entry_meta(&test_entry_a().key()));
+
+        chain.push(&test_entry_a()).unwrap();
+        let meta = chain.get_entry_meta(&test_entry_a().key()).unwrap().unwrap();
+        assert_eq!(test_type_a(), meta.entry_type);
+        assert_eq!(test_entry_a().content().len() as u64, meta.size);
+        assert_eq!(None, meta.author);
+        assert_eq!(None, meta.status);
+        assert_eq!(Ok(None), chain.get_entry_meta(&test_entry_b().key()));
+    }
+
+    #[test]
+    /// lookups by the fields indexed find what was pushed before and after they were declared
+    fn query_index() {
+        let mut chain = test_chain();
+        let alice = chain.push(&test_profile("alice", 30)).unwrap();
+        chain.set_indexed_fields(&test_indexed_fields()).unwrap();
+        let bob = chain.push(&test_profile("bob", 17)).unwrap();
+        chain.push(&test_entry_a()).unwrap();
+        let mut archived = test_archive_chain();
+        for pair in &[&alice, &bob] {
+            archived.push(pair.entry()).unwrap();
+        }
+
+        let everyone = IndexQuery::Range {
+            from: Some(IndexValue::Int(0)),
+            to: None,
+        };
+        let chains: [&dyn ChainRead; 2] = [&chain, &archived];
+        for chain in chains.iter() {
+            let found = chain.query_index("profile", "about.age", &everyone).unwrap();
+            let handles = found
+                .iter()
+                .map(|pair| extract(pair.entry().content(), "handle"))
+                .collect::<Vec<_>>();
+            let text = |h: &str| Some(IndexValue::Text(h.to_string()));
+            assert_eq!(vec![text("bob"), text("alice")], handles);
+        }
+        let carol = IndexQuery::Equals(IndexValue::Text("carol".to_string()));
+        assert_eq!(Ok(Vec::new()), chain.query_index("profile", "handle", &carol));
+    }
+
+    #[test]
+    /// chains kept in different HashTables can be held side by side
+    fn boxed() {
+        let mut chains: Vec<Box<dyn ChainWrite>> =
+            vec![Box::new(test_chain()), Box::new(test_archive_chain())];
+        for chain in &mut chains {
+            chain.push(&test_entry_a()).unwrap();
+            chain.push(&test_entry_b()).unwrap();
+        }
+        assert_eq!(chains[0].to_json().unwrap(), chains[1].to_json().unwrap());
+        assert!(chains.iter().all(|chain| verify(&**chain).is_valid()));
+    }
 }
@@ -0,0 +1,153 @@
+//! checking a chain holds together without trusting whoever handed it over, e.g. in a browser
+//! light client verifying the chain of an agent it doesn't run an instance for
+//! this builds without the native feature
+
+use chain::{bloom::BloomFilter, Chain, ChainRead, SavedChain};
+use error::HolochainError;
+use hash_table::{memory::MemTable, pair::Pair, HashTable};
+use serde_json;
+use std::rc::Rc;
+
+/// what verify() found
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Verification {
+    /// headers walked from the top
+    pub checked: usize,
+    /// headers without provenances, i.e. no signature to check
+    pub unsigned: usize,
+    /// everything wrong, one problem per line
+    pub problems: Vec<String>,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// the JSON of a chain, as Chain::save_json() writes it or the pairs alone
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ChainJson {
+    Saved(SavedChain),
+    Pairs(Vec<Pair>),
+}
+
+/// load a chain from its JSON, top to bottom as written by Chain::save_json() or
+/// Chain::to_json(), the bloom filter saved with it is used unless it has fewer entries than the
+/// chain, it is built from the pairs otherwise
+/// unlike Chain::from_json() the pairs are loaded as they are, broken links and all
+pub fn chain_from_json(json: &str) -> Result<Chain<MemTable>, HolochainError> {
+    let (pairs, bloom): (Vec<Pair>, Option<BloomFilter>) =
+        match serde_json::from_str(json).map_err(|e| HolochainError::new(&e.to_string()))? {
+            ChainJson::Saved(saved) => (saved.pairs, Some(saved.bloom)),
+            ChainJson::Pairs(pairs) => (pairs, None),
+        };
+    let mut table = MemTable::new();
+    for pair in &pairs {
+        table.commit(pair)?;
+    }
+    let bloom = bloom.filter(|bloom| bloom.count() >= pairs.len());
+    Ok(Chain::load(Rc::new(table), pairs.first().cloned(), bloom))
+}
+
+fn find<C: ChainRead + ?Sized>(chain: &C, key: &str) -> Option<Pair> {
+    chain.get(key).ok().and_then(|pair| pair)
+}
+
+/// walk the chain from the top checking every header matches its entry and links to headers that
+/// are there, of the same entry type for type_next
+pub fn verify<C: ChainRead + ?Sized>(chain: &C) -> Verification {
+    let mut verification = Verification::default();
+    for pair in chain.pairs() {
+        verification.checked += 1;
+        let key = pair.key();
+        let header = pair.header();
+        let mut problem = |problem: String| verification.problems.push(problem);
+        if header.entry() != pair.entry().hash() {
+            problem(format!("{} does not match the hash of its entry", key));
+        }
+        if header.entry_type() != pair.entry().entry_type() {
+            problem(format!("{} does not match the type of its entry", key));
+        }
+        if let Some(next) = header.next() {
+            if find(chain, next).is_none() {
+                problem(format!("{} links to missing next {}", key, next));
+            }
+        }
+        if let Some(type_next) = header.type_next() {
+            match find(chain, type_next) {
+                None => problem(format!("{} links to missing type_next {}", key, type_next)),
+                Some(ref previous) if previous.header().entry_type() != header.entry_type() => {
+                    problem(format!(
+                        "{} links to type_next {} of another entry type",
+                        key, type_next
+                    ))
+                }
+                Some(_) => (),
+            }
+        }
+        // @TODO check the signatures once headers are signed
+        // @see https://github.com/holochain/holochain-rust/issues/71
+        if header.provenances().is_empty() {
+            verification.unsigned += 1;
+        }
+    }
+    verification
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chain::{tests::test_chain, ChainWrite};
+    use hash_table::entry::Entry;
+
+    #[test]
+    /// chains pushed to are valid, ones with headers missing aren't
+    fn verify_chain() {
+        let mut chain = test_chain();
+        let pairs = chain
+            .push_batch(&[
+                Entry::new("post", "a"),
+                Entry::new("post", "b"),
+                Entry::new("post", "c"),
+            ])
+            .unwrap();
+        let verification = verify(&chain);
+        assert!(verification.is_valid());
+        assert_eq!(3, verification.checked);
+        assert_eq!(3, verification.unsigned);
+
+        let json = serde_json::to_string(&vec![pairs[2].clone(), pairs[0].clone()]).unwrap();
+        let broken = verify(&chain_from_json(&json).unwrap());
+        assert_eq!(1, broken.checked);
+        assert_eq!(
+            vec![
+                format!("{} links to missing next {}", pairs[2].key(), pairs[1].key()),
+                format!("{} links to missing type_next {}", pairs[2].key(), pairs[1].key()),
+            ],
+            broken.problems
+        );
+        assert!(chain_from_json("not a chain").is_err());
+    }
+
+    #[test]
+    /// the bloom filter saved with a chain is loaded with it
+    fn load_saved_chain() {
+        let mut chain = test_chain();
+        chain
+            .push_batch(&[Entry::new("post", "a"), Entry::new("post", "b")])
+            .unwrap();
+        let loaded = chain_from_json(&chain.save_json().unwrap()).unwrap();
+        assert_eq!(chain, loaded);
+        assert_eq!(chain.bloom(), loaded.bloom());
+        assert!(verify(&loaded).is_valid());
+
+        // a filter missing entries of the chain would hide them, it is built again
+        let mut saved: SavedChain = serde_json::from_str(&chain.save_json().unwrap()).unwrap();
+        saved.bloom = BloomFilter::new(10, 0.01);
+        let loaded = chain_from_json(&serde_json::to_string(&saved).unwrap()).unwrap();
+        assert_eq!(2, loaded.bloom().count());
+        assert_eq!(chain, loaded);
+    }
+}
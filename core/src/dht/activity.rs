@@ -0,0 +1,273 @@
+//! the neighborhood of an agent holds the headers it commits as Activity aspects of its address,
+//! so anyone can audit the sequence of its chain, spot forks and follow what it does without
+//! asking the agent itself
+//! the sequence is rebuilt from the next links of the headers, headers whose predecessors aren't
+//! held (yet) have no index until they are
+
+use dht::DhtState;
+use hash_table::header::Header;
+use std::{collections::HashMap, ops::Range};
+use validation::warrants::Warrant;
+
+/// headers get_agent_activity gives zomes at once when they don't ask for fewer
+pub const GET_AGENT_ACTIVITY_DEFAULT_LIMIT: u64 = 100;
+/// the most headers get_agent_activity gives zomes at once, so the activity fits the page the
+/// zome reads it from
+pub const GET_AGENT_ACTIVITY_MAX_LIMIT: u64 = 250;
+
+/// a header an agent committed with its position on the agent's chain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActivityHeader {
+    /// how many headers precede it on the chain, 0 for the first
+    pub index: u64,
+    /// hash of the header without its provenances, the one the next header links to
+    pub hash: String,
+    pub entry_type: String,
+    /// address of the entry committed under the header
+    pub entry: String,
+}
+
+/// what the neighborhood of an agent holds of its chain
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AgentActivity {
+    /// the headers with an index in the range asked for, by index then hash
+    pub headers: Vec<ActivityHeader>,
+    /// the highest index of the headers held, None if none link back to the first
+    pub highest: Option<u64>,
+    /// the indices more than one header is held at, where the agent forked its chain
+    pub forks: Vec<u64>,
+    /// hashes of the headers held whose predecessors aren't, sorted
+    pub unlinked: Vec<String>,
+    /// the warrants held of the agent, see validation::warrants
+    pub warrants: Vec<Warrant>,
+}
+
+/// the index of the header with the hash among headers, by hash, None if it doesn't link back to
+/// the first
+/// indices found on the way are kept in indices, so each header is walked past once
+fn index(
+    hash: &str,
+    headers: &HashMap<String, Header>,
+    indices: &mut HashMap<String, Option<u64>>,
+) -> Option<u64> {
+    // walk back until a header with a known index, the first or a gap
+    let mut path = Vec::new();
+    let mut current = hash.to_string();
+    let mut next_index = loop {
+        if let Some(known) = indices.get(&current) {
+            break known.map(|index| index + 1);
+        }
+        let header = match headers.get(&current) {
+            Some(header) => header,
+            None => break None,
+        };
+        path.push(current.clone());
+        match header.next() {
+            Some(next) => current = next.to_string(),
+            None => break Some(0),
+        }
+    };
+    for hash in path.into_iter().rev() {
+        indices.insert(hash, next_index);
+        next_index = next_index.map(|index| index + 1);
+    }
+    indices.get(hash).cloned().unwrap_or(None)
+}
+
+/// the activity of the agent held, with the headers in the range of indices, from included to
+/// not
+pub fn get_agent_activity(dht: &DhtState, agent: &str, range: &Range<u64>) -> AgentActivity {
+    // by the hash they are linked by, without the provenances, so a header held with more
+    // provenances than another copy of it is the same one
+    let headers: HashMap<String, Header> = dht
+        .activity(agent)
+        .into_iter()
+        .map(|header| (header.signed_hash(), header))
+        .collect();
+    let mut indices = HashMap::new();
+    let mut activity = AgentActivity::default();
+    let mut held_at: HashMap<u64, usize> = HashMap::new();
+    for (hash, header) in &headers {
+        match index(hash, &headers, &mut indices) {
+            Some(index) => {
+                *held_at.entry(index).or_default() += 1;
+                activity.highest = activity.highest.max(Some(index));
+                if range.start <= index && index < range.end {
+                    activity.headers.push(ActivityHeader {
+                        index,
+                        hash: hash.clone(),
+                        entry_type: header.entry_type().to_string(),
+                        entry: header.entry().to_string(),
+                    });
+                }
+            }
+            None => activity.unlinked.push(hash.clone()),
+        }
+    }
+    activity
+        .headers
+        .sort_by(|a, b| (a.index, &a.hash).cmp(&(b.index, &b.hash)));
+    activity.forks = held_at
+        .into_iter()
+        .filter(|&(_, held)| held > 1)
+        .map(|(index, _)| index)
+        .collect();
+    activity.forks.sort();
+    activity.unlinked.sort();
+    activity.warrants = dht.warrants(agent);
+    activity
+}
+
+/// the range of indices zomes get the headers of, from included to not and at most limit of
+/// them, GET_AGENT_ACTIVITY_DEFAULT_LIMIT if not set and GET_AGENT_ACTIVITY_MAX_LIMIT at most
+/// the next page is from the end of the range
+pub fn limited_range(from: u64, to: Option<u64>, limit: Option<u64>) -> Range<u64> {
+    let limit = limit
+        .unwrap_or(GET_AGENT_ACTIVITY_DEFAULT_LIMIT)
+        .min(GET_AGENT_ACTIVITY_MAX_LIMIT);
+    let end = from.saturating_add(limit);
+    from..to.map_or(end, |to| to.min(end))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        aspect::Aspect, tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::{
+        entry::Entry, provenance::tests::{test_agent_address, test_signing_keys},
+    };
+
+    /// headers of a chain of n entries, the first first
+    pub fn test_activity_headers(n: usize) -> Vec<Header> {
+        let mut headers: Vec<Header> = Vec::new();
+        for i in 0..n {
+            let entry = Entry::new("post", &format!("post {}", i));
+            let next = headers.last().map(|header| header.hash());
+            headers.push(Header::link(&entry, next, None));
+        }
+        headers
+    }
+
+    /// headers of a chain of n entries signed by the named test agent, e.g. "alice"
+    pub fn test_signed_activity(name: &str, n: usize) -> Vec<Header> {
+        test_activity_headers(n)
+            .iter()
+            .map(|header| header.sign(&test_signing_keys(name)))
+            .collect()
+    }
+
+    #[test]
+    /// zomes get at most the limit of headers, the next page starting where the range ends
+    fn limited() {
+        assert_eq!(0..100, limited_range(0, None, None));
+        assert_eq!(5..15, limited_range(5, None, Some(10)));
+        assert_eq!(5..8, limited_range(5, Some(8), Some(10)));
+        assert_eq!(5..255, limited_range(5, None, Some(u64::MAX)));
+        assert_eq!(u64::MAX..u64::MAX, limited_range(u64::MAX, None, None));
+    }
+
+    fn hold(dht: DhtState, agent: &str, headers: &[Header]) -> DhtState {
+        headers.iter().fold(dht, |dht, header| {
+            let aspect = Aspect::Activity(header.clone());
+            test_reduce(dht, Action::HoldAspect(agent.to_string(), aspect))
+        })
+    }
+
+    #[test]
+    /// headers are indexed by their position on the chain, whatever order they are held in
+    fn indexed_in_chain_order() {
+        let alice = test_agent_address("alice");
+        let mut headers = test_signed_activity("alice", 4);
+        let expected: Vec<(u64, String)> = headers
+            .iter()
+            .enumerate()
+            .map(|(index, header)| (index as u64, header.signed_hash()))
+            .collect();
+        headers.reverse();
+        let dht = hold(test_dht_state(), &alice, &headers);
+
+        let activity = get_agent_activity(&dht, &alice, &(0..u64::MAX));
+        let got: Vec<(u64, String)> = activity
+            .headers
+            .iter()
+            .map(|header| (header.index, header.hash.clone()))
+            .collect();
+        assert_eq!(expected, got);
+        assert_eq!("post", activity.headers[0].entry_type);
+        assert_eq!(Some(3), activity.highest);
+        assert!(activity.forks.is_empty());
+        assert!(activity.unlinked.is_empty());
+
+        // only the range asked for comes back
+        let activity = get_agent_activity(&dht, &alice, &(1..3));
+        assert_eq!(vec![1, 2], activity.headers.iter().map(|h| h.index).collect::<Vec<_>>());
+        assert_eq!(Some(3), activity.highest);
+
+        // the activity of others is theirs
+        assert_eq!(
+            AgentActivity::default(),
+            get_agent_activity(&dht, &test_agent_address("bob"), &(0..10))
+        );
+    }
+
+    #[test]
+    /// headers past a gap aren't indexed until what is missing is held
+    fn gaps() {
+        let alice = test_agent_address("alice");
+        let headers = test_signed_activity("alice", 3);
+        let dht = hold(test_dht_state(), &alice, &[headers[0].clone(), headers[2].clone()]);
+        let activity = get_agent_activity(&dht, &alice, &(0..10));
+        assert_eq!(1, activity.headers.len());
+        assert_eq!(Some(0), activity.highest);
+        assert_eq!(vec![headers[2].signed_hash()], activity.unlinked);
+
+        let dht = hold(dht, &alice, &headers[1..2]);
+        let activity = get_agent_activity(&dht, &alice, &(0..10));
+        assert_eq!(3, activity.headers.len());
+        assert!(activity.unlinked.is_empty());
+    }
+
+    #[test]
+    /// two headers committed on top of the same one fork the chain
+    fn forks() {
+        let alice = test_agent_address("alice");
+        let headers = test_signed_activity("alice", 2);
+        let other = Header::link(
+            &Entry::new("post", "something else"),
+            Some(headers[0].signed_hash()),
+            None,
+        ).sign(&test_signing_keys("alice"));
+        let dht = hold(test_dht_state(), &alice, &headers);
+        let dht = hold(dht, &alice, &[other]);
+        let activity = get_agent_activity(&dht, &alice, &(0..10));
+        assert_eq!(vec![1], activity.forks);
+        assert_eq!(3, activity.headers.len());
+        assert_eq!(Some(1), activity.highest);
+    }
+
+    #[test]
+    /// a header countersigned after it was held isn't a fork of it
+    fn countersigned() {
+        let alice = test_agent_address("alice");
+        let header = test_activity_headers(1)[0].sign(&test_signing_keys("alice"));
+        let countersigned = header.sign(&test_signing_keys("bob"));
+        let dht = hold(test_dht_state(), &alice, &[header.clone(), countersigned]);
+        let activity = get_agent_activity(&dht, &alice, &(0..10));
+        assert_eq!(1, activity.headers.len());
+        assert_eq!(header.signed_hash(), activity.headers[0].hash);
+        assert!(activity.forks.is_empty());
+    }
+
+    #[test]
+    /// headers signed by other agents aren't held as the activity of the agent
+    fn signed_by_others() {
+        let (alice, bob) = (test_agent_address("alice"), test_agent_address("bob"));
+        let headers = vec![test_activity_headers(1)[0].sign(&test_signing_keys("bob"))];
+        let dht = hold(test_dht_state(), &alice, &headers);
+        assert!(get_agent_activity(&dht, &alice, &(0..10)).headers.is_empty());
+        let dht = hold(dht, &bob, &headers);
+        assert_eq!(1, get_agent_activity(&dht, &bob, &(0..10)).headers.len());
+    }
+}
@@ -0,0 +1,211 @@
+//! what the DHT holds about an address comes in aspects attached to it: the entry itself, the
+//! headers it was committed under, links from it and what became of it
+//! aspects are held, hashed and gossiped one by one, so e.g. links from an address or its
+//! deletion can spread before its content arrives
+
+use agent::{
+    checkpoints::Checkpoint, devices::{self, Devices},
+};
+use dht::links::LinkMeta;
+use hash::serializable_to_b58_hash;
+use hash_table::{entry::Entry, header::Header};
+use multihash::Hash;
+use validation::{links::Link, warrants::Warrant};
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Aspect {
+    /// the entry at the address
+    Content(Entry),
+    /// a header the entry at the address was committed under
+    Header(Header),
+    /// a link from the address, with the address of its link entry
+    LinkAdd(String, Link, LinkMeta),
+    /// the link with the given link entry address removed from the address
+    /// held links stay removed when the LinkAdd arrives after it
+    LinkRemove(String),
+    /// the entry at the address replaced by the entry at the given address
+    Update(String),
+    /// the entry at the address deleted
+    Delete,
+    /// a header the agent at the address committed, held by its neighborhood, see activity
+    Activity(Header),
+    /// evidence that the agent at the address forked its chain, see validation::warrants
+    Warrant(Warrant),
+    /// the head of the chain of the agent at the address as it attested it, see
+    /// agent::checkpoints
+    Checkpoint(Checkpoint),
+    /// a device binding or revocation entry the root key at the address signed, held so its
+    /// activity is only taken signed by keys it trusts, see agent::devices
+    Device(Entry),
+}
+
+/// the device keys bound and revoked by the Device aspects among the aspects
+pub fn devices<'a, I: IntoIterator<Item = &'a Aspect>>(aspects: I) -> Devices {
+    let mut devices = Devices::default();
+    for aspect in aspects {
+        if let Aspect::Device(ref entry) = *aspect {
+            devices.apply(entry);
+        }
+    }
+    devices
+}
+
+impl Aspect {
+    /// hash of the aspect attached to base, what nodes gossiping compare
+    pub fn address(&self, base: &str) -> String {
+        serializable_to_b58_hash((base, self), Hash::SHA2256)
+    }
+
+    /// true if the aspect belongs at base, e.g. content at the address of the entry
+    /// headers belong nowhere unless they are signed and every provenance verifies against its
+    /// source, activity nowhere but at the agent it is signed by, see belongs_with()
+    pub fn belongs_at(&self, base: &str) -> bool {
+        self.belongs_with(base, &Devices::default())
+    }
+
+    /// belongs_at() knowing the device keys the agent at base bound and revoked, e.g. from the
+    /// Device aspects held there, activity signed by a key the agent trusts belongs at it
+    pub fn belongs_with(&self, base: &str, devices: &Devices) -> bool {
+        match *self {
+            Aspect::Content(ref entry) => entry.key() == base,
+            // forged provenances would let anyone claim an agent committed the entry
+            Aspect::Header(ref header) => header.entry() == base && header.verify_provenances(),
+            Aspect::LinkAdd(_, ref link, _) => link.base == base,
+            Aspect::LinkRemove(_) | Aspect::Update(_) | Aspect::Delete => true,
+            Aspect::Activity(ref header) => devices.verify_header(base, header),
+            Aspect::Warrant(ref warrant) => warrant.agent == base && warrant.is_valid(),
+            Aspect::Checkpoint(ref checkpoint) => {
+                checkpoint.agent == base && checkpoint.is_signed()
+            }
+            Aspect::Device(ref entry) => devices::root_of(entry).as_deref() == Some(base),
+        }
+    }
+
+    /// bytes of entry content held for the aspect, what limits::Resource::DhtBytes counts
+    pub fn held_bytes(&self) -> u64 {
+        match *self {
+            Aspect::Content(ref entry) => entry.content().len() as u64,
+            _ => 0,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{devices, Aspect};
+    use agent::{
+        devices::{DeviceBinding, DeviceRevocation, DEVICE_SIGNING_KEY_PATH, ROOT_KEY_PATH},
+        keystore::Keystore,
+    };
+    use dht::links::LinkMeta;
+    use hash_table::{
+        entry::tests::{test_entry_a, test_entry_b}, header::tests::test_header,
+        provenance::{
+            tests::{test_agent_address, test_signing_keys}, Provenance,
+        },
+    };
+    use validation::links::Link;
+
+    #[test]
+    /// aspects hash differently depending on what they are and where
+    fn address() {
+        let base = test_entry_a().key();
+        let content = Aspect::Content(test_entry_a());
+        assert_eq!(content.address(&base), content.address(&base));
+        assert_ne!(content.address(&base), Aspect::Delete.address(&base));
+        assert_ne!(
+            Aspect::Delete.address(&base),
+            Aspect::Delete.address(&test_entry_b().key())
+        );
+    }
+
+    #[test]
+    /// content, headers and links belong at the address they are about, activity at its author
+    fn belongs_at() {
+        let base = test_entry_a().key();
+        assert!(Aspect::Content(test_entry_a()).belongs_at(&base));
+        assert!(!Aspect::Content(test_entry_b()).belongs_at(&base));
+
+        let header = test_header().sign(&test_signing_keys("alice"));
+        assert!(Aspect::Header(header.clone()).belongs_at(header.entry()));
+        assert!(!Aspect::Header(header).belongs_at("elsewhere"));
+
+        let link = Link::new(&base, &test_entry_b().key(), "tag");
+        let link_add = Aspect::LinkAdd(link.to_entry().key(), link, LinkMeta::default());
+        assert!(link_add.belongs_at(&base));
+        assert!(!link_add.belongs_at(&test_entry_b().key()));
+        assert!(Aspect::Delete.belongs_at(&base));
+
+        let alice = test_agent_address("alice");
+        let signed = test_header().sign(&test_signing_keys("alice"));
+        assert!(Aspect::Header(signed.clone()).belongs_at(signed.entry()));
+        assert!(Aspect::Activity(signed.clone()).belongs_at(&alice));
+        assert!(!Aspect::Activity(signed).belongs_at(&test_agent_address("bob")));
+
+        // unsigned headers are nobody's
+        let unsigned = test_header();
+        assert!(!Aspect::Header(unsigned.clone()).belongs_at(unsigned.entry()));
+        assert!(!Aspect::Activity(unsigned).belongs_at(&alice));
+    }
+
+    #[test]
+    /// activity signed by a device key belongs at the root key that bound it, until it revokes it
+    fn device_activity() {
+        let root = Keystore::default();
+        let agent = root.signing_key(ROOT_KEY_PATH).unwrap().to_base58();
+        let phone = root.device_keystore("phone").unwrap();
+        let phone_key = phone.signing_key(DEVICE_SIGNING_KEY_PATH).unwrap();
+        let binding = DeviceBinding::new(&root, "phone", &phone_key).unwrap().to_entry();
+        let revocation = DeviceRevocation::new(&root, &phone_key).unwrap().to_entry();
+        assert!(Aspect::Device(binding.clone()).belongs_at(&agent));
+        assert!(!Aspect::Device(binding.clone()).belongs_at(&test_agent_address("alice")));
+        assert!(!Aspect::Device(test_entry_a()).belongs_at(&agent));
+
+        let activity = Aspect::Activity(
+            phone
+                .sign_header(DEVICE_SIGNING_KEY_PATH, &test_header())
+                .unwrap(),
+        );
+        assert!(!activity.belongs_at(&agent));
+        let bound = [Aspect::Device(binding)];
+        assert!(activity.belongs_with(&agent, &devices(&bound)));
+        let revoked = [bound[0].clone(), Aspect::Device(revocation)];
+        assert!(!activity.belongs_with(&agent, &devices(&revoked)));
+    }
+
+    #[test]
+    /// headers with a provenance that doesn't verify against its source are held nowhere
+    fn forged_provenance() {
+        let alice = test_agent_address("alice");
+        let header = test_header();
+        // bob signing in alice's name
+        let bobs = header.sign(&test_signing_keys("bob")).provenances()[0].clone();
+        let forged = header.with_provenance(Provenance::new(&alice, bobs.signature()));
+        assert!(!Aspect::Header(forged.clone()).belongs_at(forged.entry()));
+        assert!(!Aspect::Activity(forged).belongs_at(&alice));
+
+        let unsigned = header.with_provenance(Provenance::new(&alice, ""));
+        assert!(!Aspect::Activity(unsigned).belongs_at(&alice));
+
+        // a valid signature tampered with after a countersigner signed
+        let countersigned = header
+            .sign(&test_signing_keys("alice"))
+            .sign(&test_signing_keys("carol"));
+        let mut provenances = countersigned.provenances().to_vec();
+        provenances[1] = Provenance::new(provenances[1].source(), bobs.signature());
+        let tampered = provenances
+            .into_iter()
+            .fold(header.clone(), |h, provenance| h.with_provenance(provenance));
+        assert!(!Aspect::Header(tampered).belongs_at(header.entry()));
+    }
+
+    #[test]
+    /// only content counts towards the bytes held
+    fn held_bytes() {
+        assert_eq!(
+            test_entry_a().content().len() as u64,
+            Aspect::Content(test_entry_a()).held_bytes()
+        );
+        assert_eq!(0, Aspect::Delete.held_bytes());
+    }
+}
@@ -0,0 +1,352 @@
+//! getting entries from the DHT by address, either just the entry or everything the held aspects
+//! tell about it so apps can tell a deleted entry from one that never existed
+
+use dht::{aspect::Aspect, read, DhtState};
+use hash_table::{
+    entry::{Entry, EntryMeta}, header::Header, status::EntryStatus,
+};
+use std::collections::HashSet;
+
+/// which entries get_entry returns by their status
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum StatusRequest {
+    /// entries that weren't deleted, updated ones are followed to the latest update
+    #[default]
+    #[serde(rename = "live")]
+    Live,
+    /// deleted entries only
+    #[serde(rename = "deleted")]
+    Deleted,
+    /// entries whatever their status, updates aren't followed
+    #[serde(rename = "all")]
+    All,
+}
+
+impl StatusRequest {
+    fn matches(self, status: EntryStatus) -> bool {
+        match self {
+            StatusRequest::Live => status != EntryStatus::Deleted,
+            StatusRequest::Deleted => status == EntryStatus::Deleted,
+            StatusRequest::All => true,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum GetEntryResultType {
+    /// just the entry, null if there is none with a status asked for
+    #[default]
+    #[serde(rename = "masked")]
+    Masked,
+    /// the entry, if its status is asked for, along with its status, headers and updates
+    #[serde(rename = "details")]
+    Details,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetEntryOptions {
+    #[serde(default)]
+    pub status_request: StatusRequest,
+    #[serde(default)]
+    pub result_type: GetEntryResultType,
+    /// read the entries authored by these agents as if they weren't held, e.g. the ones blocked,
+    /// see agent::blocks
+    #[serde(default)]
+    pub excluded_authors: Vec<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EntryDetails {
+    /// None if the content isn't held or its status wasn't asked for
+    pub entry: Option<Entry>,
+    /// None if nothing is held of the address, as far as this node knows it never existed
+    pub status: Option<EntryStatus>,
+    /// the headers the entry was committed under
+    pub headers: Vec<Header>,
+    /// addresses of the entries it was updated to, sorted
+    pub updated_to: Vec<String>,
+}
+
+/// serializes as the entry or null for GetEntryResultType::Masked and as EntryDetails otherwise
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum GetEntryResult {
+    Masked(Option<Entry>),
+    Details(EntryDetails),
+}
+
+fn status(dht: &DhtState, address: &str) -> Option<EntryStatus> {
+    EntryStatus::from_crud_status(dht.crud_status(address))
+}
+
+fn updated_to(dht: &DhtState, address: &str) -> Vec<String> {
+    let mut updated_to: Vec<String> = dht
+        .aspects(address)
+        .into_iter()
+        .filter_map(|aspect| match aspect {
+            Aspect::Update(address) => Some(address),
+            _ => None,
+        })
+        .collect();
+    updated_to.sort();
+    updated_to
+}
+
+/// the latest entry following updates from address, the first one where there are several
+/// None if an entry on the way is deleted, isn't held or updates loop
+fn latest(dht: &DhtState, address: &str) -> Option<Entry> {
+    let mut address = address.to_string();
+    let mut seen = HashSet::new();
+    loop {
+        if !seen.insert(address.clone()) {
+            return None;
+        }
+        match status(dht, &address)? {
+            EntryStatus::Deleted => return None,
+            EntryStatus::Modified => match updated_to(dht, &address).into_iter().next() {
+                Some(update) => address = update,
+                None => return None,
+            },
+            EntryStatus::Live => return dht.holding(&address),
+        }
+    }
+}
+
+/// the entry at address as asked for by the options
+pub fn get_entry(dht: &DhtState, address: &str, options: &GetEntryOptions) -> GetEntryResult {
+    let excluded = read::author(dht, address)
+        .map(|author| options.excluded_authors.contains(&author))
+        .unwrap_or(false);
+    if excluded {
+        return match options.result_type {
+            GetEntryResultType::Masked => GetEntryResult::Masked(None),
+            GetEntryResultType::Details => GetEntryResult::Details(EntryDetails::default()),
+        };
+    }
+    let status = status(dht, address);
+    let asked_for = status
+        .map(|status| options.status_request.matches(status))
+        .unwrap_or(false);
+    match options.result_type {
+        GetEntryResultType::Masked => GetEntryResult::Masked(match options.status_request {
+            StatusRequest::Live => latest(dht, address),
+            _ if asked_for => dht.holding(address),
+            _ => None,
+        }),
+        GetEntryResultType::Details => GetEntryResult::Details(EntryDetails {
+            entry: if asked_for {
+                dht.holding(address)
+            } else {
+                None
+            },
+            status,
+            headers: dht.headers(address),
+            updated_to: updated_to(dht, address),
+        }),
+    }
+}
+
+/// the metadata of the entry at address, None unless its content is held
+pub fn get_entry_meta(dht: &DhtState, address: &str) -> Option<EntryMeta> {
+    let entry = dht.holding(address)?;
+    Some(EntryMeta::new(&entry, &dht.headers(address), status(dht, address)))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::{
+        entry::tests::{test_entry, test_entry_a, test_entry_b}, header::tests::test_header,
+        provenance::tests::{test_agent_address, test_signing_keys},
+    };
+    use serde_json;
+
+    fn options(status_request: StatusRequest, result_type: GetEntryResultType) -> GetEntryOptions {
+        GetEntryOptions {
+            status_request,
+            result_type,
+            ..GetEntryOptions::default()
+        }
+    }
+
+    fn masked(status_request: StatusRequest) -> GetEntryOptions {
+        options(status_request, GetEntryResultType::Masked)
+    }
+
+    fn hold(dht: DhtState, address: &str, aspect: Aspect) -> DhtState {
+        test_reduce(dht, Action::HoldAspect(address.to_string(), aspect))
+    }
+
+    #[test]
+    /// live entries are returned unless deleted, updated ones are followed to the latest update
+    fn get_masked() {
+        let a = test_entry_a().key();
+        let b = test_entry_b().key();
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry_a()));
+        dht = test_reduce(dht, Action::Hold(test_entry_b()));
+        let get =
+            |dht: &DhtState, address: &str, request| get_entry(dht, address, &masked(request));
+
+        assert_eq!(
+            GetEntryResult::Masked(Some(test_entry_a())),
+            get(&dht, &a, StatusRequest::Live)
+        );
+        assert_eq!(GetEntryResult::Masked(None), get(&dht, &a, StatusRequest::Deleted));
+        assert_eq!(GetEntryResult::Masked(None), get(&dht, "nowhere", StatusRequest::All));
+
+        dht = hold(dht, &a, Aspect::Update(b.clone()));
+        assert_eq!(
+            GetEntryResult::Masked(Some(test_entry_b())),
+            get(&dht, &a, StatusRequest::Live)
+        );
+        assert_eq!(
+            GetEntryResult::Masked(Some(test_entry_a())),
+            get(&dht, &a, StatusRequest::All)
+        );
+
+        dht = hold(dht, &b, Aspect::Delete);
+        assert_eq!(GetEntryResult::Masked(None), get(&dht, &a, StatusRequest::Live));
+        assert_eq!(
+            GetEntryResult::Masked(Some(test_entry_b())),
+            get(&dht, &b, StatusRequest::Deleted)
+        );
+
+        // updates going round in circles lead nowhere
+        dht = test_reduce(dht, Action::Drop(b.clone()));
+        dht = test_reduce(dht, Action::Hold(test_entry_b()));
+        dht = hold(dht, &b, Aspect::Update(a.clone()));
+        assert_eq!(GetEntryResult::Masked(None), get(&dht, &a, StatusRequest::Live));
+    }
+
+    #[test]
+    /// details tell deleted entries from ones that never existed
+    fn get_details() {
+        let header = test_header().sign(&test_signing_keys("alice"));
+        let address = test_entry().key();
+        let details = |dht: &DhtState, address: &str| {
+            get_entry(
+                dht,
+                address,
+                &options(StatusRequest::Live, GetEntryResultType::Details),
+            )
+        };
+        assert_eq!(
+            GetEntryResult::Details(EntryDetails::default()),
+            details(&test_dht_state(), &address)
+        );
+
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        dht = hold(dht, &address, Aspect::Header(header.clone()));
+        dht = hold(dht, &address, Aspect::Update(test_entry_b().key()));
+        dht = hold(dht, &address, Aspect::Delete);
+        assert_eq!(
+            GetEntryResult::Details(EntryDetails {
+                entry: None,
+                status: Some(EntryStatus::Deleted),
+                headers: vec![header],
+                updated_to: vec![test_entry_b().key()],
+            }),
+            details(&dht, &address)
+        );
+    }
+
+    #[test]
+    /// metadata tells the type, size, author and status of a held entry
+    fn entry_meta() {
+        let address = test_entry().key();
+        assert_eq!(None, get_entry_meta(&test_dht_state(), &address));
+
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        let meta = EntryMeta {
+            entry_type: test_entry().entry_type().to_string(),
+            size: test_entry().content().len() as u64,
+            author: None,
+            time: None,
+            status: Some(EntryStatus::Live),
+        };
+        assert_eq!(Some(meta.clone()), get_entry_meta(&dht, &address));
+
+        let header = test_header();
+        let by_alice = header.sign(&test_signing_keys("alice"));
+        dht = hold(dht, &address, Aspect::Header(header.clone()));
+        dht = hold(dht, &address, Aspect::Header(by_alice));
+        dht = hold(dht, &address, Aspect::Delete);
+        assert_eq!(
+            Some(EntryMeta {
+                author: Some(test_agent_address("alice")),
+                time: Some(header.time().to_string()),
+                status: Some(EntryStatus::Deleted),
+                ..meta
+            }),
+            get_entry_meta(&dht, &address)
+        );
+
+        // headers alone don't tell the size
+        let dht = hold(test_dht_state(), &address, Aspect::Header(test_header()));
+        assert_eq!(None, get_entry_meta(&dht, &address));
+    }
+
+    #[test]
+    /// entries of excluded authors read as if they weren't held
+    fn excluded_authors() {
+        let address = test_entry().key();
+        let by_bob = test_header().sign(&test_signing_keys("bob"));
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        dht = hold(dht, &address, Aspect::Header(by_bob));
+        let excluding = |authors: &[&str], result_type| {
+            let options = GetEntryOptions {
+                result_type,
+                excluded_authors: authors.iter().map(|author| author.to_string()).collect(),
+                ..GetEntryOptions::default()
+            };
+            get_entry(&dht, &address, &options)
+        };
+
+        assert_eq!(
+            GetEntryResult::Masked(Some(test_entry())),
+            excluding(&[&test_agent_address("carol")], GetEntryResultType::Masked)
+        );
+        let bob = test_agent_address("bob");
+        assert_eq!(
+            GetEntryResult::Masked(None),
+            excluding(&[&bob], GetEntryResultType::Masked)
+        );
+        assert_eq!(
+            GetEntryResult::Details(EntryDetails::default()),
+            excluding(&[&bob], GetEntryResultType::Details)
+        );
+    }
+
+    #[test]
+    /// options default to the live masked entry, results serialize as the entry or the details
+    fn json() {
+        assert_eq!(
+            GetEntryOptions::default(),
+            serde_json::from_str::<GetEntryOptions>("{}").unwrap()
+        );
+        assert_eq!(
+            options(StatusRequest::All, GetEntryResultType::Details),
+            serde_json::from_str(r#"{"status_request":"all","result_type":"details"}"#).unwrap()
+        );
+
+        assert_eq!(
+            "null",
+            serde_json::to_string(&GetEntryResult::Masked(None)).unwrap()
+        );
+        let entry = GetEntryResult::Masked(Some(test_entry_a()));
+        let json = serde_json::to_string(&entry).unwrap();
+        assert_eq!(serde_json::to_string(&test_entry_a()).unwrap(), json);
+        assert_eq!(entry, serde_json::from_str(&json).unwrap());
+
+        let details = GetEntryResult::Details(EntryDetails {
+            status: Some(EntryStatus::Live),
+            ..Default::default()
+        });
+        let json = serde_json::to_string(&details).unwrap();
+        assert!(json.contains(r#""status":"live""#));
+        assert_eq!(details, serde_json::from_str(&json).unwrap());
+    }
+}
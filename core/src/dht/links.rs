@@ -0,0 +1,331 @@
+//! the links the DHT holds, indexed by base with who added them and when so that get_links can
+//! return them a page at a time in a stable order
+//! pages are chained by cursors, a cursor points just after the last link of a page so links
+//! added while paging don't shift what the next page starts with
+
+use std::{cmp::Ordering, collections::BTreeMap};
+use validation::links::Link;
+
+/// page size of get_links when no limit is asked for
+pub const GET_LINKS_DEFAULT_LIMIT: usize = 100;
+/// the most links in a page, so a page fits the one the zome reads it from
+pub const GET_LINKS_MAX_LIMIT: usize = 250;
+
+/// who added a link and when, in unix seconds
+/// links held without it, e.g. gossiped by older nodes, have an empty author and timestamp 0
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkMeta {
+    pub author: String,
+    pub timestamp: u64,
+}
+
+/// a held link with its metadata
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LinkResult {
+    /// key of the link entry
+    pub address: String,
+    pub target: String,
+    pub tag: String,
+    pub author: String,
+    pub timestamp: u64,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum LinkOrder {
+    #[default]
+    #[serde(rename = "oldest_first")]
+    OldestFirst,
+    #[serde(rename = "newest_first")]
+    NewestFirst,
+}
+
+/// which links from a base get_links returns and how
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct GetLinksOptions {
+    /// only links with exactly this tag
+    #[serde(default)]
+    pub tag: Option<String>,
+    /// only links with a tag starting with this
+    #[serde(default)]
+    pub tag_prefix: Option<String>,
+    #[serde(default)]
+    pub order: LinkOrder,
+    /// max number of links in the page, GET_LINKS_DEFAULT_LIMIT if not set and
+    /// GET_LINKS_MAX_LIMIT at most
+    #[serde(default)]
+    pub limit: Option<usize>,
+    /// next from the previous page, None for the first page
+    #[serde(default)]
+    pub cursor: Option<String>,
+    /// leave out the links added by these agents, e.g. the ones blocked, see agent::blocks
+    #[serde(default)]
+    pub excluded_authors: Vec<String>,
+}
+
+impl GetLinksOptions {
+    fn matches(&self, link: &Link, meta: &LinkMeta) -> bool {
+        let tagged = match self.tag {
+            Some(ref wanted) => *wanted == link.tag,
+            None => true,
+        };
+        let prefixed = match self.tag_prefix {
+            Some(ref prefix) => link.tag.starts_with(prefix.as_str()),
+            None => true,
+        };
+        tagged && prefixed && !self.excluded_authors.contains(&meta.author)
+    }
+}
+
+/// a page of links
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LinkPage {
+    pub links: Vec<LinkResult>,
+    /// cursor for the next page, None if this is the last one
+    pub next: Option<String>,
+}
+
+/// position of a link in the order of its base, oldest first
+type LinkKey = (u64, String);
+
+fn cursor_to_string(key: &LinkKey) -> String {
+    format!("{}:{}", key.0, key.1)
+}
+
+fn cursor_from_string(cursor: &str) -> Option<LinkKey> {
+    let mut parts = cursor.splitn(2, ':');
+    let timestamp = parts.next()?.parse().ok()?;
+    let address = parts.next()?.to_string();
+    Some((timestamp, address))
+}
+
+/// the links from a single base
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LinkIndex {
+    links: BTreeMap<LinkKey, (Link, LinkMeta)>,
+}
+
+impl LinkIndex {
+    /// add a link held at address, replacing its metadata if it is there already
+    pub fn insert(&mut self, address: &str, link: &Link, meta: &LinkMeta) {
+        self.remove(address);
+        self.links.insert(
+            (meta.timestamp, address.to_string()),
+            (link.clone(), meta.clone()),
+        );
+    }
+
+    /// remove the link held at address, true if it was there
+    pub fn remove(&mut self, address: &str) -> bool {
+        let before = self.links.len();
+        self.links.retain(|key, _| key.1 != address);
+        self.links.len() != before
+    }
+
+    pub fn contains(&self, address: &str) -> bool {
+        self.links.keys().any(|key| key.1 == address)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.links.is_empty()
+    }
+
+    /// every link, oldest first
+    pub fn links(&self) -> Vec<Link> {
+        self.links.values().map(|(link, _)| link.clone()).collect()
+    }
+
+    /// a page of the links matching the options
+    /// a cursor that doesn't parse starts from the beginning
+    pub fn page(&self, options: &GetLinksOptions) -> LinkPage {
+        let limit = options
+            .limit
+            .unwrap_or(GET_LINKS_DEFAULT_LIMIT)
+            .min(GET_LINKS_MAX_LIMIT);
+        let cursor = options.cursor.as_ref().and_then(|c| cursor_from_string(c));
+        let after_cursor = |key: &LinkKey| match (options.order, cursor.as_ref()) {
+            (_, None) => true,
+            (LinkOrder::OldestFirst, Some(cursor)) => key.cmp(cursor) == Ordering::Greater,
+            (LinkOrder::NewestFirst, Some(cursor)) => key.cmp(cursor) == Ordering::Less,
+        };
+        let ordered: Box<dyn Iterator<Item = (&LinkKey, &(Link, LinkMeta))>> =
+            match options.order {
+                LinkOrder::OldestFirst => Box::new(self.links.iter()),
+                LinkOrder::NewestFirst => Box::new(self.links.iter().rev()),
+            };
+        let mut matching = ordered
+            .filter(|(key, (link, meta))| after_cursor(key) && options.matches(link, meta))
+            .take(limit + 1)
+            .collect::<Vec<_>>();
+
+        let next = if matching.len() > limit {
+            matching.truncate(limit);
+            matching.last().map(|(key, _)| cursor_to_string(key))
+        } else {
+            None
+        };
+        LinkPage {
+            links: matching
+                .into_iter()
+                .map(|((_, address), (link, meta))| LinkResult {
+                    address: address.clone(),
+                    target: link.target.clone(),
+                    tag: link.tag.clone(),
+                    author: meta.author.clone(),
+                    timestamp: meta.timestamp,
+                })
+                .collect(),
+            next,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// link from base to target_<n> tagged "tag_<n % 2>", added by alice at timestamp n
+    pub fn test_link(n: u64) -> (String, Link, LinkMeta) {
+        let link = Link::new("base", &format!("target_{}", n), &format!("tag_{}", n % 2));
+        let meta = LinkMeta {
+            author: "alice".to_string(),
+            timestamp: n,
+        };
+        (link.to_entry().key(), link, meta)
+    }
+
+    /// index of test_link(n) for n in 0..count, inserted newest first
+    pub fn test_link_index(count: u64) -> LinkIndex {
+        let mut index = LinkIndex::default();
+        for n in (0..count).rev() {
+            let (address, link, meta) = test_link(n);
+            index.insert(&address, &link, &meta);
+        }
+        index
+    }
+
+    fn targets(page: &LinkPage) -> Vec<String> {
+        page.links.iter().map(|link| link.target.clone()).collect()
+    }
+
+    #[test]
+    /// links are indexed by address and ordered by timestamp
+    fn index() {
+        let mut index = test_link_index(3);
+        let (address, link, meta) = test_link(1);
+        assert!(index.contains(&address));
+        assert_eq!(link, index.links()[1]);
+
+        let later = LinkMeta {
+            timestamp: 10,
+            ..meta
+        };
+        index.insert(&address, &link, &later);
+        assert_eq!(3, index.links().len());
+        assert_eq!(link, index.links()[2]);
+
+        assert!(index.remove(&address));
+        assert!(!index.remove(&address));
+        assert!(!index.contains(&address));
+        assert_eq!(2, index.links().len());
+    }
+
+    #[test]
+    /// pages follow each other in either order without gaps or repeats
+    fn pages() {
+        let index = test_link_index(5);
+        let mut options = GetLinksOptions {
+            limit: Some(2),
+            ..GetLinksOptions::default()
+        };
+
+        let first = index.page(&options);
+        assert_eq!(vec!["target_0", "target_1"], targets(&first));
+        assert_eq!("alice", first.links[0].author);
+        assert_eq!(0, first.links[0].timestamp);
+        options.cursor = first.next;
+        let second = index.page(&options);
+        assert_eq!(vec!["target_2", "target_3"], targets(&second));
+        options.cursor = second.next;
+        let last = index.page(&options);
+        assert_eq!(vec!["target_4"], targets(&last));
+        assert_eq!(None, last.next);
+
+        let newest = GetLinksOptions {
+            order: LinkOrder::NewestFirst,
+            limit: Some(3),
+            ..GetLinksOptions::default()
+        };
+        let first = index.page(&newest);
+        assert_eq!(vec!["target_4", "target_3", "target_2"], targets(&first));
+        let second = index.page(&GetLinksOptions {
+            cursor: first.next,
+            ..newest
+        });
+        assert_eq!(vec!["target_1", "target_0"], targets(&second));
+        assert_eq!(None, second.next);
+
+        assert_eq!(5, index.page(&GetLinksOptions::default()).links.len());
+    }
+
+    #[test]
+    /// a page has GET_LINKS_MAX_LIMIT links at most, however many are asked for
+    fn max_limit() {
+        let index = test_link_index(GET_LINKS_MAX_LIMIT as u64 + 1);
+        let page = index.page(&GetLinksOptions {
+            limit: Some(usize::MAX),
+            ..GetLinksOptions::default()
+        });
+        assert_eq!(GET_LINKS_MAX_LIMIT, page.links.len());
+        assert!(page.next.is_some());
+    }
+
+    #[test]
+    /// links can be filtered by exact tag or tag prefix
+    fn tags() {
+        let index = test_link_index(5);
+        let tagged = |tag: Option<&str>, tag_prefix: Option<&str>| {
+            targets(&index.page(&GetLinksOptions {
+                tag: tag.map(|t| t.to_string()),
+                tag_prefix: tag_prefix.map(|t| t.to_string()),
+                ..GetLinksOptions::default()
+            }))
+        };
+        assert_eq!(vec!["target_1", "target_3"], tagged(Some("tag_1"), None));
+        assert_eq!(5, tagged(None, Some("tag_")).len());
+        assert!(tagged(None, Some("other")).is_empty());
+        assert!(tagged(Some("tag"), None).is_empty());
+        assert_eq!(
+            vec!["target_0", "target_2", "target_4"],
+            tagged(Some("tag_0"), Some("tag"))
+        );
+    }
+
+    #[test]
+    /// links added by excluded authors are left out
+    fn excluded_authors() {
+        let mut index = test_link_index(3);
+        let (address, link, meta) = test_link(3);
+        let by_bob = LinkMeta {
+            author: "bob".to_string(),
+            ..meta
+        };
+        index.insert(&address, &link, &by_bob);
+        let excluding = |authors: &[&str]| {
+            targets(&index.page(&GetLinksOptions {
+                excluded_authors: authors.iter().map(|author| author.to_string()).collect(),
+                ..GetLinksOptions::default()
+            }))
+        };
+        assert_eq!(4, excluding(&[]).len());
+        assert_eq!(vec!["target_0", "target_1", "target_2"], excluding(&["bob"]));
+        assert_eq!(vec!["target_3"], excluding(&["alice", "carol"]));
+    }
+
+    #[test]
+    /// cursors are opaque but stable
+    fn cursor() {
+        let key = (12, "Qm:address".to_string());
+        assert_eq!(Some(key.clone()), cursor_from_string(&cursor_to_string(&key)));
+        assert_eq!(None, cursor_from_string("garbage"));
+    }
+}
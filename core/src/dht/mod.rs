@@ -0,0 +1,930 @@
+//! the dht module holds what this node stores on behalf of the network and what it knows about
+//! its peers
+//! what is held is held as aspects of addresses, see aspect
+
+pub mod activity;
+pub mod aspect;
+pub mod entries;
+pub mod links;
+pub mod read;
+pub mod retention;
+pub mod store;
+pub mod subscriptions;
+
+use dht::{
+    activity::AgentActivity, aspect::Aspect, entries::{GetEntryOptions, GetEntryResult},
+    links::{GetLinksOptions, LinkIndex, LinkMeta, LinkPage}, store::HoldingRecord,
+};
+use hash_table::{
+    entry::{Entry, EntryMeta}, header::Header, status::CRUDStatus,
+};
+use limits::{self, Resource};
+use nucleus::scheduler::unix_now;
+use sha2::{Digest, Sha256};
+use state;
+use std::{
+    collections::{BTreeMap, HashMap}, ops::Range, sync::{
+        mpsc::{channel, Sender}, Arc,
+    },
+    time::Instant,
+};
+use validation::{links::Link, warrants::Warrant};
+
+/// peers not heard from for this long are considered stale
+pub const DHT_PEER_STALE_SECS: u64 = 300;
+
+/// size of the location ring addresses are mapped onto
+const RING_SIZE: u64 = 1 << 32;
+
+/// the location of an address on the ring, derived from the hash of the address so locations are
+/// evenly spread regardless of how addresses are encoded
+pub fn location(address: &str) -> u32 {
+    let hash = Sha256::digest(address.as_bytes());
+    (u32::from(hash[0]) << 24) | (u32::from(hash[1]) << 16) | (u32::from(hash[2]) << 8)
+        | u32::from(hash[3])
+}
+
+/// the range of locations a node stores, everything within half_length of center either way
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StorageArc {
+    pub center: u32,
+    pub half_length: u32,
+}
+
+impl Default for StorageArc {
+    /// nodes hold everything until the network is big enough to share the load
+    fn default() -> Self {
+        StorageArc::full(0)
+    }
+}
+
+impl StorageArc {
+    pub fn new(center: u32, half_length: u32) -> StorageArc {
+        StorageArc {
+            center,
+            half_length: half_length.min(1 << 31),
+        }
+    }
+
+    /// an arc covering the whole ring
+    pub fn full(center: u32) -> StorageArc {
+        StorageArc::new(center, 1 << 31)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.half_length >= 1 << 31
+    }
+
+    /// true if the location falls in the arc
+    pub fn contains(&self, location: u32) -> bool {
+        let distance = location.wrapping_sub(self.center).min(self.center.wrapping_sub(location));
+        self.is_full() || distance <= self.half_length
+    }
+
+    /// the arc as [start, end) ranges that don't wrap around the ring
+    fn ranges(&self) -> Vec<(u64, u64)> {
+        if self.is_full() {
+            return vec![(0, RING_SIZE)];
+        }
+        let start = (u64::from(self.center) + RING_SIZE - u64::from(self.half_length)) % RING_SIZE;
+        let end = start + 2 * u64::from(self.half_length) + 1;
+        if end <= RING_SIZE {
+            vec![(start, end)]
+        } else {
+            vec![(start, RING_SIZE), (0, end - RING_SIZE)]
+        }
+    }
+}
+
+/// fraction of the ring covered by at least one of the arcs
+pub fn coverage(arcs: &[&StorageArc]) -> f64 {
+    let mut ranges = arcs
+        .iter()
+        .flat_map(|arc| arc.ranges())
+        .collect::<Vec<(u64, u64)>>();
+    ranges.sort();
+
+    let mut covered = 0;
+    let mut reached = 0;
+    for (start, end) in ranges {
+        if end > reached {
+            covered += end - start.max(reached);
+            reached = end;
+        }
+    }
+    covered as f64 / RING_SIZE as f64
+}
+
+/// what came of validating what is held of an address
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum HoldingValidation {
+    /// held as it was published, not validated (yet)
+    #[default]
+    #[serde(rename = "unvalidated")]
+    Unvalidated,
+    #[serde(rename = "valid")]
+    Valid,
+    /// with the reason it isn't
+    #[serde(rename = "invalid")]
+    Invalid(String),
+    /// valid when it was held but failing since the rules changed, with the reason, kept until
+    /// the rules change back, see validation::revalidation
+    #[serde(rename = "quarantined")]
+    Quarantined(String),
+}
+
+/// when and how an address came to be held
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Integration {
+    /// seconds since the unix epoch when the first aspect of the address was held
+    pub integrated_at: u64,
+    pub validation: HoldingValidation,
+}
+
+/// what this node knows about another node on the network
+#[derive(Clone, Debug, PartialEq)]
+pub struct Peer {
+    pub id: String,
+    pub arc: StorageArc,
+    pub last_seen: Instant,
+}
+
+#[derive(Clone, Debug, PartialEq, Default)]
+pub struct DhtState {
+    /// aspects held for the network by the address they are attached to, then by aspect address
+    aspects: HashMap<String, BTreeMap<String, Aspect>>,
+    /// the held links by base, indexed from the LinkAdd and LinkRemove aspects
+    links: HashMap<String, LinkIndex>,
+    /// when and how the held addresses came to be held
+    integrations: HashMap<String, Integration>,
+    peers: HashMap<String, Peer>,
+    arc: StorageArc,
+    /// bytes of held entry content
+    held_bytes: u64,
+    /// entries that would take held_bytes over this aren't held, see limits
+    max_held_bytes: Option<u64>,
+}
+
+impl DhtState {
+    /// builds a new, empty DhtState
+    pub fn new() -> DhtState {
+        DhtState::default()
+    }
+
+    /// the held aspects of an address
+    fn held(&self, address: &str) -> impl Iterator<Item = &Aspect> {
+        self.aspects.get(address).into_iter().flat_map(|aspects| aspects.values())
+    }
+
+    /// getter for a held entry, None if only other aspects of the address are held
+    pub fn holding(&self, address: &str) -> Option<Entry> {
+        self.held(address)
+            .filter_map(|aspect| match *aspect {
+                Aspect::Content(ref entry) => Some(entry.clone()),
+                _ => None,
+            })
+            .next()
+    }
+
+    /// addresses of the held entries, sorted
+    pub fn held_addresses(&self) -> Vec<String> {
+        let mut addresses: Vec<String> = self
+            .aspects
+            .keys()
+            .filter(|address| self.holding(address).is_some())
+            .cloned()
+            .collect();
+        addresses.sort();
+        addresses
+    }
+
+    /// copy of the aspects held of an address, ordered by aspect address
+    pub fn aspects(&self, address: &str) -> Vec<Aspect> {
+        self.held(address).cloned().collect()
+    }
+
+    /// addresses of the aspects held of an address, sorted, e.g. to gossip what is held
+    pub fn aspect_addresses(&self, address: &str) -> Vec<String> {
+        self.aspects
+            .get(address)
+            .map(|aspects| aspects.keys().cloned().collect())
+            .unwrap_or_default()
+    }
+
+    /// when and how the address came to be held, None if nothing of it is
+    pub fn integration(&self, address: &str) -> Option<Integration> {
+        self.integrations.get(address).cloned()
+    }
+
+    /// the headers the entry at address was committed under
+    pub fn headers(&self, address: &str) -> Vec<Header> {
+        self.held(address)
+            .filter_map(|aspect| match *aspect {
+                Aspect::Header(ref header) => Some(header.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the headers the agent at address committed, as far as they are held, see activity
+    pub fn activity(&self, address: &str) -> Vec<Header> {
+        self.held(address)
+            .filter_map(|aspect| match *aspect {
+                Aspect::Activity(ref header) => Some(header.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// the warrants held of the agent at address, see validation::warrants
+    pub fn warrants(&self, address: &str) -> Vec<Warrant> {
+        self.held(address)
+            .filter_map(|aspect| match *aspect {
+                Aspect::Warrant(ref warrant) => Some(warrant.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// what became of the entry at address as far as the aspects held tell
+    /// no flags are set if nothing is held of the address
+    pub fn crud_status(&self, address: &str) -> CRUDStatus {
+        self.held(address)
+            .fold(CRUDStatus::default(), |status, aspect| match *aspect {
+                Aspect::Content(_) => status | CRUDStatus::LIVE,
+                Aspect::Update(_) => status | CRUDStatus::MODIFIED,
+                Aspect::Delete => status | CRUDStatus::DELETED,
+                _ => status,
+            })
+    }
+
+    /// the entry at address, or what is known about it, as asked for by the options
+    pub fn get_entry(&self, address: &str, options: &GetEntryOptions) -> GetEntryResult {
+        entries::get_entry(self, address, options)
+    }
+
+    /// what is known about the entry at address short of its content, None unless it is held
+    pub fn get_entry_meta(&self, address: &str) -> Option<EntryMeta> {
+        entries::get_entry_meta(self, address)
+    }
+
+    /// the activity held of the agent, with the headers in the range of indices, see activity
+    pub fn get_agent_activity(&self, agent: &str, range: &Range<u64>) -> AgentActivity {
+        activity::get_agent_activity(self, agent, range)
+    }
+
+    /// the held links from the entry at base with the tag, oldest first
+    pub fn links_from(&self, base: &str, tag: &str) -> Vec<Link> {
+        self.links
+            .get(base)
+            .map(|index| index.links())
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|link| link.tag == tag)
+            .collect()
+    }
+
+    /// a page of the held links from the entry at base
+    pub fn get_links(&self, base: &str, options: &GetLinksOptions) -> LinkPage {
+        self.links
+            .get(base)
+            .map(|index| index.page(options))
+            .unwrap_or_default()
+    }
+
+    /// a copy of the state also holding aspects of address other nodes hold, to answer a read from
+    /// with replace what is held of address is dropped first, e.g. for the author's word on it
+    /// the copy isn't limited in what it holds
+    pub fn reconciled(&self, address: &str, aspects: Vec<Aspect>, replace: bool) -> DhtState {
+        let (sender, _receiver) = channel();
+        let mut reconciled = self.clone();
+        reconciled.max_held_bytes = None;
+        if replace {
+            reconciled.drop_address(address);
+        }
+        for aspect in aspects {
+            reconciled.hold(address, aspect, &sender);
+        }
+        reconciled
+    }
+
+    /// a copy of the state also holding entries committed but not published yet, e.g. what a zome
+    /// call committed so far, for it to read
+    /// the copy isn't limited in what it holds
+    pub fn with_pending(&self, entries: &[Entry]) -> DhtState {
+        let (sender, _receiver) = channel();
+        let mut pending = self.clone();
+        pending.max_held_bytes = None;
+        for entry in entries {
+            pending.hold_entry(entry, &sender);
+        }
+        pending
+    }
+
+    /// getter for a copy of the peers
+    pub fn peers(&self) -> Vec<Peer> {
+        self.peers.values().cloned().collect()
+    }
+
+    /// getter for a copy of the storage arc
+    pub fn arc(&self) -> StorageArc {
+        self.arc.clone()
+    }
+
+    /// true if aspects of the address published by others are for this node to validate and
+    /// hold, i.e. the address is in its arc or the aspects carry a header me authored
+    /// warrants are held wherever they arrive, see validation::warrants
+    pub fn should_hold(&self, address: &str, aspects: &[Aspect], me: &str) -> bool {
+        let for_me = |aspect: &Aspect| match *aspect {
+            Aspect::Header(ref header) => header
+                .provenances()
+                .first()
+                .map(|provenance| provenance.source() == me)
+                .unwrap_or(false),
+            Aspect::Warrant(_) => true,
+            _ => false,
+        };
+        self.arc.contains(location(address)) || aspects.iter().any(for_me)
+    }
+
+    /// bytes of entry content held
+    pub fn held_bytes(&self) -> u64 {
+        self.held_bytes
+    }
+
+    /// make room for holding the aspect, true if it fits within the limit
+    /// going over it is reported
+    fn reserve(&mut self, aspect: &Aspect, action_channel: &Sender<state::ActionWrapper>) -> bool {
+        if aspect.held_bytes() == 0 {
+            return true;
+        }
+        let requested = self.held_bytes + aspect.held_bytes();
+        match limits::check(Resource::DhtBytes, self.max_held_bytes, requested) {
+            Ok(()) => {
+                self.held_bytes = requested;
+                true
+            }
+            Err(exceeded) => {
+                ::instance::dispatch_action(
+                    action_channel,
+                    state::Action::Nucleus(::nucleus::Action::ReportLimitExceeded(exceeded)),
+                );
+                false
+            }
+        }
+    }
+
+    /// the address of the link entry of a LinkAdd held at base for it, if one is
+    fn link_add_address(&self, base: &str, link_address: &str) -> Option<String> {
+        self.aspects.get(base).and_then(|aspects| {
+            aspects
+                .iter()
+                .find(|(_, aspect)| match **aspect {
+                    Aspect::LinkAdd(ref address, _, _) => address == link_address,
+                    _ => false,
+                })
+                .map(|(aspect_address, _)| aspect_address.clone())
+        })
+    }
+
+    fn remove_aspect(&mut self, base: &str, aspect_address: &str) {
+        let mut emptied = false;
+        if let Some(aspects) = self.aspects.get_mut(base) {
+            aspects.remove(aspect_address);
+            emptied = aspects.is_empty();
+        }
+        if emptied {
+            self.aspects.remove(base);
+            self.integrations.remove(base);
+        }
+    }
+
+    fn unindex_link(&mut self, base: &str, link_address: &str) {
+        let mut emptied = false;
+        if let Some(index) = self.links.get_mut(base) {
+            index.remove(link_address);
+            emptied = index.is_empty();
+        }
+        if emptied {
+            self.links.remove(base);
+        }
+    }
+
+    /// hold the content of an entry, indexing it as a link from its base if it is a link entry
+    /// false if it doesn't fit within the holdings limit
+    fn hold_entry(
+        &mut self,
+        entry: &Entry,
+        action_channel: &Sender<state::ActionWrapper>,
+    ) -> bool {
+        let address = entry.key();
+        if !self.hold(&address, Aspect::Content(entry.clone()), action_channel) {
+            return false;
+        }
+        if let Some(link) = Link::from_entry(entry) {
+            if self.link_add_address(&link.base, &address).is_none() {
+                let base = link.base.clone();
+                let link_add = Aspect::LinkAdd(address, link, LinkMeta::default());
+                self.hold(&base, link_add, action_channel);
+            }
+        }
+        true
+    }
+
+    /// hold an aspect of base, false if it doesn't fit within the holdings limit
+    /// aspects that don't belong at base are ignored
+    fn hold(
+        &mut self,
+        base: &str,
+        aspect: Aspect,
+        action_channel: &Sender<state::ActionWrapper>,
+    ) -> bool {
+        let address = aspect.address(base);
+        let held = self
+            .aspects
+            .get(base)
+            .map(|aspects| aspects.contains_key(&address))
+            .unwrap_or(false);
+        // activity is taken signed by the keys the agent trusts as far as its devices are held
+        if held || !aspect.belongs_with(base, &aspect::devices(self.held(base))) {
+            return true;
+        }
+        if !self.reserve(&aspect, action_channel) {
+            return false;
+        }
+        match aspect {
+            Aspect::LinkAdd(ref link_address, ref link, ref meta) => {
+                // newer metadata replaces what was held with the link
+                if let Some(replaced) = self.link_add_address(base, link_address) {
+                    self.remove_aspect(base, &replaced);
+                }
+                let removed = self.held(base).any(|held| match *held {
+                    Aspect::LinkRemove(ref removed) => removed == link_address,
+                    _ => false,
+                });
+                if !removed {
+                    self.links
+                        .entry(base.to_string())
+                        .or_default()
+                        .insert(link_address, link, meta);
+                }
+            }
+            Aspect::LinkRemove(ref link_address) => self.unindex_link(base, link_address),
+            _ => (),
+        }
+        self.aspects
+            .entry(base.to_string())
+            .or_default()
+            .insert(address, aspect);
+        self.integrations
+            .entry(base.to_string())
+            .or_insert_with(|| Integration {
+                integrated_at: unix_now(),
+                validation: HoldingValidation::default(),
+            });
+        true
+    }
+
+    /// stop holding every aspect of the address, and the link it is the link entry of
+    fn drop_address(&mut self, address: &str) {
+        self.integrations.remove(address);
+        let dropped = self.aspects.remove(address).unwrap_or_default();
+        for aspect in dropped.values() {
+            self.held_bytes -= aspect.held_bytes();
+            if let Aspect::Content(ref entry) = *aspect {
+                if let Some(link) = Link::from_entry(entry) {
+                    if let Some(link_add) = self.link_add_address(&link.base, address) {
+                        self.remove_aspect(&link.base, &link_add);
+                    }
+                    self.unindex_link(&link.base, address);
+                }
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Action {
+    /// hold an entry for the network, i.e. the Content aspect of its address
+    /// link entries are indexed without metadata unless they are already
+    Hold(Entry),
+    /// hold a link for the network along with who added it when, i.e. the Content aspect of the
+    /// link entry and the LinkAdd aspect of the base
+    HoldLink(Link, LinkMeta),
+    /// hold an aspect of the address, e.g. gossiped ahead of the content
+    HoldAspect(String, Aspect),
+    /// stop holding every aspect of an address
+    Drop(String),
+    /// record what came of validating what is held of an address, if anything is
+    SetValidation(String, HoldingValidation),
+    /// hold what was kept in a holding store again, e.g. after a restart, see store
+    /// records are held as they were integrated, those over the holdings limit are left out
+    Restore(Vec<HoldingRecord>),
+    /// ask for the entry at an address, to be read from the state once the action is reduced
+    /// what is held is all there is until entries are fetched from the network
+    GetEntry(String),
+    /// ask for links from the base, to be read from the state once the action is reduced
+    /// the links held are all there is until links are fetched from the network
+    GetLinks(String),
+    /// ask for the activity of an agent, to be read from the state once the action is reduced
+    /// the headers held are all there is until they are fetched from the network
+    GetAgentActivity(String),
+    /// a peer was heard from, e.g. through gossip
+    PeerSeen(String, StorageArc),
+    SetArc(StorageArc),
+    /// limit the bytes of entry content held, None for no limit
+    /// entries already held are kept
+    SetHoldingsLimit(Option<u64>),
+}
+
+/// Reduce DHT state according to provided Action
+pub fn reduce(
+    old_state: Arc<DhtState>,
+    action: &state::Action,
+    action_channel: &Sender<state::ActionWrapper>,
+) -> Arc<DhtState> {
+    match *action {
+        state::Action::Dht(ref dht_action) => {
+            let mut new_state: DhtState = (*old_state).clone();
+            match *dht_action {
+                Action::Hold(ref entry) => {
+                    if !new_state.hold_entry(entry, action_channel) {
+                        return old_state;
+                    }
+                }
+                Action::HoldLink(ref link, ref meta) => {
+                    let entry = link.to_entry();
+                    let address = entry.key();
+                    if !new_state.hold(&address, Aspect::Content(entry), action_channel) {
+                        return old_state;
+                    }
+                    let link_add = Aspect::LinkAdd(address, link.clone(), meta.clone());
+                    new_state.hold(&link.base, link_add, action_channel);
+                }
+                Action::HoldAspect(ref address, ref aspect) => {
+                    if !new_state.hold(address, aspect.clone(), action_channel) {
+                        return old_state;
+                    }
+                }
+                Action::Drop(ref address) => new_state.drop_address(address),
+                Action::SetValidation(ref address, ref validation) => {
+                    match new_state.integrations.get_mut(address) {
+                        Some(integration) => integration.validation = validation.clone(),
+                        None => return old_state,
+                    }
+                }
+                Action::Restore(ref records) => {
+                    for record in records {
+                        let address = &record.address;
+                        let held = record
+                            .aspects
+                            .values()
+                            .all(|aspect| new_state.hold(address, aspect.clone(), action_channel));
+                        if held {
+                            new_state
+                                .integrations
+                                .insert(address.clone(), record.integration.clone());
+                        } else {
+                            new_state.drop_address(address);
+                        }
+                    }
+                }
+                Action::GetEntry(_) | Action::GetLinks(_) | Action::GetAgentActivity(_) => {}
+                Action::PeerSeen(ref id, ref arc) => {
+                    new_state.peers.insert(
+                        id.clone(),
+                        Peer {
+                            id: id.clone(),
+                            arc: arc.clone(),
+                            last_seen: Instant::now(),
+                        },
+                    );
+                }
+                Action::SetArc(ref arc) => {
+                    new_state.arc = arc.clone();
+                }
+                Action::SetHoldingsLimit(limit) => {
+                    new_state.max_held_bytes = limit;
+                }
+            }
+            Arc::new(new_state)
+        }
+        _ => old_state,
+    }
+}
+
+/// health data about the DHT from this node's point of view, e.g. for an operator dashboard
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DhtStats {
+    /// number of held entries by entry type
+    pub holdings_by_type: BTreeMap<String, usize>,
+    /// total bytes of held entry content
+    pub storage_bytes: usize,
+    /// estimated fraction of the address space held by this node and its known peers together
+    /// anything under 1.0 means some addresses may have nobody holding them
+    pub arc_coverage: f64,
+    pub peers: usize,
+    /// peers not heard from for DHT_PEER_STALE_SECS
+    pub stale_peers: usize,
+    /// seconds since the least recently heard from peer was seen
+    pub stalest_peer_secs: Option<u64>,
+}
+
+/// summarise the DHT state into DhtStats
+pub fn stats(state: &DhtState) -> DhtStats {
+    let mut holdings_by_type = BTreeMap::new();
+    let mut storage_bytes = 0;
+    let held = state.aspects.keys().filter_map(|address| state.holding(address));
+    for entry in held {
+        *holdings_by_type
+            .entry(entry.entry_type().to_string())
+            .or_insert(0) += 1;
+        storage_bytes += entry.content().len();
+    }
+
+    let mut arcs = vec![&state.arc];
+    arcs.extend(state.peers.values().map(|peer| &peer.arc));
+
+    let ages = state
+        .peers
+        .values()
+        .map(|peer| peer.last_seen.elapsed().as_secs())
+        .collect::<Vec<u64>>();
+
+    DhtStats {
+        holdings_by_type,
+        storage_bytes,
+        arc_coverage: coverage(&arcs),
+        peers: ages.len(),
+        stale_peers: ages
+            .iter()
+            .filter(|age| **age >= DHT_PEER_STALE_SECS)
+            .count(),
+        stalest_peer_secs: ages.iter().max().cloned(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{
+        aspect::Aspect, coverage, links::{tests::test_link, GetLinksOptions, LinkPage}, location,
+        reduce, stats, Action, DhtState, StorageArc,
+    };
+    use hash_table::{
+        entry::tests::{test_entry_a, test_entry_b, test_type_a, test_type_b},
+        header::tests::test_header, provenance::{tests::test_signing_keys, Provenance},
+        status::CRUDStatus,
+    };
+    use limits::Resource;
+    use nucleus::Action::ReportLimitExceeded;
+    use state;
+    use std::sync::{mpsc::channel, Arc};
+    use validation::links::Link;
+
+    /// builds a dummy dht state for testing
+    pub fn test_dht_state() -> DhtState {
+        DhtState::new()
+    }
+
+    /// reduce a dht action against a dht state
+    pub fn test_reduce(dht_state: DhtState, action: Action) -> DhtState {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        (*reduce(Arc::new(dht_state), &state::Action::Dht(action), &sender)).clone()
+    }
+
+    #[test]
+    /// locations are stable
+    fn location_stable() {
+        assert_eq!(location("foo"), location("foo"));
+        assert_ne!(location("foo"), location("bar"));
+    }
+
+    #[test]
+    /// arcs contain the locations within half_length of their center, wrapping around the ring
+    fn arc_contains() {
+        let arc = StorageArc::new(10, 5);
+        assert!(arc.contains(10));
+        assert!(arc.contains(5));
+        assert!(arc.contains(15));
+        assert!(!arc.contains(16));
+        assert!(!arc.contains(4));
+
+        let wrapping = StorageArc::new(2, 5);
+        assert!(wrapping.contains(u32::MAX));
+        assert!(!wrapping.contains(u32::MAX - 3));
+
+        assert!(StorageArc::default().contains(12345));
+    }
+
+    #[test]
+    /// only addresses in the arc are held, up to its boundaries, unless authored by this node
+    fn should_hold() {
+        let address = test_entry_a().key();
+        let here = location(&address);
+        let hold_in = |arc| test_reduce(test_dht_state(), Action::SetArc(arc));
+        let content = [Aspect::Content(test_entry_a())];
+        let authored = |agent| {
+            Aspect::Header(test_header().with_provenance(Provenance::new(agent, "signature")))
+        };
+
+        assert!(test_dht_state().should_hold(&address, &content, "me"));
+        let edge = hold_in(StorageArc::new(here.wrapping_add(100), 100));
+        assert!(edge.should_hold(&address, &content, "me"));
+        let beyond = hold_in(StorageArc::new(here.wrapping_sub(101), 100));
+        assert!(!beyond.should_hold(&address, &content, "me"));
+
+        assert!(beyond.should_hold(&address, &[authored("me")], "me"));
+        assert!(!beyond.should_hold(&address, &[authored("bob")], "me"));
+    }
+
+    #[test]
+    /// coverage is the union of arcs
+    fn arc_coverage() {
+        let eighth = 1 << 29;
+        let a = StorageArc::new(eighth, eighth);
+        let b = StorageArc::new(2 * eighth, eighth);
+        let c = StorageArc::new(0, eighth);
+
+        assert_eq!(0.0, coverage(&[]));
+        assert_eq!(1.0, coverage(&[&StorageArc::default()]));
+        // a alone is a quarter of the ring (plus its center)
+        assert!((coverage(&[&a]) - 0.25).abs() < 0.001);
+        // a and b overlap by half of a
+        assert!((coverage(&[&a, &b]) - 0.375).abs() < 0.001);
+        // c wraps around the ring and overlaps half of a
+        assert!((coverage(&[&a, &c]) - 0.375).abs() < 0.001);
+    }
+
+    #[test]
+    /// holdings and peers are tracked by the reducer
+    fn reduce_holdings() {
+        let state = test_reduce(test_dht_state(), Action::Hold(test_entry_a()));
+        let state = test_reduce(state, Action::Hold(test_entry_b()));
+        assert_eq!(Some(test_entry_a()), state.holding(&test_entry_a().key()));
+
+        let state = test_reduce(state, Action::Drop(test_entry_a().key()));
+        assert_eq!(None, state.holding(&test_entry_a().key()));
+        assert_eq!(vec![test_entry_b().key()], state.held_addresses());
+
+        let state = test_reduce(
+            state,
+            Action::PeerSeen("peer".to_string(), StorageArc::new(0, 1)),
+        );
+        assert_eq!(1, state.peers().len());
+
+        let state = test_reduce(state, Action::SetArc(StorageArc::new(0, 1)));
+        assert_eq!(StorageArc::new(0, 1), state.arc());
+    }
+
+    #[test]
+    /// links are found by base and tag
+    fn links_from() {
+        let link = Link::new(&test_entry_a().key(), &test_entry_b().key(), "tag");
+        let mut state = test_reduce(test_dht_state(), Action::Hold(test_entry_a()));
+        state = test_reduce(state, Action::Hold(link.to_entry()));
+
+        assert_eq!(vec![link.clone()], state.links_from(&link.base, "tag"));
+        assert!(state.links_from(&link.base, "other").is_empty());
+        assert!(state.links_from(&link.target, "tag").is_empty());
+
+        state = test_reduce(state, Action::Drop(link.to_entry().key()));
+        assert!(state.links_from(&link.base, "tag").is_empty());
+        assert_eq!(None, state.holding(&link.to_entry().key()));
+    }
+
+    #[test]
+    /// links are held with their metadata, which plain holds of the same link don't erase
+    fn hold_link() {
+        let (address, link, meta) = test_link(3);
+        let mut state = test_reduce(test_dht_state(), Action::HoldLink(link.clone(), meta));
+        state = test_reduce(state, Action::Hold(link.to_entry()));
+        assert_eq!(Some(link.to_entry()), state.holding(&address));
+
+        let page = state.get_links("base", &GetLinksOptions::default());
+        assert_eq!(1, page.links.len());
+        assert_eq!(address, page.links[0].address);
+        assert_eq!("alice", page.links[0].author);
+        assert_eq!(3, page.links[0].timestamp);
+        assert_eq!(LinkPage::default(), state.get_links("other", &GetLinksOptions::default()));
+    }
+
+    #[test]
+    /// aspects of an address are held before its content arrives and tell what became of it
+    fn hold_aspects() {
+        let header = test_header().sign(&test_signing_keys("alice"));
+        let address = header.entry().to_string();
+        let (link_address, link, meta) = test_link(3);
+        let link = Link::new(&address, &link.target, &link.tag);
+        let hold = |state, aspect| test_reduce(state, Action::HoldAspect(address.clone(), aspect));
+
+        let mut state = hold(test_dht_state(), Aspect::Header(header.clone()));
+        state = hold(state, Aspect::LinkAdd(link_address.clone(), link.clone(), meta));
+        assert_eq!(None, state.holding(&address));
+        assert!(state.held_addresses().is_empty());
+        assert_eq!(vec![header.clone()], state.headers(&address));
+        assert_eq!(vec![link.clone()], state.links_from(&address, &link.tag));
+        assert_eq!(CRUDStatus::default(), state.crud_status(&address));
+        assert_eq!(2, state.aspect_addresses(&address).len());
+
+        // aspects are held once, and only where they belong
+        state = hold(state, Aspect::Header(header.clone()));
+        state = hold(state, Aspect::Content(test_entry_b()));
+        assert_eq!(2, state.aspects(&address).len());
+
+        state = hold(state, Aspect::Update(test_entry_b().key()));
+        assert_eq!(CRUDStatus::MODIFIED, state.crud_status(&address));
+        state = hold(state, Aspect::Delete);
+        assert_eq!(
+            CRUDStatus::MODIFIED | CRUDStatus::DELETED,
+            state.crud_status(&address)
+        );
+
+        state = hold(state, Aspect::LinkRemove(link_address));
+        assert!(state.links_from(&address, &link.tag).is_empty());
+
+        state = test_reduce(state, Action::Drop(address.clone()));
+        assert!(state.aspects(&address).is_empty());
+        assert_eq!(CRUDStatus::default(), state.crud_status(&address));
+    }
+
+    #[test]
+    /// links removed before they are added stay removed
+    fn link_removed_first() {
+        let (address, link, meta) = test_link(3);
+        let remove = Action::HoldAspect(link.base.clone(), Aspect::LinkRemove(address.clone()));
+        let mut state = test_reduce(test_dht_state(), remove);
+        state = test_reduce(state, Action::HoldLink(link.clone(), meta));
+        assert!(state.links_from(&link.base, &link.tag).is_empty());
+        assert_eq!(Some(link.to_entry()), state.holding(&address));
+    }
+
+    #[test]
+    /// pending entries read as held in the copy, links indexed, the state itself is unchanged
+    fn pending_entries() {
+        let (address, link, _) = test_link(3);
+        let state = test_reduce(test_dht_state(), Action::SetHoldingsLimit(Some(1)));
+        let pending = state.with_pending(&[test_entry_a(), link.to_entry()]);
+        assert_eq!(Some(test_entry_a()), pending.holding(&test_entry_a().key()));
+        assert_eq!(Some(link.to_entry()), pending.holding(&address));
+        assert_eq!(1, pending.links_from(&link.base, &link.tag).len());
+        assert_eq!(None, state.holding(&test_entry_a().key()));
+        assert!(state.links_from(&link.base, &link.tag).is_empty());
+    }
+
+    #[test]
+    /// entries going over the holdings limit aren't held and the limit exceeded is reported
+    fn holdings_limit() {
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let apply = |dht_state, action| reduce(dht_state, &state::Action::Dht(action), &sender);
+        let limit = test_entry_b().content().len() as u64;
+
+        let state = apply(
+            Arc::new(test_dht_state()),
+            Action::SetHoldingsLimit(Some(limit)),
+        );
+        let state = apply(state, Action::Hold(test_entry_a()));
+        let state = apply(state, Action::Hold(test_entry_a()));
+        assert_eq!(test_entry_a().content().len() as u64, state.held_bytes());
+        assert!(receiver.try_recv().is_err());
+
+        let full = apply(state.clone(), Action::Hold(test_entry_b()));
+        assert_eq!(state, full);
+        assert_eq!(None, full.holding(&test_entry_b().key()));
+        match receiver.try_recv().unwrap().action {
+            state::Action::Nucleus(ReportLimitExceeded(exceeded)) => {
+                assert_eq!(Resource::DhtBytes, exceeded.resource);
+            }
+            other => panic!("unexpected action {:?}", other),
+        }
+
+        let state = apply(state, Action::Drop(test_entry_a().key()));
+        assert_eq!(0, state.held_bytes());
+        let state = apply(state, Action::Hold(test_entry_b()));
+        assert_eq!(Some(test_entry_b()), state.holding(&test_entry_b().key()));
+    }
+
+    #[test]
+    /// stats summarise the dht state
+    fn dht_stats() {
+        let mut state = test_dht_state();
+        state = test_reduce(state, Action::SetArc(StorageArc::new(0, 1 << 29)));
+        state = test_reduce(state, Action::Hold(test_entry_a()));
+        state = test_reduce(state, Action::Hold(test_entry_b()));
+        state = test_reduce(
+            state,
+            Action::PeerSeen("peer".to_string(), StorageArc::new(1 << 31, 1 << 29)),
+        );
+
+        let stats = stats(&state);
+        assert_eq!(Some(&1), stats.holdings_by_type.get(&test_type_a()));
+        assert_eq!(Some(&1), stats.holdings_by_type.get(&test_type_b()));
+        assert_eq!(
+            test_entry_a().content().len() + test_entry_b().content().len(),
+            stats.storage_bytes
+        );
+        assert!((stats.arc_coverage - 0.5).abs() < 0.001);
+        assert_eq!(1, stats.peers);
+        assert_eq!(0, stats.stale_peers);
+        assert_eq!(Some(0), stats.stalest_peer_secs);
+    }
+}
@@ -0,0 +1,427 @@
+//! how consistent DHT reads are: answered from what this node holds right away, reconciled with
+//! what a quorum of neighbors holding the address or the first of them to answer hold, or taken
+//! from the author of the entry
+//! other nodes are asked for the aspects they hold of the address read, see
+//! DirectMessenger::get_aspects(), aspects being content addressed reconciling is holding them all
+//! they are asked in parallel as configured by NetworkConfig::fan_out, answers that are the same
+//! as one already verified aren't verified again
+//! updates are followed as far as this node holds them
+
+use dht::{
+    aspect::{self, Aspect}, location, DhtState,
+};
+use hash::serializable_to_b58_hash;
+use multihash::Hash;
+use network::{config::FanOut, direct_message::DirectMessenger};
+use platform;
+use std::{
+    collections::HashMap, sync::mpsc::channel, time::{Duration, Instant},
+};
+
+/// which nodes a read is answered from
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum ReadConsistency {
+    /// what this node holds, without asking anyone
+    #[default]
+    #[serde(rename = "local")]
+    Local,
+    /// what this node and the given number of neighbors holding the address hold together
+    /// neighbors that don't answer are replaced by the next closest ones while there are any
+    #[serde(rename = "quorum")]
+    Quorum(usize),
+    /// what this node and the first of the nearest holders to answer hold together
+    #[serde(rename = "fastest")]
+    Fastest,
+    /// what the agent that signed the entry's first header holds, it committed the entry
+    /// falls back to what this node holds if the author isn't known or doesn't answer
+    #[serde(rename = "author")]
+    Author,
+}
+
+/// what asking other nodes for a read took
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadStats {
+    /// agents asked
+    pub asked: usize,
+    /// agents that couldn't be reached or didn't answer in time
+    pub failed: usize,
+    /// agents that answered they hold nothing of the address
+    pub empty: usize,
+    /// answers holding aspects that don't belong at the address
+    pub invalid: usize,
+    /// answers the same as one verified before, not verified again
+    pub duplicates: usize,
+    /// time from asking the first agent to the last answer waited for
+    pub elapsed_ms: u64,
+}
+
+/// how a read was answered
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ReadMeta {
+    pub consistency: ReadConsistency,
+    /// the other agents that answered, in the order they did
+    /// this node's holdings count unless the author answered
+    pub responders: Vec<String>,
+    pub stats: ReadStats,
+}
+
+/// the result of a read along with how it was answered
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Read<T> {
+    pub result: T,
+    #[serde(flatten)]
+    pub meta: ReadMeta,
+}
+
+/// peers whose arc holds the address, those centered closest to it first
+pub fn neighbors(dht: &DhtState, address: &str) -> Vec<String> {
+    let location = location(address);
+    let mut peers: Vec<(u32, String)> = dht
+        .peers()
+        .into_iter()
+        .filter(|peer| peer.arc.contains(location))
+        .map(|peer| {
+            let center = peer.arc.center;
+            let distance = location.wrapping_sub(center).min(center.wrapping_sub(location));
+            (distance, peer.id)
+        })
+        .collect();
+    peers.sort();
+    peers.into_iter().map(|(_, id)| id).collect()
+}
+
+/// the agent that signed the first of the held headers of the entry at address, if any did
+pub fn author(dht: &DhtState, address: &str) -> Option<String> {
+    dht.headers(address)
+        .iter()
+        .filter_map(|header| header.provenances().first().map(|p| p.source().to_string()))
+        .next()
+}
+
+/// ask the candidates alpha at a time for the aspects they hold of address until wanted gave
+/// valid answers or k were asked, every agent that fails being replaced by the next one
+/// answers come in the order they arrived, the ones still on the way when enough are in are
+/// dropped
+fn fan_out(
+    messenger: &DirectMessenger,
+    address: &str,
+    candidates: Vec<String>,
+    wanted: usize,
+    fan_out: &FanOut,
+    timeout: Duration,
+) -> (Vec<(String, Vec<Aspect>)>, ReadStats) {
+    let started = Instant::now();
+    let mut stats = ReadStats::default();
+    let mut answers = Vec::new();
+    // whether answers were valid by hash of the answer
+    let mut verified: HashMap<String, bool> = HashMap::new();
+    let mut candidates = candidates.into_iter().take(fan_out.k);
+    let (sender, receiver) = channel();
+    let mut in_flight = 0;
+    while answers.len() < wanted {
+        while in_flight < fan_out.alpha.max(1) {
+            let agent = match candidates.next() {
+                Some(agent) => agent,
+                None => break,
+            };
+            let messenger = messenger.clone();
+            let address = address.to_string();
+            let sender = sender.clone();
+            platform::spawn("dht_get", move || {
+                let answer = messenger.get_aspects(&agent, &address, timeout);
+                // nobody listens anymore once enough answers are in
+                let _ = sender.send((agent, answer));
+            });
+            stats.asked += 1;
+            in_flight += 1;
+        }
+        if in_flight == 0 {
+            break;
+        }
+        let (agent, answer) = receiver.recv().expect("local channel to work");
+        in_flight -= 1;
+        let aspects = match answer {
+            Err(_) => {
+                stats.failed += 1;
+                continue;
+            }
+            Ok(ref aspects) if aspects.is_empty() => {
+                stats.empty += 1;
+                continue;
+            }
+            Ok(aspects) => aspects,
+        };
+        let hash = serializable_to_b58_hash(&aspects, Hash::SHA2256);
+        let valid = match verified.get(&hash) {
+            Some(&valid) => {
+                stats.duplicates += 1;
+                valid
+            }
+            None => {
+                let devices = aspect::devices(&aspects);
+                let valid = aspects
+                    .iter()
+                    .all(|aspect| aspect.belongs_with(address, &devices));
+                verified.insert(hash, valid);
+                valid
+            }
+        };
+        if valid {
+            answers.push((agent, aspects));
+        } else {
+            stats.invalid += 1;
+        }
+    }
+    let elapsed = started.elapsed();
+    stats.elapsed_ms = elapsed.as_secs() * 1000 + u64::from(elapsed.subsec_millis());
+    (answers, stats)
+}
+
+/// what is known of address as consistent as asked for, to get entries or links from, along with
+/// who answered
+/// other agents are asked as the messenger is configured to
+pub fn read(
+    dht: &DhtState,
+    messenger: &DirectMessenger,
+    address: &str,
+    consistency: &ReadConsistency,
+) -> (DhtState, ReadMeta) {
+    let me = messenger.address();
+    let (candidates, wanted) = match *consistency {
+        ReadConsistency::Local => (vec![], 0),
+        ReadConsistency::Quorum(count) => (neighbors(dht, address), count),
+        ReadConsistency::Fastest => (neighbors(dht, address), 1),
+        ReadConsistency::Author => (author(dht, address).into_iter().collect(), 1),
+    };
+    let candidates = candidates
+        .into_iter()
+        .filter(|agent| Some(agent) != me.as_ref())
+        .collect();
+
+    let config = messenger.config();
+    let (answers, stats) = fan_out(
+        messenger,
+        address,
+        candidates,
+        wanted,
+        &config.fan_out,
+        config.direct_message_timeout(),
+    );
+    let mut responders = Vec::new();
+    let mut aspects = Vec::new();
+    for (agent, held) in answers {
+        responders.push(agent);
+        aspects.extend(held);
+    }
+
+    let replace = *consistency == ReadConsistency::Author && !responders.is_empty();
+    let meta = ReadMeta {
+        consistency: consistency.clone(),
+        responders,
+        stats,
+    };
+    (dht.reconciled(address, aspects, replace), meta)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        aspect::Aspect, tests::{test_dht_state, test_reduce}, Action, StorageArc,
+    };
+    use hash_table::{
+        entry::tests::{test_entry, test_entry_b}, header::tests::test_header,
+        provenance::tests::{test_agent_address, test_signing_keys},
+    };
+    use network::{
+        config::{NetworkConfig, RetryPolicy},
+        direct_message::{tests::test_caller, DirectMessage, MemoryNetwork}, Envelope,
+    };
+    use serde_json;
+    use std::thread;
+
+    /// node connected under the given address answering aspect requests with what answer gives
+    fn test_answering<F>(network: &MemoryNetwork, address: &str, answer: F)
+    where
+        F: Fn(&str) -> Vec<Aspect> + Send + 'static,
+    {
+        let receiver = network.connect(address);
+        let network = network.clone();
+        thread::spawn(move || {
+            for envelope in receiver {
+                if let DirectMessage::GetAspects { id, from, address } = envelope.message {
+                    let answer = DirectMessage::GetAspectsResult(id, answer(&address));
+                    let _ = network.send(&from, Envelope::new(answer));
+                }
+            }
+        });
+    }
+
+    /// node connected under the given address answering aspect requests from the dht
+    fn test_holder(network: &MemoryNetwork, address: &str, dht: DhtState) {
+        test_answering(network, address, move |address| dht.aspects(address));
+    }
+
+    /// messenger connected as "me" giving up on agents that can't be reached right away
+    fn test_reader(network: &MemoryNetwork, fan_out: FanOut) -> DirectMessenger {
+        let messenger = test_caller(network, "me");
+        messenger.configure(&NetworkConfig {
+            direct_message_timeout_ms: 1000,
+            retry: RetryPolicy::never(),
+            fan_out,
+            ..NetworkConfig::default()
+        });
+        messenger
+    }
+
+    fn seen(dht: DhtState, id: &str, arc: StorageArc) -> DhtState {
+        test_reduce(dht, Action::PeerSeen(id.to_string(), arc))
+    }
+
+    /// header of test_entry signed by the named test agent
+    fn signed_header(name: &str) -> Aspect {
+        Aspect::Header(test_header().sign(&test_signing_keys(name)))
+    }
+
+    #[test]
+    /// neighbors are the peers holding the address, closest first
+    /// they are who publishes of the address are routed to
+    fn neighbors_by_distance() {
+        let address = test_entry().key();
+        let here = location(&address);
+        let mut dht = seen(test_dht_state(), "far", StorageArc::new(here.wrapping_add(90), 100));
+        dht = seen(dht, "near", StorageArc::new(here.wrapping_sub(10), 100));
+        dht = seen(dht, "elsewhere", StorageArc::new(here.wrapping_add(1000), 100));
+        // arcs hold their boundaries but nothing past them
+        dht = seen(dht, "edge", StorageArc::new(here.wrapping_add(100), 100));
+        dht = seen(dht, "past", StorageArc::new(here.wrapping_sub(101), 100));
+        assert_eq!(vec!["near", "far", "edge"], neighbors(&dht, &address));
+        assert!(neighbors(&test_dht_state(), &address).is_empty());
+    }
+
+    #[test]
+    /// the author is whoever signed the first header held
+    fn author_from_headers() {
+        let address = test_entry().key();
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        assert_eq!(None, author(&dht, &address));
+        dht = test_reduce(dht, Action::HoldAspect(address.clone(), signed_header("alice")));
+        assert_eq!(Some(test_agent_address("alice")), author(&dht, &address));
+    }
+
+    #[test]
+    /// quorum reads add what neighbors hold, author reads take the author's word
+    fn read_consistency() {
+        let network = MemoryNetwork::new();
+        let messenger = test_reader(&network, FanOut::default());
+        let address = test_entry().key();
+
+        // bob and carol hold the entry, carol also holds its update, dave is gone
+        // bob is the author, so bob's node id is the address bob signs with
+        let bob = test_agent_address("bob");
+        let held = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        test_holder(&network, &bob, held.clone());
+        let updated = test_reduce(
+            held.clone(),
+            Action::HoldAspect(address.clone(), Aspect::Update(test_entry_b().key())),
+        );
+        test_holder(&network, "carol", updated);
+        let mut dht = seen(test_dht_state(), "dave", StorageArc::default());
+        dht = seen(dht, &bob, StorageArc::default());
+        dht = seen(dht, "carol", StorageArc::default());
+        dht = test_reduce(dht, Action::HoldAspect(address.clone(), signed_header("bob")));
+
+        let (local, meta) = read(&dht, &messenger, &address, &ReadConsistency::Local);
+        assert_eq!(dht, local);
+        assert!(meta.responders.is_empty());
+
+        let (quorum, meta) = read(&dht, &messenger, &address, &ReadConsistency::Quorum(2));
+        let mut responders = meta.responders.clone();
+        responders.sort();
+        let mut expected = vec![bob.clone(), "carol".to_string()];
+        expected.sort();
+        assert_eq!(expected, responders);
+        assert_eq!(Some(test_entry()), quorum.holding(&address));
+        assert!(quorum.aspects(&address).contains(&Aspect::Update(test_entry_b().key())));
+        assert_eq!(1, quorum.headers(&address).len());
+
+        assert_eq!(3, meta.stats.asked);
+        assert_eq!(1, meta.stats.failed);
+
+        // bob holds no headers, bob's word replaces the one held here
+        let (authored, meta) = read(&dht, &messenger, &address, &ReadConsistency::Author);
+        assert_eq!(vec![bob], meta.responders);
+        assert_eq!(held.aspects(&address), authored.aspects(&address));
+    }
+
+    #[test]
+    /// holders are asked alpha at a time, the first valid answer wins
+    fn fan_out_fastest() {
+        let network = MemoryNetwork::new();
+        let address = test_entry().key();
+        let held = test_reduce(test_dht_state(), Action::Hold(test_entry()));
+        let mut dht = test_dht_state();
+        // a is gone, b holds nothing, c answers with another entry, d and e hold the entry
+        for agent in &["a", "b", "c", "d", "e"] {
+            dht = seen(dht, agent, StorageArc::default());
+        }
+        test_answering(&network, "b", |_| vec![]);
+        test_answering(&network, "c", |_| vec![Aspect::Content(test_entry_b())]);
+        test_holder(&network, "d", held.clone());
+        test_holder(&network, "e", held.clone());
+
+        // one at a time, a to d are asked in turn
+        let one_at_a_time = FanOut { alpha: 1, k: 8 };
+        let messenger = test_reader(&network, one_at_a_time);
+        let (fastest, meta) = read(&dht, &messenger, &address, &ReadConsistency::Fastest);
+        assert_eq!(vec!["d".to_string()], meta.responders);
+        assert_eq!(Some(test_entry()), fastest.holding(&address));
+        assert_eq!(
+            ReadStats {
+                asked: 4,
+                failed: 1,
+                empty: 1,
+                invalid: 1,
+                duplicates: 0,
+                elapsed_ms: meta.stats.elapsed_ms,
+            },
+            meta.stats
+        );
+
+        // d and e answer the same, e's answer isn't verified again
+        let (_, meta) = read(&dht, &messenger, &address, &ReadConsistency::Quorum(2));
+        assert_eq!(vec!["d".to_string(), "e".to_string()], meta.responders);
+        assert_eq!(1, meta.stats.duplicates);
+
+        // all at once, but never more than k
+        let messenger = test_reader(&network, FanOut { alpha: 5, k: 3 });
+        let (nothing, meta) = read(&dht, &messenger, &address, &ReadConsistency::Fastest);
+        assert!(meta.responders.is_empty());
+        assert_eq!(3, meta.stats.asked);
+        assert_eq!(None, nothing.holding(&address));
+    }
+
+    #[test]
+    /// consistencies and read results serialize as the zome API takes and returns them
+    fn json() {
+        assert_eq!(
+            ReadConsistency::Quorum(3),
+            serde_json::from_str(r#"{"quorum":3}"#).unwrap()
+        );
+        assert_eq!(
+            ReadConsistency::Author,
+            serde_json::from_str(r#""author""#).unwrap()
+        );
+        assert_eq!(
+            ReadConsistency::Fastest,
+            serde_json::from_str(r#""fastest""#).unwrap()
+        );
+        let read = Read {
+            result: 1,
+            meta: ReadMeta::default(),
+        };
+        let json = serde_json::to_string(&read).unwrap();
+        assert!(json.starts_with(r#"{"result":1,"consistency":"local","responders":[],"stats":"#));
+        assert_eq!(read, serde_json::from_str(&json).unwrap());
+    }
+}
@@ -0,0 +1,280 @@
+//! retention rules say how long the nodes holding entries for the network keep them, as the
+//! entry types of the DNA declare, see holochain_dna::zome::entry_types::Retention
+//! every PRUNE_INTERVAL_SECS a holder checks what it holds against the rules and drops what is
+//! past them, logs what it pruned and tells the author, so the receipts authors count their
+//! holders by stay accurate, see validation::receipts
+//! entries kept while linked are kept until a link from them was removed and none are left, the
+//! links from an entry are held at its address so every holder of it comes to the same
+
+use dht::{aspect::Aspect, Action, DhtState};
+use holochain_dna::{zome::entry_types::Retention, Dna};
+use network::direct_message::DirectMessage;
+use nucleus::scheduler::unix_now;
+use state::{self, State};
+use std::{
+    collections::{BTreeMap, VecDeque}, fmt, sync::{mpsc::Sender, Arc, Mutex},
+};
+use validation::rates;
+
+/// how often holders check what they hold against the retention rules
+pub const PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// how many prunings the prune log keeps, the oldest go first
+pub const PRUNE_LOG_SIZE: usize = 1000;
+
+const DAY_SECS: u64 = 24 * 60 * 60;
+
+/// the retention rules the entry types of a DNA declare by entry type name, entry types held
+/// forever are left out
+pub type Rules = BTreeMap<String, Retention>;
+
+/// the retention rules declared in the DNA
+pub fn rules(dna: &Dna) -> Rules {
+    dna.zomes
+        .iter()
+        .flat_map(|zome| zome.entry_types.iter())
+        .filter(|entry_type| entry_type.retention != Retention::Forever)
+        .map(|entry_type| (entry_type.name.clone(), entry_type.retention.clone()))
+        .collect()
+}
+
+/// an address a holder stopped holding as the retention rule of its entry type says
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pruning {
+    pub address: String,
+    pub entry_type: String,
+    pub retention: Retention,
+    /// seconds since the unix epoch when it was pruned
+    pub pruned_at: u64,
+}
+
+/// true if a link from the entry at address was held and removed, and none are left
+fn unlinked(dht: &DhtState, address: &str) -> bool {
+    let removed = dht
+        .held(address)
+        .any(|aspect| matches!(*aspect, Aspect::LinkRemove(_)));
+    removed && !dht.links.contains_key(address)
+}
+
+/// the entries held past the retention rules at now, sorted by address
+pub fn expired(dht: &DhtState, rules: &Rules, now: u64) -> Vec<Pruning> {
+    let mut expired: Vec<Pruning> = dht
+        .integrations
+        .iter()
+        .filter_map(|(address, integration)| {
+            let entry = dht.holding(address)?;
+            let retention = rules.get(entry.entry_type())?;
+            let past = match *retention {
+                Retention::Forever => false,
+                Retention::Days(days) => {
+                    now.saturating_sub(integration.integrated_at) >= days.saturating_mul(DAY_SECS)
+                }
+                Retention::WhileLinked => unlinked(dht, address),
+            };
+            if !past {
+                return None;
+            }
+            Some(Pruning {
+                address: address.clone(),
+                entry_type: entry.entry_type().to_string(),
+                retention: retention.clone(),
+                pruned_at: now,
+            })
+        })
+        .collect();
+    expired.sort_by(|a, b| a.address.cmp(&b.address));
+    expired
+}
+
+#[derive(Default)]
+struct Log {
+    /// the latest prunings, oldest first
+    pruned: VecDeque<Pruning>,
+    /// seconds since the unix epoch when the holdings were last checked
+    checked: Option<u64>,
+}
+
+/// what an instance pruned lately
+/// the log is a cheap handle, clones share the same prunings
+#[derive(Clone, Default)]
+pub struct PruneLog {
+    log: Arc<Mutex<Log>>,
+}
+
+impl PartialEq for PruneLog {
+    fn eq(&self, other: &PruneLog) -> bool {
+        Arc::ptr_eq(&self.log, &other.log)
+    }
+}
+
+impl fmt::Debug for PruneLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let log = self.log.lock().unwrap();
+        f.debug_struct("PruneLog")
+            .field("pruned", &log.pruned.len())
+            .field("checked", &log.checked)
+            .finish()
+    }
+}
+
+impl PruneLog {
+    /// the last PRUNE_LOG_SIZE prunings, oldest first
+    pub fn pruned(&self) -> Vec<Pruning> {
+        self.log.lock().unwrap().pruned.iter().cloned().collect()
+    }
+
+    /// true if a check of the holdings is due at now
+    fn due(&self, now: u64) -> bool {
+        self.log
+            .lock()
+            .unwrap()
+            .checked
+            .map(|checked| now.saturating_sub(checked) >= PRUNE_INTERVAL_SECS)
+            .unwrap_or(true)
+    }
+
+    /// note the holdings were checked at now, pruning what is given
+    fn checked(&self, now: u64, pruned: &[Pruning]) {
+        let mut log = self.log.lock().unwrap();
+        log.checked = Some(now);
+        log.pruned.extend(pruned.iter().cloned());
+        while log.pruned.len() > PRUNE_LOG_SIZE {
+            log.pruned.pop_front();
+        }
+    }
+}
+
+/// drop what the instance with the state holds past the retention rules of its DNA if a check
+/// is due, logging it and telling the authors, returns what was pruned
+/// authors that can't be reached right away count the holder until they publish to it again
+pub fn prune(state: &State, action_channel: &Sender<state::ActionWrapper>) -> Vec<Pruning> {
+    let nucleus = state.nucleus();
+    let rules = match nucleus.dna() {
+        Some(ref dna) => rules(dna),
+        None => return Vec::new(),
+    };
+    let now = unix_now();
+    if rules.is_empty() || !nucleus.prune_log().due(now) {
+        return Vec::new();
+    }
+    let dht = state.dht();
+    let pruned = expired(&dht, &rules, now);
+    let messenger = nucleus.messenger();
+    let me = messenger.address();
+    for pruning in &pruned {
+        let author = rates::author(&dht.aspects(&pruning.address));
+        ::instance::dispatch_action(
+            action_channel,
+            state::Action::Dht(Action::Drop(pruning.address.clone())),
+        );
+        if let (Some(me), Some(author)) = (me.as_ref(), author) {
+            if author != *me {
+                let told = DirectMessage::Pruned {
+                    from: me.clone(),
+                    address: pruning.address.clone(),
+                };
+                let _ = messenger.send(&author, told);
+            }
+        }
+    }
+    nucleus.prune_log().checked(now, &pruned);
+    pruned
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        links::LinkMeta, tests::{test_dht_state, test_reduce},
+    };
+    use hash_table::entry::Entry;
+    use holochain_dna::zome::{entry_types::EntryType, Zome};
+    use validation::links::Link;
+
+    fn test_rules() -> Rules {
+        let mut rules = Rules::new();
+        rules.insert("story".to_string(), Retention::Days(1));
+        rules.insert("anchor".to_string(), Retention::WhileLinked);
+        rules
+    }
+
+    fn addresses(pruned: &[Pruning]) -> Vec<String> {
+        pruned.iter().map(|pruning| pruning.address.clone()).collect()
+    }
+
+    #[test]
+    /// the rules are those the entry types of the DNA declare, but forever
+    fn dna_rules() {
+        let mut dna = Dna::new();
+        let mut zome = Zome::default();
+        for (name, retention) in test_rules() {
+            let mut entry_type = EntryType::new();
+            entry_type.name = name;
+            entry_type.retention = retention;
+            zome.entry_types.push(entry_type);
+        }
+        zome.entry_types.push(EntryType::new());
+        dna.zomes.push(zome);
+        assert_eq!(test_rules(), rules(&dna));
+    }
+
+    #[test]
+    /// entries kept for days are pruned once they were held for that long
+    fn expired_days() {
+        let story = Entry::new("story", "gone tomorrow");
+        let post = Entry::new("post", "here to stay");
+        let dht = test_reduce(test_dht_state(), Action::Hold(story.clone()));
+        let dht = test_reduce(dht, Action::Hold(post));
+        let now = unix_now();
+        assert!(expired(&dht, &test_rules(), now).is_empty());
+
+        let pruned = expired(&dht, &test_rules(), now + DAY_SECS);
+        assert_eq!(vec![story.key()], addresses(&pruned));
+        assert_eq!(Retention::Days(1), pruned[0].retention);
+        assert_eq!("story", pruned[0].entry_type);
+    }
+
+    #[test]
+    /// entries kept while linked are pruned once every link from them is removed
+    fn expired_unlinked() {
+        let anchor = Entry::new("anchor", "rust");
+        let dht = test_reduce(test_dht_state(), Action::Hold(anchor.clone()));
+        let now = unix_now();
+        // never linked from yet
+        assert!(expired(&dht, &test_rules(), now).is_empty());
+
+        let linked = |dht: DhtState, target: &str| {
+            let link = Link::new(&anchor.key(), target, "tagged");
+            let address = link.to_entry().key();
+            (test_reduce(dht, Action::HoldLink(link, LinkMeta::default())), address)
+        };
+        let (dht, first) = linked(dht, "post_1");
+        let (dht, second) = linked(dht, "post_2");
+        let remove = |dht: DhtState, address: &str| {
+            let aspect = Aspect::LinkRemove(address.to_string());
+            test_reduce(dht, Action::HoldAspect(anchor.key(), aspect))
+        };
+        let dht = remove(dht, &first);
+        assert!(expired(&dht, &test_rules(), now).is_empty());
+        let dht = remove(dht, &second);
+        assert_eq!(vec![anchor.key()], addresses(&expired(&dht, &test_rules(), now)));
+    }
+
+    #[test]
+    /// holdings are checked every interval, and the log keeps the latest prunings
+    fn log() {
+        let log = PruneLog::default();
+        assert!(log.due(100));
+        let pruning = |n: usize| Pruning {
+            address: format!("address_{}", n),
+            entry_type: "story".to_string(),
+            retention: Retention::Days(1),
+            pruned_at: 100,
+        };
+        let pruned: Vec<Pruning> = (0..PRUNE_LOG_SIZE + 1).map(pruning).collect();
+        log.checked(100, &pruned);
+        assert!(!log.due(100 + PRUNE_INTERVAL_SECS - 1));
+        assert!(log.due(100 + PRUNE_INTERVAL_SECS));
+        assert_eq!(pruned[1..].to_vec(), log.pruned());
+    }
+}
@@ -0,0 +1,218 @@
+//! the holding store keeps what the DHT holds in a JSON file, along with when each address was
+//! integrated and what came of validating it, so holdings survive restarting the instance
+//! the file is rewritten whenever the holdings change
+//! records are hashed aspect by aspect, reloading re-verifies a random sample of them to catch a
+//! corrupted store before holding it again
+
+use dht::{
+    aspect::{self, Aspect}, DhtState, Integration,
+};
+use error::HolochainError;
+use rand::{self, seq};
+use serde_json;
+use std::{
+    collections::BTreeMap, fmt, fs, path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// how many reloaded records are re-verified by default
+pub const HOLDING_STORE_VERIFY_SAMPLE: usize = 32;
+
+/// what is held of an address
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HoldingRecord {
+    pub address: String,
+    /// the aspects by aspect address
+    pub aspects: BTreeMap<String, Aspect>,
+    pub integration: Integration,
+}
+
+impl HoldingRecord {
+    /// false if the record is corrupted, i.e. it is empty or one of its aspects doesn't hash to
+    /// its aspect address or doesn't belong at the address
+    pub fn verify(&self) -> bool {
+        let devices = aspect::devices(self.aspects.values());
+        !self.aspects.is_empty()
+            && self.aspects.iter().all(|(aspect_address, aspect)| {
+                *aspect_address == aspect.address(&self.address)
+                    && aspect.belongs_with(&self.address, &devices)
+            })
+    }
+}
+
+/// the records of what dht holds, by address
+pub fn records(dht: &DhtState) -> Vec<HoldingRecord> {
+    let mut records: Vec<HoldingRecord> = dht
+        .aspects
+        .iter()
+        .filter_map(|(address, aspects)| {
+            dht.integrations
+                .get(address)
+                .map(|integration| HoldingRecord {
+                    address: address.clone(),
+                    aspects: aspects.clone(),
+                    integration: integration.clone(),
+                })
+        })
+        .collect();
+    records.sort_by(|a, b| a.address.cmp(&b.address));
+    records
+}
+
+/// addresses of the records that don't verify among a random sample of up to size of them
+pub fn corrupted(records: &[HoldingRecord], size: usize) -> Vec<String> {
+    let mut corrupted: Vec<String> = seq::sample_iter(&mut rand::thread_rng(), records, size)
+        .unwrap_or_else(|all| all)
+        .into_iter()
+        .filter(|record| !record.verify())
+        .map(|record| record.address.clone())
+        .collect();
+    corrupted.sort();
+    corrupted
+}
+
+/// where an instance keeps its holdings, if anywhere
+/// the store is a cheap handle, clones share the same file
+#[derive(Clone, Default)]
+pub struct HoldingStore {
+    path: Arc<Mutex<Option<PathBuf>>>,
+}
+
+impl PartialEq for HoldingStore {
+    fn eq(&self, other: &HoldingStore) -> bool {
+        Arc::ptr_eq(&self.path, &other.path)
+    }
+}
+
+impl fmt::Debug for HoldingStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("HoldingStore")
+            .field("path", &*self.path.lock().unwrap())
+            .finish()
+    }
+}
+
+impl HoldingStore {
+    /// the records kept in the file, none if there is no file yet
+    /// fails if the file can't be read or a random sample of size records doesn't verify
+    pub fn load(path: &Path, size: usize) -> Result<Vec<HoldingRecord>, HolochainError> {
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+        let json = fs::read_to_string(path).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let records: Vec<HoldingRecord> =
+            serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let corrupted = corrupted(&records, size);
+        if !corrupted.is_empty() {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "holding store {} is corrupted at {}",
+                path.display(),
+                corrupted.join(", ")
+            )));
+        }
+        Ok(records)
+    }
+
+    /// keep the holdings in the file from now on
+    pub fn open(&self, path: &Path) {
+        *self.path.lock().unwrap() = Some(path.to_path_buf());
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.path.lock().unwrap().is_some()
+    }
+
+    /// write what dht holds to the file, if open
+    pub fn save(&self, dht: &DhtState) -> Result<(), HolochainError> {
+        match *self.path.lock().unwrap() {
+            Some(ref path) => {
+                let json = serde_json::to_string(&records(dht))
+                    .map_err(|e| HolochainError::new(&e.to_string()))?;
+                fs::write(path, json).map_err(|e| HolochainError::new(&e.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        tests::{test_dht_state, test_reduce}, Action, HoldingValidation,
+    };
+    use hash_table::{
+        entry::tests::{test_entry_a, test_entry_b}, header::tests::test_header,
+    };
+    use std::{env, process};
+
+    /// dht holding test_entry_a with a header, and test_entry_b found valid
+    fn test_holdings() -> DhtState {
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry_a()));
+        let header = Aspect::Header(test_header());
+        dht = test_reduce(dht, Action::HoldAspect(test_header().entry().to_string(), header));
+        dht = test_reduce(dht, Action::Hold(test_entry_b()));
+        test_reduce(
+            dht,
+            Action::SetValidation(test_entry_b().key(), HoldingValidation::Valid),
+        )
+    }
+
+    #[test]
+    /// records restore what was held as it was integrated
+    fn restore() {
+        let dht = test_holdings();
+        let records = records(&dht);
+        assert!(records.iter().all(HoldingRecord::verify));
+        let b = records
+            .iter()
+            .find(|record| record.address == test_entry_b().key())
+            .unwrap();
+        assert_eq!(HoldingValidation::Valid, b.integration.validation);
+        assert_eq!(dht.integration(&b.address), Some(b.integration.clone()));
+
+        let restored = test_reduce(test_dht_state(), Action::Restore(records.clone()));
+        assert_eq!(records, super::records(&restored));
+        assert_eq!(dht.held_addresses(), restored.held_addresses());
+        assert_eq!(dht.held_bytes(), restored.held_bytes());
+    }
+
+    #[test]
+    /// records whose aspects don't hash to their addresses are caught
+    fn verify() {
+        let mut record = records(&test_holdings()).remove(0);
+        assert!(record.verify());
+        assert!(corrupted(&[record.clone()], 1).is_empty());
+
+        let aspect_address = record.aspects.keys().next().unwrap().clone();
+        record
+            .aspects
+            .insert(aspect_address, Aspect::Update("elsewhere".to_string()));
+        assert!(!record.verify());
+        assert_eq!(vec![record.address.clone()], corrupted(&[record.clone()], 1));
+        assert!(corrupted(&[record], 0).is_empty());
+    }
+
+    #[test]
+    /// the store is written while open and read back unless corrupted
+    fn save_and_load() {
+        let path = env::temp_dir().join(format!("holochain_holdings_test_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        assert_eq!(Ok(vec![]), HoldingStore::load(&path, 1));
+
+        let store = HoldingStore::default();
+        store.save(&test_holdings()).unwrap();
+        assert!(!path.exists());
+        store.open(&path);
+        assert!(store.is_open());
+        store.save(&test_holdings()).unwrap();
+        assert_eq!(Ok(records(&test_holdings())), HoldingStore::load(&path, 1));
+
+        let corrupt = fs::read_to_string(&path)
+            .unwrap()
+            .replace(&test_entry_a().key(), &test_entry_b().key());
+        fs::write(&path, corrupt).unwrap();
+        assert!(HoldingStore::load(&path, HOLDING_STORE_VERIFY_SAMPLE).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+}
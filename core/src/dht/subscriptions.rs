@@ -0,0 +1,182 @@
+//! subscriptions let clients watch an address or an entry type and be signalled as new aspects of
+//! it are held, e.g. its content, updates, links and deletes, so UIs react without polling
+//! the instance notifies the subscriptions after every action changing what the DHT holds
+//! a subscription lasts as long as its receiver, e.g. while the interface connection keeping it
+//! is open
+//! entry types are only known of addresses whose content is held, aspects arriving ahead of it
+//! only reach the subscribers of the address
+
+use dht::{aspect::Aspect, DhtState};
+use hash::cid;
+use signal::Signal;
+use std::{
+    fmt, sync::{
+        mpsc::{channel, Receiver, Sender}, Arc, Mutex,
+    },
+};
+
+/// name of the signals subscribers receive
+pub const ASPECT_HELD_SIGNAL: &str = "aspect_held";
+
+/// what a subscription watches
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Target {
+    /// the aspects held of the address
+    Address(String),
+    /// the aspects held of the addresses of entries of the type
+    EntryType(String),
+}
+
+impl Target {
+    fn matches(&self, dht: &DhtState, address: &str) -> bool {
+        match *self {
+            Target::Address(ref watched) => watched == address,
+            Target::EntryType(ref entry_type) => dht
+                .holding(address)
+                .map(|entry| entry.entry_type() == entry_type)
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// the aspects new holds of each address that old didn't, by address
+pub fn held_since(old: &DhtState, new: &DhtState) -> Vec<(String, Aspect)> {
+    let mut held = Vec::new();
+    for (address, aspects) in &new.aspects {
+        let before = old.aspects.get(address);
+        if before == Some(aspects) {
+            continue;
+        }
+        for (aspect_address, aspect) in aspects {
+            let held_before = before
+                .map(|before| before.contains_key(aspect_address))
+                .unwrap_or(false);
+            if !held_before {
+                held.push((address.clone(), aspect.clone()));
+            }
+        }
+    }
+    held.sort_by(|a, b| a.0.cmp(&b.0));
+    held
+}
+
+/// the subscribers with what they watch
+type Subscribers = Vec<(Target, Sender<Signal>)>;
+
+/// the subscriptions of an instance
+/// the subscriptions are a cheap handle, clones share the same subscribers
+#[derive(Clone, Default)]
+pub struct Subscriptions {
+    subscribers: Arc<Mutex<Subscribers>>,
+}
+
+impl PartialEq for Subscriptions {
+    fn eq(&self, other: &Subscriptions) -> bool {
+        Arc::ptr_eq(&self.subscribers, &other.subscribers)
+    }
+}
+
+impl fmt::Debug for Subscriptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Subscriptions")
+            .field("subscribers", &self.subscribers.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl Subscriptions {
+    /// receive a signal for every aspect of the target held from now on
+    /// dropping the receiver unsubscribes
+    pub fn subscribe(&self, target: Target) -> Receiver<Signal> {
+        let target = match target {
+            Target::Address(address) => Target::Address(cid::normalize(&address)),
+            entry_type => entry_type,
+        };
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push((target, sender));
+        receiver
+    }
+
+    /// signal the subscribers of the aspects new holds that old didn't, returns how many signals
+    /// were sent
+    pub fn notify(&self, old: &DhtState, new: &DhtState) -> usize {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        if subscribers.is_empty() {
+            return 0;
+        }
+        let held = held_since(old, new);
+        let mut sent = 0;
+        subscribers.retain(|(target, subscriber)| {
+            for (address, aspect) in &held {
+                if !target.matches(new, address) {
+                    continue;
+                }
+                let signal = Signal {
+                    zome: String::new(),
+                    name: ASPECT_HELD_SIGNAL.to_string(),
+                    payload: json!({"target": target, "address": address, "aspect": aspect}),
+                };
+                if subscriber.send(signal).is_err() {
+                    return false;
+                }
+                sent += 1;
+            }
+            true
+        });
+        sent
+    }
+
+    /// how many subscriptions there are, including those whose receivers are dropped until the
+    /// next notify()
+    pub fn len(&self) -> usize {
+        self.subscribers.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash::cid::Codec;
+    use hash_table::entry::Entry;
+
+    #[test]
+    /// subscribers of an address get its new aspects, subscribers of a type those of its entries
+    fn notify() {
+        let subscriptions = Subscriptions::default();
+        let post = Entry::new("post", "hello");
+        let comment = Entry::new("comment", "hi");
+        // addresses can be watched as CIDs too
+        let cid = cid::to_cid(&post.key(), Codec::Raw).unwrap();
+        let by_address = subscriptions.subscribe(Target::Address(cid));
+        let by_type = subscriptions.subscribe(Target::EntryType("post".to_string()));
+        let dht = test_dht_state();
+        assert_eq!(0, subscriptions.notify(&dht, &dht));
+
+        let held = test_reduce(dht.clone(), Action::Hold(post.clone()));
+        assert_eq!(2, subscriptions.notify(&dht, &held));
+        let signal = by_address.try_recv().unwrap();
+        assert_eq!(ASPECT_HELD_SIGNAL, signal.name);
+        assert_eq!(json!(post.key()), signal.payload["address"]);
+        assert_eq!(json!({"address": post.key()}), signal.payload["target"]);
+        assert_eq!(signal.payload["aspect"], by_type.try_recv().unwrap().payload["aspect"]);
+
+        let deleted = test_reduce(held.clone(), Action::HoldAspect(post.key(), Aspect::Delete));
+        let commented = test_reduce(deleted.clone(), Action::Hold(comment));
+        assert_eq!(2, subscriptions.notify(&held, &commented));
+        assert_eq!(json!("Delete"), by_type.try_recv().unwrap().payload["aspect"]);
+        assert!(by_type.try_recv().is_err());
+
+        // dropping a receiver unsubscribes
+        drop(by_address);
+        assert_eq!(1, subscriptions.notify(&held, &deleted));
+        assert_eq!(1, subscriptions.len());
+    }
+}
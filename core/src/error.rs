@@ -13,6 +13,9 @@ pub enum HolochainError {
     ZomeNotFound(String),
     CapabilityNotFound(String),
     ZomeFunctionNotFound(String),
+    /// the chain head kept moving between the reads and commits of a zome call, however often it
+    /// was run again
+    HeadMoved,
 }
 
 impl HolochainError {
@@ -41,6 +44,7 @@ impl Error for HolochainError {
             ZomeNotFound(err_msg) => &err_msg,
             CapabilityNotFound(err_msg) => &err_msg,
             ZomeFunctionNotFound(err_msg) => &err_msg,
+            HeadMoved => "the chain head moved since the zome call read it",
         }
     }
 }
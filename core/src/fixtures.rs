@@ -0,0 +1,283 @@
+//! canonical test vectors, what this implementation hashes entries and headers to, published as
+//! JSON in core/fixtures/test_vectors.json so other implementations, e.g. holochain-proto, can
+//! check they agree byte for byte
+//! an entry's address is the SHA2-256 multihash of its UTF-8 content in base58, a header's hash is
+//! the same of the string_to_hash given with it
+//! a header's signature is the Ed25519 signature of the UTF-8 of its signed_hash by the secret key
+//! given with it, keys and signatures in base58, the secret keys are the ones of RFC 8032's first
+//! two test vectors
+//! this builds without the native feature, without it signatures aren't generated or checked
+
+#[cfg(feature = "native")]
+use agent::secbuf::SecBuf;
+use error::HolochainError;
+use hash_table::{entry::Entry, header::Header};
+#[cfg(feature = "native")]
+use network::sealing::SigningKeyPair;
+#[cfg(feature = "native")]
+use rust_base58::{FromBase58, ToBase58};
+use serde_json::{self, Value};
+
+/// where the published vectors are, relative to the core crate
+pub const TEST_VECTORS_PATH: &str = "fixtures/test_vectors.json";
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntryVector {
+    pub entry: Entry,
+    pub address: String,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HeaderVector {
+    pub header: Header,
+    pub string_to_hash: String,
+    pub hash: String,
+}
+
+/// the signature of the secret key's holder on a header, a provenance of it is
+/// Provenance(public_key, signature)
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SignatureVector {
+    pub header: Header,
+    pub secret_key: String,
+    pub public_key: String,
+    pub signed_hash: String,
+    pub signature: String,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct TestVectors {
+    pub entries: Vec<EntryVector>,
+    pub headers: Vec<HeaderVector>,
+    #[serde(default)]
+    pub signatures: Vec<SignatureVector>,
+}
+
+impl TestVectors {
+    pub fn from_json(json: &str) -> Result<TestVectors, HolochainError> {
+        serde_json::from_str(json).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+
+    /// pretty printed, as published
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("TestVectors should serialize")
+    }
+}
+
+/// the header with its time set, pinned as Header::link() times headers by the host clock
+fn timed(header: &Header, time: &str) -> Header {
+    let mut value = serde_json::to_value(header).expect("Header should serialize");
+    value["time"] = Value::String(time.to_string());
+    serde_json::from_value(value).expect("Header should deserialize")
+}
+
+fn header_vector(header: Header) -> HeaderVector {
+    HeaderVector {
+        string_to_hash: header.string_to_hash(),
+        hash: header.hash(),
+        header,
+    }
+}
+
+/// the bytes of a secret key in hex
+#[cfg(feature = "native")]
+fn secret_key(hex: &str) -> Vec<u8> {
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).expect("the secret key is hex"))
+        .collect()
+}
+
+#[cfg(feature = "native")]
+fn signature_vector(header: &Header, secret_key: &[u8]) -> SignatureVector {
+    let keys = SigningKeyPair::from_secret(SecBuf::from_slice(secret_key));
+    let signed_hash = header.signed_hash();
+    SignatureVector {
+        header: header.clone(),
+        secret_key: secret_key.to_base58(),
+        public_key: keys.public().to_base58(),
+        signature: keys.sign(signed_hash.as_bytes()).to_base58(),
+        signed_hash,
+    }
+}
+
+/// the vectors as this implementation computes them
+#[cfg(feature = "native")]
+pub fn generate() -> TestVectors {
+    let entries = vec![
+        // known from the golang implementation, see hash::tests
+        Entry::new("testEntryType", "test data"),
+        Entry::new("testEntryType", ""),
+        Entry::new("post", "h\u{e9}llo w\u{f6}rld \u{2713}"),
+        Entry::new("post", "{\"title\":\"json content\",\"tags\":[\"a\",\"b\"]}"),
+        Entry::new("comment", &"a".repeat(1024)),
+    ];
+
+    // the untimed ones as headers were written before they were timed
+    let first = timed(&Header::link(&entries[0], None, None), "");
+    let second = timed(&Header::link(&entries[2], Some(first.hash()), None), "");
+    let third = timed(
+        &Header::link(&entries[3], Some(second.hash()), Some(second.hash())),
+        "",
+    );
+    let author = secret_key("9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60");
+    let countersigner =
+        secret_key("4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb");
+    let keys = |secret: &[u8]| SigningKeyPair::from_secret(SecBuf::from_slice(secret));
+    let unsigned = timed(&third, "2018-07-01T12:00:00+00:00");
+    let signed = unsigned.sign(&keys(&author));
+    let countersigned = signed.sign(&keys(&countersigner));
+    let signatures = vec![
+        signature_vector(&unsigned, &author),
+        // countersigners sign the same hash, whatever signatures came before theirs
+        signature_vector(&signed, &countersigner),
+    ];
+    let headers = vec![first, second, third, signed, countersigned];
+
+    TestVectors {
+        entries: entries
+            .into_iter()
+            .map(|entry| EntryVector {
+                address: entry.hash(),
+                entry,
+            })
+            .collect(),
+        headers: headers.into_iter().map(header_vector).collect(),
+        signatures,
+    }
+}
+
+/// everything in vectors this implementation disagrees with, one problem per line
+pub fn verify(vectors: &TestVectors) -> Vec<String> {
+    let mut problems = Vec::new();
+    for (i, vector) in vectors.entries.iter().enumerate() {
+        let address = vector.entry.hash();
+        if address != vector.address {
+            problems.push(format!(
+                "entry {}: address is {}, not {}",
+                i, address, vector.address
+            ));
+        }
+    }
+    for (i, vector) in vectors.headers.iter().enumerate() {
+        let string_to_hash = vector.header.string_to_hash();
+        if string_to_hash != vector.string_to_hash {
+            problems.push(format!(
+                "header {}: string to hash is {:?}, not {:?}",
+                i, string_to_hash, vector.string_to_hash
+            ));
+        }
+        let hash = vector.header.hash();
+        if hash != vector.hash {
+            problems.push(format!("header {}: hash is {}, not {}", i, hash, vector.hash));
+        }
+        #[cfg(feature = "native")]
+        {
+            if !vector.header.provenances().is_empty() && !vector.header.verify_provenances() {
+                problems.push(format!("header {}: a provenance doesn't verify", i));
+            }
+        }
+    }
+    for (i, vector) in vectors.signatures.iter().enumerate() {
+        let signed_hash = vector.header.signed_hash();
+        if signed_hash != vector.signed_hash {
+            problems.push(format!(
+                "signature {}: signed hash is {}, not {}",
+                i, signed_hash, vector.signed_hash
+            ));
+        }
+        #[cfg(feature = "native")]
+        problems.extend(verify_signature(i, vector));
+    }
+    problems
+}
+
+/// what is wrong with the keys and signature of the vector
+#[cfg(feature = "native")]
+fn verify_signature(i: usize, vector: &SignatureVector) -> Vec<String> {
+    let secret = match vector.secret_key.from_base58() {
+        Ok(ref secret) if secret.len() == 32 => SecBuf::from_slice(secret),
+        _ => return vec![format!("signature {}: the secret key isn't 32 bytes of base58", i)],
+    };
+    let keys = SigningKeyPair::from_secret(secret);
+    let mut problems = Vec::new();
+    let public_key = keys.public().to_base58();
+    if public_key != vector.public_key {
+        problems.push(format!(
+            "signature {}: public key is {}, not {}",
+            i, public_key, vector.public_key
+        ));
+    }
+    let signature = keys.sign(vector.signed_hash.as_bytes()).to_base58();
+    if signature != vector.signature {
+        problems.push(format!(
+            "signature {}: signature is {}, not {}",
+            i, signature, vector.signature
+        ));
+    }
+    problems
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(feature = "native")]
+    /// the published vectors are the ones generated, all of them verified
+    /// regenerate them with holochain_test_bin --write-test-vectors if hashing changes on purpose
+    fn published_vectors() {
+        let published = TestVectors::from_json(include_str!("../fixtures/test_vectors.json"));
+        assert_eq!(Ok(generate()), published);
+        assert!(verify(&published.unwrap()).is_empty());
+        assert_eq!(
+            "QmY8Mzg9F69e5P9AoQPYat655HEhc1TVGs11tmfNSzkqh2",
+            generate().entries[0].address
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    /// vectors this implementation disagrees with are reported
+    fn verify_mismatches() {
+        let mut vectors = generate();
+        vectors.entries[1].address = "QmWrong".to_string();
+        vectors.headers[3].hash = "QmWrong".to_string();
+        vectors.signatures[0].signature = "Wrong".to_string();
+        let problems = verify(&vectors);
+        assert_eq!(3, problems.len());
+        assert!(problems[0].starts_with("entry 1: address is "));
+        assert!(problems[1].starts_with("header 3: hash is "));
+        assert!(problems[1].ends_with(", not QmWrong"));
+        assert!(problems[2].starts_with("signature 0: signature is "));
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    /// the keys are RFC 8032's and the signed headers carry the signatures of the vectors
+    fn signature_vectors() {
+        let vectors = generate();
+        let rfc_public_keys = [
+            "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+            "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+        ];
+        for (vector, public_key) in vectors.signatures.iter().zip(rfc_public_keys.iter()) {
+            assert_eq!(secret_key(public_key).to_base58(), vector.public_key);
+        }
+        let countersigned = &vectors.headers[4].header;
+        assert!(countersigned.verify_provenances());
+        let provenances = vectors
+            .signatures
+            .iter()
+            .map(|vector| (vector.public_key.as_str(), vector.signature.as_str()))
+            .collect::<Vec<(&str, &str)>>();
+        assert_eq!(
+            provenances,
+            countersigned
+                .provenances()
+                .iter()
+                .map(|provenance| (provenance.source(), provenance.signature()))
+                .collect::<Vec<(&str, &str)>>()
+        );
+    }
+}
@@ -0,0 +1,192 @@
+//! IPFS content identifiers for entry addresses
+//! entries are addressed by the SHA2-256 multihash of their content, which is also what a CIDv1
+//! of the content as a raw IPFS block holds, e.g. after `ipfs add --cid-version 1`, so the two
+//! convert into each other without the content
+//! CIDv1 are written base32 as IPFS does, e.g. "bafkrei...", and read in base32 or base58btc,
+//! CIDv0 are base58 multihashes like our addresses already
+
+use error::HolochainError;
+use rust_base58::{FromBase58, ToBase58};
+
+/// how the content a CID refers to is encoded, the multicodec of the CID
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Codec {
+    /// the bytes as they are, what entry addresses are hashes of
+    Raw,
+    /// JSON, for entries with JSON content that should be read as such
+    Json,
+}
+
+impl Codec {
+    fn code(self) -> u64 {
+        match self {
+            Codec::Raw => 0x55,
+            Codec::Json => 0x0200,
+        }
+    }
+}
+
+const CID_V1: u64 = 1;
+const BASE32_ALPHABET: &[u8] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut n = 0;
+    for (i, byte) in bytes.iter().enumerate().take(9) {
+        n |= u64::from(byte & 0x7f) << (7 * i);
+        if *byte < 0x80 {
+            return Some((n, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+/// RFC 4648 base32, lowercase without padding
+fn base32_encode(bytes: &[u8]) -> String {
+    let mut encoded = String::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for byte in bytes {
+        buffer = (buffer << 8) | u32::from(*byte);
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+fn base32_decode(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let (mut buffer, mut bits) = (0u32, 0);
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET
+            .iter()
+            .position(|a| *a == c.to_ascii_lowercase())?;
+        buffer = (buffer << 5) | value as u32;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
+
+/// the multihash in the bytes of a multihash, if that is what they are
+fn check_multihash(bytes: &[u8]) -> Option<&[u8]> {
+    let (_, rest) = read_varint(bytes)?;
+    let (len, digest) = read_varint(rest)?;
+    if digest.len() as u64 == len {
+        Some(bytes)
+    } else {
+        None
+    }
+}
+
+/// the CIDv1 of the content at an address, which has to be a base58 multihash
+pub fn to_cid(address: &str, codec: Codec) -> Result<String, HolochainError> {
+    let multihash = address
+        .from_base58()
+        .ok()
+        .filter(|bytes| check_multihash(bytes).is_some())
+        .ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("{} is not a multihash address", address))
+        })?;
+    let mut bytes = Vec::new();
+    write_varint(&mut bytes, CID_V1);
+    write_varint(&mut bytes, codec.code());
+    bytes.extend_from_slice(&multihash);
+    Ok(format!("b{}", base32_encode(&bytes)))
+}
+
+/// the multihash of a CIDv1, whatever its codec, None if it isn't one
+fn cid_multihash(cid: &str) -> Option<Vec<u8>> {
+    let bytes = match cid.chars().next()? {
+        'b' | 'B' => base32_decode(&cid[1..])?,
+        'z' => cid[1..].from_base58().ok()?,
+        _ => return None,
+    };
+    let (version, rest) = read_varint(&bytes)?;
+    if version != CID_V1 {
+        return None;
+    }
+    let (_, multihash) = read_varint(rest)?;
+    check_multihash(multihash).map(|multihash| multihash.to_vec())
+}
+
+/// the address a CID refers to, as the base58 multihash entries are stored under
+/// CIDv0 are such addresses already
+pub fn to_address(cid: &str) -> Result<String, HolochainError> {
+    cid_multihash(cid)
+        .map(|multihash| multihash.to_base58())
+        .or_else(|| {
+            cid.from_base58()
+                .ok()
+                .filter(|bytes| check_multihash(bytes).is_some())
+                .map(|_| cid.to_string())
+        })
+        .ok_or_else(|| HolochainError::ErrorGeneric(format!("{} is not a CID", cid)))
+}
+
+/// the address as entries are stored under it, whether it is a CID or not, e.g. for what zomes
+/// pass in
+/// what is neither a CIDv1 nor a multihash, e.g. an agent address, is left as it is
+pub fn normalize(address: &str) -> String {
+    cid_multihash(address)
+        .map(|multihash| multihash.to_base58())
+        .unwrap_or_else(|| address.to_string())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::entry::Entry;
+
+    /// the raw CIDv1 IPFS gives "bar"
+    const BAR_CID: &str = "bafkreih43yvs5w5fnp2aqya7w4q75g24gogrb3sct2qe7lsvcg3i7p4pxe";
+
+    #[test]
+    /// entry addresses and the CIDs IPFS gives their content convert into each other
+    fn converts() {
+        let address = Entry::new("fooType", "bar").key();
+        assert_eq!(Ok(BAR_CID.to_string()), to_cid(&address, Codec::Raw));
+        assert_eq!(Ok(address.clone()), to_address(BAR_CID));
+        assert_eq!(Ok(address.clone()), to_address(&BAR_CID.to_uppercase()));
+        assert_eq!(Ok(address.clone()), to_address(&address));
+
+        let json = to_cid(&address, Codec::Json).unwrap();
+        assert_eq!(
+            "bagaaiera7tpcwlw3uvv7icdad63sd7u3lqzy2ehoikpkat5okui3nd57r64q",
+            json
+        );
+        assert_eq!(Ok(address.clone()), to_address(&json));
+
+        assert_eq!(
+            Ok("QmaozNR7DZHQK1ZcU9p7QdrshMvXqWK6gpu5rmrkPdT3L4".to_string()),
+            to_address("bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e")
+        );
+    }
+
+    #[test]
+    /// what isn't a CID or multihash doesn't convert
+    fn rejects_others() {
+        assert!(to_cid("alice", Codec::Raw).is_err());
+        assert!(to_address("alice").is_err());
+        assert!(to_address("bafkrei").is_err());
+        assert!(to_address(&BAR_CID[..BAR_CID.len() - 2]).is_err());
+        assert!(to_address("").is_err());
+        assert_eq!("bob", normalize("bob"));
+        assert_eq!(Entry::new("fooType", "bar").key(), normalize(BAR_CID));
+    }
+}
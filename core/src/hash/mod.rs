@@ -1,3 +1,5 @@
+pub mod cid;
+
 // use multihash::Multihash;
 use multihash::{encode, Hash};
 use rust_base58::ToBase58;
@@ -1,13 +1,17 @@
 use hash;
+use hash_table::{header::Header, status::EntryStatus};
 use multihash::Hash;
+use std::sync::Arc;
 
+/// content is reference counted so clones of an Entry (e.g. every Pair read out of a HashTable)
+/// share the same bytes rather than copying them
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Entry {
-    content: String,
+    content: Arc<str>,
 
     // @TODO do NOT serialize entry_type in Entry as it should only be in Header
     // @see https://github.com/holochain/holochain-rust/issues/80
-    entry_type: String,
+    entry_type: Arc<str>,
 }
 
 impl PartialEq for Entry {
@@ -27,8 +31,8 @@ impl Entry {
     /// @see chain::pair::Pair
     pub fn new(entry_type: &str, content: &str) -> Entry {
         Entry {
-            entry_type: entry_type.to_string(),
-            content: content.to_string(),
+            entry_type: Arc::from(entry_type),
+            content: Arc::from(content),
         }
     }
 
@@ -36,21 +40,21 @@ impl Entry {
     pub fn hash(&self) -> String {
         // @TODO - this is the wrong string being hashed
         // @see https://github.com/holochain/holochain-rust/issues/103
-        let string_to_hash = self.content.clone();
+        let string_to_hash = &self.content;
 
         // @TODO the hashing algo should not be hardcoded
         // @see https://github.com/holochain/holochain-rust/issues/104
-        hash::str_to_b58_hash(&string_to_hash, Hash::SHA2256)
+        hash::str_to_b58_hash(string_to_hash, Hash::SHA2256)
     }
 
     /// content getter
-    pub fn content(&self) -> String {
-        self.content.clone()
+    pub fn content(&self) -> &str {
+        &self.content
     }
 
     /// entry_type getter
-    pub fn entry_type(&self) -> String {
-        self.entry_type.clone()
+    pub fn entry_type(&self) -> &str {
+        &self.entry_type
     }
 
     /// returns true if the entry is valid
@@ -66,6 +70,42 @@ impl Entry {
     }
 }
 
+/// what is known about an entry short of its content, e.g. to list entries without getting them
+/// or to decide whether to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntryMeta {
+    pub entry_type: String,
+    /// bytes of entry content
+    pub size: u64,
+    /// the first agent signing a header of the entry, None while none is signed
+    pub author: Option<String>,
+    /// when the entry was committed as the first timestamped header of it has it
+    pub time: Option<String>,
+    /// None where it isn't known, e.g. on a chain
+    pub status: Option<EntryStatus>,
+}
+
+impl EntryMeta {
+    /// the metadata of an entry committed under the headers
+    pub fn new(entry: &Entry, headers: &[Header], status: Option<EntryStatus>) -> EntryMeta {
+        EntryMeta {
+            entry_type: entry.entry_type().to_string(),
+            size: entry.content().len() as u64,
+            author: headers
+                .iter()
+                .filter_map(|header| header.provenances().first())
+                .map(|provenance| provenance.source().to_string())
+                .next(),
+            time: headers
+                .iter()
+                .map(|header| header.time())
+                .find(|time| !time.is_empty())
+                .map(str::to_string),
+            status,
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use super::Entry;
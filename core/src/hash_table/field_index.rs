@@ -0,0 +1,301 @@
+//! secondary indexes over fields of the content of entries, declared per entry type
+//! a field is named by its path in the JSON content, e.g. "handle" or "profile.handle", and
+//! entries are indexed by the value they have there, so lookups like "profiles where handle is
+//! X" don't walk every entry
+//! strings, integers and booleans are indexed, entries without the field or with some other value
+//! at it aren't
+
+use error::HolochainError;
+use hash_table::{entry::Entry, HashTable};
+use serde_json::{self, Value};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap}, mem, ops::Bound,
+};
+
+/// the paths of the fields indexed by entry type name
+pub type IndexedFields = BTreeMap<String, Vec<String>>;
+
+/// a value a field is indexed by
+/// values of different kinds never match each other, e.g. 1 and "1"
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum IndexValue {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+
+impl IndexValue {
+    /// the value a JSON value is indexed by, None for the ones that aren't indexed
+    pub fn from_json(value: &Value) -> Option<IndexValue> {
+        match *value {
+            Value::Bool(b) => Some(IndexValue::Bool(b)),
+            Value::Number(ref n) => n.as_i64().map(IndexValue::Int),
+            Value::String(ref s) => Some(IndexValue::Text(s.clone())),
+            _ => None,
+        }
+    }
+}
+
+/// what an indexed field is looked up by
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IndexQuery {
+    /// the field has the value
+    Equals(IndexValue),
+    /// the field is between from and to, both included, an open end if None
+    Range {
+        from: Option<IndexValue>,
+        to: Option<IndexValue>,
+    },
+}
+
+impl IndexQuery {
+    pub fn matches(&self, value: &IndexValue) -> bool {
+        let same_kind = |bound: &IndexValue| mem::discriminant(bound) == mem::discriminant(value);
+        match *self {
+            IndexQuery::Equals(ref equals) => equals == value,
+            IndexQuery::Range { ref from, ref to } => {
+                let above = match *from {
+                    Some(ref from) => same_kind(from) && from <= value,
+                    None => true,
+                };
+                let below = match *to {
+                    Some(ref to) => same_kind(to) && value <= to,
+                    None => true,
+                };
+                above && below
+            }
+        }
+    }
+}
+
+/// the value at the path of the field in the content, if the content is JSON and it is indexed
+pub fn extract(content: &str, path: &str) -> Option<IndexValue> {
+    let json: Value = serde_json::from_str(content).ok()?;
+    let value = path.split('.').try_fold(&json, |value, key| value.get(key))?;
+    IndexValue::from_json(value)
+}
+
+/// the keys of the Pairs in the table of entry_type with a value at path matching the query, by
+/// value then key, from every Pair in it
+pub fn scan<T: HashTable + ?Sized>(
+    table: &T,
+    entry_type: &str,
+    path: &str,
+    query: &IndexQuery,
+) -> Result<Vec<String>, HolochainError> {
+    let mut found = Vec::new();
+    for key in table.keys()? {
+        let pair = match table.get(&key)? {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if pair.entry().entry_type() != entry_type {
+            continue;
+        }
+        if let Some(value) = extract(pair.entry().content(), path) {
+            if query.matches(&value) {
+                found.push((value, key));
+            }
+        }
+    }
+    found.sort();
+    Ok(found.into_iter().map(|(_, key)| key).collect())
+}
+
+/// indexes of the declared fields, from values to the keys of what holds them, e.g. Pairs in a
+/// table
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldIndex {
+    fields: IndexedFields,
+    /// keys by entry type, path and value
+    values: HashMap<String, HashMap<String, BTreeMap<IndexValue, BTreeSet<String>>>>,
+}
+
+impl FieldIndex {
+    /// an empty index of the fields
+    pub fn new(fields: &IndexedFields) -> FieldIndex {
+        FieldIndex {
+            fields: fields.clone(),
+            values: HashMap::new(),
+        }
+    }
+
+    pub fn fields(&self) -> &IndexedFields {
+        &self.fields
+    }
+
+    /// true if fields of entries of the type are indexed
+    pub fn is_indexed(&self, entry_type: &str) -> bool {
+        self.fields.contains_key(entry_type)
+    }
+
+    /// index the declared fields of the entry under key
+    pub fn insert(&mut self, key: &str, entry: &Entry) {
+        let paths = match self.fields.get(entry.entry_type()) {
+            Some(paths) => paths,
+            None => return,
+        };
+        for path in paths {
+            if let Some(value) = extract(entry.content(), path) {
+                self.values
+                    .entry(entry.entry_type().to_string())
+                    .or_default()
+                    .entry(path.clone())
+                    .or_default()
+                    .entry(value)
+                    .or_default()
+                    .insert(key.to_string());
+            }
+        }
+    }
+
+    /// drop the entry under key from the index
+    pub fn remove(&mut self, key: &str, entry: &Entry) {
+        let paths = match self.values.get_mut(entry.entry_type()) {
+            Some(paths) => paths,
+            None => return,
+        };
+        for (path, values) in paths.iter_mut() {
+            if let Some(value) = extract(entry.content(), path) {
+                let emptied = values
+                    .get_mut(&value)
+                    .map(|keys| {
+                        keys.remove(key);
+                        keys.is_empty()
+                    })
+                    .unwrap_or(false);
+                if emptied {
+                    values.remove(&value);
+                }
+            }
+        }
+    }
+
+    /// the keys with a value at path matching the query, by value then key, or None if the path
+    /// isn't indexed for entry_type
+    pub fn query(&self, entry_type: &str, path: &str, query: &IndexQuery) -> Option<Vec<String>> {
+        if !self.fields.get(entry_type).into_iter().flatten().any(|p| p == path) {
+            return None;
+        }
+        let values = match self.values.get(entry_type).and_then(|paths| paths.get(path)) {
+            Some(values) => values,
+            None => return Some(Vec::new()),
+        };
+        let bounds = match *query {
+            IndexQuery::Equals(ref value) => (Bound::Included(value), Bound::Included(value)),
+            IndexQuery::Range { ref from, ref to } => (
+                from.as_ref().map_or(Bound::Unbounded, Bound::Included),
+                to.as_ref().map_or(Bound::Unbounded, Bound::Included),
+            ),
+        };
+        if let (Bound::Included(from), Bound::Included(to)) = bounds {
+            if from > to {
+                return Some(Vec::new());
+            }
+        }
+        Some(
+            values
+                .range::<IndexValue, _>(bounds)
+                .filter(|(value, _)| query.matches(value))
+                .flat_map(|(_, keys)| keys.iter().cloned())
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// "profile" entries indexed by handle and age
+    pub fn test_indexed_fields() -> IndexedFields {
+        let mut fields = IndexedFields::new();
+        fields.insert(
+            "profile".to_string(),
+            vec!["handle".to_string(), "about.age".to_string()],
+        );
+        fields
+    }
+
+    pub fn test_profile(handle: &str, age: i64) -> Entry {
+        Entry::new(
+            "profile",
+            &format!(r#"{{"handle":"{}","about":{{"age":{}}}}}"#, handle, age),
+        )
+    }
+
+    fn text(s: &str) -> IndexValue {
+        IndexValue::Text(s.to_string())
+    }
+
+    #[test]
+    /// values are found by their path, only the indexed kinds of them
+    fn extract_values() {
+        let content = r#"{"handle":"alice","about":{"age":30,"verified":true,"tags":[]}}"#;
+        assert_eq!(Some(text("alice")), extract(content, "handle"));
+        assert_eq!(Some(IndexValue::Int(30)), extract(content, "about.age"));
+        assert_eq!(Some(IndexValue::Bool(true)), extract(content, "about.verified"));
+        assert_eq!(None, extract(content, "about.tags"));
+        assert_eq!(None, extract(content, "about.height"));
+        assert_eq!(None, extract("alice", "handle"));
+    }
+
+    #[test]
+    /// ranges only match values of the kind of their bounds
+    fn query_matches() {
+        let adults = IndexQuery::Range {
+            from: Some(IndexValue::Int(18)),
+            to: None,
+        };
+        assert!(adults.matches(&IndexValue::Int(18)));
+        assert!(!adults.matches(&IndexValue::Int(17)));
+        assert!(!adults.matches(&text("eighteen")));
+        assert!(IndexQuery::Equals(text("bob")).matches(&text("bob")));
+        assert!(!IndexQuery::Equals(text("1")).matches(&IndexValue::Int(1)));
+    }
+
+    #[test]
+    fn index_and_query() {
+        let mut index = FieldIndex::new(&test_indexed_fields());
+        let alice = test_profile("alice", 30);
+        let bob = test_profile("bob", 17);
+        let carol = test_profile("carol", 45);
+        for entry in [&alice, &bob, &carol].iter() {
+            index.insert(&entry.key(), entry);
+        }
+        index.insert("post", &Entry::new("post", r#"{"handle":"alice"}"#));
+
+        let handle = |h: &str| IndexQuery::Equals(text(h));
+        assert_eq!(
+            Some(vec![alice.key()]),
+            index.query("profile", "handle", &handle("alice"))
+        );
+        assert_eq!(
+            Some(Vec::new()),
+            index.query("profile", "handle", &handle("dave"))
+        );
+        let adults = IndexQuery::Range {
+            from: Some(IndexValue::Int(18)),
+            to: Some(IndexValue::Int(50)),
+        };
+        assert_eq!(
+            Some(vec![alice.key(), carol.key()]),
+            index.query("profile", "about.age", &adults)
+        );
+        // what isn't declared isn't indexed
+        assert_eq!(None, index.query("profile", "name", &adults));
+        assert_eq!(None, index.query("post", "handle", &adults));
+
+        index.remove(&alice.key(), &alice);
+        assert_eq!(
+            Some(Vec::new()),
+            index.query("profile", "handle", &handle("alice"))
+        );
+        assert_eq!(
+            Some(vec![carol.key()]),
+            index.query("profile", "about.age", &adults)
+        );
+    }
+}
@@ -1,26 +1,38 @@
-use chain::Chain;
+use chain::ChainRead;
 use hash;
-use hash_table::{entry::Entry, HashTable};
+use hash_table::{entry::Entry, provenance::Provenance};
 use multihash::Hash;
+#[cfg(feature = "native")]
+use network::sealing::SigningKeyPair;
+use std::{
+    sync::Arc, time::{SystemTime, UNIX_EPOCH},
+};
 
 // @TODO - serialize properties as defined in HeadersEntrySchema from golang alpha 1
 // @see https://github.com/holochain/holochain-proto/blob/4d1b8c8a926e79dfe8deaa7d759f930b66a5314f/entry_headers.go#L7
 // @see https://github.com/holochain/holochain-rust/issues/75
+/// fields are reference counted so Headers are cheap to clone, getters borrow
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Header {
     /// the type of this entry
     /// system types may have associated "subconscious" behavior
-    entry_type: String,
+    entry_type: Arc<str>,
     /// ISO8601 time stamp
-    time: String,
+    time: Arc<str>,
     /// link to the immediately preceding header, None is valid only for genesis
-    next: Option<String>,
+    next: Option<Arc<str>>,
     /// mandatory link to the entry for this header
-    entry: String,
+    entry: Arc<str>,
     /// link to the most recent header of the same type, None is valid only for the first of type
-    type_next: Option<String>,
-    /// agent's cryptographic signature
-    signature: String,
+    type_next: Option<Arc<str>>,
+    /// the agents that signed the header, more than one for countersigned entries
+    /// chains written before provenances were added have none
+    #[serde(default = "no_provenances")]
+    provenances: Arc<[Provenance]>,
+}
+
+fn no_provenances() -> Arc<[Provenance]> {
+    Arc::from(Vec::new())
 }
 
 impl PartialEq for Header {
@@ -35,74 +47,142 @@ impl Header {
     /// this means that a header becomes invalid and useless as soon as the chain is mutated
     /// the only valid usage of a header is to immediately push it onto a chain in a Pair.
     /// normally (outside unit tests) the generation of valid headers is internal to the
-    /// chain::SourceChain trait and should not need to be handled manually
+    /// chain::ChainWrite trait and should not need to be handled manually
     /// @see chain::pair::Pair
     /// @see chain::entry::Entry
-    pub fn new<T: HashTable>(chain: &Chain<T>, entry: &Entry) -> Header {
-        Header {
-            entry_type: entry.entry_type().clone(),
-            // @TODO implement timestamps
-            // https://github.com/holochain/holochain-rust/issues/70
-            time: String::new(),
-            next: chain.top().and_then(|p| Some(p.header().hash())),
-            entry: entry.hash().to_string(),
-            type_next: chain
-                .top_type(&entry.entry_type())
+    pub fn new<C: ChainRead + ?Sized>(chain: &C, entry: &Entry) -> Header {
+        Header::link(
+            entry,
+            chain.top().map(|p| p.header().hash()),
+            chain
+                .top_type(entry.entry_type())
                 // @TODO inappropriate unwrap()?
                 // @see https://github.com/holochain/holochain-rust/issues/147
                 .unwrap()
-                .and_then(|p| Some(p.header().hash())),
-            // @TODO implement signatures
-            // https://github.com/holochain/holochain-rust/issues/71
-            signature: String::new(),
+                .map(|p| p.header().hash()),
+        )
+    }
+
+    /// build a new Header for an entry from explicit links to the previous header and previous
+    /// header of the same type, timed now by the host clock, see unix_now()
+    /// used where headers are generated ahead of pushing, e.g. chain.push_batch()
+    pub(crate) fn link(entry: &Entry, next: Option<String>, type_next: Option<String>) -> Header {
+        Header {
+            entry_type: Arc::from(entry.entry_type()),
+            time: Arc::from(iso8601(unix_now())),
+            next: next.map(Arc::from),
+            entry: Arc::from(entry.hash()),
+            type_next: type_next.map(Arc::from),
+            // signed once built, see sign()
+            provenances: no_provenances(),
+        }
+    }
+
+    /// a copy of the header signed by one more agent, e.g. a countersigning one
+    /// the signatures are part of the hash so the copy is a different header
+    pub fn with_provenance(&self, provenance: Provenance) -> Header {
+        let mut provenances = self.provenances.to_vec();
+        provenances.push(provenance);
+        Header {
+            provenances: Arc::from(provenances),
+            ..self.clone()
+        }
+    }
+
+    /// a copy of the header timed at secs since the unix epoch
+    /// the time is part of the hash so the copy is a different header
+    pub fn with_time(&self, secs: u64) -> Header {
+        Header {
+            time: Arc::from(iso8601(secs)),
+            ..self.clone()
         }
     }
 
     /// entry_type getter
-    pub fn entry_type(&self) -> String {
-        self.entry_type.clone()
+    pub fn entry_type(&self) -> &str {
+        &self.entry_type
     }
 
     /// time getter
-    pub fn time(&self) -> String {
-        self.time.clone()
+    pub fn time(&self) -> &str {
+        &self.time
+    }
+
+    /// seconds since the unix epoch the header is timed at, None if it has no time, as headers
+    /// written before they were timed, or the time isn't ISO8601
+    pub fn timestamp(&self) -> Option<u64> {
+        parse_iso8601(&self.time)
     }
 
     /// next getter
-    pub fn next(&self) -> Option<String> {
-        self.next.clone()
+    pub fn next(&self) -> Option<&str> {
+        self.next.as_deref()
     }
 
     /// entry getter
-    pub fn entry(&self) -> String {
-        self.entry.clone()
+    pub fn entry(&self) -> &str {
+        &self.entry
     }
 
     /// type_next getter
-    pub fn type_next(&self) -> Option<String> {
-        self.type_next.clone()
+    pub fn type_next(&self) -> Option<&str> {
+        self.type_next.as_deref()
     }
 
-    /// signature getter
-    pub fn signature(&self) -> String {
-        self.signature.clone()
+    /// provenances getter, the agents to check the signatures of the header against
+    pub fn provenances(&self) -> &[Provenance] {
+        &self.provenances
     }
 
-    /// hashes the header
-    pub fn hash(&self) -> String {
+    /// the string hash() hashes, the fields concatenated
+    pub fn string_to_hash(&self) -> String {
         // @TODO this is the wrong string being hashed
         // @see https://github.com/holochain/holochain-rust/issues/103
-        let string_to_hash = String::new()
+        String::new()
             + &self.entry_type
             + &self.time
-            + &self.next.clone().unwrap_or_default()
+            + self.next().unwrap_or_default()
             + &self.entry
-            + &self.type_next.clone().unwrap_or_default()
-            + &self.signature;
+            + self.type_next().unwrap_or_default()
+            + &self
+                .provenances
+                .iter()
+                .map(|provenance| provenance.source().to_string() + provenance.signature())
+                .collect::<String>()
+    }
 
+    /// hashes the header
+    pub fn hash(&self) -> String {
         // @TODO the hashing algo should not be hardcoded
         // @see https://github.com/holochain/holochain-rust/issues/104
-        hash::str_to_b58_hash(&string_to_hash, Hash::SHA2256)
+        hash::str_to_b58_hash(&self.string_to_hash(), Hash::SHA2256)
+    }
+
+    /// hash of the header without its provenances, what each of them signs
+    /// countersigners sign the same hash whatever signatures came before theirs
+    pub fn signed_hash(&self) -> String {
+        Header {
+            provenances: no_provenances(),
+            ..self.clone()
+        }.hash()
+    }
+
+    /// a copy of the header with the provenance of the holder of keys, see signed_hash()
+    #[cfg(feature = "native")]
+    pub fn sign(&self, keys: &SigningKeyPair) -> Header {
+        self.with_provenance(Provenance::sign(keys, self.signed_hash().as_bytes()))
+    }
+
+    /// true if the header is signed and every provenance is a valid signature of its source
+    /// whether the sources are keys of the agents they claim to be is for agent::devices to tell
+    #[cfg(feature = "native")]
+    pub fn verify_provenances(&self) -> bool {
+        let signed_hash = self.signed_hash();
+        !self.provenances.is_empty()
+            && self
+                .provenances
+                .iter()
+                .all(|provenance| provenance.verify(signed_hash.as_bytes()))
     }
 
     /// returns true if the header is valid
@@ -117,14 +197,139 @@ impl Header {
     }
 }
 
+/// days since the unix epoch of a date of the Gregorian calendar
+fn days_from_civil(year: i64, month: u64, day: u64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = (year - era * 400) as u64;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era as i64 - 719_468
+}
+
+/// the date of the Gregorian calendar days after the unix epoch
+fn civil_from_days(days: u64) -> (u64, u64, u64) {
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days - era * 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = (shifted_month + 2) % 12 + 1;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+/// seconds since the unix epoch by the host clock, what headers are timed at and the sys_time
+/// host function tells zomes
+pub fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// secs since the unix epoch in ISO8601, in UTC to the second, e.g. "2023-11-14T22:13:20Z"
+pub fn iso8601(secs: u64) -> String {
+    let (year, month, day) = civil_from_days(secs / 86_400);
+    let secs_of_day = secs % 86_400;
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60
+    )
+}
+
+/// the number the ASCII digits spell, None if there are none or something else
+fn digits(text: Option<&str>) -> Option<u64> {
+    text.filter(|text| !text.is_empty() && text.bytes().all(|byte| byte.is_ascii_digit()))
+        .and_then(|text| text.parse().ok())
+}
+
+/// seconds since the unix epoch of an ISO8601 time, with a UTC offset or Z, fractions of a second
+/// dropped, None for other times or times before the epoch
+fn parse_iso8601(time: &str) -> Option<u64> {
+    let separators = [(4, b'-'), (7, b'-'), (10, b'T'), (13, b':'), (16, b':')];
+    if !separators
+        .iter()
+        .all(|&(at, separator)| time.as_bytes().get(at) == Some(&separator))
+    {
+        return None;
+    }
+    let year = digits(time.get(0..4))?;
+    let (month, day) = (digits(time.get(5..7))?, digits(time.get(8..10))?);
+    let (hour, minute) = (digits(time.get(11..13))?, digits(time.get(14..16))?);
+    let second = digits(time.get(17..19))?;
+    let valid = (1..=12).contains(&month) && (1..=31).contains(&day);
+    if !valid || hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    let mut zone = time.get(19..)?;
+    if zone.starts_with('.') {
+        let fraction = zone[1..].bytes().take_while(|byte| byte.is_ascii_digit()).count();
+        if fraction == 0 {
+            return None;
+        }
+        zone = &zone[1 + fraction..];
+    }
+    let offset = match zone.as_bytes() {
+        b"Z" => 0,
+        &[sign, _, _, b':', _, _] if sign == b'+' || sign == b'-' => {
+            let offset = (digits(zone.get(1..3))? * 60 + digits(zone.get(4..6))?) as i64 * 60;
+            if sign == b'+' {
+                offset
+            } else {
+                -offset
+            }
+        }
+        _ => return None,
+    };
+    let secs = days_from_civil(year as i64, month, day) * 86_400
+        + (hour * 3600 + minute * 60 + second) as i64
+        - offset;
+    if secs < 0 {
+        None
+    } else {
+        Some(secs as u64)
+    }
+}
+
 #[cfg(test)]
-mod tests {
-    use chain::tests::test_chain;
-    use hash_table::{entry::Entry, header::Header, pair::tests::test_pair};
+pub mod tests {
+    use super::{iso8601, parse_iso8601, unix_now};
+    use chain::{tests::test_chain, ChainRead, ChainWrite};
+    use hash_table::{
+        entry::Entry, header::Header, pair::tests::test_pair,
+        provenance::{tests::test_provenance, Provenance},
+    };
+    #[cfg(feature = "native")]
+    use hash_table::provenance::tests::{test_agent_address, test_signing_keys};
+    use serde_json;
+    use std::sync::Arc;
 
     /// returns a dummy header for use in tests
     pub fn test_header() -> Header {
-        test_pair().header()
+        test_pair().header().clone()
+    }
+
+    /// a header for the entry on the chain timed at the epoch, so headers built in a test compare
+    /// alike whatever the clock says between them
+    pub fn test_timed_header(chain: &dyn ChainRead, entry: &Entry) -> Header {
+        Header::new(chain, entry).with_time(0)
+    }
+
+    /// a dummy header without a time, as headers were written before they were timed
+    pub fn test_untimed_header() -> Header {
+        Header {
+            time: Arc::from(""),
+            ..test_header()
+        }
     }
 
     #[test]
@@ -136,22 +341,22 @@ mod tests {
         let t1 = "a";
         let t2 = "b";
 
-        // same content + type + state is equal
+        // same content + type + state + time is equal
         assert_eq!(
-            Header::new(&chain1, &Entry::new(t1, c1)),
-            Header::new(&chain1, &Entry::new(t1, c1))
+            test_timed_header(&chain1, &Entry::new(t1, c1)),
+            test_timed_header(&chain1, &Entry::new(t1, c1))
         );
 
         // different content is different
         assert_ne!(
-            Header::new(&chain1, &Entry::new(t1, c1)),
-            Header::new(&chain1, &Entry::new(t1, c2))
+            test_timed_header(&chain1, &Entry::new(t1, c1)),
+            test_timed_header(&chain1, &Entry::new(t1, c2))
         );
 
         // different type is different
         assert_ne!(
-            Header::new(&chain1, &Entry::new(t1, c1)),
-            Header::new(&chain1, &Entry::new(t2, c1)),
+            test_timed_header(&chain1, &Entry::new(t1, c1)),
+            test_timed_header(&chain1, &Entry::new(t2, c1)),
         );
 
         // different state is different
@@ -159,7 +364,11 @@ mod tests {
         let e = Entry::new(t1, c1);
         chain2.push(&e).unwrap();
 
-        assert_ne!(Header::new(&chain1, &e), Header::new(&chain2, &e));
+        assert_ne!(test_timed_header(&chain1, &e), test_timed_header(&chain2, &e));
+
+        // different time is different
+        let h = test_timed_header(&chain1, &e);
+        assert_ne!(h, h.with_time(1));
     }
 
     #[test]
@@ -193,9 +402,13 @@ mod tests {
         let chain = test_chain();
         let t = "foo";
         let e = Entry::new(t, "");
+        let before = unix_now();
         let h = Header::new(&chain, &e);
 
-        assert_eq!(h.time(), "");
+        // timed by the host clock
+        let time = h.timestamp().expect("headers are timed");
+        assert!(before <= time && time <= unix_now());
+        assert_eq!(h.time(), iso8601(time));
     }
 
     #[test]
@@ -216,7 +429,7 @@ mod tests {
         let p2 = chain.push(&e2).unwrap();
         let h2 = p2.header();
 
-        assert_eq!(h2.next(), Some(h1.hash()));
+        assert_eq!(h2.next(), Some(h1.hash().as_str()));
     }
 
     #[test]
@@ -258,19 +471,97 @@ mod tests {
         let p3 = chain.push(&e3).unwrap();
         let h3 = p3.header();
 
-        assert_eq!(h3.type_next(), Some(h1.hash()));
+        assert_eq!(h3.type_next(), Some(h1.hash().as_str()));
     }
 
     #[test]
-    /// tests for header.signature()
-    fn signature() {
+    /// tests for header.provenances() and header.with_provenance()
+    fn provenances() {
         let chain = test_chain();
         let t = "foo";
 
         let e = Entry::new(t, "");
         let h = Header::new(&chain, &e);
+        assert!(h.provenances().is_empty());
+
+        let countersigner = Provenance::new("other node id", "other signature");
+        let signed = h
+            .with_provenance(test_provenance())
+            .with_provenance(countersigner.clone());
+        assert_eq!(&[test_provenance(), countersigner], signed.provenances());
+        assert_eq!(h.entry(), signed.entry());
+        assert_ne!(h.hash(), signed.hash());
+        assert!(h.provenances().is_empty());
+    }
 
-        assert_eq!("", h.signature());
+    #[test]
+    /// provenances survive serialization, headers serialized without any load unsigned
+    fn provenances_json() {
+        let chain = test_chain();
+        let h = Header::new(&chain, &Entry::new("foo", "")).with_provenance(test_provenance());
+        let json = serde_json::to_string(&h).unwrap();
+        assert!(json.ends_with("\"provenances\":[[\"test node id\",\"test signature\"]]}"));
+        assert_eq!(h, serde_json::from_str(&json).unwrap());
+
+        let unsigned: Header = serde_json::from_str(
+            "{\"entry_type\":\"foo\",\"time\":\"\",\"next\":null,\"entry\":\"Qm\",\
+             \"type_next\":null,\"signature\":\"\"}",
+        ).unwrap();
+        assert!(unsigned.provenances().is_empty());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    /// countersigners sign the same hash, any forged provenance fails the header
+    fn verify_provenances() {
+        let chain = test_chain();
+        let h = Header::new(&chain, &Entry::new("foo", ""));
+        // unsigned headers are nobody's
+        assert!(!h.verify_provenances());
+
+        let signed = h
+            .sign(&test_signing_keys("alice"))
+            .sign(&test_signing_keys("bob"));
+        assert_eq!(h.signed_hash(), signed.signed_hash());
+        assert_eq!(test_agent_address("bob"), signed.provenances()[1].source());
+        assert!(signed.verify_provenances());
+
+        assert!(!signed.with_provenance(test_provenance()).verify_provenances());
+        // alice's signature of another header
+        let other = Header::new(&chain, &Entry::new("bar", "")).sign(&test_signing_keys("alice"));
+        assert!(!h.with_provenance(other.provenances()[0].clone()).verify_provenances());
+    }
+
+    #[test]
+    /// times go to ISO8601 and back
+    fn times() {
+        assert_eq!("1970-01-01T00:00:00Z", iso8601(0));
+        assert_eq!("2000-02-29T00:00:00Z", iso8601(951_782_400));
+        assert_eq!("2023-11-14T22:13:20Z", iso8601(1_700_000_000));
+        for secs in &[0, 951_782_400, 1_700_000_000, 4_102_444_800] {
+            assert_eq!(Some(*secs), parse_iso8601(&iso8601(*secs)));
+        }
+        assert_eq!(Some(1_700_000_000), parse_iso8601("2023-11-14T23:13:20+01:00"));
+        assert_eq!(Some(1_700_000_000), parse_iso8601("2023-11-14T20:13:20-02:00"));
+        assert_eq!(Some(1_700_000_000), parse_iso8601("2023-11-14T22:13:20.250Z"));
+        for time in &[
+            "",
+            "yesterday",
+            "2023-11-14",
+            "2023-13-14T22:13:20Z",
+            "2023-11-14T22:13:20",
+            "2023-11-14T22:13:20.Z",
+            "1969-12-31T23:59:59Z",
+            "2023-11-14T22:13:20+0100",
+        ] {
+            assert_eq!(None, parse_iso8601(time), "{}", time);
+        }
+
+        let header = test_untimed_header();
+        assert_eq!(None, header.timestamp());
+        let timed = header.with_time(1_700_000_000);
+        assert_eq!(Some(1_700_000_000), timed.timestamp());
+        assert_ne!(header, timed);
     }
 
     #[test]
@@ -279,9 +570,12 @@ mod tests {
         let chain = test_chain();
         let t = "foo";
 
-        // check a known hash
+        // check a known hash, without the time that changes with the clock
         let e = Entry::new(t, "");
-        let h = Header::new(&chain, &e);
+        let h = Header {
+            time: Arc::from(""),
+            ..Header::new(&chain, &e)
+        };
 
         assert_eq!("QmSpmouzp7PoTFeEcrG1GWVGVneacJcuwU91wkDCGYvPZ9", h.hash());
     }
@@ -294,16 +588,16 @@ mod tests {
 
         // different entries must return different hashes
         let e1 = Entry::new(t, "");
-        let h1 = Header::new(&chain, &e1);
+        let h1 = test_timed_header(&chain, &e1);
 
         let e2 = Entry::new(t, "a");
-        let h2 = Header::new(&chain, &e2);
+        let h2 = test_timed_header(&chain, &e2);
 
         assert_ne!(h1.hash(), h2.hash());
 
         // same entry must return same hash
         let e3 = Entry::new(t, "");
-        let h3 = Header::new(&chain, &e3);
+        let h3 = test_timed_header(&chain, &e3);
 
         assert_eq!(h1.hash(), h3.hash());
     }
@@ -334,14 +628,15 @@ mod tests {
         let t = "foo";
         let c = "bar";
         let e = Entry::new(t, c);
-        let h = Header::new(&chain, &e);
+        let h = test_timed_header(&chain, &e);
 
         let p1 = chain.push(&e).unwrap();
         // p2 will have a different hash to p1 with the same entry as the chain state is different
         let p2 = chain.push(&e).unwrap();
 
-        assert_eq!(h.hash(), p1.header().hash());
-        assert_ne!(h.hash(), p2.header().hash());
+        // the pushed headers are timed by the clock, compare them at the same time
+        assert_eq!(h.hash(), p1.header().with_time(0).hash());
+        assert_ne!(h.hash(), p2.header().with_time(0).hash());
     }
 
     #[test]
@@ -4,13 +4,17 @@ use error::HolochainError;
 
 use agent::keys::Keys;
 use hash_table::{
-    pair::Pair, pair_meta::PairMeta, status::{CRUDStatus, LINK_NAME, STATUS_NAME}, HashTable,
+    field_index::{self, FieldIndex, IndexQuery, IndexedFields}, pair::Pair, pair_meta::PairMeta,
+    status::{CRUDStatus, LINK_NAME, STATUS_NAME}, HashTable,
 };
 
 #[derive(Serialize, Debug, Clone, PartialEq)]
 pub struct MemTable {
     pairs: HashMap<String, Pair>,
     meta: HashMap<String, PairMeta>,
+    /// the declared fields of the Pairs, see set_indexed_fields()
+    #[serde(skip)]
+    index: FieldIndex,
 }
 
 impl MemTable {
@@ -18,6 +22,7 @@ impl MemTable {
         MemTable {
             pairs: HashMap::new(),
             meta: HashMap::new(),
+            index: FieldIndex::default(),
         }
     }
 }
@@ -32,12 +37,13 @@ impl HashTable for MemTable {
     }
 
     fn commit(&mut self, pair: &Pair) -> Result<(), HolochainError> {
+        self.index.insert(&pair.key(), pair.entry());
         self.pairs.insert(pair.key(), pair.clone());
         Ok(())
     }
 
     fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError> {
-        Ok(self.pairs.get(key.into()).and_then(|p| Some(p.clone())))
+        Ok(self.pairs.get(key).cloned())
     }
 
     fn modify(
@@ -77,6 +83,17 @@ impl HashTable for MemTable {
         ))
     }
 
+    fn keys(&self) -> Result<Vec<String>, HolochainError> {
+        Ok(self.pairs.keys().cloned().collect())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError> {
+        if let Some(pair) = self.pairs.remove(key) {
+            self.index.remove(key, pair.entry());
+        }
+        Ok(())
+    }
+
     fn assert_meta(&mut self, meta: &PairMeta) -> Result<(), HolochainError> {
         self.meta.insert(meta.key(), meta.clone());
         Ok(())
@@ -98,13 +115,38 @@ impl HashTable for MemTable {
         metas.sort();
         Ok(metas)
     }
+
+    fn set_indexed_fields(&mut self, fields: &IndexedFields) -> Result<(), HolochainError> {
+        let mut index = FieldIndex::new(fields);
+        for (key, pair) in &self.pairs {
+            index.insert(key, pair.entry());
+        }
+        self.index = index;
+        Ok(())
+    }
+
+    fn query_index(
+        &self,
+        entry_type: &str,
+        path: &str,
+        query: &IndexQuery,
+    ) -> Result<Vec<String>, HolochainError> {
+        match self.index.query(entry_type, path, query) {
+            Some(keys) => Ok(keys),
+            None => field_index::scan(self, entry_type, path, query),
+        }
+    }
 }
 
 #[cfg(test)]
 pub mod tests {
 
     use agent::keys::tests::test_keys;
+    use chain::{tests::test_chain, ChainWrite};
     use hash_table::{
+        field_index::{
+            tests::{test_indexed_fields, test_profile}, IndexQuery, IndexValue,
+        },
         memory::MemTable, pair::tests::{test_pair, test_pair_a, test_pair_b},
         pair_meta::{
             tests::{test_pair_meta, test_pair_meta_a, test_pair_meta_b}, PairMeta,
@@ -194,6 +236,39 @@ pub mod tests {
         );
     }
 
+    #[test]
+    /// all Pair keys are listed by table.keys()
+    fn keys() {
+        let mut ht = test_table();
+        let p1 = test_pair_a();
+        let p2 = test_pair_b();
+        assert_eq!(Ok(Vec::new()), ht.keys());
+
+        ht.commit(&p1).unwrap();
+        ht.commit(&p2).unwrap();
+        let mut keys = ht.keys().unwrap();
+        keys.sort();
+        let mut expected = vec![p1.key(), p2.key()];
+        expected.sort();
+        assert_eq!(expected, keys);
+    }
+
+    #[test]
+    /// Pairs can be dropped through table.remove()
+    fn remove() {
+        let mut ht = test_table();
+        let p = test_pair();
+
+        ht.commit(&p).unwrap();
+        assert_eq!(Some(p.clone()), ht.get(&p.key()).unwrap());
+
+        ht.remove(&p.key()).unwrap();
+        assert_eq!(None, ht.get(&p.key()).unwrap());
+
+        // removing something that isn't there is not an error
+        assert_eq!(Ok(()), ht.remove(&p.key()));
+    }
+
     #[test]
     /// PairMeta can round trip through table.assert_meta() and table.get_meta()
     fn meta_round_trip() {
@@ -223,4 +298,43 @@ pub mod tests {
         ht.assert_meta(&m2).unwrap();
         assert_eq!(vec![m2.clone(), m1.clone()], ht.get_pair_meta(&p).unwrap());
     }
+
+    #[test]
+    /// the declared fields are answered from the index, kept as Pairs come and go, others by
+    /// scanning
+    fn query_index() {
+        let mut chain = test_chain();
+        let alice = chain.push(&test_profile("alice", 30)).unwrap();
+        let bob = chain.push(&test_profile("bob", 17)).unwrap();
+        let mut ht = test_table();
+        ht.commit(&alice).unwrap();
+        ht.set_indexed_fields(&test_indexed_fields()).unwrap();
+        ht.commit(&bob).unwrap();
+
+        let handle = |h: &str| IndexQuery::Equals(IndexValue::Text(h.to_string()));
+        assert_eq!(
+            Ok(vec![alice.key()]),
+            ht.query_index("profile", "handle", &handle("alice"))
+        );
+        assert_eq!(
+            Ok(vec![bob.key()]),
+            ht.query_index("profile", "handle", &handle("bob"))
+        );
+        let young = IndexQuery::Range {
+            from: None,
+            to: Some(IndexValue::Int(20)),
+        };
+        assert_eq!(Ok(vec![bob.key()]), ht.query_index("profile", "about.age", &young));
+
+        ht.remove(&bob.key()).unwrap();
+        assert_eq!(Ok(Vec::new()), ht.query_index("profile", "handle", &handle("bob")));
+
+        // fields that aren't declared are found all the same
+        let mut undeclared = test_table();
+        undeclared.commit(&alice).unwrap();
+        assert_eq!(
+            Ok(vec![alice.key()]),
+            undeclared.query_index("profile", "handle", &handle("alice"))
+        );
+    }
 }
@@ -1,13 +1,19 @@
 pub mod entry;
+pub mod field_index;
 pub mod header;
 pub mod memory;
 pub mod pair;
 pub mod pair_meta;
+pub mod provenance;
+#[cfg(feature = "s3")]
+pub mod s3;
 pub mod status;
 
 use agent::keys::Keys;
 use error::HolochainError;
-use hash_table::{pair::Pair, pair_meta::PairMeta};
+use hash_table::{
+    field_index::{IndexQuery, IndexedFields}, pair::Pair, pair_meta::PairMeta,
+};
 
 pub trait HashTable {
     // internal state management
@@ -17,6 +23,21 @@ pub trait HashTable {
     // crud
     /// add a Pair to the HashTable, analogous to chain.push() but ordering is not enforced
     fn commit(&mut self, pair: &Pair) -> Result<(), HolochainError>;
+    /// add many Pairs to the HashTable in a single all-or-nothing transaction
+    /// implementations backed by durable storage should override this to write (and sync) the
+    /// whole batch at once, the default commits one at a time and removes what it already
+    /// committed if any commit fails
+    fn commit_batch(&mut self, pairs: &[Pair]) -> Result<(), HolochainError> {
+        for (i, pair) in pairs.iter().enumerate() {
+            if let Err(e) = self.commit(pair) {
+                for committed in &pairs[..i] {
+                    self.remove(&committed.key())?;
+                }
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
     /// lookup a Pair from the HashTable by Pair/Header key
     fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError>;
     /// add a new Pair to the HashTable as per commit and status link an old Pair as MODIFIED
@@ -28,6 +49,11 @@ pub trait HashTable {
     ) -> Result<(), HolochainError>;
     /// set the status of a Pair to DELETED
     fn retract(&mut self, keys: &Keys, pair: &Pair) -> Result<(), HolochainError>;
+    /// keys of every Pair in the HashTable, in no particular order
+    fn keys(&self) -> Result<Vec<String>, HolochainError>;
+    /// physically drop a Pair from the HashTable by Pair/Header key
+    /// unlike retract() this leaves no trace, it is for internal storage management only
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError>;
 
     // meta
     /// assert a given PairMeta in the HashTable
@@ -38,7 +64,21 @@ pub trait HashTable {
     fn get_pair_meta(&mut self, pair: &Pair) -> Result<Vec<PairMeta>, HolochainError>;
 
     // query
-    // @TODO how should we handle queries?
-    // @see https://github.com/holochain/holochain-rust/issues/141
-    // fn query (&self, query: &Query) -> Result<std::collections::HashSet, HolochainError>;
+    /// index the declared fields of the content of the Pairs, see field_index
+    /// the default keeps no index and leaves query_index() to scan
+    fn set_indexed_fields(&mut self, _fields: &IndexedFields) -> Result<(), HolochainError> {
+        Ok(())
+    }
+    /// keys of the Pairs of entry_type with a value at path matching the query, by value then
+    /// key
+    /// implementations keeping a FieldIndex answer the fields declared with set_indexed_fields()
+    /// from it, the default scans every Pair
+    fn query_index(
+        &self,
+        entry_type: &str,
+        path: &str,
+        query: &IndexQuery,
+    ) -> Result<Vec<String>, HolochainError> {
+        field_index::scan(self, entry_type, path, query)
+    }
 }
@@ -1,5 +1,5 @@
-use chain::Chain;
-use hash_table::{entry::Entry, header::Header, HashTable};
+use chain::ChainRead;
+use hash_table::{entry::Entry, header::Header};
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Pair {
@@ -17,11 +17,14 @@ impl Pair {
     /// now be valid, the new Y' will include correct headers pointing to X.
     /// @see chain::entry::Entry
     /// @see chain::header::Header
-    pub fn new<T: HashTable>(chain: &Chain<T>, entry: &Entry) -> Pair {
-        let header = Header::new(chain, entry);
+    pub fn new<C: ChainRead + ?Sized>(chain: &C, entry: &Entry) -> Pair {
+        Pair::from_header(Header::new(chain, entry), entry)
+    }
 
+    /// build a new Pair from an already generated Header and its Entry
+    pub(crate) fn from_header(header: Header, entry: &Entry) -> Pair {
         let p = Pair {
-            header: header.clone(),
+            header,
             entry: entry.clone(),
         };
 
@@ -36,13 +39,13 @@ impl Pair {
     }
 
     /// header getter
-    pub fn header(&self) -> Header {
-        self.header.clone()
+    pub fn header(&self) -> &Header {
+        &self.header
     }
 
     /// entry getter
-    pub fn entry(&self) -> Entry {
-        self.entry.clone()
+    pub fn entry(&self) -> &Entry {
+        &self.entry
     }
 
     /// key used in hash table lookups and other references
@@ -64,16 +67,16 @@ impl Pair {
 #[cfg(test)]
 pub mod tests {
     use super::Pair;
-    use chain::tests::test_chain;
+    use chain::{tests::test_chain, ChainWrite};
     use hash_table::{
         entry::{
             tests::{test_entry, test_entry_b}, Entry,
-        }, header::Header,
+        }, header::{tests::test_timed_header, Header},
     };
 
-    /// dummy pair
+    /// dummy pair, timed at the epoch so every one is the same
     pub fn test_pair() -> Pair {
-        Pair::new(&test_chain(), &test_entry())
+        Pair::from_header(test_timed_header(&test_chain(), &test_entry()), &test_entry())
     }
 
     /// dummy pair, same as test_pair()
@@ -83,7 +86,7 @@ pub mod tests {
 
     /// dummy pair, differs from test_pair()
     pub fn test_pair_b() -> Pair {
-        Pair::new(&test_chain(), &test_entry_b())
+        Pair::from_header(test_timed_header(&test_chain(), &test_entry_b()), &test_entry_b())
     }
 
     #[test]
@@ -98,8 +101,8 @@ pub mod tests {
         assert_eq!(h1.next(), None);
 
         let p1 = Pair::new(&chain, &e1);
-        assert_eq!(e1, p1.entry());
-        assert_eq!(h1, p1.header());
+        assert_eq!(&e1, p1.entry());
+        assert_eq!(&h1, p1.header());
     }
 
     #[test]
@@ -112,7 +115,7 @@ pub mod tests {
         let h = Header::new(&chain, &e);
         let p = Pair::new(&chain, &e);
 
-        assert_eq!(h, p.header());
+        assert_eq!(&h, p.header());
     }
 
     #[test]
@@ -123,7 +126,7 @@ pub mod tests {
         let e = Entry::new(t, "");
         let p = chain.push(&e).unwrap();
 
-        assert_eq!(e, p.entry());
+        assert_eq!(&e, p.entry());
     }
 
     #[test]
@@ -4,7 +4,7 @@ use hash_table::pair::Pair;
 use multihash::Hash;
 use std::cmp::Ordering;
 
-#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 /// PairMeta represents an extended form of EAV (entity-attribute-value) data
 /// E = the pair key for hash table lookups
 /// A = the name of the meta attribute
@@ -178,8 +178,9 @@ pub mod tests {
     #[test]
     /// test that we can sort pair metas with cmp
     fn cmp() {
-        let p1 = test_pair_a();
-        let p2 = test_pair_b();
+        // pair keys hash the time the headers were made at, so order them first
+        let (a, b) = (test_pair_a(), test_pair_b());
+        let (p1, p2) = if a.key() < b.key() { (a, b) } else { (b, a) };
 
         // basic ordering
         let m_1ax = PairMeta::new(&test_keys(), &p1, "a", "x");
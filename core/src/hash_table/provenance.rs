@@ -0,0 +1,114 @@
+#[cfg(feature = "native")]
+use network::sealing::{SigningKeyPair, SigningPublicKey};
+#[cfg(feature = "native")]
+use rust_base58::{FromBase58, ToBase58};
+
+/// address of an agent, i.e. the node id of its keys
+pub type AgentAddress = String;
+
+/// an agent's signature, base58
+pub type Signature = String;
+
+/// who vouches for a header: the address of the agent that signed it and the signature to check
+/// against the agent's key
+/// countersigned entries have a Provenance per signing agent
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Provenance(pub AgentAddress, pub Signature);
+
+impl Provenance {
+    pub fn new(source: &str, signature: &str) -> Provenance {
+        Provenance(source.to_string(), signature.to_string())
+    }
+
+    /// address of the signing agent
+    pub fn source(&self) -> &str {
+        &self.0
+    }
+
+    pub fn signature(&self) -> &str {
+        &self.1
+    }
+
+    /// the provenance of the holder of keys for the message, its source is the base58 public key
+    #[cfg(feature = "native")]
+    pub fn sign(keys: &SigningKeyPair, message: &[u8]) -> Provenance {
+        Provenance::new(&keys.public().to_base58(), &keys.sign(message).to_base58())
+    }
+
+    /// true if the signature is the source's for the message
+    /// false if the source isn't a base58 signing key or the signature isn't base58
+    #[cfg(feature = "native")]
+    pub fn verify(&self, message: &[u8]) -> bool {
+        match (
+            SigningPublicKey::from_base58(self.source()),
+            self.signature().from_base58(),
+        ) {
+            (Ok(key), Ok(signature)) => key.verify(message, &signature),
+            _ => false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::Provenance;
+    #[cfg(feature = "native")]
+    use network::sealing::SigningKeyPair;
+    use serde_json;
+
+    /// returns a dummy provenance for use in tests
+    pub fn test_provenance() -> Provenance {
+        Provenance::new("test node id", "test signature")
+    }
+
+    /// the signing keys of a named test agent, e.g. "alice"
+    #[cfg(feature = "native")]
+    pub fn test_signing_keys(name: &str) -> SigningKeyPair {
+        SigningKeyPair::derive(&[0; 32], name)
+    }
+
+    /// the address of a named test agent, the public key its provenances verify against
+    #[cfg(feature = "native")]
+    pub fn test_agent_address(name: &str) -> String {
+        test_signing_keys(name).public().to_base58()
+    }
+
+    #[test]
+    /// tests for the getters
+    fn getters() {
+        let provenance = test_provenance();
+        assert_eq!("test node id", provenance.source());
+        assert_eq!("test signature", provenance.signature());
+    }
+
+    #[test]
+    #[cfg(feature = "native")]
+    /// signatures verify against the source for the signed message only
+    fn sign_and_verify() {
+        let provenance = Provenance::sign(&test_signing_keys("alice"), b"message");
+        assert_eq!(test_agent_address("alice"), provenance.source());
+        assert!(provenance.verify(b"message"));
+        assert!(!provenance.verify(b"other message"));
+
+        // someone else's signature claimed by alice
+        let forged = Provenance::new(
+            &test_agent_address("alice"),
+            Provenance::sign(&test_signing_keys("bob"), b"message").signature(),
+        );
+        assert!(!forged.verify(b"message"));
+
+        assert!(!test_provenance().verify(b"message"));
+        assert!(!Provenance::new(provenance.source(), "not base58 0OIl").verify(b"message"));
+    }
+
+    #[test]
+    /// provenances serialize as an (agent address, signature) tuple
+    fn json_round_trip() {
+        let json = serde_json::to_string(&test_provenance()).unwrap();
+        assert_eq!("[\"test node id\",\"test signature\"]", json);
+        assert_eq!(
+            test_provenance(),
+            serde_json::from_str::<Provenance>(&json).unwrap()
+        );
+    }
+}
@@ -0,0 +1,309 @@
+//! ObjectStore speaking the S3 REST API, for AWS S3 itself or any compatible store, e.g. MinIO
+//! requests are signed with the access key as presigned URLs and sent over blocking HTTP, so the
+//! S3Table stays synchronous like every other HashTable
+
+use error::HolochainError;
+use hash_table::s3::ObjectStore;
+use rusty_s3::{actions::ListObjectsV2, Bucket, Credentials, S3Action, UrlStyle};
+use std::{io::Read, time::Duration};
+use ureq;
+use url::Url;
+
+/// how long the presigned URL of a request is valid for, only long enough to send it
+const S3_SIGNATURE_EXPIRY_SECS: u64 = 60;
+
+/// where the bucket is and how to sign for it
+#[derive(Clone, Debug, PartialEq)]
+pub struct S3Config {
+    /// e.g. "https://s3.eu-west-1.amazonaws.com" or "http://localhost:9000" for MinIO
+    pub endpoint: String,
+    pub bucket: String,
+    /// e.g. "eu-west-1", part of every signature
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+    /// address the bucket in the path rather than as a subdomain of the endpoint, which most
+    /// compatible stores need
+    pub path_style: bool,
+}
+
+/// ObjectStore of the objects of a bucket
+pub struct S3ObjectStore {
+    bucket: Bucket,
+    credentials: Credentials,
+    agent: ureq::Agent,
+}
+
+fn s3_error(action: &str, key: &str, error: &ureq::Error) -> HolochainError {
+    HolochainError::new(&format!("S3 {} of '{}' failed: {}", action, key, error))
+}
+
+impl S3ObjectStore {
+    pub fn new(config: &S3Config) -> Result<S3ObjectStore, HolochainError> {
+        let endpoint = Url::parse(&config.endpoint).map_err(|e| {
+            HolochainError::new(&format!("S3 endpoint '{}': {}", config.endpoint, e))
+        })?;
+        let style = if config.path_style {
+            UrlStyle::Path
+        } else {
+            UrlStyle::VirtualHost
+        };
+        let bucket = Bucket::new(endpoint, style, config.bucket.clone(), config.region.clone())
+            .map_err(|e| HolochainError::new(&format!("S3 bucket '{}': {}", config.bucket, e)))?;
+        Ok(S3ObjectStore {
+            bucket,
+            credentials: Credentials::new(config.access_key.clone(), config.secret_key.clone()),
+            agent: ureq::Agent::new(),
+        })
+    }
+
+    fn expiry() -> Duration {
+        Duration::from_secs(S3_SIGNATURE_EXPIRY_SECS)
+    }
+}
+
+impl ObjectStore for S3ObjectStore {
+    fn put_object(&mut self, key: &str, bytes: &[u8]) -> Result<(), HolochainError> {
+        let url = self
+            .bucket
+            .put_object(Some(&self.credentials), key)
+            .sign(S3ObjectStore::expiry());
+        self.agent
+            .put(url.as_str())
+            .send_bytes(bytes)
+            .map_err(|e| s3_error("put", key, &e))?;
+        Ok(())
+    }
+
+    fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, HolochainError> {
+        let url = self
+            .bucket
+            .get_object(Some(&self.credentials), key)
+            .sign(S3ObjectStore::expiry());
+        let response = match self.agent.get(url.as_str()).call() {
+            Ok(response) => response,
+            Err(ureq::Error::Status(404, _)) => return Ok(None),
+            Err(e) => return Err(s3_error("get", key, &e)),
+        };
+        let mut bytes = Vec::new();
+        response
+            .into_reader()
+            .read_to_end(&mut bytes)
+            .map_err(|e| HolochainError::new(&format!("S3 get of '{}' failed: {}", key, e)))?;
+        Ok(Some(bytes))
+    }
+
+    fn delete_object(&mut self, key: &str) -> Result<(), HolochainError> {
+        let url = self
+            .bucket
+            .delete_object(Some(&self.credentials), key)
+            .sign(S3ObjectStore::expiry());
+        match self.agent.delete(url.as_str()).call() {
+            Ok(_) | Err(ureq::Error::Status(404, _)) => Ok(()),
+            Err(e) => Err(s3_error("delete", key, &e)),
+        }
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, HolochainError> {
+        let mut keys = Vec::new();
+        let mut continuation: Option<String> = None;
+        loop {
+            let mut action = self.bucket.list_objects_v2(Some(&self.credentials));
+            // keys come back as they are rather than url encoded
+            action.query_mut().remove("encoding-type");
+            action.with_prefix(prefix);
+            if let Some(ref token) = continuation {
+                action.with_continuation_token(token.as_str());
+            }
+            let url = action.sign(S3ObjectStore::expiry());
+            let body = self
+                .agent
+                .get(url.as_str())
+                .call()
+                .map_err(|e| s3_error("list", prefix, &e))?
+                .into_string()
+                .map_err(|e| HolochainError::new(&format!("S3 list of '{}': {}", prefix, e)))?;
+            let page = ListObjectsV2::parse_response(&body)
+                .map_err(|e| HolochainError::new(&format!("S3 list of '{}': {}", prefix, e)))?;
+            keys.extend(page.contents.into_iter().map(|object| object.key));
+            continuation = page.next_continuation_token;
+            if continuation.is_none() {
+                break;
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{S3Config, S3ObjectStore};
+    use hash_table::s3::{ObjectStore, S3Table};
+    use hash_table::{pair::tests::test_pair, HashTable};
+    use std::{
+        collections::BTreeMap, io::{BufRead, BufReader, Read, Write}, net::{TcpListener, TcpStream},
+        sync::{Arc, Mutex}, thread,
+    };
+
+    type Objects = Arc<Mutex<BTreeMap<String, Vec<u8>>>>;
+
+    /// the query parameter of a request target
+    fn query_param(target: &str, name: &str) -> Option<String> {
+        let query = target.splitn(2, '?').nth(1)?;
+        query
+            .split('&')
+            .filter_map(|pair| {
+                let mut parts = pair.splitn(2, '=');
+                Some((parts.next()?, parts.next().unwrap_or("")))
+            })
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.replace("%2F", "/"))
+    }
+
+    /// answer a request the way S3 does, listing one key per page to exercise continuation
+    fn respond(objects: &Objects, method: &str, target: &str, body: Vec<u8>) -> (u16, Vec<u8>) {
+        let path = target.splitn(2, '?').next().unwrap();
+        let key = path.splitn(3, '/').nth(2).unwrap_or("").to_string();
+        let mut objects = objects.lock().unwrap();
+        match method {
+            "PUT" => {
+                objects.insert(key, body);
+                (200, Vec::new())
+            }
+            "DELETE" => {
+                objects.remove(&key);
+                (204, Vec::new())
+            }
+            "GET" if key.is_empty() => {
+                let prefix = query_param(target, "prefix").unwrap_or_default();
+                let after = query_param(target, "continuation-token").unwrap_or_default();
+                let mut matching = objects
+                    .keys()
+                    .filter(|k| k.starts_with(&prefix) && **k > after)
+                    .take(2);
+                let mut xml = "<ListBucketResult>".to_string();
+                if let Some(k) = matching.next() {
+                    xml += &format!(
+                        "<Contents><Key>{}</Key><LastModified>2018-07-01T00:00:00.000Z\
+                         </LastModified><ETag>\"0\"</ETag><Size>0</Size></Contents>",
+                        k
+                    );
+                    if matching.next().is_some() {
+                        xml += &format!("<NextContinuationToken>{}</NextContinuationToken>", k);
+                    }
+                }
+                xml += "</ListBucketResult>";
+                (200, xml.into_bytes())
+            }
+            "GET" => match objects.get(&key) {
+                Some(bytes) => (200, bytes.clone()),
+                None => (404, b"<Error><Code>NoSuchKey</Code></Error>".to_vec()),
+            },
+            _ => (405, Vec::new()),
+        }
+    }
+
+    fn serve(stream: TcpStream, objects: &Objects) {
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut stream = stream;
+        loop {
+            let mut request_line = String::new();
+            if reader.read_line(&mut request_line).unwrap_or(0) == 0 {
+                return;
+            }
+            let mut parts = request_line.split_whitespace();
+            let (method, target) = (parts.next().unwrap(), parts.next().unwrap());
+            let mut length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                let lower = header.to_lowercase();
+                if lower.starts_with("content-length:") {
+                    length = lower[15..].trim().parse().unwrap();
+                }
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            let (status, body) = respond(objects, method, target, body);
+            write!(
+                stream,
+                "HTTP/1.1 {} S3\r\nContent-Length: {}\r\n\r\n",
+                status,
+                body.len()
+            ).unwrap();
+            stream.write_all(&body).unwrap();
+        }
+    }
+
+    /// a fake S3 on localhost, keeping the objects of every bucket in one map
+    pub fn test_s3_server() -> (S3Config, Objects) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let config = S3Config {
+            endpoint: format!("http://{}", listener.local_addr().unwrap()),
+            bucket: "holochain".to_string(),
+            region: "test".to_string(),
+            access_key: "alice".to_string(),
+            secret_key: "secret".to_string(),
+            path_style: true,
+        };
+        let objects = Objects::default();
+        let served = Arc::clone(&objects);
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let objects = Arc::clone(&served);
+                thread::spawn(move || serve(stream.unwrap(), &objects));
+            }
+        });
+        (config, objects)
+    }
+
+    #[test]
+    fn objects() {
+        let (config, objects) = test_s3_server();
+        let mut store = S3ObjectStore::new(&config).unwrap();
+        assert_eq!(Ok(None), store.get_object("a/1"));
+        for key in &["a/1", "a/2", "a/3", "b/1"] {
+            store.put_object(key, key.as_bytes()).unwrap();
+        }
+        assert_eq!(Some(b"a/2".to_vec()), objects.lock().unwrap().get("a/2").cloned());
+        assert_eq!(Ok(Some(b"a/2".to_vec())), store.get_object("a/2"));
+        // listed over several pages
+        assert_eq!(
+            Ok(vec!["a/1".to_string(), "a/2".to_string(), "a/3".to_string()]),
+            store.list_objects("a/")
+        );
+        store.delete_object("a/2").unwrap();
+        store.delete_object("a/2").unwrap();
+        assert_eq!(Ok(None), store.get_object("a/2"));
+    }
+
+    #[test]
+    fn table() {
+        let (config, _) = test_s3_server();
+        let mut table = S3Table::new(S3ObjectStore::new(&config).unwrap(), "node/");
+        let pair = test_pair();
+        table.commit(&pair).unwrap();
+        table.clear_cache();
+        assert_eq!(Ok(Some(pair.clone())), table.get(&pair.key()));
+        assert_eq!(Ok(vec![pair.key()]), table.keys());
+    }
+
+    #[test]
+    fn bad_config() {
+        let (config, _) = test_s3_server();
+        let endpoint = S3Config {
+            endpoint: "not a url".to_string(),
+            ..config.clone()
+        };
+        assert!(S3ObjectStore::new(&endpoint).is_err());
+        let scheme = S3Config {
+            endpoint: "ftp://localhost".to_string(),
+            ..config
+        };
+        assert!(S3ObjectStore::new(&scheme).is_err());
+    }
+}
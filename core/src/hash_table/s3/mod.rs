@@ -0,0 +1,321 @@
+//! HashTable backed by an S3 compatible object store, for hosted nodes that need durable storage
+//! beyond a single disk
+//! every write goes through to the object store before landing in a local in memory cache, reads
+//! are served from the cache and fall back to the object store on a miss
+//! objects are kept in an ObjectStore, S3ObjectStore for a real bucket, see client, or
+//! MemObjectStore for tests
+//! only built with the `s3` cargo feature
+
+pub mod client;
+
+use agent::keys::Keys;
+use error::HolochainError;
+use hash_table::{
+    memory::MemTable, pair::Pair, pair_meta::PairMeta, status::{CRUDStatus, LINK_NAME, STATUS_NAME},
+    HashTable,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
+use std::{cell::RefCell, collections::BTreeMap};
+
+/// the subset of object store operations the S3Table needs
+pub trait ObjectStore {
+    fn put_object(&mut self, key: &str, bytes: &[u8]) -> Result<(), HolochainError>;
+    fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, HolochainError>;
+    fn delete_object(&mut self, key: &str) -> Result<(), HolochainError>;
+    /// keys of every object starting with prefix, sorted
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, HolochainError>;
+}
+
+/// in memory ObjectStore, e.g. for tests
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MemObjectStore {
+    objects: BTreeMap<String, Vec<u8>>,
+}
+
+impl MemObjectStore {
+    pub fn new() -> MemObjectStore {
+        MemObjectStore {
+            objects: BTreeMap::new(),
+        }
+    }
+}
+
+impl ObjectStore for MemObjectStore {
+    fn put_object(&mut self, key: &str, bytes: &[u8]) -> Result<(), HolochainError> {
+        self.objects.insert(key.to_string(), bytes.to_vec());
+        Ok(())
+    }
+
+    fn get_object(&self, key: &str) -> Result<Option<Vec<u8>>, HolochainError> {
+        Ok(self.objects.get(key).cloned())
+    }
+
+    fn delete_object(&mut self, key: &str) -> Result<(), HolochainError> {
+        self.objects.remove(key);
+        Ok(())
+    }
+
+    fn list_objects(&self, prefix: &str) -> Result<Vec<String>, HolochainError> {
+        Ok(self
+            .objects
+            .keys()
+            .filter(|key| key.starts_with(prefix))
+            .cloned()
+            .collect())
+    }
+}
+
+fn to_bytes<S: Serialize>(s: &S) -> Result<Vec<u8>, HolochainError> {
+    serde_json::to_vec(s).map_err(|e| HolochainError::new(&e.to_string()))
+}
+
+fn from_bytes<D: DeserializeOwned>(bytes: &[u8]) -> Result<D, HolochainError> {
+    serde_json::from_slice(bytes).map_err(|e| HolochainError::new(&e.to_string()))
+}
+
+/// HashTable storing Pairs and PairMeta as JSON objects under a key prefix in an ObjectStore
+/// layout:
+/// - `{prefix}pair/{pair key}`
+/// - `{prefix}meta/{meta key}`
+/// - `{prefix}pair_meta/{pair key}/{meta key}` so all the meta for a Pair can be listed
+pub struct S3Table<O: ObjectStore> {
+    store: O,
+    prefix: String,
+    cache: RefCell<MemTable>,
+}
+
+impl<O: ObjectStore> S3Table<O> {
+    pub fn new(store: O, prefix: &str) -> S3Table<O> {
+        S3Table {
+            store,
+            prefix: prefix.to_string(),
+            cache: RefCell::new(MemTable::new()),
+        }
+    }
+
+    /// drop everything from the local cache, the object store is untouched
+    pub fn clear_cache(&mut self) {
+        self.cache = RefCell::new(MemTable::new());
+    }
+
+    fn pair_key(&self, key: &str) -> String {
+        format!("{}pair/{}", self.prefix, key)
+    }
+
+    fn meta_key(&self, key: &str) -> String {
+        format!("{}meta/{}", self.prefix, key)
+    }
+
+    fn pair_meta_prefix(&self, pair_key: &str) -> String {
+        format!("{}pair_meta/{}/", self.prefix, pair_key)
+    }
+}
+
+impl<O: ObjectStore> HashTable for S3Table<O> {
+    fn setup(&mut self) -> Result<(), HolochainError> {
+        Ok(())
+    }
+
+    fn teardown(&mut self) -> Result<(), HolochainError> {
+        Ok(())
+    }
+
+    fn commit(&mut self, pair: &Pair) -> Result<(), HolochainError> {
+        let object_key = self.pair_key(&pair.key());
+        self.store.put_object(&object_key, &to_bytes(pair)?)?;
+        self.cache.borrow_mut().commit(pair)
+    }
+
+    fn get(&self, key: &str) -> Result<Option<Pair>, HolochainError> {
+        if let Some(pair) = self.cache.borrow().get(key)? {
+            return Ok(Some(pair));
+        }
+
+        let pair: Pair = match self.store.get_object(&self.pair_key(key))? {
+            Some(bytes) => from_bytes(&bytes)?,
+            None => return Ok(None),
+        };
+        // content addressing means the object store can't hand back the wrong thing unnoticed
+        if pair.key() != key || !pair.validate() {
+            return Err(HolochainError::new(&format!(
+                "object for pair {} failed integrity check",
+                key
+            )));
+        }
+        self.cache.borrow_mut().commit(&pair)?;
+        Ok(Some(pair))
+    }
+
+    fn modify(
+        &mut self,
+        keys: &Keys,
+        old_pair: &Pair,
+        new_pair: &Pair,
+    ) -> Result<(), HolochainError> {
+        self.commit(new_pair)?;
+
+        // @TODO what if meta fails when commit succeeds?
+        // @see https://github.com/holochain/holochain-rust/issues/142
+        self.assert_meta(&PairMeta::new(
+            keys,
+            old_pair,
+            STATUS_NAME,
+            &CRUDStatus::MODIFIED.bits().to_string(),
+        ))?;
+
+        // @TODO what if meta fails when commit succeeds?
+        // @see https://github.com/holochain/holochain-rust/issues/142
+        self.assert_meta(&PairMeta::new(keys, old_pair, LINK_NAME, &new_pair.key()))
+    }
+
+    fn retract(&mut self, keys: &Keys, pair: &Pair) -> Result<(), HolochainError> {
+        self.assert_meta(&PairMeta::new(
+            keys,
+            pair,
+            STATUS_NAME,
+            &CRUDStatus::DELETED.bits().to_string(),
+        ))
+    }
+
+    fn keys(&self) -> Result<Vec<String>, HolochainError> {
+        let prefix = self.pair_key("");
+        Ok(self
+            .store
+            .list_objects(&prefix)?
+            .into_iter()
+            .map(|object_key| object_key[prefix.len()..].to_string())
+            .collect())
+    }
+
+    fn remove(&mut self, key: &str) -> Result<(), HolochainError> {
+        let object_key = self.pair_key(key);
+        self.store.delete_object(&object_key)?;
+        self.cache.borrow_mut().remove(key)
+    }
+
+    fn assert_meta(&mut self, meta: &PairMeta) -> Result<(), HolochainError> {
+        let bytes = to_bytes(meta)?;
+        let meta_key = self.meta_key(&meta.key());
+        let pair_meta_key = format!("{}{}", self.pair_meta_prefix(&meta.pair()), meta.key());
+        self.store.put_object(&meta_key, &bytes)?;
+        self.store.put_object(&pair_meta_key, &bytes)?;
+        self.cache.borrow_mut().assert_meta(meta)
+    }
+
+    fn get_meta(&mut self, key: &str) -> Result<Option<PairMeta>, HolochainError> {
+        if let Some(meta) = self.cache.borrow_mut().get_meta(key)? {
+            return Ok(Some(meta));
+        }
+        match self.store.get_object(&self.meta_key(key))? {
+            Some(bytes) => {
+                let meta: PairMeta = from_bytes(&bytes)?;
+                self.cache.borrow_mut().assert_meta(&meta)?;
+                Ok(Some(meta))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn get_pair_meta(&mut self, pair: &Pair) -> Result<Vec<PairMeta>, HolochainError> {
+        // the cache can't know whether it has all the meta for a pair so always list
+        let mut metas = Vec::new();
+        for object_key in self.store.list_objects(&self.pair_meta_prefix(&pair.key()))? {
+            if let Some(bytes) = self.store.get_object(&object_key)? {
+                metas.push(from_bytes::<PairMeta>(&bytes)?);
+            }
+        }
+        // @TODO should this be sorted at all at this point?
+        // @see https://github.com/holochain/holochain-rust/issues/144
+        metas.sort();
+        Ok(metas)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+
+    use super::{MemObjectStore, ObjectStore, S3Table};
+    use agent::keys::tests::test_keys;
+    use hash_table::{
+        pair::tests::{test_pair, test_pair_a, test_pair_b},
+        pair_meta::{tests::test_pair_meta, PairMeta},
+        status::{CRUDStatus, LINK_NAME, STATUS_NAME}, HashTable,
+    };
+    use serde_json;
+
+    /// builds a dummy s3 table for testing
+    pub fn test_s3_table() -> S3Table<MemObjectStore> {
+        S3Table::new(MemObjectStore::new(), "test/")
+    }
+
+    #[test]
+    /// Pairs are written through to the object store
+    fn pair_round_trip() {
+        let mut ht = test_s3_table();
+        let p = test_pair();
+        ht.commit(&p).unwrap();
+        assert_eq!(Ok(Some(p.clone())), ht.get(&p.key()));
+        assert!(
+            ht.store
+                .get_object(&format!("test/pair/{}", p.key()))
+                .unwrap()
+                .is_some()
+        );
+
+        // still there once the cache is gone
+        ht.clear_cache();
+        assert_eq!(Ok(Some(p.clone())), ht.get(&p.key()));
+
+        ht.remove(&p.key()).unwrap();
+        ht.clear_cache();
+        assert_eq!(Ok(None), ht.get(&p.key()));
+    }
+
+    #[test]
+    /// objects that don't match their key are rejected
+    fn integrity() {
+        let mut ht = test_s3_table();
+        let p1 = test_pair_a();
+        let p2 = test_pair_b();
+        ht.store
+            .put_object(
+                &format!("test/pair/{}", p1.key()),
+                &serde_json::to_vec(&p2).unwrap(),
+            )
+            .unwrap();
+        assert!(ht.get(&p1.key()).is_err());
+    }
+
+    #[test]
+    /// meta is written through and can be listed per pair
+    fn meta() {
+        let mut ht = test_s3_table();
+        let p1 = test_pair_a();
+        let p2 = test_pair_b();
+
+        ht.commit(&p1).unwrap();
+        ht.modify(&test_keys(), &p1, &p2).unwrap();
+        ht.clear_cache();
+
+        assert_eq!(
+            vec![
+                PairMeta::new(&test_keys(), &p1, LINK_NAME, &p2.key()),
+                PairMeta::new(
+                    &test_keys(),
+                    &p1,
+                    STATUS_NAME,
+                    &CRUDStatus::MODIFIED.bits().to_string(),
+                ),
+            ],
+            ht.get_pair_meta(&p1).unwrap()
+        );
+        assert_eq!(Vec::<PairMeta>::new(), ht.get_pair_meta(&p2).unwrap());
+
+        let m = test_pair_meta();
+        assert_eq!(None, ht.get_meta(&m.key()).unwrap());
+        ht.assert_meta(&m).unwrap();
+        ht.clear_cache();
+        assert_eq!(Some(m.clone()), ht.get_meta(&m.key()).unwrap());
+    }
+}
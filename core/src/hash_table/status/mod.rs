@@ -16,6 +16,32 @@ bitflags! {
     }
 }
 
+/// what became of an entry, deleted ones count as deleted even if they were updated too
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EntryStatus {
+    #[serde(rename = "live")]
+    Live,
+    #[serde(rename = "modified")]
+    Modified,
+    #[serde(rename = "deleted")]
+    Deleted,
+}
+
+impl EntryStatus {
+    /// the status from the CRUD flags of the aspects held, None if nothing is held
+    pub fn from_crud_status(status: CRUDStatus) -> Option<EntryStatus> {
+        if status.contains(CRUDStatus::DELETED) {
+            Some(EntryStatus::Deleted)
+        } else if status.contains(CRUDStatus::MODIFIED) {
+            Some(EntryStatus::Modified)
+        } else if status.contains(CRUDStatus::LIVE) {
+            Some(EntryStatus::Live)
+        } else {
+            None
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::CRUDStatus;
@@ -0,0 +1,272 @@
+//! health of an instance as a supervisor sees it from outside, without taking the state lock a
+//! wedged instance may be holding
+//! the action loop beats a heartbeat on every turn, noting the action it reduces, and zome calls
+//! are timed from when they are started until their result comes back
+
+use nucleus::FunctionCall;
+use state::Action;
+use std::{
+    cmp::Reverse, collections::HashMap, fmt, sync::{Arc, Mutex}, time::{Duration, Instant},
+};
+
+struct Beat {
+    last: Instant,
+    /// the action being reduced and since when
+    reducing: Option<(Action, Instant)>,
+}
+
+/// when the action loop last came around and what it is busy with
+/// the heartbeat is a cheap handle, clones share the same beat
+#[derive(Clone)]
+pub struct Heartbeat {
+    beat: Arc<Mutex<Beat>>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Heartbeat {
+            beat: Arc::new(Mutex::new(Beat {
+                last: Instant::now(),
+                reducing: None,
+            })),
+        }
+    }
+}
+
+impl PartialEq for Heartbeat {
+    fn eq(&self, other: &Heartbeat) -> bool {
+        Arc::ptr_eq(&self.beat, &other.beat)
+    }
+}
+
+impl fmt::Debug for Heartbeat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Heartbeat")
+            .field("age", &self.age())
+            .finish()
+    }
+}
+
+impl Heartbeat {
+    pub fn beat(&self) {
+        self.beat.lock().unwrap().last = Instant::now();
+    }
+
+    /// beat, noting the action about to be reduced
+    pub fn begin(&self, action: &Action) {
+        let now = Instant::now();
+        let mut beat = self.beat.lock().unwrap();
+        beat.last = now;
+        beat.reducing = Some((action.clone(), now));
+    }
+
+    /// beat, the action is reduced
+    pub fn end(&self) {
+        let mut beat = self.beat.lock().unwrap();
+        beat.last = Instant::now();
+        beat.reducing = None;
+    }
+
+    /// time since the last beat
+    pub fn age(&self) -> Duration {
+        self.beat.lock().unwrap().last.elapsed()
+    }
+
+    /// the action being reduced and for how long
+    pub fn reducing(&self) -> Option<(Action, Duration)> {
+        let beat = self.beat.lock().unwrap();
+        beat.reducing
+            .as_ref()
+            .map(|(action, since)| (action.clone(), since.elapsed()))
+    }
+}
+
+/// when each zome call still running was started
+/// the monitor is a cheap handle, clones share the same calls
+#[derive(Clone, Default)]
+pub struct CallMonitor {
+    calls: Arc<Mutex<HashMap<FunctionCall, Instant>>>,
+}
+
+impl PartialEq for CallMonitor {
+    fn eq(&self, other: &CallMonitor) -> bool {
+        Arc::ptr_eq(&self.calls, &other.calls)
+    }
+}
+
+impl fmt::Debug for CallMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("CallMonitor")
+            .field("calls", &self.calls.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl CallMonitor {
+    pub fn started(&self, call: &FunctionCall) {
+        self.calls
+            .lock()
+            .unwrap()
+            .insert(call.clone(), Instant::now());
+    }
+
+    pub fn finished(&self, call: &FunctionCall) {
+        self.calls.lock().unwrap().remove(call);
+    }
+
+    /// forget every call, e.g. when they are abandoned
+    pub fn clear(&self) {
+        self.calls.lock().unwrap().clear();
+    }
+
+    /// the calls running for longer than budget, longest running first
+    pub fn over_budget(&self, budget: Duration) -> Vec<StuckCall> {
+        let mut stuck: Vec<StuckCall> = self
+            .calls
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(call, started)| StuckCall {
+                call: call.clone(),
+                running: started.elapsed(),
+            })
+            .filter(|stuck| stuck.running > budget)
+            .collect();
+        stuck.sort_by_key(|stuck| Reverse(stuck.running));
+        stuck
+    }
+}
+
+/// a zome call running for longer than it should
+#[derive(Clone, Debug, PartialEq)]
+pub struct StuckCall {
+    pub call: FunctionCall,
+    pub running: Duration,
+}
+
+/// health of an instance at one point in time
+#[derive(Clone, Debug, PartialEq)]
+pub struct Health {
+    /// time since the action loop last came around
+    pub heartbeat_age: Duration,
+    /// the action the loop is reducing and for how long
+    pub reducing: Option<(Action, Duration)>,
+    /// zome calls over budget
+    pub stuck_calls: Vec<StuckCall>,
+}
+
+impl Health {
+    /// whether the instance should be restarted: the action loop hasn't come around within
+    /// heartbeat_timeout or some zome call is over budget
+    pub fn is_wedged(&self, heartbeat_timeout: Duration) -> bool {
+        self.heartbeat_age > heartbeat_timeout || !self.stuck_calls.is_empty()
+    }
+
+    /// what the instance is stuck doing, the action loop first, then the stuck calls
+    pub fn backtrace(&self) -> Vec<String> {
+        let mut backtrace = Vec::new();
+        if let Some((ref action, ref reducing)) = self.reducing {
+            backtrace.push(format!(
+                "action loop reducing {:?} for {}ms",
+                action,
+                reducing.as_millis()
+            ));
+        }
+        for stuck in &self.stuck_calls {
+            backtrace.push(format!(
+                "zome call {}/{}/{} running for {}ms",
+                stuck.call.zome,
+                stuck.call.capability,
+                stuck.call.function,
+                stuck.running.as_millis()
+            ));
+        }
+        backtrace
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use agent::Action::Commit;
+    use hash_table::entry::tests::test_entry;
+    use state::Action::Agent;
+    use std::thread::sleep;
+
+    fn test_call() -> FunctionCall {
+        FunctionCall::new(
+            "test_zome".to_string(),
+            "test_cap".to_string(),
+            "main".to_string(),
+            "{}".to_string(),
+        )
+    }
+
+    #[test]
+    /// the heartbeat tells what is being reduced
+    fn heartbeat() {
+        let heartbeat = Heartbeat::default();
+        assert_eq!(None, heartbeat.reducing());
+        sleep(Duration::from_millis(10));
+        assert!(heartbeat.age() >= Duration::from_millis(10));
+
+        let action = Agent(Commit(test_entry()));
+        heartbeat.clone().begin(&action);
+        assert!(heartbeat.age() < Duration::from_millis(10));
+        assert_eq!(Some(action), heartbeat.reducing().map(|(action, _)| action));
+        heartbeat.end();
+        assert_eq!(None, heartbeat.reducing());
+    }
+
+    #[test]
+    /// only calls running longer than the budget are stuck
+    fn call_monitor() {
+        let monitor = CallMonitor::default();
+        let call = test_call();
+        monitor.started(&call);
+        assert!(monitor.over_budget(Duration::from_millis(1000)).is_empty());
+        sleep(Duration::from_millis(10));
+        let stuck = monitor.over_budget(Duration::from_millis(5));
+        assert_eq!(1, stuck.len());
+        assert_eq!(call, stuck[0].call);
+
+        monitor.finished(&call);
+        assert!(monitor.over_budget(Duration::from_millis(0)).is_empty());
+        monitor.started(&call);
+        monitor.clear();
+        assert!(monitor.over_budget(Duration::from_millis(0)).is_empty());
+    }
+
+    #[test]
+    /// wedged instances are told by their heartbeat and stuck calls
+    fn wedged() {
+        let healthy = Health {
+            heartbeat_age: Duration::from_millis(10),
+            reducing: None,
+            stuck_calls: Vec::new(),
+        };
+        assert!(!healthy.is_wedged(Duration::from_millis(100)));
+        assert!(healthy.backtrace().is_empty());
+
+        let late = Health {
+            heartbeat_age: Duration::from_millis(200),
+            reducing: Some((Agent(Commit(test_entry())), Duration::from_millis(200))),
+            ..healthy.clone()
+        };
+        assert!(late.is_wedged(Duration::from_millis(100)));
+        assert!(late.backtrace()[0].starts_with("action loop reducing Agent(Commit("));
+
+        let stuck = Health {
+            stuck_calls: vec![StuckCall {
+                call: test_call(),
+                running: Duration::from_millis(1500),
+            }],
+            ..healthy
+        };
+        assert!(stuck.is_wedged(Duration::from_millis(100)));
+        assert_eq!(
+            vec!["zome call test_zome/test_cap/main running for 1500ms".to_string()],
+            stuck.backtrace()
+        );
+    }
+}
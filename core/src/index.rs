@@ -0,0 +1,370 @@
+//! full-text index over the content of the entries of the types the DNA declares searchable,
+//! those committed to the chain and those held for the network alike
+//! the index is kept in memory and brought up to date after every action that commits or holds
+//! something, see update()
+//! content that is JSON is indexed by its string values, other content as it is
+//! the same entries are indexed by the fields their types declare indexed too, see
+//! hash_table::field_index
+
+use dht::DhtState;
+use hash_table::{
+    entry::Entry, field_index::{FieldIndex, IndexQuery, IndexedFields}, pair::Pair,
+    status::CRUDStatus,
+};
+use holochain_dna::Dna;
+use serde_json::{self, Value};
+use std::{
+    collections::{HashMap, HashSet}, fmt, mem, sync::{Arc, RwLock},
+};
+
+/// addresses search gives zomes at once when they don't ask for fewer
+pub const SEARCH_DEFAULT_LIMIT: usize = 100;
+/// the most addresses search gives zomes at once, so they fit the page the zome reads them from
+pub const SEARCH_MAX_LIMIT: usize = 1000;
+/// addresses query_index gives zomes at once when they don't ask for fewer
+pub const QUERY_INDEX_DEFAULT_LIMIT: usize = 100;
+/// the most addresses query_index gives zomes at once, so they fit the page the zome reads them
+/// from
+pub const QUERY_INDEX_MAX_LIMIT: usize = 1000;
+
+#[derive(Default)]
+struct Inner {
+    /// how often each term occurs in the content of the indexed entries, by term then address
+    postings: HashMap<String, HashMap<String, usize>>,
+    /// entry type and terms of the indexed entries by address
+    indexed: HashMap<String, (String, Vec<String>)>,
+    /// the fields the DNA declares indexed, of the entries by address
+    fields: FieldIndex,
+    /// the entries in the field index by address
+    fielded: HashMap<String, Entry>,
+    /// addresses of the entries indexed as they were committed, kept whether held or not
+    committed: HashSet<String>,
+}
+
+impl Inner {
+    /// index the entry by its terms if its type is searchable and by its fields if they are
+    /// declared indexed, returns true if it is indexed
+    fn insert(&mut self, address: &str, entry: &Entry, searchable: bool) -> bool {
+        if self.fields.is_indexed(entry.entry_type()) && !self.fielded.contains_key(address) {
+            self.fields.insert(address, entry);
+            self.fielded.insert(address.to_string(), entry.clone());
+        }
+        if searchable {
+            self.insert_terms(address, entry);
+        }
+        searchable || self.fielded.contains_key(address)
+    }
+
+    fn insert_terms(&mut self, address: &str, entry: &Entry) {
+        if self.indexed.contains_key(address) {
+            return;
+        }
+        let terms = terms(entry.content());
+        for term in &terms {
+            *self
+                .postings
+                .entry(term.clone())
+                .or_default()
+                .entry(address.to_string())
+                .or_default() += 1;
+        }
+        self.indexed
+            .insert(address.to_string(), (entry.entry_type().to_string(), terms));
+    }
+
+    fn remove(&mut self, address: &str) {
+        if let Some(entry) = self.fielded.remove(address) {
+            self.fields.remove(address, &entry);
+        }
+        self.committed.remove(address);
+        let (_, terms) = match self.indexed.remove(address) {
+            Some(indexed) => indexed,
+            None => return,
+        };
+        for term in terms {
+            let emptied = self
+                .postings
+                .get_mut(&term)
+                .map(|addresses| {
+                    addresses.remove(address);
+                    addresses.is_empty()
+                })
+                .unwrap_or(false);
+            if emptied {
+                self.postings.remove(&term);
+            }
+        }
+    }
+}
+
+/// the search index of an instance
+/// the index is a cheap handle, clones share the same index
+#[derive(Clone, Default)]
+pub struct SearchIndex {
+    inner: Arc<RwLock<Inner>>,
+}
+
+impl PartialEq for SearchIndex {
+    fn eq(&self, other: &SearchIndex) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for SearchIndex {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SearchIndex")
+            .field("indexed", &self.len())
+            .finish()
+    }
+}
+
+/// the fields the entry types of the DNA declare indexed
+pub fn indexed_fields(dna: &Dna) -> IndexedFields {
+    dna.zomes
+        .iter()
+        .flat_map(|zome| zome.entry_types.iter())
+        .filter(|entry_type| !entry_type.indexes.is_empty())
+        .map(|entry_type| (entry_type.name.clone(), entry_type.indexes.clone()))
+        .collect()
+}
+
+/// the lowercased words of the content, the string values of it if it is JSON, in order
+fn terms(content: &str) -> Vec<String> {
+    let text = match serde_json::from_str(content) {
+        Ok(value) => {
+            let mut strings = Vec::new();
+            strings_in(&value, &mut strings);
+            strings.join(" ")
+        }
+        Err(_) => content.to_string(),
+    };
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+fn strings_in(value: &Value, strings: &mut Vec<String>) {
+    match *value {
+        Value::String(ref string) => strings.push(string.clone()),
+        Value::Array(ref values) => {
+            for value in values {
+                strings_in(value, strings);
+            }
+        }
+        Value::Object(ref map) => {
+            for value in map.values() {
+                strings_in(value, strings);
+            }
+        }
+        _ => (),
+    }
+}
+
+impl SearchIndex {
+    /// index the Pairs committed and the entries the DHT holds of the types the DNA declares
+    /// searchable or with fields indexed
+    /// entries already indexed aren't indexed again, the ones neither held nor committed are
+    /// dropped, as are the ones deleted
+    pub fn update(&self, dna: &Dna, committed: &[Pair], dht: &DhtState) {
+        let searchable = |entry: &Entry| {
+            dna.get_entry_type(entry.entry_type())
+                .map(|entry_type| entry_type.searchable)
+                .unwrap_or(false)
+        };
+        let mut inner = self.inner.write().unwrap();
+        let fields = indexed_fields(dna);
+        if *inner.fields.fields() != fields {
+            inner.fields = FieldIndex::new(&fields);
+            for (address, entry) in mem::take(&mut inner.fielded) {
+                inner.insert(&address, &entry, false);
+            }
+        }
+        for pair in committed {
+            let address = pair.entry().key();
+            if inner.insert(&address, pair.entry(), searchable(pair.entry())) {
+                inner.committed.insert(address);
+            }
+        }
+        for address in dht.held_addresses() {
+            if let Some(entry) = dht.holding(&address) {
+                inner.insert(&address, &entry, searchable(&entry));
+            }
+        }
+        let dropped: Vec<String> = inner
+            .indexed
+            .keys()
+            .chain(inner.fielded.keys())
+            .filter(|address| {
+                let held = dht.holding(address).is_some() || inner.committed.contains(*address);
+                !held || dht.crud_status(address).contains(CRUDStatus::DELETED)
+            })
+            .cloned()
+            .collect();
+        for address in dropped {
+            inner.remove(&address);
+        }
+    }
+
+    /// addresses of the indexed entries of entry_type with a value at the path of the field
+    /// matching the query, by value then address, or None if the DNA doesn't declare the field
+    /// indexed
+    pub fn query(&self, entry_type: &str, field: &str, query: &IndexQuery) -> Option<Vec<String>> {
+        self.inner
+            .read()
+            .unwrap()
+            .fields
+            .query(entry_type, field, query)
+    }
+
+    /// addresses of the indexed entries with content holding every word of the query, of the
+    /// given types or any type if none are given
+    /// entries holding the words more often come first, then by address
+    pub fn search(&self, query: &str, types: &[String]) -> Vec<String> {
+        let inner = self.inner.read().unwrap();
+        let mut words = terms(query);
+        words.sort();
+        words.dedup();
+        let (first, rest) = match words.split_first() {
+            Some(split) => split,
+            None => return Vec::new(),
+        };
+        let mut found: Vec<(usize, String)> = inner
+            .postings
+            .get(first)
+            .into_iter()
+            .flat_map(|addresses| addresses.iter())
+            .filter_map(|(address, count)| {
+                let mut score = *count;
+                for word in rest {
+                    score += *inner.postings.get(word)?.get(address)?;
+                }
+                Some((score, address.clone()))
+            })
+            .filter(|(_, address)| {
+                types.is_empty() || types.contains(&inner.indexed[address].0)
+            })
+            .collect();
+        found.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+        found.into_iter().map(|(_, address)| address).collect()
+    }
+
+    /// how many entries are indexed
+    pub fn len(&self) -> usize {
+        self.inner.read().unwrap().indexed.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chain::{tests::test_chain, ChainWrite};
+    use dht::{
+        aspect::Aspect, tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::field_index::{
+        tests::{test_indexed_fields, test_profile}, IndexValue,
+    };
+    use holochain_dna::zome::{entry_types::EntryType, Zome};
+
+    /// DNA with a searchable "post" type, a "comment" type that isn't and "profile" entries
+    /// indexed by handle and age
+    pub fn test_search_dna() -> Dna {
+        let mut post = EntryType::new();
+        post.name = "post".to_string();
+        post.searchable = true;
+        let mut comment = EntryType::new();
+        comment.name = "comment".to_string();
+        let mut profile = EntryType::new();
+        profile.name = "profile".to_string();
+        profile.indexes = test_indexed_fields()["profile"].clone();
+        let mut zome = Zome::new();
+        zome.entry_types = vec![post, comment, profile];
+        let mut dna = Dna::new();
+        dna.zomes.push(zome);
+        dna
+    }
+
+    #[test]
+    /// words are lowercased, JSON is indexed by its string values
+    fn terms_of_content() {
+        assert_eq!(vec!["hello", "world"], terms("Hello, world!"));
+        assert_eq!(
+            vec!["cats", "and", "dogs"],
+            terms(r#"{"title":"Cats and","tags":["dogs"],"likes":3}"#)
+        );
+        assert!(terms("").is_empty());
+    }
+
+    #[test]
+    /// committed and held entries of searchable types are found by all the words searched for
+    fn search() {
+        let dna = test_search_dna();
+        let index = SearchIndex::default();
+        let held = Entry::new("post", "cats and dogs, dogs everywhere");
+        let committed = Entry::new("post", "Dogs");
+        let comment = Entry::new("comment", "dogs");
+
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(held.clone()));
+        dht = test_reduce(dht, Action::Hold(comment.clone()));
+        let pairs = vec![test_chain().push(&committed).unwrap()];
+        index.update(&dna, &pairs, &dht);
+        index.update(&dna, &pairs, &dht);
+
+        assert_eq!(2, index.len());
+        assert_eq!(vec![held.key(), committed.key()], index.search("DOGS", &[]));
+        assert_eq!(vec![held.key()], index.search("dogs cats", &[]));
+        assert!(index.search("dogs birds", &[]).is_empty());
+        assert!(index.search("dogs", &["comment".to_string()]).is_empty());
+        assert!(index.search("", &[]).is_empty());
+
+        // deleted entries are dropped, committed ones stay while not held
+        dht = test_reduce(dht, Action::HoldAspect(held.key(), Aspect::Delete));
+        index.update(&dna, &[], &dht);
+        assert_eq!(vec![committed.key()], index.search("dogs", &[]));
+        index.update(&dna, &[], &test_dht_state());
+        assert_eq!(1, index.len());
+    }
+
+    #[test]
+    /// committed and held entries are found by the fields their types declare indexed
+    fn query() {
+        let dna = test_search_dna();
+        assert_eq!(test_indexed_fields(), indexed_fields(&dna));
+        let index = SearchIndex::default();
+        let alice = test_profile("alice", 30);
+        let bob = test_profile("bob", 17);
+
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(bob.clone()));
+        let pairs = vec![test_chain().push(&alice).unwrap()];
+        index.update(&dna, &pairs, &dht);
+
+        let handle = |h: &str| IndexQuery::Equals(IndexValue::Text(h.to_string()));
+        assert_eq!(
+            Some(vec![alice.key()]),
+            index.query("profile", "handle", &handle("alice"))
+        );
+        let everyone = IndexQuery::Range {
+            from: None,
+            to: Some(IndexValue::Int(100)),
+        };
+        assert_eq!(
+            Some(vec![bob.key(), alice.key()]),
+            index.query("profile", "about.age", &everyone)
+        );
+        assert_eq!(None, index.query("profile", "name", &everyone));
+        // profiles aren't searchable
+        assert!(index.search("alice", &[]).is_empty());
+
+        dht = test_reduce(dht, Action::HoldAspect(bob.key(), Aspect::Delete));
+        index.update(&dna, &[], &dht);
+        assert_eq!(
+            Some(vec![alice.key()]),
+            index.query("profile", "about.age", &everyone)
+        );
+    }
+}
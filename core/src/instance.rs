@@ -1,11 +1,41 @@
-//use error::HolochainError;
+use agent::checkpoints;
+use dht::{
+    retention,
+    store::{HoldingStore, HOLDING_STORE_VERIFY_SAMPLE},
+};
+use error::HolochainError;
+use health::{CallMonitor, Health, Heartbeat};
+use holochain_dna::Dna;
+use limits::ResourceLimits;
+use network::{
+    config::NetworkConfig, connectivity::{self, NetworkInfo},
+    direct_message::{self, MemoryNetwork}, outbox::{OutboxMetrics, OUTBOX_RETRY_INTERVAL_MS},
+    presence,
+};
+#[cfg(feature = "native_zomes")]
+use nucleus::native_zome::NativeZome;
+use nucleus::{
+    scheduler::{Scheduler, SchedulerConfig}, NucleusStatus,
+};
+use platform;
 use state::*;
 use std::{
-    sync::{mpsc::*, Arc, RwLock, RwLockReadGuard}, thread, time::Duration,
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering}, mpsc::*, Arc, RwLock, RwLockReadGuard,
+    },
+    time::{Duration, Instant},
+};
+use trace::Tracer;
+use validation::{
+    pool::{ValidationPool, ValidationPoolConfig},
+    receipts::{self, PublishStatus, ValidationReceipt},
+    revalidation::{self, RevalidationReport}, warrants, Validator,
 };
 
 pub const REDUX_LOOP_TIMEOUT_MS: u64 = 400;
 pub const REDUX_DEFAULT_TIMEOUT_MS: u64 = 2000;
+pub const IDLE_POLL_INTERVAL_MS: u64 = 10;
 
 /// Object representing a Holochain app instance.
 /// Holds the Event loop and processes it with the redux state model.
@@ -14,6 +44,13 @@ pub struct Instance {
     state: Arc<RwLock<State>>,
     action_channel: Sender<ActionWrapper>,
     observer_channel: Sender<Observer>,
+    validation_pool: Option<ValidationPool>,
+    scheduler: Option<Scheduler>,
+    heartbeat: Heartbeat,
+    /// tells the running action loop to stop
+    stopped: Arc<AtomicBool>,
+    /// the state's, kept to check calls while the state is locked
+    call_monitor: CallMonitor,
 }
 
 type ClosureType = Box<FnMut(&State) -> bool + Send>;
@@ -64,17 +101,51 @@ impl Instance {
         self.observer_channel = tx_observer.clone();
 
         let state_mutex = self.state.clone();
+        let heartbeat = self.heartbeat.clone();
+        self.stop_action_loop();
+        let stopped = self.stopped.clone();
 
-        thread::spawn(move || {
+        platform::spawn("action_loop", move || {
             let mut state_observers: Vec<Box<Observer>> = Vec::new();
 
-            loop {
+            while !stopped.load(Ordering::SeqCst) {
                 match rx_action.recv_timeout(Duration::from_millis(REDUX_LOOP_TIMEOUT_MS)) {
                     Ok(action_wrapper) => {
+                        heartbeat.begin(&action_wrapper.action);
+
                         // Mutate state
-                        {
+                        let (old_dht, dht_changed, agent_changed) = {
                             let mut state = state_mutex.write().unwrap();
+                            let (dht, agent) = (state.dht(), state.agent());
                             *state = state.reduce(action_wrapper, &tx_action, &tx_observer);
+                            let dht_changed = !Arc::ptr_eq(&dht, &state.dht());
+                            (dht, dht_changed, !Arc::ptr_eq(&agent, &state.agent()))
+                        };
+                        heartbeat.end();
+
+                        // Keep the holdings, a store that can't be written now catches up with
+                        // the next change, tell the subscribers what is newly held and warrant
+                        // the forks it reveals
+                        if dht_changed {
+                            let state = state_mutex.read().unwrap();
+                            let _ = state.nucleus().holding_store().save(&state.dht());
+                            state
+                                .nucleus()
+                                .subscriptions()
+                                .notify(&old_dht, &state.dht());
+                            warrants::issue(&state, &old_dht, &tx_action);
+                        }
+
+                        // Index what was committed or held
+                        if dht_changed || agent_changed {
+                            let state = state_mutex.read().unwrap();
+                            if let Some(dna) = state.nucleus().dna() {
+                                let committed = state.agent().last_commit().unwrap_or_default();
+                                state
+                                    .nucleus()
+                                    .search_index()
+                                    .update(&dna, &committed, &state.dht());
+                            }
                         }
 
                         // Add new observers
@@ -94,20 +165,339 @@ impl Instance {
                                 .filter(|observer| !observer.done)
                                 .collect::<Vec<_>>();
                         }
+                        connectivity::check(&state_mutex.read().unwrap());
+                        presence::beat(&state_mutex.read().unwrap());
+                        checkpoints::publish(&state_mutex.read().unwrap());
+                        retention::prune(&state_mutex.read().unwrap(), &tx_action);
+                    }
+                    Err(ref _recv_error) => {
+                        heartbeat.beat();
+                        // peers also go stale while nothing happens
+                        connectivity::check(&state_mutex.read().unwrap());
+                        presence::beat(&state_mutex.read().unwrap());
+                        checkpoints::publish(&state_mutex.read().unwrap());
+                        retention::prune(&state_mutex.read().unwrap(), &tx_action);
                     }
-                    Err(ref _recv_error) => {}
                 }
             }
         });
     }
 
+    /// Stop the Event Loop once it is done with the action it is reducing, if any
+    /// Actions dispatched after are not reduced
+    pub fn stop_action_loop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        self.stopped = Arc::new(AtomicBool::new(false));
+    }
+
+    /// How the instance is doing, zome calls running longer than call_budget count as stuck
+    /// Doesn't block, even if the Event Loop is stuck reducing an action
+    pub fn health(&self, call_budget: Duration) -> Health {
+        Health {
+            heartbeat_age: self.heartbeat.age(),
+            reducing: self.heartbeat.reducing(),
+            stuck_calls: self.call_monitor.over_budget(call_budget),
+        }
+    }
+
+    /// A copy of the state, None if the Event Loop is busy changing it
+    pub fn snapshot(&self) -> Option<State> {
+        self.state.try_read().ok().map(|state| state.clone())
+    }
+
+    /// Start the pool of threads validating entries received from the network
+    /// Any previously started pool is shut down first
+    /// Validation is traced by the instance's tracer unless the config says otherwise
+    pub fn start_validation_pool(&mut self, config: &ValidationPoolConfig, validator: Validator) {
+        let mut config = config.clone();
+        if config.tracer.is_none() {
+            config.tracer = Some(self.tracer());
+        }
+        self.validation_pool = None;
+        self.validation_pool = Some(ValidationPool::new(&config, validator));
+    }
+
+    /// The validation pool, if it has been started
+    pub fn validation_pool(&self) -> Option<&ValidationPool> {
+        self.validation_pool.as_ref()
+    }
+
+    /// Start calling scheduled zome functions as they fall due, once the action loop is started
+    /// Any previously started scheduler is stopped first
+    pub fn start_scheduler(&mut self, config: &SchedulerConfig) {
+        self.scheduler = None;
+        self.scheduler = Some(Scheduler::start(
+            config,
+            self.state.clone(),
+            self.action_channel.clone(),
+        ));
+    }
+
+    /// Stop calling scheduled zome functions, the schedules are kept
+    pub fn stop_scheduler(&mut self) {
+        self.scheduler = None;
+    }
+
+    /// Connect to a network under the given agent address, once the action loop is started, and
+    /// answer the direct messages sent to it, e.g. remote calls
+    /// What is waiting in the outbox is sent right away, then whenever something is queued and
+    /// every OUTBOX_RETRY_INTERVAL_MS while anything is left
+    /// Any previous connection is dropped
+    pub fn join_network(&mut self, network: &MemoryNetwork, address: &str) {
+        let messenger = self.state().nucleus().messenger().clone();
+        let receiver = messenger.connect(network, address);
+        let state = self.state.clone();
+        let action_channel = self.action_channel.clone();
+        let observer_channel = self.observer_channel.clone();
+        let receiving = messenger.clone();
+        // runs until the connection is dropped
+        platform::spawn("direct_messages", move || {
+            for envelope in receiver {
+                direct_message::receive(
+                    envelope.message,
+                    &receiving,
+                    &state,
+                    &action_channel,
+                    &observer_channel,
+                );
+            }
+        });
+
+        let outbox = self.state().nucleus().outbox().clone();
+        let wakeup = outbox.wakeup();
+        let state = self.state.clone();
+        let network = network.clone();
+        // runs until the network is left or joined again
+        platform::spawn("outbox", move || loop {
+            if messenger.network() != Some(network.clone()) {
+                break;
+            }
+            let dht = state.read().unwrap().dht();
+            outbox.flush(&messenger, &dht);
+            let interval = Duration::from_millis(OUTBOX_RETRY_INTERVAL_MS);
+            if let Err(RecvTimeoutError::Disconnected) = wakeup.recv_timeout(interval) {
+                break;
+            }
+        });
+    }
+
+    /// How many publishes and messages are waiting to get through, e.g. to show sync status
+    pub fn outbox_depth(&self) -> usize {
+        self.state().nucleus().outbox().depth()
+    }
+
+    /// How many publishes and messages wait at each priority and how many were refused
+    pub fn outbox_metrics(&self) -> OutboxMetrics {
+        self.state().nucleus().outbox().metrics()
+    }
+
+    /// Persist the outbox to the file, picking up what was pending in it, e.g. before a restart
+    pub fn persist_outbox(&self, path: &Path) -> Result<(), HolochainError> {
+        self.state().nucleus().outbox().persist(path)
+    }
+
+    /// Keep the DHT holdings in the file from now on, holding what was kept there again first,
+    /// e.g. on boot
+    /// A random sample of HOLDING_STORE_VERIFY_SAMPLE records kept is re-verified, if any of them
+    /// is corrupted nothing is held and the file is left alone
+    /// Returns how many addresses were kept
+    pub fn persist_holdings(&mut self, path: &Path) -> Result<usize, HolochainError> {
+        let records = HoldingStore::load(path, HOLDING_STORE_VERIFY_SAMPLE)?;
+        let kept = records.len();
+        self.dispatch_and_wait(Action::Dht(::dht::Action::Restore(records)));
+        let state = self.state();
+        let store = state.nucleus().holding_store().clone();
+        store.open(path);
+        store.save(&state.dht())?;
+        Ok(kept)
+    }
+
+    /// Persist the validation receipts received to the file, picking up the ones kept in it
+    pub fn persist_receipts(&self, path: &Path) -> Result<(), HolochainError> {
+        self.state().nucleus().receipts().persist(path)
+    }
+
+    /// Persist the group secrets created and received to the file, picking up the ones kept in
+    /// it, see agent::groups
+    pub fn persist_group_secrets(&self, path: &Path) -> Result<(), HolochainError> {
+        self.state().nucleus().group_secrets().persist(path)
+    }
+
+    /// Persist the seed of the keys zomes derive to the file, or take the one kept in it, so
+    /// they get the same keys after a restart, see agent::keystore
+    pub fn persist_keystore(&self, path: &Path) -> Result<(), HolochainError> {
+        self.state().nucleus().keystore().persist(path)
+    }
+
+    /// Persist the seed of the keys zomes derive to the file encrypted with the passphrase, or
+    /// take the one kept in it, the passphrase isn't kept, see agent::keystore
+    pub fn persist_encrypted_keystore(
+        &self,
+        path: &Path,
+        passphrase: &str,
+    ) -> Result<(), HolochainError> {
+        self.state()
+            .nucleus()
+            .keystore()
+            .persist_encrypted(path, passphrase)
+    }
+
+    /// Read the seed of a keystore encrypted in its file back with the passphrase, after it was
+    /// locked, see agent::keystore
+    pub fn unlock_keystore(&self, passphrase: &str) -> Result<(), HolochainError> {
+        self.state().nucleus().keystore().unlock_with(passphrase)
+    }
+
+    /// The validation receipts holders sent for the entry at address, by validator
+    pub fn validation_receipts(&self, address: &str) -> Vec<ValidationReceipt> {
+        self.state().nucleus().receipts().receipts(address)
+    }
+
+    /// How far the publish of the entry at address got: whether it still waits in the outbox and
+    /// how many holders sent a receipt for it
+    pub fn publish_status(&self, address: &str) -> PublishStatus {
+        receipts::publish_status(&self.state(), address)
+    }
+
+    /// Where the instance stands on the network: connectivity, peers, arc and last gossip
+    /// Changes of connectivity are also emitted as connectivity::CONNECTIVITY_SIGNAL
+    pub fn network_info(&self) -> NetworkInfo {
+        connectivity::network_info(&self.state())
+    }
+
+    /// The agents that sent a presence heartbeat lately, sorted
+    /// Empty unless the network config turns presence on
+    pub fn online_agents(&self) -> Vec<String> {
+        let state = self.state();
+        let nucleus = state.nucleus();
+        nucleus.presence().online_agents(&nucleus.messenger().config())
+    }
+
+    /// Set the timeouts and retries of the network traffic from now on
+    pub fn set_network_config(&self, config: &NetworkConfig) {
+        self.state().nucleus().messenger().configure(config);
+    }
+
+    /// Disconnect from the network joined, saying goodbye to the agents talked to
+    pub fn leave_network(&mut self) {
+        self.state().nucleus().messenger().leave();
+    }
+
+    /// Refuse remote calls until the network is joined again, e.g. while shutting down
+    pub fn close_network(&self) {
+        self.state().nucleus().messenger().close();
+    }
+
+    /// No zome function call is in flight and no commits are staged
+    pub fn is_idle(&self) -> bool {
+        let state = self.state();
+        state.nucleus().calls_in_flight() == 0 && !state.agent().is_staging()
+    }
+
+    /// Block until the instance is idle or the timeout passes, returns whether it is idle
+    pub fn wait_until_idle(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        while !self.is_idle() {
+            if Instant::now() >= deadline {
+                return false;
+            }
+            platform::sleep(Duration::from_millis(IDLE_POLL_INTERVAL_MS));
+        }
+        true
+    }
+
+    /// Set the max number of compiled zome modules kept in memory between calls
+    pub fn set_module_cache_size(&self, size: usize) {
+        self.state().nucleus().module_cache().set_capacity(size);
+    }
+
+    /// Register a zome implemented in Rust, called like the zomes of the DNA, see
+    /// nucleus::native_zome
+    /// Register it before initializing the DNA for it to get the lifecycle calls
+    #[cfg(feature = "native_zomes")]
+    pub fn register_native_zome(&self, name: &str, zome: Arc<dyn NativeZome>) {
+        self.state().nucleus().native_zomes().register(name, zome);
+    }
+
+    /// Limit the resources the instance can use, once the action loop is started
+    /// Whatever is already used over the new limits is kept, only growing further fails
+    pub fn set_resource_limits(&mut self, limits: &ResourceLimits) {
+        self.dispatch_and_wait(Action::Nucleus(::nucleus::Action::SetWasmPageLimit(
+            limits.max_wasm_pages,
+        )));
+        self.dispatch_and_wait(Action::Agent(::agent::Action::SetChainLimit(
+            limits.max_chain_bytes,
+        )));
+        self.dispatch_and_wait(Action::Dht(::dht::Action::SetHoldingsLimit(
+            limits.max_dht_bytes,
+        )));
+        self.dispatch_and_wait(Action::Nucleus(::nucleus::Action::SetConcurrentReadLimit(
+            limits.max_concurrent_reads,
+        )));
+        self.state()
+            .nucleus()
+            .outbox()
+            .set_max_depth(limits.max_outbox_depth);
+    }
+
+    /// Allow the clocks of the agents to be apart by the seconds, for the times of the headers
+    /// committed and published, once the action loop is started, see validation::timestamps
+    pub fn set_max_clock_skew(&mut self, secs: u64) {
+        self.dispatch_and_wait(Action::Agent(::agent::Action::SetClockSkew(secs)));
+    }
+
+    /// Swap in a new version of the DNA, e.g. with its code or properties changed during
+    /// development, once the instance is initialized and the action loop is started
+    /// What the DHT holds is validated again under the new rules, the entries newly failing are
+    /// quarantined and the quarantined ones passing again released, as reported
+    /// The validation pool keeps the validator it was started with
+    pub fn reload_dna(&mut self, dna: Dna) -> Result<RevalidationReport, HolochainError> {
+        if self.state().nucleus().status() != NucleusStatus::Initialized {
+            return Err(HolochainError::new(
+                "the DNA can only be reloaded once the instance is initialized",
+            ));
+        }
+        self.dispatch_and_wait(Action::Nucleus(::nucleus::Action::ReloadDna(dna.clone())));
+
+        let state = self.state.clone();
+        let validator = revalidation::dna_validator(
+            dna,
+            Arc::new(move |key: &str| state.read().unwrap().dht().holding(key)),
+            &self.action_channel,
+            &self.observer_channel,
+        );
+        let dht = self.state().dht();
+        let report = revalidation::revalidate(&dht, &validator);
+        for action in report.actions() {
+            self.dispatch_and_wait(Action::Dht(action));
+        }
+        Ok(report)
+    }
+
+    /// The tracer spans of this instance's work are recorded by
+    pub fn tracer(&self) -> Tracer {
+        self.state().nucleus().tracer().clone()
+    }
+
     pub fn new() -> Self {
+        Instance::from_state(State::new())
+    }
+
+    /// Instance picking up from the given state, e.g. one persisted earlier
+    /// The Event Loop has to be started
+    pub fn from_state(state: State) -> Self {
         let (tx_action, _) = channel();
         let (tx_observer, _) = channel();
+        let call_monitor = state.nucleus().call_monitor().clone();
         Instance {
-            state: Arc::new(RwLock::new(State::new())),
+            state: Arc::new(RwLock::new(state)),
             action_channel: tx_action,
             observer_channel: tx_observer,
+            validation_pool: None,
+            scheduler: None,
+            heartbeat: Heartbeat::default(),
+            stopped: Arc::new(AtomicBool::new(false)),
+            call_monitor,
         }
     }
 
@@ -128,8 +518,20 @@ pub fn dispatch_action_and_wait(
     observer_channel: &Sender<Observer>,
     action: Action,
 ) {
-    // Wrap Action
-    let wrapper = ::state::ActionWrapper::new(action);
+    dispatch_wrapper_and_wait(
+        action_channel,
+        observer_channel,
+        ::state::ActionWrapper::new(action),
+    );
+}
+
+/// Send an already wrapped Action, e.g. one carrying a trace, to Instance's Event Queue and block
+/// until it has been processed.
+pub fn dispatch_wrapper_and_wait(
+    action_channel: &Sender<::state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+    wrapper: ActionWrapper,
+) {
     let wrapper_clone = wrapper.clone();
 
     // Create blocking channel
@@ -175,6 +577,24 @@ pub fn dispatch_action_with_observer<F>(
     closure: F,
 ) where
     F: 'static + FnMut(&State) -> bool + Send,
+{
+    dispatch_wrapper_with_observer(
+        action_channel,
+        observer_channel,
+        ActionWrapper::new(action),
+        closure,
+    );
+}
+
+/// Send an already wrapped Action to the Event Queue and create an Observer for it with the
+/// specified closure
+pub fn dispatch_wrapper_with_observer<F>(
+    action_channel: &Sender<::state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+    wrapper: ActionWrapper,
+    closure: F,
+) where
+    F: 'static + FnMut(&State) -> bool + Send,
 {
     let observer = Observer {
         sensor: Box::new(closure),
@@ -184,7 +604,9 @@ pub fn dispatch_action_with_observer<F>(
     observer_channel
         .send(observer)
         .expect("observer channel to be open");
-    dispatch_action(action_channel, action);
+    action_channel
+        .send(wrapper)
+        .expect("action channel to be open");
 }
 
 /// Send Action to the Event Queue
@@ -198,3 +620,448 @@ pub fn dispatch_action(
         .unwrap_or_else(|_| panic!(DISPATCH_WITHOUT_CHANNELS));
     wrapper
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{dispatch_action, Instance, IDLE_POLL_INTERVAL_MS, REDUX_LOOP_TIMEOUT_MS};
+    use agent::{
+        checkpoints, Action::{AbortStaged, BeginStaging, Commit},
+    };
+    use error::HolochainError;
+    use hash_table::{
+        entry::{tests::test_entry, Entry}, provenance::tests::test_agent_address,
+    };
+    use holochain_dna::{
+        zome::{capabilities::Capability, Zome}, Dna,
+    };
+    use limits::{ResourceLimits, LIMIT_EXCEEDED_SIGNAL};
+    use dht::{
+        aspect::Aspect, Action::{Hold, HoldAspect, PeerSeen}, HoldingValidation, StorageArc,
+    };
+    use network::{
+        config::{NetworkConfig, PresenceConfig},
+        connectivity::{Connectivity, CONNECTIVITY_SIGNAL}, direct_message::{
+            tests::{test_caller, test_remote_call}, MemoryNetwork,
+        },
+        outbox::{tests::test_publish, Priority},
+    };
+    use nucleus::{
+        module_cache::RIBOSOME_MODULE_CACHE_DEFAULT_SIZE,
+        scheduler::{tests::test_schedule, unix_now, SchedulerConfig},
+        Action::{InitApplication, Schedule}, NucleusStatus,
+    };
+    use state::Action::{Agent, Dht, Nucleus};
+    use std::{
+        env, fs, process, thread::{self, sleep}, time::{Duration, Instant},
+    };
+    use trace::tests::test_trace_context;
+    use validation::{
+        links::{tests::test_link_dna, Link}, pool::{tests::test_validator, ValidationPoolConfig},
+        tests::test_validation_item, warrants::{tests::test_fork, Warrant},
+    };
+
+    #[test]
+    /// the validation pool is only there once started
+    fn validation_pool() {
+        let mut instance = Instance::new();
+        assert!(instance.validation_pool().is_none());
+
+        instance.start_validation_pool(&ValidationPoolConfig::default(), test_validator());
+        let pool = instance.validation_pool().expect("pool should be started");
+
+        let item = test_validation_item();
+        pool.submit(item.clone());
+        let result = pool
+            .results()
+            .recv_timeout(Duration::from_millis(1000))
+            .unwrap();
+        assert_eq!(item.address, result.address);
+        assert_eq!(Ok(()), result.result);
+    }
+
+    #[test]
+    /// remote validation joins the trace it was published in, recorded by the instance tracer
+    fn validation_traced() {
+        let mut instance = Instance::new();
+        instance.start_validation_pool(&ValidationPoolConfig::default(), test_validator());
+        let pool = instance.validation_pool().expect("pool should be started");
+
+        pool.submit(test_validation_item().traced(test_trace_context()));
+        pool.results()
+            .recv_timeout(Duration::from_millis(1000))
+            .unwrap();
+
+        let spans = instance.tracer().trace(&test_trace_context().trace_id);
+        assert_eq!(1, spans.len());
+        assert_eq!("validate", spans[0].name);
+    }
+
+    #[test]
+    /// the module cache size can be configured
+    fn set_module_cache_size() {
+        let instance = Instance::new();
+        assert_eq!(
+            RIBOSOME_MODULE_CACHE_DEFAULT_SIZE,
+            instance.state().nucleus().module_cache().capacity()
+        );
+        instance.set_module_cache_size(3);
+        assert_eq!(3, instance.state().nucleus().module_cache().capacity());
+    }
+
+    #[test]
+    /// limits are enforced once set and hitting one is signalled
+    fn set_resource_limits() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        instance.set_resource_limits(&ResourceLimits {
+            max_wasm_pages: Some(4),
+            max_chain_bytes: Some(0),
+            max_dht_bytes: None,
+            max_concurrent_reads: Some(1),
+            max_outbox_depth: Some(2),
+        });
+        assert_eq!(Some(4), instance.state().nucleus().max_wasm_pages());
+        assert_eq!(Some(1), instance.state().nucleus().call_gate().max_reads());
+        let outbox = instance.state().nucleus().outbox().clone();
+        assert!(outbox.room(Priority::Low, 2).is_ok());
+        assert!(outbox.room(Priority::Low, 3).is_err());
+
+        let signals = instance.state().nucleus().signal_bus().subscribe();
+        instance.dispatch_and_wait(Agent(Commit(test_entry())));
+        assert!(instance.state().agent().last_commit().is_err());
+        let signal = signals.recv_timeout(Duration::from_millis(1000)).unwrap();
+        assert_eq!(LIMIT_EXCEEDED_SIGNAL, signal.name);
+    }
+
+    #[test]
+    /// remote calls are answered while the instance is on the network
+    fn remote_calls() {
+        let mut capability = Capability::new();
+        capability.name = "test_cap".to_string();
+        let mut zome = Zome::new();
+        zome.name = "test_zome".to_string();
+        zome.capabilities.push(capability);
+        let mut dna = Dna::new();
+        dna.zomes.push(zome);
+
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Nucleus(InitApplication(dna)));
+
+        let network = MemoryNetwork::new();
+        instance.join_network(&network, "bob");
+        assert_eq!(
+            Some("bob".to_string()),
+            instance.state().nucleus().messenger().address()
+        );
+        let alice = test_caller(&network, "alice");
+        let call = test_remote_call().call();
+        let timeout = Duration::from_millis(1000);
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric(
+                "Capability 'test_cap' of Zome 'test_zome' is not granted to alice".to_string()
+            )),
+            alice.call_remote("bob", &call, None, timeout)
+        );
+
+        instance.leave_network();
+        assert!(alice.call_remote("bob", &call, None, timeout).is_err());
+    }
+
+    #[test]
+    /// holdings kept in a store are held again by the next instance
+    fn persist_holdings() {
+        let name = format!("holochain_instance_holdings_{}.json", process::id());
+        let path = env::temp_dir().join(name);
+        let _ = fs::remove_file(&path);
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        assert_eq!(Ok(0), instance.persist_holdings(&path));
+        instance.dispatch_and_wait(Dht(Hold(test_entry())));
+        instance.stop_action_loop();
+
+        let mut restarted = Instance::new();
+        restarted.start_action_loop();
+        assert_eq!(Ok(1), restarted.persist_holdings(&path));
+        assert_eq!(Some(test_entry()), restarted.state().dht().holding(&test_entry().key()));
+        assert_eq!(
+            instance.state().dht().integration(&test_entry().key()),
+            restarted.state().dht().integration(&test_entry().key())
+        );
+
+        fs::write(&path, "not json").unwrap();
+        assert!(Instance::new().persist_holdings(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// forks revealed by the activity held are warranted and the warrants sent to the peers
+    fn forks_warranted() {
+        let network = MemoryNetwork::new();
+        let mut bob = Instance::new();
+        bob.start_action_loop();
+        bob.join_network(&network, "bob");
+        let mut carol = Instance::new();
+        carol.start_action_loop();
+        carol.join_network(&network, "carol");
+        bob.dispatch_and_wait(Dht(PeerSeen("carol".to_string(), StorageArc::default())));
+
+        let alice = test_agent_address("alice");
+        let (a, b) = test_fork();
+        for header in &[a.clone(), b.clone()] {
+            let aspect = Aspect::Activity(header.clone());
+            bob.dispatch_and_wait(Dht(HoldAspect(alice.clone(), aspect)));
+        }
+        let warrant = Warrant::fork(&alice, &a, &b).unwrap();
+        for _ in 0..100 {
+            if !carol.state().dht().warrants(&alice).is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(vec![warrant.clone()], bob.state().dht().warrants(&alice));
+        assert_eq!(vec![warrant], carol.state().dht().warrants(&alice));
+    }
+
+    #[test]
+    /// with checkpoints on the head of the chain is attested to the neighborhood
+    fn checkpoints_published() {
+        let network = MemoryNetwork::new();
+        let mut alice = Instance::new();
+        alice.start_action_loop();
+        alice.join_network(&network, "alice");
+        alice.set_network_config(&NetworkConfig {
+            checkpoint_interval_secs: Some(60),
+            ..NetworkConfig::default()
+        });
+        let mut bob = Instance::new();
+        bob.start_action_loop();
+        bob.join_network(&network, "bob");
+        alice.dispatch_and_wait(Dht(PeerSeen("bob".to_string(), StorageArc::default())));
+
+        alice.dispatch_and_wait(Agent(Commit(test_entry())));
+        let head = alice.state().agent().head().unwrap();
+        for _ in 0..100 {
+            if !checkpoints::checkpoints(&bob.state().dht(), "alice").is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        let held = checkpoints::checkpoints(&bob.state().dht(), "alice");
+        assert_eq!(1, held.len());
+        assert_eq!(head, held[0].head);
+        assert_eq!(0, held[0].index);
+        assert_eq!(Some(held[0].clone()), alice.state().nucleus().checkpoints().last());
+    }
+
+    #[test]
+    /// publishes queued offline go out once the network is joined
+    fn outbox_flushed_on_join() {
+        let mut alice = Instance::new();
+        alice.start_action_loop();
+        alice.dispatch_and_wait(Dht(PeerSeen("bob".to_string(), StorageArc::default())));
+        let mut bob = Instance::new();
+        bob.start_action_loop();
+
+        alice.state().nucleus().outbox().queue(test_publish()).unwrap();
+        assert_eq!(1, alice.outbox_depth());
+
+        let network = MemoryNetwork::new();
+        bob.join_network(&network, "bob");
+        alice.join_network(&network, "alice");
+        for _ in 0..100 {
+            if bob.state().dht().holding(&test_entry().key()).is_some() {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(Some(test_entry()), bob.state().dht().holding(&test_entry().key()));
+        assert_eq!(0, alice.outbox_depth());
+
+        // bob's receipt finds its way back
+        for _ in 0..100 {
+            if alice.publish_status(&test_entry().key()).receipts > 0 {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        let receipts = alice.validation_receipts(&test_entry().key());
+        assert_eq!(1, receipts.len());
+        assert_eq!("bob", receipts[0].validator);
+        assert!(!alice.publish_status(&test_entry().key()).pending);
+    }
+
+    #[test]
+    /// joining the network and hearing from peers changes the connectivity, which is signalled
+    fn connectivity_signalled() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        let signals = instance.state().nucleus().signal_bus().subscribe();
+        let next = || {
+            let signal = signals.recv_timeout(Duration::from_millis(2000)).unwrap();
+            assert_eq!(CONNECTIVITY_SIGNAL, signal.name);
+            signal.payload["to"].clone()
+        };
+        assert_eq!(Connectivity::Offline, instance.network_info().connectivity);
+
+        instance.join_network(&MemoryNetwork::new(), "alice");
+        assert_eq!(json!("bootstrapping"), next());
+        instance.dispatch_and_wait(Dht(PeerSeen("bob".to_string(), StorageArc::default())));
+        assert_eq!(json!({"connected": 1}), next());
+
+        let info = instance.network_info();
+        assert_eq!(Some("alice".to_string()), info.address);
+        assert_eq!("bob", info.peers[0].id);
+
+        instance.leave_network();
+        assert_eq!(json!("offline"), next());
+    }
+
+    #[test]
+    /// instances with presence on see each other online
+    fn presence_heartbeats() {
+        let network = MemoryNetwork::new();
+        let config = NetworkConfig {
+            presence: Some(PresenceConfig::default()),
+            ..NetworkConfig::default()
+        };
+        let join = |address: &str, peer: &str| {
+            let mut instance = Instance::new();
+            instance.start_action_loop();
+            instance.set_network_config(&config);
+            instance.join_network(&network, address);
+            instance.dispatch_and_wait(Dht(PeerSeen(peer.to_string(), StorageArc::default())));
+            instance
+        };
+        let alice = join("alice", "bob");
+        let bob = join("bob", "alice");
+
+        let deadline = Instant::now() + Duration::from_millis(2000);
+        while bob.online_agents().is_empty() && Instant::now() < deadline {
+            sleep(Duration::from_millis(IDLE_POLL_INTERVAL_MS));
+        }
+        assert_eq!(vec!["alice"], bob.online_agents());
+        bob.set_network_config(&NetworkConfig::default());
+        assert!(bob.online_agents().is_empty());
+        assert!(alice.online_agents().iter().all(|agent| agent == "bob"));
+    }
+
+    #[test]
+    /// held entries failing under a reloaded DNA are quarantined, and released when it is
+    /// reloaded again
+    fn dna_reloaded() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        assert!(instance.reload_dna(Dna::new()).is_err());
+
+        instance.dispatch_and_wait(Nucleus(InitApplication(test_link_dna())));
+        let deadline = Instant::now() + Duration::from_millis(2000);
+        while instance.state().nucleus().status() != NucleusStatus::Initialized
+            && Instant::now() < deadline
+        {
+            sleep(Duration::from_millis(IDLE_POLL_INTERVAL_MS));
+        }
+        let post = Entry::new("post", "hello");
+        let comment = Entry::new("comment", "nice post");
+        let link = Link::new(&post.key(), &comment.key(), "comments").to_entry();
+        for entry in &[post, comment, link.clone()] {
+            instance.dispatch_and_wait(Dht(Hold(entry.clone())));
+        }
+
+        let unlinked = Dna::new();
+        let report = instance.reload_dna(unlinked.clone()).unwrap();
+        assert_eq!(3, report.checked);
+        assert_eq!(vec![&link.key()], report.quarantined.keys().collect::<Vec<_>>());
+        let reason = report.quarantined[&link.key()].clone();
+        assert_eq!(
+            HoldingValidation::Quarantined(reason),
+            instance.state().dht().integration(&link.key()).unwrap().validation
+        );
+        assert_eq!(Some(unlinked), instance.state().nucleus().dna());
+
+        let report = instance.reload_dna(test_link_dna()).unwrap();
+        assert_eq!(vec![link.key()], report.released);
+    }
+
+    #[test]
+    /// the instance is only idle once staged commits are done
+    fn wait_until_idle() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        assert!(instance.is_idle());
+        assert!(instance.wait_until_idle(Duration::from_millis(0)));
+
+        instance.dispatch_and_wait(Agent(BeginStaging));
+        assert!(!instance.is_idle());
+        assert!(!instance.wait_until_idle(Duration::from_millis(20)));
+
+        let action_channel = instance.action_channel.clone();
+        thread::spawn(move || {
+            sleep(Duration::from_millis(20));
+            dispatch_action(&action_channel, Agent(AbortStaged));
+        });
+        assert!(instance.wait_until_idle(Duration::from_millis(1000)));
+    }
+
+    #[test]
+    /// running loops beat, stopped ones don't
+    fn health() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Agent(Commit(test_entry())));
+        let health = instance.health(Duration::from_millis(1000));
+        assert!(!health.is_wedged(Duration::from_millis(REDUX_LOOP_TIMEOUT_MS * 2)));
+        assert_eq!(None, health.reducing);
+        assert_eq!(
+            Some(instance.state().clone()),
+            instance.snapshot()
+        );
+
+        // a stopped loop doesn't beat anymore
+        instance.stop_action_loop();
+        sleep(Duration::from_millis(REDUX_LOOP_TIMEOUT_MS * 2));
+        let health = instance.health(Duration::from_millis(1000));
+        assert!(health.is_wedged(Duration::from_millis(REDUX_LOOP_TIMEOUT_MS)));
+    }
+
+    #[test]
+    /// instances pick up from the state they are given
+    fn from_state() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Agent(Commit(test_entry())));
+        let state = instance.snapshot().unwrap();
+
+        let mut restored = Instance::from_state(state.clone());
+        assert_eq!(state, *restored.state());
+        restored.start_action_loop();
+        restored.dispatch_and_wait(Agent(Commit(test_entry())));
+        assert_eq!(state.history.len() + 1, restored.state().history.len());
+    }
+
+    #[test]
+    /// scheduled functions are called by the scheduler once it is started
+    fn scheduler() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        let mut schedule = test_schedule();
+        schedule.start = unix_now() - 15;
+        instance.dispatch_and_wait(Nucleus(Schedule(schedule.clone())));
+
+        instance.start_scheduler(&SchedulerConfig {
+            resolution: Duration::from_millis(10),
+        });
+        let mut waited = 0;
+        while instance.state().nucleus().schedules()[&schedule.key()].last_tick.is_none() {
+            assert!(waited < 1000, "schedule was not fired");
+            sleep(Duration::from_millis(10));
+            waited += 10;
+        }
+        instance.stop_scheduler();
+
+        assert_eq!(
+            Some(1),
+            instance.state().nucleus().schedules()[&schedule.key()].last_tick
+        );
+    }
+}
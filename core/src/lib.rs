@@ -1,32 +1,113 @@
 #[macro_use]
 extern crate serde_derive;
+#[cfg(feature = "native")]
+extern crate argon2;
+#[cfg(feature = "native")]
+extern crate blake2;
+#[cfg(feature = "native")]
 extern crate chrono;
+// the types prost-build generates, see network::wire, name it by path
+#[cfg(feature = "native")]
+extern crate core;
+#[cfg(feature = "native")]
+extern crate crypto_secretbox;
+#[cfg(feature = "native")]
+extern crate ed25519_dalek;
+#[cfg(feature = "native")]
+extern crate libc;
+#[cfg(feature = "lmdb")]
+extern crate lmdb;
+extern crate miniz_oxide;
 extern crate multihash;
+#[cfg(feature = "native")]
+extern crate parity_wasm;
+#[cfg(feature = "pickle")]
+extern crate pickledb;
+#[cfg(feature = "native")]
+extern crate prost;
+#[cfg(feature = "native")]
+extern crate rand;
 extern crate rust_base58;
+#[cfg(feature = "s3")]
+extern crate rusty_s3;
+#[cfg(feature = "native")]
+extern crate salsa20;
 extern crate serde;
+#[cfg_attr(feature = "native", macro_use)]
 extern crate serde_json;
+#[cfg(feature = "native")]
+extern crate sha2;
+#[cfg(feature = "native")]
 extern crate snowflake;
 #[cfg(test)]
 extern crate test_utils;
+#[cfg(feature = "native")]
+extern crate tracing;
+#[cfg(feature = "native")]
+extern crate tracing_subscriber;
+#[cfg(feature = "s3")]
+extern crate ureq;
+#[cfg(feature = "s3")]
+extern crate url;
+#[cfg(feature = "native")]
 extern crate wasmi;
+#[cfg(feature = "native")]
+extern crate x25519_dalek;
 #[macro_use]
 extern crate bitflags;
 
+#[cfg(feature = "native")]
 extern crate holochain_agent;
+#[cfg(feature = "native")]
 extern crate holochain_dna;
+#[cfg(feature = "native")]
+#[macro_use]
+extern crate holochain_serialization;
 
+#[cfg(feature = "native")]
 pub mod agent;
+/// without the native feature only the agent's keys are there, for the hash table
+#[cfg(not(feature = "native"))]
+pub mod agent {
+    pub mod keys;
+}
+#[cfg(feature = "native")]
+pub mod anchors;
 pub mod chain;
+#[cfg(feature = "native")]
 pub mod context;
+#[cfg(feature = "native")]
+pub mod dht;
 pub mod error;
+pub mod fixtures;
 pub mod hash;
 pub mod hash_table;
+#[cfg(feature = "native")]
+pub mod health;
+#[cfg(feature = "native")]
+pub mod index;
+#[cfg(feature = "native")]
 pub mod instance;
+#[cfg(feature = "native")]
+pub mod limits;
+#[cfg(feature = "native")]
 pub mod logger;
+#[cfg(feature = "native")]
 pub mod network;
+#[cfg(feature = "native")]
 pub mod nucleus;
+#[cfg(feature = "native")]
 pub mod persister;
+#[cfg(feature = "native")]
+pub mod platform;
+#[cfg(feature = "native")]
+pub mod signal;
+#[cfg(feature = "native")]
 pub mod state;
+#[cfg(feature = "native")]
+pub mod trace;
+#[cfg(feature = "native")]
+pub mod validation;
 
 #[cfg(test)]
 mod tests {
@@ -49,7 +130,9 @@ mod tests {
         assert_eq!(instance.state().nucleus().dna(), Some(dna));
 
         // Wait for Init to finish
-        while instance.state().history.len() < 4 {
+        while !instance.state().nucleus().has_initialized()
+            && !instance.state().nucleus().has_initialization_failed()
+        {
             // TODO - #21
             // This println! should be converted to either a call to the app logger, or to the core debug log.
             println!("Waiting... {}", instance.state().history.len());
@@ -112,11 +195,14 @@ mod tests {
         assert_eq!(instance.state().nucleus().dna(), Some(dna));
 
         // Wait for Init to finish
-        while instance.state().history.len() < 2 {
+        while !instance.state().nucleus().has_initialized()
+            && !instance.state().nucleus().has_initialization_failed()
+        {
             println!("Waiting... {}", instance.state().history.len());
             sleep(Duration::from_millis(10));
         }
         assert!(instance.state().nucleus().has_initialized());
+        assert!(instance.state().agent().init_complete());
     }
 
     #[test]
@@ -176,6 +262,33 @@ mod tests {
         }
     }
 
+    #[test]
+    /// a call panicking on its way through the host still returns a result
+    fn call_ribosome_panic() {
+        let dna = test_utils::create_test_dna_with_wat(
+            "test_zome".to_string(),
+            "test_cap".to_string(),
+            Some(
+                r#"
+            (module
+                (memory (;0;) 17)
+                (func (export "main_dispatch") (param $p0 i32) (param $p1 i32))
+                (export "memory" (memory 0))
+            )
+        "#,
+            ),
+        );
+        let mut instance = create_instance(dna);
+
+        let call = FunctionCall::new("test_zome", "test_cap", "main", "");
+        let result = nucleus::call_and_wait_for_result(call, &mut instance);
+
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric("zome call panicked".to_string())),
+            result
+        );
+    }
+
     #[test]
     fn call_wrong_ribosome_function() {
         let dna = test_utils::create_test_dna_with_wat(
@@ -219,8 +332,10 @@ mod tests {
 
         let instance = create_instance(dna);
 
-        assert_eq!(instance.state().history.len(), 4);
         assert!(instance.state().nucleus().has_initialized());
+        assert!(instance.state().agent().init_complete());
+        // only the init complete marker was committed
+        assert_eq!(instance.state().agent().chain_length(), 1);
     }
 
     #[test]
@@ -246,8 +361,43 @@ mod tests {
 
         let instance = create_instance(dna);
 
-        assert_eq!(instance.state().history.len(), 4);
         assert!(instance.state().nucleus().has_initialized());
+        assert!(instance.state().agent().init_complete());
+        assert_eq!(instance.state().agent().chain_length(), 1);
+    }
+
+    #[test]
+    fn test_init_err() {
+        let dna = test_utils::create_test_dna_with_wat(
+            "test_zome".to_string(),
+            ReservedCapabilityNames::LifeCycle.as_str().to_string(),
+            Some(
+                r#"
+            (module
+                (memory (;0;) 17)
+                (func (export "genesis_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                    i32.const 0
+                )
+                (func (export "init_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                    i32.const 4
+                )
+                (data (i32.const 0)
+                    "fail"
+                )
+                (export "memory" (memory 0))
+            )
+        "#,
+            ),
+        );
+
+        let instance = create_instance(dna);
+
+        assert_eq!(
+            instance.state().nucleus().status(),
+            ::nucleus::NucleusStatus::InitializationFailed("fail".to_string())
+        );
+        assert!(!instance.state().agent().init_complete());
+        assert_eq!(instance.state().agent().chain_length(), 0);
     }
 
     #[test]
@@ -273,8 +423,10 @@ mod tests {
 
         let instance = create_instance(dna);
 
-        assert_eq!(instance.state().history.len(), 4);
-        assert!(instance.state().nucleus().has_initialized() == false);
+        assert!(instance.state().nucleus().has_initialization_failed());
+        assert!(!instance.state().agent().init_complete());
+        // nothing staged during genesis made it to the chain
+        assert_eq!(instance.state().agent().chain_length(), 0);
     }
 
 }
@@ -0,0 +1,157 @@
+//! resource limits keep a runaway app from exhausting the host running it
+//! an instance can cap the wasm memory of its zome calls, the bytes on its chain and the bytes it
+//! holds for the DHT, whatever would go over a limit fails instead and a signal reports it
+//! it can also cap how many read_only zome calls run at once, calls over it wait their turn, see
+//! nucleus::call_gate, and how much waits in its outbox, see network::outbox
+
+use serde_json;
+use signal::Signal;
+use std::fmt;
+
+/// name of the signal emitted when a limit is hit, its payload is the LimitExceeded
+pub const LIMIT_EXCEEDED_SIGNAL: &str = "resource_limit_exceeded";
+
+/// limits of a single instance, nothing is limited if not set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ResourceLimits {
+    /// max pages of 64KiB the memory of a zome call can grow to
+    #[serde(default)]
+    pub max_wasm_pages: Option<u32>,
+    /// max bytes of entry content committed to the chain
+    #[serde(default)]
+    pub max_chain_bytes: Option<u64>,
+    /// max bytes of entry content held for the DHT
+    #[serde(default)]
+    pub max_dht_bytes: Option<u64>,
+    /// max read_only zome calls running at once
+    #[serde(default)]
+    pub max_concurrent_reads: Option<usize>,
+    /// max publishes and messages waiting in the outbox at each priority
+    #[serde(default)]
+    pub max_outbox_depth: Option<usize>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Resource {
+    #[serde(rename = "wasm_pages")]
+    WasmPages,
+    #[serde(rename = "chain_bytes")]
+    ChainBytes,
+    #[serde(rename = "dht_bytes")]
+    DhtBytes,
+    #[serde(rename = "outbox_depth")]
+    OutboxDepth,
+}
+
+impl Resource {
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Resource::WasmPages => "wasm_pages",
+            Resource::ChainBytes => "chain_bytes",
+            Resource::DhtBytes => "dht_bytes",
+            Resource::OutboxDepth => "outbox_depth",
+        }
+    }
+}
+
+/// what went over which limit
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LimitExceeded {
+    pub resource: Resource,
+    pub limit: u64,
+    /// how much would have been used
+    pub requested: u64,
+}
+
+impl LimitExceeded {
+    /// the signal reporting it, from no zome in particular
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            zome: String::new(),
+            name: LIMIT_EXCEEDED_SIGNAL.to_string(),
+            payload: serde_json::to_value(self).expect("LimitExceeded should serialize"),
+        }
+    }
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} limit of {} exceeded, {} requested",
+            self.resource.as_str(),
+            self.limit,
+            self.requested
+        )
+    }
+}
+
+/// check using requested of the resource stays within limit, if any
+pub fn check(resource: Resource, limit: Option<u64>, requested: u64) -> Result<(), LimitExceeded> {
+    match limit {
+        Some(limit) if requested > limit => Err(LimitExceeded {
+            resource,
+            limit,
+            requested,
+        }),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    pub fn test_limit_exceeded() -> LimitExceeded {
+        LimitExceeded {
+            resource: Resource::ChainBytes,
+            limit: 10,
+            requested: 12,
+        }
+    }
+
+    #[test]
+    /// only going over a limit that is set fails
+    fn check_limit() {
+        assert_eq!(Ok(()), check(Resource::ChainBytes, None, 12));
+        assert_eq!(Ok(()), check(Resource::ChainBytes, Some(12), 12));
+        assert_eq!(
+            Err(test_limit_exceeded()),
+            check(Resource::ChainBytes, Some(10), 12)
+        );
+    }
+
+    #[test]
+    /// exceeded limits are reported as signals
+    fn signal() {
+        let signal = test_limit_exceeded().to_signal();
+        assert_eq!(LIMIT_EXCEEDED_SIGNAL, signal.name);
+        assert_eq!(
+            json!({"resource": "chain_bytes", "limit": 10, "requested": 12}),
+            signal.payload
+        );
+        assert_eq!(
+            "chain_bytes limit of 10 exceeded, 12 requested",
+            test_limit_exceeded().to_string()
+        );
+    }
+
+    #[test]
+    /// limits parse from config, unset limits don't limit
+    fn parse() {
+        let limits: ResourceLimits = serde_json::from_str(
+            r#"{"max_wasm_pages": 32, "max_dht_bytes": 1024, "max_outbox_depth": 100}"#,
+        ).unwrap();
+        assert_eq!(
+            ResourceLimits {
+                max_wasm_pages: Some(32),
+                max_chain_bytes: None,
+                max_dht_bytes: Some(1024),
+                max_concurrent_reads: None,
+                max_outbox_depth: Some(100),
+            },
+            limits
+        );
+        assert_eq!(ResourceLimits::default(), serde_json::from_str("{}").unwrap());
+    }
+}
@@ -3,10 +3,14 @@
 //! gets emitted globaly from the container.
 
 use chrono::Local;
-use std::fmt;
+use serde_json::Value;
+use std::{
+    collections::HashMap, fmt, sync::{Arc, Mutex},
+};
 
 /// trait that defines the logging functionality that holochain_core requires
-pub trait Logger: fmt::Debug {
+/// loggers are shared with the threads zome calls run in, see ZomeLogger
+pub trait Logger: fmt::Debug + Send {
     fn log(&mut self, msg: String);
 }
 
@@ -30,3 +34,196 @@ impl fmt::Debug for SimpleLogger {
         write!(f, "<empty>")
     }
 }
+
+/// levels zome log messages are filtered by, least severe first
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Trace,
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+/// default level zome log messages must be at to reach the host logger
+pub const ZOME_LOG_DEFAULT_LEVEL: LogLevel = LogLevel::Info;
+
+/// a structured message emitted by a zome through the log host function
+/// target is whatever the zome wants to group messages by, e.g. "posts::create"
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ZomeLogMessage {
+    pub level: LogLevel,
+    pub target: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+json_string_conversions!(ZomeLogMessage);
+
+#[derive(Default)]
+struct ZomeLoggerInner {
+    logger: Option<Arc<Mutex<dyn Logger>>>,
+    instance: String,
+    default_level: Option<LogLevel>,
+    zome_levels: HashMap<String, LogLevel>,
+}
+
+/// routes zome log messages to the host logger with the instance and zome they came from
+/// attached, dropping those below the level set for the zome
+/// the ZomeLogger is a cheap handle, clones share the same logger and levels
+#[derive(Clone, Default)]
+pub struct ZomeLogger {
+    inner: Arc<Mutex<ZomeLoggerInner>>,
+}
+
+impl PartialEq for ZomeLogger {
+    fn eq(&self, other: &ZomeLogger) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for ZomeLogger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("ZomeLogger")
+            .field("instance", &inner.instance)
+            .field("default_level", &inner.default_level)
+            .field("zome_levels", &inner.zome_levels)
+            .finish()
+    }
+}
+
+impl ZomeLogger {
+    /// send messages to the given host logger, tagged with the instance they came from
+    /// messages logged before a logger is attached are dropped
+    pub fn attach(&self, logger: Arc<Mutex<dyn Logger>>, instance: &str) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.logger = Some(logger);
+        inner.instance = instance.to_string();
+    }
+
+    /// set the level messages from zomes without a level of their own must be at
+    pub fn set_default_level(&self, level: LogLevel) {
+        self.inner.lock().unwrap().default_level = Some(level);
+    }
+
+    /// set the level messages from the given zome must be at
+    pub fn set_zome_level(&self, zome: &str, level: LogLevel) {
+        self.inner
+            .lock()
+            .unwrap()
+            .zome_levels
+            .insert(zome.to_string(), level);
+    }
+
+    /// the level messages from the given zome must be at to be logged
+    pub fn level(&self, zome: &str) -> LogLevel {
+        let inner = self.inner.lock().unwrap();
+        inner
+            .zome_levels
+            .get(zome)
+            .cloned()
+            .or(inner.default_level)
+            .unwrap_or(ZOME_LOG_DEFAULT_LEVEL)
+    }
+
+    /// log a message from a zome as a line of JSON, returns false if it was filtered out or
+    /// there is no logger to send it to
+    pub fn log(&self, zome: &str, message: &ZomeLogMessage) -> bool {
+        if message.level < self.level(zome) {
+            return false;
+        }
+        let inner = self.inner.lock().unwrap();
+        match inner.logger {
+            Some(ref logger) => {
+                let line = json!({
+                    "instance": inner.instance,
+                    "zome": zome,
+                    "level": message.level,
+                    "target": message.target,
+                    "payload": message.payload,
+                });
+                logger.lock().unwrap().log(line.to_string());
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{LogLevel, Logger, ZomeLogMessage, ZomeLogger};
+    use serde_json;
+    use std::{
+        fmt, sync::{Arc, Mutex},
+    };
+
+    /// logger that keeps everything logged to it
+    #[derive(Default)]
+    pub struct TestLogger {
+        pub log: Vec<String>,
+    }
+
+    impl Logger for TestLogger {
+        fn log(&mut self, msg: String) {
+            self.log.push(msg);
+        }
+    }
+
+    impl fmt::Debug for TestLogger {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:?}", self.log)
+        }
+    }
+
+    /// dummy zome log message at the given level
+    pub fn test_zome_log_message(level: LogLevel) -> ZomeLogMessage {
+        ZomeLogMessage {
+            level,
+            target: "posts::create".to_string(),
+            payload: json!({"title": "hello"}),
+        }
+    }
+
+    #[test]
+    /// levels parse from their lowercase names
+    fn log_level_json() {
+        assert_eq!(
+            LogLevel::Warn,
+            serde_json::from_str::<LogLevel>("\"warn\"").unwrap()
+        );
+        assert!(LogLevel::Debug < LogLevel::Error);
+    }
+
+    #[test]
+    /// messages go to the host logger with instance and zome attached, filtered per zome
+    fn zome_logger() {
+        let zome_logger = ZomeLogger::default();
+        assert!(!zome_logger.log("blog", &test_zome_log_message(LogLevel::Error)));
+
+        let logger = Arc::new(Mutex::new(TestLogger::default()));
+        zome_logger.attach(logger.clone(), "app");
+        assert!(zome_logger.log("blog", &test_zome_log_message(LogLevel::Info)));
+        assert!(!zome_logger.log("blog", &test_zome_log_message(LogLevel::Debug)));
+
+        zome_logger.set_zome_level("blog", LogLevel::Trace);
+        zome_logger.set_default_level(LogLevel::Error);
+        assert!(zome_logger.log("blog", &test_zome_log_message(LogLevel::Debug)));
+        assert!(!zome_logger.log("other", &test_zome_log_message(LogLevel::Warn)));
+
+        let log = logger.lock().unwrap().log.clone();
+        assert_eq!(2, log.len());
+        assert_eq!(
+            json!({
+                "instance": "app",
+                "zome": "blog",
+                "level": "info",
+                "target": "posts::create",
+                "payload": {"title": "hello"},
+            }),
+            serde_json::from_str::<serde_json::Value>(&log[0]).unwrap()
+        );
+    }
+}
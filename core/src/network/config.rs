@@ -0,0 +1,400 @@
+//! how patient an instance is with the network: how long it waits for answers and how it retries
+//! sending to nodes that can't be reached and how many nodes DHT gets and pushes go to
+//! presence is off unless its section is there, no checkpoints are published unless
+//! checkpoint_interval_secs is set, and the entries authors get held by the instance aren't
+//! limited unless author_rates says so
+//! direct messages come as JSON unless the node also takes other encodings, see encodings
+//! configs are JSON, every field is optional, e.g.
+//!
+//! ```json
+//! {
+//!     "direct_message_timeout_ms": 5000,
+//!     "retry": { "attempts": 5, "initial_delay_ms": 100, "max_delay_ms": 2000, "backoff": "exponential" },
+//!     "fan_out": { "alpha": 3, "k": 8 },
+//!     "presence": { "interval_secs": 30, "fresh_secs": 90 },
+//!     "checkpoint_interval_secs": 3600,
+//!     "author_rates": { "max_entries_per_minute": 60, "max_bytes_per_hour": 1048576 },
+//!     "encodings": ["msgpack"]
+//! }
+//! ```
+
+use error::HolochainError;
+use holochain_serialization::Encoding;
+use std::time::Duration;
+
+/// how long call_remote waits for the result by default
+pub const DIRECT_MESSAGE_DEFAULT_TIMEOUT_MS: u64 = 10000;
+/// how many times a message is tried by default before the node is given up on
+pub const RETRY_DEFAULT_ATTEMPTS: u32 = 3;
+pub const RETRY_DEFAULT_INITIAL_DELAY_MS: u64 = 100;
+pub const RETRY_DEFAULT_MAX_DELAY_MS: u64 = 2000;
+/// how many holders DHT gets ask at once by default
+pub const FAN_OUT_DEFAULT_ALPHA: usize = 3;
+/// how many of the nearest holders DHT gets ask and commits are pushed to at most by default
+pub const FAN_OUT_DEFAULT_K: usize = 8;
+/// how often presence heartbeats are sent by default
+pub const PRESENCE_DEFAULT_INTERVAL_SECS: u64 = 30;
+/// how long an agent counts as online after its last heartbeat by default
+pub const PRESENCE_DEFAULT_FRESH_SECS: u64 = 90;
+
+/// how the delay between attempts grows
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Backoff {
+    /// always initial_delay_ms
+    #[serde(rename = "constant")]
+    Constant,
+    /// initial_delay_ms more after every attempt
+    #[serde(rename = "linear")]
+    Linear,
+    /// twice as long after every attempt
+    #[serde(rename = "exponential")]
+    Exponential,
+}
+
+/// how sending to a node that can't be reached is retried
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RetryPolicy {
+    /// tries in total, the first one included, 1 never retries
+    pub attempts: u32,
+    /// delay before the first retry
+    pub initial_delay_ms: u64,
+    /// the delay never grows past this
+    pub max_delay_ms: u64,
+    pub backoff: Backoff,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: RETRY_DEFAULT_ATTEMPTS,
+            initial_delay_ms: RETRY_DEFAULT_INITIAL_DELAY_MS,
+            max_delay_ms: RETRY_DEFAULT_MAX_DELAY_MS,
+            backoff: Backoff::Exponential,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// a policy trying only once
+    pub fn never() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 1,
+            ..RetryPolicy::default()
+        }
+    }
+
+    /// how long to wait before the given retry, the first retry being 1
+    pub fn delay(&self, retry: u32) -> Duration {
+        let steps = u64::from(retry.max(1) - 1);
+        let delay = match self.backoff {
+            Backoff::Constant => self.initial_delay_ms,
+            Backoff::Linear => self.initial_delay_ms.saturating_mul(steps + 1),
+            Backoff::Exponential => self
+                .initial_delay_ms
+                .saturating_mul(1u64.checked_shl(steps as u32).unwrap_or(u64::MAX)),
+        };
+        Duration::from_millis(delay.min(self.max_delay_ms))
+    }
+
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.attempts == 0 {
+            return Err(HolochainError::new("retry attempts have to be at least 1"));
+        }
+        if self.initial_delay_ms > self.max_delay_ms {
+            return Err(HolochainError::new(&format!(
+                "retry initial_delay_ms {} is over max_delay_ms {}",
+                self.initial_delay_ms, self.max_delay_ms
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// how DHT gets spread over the holders of an address, see dht::read
+/// alpha holders are asked at once, each one failing is replaced by the next nearest one until
+/// enough have answered or k were asked
+/// what is committed is pushed to the k nearest holders at once, see network::outbox
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FanOut {
+    pub alpha: usize,
+    pub k: usize,
+}
+
+impl Default for FanOut {
+    fn default() -> Self {
+        FanOut {
+            alpha: FAN_OUT_DEFAULT_ALPHA,
+            k: FAN_OUT_DEFAULT_K,
+        }
+    }
+}
+
+impl FanOut {
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.alpha == 0 {
+            return Err(HolochainError::new("fan_out alpha has to be at least 1"));
+        }
+        if self.k < self.alpha {
+            return Err(HolochainError::new(&format!(
+                "fan_out k {} is under alpha {}",
+                self.k, self.alpha
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// how agents tell their neighborhood they are online, see network::presence
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PresenceConfig {
+    pub interval_secs: u64,
+    /// agents count as online for this long after their last heartbeat
+    pub fresh_secs: u64,
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        PresenceConfig {
+            interval_secs: PRESENCE_DEFAULT_INTERVAL_SECS,
+            fresh_secs: PRESENCE_DEFAULT_FRESH_SECS,
+        }
+    }
+}
+
+impl PresenceConfig {
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.interval_secs == 0 {
+            return Err(HolochainError::new(
+                "presence interval_secs has to be more than 0",
+            ));
+        }
+        if self.fresh_secs < self.interval_secs {
+            return Err(HolochainError::new(&format!(
+                "presence fresh_secs {} is under interval_secs {}",
+                self.fresh_secs, self.interval_secs
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// how much of a single author the instance takes to hold, so a hostile agent can't flood it,
+/// see validation::rates
+/// nothing is limited if not set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthorRateLimits {
+    /// max entries of an author taken in any minute
+    pub max_entries_per_minute: Option<u64>,
+    /// max bytes of entry content of an author taken in any hour
+    pub max_bytes_per_hour: Option<u64>,
+}
+
+impl AuthorRateLimits {
+    /// true if any limit is set
+    pub fn is_limited(&self) -> bool {
+        self.max_entries_per_minute.is_some() || self.max_bytes_per_hour.is_some()
+    }
+
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.max_entries_per_minute == Some(0) || self.max_bytes_per_hour == Some(0) {
+            return Err(HolochainError::new(
+                "author rate limits have to be more than 0, leave them out for no limit",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// network settings of an instance
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+    /// how long call_remote waits for the result
+    pub direct_message_timeout_ms: u64,
+    /// how direct messages to nodes that can't be reached are retried
+    pub retry: RetryPolicy,
+    /// how DHT gets ask other nodes and how many commits are pushed to
+    pub fan_out: FanOut,
+    /// how agents tell they are online, None for no presence
+    pub presence: Option<PresenceConfig>,
+    /// how often the head of the chain is attested to the neighborhood of the agent, None for no
+    /// checkpoints, see agent::checkpoints
+    pub checkpoint_interval_secs: Option<u64>,
+    /// how much of each author is taken to hold
+    pub author_rates: AuthorRateLimits,
+    /// encodings the node takes direct messages in besides JSON, most preferred first, senders
+    /// use the first one they speak, see MemoryNetwork::send()
+    pub encodings: Vec<Encoding>,
+}
+
+impl Default for NetworkConfig {
+    fn default() -> Self {
+        NetworkConfig {
+            direct_message_timeout_ms: DIRECT_MESSAGE_DEFAULT_TIMEOUT_MS,
+            retry: RetryPolicy::default(),
+            fan_out: FanOut::default(),
+            presence: None,
+            checkpoint_interval_secs: None,
+            author_rates: AuthorRateLimits::default(),
+            encodings: Vec::new(),
+        }
+    }
+}
+
+impl NetworkConfig {
+    pub fn direct_message_timeout(&self) -> Duration {
+        Duration::from_millis(self.direct_message_timeout_ms)
+    }
+
+    /// checks the settings make sense, e.g. when loading them
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.direct_message_timeout_ms == 0 {
+            return Err(HolochainError::new(
+                "direct_message_timeout_ms has to be more than 0",
+            ));
+        }
+        self.retry.check()?;
+        self.fan_out.check()?;
+        self.author_rates.check()?;
+        if self.checkpoint_interval_secs == Some(0) {
+            return Err(HolochainError::new(
+                "checkpoint_interval_secs has to be more than 0, leave it out for no checkpoints",
+            ));
+        }
+        match self.presence {
+            Some(ref presence) => presence.check(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use serde_json;
+
+    /// exponential policy starting at 10ms capped at 50ms
+    pub fn test_retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            attempts: 4,
+            initial_delay_ms: 10,
+            max_delay_ms: 50,
+            backoff: Backoff::Exponential,
+        }
+    }
+
+    #[test]
+    /// delays grow with the backoff up to the max
+    fn delays() {
+        let delays = |backoff| {
+            let policy = RetryPolicy {
+                backoff,
+                ..test_retry_policy()
+            };
+            (1..6)
+                .map(|retry| policy.delay(retry).as_millis() as u64)
+                .collect::<Vec<u64>>()
+        };
+        assert_eq!(vec![10, 10, 10, 10, 10], delays(Backoff::Constant));
+        assert_eq!(vec![10, 20, 30, 40, 50], delays(Backoff::Linear));
+        assert_eq!(vec![10, 20, 40, 50, 50], delays(Backoff::Exponential));
+        assert_eq!(
+            Duration::from_millis(50),
+            test_retry_policy().delay(u32::MAX)
+        );
+    }
+
+    #[test]
+    /// partial configs are filled in with defaults
+    fn parse() {
+        let config: NetworkConfig = serde_json::from_str(
+            r#"{"direct_message_timeout_ms": 500, "retry": {"backoff": "linear"}}"#,
+        ).unwrap();
+        assert_eq!(Duration::from_millis(500), config.direct_message_timeout());
+        assert_eq!(Backoff::Linear, config.retry.backoff);
+        assert_eq!(RETRY_DEFAULT_ATTEMPTS, config.retry.attempts);
+        assert_eq!(FanOut::default(), config.fan_out);
+        assert_eq!(None, config.presence);
+        assert!(!config.author_rates.is_limited());
+        let config: NetworkConfig =
+            serde_json::from_str(r#"{"presence": {"fresh_secs": 120}}"#).unwrap();
+        assert_eq!(
+            Some(PresenceConfig {
+                interval_secs: PRESENCE_DEFAULT_INTERVAL_SECS,
+                fresh_secs: 120,
+            }),
+            config.presence
+        );
+        let config: NetworkConfig =
+            serde_json::from_str(r#"{"author_rates": {"max_entries_per_minute": 60}}"#).unwrap();
+        assert_eq!(Some(60), config.author_rates.max_entries_per_minute);
+        assert_eq!(None, config.author_rates.max_bytes_per_hour);
+        let config: NetworkConfig = serde_json::from_str(r#"{"encodings": ["msgpack"]}"#).unwrap();
+        assert_eq!(vec![Encoding::MessagePack], config.encodings);
+        assert_eq!(NetworkConfig::default(), serde_json::from_str("{}").unwrap());
+        assert!(serde_json::from_str::<NetworkConfig>(r#"{"retry": {"backoff": "x"}}"#).is_err());
+    }
+
+    #[test]
+    /// nonsensical settings are caught
+    fn check() {
+        assert_eq!(Ok(()), NetworkConfig::default().check());
+        assert_eq!(Ok(()), RetryPolicy::never().check());
+        let config = |timeout, retry| NetworkConfig {
+            direct_message_timeout_ms: timeout,
+            retry,
+            ..NetworkConfig::default()
+        };
+        assert!(config(0, RetryPolicy::default()).check().is_err());
+        let no_attempts = RetryPolicy {
+            attempts: 0,
+            ..test_retry_policy()
+        };
+        assert!(config(100, no_attempts).check().is_err());
+        let inverted = RetryPolicy {
+            initial_delay_ms: 100,
+            ..test_retry_policy()
+        };
+        assert!(config(100, inverted).check().is_err());
+
+        let fan_out = |alpha, k| NetworkConfig {
+            fan_out: FanOut { alpha, k },
+            ..NetworkConfig::default()
+        };
+        assert_eq!(Ok(()), fan_out(1, 1).check());
+        assert!(fan_out(0, 8).check().is_err());
+        assert!(fan_out(4, 3).check().is_err());
+
+        let presence = |interval_secs, fresh_secs| NetworkConfig {
+            presence: Some(PresenceConfig {
+                interval_secs,
+                fresh_secs,
+            }),
+            ..NetworkConfig::default()
+        };
+        assert_eq!(Ok(()), presence(10, 10).check());
+        assert!(presence(0, 10).check().is_err());
+        assert!(presence(10, 5).check().is_err());
+
+        let author_rates = |max_entries_per_minute| NetworkConfig {
+            author_rates: AuthorRateLimits {
+                max_entries_per_minute,
+                max_bytes_per_hour: None,
+            },
+            ..NetworkConfig::default()
+        };
+        assert_eq!(Ok(()), author_rates(Some(1)).check());
+        assert!(author_rates(Some(0)).check().is_err());
+
+        let checkpoints = |checkpoint_interval_secs| NetworkConfig {
+            checkpoint_interval_secs,
+            ..NetworkConfig::default()
+        };
+        assert_eq!(Ok(()), checkpoints(Some(60)).check());
+        assert!(checkpoints(Some(0)).check().is_err());
+    }
+}
@@ -0,0 +1,254 @@
+//! how connected an instance is, worked out from whether it is on a network and when it last
+//! heard from its peers, e.g. through gossip
+//! the instance checks after every action it reduces and every beat of its idle action loop,
+//! each change is emitted as a CONNECTIVITY_SIGNAL so apps can show it
+
+use dht::{coverage, StorageArc, DHT_PEER_STALE_SECS};
+use serde_json;
+use signal::{Signal, SignalBus};
+use state::State;
+use std::{
+    fmt, sync::{Arc, Mutex},
+};
+
+/// name of the signal emitted when the connectivity changes, its payload is the
+/// ConnectivityChange
+pub const CONNECTIVITY_SIGNAL: &str = "connectivity_changed";
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Connectivity {
+    /// not on a network
+    #[default]
+    #[serde(rename = "offline")]
+    Offline,
+    /// on a network without having heard from any peer yet
+    #[serde(rename = "bootstrapping")]
+    Bootstrapping,
+    /// on a network with the given number of peers heard from recently
+    #[serde(rename = "connected")]
+    Connected(usize),
+    /// on a network, but not heard from any peer for DHT_PEER_STALE_SECS
+    #[serde(rename = "partitioned")]
+    Partitioned,
+}
+
+impl Connectivity {
+    /// the connectivity of an instance on a network or not, given how many seconds ago it last
+    /// heard from each of its peers
+    pub fn of(on_network: bool, peer_ages: &[u64]) -> Connectivity {
+        let fresh = peer_ages
+            .iter()
+            .filter(|age| **age < DHT_PEER_STALE_SECS)
+            .count();
+        if !on_network {
+            Connectivity::Offline
+        } else if peer_ages.is_empty() {
+            Connectivity::Bootstrapping
+        } else if fresh == 0 {
+            Connectivity::Partitioned
+        } else {
+            Connectivity::Connected(fresh)
+        }
+    }
+
+    /// the connectivity of the instance with the state
+    pub fn of_state(state: &State) -> Connectivity {
+        let on_network = state.nucleus().messenger().address().is_some();
+        let ages: Vec<u64> = state
+            .dht()
+            .peers()
+            .iter()
+            .map(|peer| peer.last_seen.elapsed().as_secs())
+            .collect();
+        Connectivity::of(on_network, &ages)
+    }
+}
+
+/// the payload of CONNECTIVITY_SIGNAL
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ConnectivityChange {
+    pub from: Connectivity,
+    pub to: Connectivity,
+}
+
+impl ConnectivityChange {
+    /// the signal reporting it, from no zome in particular
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            zome: String::new(),
+            name: CONNECTIVITY_SIGNAL.to_string(),
+            payload: serde_json::to_value(self).expect("ConnectivityChange should serialize"),
+        }
+    }
+}
+
+/// keeps the last connectivity of an instance to tell when it changes
+/// the monitor is a cheap handle, clones share the same connectivity
+#[derive(Clone, Default)]
+pub struct ConnectivityMonitor {
+    current: Arc<Mutex<Connectivity>>,
+}
+
+impl PartialEq for ConnectivityMonitor {
+    fn eq(&self, other: &ConnectivityMonitor) -> bool {
+        Arc::ptr_eq(&self.current, &other.current)
+    }
+}
+
+impl fmt::Debug for ConnectivityMonitor {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ConnectivityMonitor")
+            .field("current", &*self.current.lock().unwrap())
+            .finish()
+    }
+}
+
+impl ConnectivityMonitor {
+    pub fn current(&self) -> Connectivity {
+        *self.current.lock().unwrap()
+    }
+
+    /// move to the connectivity, signalling the change on the bus if it is one
+    /// returns the change, if any
+    pub fn update(&self, to: Connectivity, signals: &SignalBus) -> Option<ConnectivityChange> {
+        let change = {
+            let mut current = self.current.lock().unwrap();
+            if *current == to {
+                return None;
+            }
+            let change = ConnectivityChange { from: *current, to };
+            *current = to;
+            change
+        };
+        signals.emit(&change.to_signal());
+        Some(change)
+    }
+}
+
+/// update the connectivity of the instance with the state
+pub fn check(state: &State) -> Option<ConnectivityChange> {
+    let nucleus = state.nucleus();
+    nucleus
+        .connectivity()
+        .update(Connectivity::of_state(state), nucleus.signal_bus())
+}
+
+/// what an instance knows about a peer
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub id: String,
+    pub arc: StorageArc,
+    /// seconds since the peer was last heard from
+    pub last_seen_secs: u64,
+}
+
+/// where an instance stands on the network
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NetworkInfo {
+    pub connectivity: Connectivity,
+    /// agent address the instance is on the network under, None if it is offline
+    pub address: Option<String>,
+    /// peers by id
+    pub peers: Vec<PeerInfo>,
+    /// the range of addresses the instance holds
+    pub arc: StorageArc,
+    /// fraction of the address space the arc covers
+    pub arc_size: f64,
+    /// seconds since any peer was last heard from, None if none ever was
+    pub last_gossip_secs: Option<u64>,
+}
+
+/// where the instance with the state stands on the network
+pub fn network_info(state: &State) -> NetworkInfo {
+    let dht = state.dht();
+    let mut peers: Vec<PeerInfo> = dht
+        .peers()
+        .into_iter()
+        .map(|peer| PeerInfo {
+            last_seen_secs: peer.last_seen.elapsed().as_secs(),
+            id: peer.id,
+            arc: peer.arc,
+        })
+        .collect();
+    peers.sort_by(|a, b| a.id.cmp(&b.id));
+    let arc = dht.arc();
+    NetworkInfo {
+        connectivity: Connectivity::of_state(state),
+        address: state.nucleus().messenger().address(),
+        last_gossip_secs: peers.iter().map(|peer| peer.last_seen_secs).min(),
+        peers,
+        arc_size: coverage(&[&arc]),
+        arc,
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::Action::{PeerSeen, SetArc};
+    use instance::Observer;
+    use network::direct_message::MemoryNetwork;
+    use state::{Action::Dht, ActionWrapper};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    /// connectivity follows from being on a network and the age of what was heard from peers
+    fn connectivity_of() {
+        let stale = DHT_PEER_STALE_SECS;
+        assert_eq!(Connectivity::Offline, Connectivity::of(false, &[1]));
+        assert_eq!(Connectivity::Bootstrapping, Connectivity::of(true, &[]));
+        assert_eq!(Connectivity::Connected(2), Connectivity::of(true, &[0, 1, stale]));
+        assert_eq!(Connectivity::Partitioned, Connectivity::of(true, &[stale, stale + 1]));
+    }
+
+    #[test]
+    /// changes are signalled, staying the same isn't
+    fn monitor_signals_changes() {
+        let monitor = ConnectivityMonitor::default();
+        let bus = SignalBus::default();
+        let signals = bus.subscribe();
+        assert_eq!(Connectivity::Offline, monitor.current());
+        assert_eq!(None, monitor.update(Connectivity::Offline, &bus));
+
+        let change = ConnectivityChange {
+            from: Connectivity::Offline,
+            to: Connectivity::Connected(3),
+        };
+        assert_eq!(Some(change), monitor.update(Connectivity::Connected(3), &bus));
+        assert_eq!(Connectivity::Connected(3), monitor.current());
+        let signal = signals.try_recv().unwrap();
+        assert_eq!(CONNECTIVITY_SIGNAL, signal.name);
+        assert_eq!(json!({"from": "offline", "to": {"connected": 3}}), signal.payload);
+        assert!(signals.try_recv().is_err());
+    }
+
+    #[test]
+    /// the info reports the peers, the arc and the connectivity
+    fn info() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let mut state = State::new();
+        for action in [
+            PeerSeen("bob".to_string(), StorageArc::default()),
+            PeerSeen("alice".to_string(), StorageArc::new(0, 1 << 29)),
+            SetArc(StorageArc::new(0, 1 << 30)),
+        ] {
+            state = state.reduce(ActionWrapper::new(Dht(action)), &sender, &tx_observer);
+        }
+        let info = network_info(&state);
+        assert_eq!(Connectivity::Offline, info.connectivity);
+        assert_eq!(None, info.address);
+        assert_eq!(vec!["alice", "bob"], info.peers.iter().map(|p| &p.id[..]).collect::<Vec<_>>());
+        assert_eq!(Some(0), info.last_gossip_secs);
+        assert!((info.arc_size - 0.5).abs() < 0.001);
+
+        let _receiver = state
+            .nucleus()
+            .messenger()
+            .connect(&MemoryNetwork::new(), "carol");
+        assert_eq!(Some("carol".to_string()), network_info(&state).address);
+        assert_eq!(Connectivity::Connected(2), network_info(&state).connectivity);
+        assert!(check(&state).is_some());
+        assert!(check(&state).is_none());
+    }
+}
@@ -0,0 +1,1765 @@
+//! direct messages go straight to another agent's node instead of through the DHT, e.g. to call a
+//! zome function on it with call_remote
+//! nodes are reached by agent address over a MemoryNetwork, an in-process transport connecting
+//! the instances run side by side in a container
+//! a remote call is answered with the result of a zome call the remote node makes on its own
+//! instance, after checking the caller is allowed to call the capability, see check_remote_call()
+//! nodes also ask each other for the aspects they hold of an address, see dht::read, and publish
+//! aspects of what they commit to the nodes holding it, see outbox
+//! a node leaving says goodbye to the agents it talked to, so calls they still wait on fail
+//! right away instead of timing out
+//! zomes also send each other fire and forget signals, see remote_signal, and nodes tell their
+//! neighborhood they are online, see presence
+//! agents share the secrets of their groups with the other members, see agent::groups
+//! holders of gated DNAs ask authors for their validation package before holding what they
+//! publish, see validation::packages
+//! messages go over the network encoded as the receiving node asked for, JSON unless it takes
+//! MessagePack, see NetworkConfig::encodings
+//! nodes written in other languages exchange them in protocol buffers instead, see wire
+//! messages are sealed to the key of the agent they are for before they go on the wire, the
+//! node opens them before they are received, see sealing and Inbox
+
+use agent::{
+    groups::GroupSecret, membrane::{self, AgentId},
+};
+use dht::{aspect::Aspect, HoldingValidation};
+use error::HolochainError;
+use holochain_dna::{
+    zome::capabilities::{Membrane, ReservedCapabilityNames}, Dna,
+};
+use holochain_serialization::encoding::{Encoding, ENCODINGS};
+use instance::Observer;
+use network::{
+    config::NetworkConfig, remote_signal::{self, RemoteSignal},
+    sealing::{self, KeyPair, PublicKey, Sealed}, Envelope,
+};
+use nucleus::{
+    call_zome_and_wait_for_result, scheduler::unix_now, FunctionCall, NucleusState,
+};
+use platform;
+use serde_json;
+use state::{self, State};
+use std::{
+    collections::{BTreeSet, HashMap}, fmt, sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, RecvError, RecvTimeoutError, Sender, TryRecvError}, Arc, Mutex,
+        RwLock,
+    },
+    time::{Duration, Instant},
+};
+use validation::{
+    packages::{Rejection, ValidationPackage}, rates, receipts::ValidationReceipt, timestamps,
+};
+
+/// a zome function call made on behalf of another agent
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RemoteCall {
+    /// matches the result to the call, unique among the caller's calls
+    pub id: String,
+    /// address of the calling agent, the result is sent back to it
+    pub from: String,
+    pub zome: String,
+    pub capability: String,
+    pub function: String,
+    /// secret of a capability grant, needed unless the capability is public
+    #[serde(default)]
+    pub cap_secret: Option<String>,
+    #[serde(default)]
+    pub parameters: String,
+}
+
+impl RemoteCall {
+    /// the zome function call to make for it, made by the caller
+    pub fn call(&self) -> FunctionCall {
+        FunctionCall::new(
+            self.zome.clone(),
+            self.capability.clone(),
+            self.function.clone(),
+            self.parameters.clone(),
+        ).with_caller(&self.from, self.cap_secret.clone())
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum DirectMessage {
+    CallRemote(RemoteCall),
+    /// the result of the remote call with the given id, or why it failed
+    CallRemoteResult(String, Result<String, String>),
+    /// ask for the aspects the agent holds of address, see dht::read
+    GetAspects {
+        id: String,
+        from: String,
+        address: String,
+    },
+    /// the aspects asked for with the given id
+    GetAspectsResult(String, Vec<Aspect>),
+    /// aspects of the address for the agent to hold, see network::outbox
+    /// the holder sends a ValidationReceipt back to the publisher
+    Publish {
+        from: String,
+        address: String,
+        aspects: Vec<Aspect>,
+    },
+    /// a holder's receipt for what was published to it, see validation::receipts
+    ValidationReceipt(ValidationReceipt),
+    /// signals zomes of the sending agent sent to the agent, see network::remote_signal
+    RemoteSignals(Vec<RemoteSignal>),
+    /// the agent with the given address is online, see network::presence
+    Heartbeat(String),
+    /// the agent with the given address is leaving the network
+    Goodbye(String),
+    /// the agent stopped holding the entry at address as its retention rule says, see
+    /// dht::retention
+    Pruned { from: String, address: String },
+    /// a secret its creator shares with the other members of the group, see agent::groups
+    GroupSecret(GroupSecret),
+    /// ask for the validation package of the agent, see validation::packages
+    GetValidationPackage { id: String, from: String },
+    /// the validation package asked for with the given id, or why there is none yet
+    ValidationPackage(String, Result<ValidationPackage, String>),
+    /// the sender rejected the validation package of the agent, see validation::packages
+    AgentRejected(Rejection),
+}
+
+impl DirectMessage {
+    /// address of the agent the message comes from, None for the results of what was asked
+    pub fn sender(&self) -> Option<&str> {
+        match *self {
+            DirectMessage::CallRemote(ref remote_call) => Some(&remote_call.from),
+            DirectMessage::GetAspects { ref from, .. }
+            | DirectMessage::GetValidationPackage { ref from, .. } => Some(from),
+            DirectMessage::Publish { ref from, .. } | DirectMessage::Pruned { ref from, .. } => {
+                Some(from)
+            }
+            DirectMessage::ValidationReceipt(ref receipt) => Some(&receipt.validator),
+            DirectMessage::GroupSecret(ref secret) => Some(&secret.creator),
+            DirectMessage::AgentRejected(ref rejection) => Some(&rejection.validator),
+            DirectMessage::RemoteSignals(ref signals) => {
+                signals.first().map(|signal| signal.from.as_str())
+            }
+            DirectMessage::Heartbeat(ref address) | DirectMessage::Goodbye(ref address) => {
+                Some(address)
+            }
+            DirectMessage::CallRemoteResult(..)
+            | DirectMessage::GetAspectsResult(..)
+            | DirectMessage::ValidationPackage(..) => None,
+        }
+    }
+}
+
+/// a sealed message as it arrives at a node, with the encoding it came in
+type Frame = (Encoding, Sealed);
+
+/// a node connected to a MemoryNetwork
+struct Node {
+    sender: Sender<Frame>,
+    /// encodings the node takes besides JSON, most preferred first
+    encodings: Vec<Encoding>,
+    /// the key messages for the node are sealed to
+    public_key: PublicKey,
+}
+
+/// the envelope in the given encoding, sealed to the key of the agent it is for
+pub fn seal(
+    to: &str,
+    envelope: &Envelope<DirectMessage>,
+    encoding: Encoding,
+    public_key: &PublicKey,
+) -> Result<Sealed, HolochainError> {
+    let bytes = encoding
+        .encode(envelope)
+        .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))?;
+    Ok(Sealed {
+        to: to.to_string(),
+        payload: sealing::seal(&bytes, public_key),
+    })
+}
+
+/// put a sealed message through the wire in the given encoding, giving what arrives and the
+/// bytes it took
+fn carry(sealed: &Sealed, encoding: Encoding) -> Result<(Sealed, usize), HolochainError> {
+    let bytes = encoding
+        .encode(sealed)
+        .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))?;
+    let received = encoding
+        .decode(&bytes)
+        .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))?;
+    Ok((received, bytes.len()))
+}
+
+/// the messages sent to a node connected to a MemoryNetwork, opened with its keys as they are
+/// taken out
+/// messages that can't be opened, sealed to an earlier connection or changed on the way, are
+/// dropped
+pub struct Inbox {
+    receiver: Receiver<Frame>,
+    keys: KeyPair,
+}
+
+impl Inbox {
+    /// the key messages for the node are sealed to
+    pub fn public_key(&self) -> PublicKey {
+        self.keys.public()
+    }
+
+    fn open(&self, frame: Frame) -> Option<Envelope<DirectMessage>> {
+        let (encoding, sealed) = frame;
+        let bytes = sealing::open(&sealed.payload, &self.keys).ok()?;
+        encoding.decode(&bytes).ok()
+    }
+
+    /// block until a message arrives, an error once the node is disconnected
+    pub fn recv(&self) -> Result<Envelope<DirectMessage>, RecvError> {
+        loop {
+            if let Some(envelope) = self.open(self.receiver.recv()?) {
+                return Ok(envelope);
+            }
+        }
+    }
+
+    /// the next message if one is there
+    pub fn try_recv(&self) -> Result<Envelope<DirectMessage>, TryRecvError> {
+        loop {
+            if let Some(envelope) = self.open(self.receiver.try_recv()?) {
+                return Ok(envelope);
+            }
+        }
+    }
+
+    /// block until a message arrives or the timeout passes
+    pub fn recv_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<Envelope<DirectMessage>, RecvTimeoutError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let left = deadline.saturating_duration_since(Instant::now());
+            if let Some(envelope) = self.open(self.receiver.recv_timeout(left)?) {
+                return Ok(envelope);
+            }
+        }
+    }
+
+    /// the messages there are, without waiting for more
+    pub fn try_iter<'a>(&'a self) -> impl Iterator<Item = Envelope<DirectMessage>> + 'a {
+        self.receiver
+            .try_iter()
+            .filter_map(move |frame| self.open(frame))
+    }
+}
+
+/// the messages as they arrive, until the node is disconnected
+impl Iterator for Inbox {
+    type Item = Envelope<DirectMessage>;
+
+    fn next(&mut self) -> Option<Envelope<DirectMessage>> {
+        self.recv().ok()
+    }
+}
+
+/// in-process transport delivering direct messages to the nodes connected by agent address
+/// messages are encoded and decoded on the way as they would be on a real wire, so what can't
+/// make the trip fails here too and the bytes sent can be told
+/// the network is a cheap handle, clones share the same connections
+/// nodes in different spaces of a network never reach each other, see space()
+#[derive(Clone, Default)]
+pub struct MemoryNetwork {
+    nodes: Arc<Mutex<HashMap<String, Node>>>,
+    spaces: Arc<Mutex<HashMap<String, MemoryNetwork>>>,
+    bytes_sent: Arc<AtomicUsize>,
+}
+
+impl PartialEq for MemoryNetwork {
+    fn eq(&self, other: &MemoryNetwork) -> bool {
+        Arc::ptr_eq(&self.nodes, &other.nodes)
+    }
+}
+
+impl fmt::Debug for MemoryNetwork {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("MemoryNetwork")
+            .field("nodes", &self.nodes.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl MemoryNetwork {
+    pub fn new() -> MemoryNetwork {
+        MemoryNetwork::default()
+    }
+
+    /// the part of the network for the given seed, e.g. a DNA hash, the same one for every
+    /// clone of the network
+    pub fn space(&self, seed: &str) -> MemoryNetwork {
+        self.spaces
+            .lock()
+            .unwrap()
+            .entry(seed.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// receive the messages sent to an agent, replacing any previous connection of the agent
+    /// the node gets a fresh key pair messages for it are sealed to, the network only knows the
+    /// public key
+    /// the messages come as JSON until the agent takes other encodings, see accept()
+    pub fn connect(&self, address: &str) -> Inbox {
+        let (sender, receiver) = channel();
+        let keys = KeyPair::generate();
+        let node = Node {
+            sender,
+            encodings: Vec::new(),
+            public_key: keys.public(),
+        };
+        self.nodes.lock().unwrap().insert(address.to_string(), node);
+        Inbox { receiver, keys }
+    }
+
+    /// the key messages for the agent at the address are sealed to, None if it isn't connected
+    pub fn public_key(&self, address: &str) -> Option<PublicKey> {
+        let nodes = self.nodes.lock().unwrap();
+        nodes.get(address).map(|node| node.public_key)
+    }
+
+    /// have the messages sent to a connected agent come in the first of the encodings given
+    /// that senders speak, JSON otherwise
+    pub fn accept(&self, address: &str, encodings: &[Encoding]) {
+        if let Some(node) = self.nodes.lock().unwrap().get_mut(address) {
+            node.encodings = encodings.to_vec();
+        }
+    }
+
+    /// the bytes of the messages that went over the network so far
+    pub fn bytes_sent(&self) -> usize {
+        self.bytes_sent.load(Ordering::SeqCst)
+    }
+
+    /// stop delivering messages to an agent, which ends its receiver
+    pub fn disconnect(&self, address: &str) {
+        self.nodes.lock().unwrap().remove(address);
+    }
+
+    /// deliver a message to an agent, in the encoding the agent prefers, see accept(), sealed to
+    /// its key so only who it is for goes over the wire in the clear
+    pub fn send(
+        &self,
+        to: &str,
+        envelope: Envelope<DirectMessage>,
+    ) -> Result<(), HolochainError> {
+        let mut nodes = self.nodes.lock().unwrap();
+        let delivered = match nodes.get(to) {
+            Some(node) => {
+                let encoding = Encoding::negotiate(&node.encodings, ENCODINGS);
+                let sealed = seal(to, &envelope, encoding, &node.public_key)?;
+                let (received, bytes) = carry(&sealed, encoding)?;
+                self.bytes_sent.fetch_add(bytes, Ordering::SeqCst);
+                node.sender.send((encoding, received)).is_ok()
+            }
+            None => false,
+        };
+        if delivered {
+            Ok(())
+        } else {
+            nodes.remove(to);
+            Err(HolochainError::ErrorGeneric(format!(
+                "agent {} is not reachable",
+                to
+            )))
+        }
+    }
+}
+
+#[derive(Default)]
+struct Connection {
+    /// network and address the instance is connected under
+    network: Option<(MemoryNetwork, String)>,
+    /// waiting callers and the agent asked by id of their request
+    pending: HashMap<String, (String, Sender<Result<String, String>>)>,
+    next_id: u64,
+    config: NetworkConfig,
+    /// agents messages were exchanged with, they get a goodbye on leave()
+    peers: BTreeSet<String>,
+    /// remote calls are refused while closing
+    closing: bool,
+}
+
+/// deliver a message, retrying as the policy says while the agent can't be reached
+fn deliver(
+    network: &MemoryNetwork,
+    to: &str,
+    message: DirectMessage,
+    config: &NetworkConfig,
+) -> Result<(), HolochainError> {
+    let mut retry = 0;
+    loop {
+        match network.send(to, Envelope::new(message.clone())) {
+            Err(error) => {
+                retry += 1;
+                if retry >= config.retry.attempts {
+                    return Err(error);
+                }
+                platform::sleep(config.retry.delay(retry));
+            }
+            ok => return ok,
+        }
+    }
+}
+
+/// an instance's end of the network, sending its direct messages and handing results back to
+/// the calls waiting on them
+/// the messenger is a cheap handle, clones share the same connection
+#[derive(Clone, Default)]
+pub struct DirectMessenger {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl PartialEq for DirectMessenger {
+    fn eq(&self, other: &DirectMessenger) -> bool {
+        Arc::ptr_eq(&self.connection, &other.connection)
+    }
+}
+
+impl fmt::Debug for DirectMessenger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let connection = self.connection.lock().unwrap();
+        f.debug_struct("DirectMessenger")
+            .field("address", &connection.network.as_ref().map(|n| &n.1))
+            .field("pending", &connection.pending.len())
+            .finish()
+    }
+}
+
+impl DirectMessenger {
+    /// connect to a network under the given agent address, dropping any previous connection
+    /// the messages sent to the agent arrive through the receiver, see receive()
+    pub fn connect(&self, network: &MemoryNetwork, address: &str) -> Inbox {
+        self.disconnect();
+        let mut connection = self.connection.lock().unwrap();
+        connection.network = Some((network.clone(), address.to_string()));
+        connection.closing = false;
+        let receiver = network.connect(address);
+        network.accept(address, &connection.config.encodings);
+        receiver
+    }
+
+    /// leave the network, calls still waiting on a result fail
+    pub fn disconnect(&self) {
+        let mut connection = self.connection.lock().unwrap();
+        if let Some((network, address)) = connection.network.take() {
+            network.disconnect(&address);
+        }
+        connection.pending.clear();
+        connection.peers.clear();
+    }
+
+    /// say goodbye to the agents messages were exchanged with, then leave the network
+    /// goodbyes are sent once, agents that can't be reached are not waited for
+    pub fn leave(&self) {
+        let (network, address, peers) = {
+            let mut connection = self.connection.lock().unwrap();
+            match connection.network {
+                Some((ref network, ref address)) => (
+                    network.clone(),
+                    address.clone(),
+                    ::std::mem::take(&mut connection.peers),
+                ),
+                None => return,
+            }
+        };
+        for peer in peers {
+            let _ = network.send(&peer, Envelope::new(DirectMessage::Goodbye(address.clone())));
+        }
+        self.disconnect();
+    }
+
+    /// refuse remote calls from now on, e.g. while shutting down, until connected again
+    pub fn close(&self) {
+        self.connection.lock().unwrap().closing = true;
+    }
+
+    pub fn is_closing(&self) -> bool {
+        self.connection.lock().unwrap().closing
+    }
+
+    /// agents messages were exchanged with since connecting, sorted
+    pub fn peers(&self) -> Vec<String> {
+        let connection = self.connection.lock().unwrap();
+        connection.peers.iter().cloned().collect()
+    }
+
+    fn add_peer(&self, address: &str) {
+        let mut connection = self.connection.lock().unwrap();
+        if connection.network.is_some() {
+            connection.peers.insert(address.to_string());
+        }
+    }
+
+    /// the agent address the messenger is connected under, None if it is not connected
+    pub fn address(&self) -> Option<String> {
+        let connection = self.connection.lock().unwrap();
+        connection.network.as_ref().map(|n| n.1.clone())
+    }
+
+    /// the network the messenger is connected to, if any
+    pub fn network(&self) -> Option<MemoryNetwork> {
+        let connection = self.connection.lock().unwrap();
+        connection.network.as_ref().map(|n| n.0.clone())
+    }
+
+    /// change the timeouts and retries of messages sent from now on, and the encodings messages
+    /// to the agent come in
+    pub fn configure(&self, config: &NetworkConfig) {
+        let mut connection = self.connection.lock().unwrap();
+        connection.config = config.clone();
+        if let Some((ref network, ref address)) = connection.network {
+            network.accept(address, &config.encodings);
+        }
+    }
+
+    pub fn config(&self) -> NetworkConfig {
+        self.connection.lock().unwrap().config.clone()
+    }
+
+    /// send a message to an agent without waiting for an answer
+    /// sending is retried as configured while the agent can't be reached
+    pub fn send(&self, to: &str, message: DirectMessage) -> Result<(), HolochainError> {
+        let (network, config) = {
+            let connection = self.connection.lock().unwrap();
+            match connection.network {
+                Some((ref network, _)) => (network.clone(), connection.config.clone()),
+                None => return Err(not_connected()),
+            }
+        };
+        deliver(&network, to, message, &config)?;
+        self.add_peer(to);
+        Ok(())
+    }
+
+    /// send the message built from a fresh id and the messenger's address to an agent and block
+    /// until the answer with the id arrives or the timeout passes
+    fn request<F>(
+        &self,
+        agent: &str,
+        timeout: Duration,
+        what: &str,
+        message: F,
+    ) -> Result<String, HolochainError>
+    where
+        F: FnOnce(String, String) -> DirectMessage,
+    {
+        let (sender, receiver) = channel();
+        let (network, config, id, message) = {
+            let mut connection = self.connection.lock().unwrap();
+            let (network, address) = match connection.network {
+                Some((ref network, ref address)) => (network.clone(), address.clone()),
+                None => return Err(not_connected()),
+            };
+            connection.next_id += 1;
+            let id = connection.next_id.to_string();
+            connection
+                .pending
+                .insert(id.clone(), (agent.to_string(), sender));
+            let message = message(id.clone(), address);
+            (network, connection.config.clone(), id, message)
+        };
+
+        // the lock is released while retrying so answers to other requests still come through
+        let sent = deliver(&network, agent, message, &config);
+        if let Err(error) = sent {
+            self.connection.lock().unwrap().pending.remove(&id);
+            return Err(error);
+        }
+        self.add_peer(agent);
+        let result = receiver.recv_timeout(timeout);
+        self.connection.lock().unwrap().pending.remove(&id);
+        match result {
+            Ok(result) => result.map_err(HolochainError::ErrorGeneric),
+            Err(_) => Err(HolochainError::ErrorGeneric(format!(
+                "{} to {} got no result",
+                what, agent
+            ))),
+        }
+    }
+
+    /// call a zome function on an agent's node and block until its result arrives or the
+    /// timeout passes
+    pub fn call_remote(
+        &self,
+        agent: &str,
+        call: &FunctionCall,
+        cap_secret: Option<String>,
+        timeout: Duration,
+    ) -> Result<String, HolochainError> {
+        self.request(agent, timeout, "remote call", |id, from| {
+            DirectMessage::CallRemote(RemoteCall {
+                id,
+                from,
+                zome: call.zome.clone(),
+                capability: call.capability.clone(),
+                function: call.function.clone(),
+                cap_secret,
+                parameters: call.parameters.clone(),
+            })
+        })
+    }
+
+    /// ask an agent's node for the aspects it holds of an address and block until they arrive or
+    /// the timeout passes
+    pub fn get_aspects(
+        &self,
+        agent: &str,
+        address: &str,
+        timeout: Duration,
+    ) -> Result<Vec<Aspect>, HolochainError> {
+        let json = self.request(agent, timeout, "aspect request", |id, from| {
+            DirectMessage::GetAspects {
+                id,
+                from,
+                address: address.to_string(),
+            }
+        })?;
+        serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+
+    /// ask an agent's node for its validation package and block until it arrives or the timeout
+    /// passes
+    pub fn get_validation_package(
+        &self,
+        agent: &str,
+        timeout: Duration,
+    ) -> Result<ValidationPackage, HolochainError> {
+        let json = self.request(agent, timeout, "validation package request", |id, from| {
+            DirectMessage::GetValidationPackage { id, from }
+        })?;
+        serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+
+    /// hand the answer to a request to the caller waiting on it, if it still is
+    fn resolve(&self, id: &str, result: Result<String, String>) {
+        if let Some((_, sender)) = self.connection.lock().unwrap().pending.remove(id) {
+            // the caller may have timed out in the meantime
+            let _ = sender.send(result);
+        }
+    }
+
+    /// forget an agent that left, failing the calls still waiting on it
+    fn farewell(&self, address: &str) {
+        let mut connection = self.connection.lock().unwrap();
+        connection.peers.remove(address);
+        let left: Vec<String> = connection
+            .pending
+            .iter()
+            .filter(|(_, (agent, _))| agent == address)
+            .map(|(id, _)| id.clone())
+            .collect();
+        for id in left {
+            if let Some((_, sender)) = connection.pending.remove(&id) {
+                let _ = sender.send(Err(format!("agent {} left the network", address)));
+            }
+        }
+    }
+}
+
+fn not_connected() -> HolochainError {
+    HolochainError::ErrorGeneric("not connected to a network".to_string())
+}
+
+/// error message to send back for a failed remote call
+fn error_message(error: HolochainError) -> String {
+    match error {
+        HolochainError::ErrorGeneric(message)
+        | HolochainError::ZomeNotFound(message)
+        | HolochainError::CapabilityNotFound(message)
+        | HolochainError::ZomeFunctionNotFound(message) => message,
+        error => format!("{:?}", error),
+    }
+}
+
+/// make sure the caller of a remote call may call the capability
+/// public capabilities are open to anyone, all others need the secret of a grant for them
+/// lifecycle functions are never called remotely
+pub fn check_remote_call(nucleus: &NucleusState, call: &RemoteCall) -> Result<(), HolochainError> {
+    let dna = nucleus.dna().ok_or(HolochainError::DnaMissing)?;
+    let zome = dna.get_zome(&call.zome).ok_or_else(|| {
+        HolochainError::ZomeNotFound(format!("Zome '{}' not found", &call.zome))
+    })?;
+    let capability = zome
+        .capabilities
+        .iter()
+        .find(|capability| capability.name == call.capability)
+        .filter(|_| call.capability != ReservedCapabilityNames::LifeCycle.as_str())
+        .ok_or_else(|| {
+            HolochainError::CapabilityNotFound(format!(
+                "Capability '{}' not found in Zome '{}'",
+                &call.capability, &call.zome
+            ))
+        })?;
+    if capability.capability.membrane == Membrane::Public {
+        return Ok(());
+    }
+    let granted = call
+        .cap_secret
+        .as_ref()
+        .and_then(|secret| nucleus.cap_grants().get(secret))
+        .filter(|grant| grant.zome == call.zome && grant.capability == call.capability)
+        .is_some();
+    if granted {
+        Ok(())
+    } else {
+        Err(HolochainError::ErrorGeneric(format!(
+            "Capability '{}' of Zome '{}' is not granted to {}",
+            &call.capability, &call.zome, &call.from
+        )))
+    }
+}
+
+/// count the new content among the aspects published against the rate limits of its author and
+/// the rate bucket of its entry type, see validation::rates, what the holder authored itself isn't
+/// limited
+/// nothing but evidence is taken from authors warranted for forking their chain, see
+/// validation::warrants
+/// nor headers timed ahead of the clock or before the headers they follow, see
+/// validation::timestamps
+/// nothing is taken from authors whose validation package was rejected, see validation::packages
+fn admit(
+    state: &Arc<RwLock<State>>,
+    messenger: &DirectMessenger,
+    author: &str,
+    address: &str,
+    new: &[Aspect],
+    me: &str,
+) -> Result<(), String> {
+    let verdict = state.read().unwrap().nucleus().package_verdicts().get(author);
+    if let Some(Err(reason)) = verdict {
+        return Err(format!("the validation package of {} was rejected: {}", author, reason));
+    }
+    let evidence = |aspect: &Aspect| matches!(*aspect, Aspect::Activity(_) | Aspect::Warrant(_));
+    let warranted = author != me && !state.read().unwrap().dht().warrants(author).is_empty();
+    if warranted && !new.iter().all(evidence) {
+        return Err(format!("{} is warranted for forking its chain", author));
+    }
+    let skew_secs = state.read().unwrap().agent().max_clock_skew_secs();
+    let dht = state.read().unwrap().dht();
+    timestamps::check_aspects(&dht, address, new, unix_now(), skew_secs)?;
+    let entry = new.iter().find_map(|aspect| match *aspect {
+        Aspect::Content(ref entry) => Some(entry),
+        _ => None,
+    });
+    match entry {
+        Some(entry) if author != me => {
+            let nucleus = state.read().unwrap().nucleus();
+            let buckets = nucleus
+                .dna()
+                .map(|dna| rates::buckets(&dna))
+                .unwrap_or_default();
+            nucleus.author_rates().admit(
+                author,
+                entry,
+                unix_now(),
+                &messenger.config().author_rates,
+                &buckets,
+            )
+        }
+        _ => Ok(()),
+    }
+}
+
+/// check the validation package of the author of a publish in a thread of its own, as it is asked
+/// for over the network and validate_agent is a zome call, then handle the publish again with
+/// the verdict kept, telling the author if its package was rejected
+/// the publish is dropped if the package can't be had, the publisher publishes again
+fn check_package(
+    publish: DirectMessage,
+    author: String,
+    dna: Dna,
+    messenger: &DirectMessenger,
+    state: &Arc<RwLock<State>>,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) {
+    let messenger = messenger.clone();
+    let state = state.clone();
+    let action_channel = action_channel.clone();
+    let observer_channel = observer_channel.clone();
+    platform::spawn("validation_package", move || {
+        let verdicts = state.read().unwrap().nucleus().package_verdicts().clone();
+        // another publish of the author may have been checked in the meantime
+        if verdicts.get(&author).is_none() {
+            let timeout = messenger.config().direct_message_timeout();
+            let package = match messenger.get_validation_package(&author, timeout) {
+                Ok(package) => package,
+                Err(_) => return,
+            };
+            let verdict = package.verify(&author, &dna, &action_channel, &observer_channel);
+            if let Err(ref reason) = verdict {
+                let rejection = Rejection {
+                    validator: messenger.address().unwrap_or_default(),
+                    reason: reason.clone(),
+                };
+                let _ = messenger.send(&author, DirectMessage::AgentRejected(rejection));
+            }
+            verdicts.record(&author, verdict);
+        }
+        receive(publish, &messenger, &state, &action_channel, &observer_channel);
+    });
+}
+
+/// handle a message sent to the instance the messenger belongs to
+/// remote calls are checked and made in a thread of their own so a slow zome function doesn't
+/// hold up other messages, while the messenger is closing they are refused
+/// messages from agents blocked by the instance are dropped, see agent::blocks
+pub fn receive(
+    message: DirectMessage,
+    messenger: &DirectMessenger,
+    state: &Arc<RwLock<State>>,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) {
+    if let Some(sender) = message.sender() {
+        if state.read().unwrap().agent().is_blocked(sender) {
+            return;
+        }
+    }
+    match message {
+        DirectMessage::CallRemote(remote_call) => {
+            messenger.add_peer(&remote_call.from);
+            if messenger.is_closing() {
+                let refused = Err("the agent is shutting down".to_string());
+                let _ = messenger.send(
+                    &remote_call.from,
+                    DirectMessage::CallRemoteResult(remote_call.id, refused),
+                );
+                return;
+            }
+            let messenger = messenger.clone();
+            let state = state.clone();
+            let action_channel = action_channel.clone();
+            let observer_channel = observer_channel.clone();
+            platform::spawn("remote_call", move || {
+                let checked = check_remote_call(&state.read().unwrap().nucleus(), &remote_call);
+                let result = checked
+                    .and_then(|_| {
+                        call_zome_and_wait_for_result(
+                            remote_call.call(),
+                            &action_channel,
+                            &observer_channel,
+                        )
+                    })
+                    .map_err(error_message);
+                // nothing to do if the caller went away
+                let _ = messenger.send(
+                    &remote_call.from,
+                    DirectMessage::CallRemoteResult(remote_call.id, result),
+                );
+            });
+        }
+        DirectMessage::CallRemoteResult(id, result) => messenger.resolve(&id, result),
+        DirectMessage::GetAspects { id, from, address } => {
+            messenger.add_peer(&from);
+            let aspects = state.read().unwrap().dht().aspects(&address);
+            let _ = messenger.send(&from, DirectMessage::GetAspectsResult(id, aspects));
+        }
+        DirectMessage::GetAspectsResult(id, aspects) => messenger.resolve(
+            &id,
+            serde_json::to_string(&aspects).map_err(|e| e.to_string()),
+        ),
+        DirectMessage::Publish {
+            from,
+            address,
+            aspects,
+        } => {
+            // addresses outside the arc are for other nodes to hold, unless published back to
+            // their author
+            let me = messenger.address().unwrap_or_default();
+            let dht = state.read().unwrap().dht();
+            if !dht.should_hold(&address, &aspects, &me) {
+                return;
+            }
+            let integration = dht.integration(&address);
+            let receipt = ValidationReceipt {
+                address: address.clone(),
+                validator: me.clone(),
+                validation: integration
+                    .as_ref()
+                    .map(|integration| integration.validation.clone())
+                    .unwrap_or_default(),
+                timestamp: integration
+                    .map(|integration| integration.integrated_at)
+                    .unwrap_or_else(unix_now),
+            };
+            // the same aspects come again from other publishers and gossip
+            let held = dht.aspect_addresses(&address);
+            // the activity and checkpoints of an agent are only taken from the agent
+            let new: Vec<Aspect> = aspects
+                .iter()
+                .filter(|aspect| !held.contains(&aspect.address(&address)))
+                .filter(|aspect| match **aspect {
+                    Aspect::Activity(_) | Aspect::Checkpoint(_) => from == address,
+                    _ => true,
+                })
+                .cloned()
+                .collect();
+            // authors other than the holder only get so many new entries held
+            let author = rates::author(&aspects).unwrap_or_else(|| from.clone());
+            // and authors of gated DNAs get nothing held before their validation package is
+            // checked
+            let dna = state.read().unwrap().nucleus().dna();
+            let gated = dna.as_ref().map(membrane::is_gated).unwrap_or(false);
+            let unchecked = author != me
+                && state
+                    .read()
+                    .unwrap()
+                    .nucleus()
+                    .package_verdicts()
+                    .get(&author)
+                    .is_none();
+            if let (true, true, Some(dna)) = (gated, unchecked, dna.clone()) {
+                let publish = DirectMessage::Publish {
+                    from,
+                    address,
+                    aspects,
+                };
+                check_package(
+                    publish,
+                    author,
+                    dna,
+                    messenger,
+                    state,
+                    action_channel,
+                    observer_channel,
+                );
+                return;
+            }
+            if let Err(reason) = admit(state, messenger, &author, &address, &new, &me) {
+                if from != me {
+                    let refused = ValidationReceipt {
+                        validation: HoldingValidation::Invalid(reason),
+                        timestamp: unix_now(),
+                        ..receipt
+                    };
+                    let _ = messenger.send(&from, DirectMessage::ValidationReceipt(refused));
+                }
+                return;
+            }
+            // agents of gated DNAs are checked the first time their AgentId comes along
+            for aspect in new {
+                let agent_id = match aspect {
+                    Aspect::Content(ref entry) if gated => AgentId::from_entry(entry),
+                    _ => None,
+                };
+                ::instance::dispatch_action(
+                    action_channel,
+                    state::Action::Dht(::dht::Action::HoldAspect(address.clone(), aspect)),
+                );
+                if let (Some(agent_id), Some(dna)) = (agent_id, dna.clone()) {
+                    membrane::verify_published(
+                        dna,
+                        agent_id,
+                        address.clone(),
+                        action_channel,
+                        observer_channel,
+                    );
+                }
+            }
+            if from != me {
+                // nothing to do if the publisher went away, it publishes again
+                let _ = messenger.send(&from, DirectMessage::ValidationReceipt(receipt));
+            }
+        }
+        DirectMessage::ValidationReceipt(receipt) => {
+            // a receipt that can't be persisted is still kept while the instance runs
+            let _ = state.read().unwrap().nucleus().receipts().add(receipt);
+        }
+        DirectMessage::RemoteSignals(signals) => {
+            remote_signal::receive(&signals, state.read().unwrap().nucleus().signal_bus())
+        }
+        DirectMessage::Heartbeat(address) => {
+            state.read().unwrap().nucleus().presence().received(&address, unix_now())
+        }
+        DirectMessage::Goodbye(address) => messenger.farewell(&address),
+        DirectMessage::Pruned { from, address } => {
+            // a receipt store that can't be persisted still forgets the holder while the
+            // instance runs
+            let _ = state
+                .read()
+                .unwrap()
+                .nucleus()
+                .receipts()
+                .withdraw(&address, &from);
+        }
+        DirectMessage::GroupSecret(secret) => {
+            // secrets are only kept by the members of their group
+            let me = messenger.address().unwrap_or_default();
+            if secret.is_member(&me) {
+                // a secret that can't be persisted is still known while the instance runs
+                let _ = state.read().unwrap().nucleus().group_secrets().add(secret);
+            }
+        }
+        DirectMessage::GetValidationPackage { id, from } => {
+            messenger.add_peer(&from);
+            let package = {
+                let state = state.read().unwrap();
+                let agent_id = AgentId {
+                    address: messenger.address().unwrap_or_default(),
+                    membrane_proof: state
+                        .nucleus()
+                        .agent_id()
+                        .and_then(|agent_id| agent_id.membrane_proof.clone()),
+                };
+                ValidationPackage::of(&state.agent(), &agent_id)
+                    .ok_or_else(|| "the agent has not completed genesis yet".to_string())
+            };
+            let _ = messenger.send(&from, DirectMessage::ValidationPackage(id, package));
+        }
+        DirectMessage::ValidationPackage(id, package) => messenger.resolve(
+            &id,
+            package.and_then(|package| {
+                serde_json::to_string(&package).map_err(|e| e.to_string())
+            }),
+        ),
+        DirectMessage::AgentRejected(rejection) => {
+            state
+                .read()
+                .unwrap()
+                .nucleus()
+                .signal_bus()
+                .emit(&rejection.to_signal());
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_dna::{
+        zome::{
+            capabilities::{Capability, Membrane}, Zome,
+        },
+        Dna,
+    };
+    use dht::StorageArc;
+    use network::config::{AuthorRateLimits, Backoff, RetryPolicy};
+    use nucleus::{Action, CapabilityGrant};
+    use hash_table::{
+        entry::{
+            tests::{test_entry, test_entry_a, test_entry_b}, Entry,
+        },
+        header::tests::test_header, provenance::tests::test_agent_address,
+    };
+    use agent::{
+        blocks::block_entry, groups::tests::test_group_secret, INIT_COMPLETE_ENTRY_TYPE,
+    };
+    use state::{
+        Action::{Agent, Dht, Nucleus}, ActionWrapper,
+    };
+    use std::thread;
+    use validation::{
+        receipts::tests::test_receipt, warrants::{tests::test_fork, Warrant},
+    };
+
+    /// remote call from alice of test_zome/test_cap/main
+    pub fn test_remote_call() -> RemoteCall {
+        RemoteCall {
+            id: "1".to_string(),
+            from: "alice".to_string(),
+            zome: "test_zome".to_string(),
+            capability: "test_cap".to_string(),
+            function: "main".to_string(),
+            cap_secret: None,
+            parameters: "{}".to_string(),
+        }
+    }
+
+    /// messenger connected under the given address that only takes answers to its requests
+    pub fn test_caller(network: &MemoryNetwork, address: &str) -> DirectMessenger {
+        let messenger = DirectMessenger::default();
+        let receiver = messenger.connect(network, address);
+        let resolver = messenger.clone();
+        thread::spawn(move || {
+            for envelope in receiver {
+                match envelope.message {
+                    DirectMessage::CallRemoteResult(id, result) => resolver.resolve(&id, result),
+                    DirectMessage::GetAspectsResult(id, aspects) => resolver.resolve(
+                        &id,
+                        serde_json::to_string(&aspects).map_err(|e| e.to_string()),
+                    ),
+                    DirectMessage::ValidationPackage(id, package) => resolver.resolve(
+                        &id,
+                        package.and_then(|package| {
+                            serde_json::to_string(&package).map_err(|e| e.to_string())
+                        }),
+                    ),
+                    DirectMessage::Goodbye(address) => resolver.farewell(&address),
+                    _ => {}
+                }
+            }
+        });
+        messenger
+    }
+
+    /// node connected under the given address answering every remote call with its parameters
+    /// and who it is from
+    pub fn test_responder(network: &MemoryNetwork, address: &str) {
+        let receiver = network.connect(address);
+        let network = network.clone();
+        thread::spawn(move || {
+            for envelope in receiver {
+                if let DirectMessage::CallRemote(call) = envelope.message {
+                    let answer = DirectMessage::CallRemoteResult(
+                        call.id,
+                        Ok(format!("{} from {}", call.parameters, call.from)),
+                    );
+                    let _ = network.send(&call.from, Envelope::new(answer));
+                }
+            }
+        });
+    }
+
+    /// nucleus state running a dna with test_zome/test_cap behind the given membrane
+    fn test_nucleus_state(membrane: Membrane, grants: Vec<CapabilityGrant>) -> NucleusState {
+        let mut capability = Capability::new();
+        capability.name = "test_cap".to_string();
+        capability.capability.membrane = membrane;
+        let mut zome = Zome::new();
+        zome.name = "test_zome".to_string();
+        zome.capabilities.push(capability);
+        let mut dna = Dna::new();
+        dna.zomes.push(zome);
+
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let mut state = State::new().reduce(
+            ActionWrapper::new(Nucleus(Action::InitApplication(dna))),
+            &sender,
+            &tx_observer,
+        );
+        for grant in grants {
+            state = state.reduce(
+                ActionWrapper::new(Nucleus(Action::GrantCapability(grant))),
+                &sender,
+                &tx_observer,
+            );
+        }
+        (*state.nucleus()).clone()
+    }
+
+    #[test]
+    /// messages reach connected agents only
+    fn memory_network_routes() {
+        let network = MemoryNetwork::new();
+        let alice = network.connect("alice");
+        let message = DirectMessage::CallRemote(test_remote_call());
+
+        assert!(network.send("alice", Envelope::new(message.clone())).is_ok());
+        assert_eq!(message, alice.recv().unwrap().message);
+        assert!(network.send("bob", Envelope::new(message.clone())).is_err());
+
+        network.disconnect("alice");
+        assert!(network.send("alice", Envelope::new(message)).is_err());
+        assert!(alice.recv().is_err());
+    }
+
+    #[test]
+    /// agents taking MessagePack get the same messages in fewer bytes
+    fn memory_network_encodings() {
+        let network = MemoryNetwork::new();
+        let bob = DirectMessenger::default();
+        let bob_receiver = bob.connect(&network, "bob");
+        let carol = network.connect("carol");
+        let message = DirectMessage::CallRemote(test_remote_call());
+
+        network.send("carol", Envelope::new(message.clone())).unwrap();
+        assert_eq!(message, carol.recv().unwrap().message);
+        let json = network.bytes_sent();
+
+        bob.configure(&NetworkConfig {
+            encodings: vec![Encoding::MessagePack],
+            ..NetworkConfig::default()
+        });
+        network.send("bob", Envelope::new(message.clone())).unwrap();
+        assert_eq!(message, bob_receiver.recv().unwrap().message);
+        assert!(network.bytes_sent() - json < json);
+    }
+
+    #[test]
+    /// agents are only reached within the space they connected in
+    fn memory_network_spaces() {
+        let network = MemoryNetwork::new();
+        let alice = network.space("a").connect("alice");
+        let _bob = network.space("b").connect("bob");
+        let message = DirectMessage::Heartbeat("carol".to_string());
+
+        assert!(network.space("b").send("alice", Envelope::new(message.clone())).is_err());
+        assert!(network.send("alice", Envelope::new(message.clone())).is_err());
+        assert!(network.clone().space("a").send("alice", Envelope::new(message.clone())).is_ok());
+        assert_eq!(message, alice.recv().unwrap().message);
+    }
+
+    #[test]
+    /// only who a message is for goes over the wire in the clear, and only its node opens it
+    fn memory_network_seals() {
+        let network = MemoryNetwork::new();
+        let bob = network.connect("bob");
+        let public_key = network.public_key("bob").unwrap();
+        assert_eq!(bob.public_key(), public_key);
+        assert_eq!(None, network.public_key("carol"));
+
+        let envelope = Envelope::new(DirectMessage::CallRemote(test_remote_call()));
+        let contains = |bytes: &[u8], text: &str| {
+            bytes.windows(text.len()).any(|window| window == text.as_bytes())
+        };
+        for encoding in ENCODINGS {
+            let sealed = seal("bob", &envelope, *encoding, &public_key).unwrap();
+            let bytes = encoding.encode(&sealed).unwrap();
+            assert!(contains(&encoding.encode(&envelope).unwrap(), "test_zome"));
+            assert!(!contains(&bytes, "test_zome"));
+            assert!(contains(&bytes, "bob"));
+        }
+
+        // sealed to another key, as to an earlier connection of bob
+        let stale = seal("bob", &envelope, Encoding::Json, &KeyPair::generate().public());
+        let nodes = network.nodes.lock().unwrap();
+        nodes["bob"].sender.send((Encoding::Json, stale.unwrap())).unwrap();
+        drop(nodes);
+        assert!(bob.try_recv().is_err());
+        network.send("bob", envelope.clone()).unwrap();
+        assert_eq!(envelope, bob.recv().unwrap());
+    }
+
+    #[test]
+    /// public capabilities are open to anyone, others need a granted secret
+    fn remote_calls_are_capability_checked() {
+        let call = test_remote_call();
+        assert_eq!(
+            Ok(()),
+            check_remote_call(&test_nucleus_state(Membrane::Public, vec![]), &call)
+        );
+        assert_eq!(
+            Err(HolochainError::DnaMissing),
+            check_remote_call(&NucleusState::new(), &call)
+        );
+
+        let grant = CapabilityGrant::new("test_zome", "test_cap");
+        let other_grant = CapabilityGrant::new("test_zome", "other_cap");
+        let nucleus = test_nucleus_state(Membrane::ApiKey, vec![grant.clone(), other_grant.clone()]);
+        assert!(check_remote_call(&nucleus, &call).is_err());
+        let with_secret = |secret: &str| RemoteCall {
+            cap_secret: Some(secret.to_string()),
+            ..test_remote_call()
+        };
+        assert_eq!(Ok(()), check_remote_call(&nucleus, &with_secret(&grant.secret)));
+        assert!(check_remote_call(&nucleus, &with_secret(&other_grant.secret)).is_err());
+        assert!(check_remote_call(&nucleus, &with_secret("guess")).is_err());
+
+        let lifecycle = RemoteCall {
+            capability: ReservedCapabilityNames::LifeCycle.as_str().to_string(),
+            ..test_remote_call()
+        };
+        assert!(check_remote_call(&nucleus, &lifecycle).is_err());
+    }
+
+    #[test]
+    /// results find their way back to the waiting caller
+    fn call_remote() {
+        let network = MemoryNetwork::new();
+        test_responder(&network, "bob");
+        let _carol = network.connect("carol");
+
+        let call = test_remote_call().call();
+        let timeout = Duration::from_millis(1000);
+        assert_eq!(
+            Err(not_connected()),
+            DirectMessenger::default().call_remote("bob", &call, None, timeout)
+        );
+
+        let messenger = test_caller(&network, "alice");
+        assert_eq!(Some("alice".to_string()), messenger.address());
+        assert_eq!(Some(network.clone()), messenger.network());
+        assert_eq!(
+            Ok("{} from alice".to_string()),
+            messenger.call_remote("bob", &call, None, timeout)
+        );
+        // carol never answers
+        assert!(
+            messenger
+                .call_remote("carol", &call, None, Duration::from_millis(10))
+                .is_err()
+        );
+        assert!(messenger.call_remote("dave", &call, None, timeout).is_err());
+
+        messenger.disconnect();
+        assert_eq!(None, messenger.address());
+        assert_eq!(None, messenger.network());
+    }
+
+    #[test]
+    /// agents leaving say goodbye, failing the calls waiting on them
+    fn goodbye() {
+        let network = MemoryNetwork::new();
+        let alice = test_caller(&network, "alice");
+        let bob = test_caller(&network, "bob");
+        let carol = network.connect("carol");
+
+        // bob only takes results, so alice's call waits
+        let waiting = alice.clone();
+        let caller = thread::spawn(move || {
+            let call = test_remote_call().call();
+            waiting.call_remote("bob", &call, None, Duration::from_millis(5000))
+        });
+        while alice.peers().is_empty() {
+            thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(vec!["bob".to_string()], alice.peers());
+
+        bob.add_peer("carol");
+        bob.add_peer("alice");
+        bob.leave();
+        assert_eq!(None, bob.address());
+        assert!(bob.peers().is_empty());
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric("agent bob left the network".to_string())),
+            caller.join().unwrap()
+        );
+        assert!(alice.peers().is_empty());
+        assert_eq!(
+            DirectMessage::Goodbye("bob".to_string()),
+            carol.recv().unwrap().message
+        );
+    }
+
+    #[test]
+    /// closing messengers refuse remote calls until connected again
+    fn closing() {
+        let network = MemoryNetwork::new();
+        let alice = test_caller(&network, "alice");
+        let bob = DirectMessenger::default();
+        let receiver = bob.connect(&network, "bob");
+        assert!(!bob.is_closing());
+        bob.close();
+        assert!(bob.is_closing());
+
+        let caller = thread::spawn(move || {
+            let call = test_remote_call().call();
+            alice.call_remote("bob", &call, None, Duration::from_millis(1000))
+        });
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+        let envelope = receiver.recv().unwrap();
+        receive(envelope.message, &bob, &state, &sender, &tx_observer);
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric("the agent is shutting down".to_string())),
+            caller.join().unwrap()
+        );
+        assert_eq!(vec!["alice".to_string()], bob.peers());
+
+        let _receiver = bob.connect(&network, "bob");
+        assert!(!bob.is_closing());
+    }
+
+    #[test]
+    /// nodes answer with the aspects they hold of an address
+    fn get_aspects() {
+        let network = MemoryNetwork::new();
+        let alice = test_caller(&network, "alice");
+        let bob = DirectMessenger::default();
+        let receiver = bob.connect(&network, "bob");
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new().reduce(
+            ActionWrapper::new(Dht(::dht::Action::Hold(test_entry()))),
+            &sender,
+            &tx_observer,
+        )));
+        thread::spawn(move || {
+            for envelope in receiver {
+                receive(envelope.message, &bob, &state, &sender, &tx_observer);
+            }
+        });
+
+        let timeout = Duration::from_millis(1000);
+        assert_eq!(
+            Ok(vec![Aspect::Content(test_entry())]),
+            alice.get_aspects("bob", &test_entry().key(), timeout)
+        );
+        assert_eq!(Ok(vec![]), alice.get_aspects("bob", "nowhere", timeout));
+        assert!(alice.get_aspects("carol", "nowhere", timeout).is_err());
+    }
+
+    #[test]
+    /// published aspects are only held by the nodes whose arc the address is in, once
+    fn publish_within_arc() {
+        let address = test_entry().key();
+        let content = vec![Aspect::Content(test_entry())];
+        let publish = || DirectMessage::Publish {
+            from: "alice".to_string(),
+            address: address.clone(),
+            aspects: content.clone(),
+        };
+        let network = MemoryNetwork::new();
+        let alice = network.connect("alice");
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+
+        let state = Arc::new(RwLock::new(State::new()));
+        receive(publish(), &bob, &state, &sender, &tx_observer);
+        let hold = ::dht::Action::HoldAspect(address.clone(), Aspect::Content(test_entry()));
+        assert_eq!(Dht(hold.clone()), receiver.try_recv().unwrap().action);
+        match alice.try_recv().unwrap().message {
+            DirectMessage::ValidationReceipt(receipt) => {
+                assert_eq!(address, receipt.address);
+                assert_eq!("bob", receipt.validator);
+            }
+            message => panic!("expected a receipt, got {:?}", message),
+        }
+
+        // what is held already isn't held again
+        let state = Arc::new(RwLock::new(State::new().reduce(
+            ActionWrapper::new(Dht(hold)),
+            &sender,
+            &tx_observer,
+        )));
+        receive(publish(), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+
+        let elsewhere = StorageArc::new(::dht::location(&address).wrapping_add(1000), 100);
+        let state = Arc::new(RwLock::new(State::new().reduce(
+            ActionWrapper::new(Dht(::dht::Action::SetArc(elsewhere))),
+            &sender,
+            &tx_observer,
+        )));
+        receive(publish(), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[test]
+    /// the activity of an agent is only held as published by the agent
+    fn publish_activity() {
+        let publish = |from: &str| DirectMessage::Publish {
+            from: from.to_string(),
+            address: "alice".to_string(),
+            aspects: vec![Aspect::Activity(test_header())],
+        };
+        let network = MemoryNetwork::new();
+        let _alice = network.connect("alice");
+        let _carol = network.connect("carol");
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+
+        receive(publish("carol"), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+        receive(publish("alice"), &bob, &state, &sender, &tx_observer);
+        let hold = ::dht::Action::HoldAspect(
+            "alice".to_string(),
+            Aspect::Activity(test_header()),
+        );
+        assert_eq!(Dht(hold), receiver.try_recv().unwrap().action);
+    }
+
+    #[test]
+    /// nothing but evidence is held from warranted authors, the publisher gets an invalid receipt
+    fn warranted_refused() {
+        let id = test_agent_address("alice");
+        let (a, b) = test_fork();
+        let warrant = Aspect::Warrant(Warrant::fork(&id, &a, &b).unwrap());
+        let publish = |aspect: Aspect| DirectMessage::Publish {
+            from: id.clone(),
+            address: test_entry().key(),
+            aspects: vec![aspect],
+        };
+        let network = MemoryNetwork::new();
+        let alice = network.connect(&id);
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let hold = ::dht::Action::HoldAspect(id.clone(), warrant);
+        let state = Arc::new(RwLock::new(State::new().reduce(
+            ActionWrapper::new(Dht(hold)),
+            &sender,
+            &tx_observer,
+        )));
+
+        receive(publish(Aspect::Content(test_entry())), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+        match alice.try_recv().unwrap().message {
+            DirectMessage::ValidationReceipt(receipt) => match receipt.validation {
+                HoldingValidation::Invalid(reason) => assert!(reason.contains("warranted")),
+                validation => panic!("expected invalid, got {:?}", validation),
+            },
+            message => panic!("expected a receipt, got {:?}", message),
+        }
+    }
+
+    #[test]
+    /// headers timed ahead of the clock aren't held, the publisher gets an invalid receipt
+    fn timed_ahead_refused() {
+        let publish = |time: u64| DirectMessage::Publish {
+            from: "alice".to_string(),
+            address: test_header().entry().to_string(),
+            aspects: vec![Aspect::Header(test_header().with_time(time))],
+        };
+        let network = MemoryNetwork::new();
+        let alice = network.connect("alice");
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+
+        receive(publish(unix_now() + 3600), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+        match alice.try_recv().unwrap().message {
+            DirectMessage::ValidationReceipt(receipt) => match receipt.validation {
+                HoldingValidation::Invalid(reason) => assert!(reason.contains("ahead")),
+                validation => panic!("expected invalid, got {:?}", validation),
+            },
+            message => panic!("expected a receipt, got {:?}", message),
+        }
+
+        receive(publish(unix_now()), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_ok());
+    }
+
+    #[test]
+    /// entries of authors over their rate limit aren't held, the publisher gets an invalid receipt
+    fn author_rate_limited() {
+        let publish = |entry: Entry| DirectMessage::Publish {
+            from: "alice".to_string(),
+            address: entry.key(),
+            aspects: vec![Aspect::Content(entry)],
+        };
+        let network = MemoryNetwork::new();
+        let alice = network.connect("alice");
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        bob.configure(&NetworkConfig {
+            author_rates: AuthorRateLimits {
+                max_entries_per_minute: Some(1),
+                max_bytes_per_hour: None,
+            },
+            ..NetworkConfig::default()
+        });
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+
+        receive(publish(test_entry_a()), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_ok());
+        receive(publish(test_entry_b()), &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+        let validations = alice
+            .try_iter()
+            .map(|envelope| match envelope.message {
+                DirectMessage::ValidationReceipt(receipt) => receipt.validation,
+                message => panic!("expected a receipt, got {:?}", message),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                HoldingValidation::Unvalidated,
+                HoldingValidation::Invalid("alice is over 1 entries per minute".to_string()),
+            ],
+            validations
+        );
+    }
+
+    #[test]
+    /// receipts from holders are kept by entry address
+    fn receipt_kept() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+        let message = DirectMessage::ValidationReceipt(test_receipt("bob"));
+        receive(message, &DirectMessenger::default(), &state, &sender, &tx_observer);
+        assert_eq!(
+            vec![test_receipt("bob")],
+            state.read().unwrap().nucleus().receipts().receipts(&test_entry().key())
+        );
+    }
+
+    #[test]
+    /// holders that pruned an entry take their receipt back
+    fn receipt_withdrawn() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+        let messenger = DirectMessenger::default();
+        for holder in &["bob", "carol"] {
+            let receipt = DirectMessage::ValidationReceipt(test_receipt(holder));
+            receive(receipt, &messenger, &state, &sender, &tx_observer);
+        }
+        let pruned = DirectMessage::Pruned {
+            from: "bob".to_string(),
+            address: test_entry().key(),
+        };
+        assert_eq!(Some("bob"), pruned.sender());
+        receive(pruned, &messenger, &state, &sender, &tx_observer);
+        assert_eq!(
+            vec![test_receipt("carol")],
+            state.read().unwrap().nucleus().receipts().receipts(&test_entry().key())
+        );
+    }
+
+    #[test]
+    /// group secrets are kept by their members only
+    fn group_secret_kept_by_members() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let network = MemoryNetwork::new();
+        let secret = test_group_secret();
+        for agent in &["bob", "dave"] {
+            let state = Arc::new(RwLock::new(State::new()));
+            let messenger = DirectMessenger::default();
+            let _inbox = messenger.connect(&network, agent);
+            let message = DirectMessage::GroupSecret(secret.clone());
+            assert_eq!(Some("alice"), message.sender());
+            receive(message, &messenger, &state, &sender, &tx_observer);
+            let kept = state.read().unwrap().nucleus().group_secrets().get(&secret.id);
+            assert_eq!(secret.is_member(agent), kept.is_some());
+        }
+    }
+
+    #[test]
+    /// agents answer with their validation package once their genesis completed
+    fn validation_package() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let network = MemoryNetwork::new();
+        let alice = test_caller(&network, "alice");
+        let dna = Dna::new();
+        let marker = Entry::new(INIT_COMPLETE_ENTRY_TYPE, &dna.hash());
+        for (agent, genesis) in [("bob", vec![test_entry(), marker]), ("carol", Vec::new())] {
+            let state = genesis.into_iter().fold(State::new(), |state, entry| {
+                let commit = Agent(::agent::Action::Commit(entry));
+                state.reduce(ActionWrapper::new(commit), &sender, &tx_observer)
+            });
+            let state = Arc::new(RwLock::new(state));
+            let messenger = DirectMessenger::default();
+            let inbox = messenger.connect(&network, agent);
+            let (sender, tx_observer) = (sender.clone(), tx_observer.clone());
+            thread::spawn(move || {
+                for envelope in inbox {
+                    receive(envelope.message, &messenger, &state, &sender, &tx_observer);
+                }
+            });
+        }
+
+        let timeout = Duration::from_millis(1000);
+        let package = alice.get_validation_package("bob", timeout).unwrap();
+        assert_eq!("bob", package.agent);
+        assert_eq!(2, package.genesis.len());
+        assert_eq!(Ok(None), package.check("bob", &dna));
+        assert!(alice.get_validation_package("carol", timeout).is_err());
+    }
+
+    #[test]
+    /// nothing is held from agents whose validation package was rejected, who are told so
+    fn rejected_refused() {
+        let network = MemoryNetwork::new();
+        let alice = network.connect("alice");
+        let bob = DirectMessenger::default();
+        let _receiver = bob.connect(&network, "bob");
+        let (sender, receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let state = Arc::new(RwLock::new(State::new()));
+        let nucleus = state.read().unwrap().nucleus();
+        nucleus
+            .package_verdicts()
+            .record("alice", Err("no proof".to_string()));
+
+        let publish = DirectMessage::Publish {
+            from: "alice".to_string(),
+            address: test_entry().key(),
+            aspects: vec![Aspect::Content(test_entry())],
+        };
+        receive(publish, &bob, &state, &sender, &tx_observer);
+        assert!(receiver.try_recv().is_err());
+        match alice.try_recv().unwrap().message {
+            DirectMessage::ValidationReceipt(receipt) => match receipt.validation {
+                HoldingValidation::Invalid(reason) => assert!(reason.contains("rejected")),
+                validation => panic!("expected invalid, got {:?}", validation),
+            },
+            message => panic!("expected a receipt, got {:?}", message),
+        }
+
+        // the rejected agent's zomes get a signal
+        let signals = nucleus.signal_bus().subscribe();
+        let rejection = Rejection {
+            validator: "carol".to_string(),
+            reason: "no proof".to_string(),
+        };
+        let message = DirectMessage::AgentRejected(rejection.clone());
+        assert_eq!(Some("carol"), message.sender());
+        receive(message, &bob, &state, &sender, &tx_observer);
+        assert_eq!(rejection.to_signal(), signals.try_recv().unwrap());
+    }
+
+    #[test]
+    /// messages from blocked agents are dropped
+    fn blocked_dropped() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel();
+        let block = Agent(::agent::Action::Commit(block_entry("bob")));
+        let state = Arc::new(RwLock::new(State::new().reduce(
+            ActionWrapper::new(block),
+            &sender,
+            &tx_observer,
+        )));
+        let messenger = DirectMessenger::default();
+        for agent in &["bob", "carol"] {
+            let heartbeat = DirectMessage::Heartbeat(agent.to_string());
+            receive(heartbeat, &messenger, &state, &sender, &tx_observer);
+            let receipt = DirectMessage::ValidationReceipt(test_receipt(agent));
+            receive(receipt, &messenger, &state, &sender, &tx_observer);
+        }
+
+        let nucleus = state.read().unwrap().nucleus();
+        assert_eq!(vec!["carol"], nucleus.presence().online(unix_now(), 60));
+        assert_eq!(
+            vec![test_receipt("carol")],
+            nucleus.receipts().receipts(&test_entry().key())
+        );
+    }
+
+    #[test]
+    /// messages to agents that can't be reached are retried as configured
+    fn retries() {
+        let network = MemoryNetwork::new();
+        let messenger = test_caller(&network, "alice");
+        let call = test_remote_call().call();
+        let timeout = Duration::from_millis(1000);
+        assert_eq!(NetworkConfig::default(), messenger.config());
+
+        messenger.configure(&NetworkConfig {
+            retry: RetryPolicy::never(),
+            ..NetworkConfig::default()
+        });
+        assert!(messenger.call_remote("bob", &call, None, timeout).is_err());
+
+        // bob comes online while alice is still retrying
+        messenger.configure(&NetworkConfig {
+            retry: RetryPolicy {
+                attempts: 50,
+                initial_delay_ms: 10,
+                max_delay_ms: 10,
+                backoff: Backoff::Constant,
+            },
+            ..NetworkConfig::default()
+        });
+        let late = network.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(30));
+            test_responder(&late, "bob");
+        });
+        assert_eq!(
+            Ok("{} from alice".to_string()),
+            messenger.call_remote("bob", &call, None, timeout)
+        );
+    }
+}
@@ -1,4 +1,65 @@
+pub mod config;
+pub mod connectivity;
+pub mod direct_message;
+pub mod outbox;
+pub mod presence;
+pub mod remote_signal;
+pub mod sealing;
+pub mod stream;
+pub mod wire;
+
+use trace::TraceContext;
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Action {
     AddPeer(String),
 }
+
+/// wraps every message sent to another node
+/// trace carries the sender's span so work the message causes remotely, e.g. validating a
+/// published entry, shows up in the same trace
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Envelope<M> {
+    pub trace: Option<TraceContext>,
+    pub message: M,
+}
+
+impl<M> Envelope<M> {
+    /// wrap an untraced message
+    pub fn new(message: M) -> Envelope<M> {
+        Envelope {
+            trace: None,
+            message,
+        }
+    }
+
+    /// wrap a message sent as part of a traced span of work
+    pub fn traced(message: M, trace: TraceContext) -> Envelope<M> {
+        Envelope {
+            trace: Some(trace),
+            message,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{stream::StreamMessage, Envelope};
+    use holochain_serialization::encoding::ENCODINGS;
+    use trace::tests::test_trace_context;
+
+    #[test]
+    /// the trace survives the trip over the wire, whatever the encoding
+    fn envelope_roundtrip() {
+        let envelope = Envelope::traced(
+            StreamMessage::GetManifest("foo".to_string()),
+            test_trace_context(),
+        );
+        for encoding in ENCODINGS {
+            let bytes = encoding.encode(&envelope).unwrap();
+            let received: Envelope<StreamMessage> = encoding.decode(&bytes).unwrap();
+            assert_eq!(envelope, received);
+        }
+        assert_eq!(None, Envelope::new(1).trace);
+    }
+}
@@ -0,0 +1,595 @@
+//! the outbox holds what an instance sends to other nodes until it gets through, so commits
+//! still succeed while the network is down and what they publish goes out once it is back
+//! what is queued waits at a priority, see Priority, and is sent highest priority first, each
+//! priority in order, flushing stops at the first publish or message that can't be sent so
+//! nothing overtakes it
+//! each priority holds at most max_outbox_depth of the resource limits, what doesn't fit is
+//! refused and commits whose publishes wouldn't fit fail before they are made, so a bulk import
+//! holds back its caller rather than what is more urgent
+//! an outbox can be persisted to a JSON file, rewritten every time it changes, so what is pending
+//! survives restarting the instance
+
+use agent::membrane::AGENT_ID_ENTRY_TYPE;
+use dht::{aspect::Aspect, read::neighbors, DhtState};
+use error::HolochainError;
+use limits::{self, LimitExceeded, Resource};
+use network::direct_message::{DirectMessage, DirectMessenger};
+use platform;
+use serde_json;
+use std::{
+    collections::{BTreeMap, VecDeque}, fmt, fs, path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Receiver, Sender}, Arc, Mutex,
+    },
+};
+
+/// how often an instance on the network retries sending what is waiting
+pub const OUTBOX_RETRY_INTERVAL_MS: u64 = 1000;
+
+/// something for other nodes
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Outgoing {
+    /// aspects of the address for the nodes holding it
+    Publish(String, Vec<Aspect>),
+    /// a direct message for the agent with the address
+    Message(String, DirectMessage),
+}
+
+/// how urgently something waits to go out
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Priority {
+    /// goes out before anything else
+    High,
+    #[default]
+    Normal,
+    /// goes out once nothing else waits, e.g. bulk imports
+    Low,
+}
+
+/// every priority, highest first
+pub const PRIORITIES: [Priority; 3] = [Priority::High, Priority::Normal, Priority::Low];
+
+impl Priority {
+    /// the priority outgoing waits at unless it is queued at a higher one: High for publishes
+    /// taking something back, i.e. deletions, updates and link removals, for warrants and
+    /// checkpoints, and for the AgentIds agents are let in by, Normal for the rest
+    pub fn of(outgoing: &Outgoing) -> Priority {
+        let urgent = |aspect: &Aspect| match *aspect {
+            Aspect::Delete
+            | Aspect::Update(_)
+            | Aspect::LinkRemove(_)
+            | Aspect::Warrant(_)
+            | Aspect::Checkpoint(_) => true,
+            Aspect::Content(ref entry) => entry.entry_type() == AGENT_ID_ENTRY_TYPE,
+            _ => false,
+        };
+        match *outgoing {
+            Outgoing::Publish(_, ref aspects) if aspects.iter().any(urgent) => Priority::High,
+            _ => Priority::Normal,
+        }
+    }
+}
+
+/// what the file of an outbox keeps of each publish or message pending, in the order they go out
+#[derive(Serialize, Deserialize)]
+struct Queued {
+    priority: Priority,
+    outgoing: Outgoing,
+}
+
+/// what is read from the file of an outbox, those kept before there were priorities only hold
+/// what is pending
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Persisted {
+    Queued(Queued),
+    Unprioritized(Outgoing),
+}
+
+/// the depth of the outbox and what it refused, by priority, e.g. for an operator dashboard
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct OutboxMetrics {
+    /// how many publishes and messages wait
+    pub depths: BTreeMap<Priority, usize>,
+    /// how many publishes and messages were refused for want of room since the instance started,
+    /// those of failed commits included
+    pub refused: BTreeMap<Priority, u64>,
+}
+
+#[derive(Default)]
+struct Queue {
+    /// what waits by priority, in the order it was queued
+    pending: BTreeMap<Priority, VecDeque<Outgoing>>,
+    /// each priority holds at most this many, None for no bound
+    max_depth: Option<usize>,
+    refused: BTreeMap<Priority, u64>,
+    /// file the pending ones are persisted to, if any
+    path: Option<PathBuf>,
+    /// true while a flush is sending, so two flushes don't send the same
+    flushing: bool,
+    /// told whenever something is queued, see wakeup()
+    waker: Option<Sender<()>>,
+}
+
+impl Queue {
+    fn depth(&self, priority: Priority) -> usize {
+        self.pending.get(&priority).map(VecDeque::len).unwrap_or(0)
+    }
+
+    /// check count more fit at priority, counting them as refused if they don't
+    fn room(&mut self, priority: Priority, count: usize) -> Result<(), LimitExceeded> {
+        let requested = (self.depth(priority) + count) as u64;
+        let max_depth = self.max_depth.map(|max_depth| max_depth as u64);
+        limits::check(Resource::OutboxDepth, max_depth, requested).inspect_err(|_| {
+            *self.refused.entry(priority).or_default() += count as u64;
+        })
+    }
+
+    /// what goes out next and its priority
+    fn front(&self) -> Option<(Priority, Outgoing)> {
+        self.pending.iter().find_map(|(priority, pending)| {
+            pending.front().map(|front| (*priority, front.clone()))
+        })
+    }
+
+    /// what is pending in the order it goes out
+    fn queued(&self) -> impl Iterator<Item = (Priority, &Outgoing)> {
+        self.pending.iter().flat_map(|(priority, pending)| {
+            pending.iter().map(move |outgoing| (*priority, outgoing))
+        })
+    }
+
+    /// write what is pending to the file, if persisted
+    fn save(&self) -> Result<(), HolochainError> {
+        match self.path {
+            Some(ref path) => {
+                let queued: Vec<Queued> = self
+                    .queued()
+                    .map(|(priority, outgoing)| Queued {
+                        priority,
+                        outgoing: outgoing.clone(),
+                    })
+                    .collect();
+                let json = serde_json::to_string(&queued)
+                    .map_err(|e| HolochainError::new(&e.to_string()))?;
+                fs::write(path, json).map_err(|e| HolochainError::new(&e.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// what an instance has yet to get through to other nodes
+/// the outbox is a cheap handle, clones share the same queue
+#[derive(Clone, Default)]
+pub struct Outbox {
+    queue: Arc<Mutex<Queue>>,
+}
+
+impl PartialEq for Outbox {
+    fn eq(&self, other: &Outbox) -> bool {
+        Arc::ptr_eq(&self.queue, &other.queue)
+    }
+}
+
+impl fmt::Debug for Outbox {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let queue = self.queue.lock().unwrap();
+        f.debug_struct("Outbox")
+            .field("depth", &queue.queued().count())
+            .field("path", &queue.path)
+            .finish()
+    }
+}
+
+impl Outbox {
+    pub fn new() -> Outbox {
+        Outbox::default()
+    }
+
+    /// persist the outbox to the file from now on, queueing what is pending in it after what is
+    /// queued already at the same priority, whether or not it fits
+    pub fn persist(&self, path: &Path) -> Result<(), HolochainError> {
+        let persisted: Vec<Persisted> = if path.exists() {
+            let json = fs::read_to_string(path).map_err(|e| HolochainError::new(&e.to_string()))?;
+            serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))?
+        } else {
+            Vec::new()
+        };
+        let mut queue = self.queue.lock().unwrap();
+        for persisted in persisted {
+            let (priority, outgoing) = match persisted {
+                Persisted::Queued(queued) => (queued.priority, queued.outgoing),
+                Persisted::Unprioritized(outgoing) => (Priority::of(&outgoing), outgoing),
+            };
+            queue.pending.entry(priority).or_default().push_back(outgoing);
+        }
+        queue.path = Some(path.to_path_buf());
+        queue.save()
+    }
+
+    /// bound how many publishes and messages wait at each priority, None for no bound
+    /// what waits already is kept, only queueing more is refused
+    pub fn set_max_depth(&self, max_depth: Option<usize>) {
+        self.queue.lock().unwrap().max_depth = max_depth;
+    }
+
+    /// how many publishes and messages are waiting to get through, e.g. to show sync status
+    pub fn depth(&self) -> usize {
+        self.queue.lock().unwrap().queued().count()
+    }
+
+    /// the depth at every priority and how many were refused
+    pub fn metrics(&self) -> OutboxMetrics {
+        let queue = self.queue.lock().unwrap();
+        OutboxMetrics {
+            depths: PRIORITIES
+                .iter()
+                .map(|priority| (*priority, queue.depth(*priority)))
+                .collect(),
+            refused: PRIORITIES
+                .iter()
+                .map(|priority| (*priority, queue.refused.get(priority).cloned().unwrap_or(0)))
+                .collect(),
+        }
+    }
+
+    /// copy of what is waiting, in the order it will be sent
+    pub fn pending(&self) -> Vec<Outgoing> {
+        let queue = self.queue.lock().unwrap();
+        queue.queued().map(|(_, outgoing)| outgoing.clone()).collect()
+    }
+
+    /// check count more publishes or messages fit at priority, e.g. before committing what
+    /// will be published, nothing is set aside for them
+    pub fn room(&self, priority: Priority, count: usize) -> Result<(), LimitExceeded> {
+        self.queue.lock().unwrap().room(priority, count)
+    }
+
+    /// queue something to send with the next flush, at its own priority, see Priority::of()
+    pub fn queue(&self, outgoing: Outgoing) -> Result<(), HolochainError> {
+        self.queue_at(Priority::of(&outgoing), outgoing)
+    }
+
+    /// queue something to send with the next flush at priority, unless there is no room for it
+    /// it is queued even if it can't be persisted, the error says why it wasn't
+    pub fn queue_at(&self, priority: Priority, outgoing: Outgoing) -> Result<(), HolochainError> {
+        let mut queue = self.queue.lock().unwrap();
+        queue
+            .room(priority, 1)
+            .map_err(|exceeded| HolochainError::ErrorGeneric(exceeded.to_string()))?;
+        queue.pending.entry(priority).or_default().push_back(outgoing);
+        if let Some(ref waker) = queue.waker {
+            let _ = waker.send(());
+        }
+        queue.save()
+    }
+
+    /// told whenever something is queued, e.g. to flush right away
+    /// only the last receiver asked for is told, earlier ones are disconnected
+    pub fn wakeup(&self) -> Receiver<()> {
+        let (sender, receiver) = channel();
+        self.queue.lock().unwrap().waker = Some(sender);
+        receiver
+    }
+
+    /// send what is waiting in order, highest priority first, until something can't be sent,
+    /// returns how many were sent
+    /// what is queued at a higher priority while sending goes out next
+    /// publishes are pushed to the nearest peers holding the address as far as dht knows
+    /// does nothing while another flush is sending
+    pub fn flush(&self, messenger: &DirectMessenger, dht: &DhtState) -> usize {
+        {
+            let mut queue = self.queue.lock().unwrap();
+            if queue.flushing {
+                return 0;
+            }
+            queue.flushing = true;
+        }
+        let mut sent = 0;
+        loop {
+            // the lock is released while sending so queueing doesn't wait on the network
+            let next = self.queue.lock().unwrap().front();
+            let (priority, outgoing) = match next {
+                Some(next) => next,
+                None => break,
+            };
+            if send(&outgoing, messenger, dht).is_err() {
+                break;
+            }
+            let mut queue = self.queue.lock().unwrap();
+            if let Some(pending) = queue.pending.get_mut(&priority) {
+                pending.pop_front();
+            }
+            // the file catches up with the next change if it can't be written now
+            let _ = queue.save();
+            sent += 1;
+        }
+        self.queue.lock().unwrap().flushing = false;
+        sent
+    }
+}
+
+/// push aspects of the address to the fan_out k nearest peers holding it at once, rather than
+/// waiting for gossip to spread them
+/// it got through once one of them got it or if there is nobody to push to, holders past the k
+/// nearest and those the push doesn't reach are left to gossip
+fn publish(
+    address: &str,
+    aspects: &[Aspect],
+    messenger: &DirectMessenger,
+    dht: &DhtState,
+) -> Result<(), HolochainError> {
+    let me = messenger.address().ok_or_else(|| {
+        HolochainError::ErrorGeneric("not connected to a network".to_string())
+    })?;
+    let holders: Vec<String> = neighbors(dht, address)
+        .into_iter()
+        .filter(|holder| *holder != me)
+        .take(messenger.config().fan_out.k)
+        .collect();
+    let (sender, receiver) = channel();
+    for holder in holders {
+        let messenger = messenger.clone();
+        let message = DirectMessage::Publish {
+            from: me.clone(),
+            address: address.to_string(),
+            aspects: aspects.to_vec(),
+        };
+        let sender = sender.clone();
+        platform::spawn("dht_push", move || {
+            // nobody listens anymore once one holder got it
+            let _ = sender.send(messenger.send(&holder, message));
+        });
+    }
+    drop(sender);
+    let mut result = Ok(());
+    for pushed in receiver {
+        match pushed {
+            Ok(()) => return Ok(()),
+            Err(error) => result = Err(error),
+        }
+    }
+    result
+}
+
+fn send(
+    outgoing: &Outgoing,
+    messenger: &DirectMessenger,
+    dht: &DhtState,
+) -> Result<(), HolochainError> {
+    match *outgoing {
+        Outgoing::Publish(ref address, ref aspects) => publish(address, aspects, messenger, dht),
+        Outgoing::Message(ref to, ref message) => messenger.send(to, message.clone()),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        location, tests::{test_dht_state, test_reduce}, Action, StorageArc,
+    };
+    use hash_table::entry::tests::test_entry;
+    use network::{
+        config::{FanOut, NetworkConfig, RetryPolicy}, direct_message::MemoryNetwork,
+    };
+    use std::{env, process, time::Duration};
+
+    /// publish of the content of test_entry
+    pub fn test_publish() -> Outgoing {
+        Outgoing::Publish(test_entry().key(), vec![Aspect::Content(test_entry())])
+    }
+
+    /// messenger connected as "me" giving up on agents that can't be reached right away
+    fn test_sender(network: &MemoryNetwork) -> DirectMessenger {
+        let messenger = DirectMessenger::default();
+        let _ = messenger.connect(network, "me");
+        messenger.configure(&NetworkConfig {
+            retry: RetryPolicy::never(),
+            ..NetworkConfig::default()
+        });
+        messenger
+    }
+
+    #[test]
+    /// what is queued waits until it gets through, in order
+    fn flush_in_order() {
+        let outbox = Outbox::new();
+        let goodbye = DirectMessage::Goodbye("me".to_string());
+        let goodbye = Outgoing::Message("bob".to_string(), goodbye);
+        outbox.queue(test_publish()).unwrap();
+        outbox.queue(goodbye.clone()).unwrap();
+        assert_eq!(2, outbox.depth());
+
+        // offline nothing gets through
+        let network = MemoryNetwork::new();
+        let messenger = DirectMessenger::default();
+        assert_eq!(0, outbox.flush(&messenger, &test_dht_state()));
+
+        // bob holds the address but is down, the message to bob waits behind the publish
+        let messenger = test_sender(&network);
+        let dht = test_reduce(
+            test_dht_state(),
+            Action::PeerSeen("bob".to_string(), StorageArc::default()),
+        );
+        assert_eq!(0, outbox.flush(&messenger, &dht));
+        assert_eq!(vec![test_publish(), goodbye.clone()], outbox.pending());
+
+        let bob = network.connect("bob");
+        assert_eq!(2, outbox.flush(&messenger, &dht));
+        assert_eq!(0, outbox.depth());
+        let publish = DirectMessage::Publish {
+            from: "me".to_string(),
+            address: test_entry().key(),
+            aspects: vec![Aspect::Content(test_entry())],
+        };
+        assert_eq!(publish, bob.recv().unwrap().message);
+        assert_eq!(DirectMessage::Goodbye("me".to_string()), bob.recv().unwrap().message);
+
+        // with nobody to publish to there is nothing to wait for
+        outbox.queue(test_publish()).unwrap();
+        assert_eq!(1, outbox.flush(&messenger, &test_dht_state()));
+    }
+
+    #[test]
+    /// publishes are pushed to the k nearest holders, it is enough that one of them gets it
+    fn push_to_nearest() {
+        let network = MemoryNetwork::new();
+        let messenger = test_sender(&network);
+        messenger.configure(&NetworkConfig {
+            retry: RetryPolicy::never(),
+            fan_out: FanOut { alpha: 1, k: 2 },
+            ..NetworkConfig::default()
+        });
+        let here = location(&test_entry().key());
+        let mut dht = test_dht_state();
+        for (holder, distance) in &[("near", 1), ("down", 2), ("far", 3)] {
+            let arc = StorageArc::new(here.wrapping_add(*distance), 100);
+            dht = test_reduce(dht, Action::PeerSeen(holder.to_string(), arc));
+        }
+        let near = network.connect("near");
+        let far = network.connect("far");
+
+        let outbox = Outbox::new();
+        outbox.queue(test_publish()).unwrap();
+        assert_eq!(1, outbox.flush(&messenger, &dht));
+        assert!(near.recv_timeout(Duration::from_millis(1000)).is_ok());
+        // far is left to gossip
+        assert!(far.try_recv().is_err());
+
+        // down can't be reached but near gets it
+        outbox.queue(test_publish()).unwrap();
+        assert_eq!(1, outbox.flush(&messenger, &dht));
+        drop(near);
+        outbox.queue(test_publish()).unwrap();
+        assert_eq!(0, outbox.flush(&messenger, &dht));
+    }
+
+    #[test]
+    /// what takes something back goes out first, the rest in the order it was queued
+    fn flush_by_priority() {
+        let network = MemoryNetwork::new();
+        let messenger = test_sender(&network);
+        let bob = network.connect("bob");
+        let message = |n: u64| {
+            Outgoing::Message("bob".to_string(), DirectMessage::Heartbeat(n.to_string()))
+        };
+        let outbox = Outbox::new();
+        outbox.queue_at(Priority::Low, message(1)).unwrap();
+        outbox.queue(message(2)).unwrap();
+        let deleted = Outgoing::Publish(test_entry().key(), vec![Aspect::Delete]);
+        assert_eq!(Priority::High, Priority::of(&deleted));
+        assert_eq!(Priority::Normal, Priority::of(&test_publish()));
+        outbox.queue(deleted.clone()).unwrap();
+        outbox.queue(message(3)).unwrap();
+        assert_eq!(vec![deleted, message(2), message(3), message(1)], outbox.pending());
+
+        assert_eq!(4, outbox.flush(&messenger, &test_dht_state()));
+        let heartbeats: Vec<DirectMessage> =
+            bob.try_iter().map(|envelope| envelope.message).collect();
+        assert_eq!(
+            vec![
+                DirectMessage::Heartbeat("2".to_string()),
+                DirectMessage::Heartbeat("3".to_string()),
+                DirectMessage::Heartbeat("1".to_string()),
+            ],
+            heartbeats
+        );
+    }
+
+    #[test]
+    /// each priority holds at most max_depth, what doesn't fit is refused and counted
+    fn bounded() {
+        let outbox = Outbox::new();
+        outbox.set_max_depth(Some(1));
+        outbox.queue_at(Priority::Low, test_publish()).unwrap();
+        assert!(outbox.queue_at(Priority::Low, test_publish()).is_err());
+        assert_eq!(
+            Err(LimitExceeded {
+                resource: Resource::OutboxDepth,
+                limit: 1,
+                requested: 3,
+            }),
+            outbox.room(Priority::Low, 2)
+        );
+        // a bulk import full up doesn't hold back the rest
+        assert_eq!(Ok(()), outbox.room(Priority::Normal, 1));
+        outbox.queue(test_publish()).unwrap();
+
+        let metrics = outbox.metrics();
+        assert_eq!(
+            vec![(Priority::High, 0), (Priority::Normal, 1), (Priority::Low, 1)],
+            metrics.depths.into_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(Priority::High, 0), (Priority::Normal, 0), (Priority::Low, 3)],
+            metrics.refused.into_iter().collect::<Vec<_>>()
+        );
+
+        outbox.set_max_depth(None);
+        assert_eq!(Ok(()), outbox.room(Priority::Low, 100));
+    }
+
+    #[test]
+    /// the queue survives in its file
+    fn persist() {
+        let path = env::temp_dir().join(format!("holochain_outbox_test_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let outbox = Outbox::new();
+        outbox.queue(test_publish()).unwrap();
+        outbox.persist(&path).unwrap();
+
+        let restarted = Outbox::new();
+        restarted.persist(&path).unwrap();
+        assert_eq!(vec![test_publish()], restarted.pending());
+
+        let network = MemoryNetwork::new();
+        assert_eq!(1, restarted.flush(&test_sender(&network), &test_dht_state()));
+        let emptied = Outbox::new();
+        emptied.persist(&path).unwrap();
+        assert_eq!(0, emptied.depth());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(Outbox::new().persist(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// priorities survive in the file, what was kept before there were any waits at its own
+    fn persist_priorities() {
+        let path = env::temp_dir().join(format!(
+            "holochain_outbox_priorities_test_{}.json",
+            process::id()
+        ));
+        let _ = fs::remove_file(&path);
+        let outbox = Outbox::new();
+        outbox.persist(&path).unwrap();
+        outbox.queue_at(Priority::Low, test_publish()).unwrap();
+        let restarted = Outbox::new();
+        restarted.persist(&path).unwrap();
+        assert_eq!(Some(&1), restarted.metrics().depths.get(&Priority::Low));
+
+        let deleted = Outgoing::Publish(test_entry().key(), vec![Aspect::Delete]);
+        let unprioritized = vec![test_publish(), deleted.clone()];
+        fs::write(&path, serde_json::to_string(&unprioritized).unwrap()).unwrap();
+        let upgraded = Outbox::new();
+        upgraded.persist(&path).unwrap();
+        assert_eq!(vec![deleted, test_publish()], upgraded.pending());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// the last receiver asked for hears of everything queued
+    fn wakeup() {
+        let outbox = Outbox::new();
+        let first = outbox.wakeup();
+        let second = outbox.wakeup();
+        outbox.queue(test_publish()).unwrap();
+        assert!(first.try_recv().is_err());
+        assert_eq!(Ok(()), second.try_recv());
+    }
+}
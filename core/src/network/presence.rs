@@ -0,0 +1,172 @@
+//! presence tells which agents are online: while the network config has a presence section, an
+//! instance sends a heartbeat to its neighborhood, the nearest peers holding its own address,
+//! every interval_secs, and the agents a heartbeat came from within fresh_secs count as online
+//! heartbeats are a single direct message carrying the agent address, sent once without retries
+//! instances hold no signing keys for their agent yet, so unlike the provenances of headers they
+//! are taken at the sender's word
+
+use network::{
+    config::{NetworkConfig, PresenceConfig}, direct_message::DirectMessage, Envelope,
+};
+use nucleus::scheduler::unix_now;
+use state::State;
+use std::{
+    collections::BTreeMap, fmt, sync::{Arc, Mutex},
+};
+
+#[derive(Default)]
+struct Heartbeats {
+    /// seconds since the unix epoch when a heartbeat last came from each agent
+    received: BTreeMap<String, u64>,
+    /// seconds since the unix epoch when the instance last sent its heartbeat
+    sent: Option<u64>,
+}
+
+/// the heartbeats an instance sent and received
+/// presence is a cheap handle, clones share the same heartbeats
+#[derive(Clone, Default)]
+pub struct Presence {
+    heartbeats: Arc<Mutex<Heartbeats>>,
+}
+
+impl PartialEq for Presence {
+    fn eq(&self, other: &Presence) -> bool {
+        Arc::ptr_eq(&self.heartbeats, &other.heartbeats)
+    }
+}
+
+impl fmt::Debug for Presence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let heartbeats = self.heartbeats.lock().unwrap();
+        f.debug_struct("Presence")
+            .field("received", &heartbeats.received.len())
+            .field("sent", &heartbeats.sent)
+            .finish()
+    }
+}
+
+impl Presence {
+    /// note a heartbeat from the agent arrived at the given time
+    pub fn received(&self, agent: &str, at: u64) {
+        let mut heartbeats = self.heartbeats.lock().unwrap();
+        let last = heartbeats.received.entry(agent.to_string()).or_insert(at);
+        *last = (*last).max(at);
+    }
+
+    /// agents a heartbeat came from within fresh_secs of now, sorted
+    pub fn online(&self, now: u64, fresh_secs: u64) -> Vec<String> {
+        self.heartbeats
+            .lock()
+            .unwrap()
+            .received
+            .iter()
+            .filter(|(_, at)| now.saturating_sub(**at) < fresh_secs)
+            .map(|(agent, _)| agent.clone())
+            .collect()
+    }
+
+    /// the online agents as the config says, none while presence is off
+    pub fn online_agents(&self, config: &NetworkConfig) -> Vec<String> {
+        match config.presence {
+            Some(ref presence) => self.online(unix_now(), presence.fresh_secs),
+            None => Vec::new(),
+        }
+    }
+
+    /// true if the heartbeat is due at now
+    fn due(&self, now: u64, config: &PresenceConfig) -> bool {
+        self.heartbeats
+            .lock()
+            .unwrap()
+            .sent
+            .map(|sent| now.saturating_sub(sent) >= config.interval_secs)
+            .unwrap_or(true)
+    }
+
+    fn sent(&self, now: u64) {
+        self.heartbeats.lock().unwrap().sent = Some(now);
+    }
+}
+
+/// send the heartbeat of the instance with the state to its neighborhood if presence is on and
+/// it is due, returns the agents it was delivered to
+/// a heartbeat nobody got is due again right away, e.g. while the neighborhood is still joining
+pub fn beat(state: &State) -> Vec<String> {
+    let nucleus = state.nucleus();
+    let messenger = nucleus.messenger();
+    let config = messenger.config();
+    let (network, me) = match (messenger.network(), messenger.address()) {
+        (Some(network), Some(me)) => (network, me),
+        _ => return Vec::new(),
+    };
+    let now = unix_now();
+    match config.presence {
+        Some(ref presence) if nucleus.presence().due(now, presence) => (),
+        _ => return Vec::new(),
+    }
+    let delivered: Vec<String> = ::dht::read::neighbors(&state.dht(), &me)
+        .into_iter()
+        .filter(|agent| *agent != me)
+        .take(config.fan_out.k)
+        .filter(|agent| {
+            let heartbeat = Envelope::new(DirectMessage::Heartbeat(me.clone()));
+            network.send(agent, heartbeat).is_ok()
+        })
+        .collect();
+    if !delivered.is_empty() {
+        nucleus.presence().sent(now);
+    }
+    delivered
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{Action::PeerSeen, StorageArc};
+    use instance::Observer;
+    use network::direct_message::MemoryNetwork;
+    use state::{Action::Dht, ActionWrapper};
+    use std::sync::mpsc::channel;
+
+    #[test]
+    /// agents are online while their last heartbeat is fresh
+    fn online() {
+        let presence = Presence::default();
+        presence.received("bob", 100);
+        presence.received("alice", 150);
+        presence.received("bob", 90);
+        assert_eq!(vec!["alice", "bob"], presence.online(150, 60));
+        assert_eq!(vec!["alice"], presence.online(160, 60));
+        assert!(presence.online(210, 60).is_empty());
+        assert!(presence
+            .online_agents(&NetworkConfig::default())
+            .is_empty());
+    }
+
+    #[test]
+    /// heartbeats go to the neighborhood every interval while presence is on
+    fn beat_neighborhood() {
+        let (sender, _receiver) = channel();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let mut state = State::new();
+        for peer in ["bob", "carol"] {
+            let action = Dht(PeerSeen(peer.to_string(), StorageArc::default()));
+            state = state.reduce(ActionWrapper::new(action), &sender, &tx_observer);
+        }
+        let network = MemoryNetwork::new();
+        let _alice = state.nucleus().messenger().connect(&network, "alice");
+        let bob = network.connect("bob");
+        assert!(beat(&state).is_empty());
+
+        state.nucleus().messenger().configure(&NetworkConfig {
+            presence: Some(PresenceConfig::default()),
+            ..NetworkConfig::default()
+        });
+        assert_eq!(vec!["bob"], beat(&state));
+        assert_eq!(
+            DirectMessage::Heartbeat("alice".to_string()),
+            bob.try_recv().unwrap().message
+        );
+        assert!(beat(&state).is_empty());
+    }
+}
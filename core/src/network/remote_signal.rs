@@ -0,0 +1,241 @@
+//! remote signals are app events a zome sends straight to other agents, e.g. presence or typing
+//! indicators, with the remote_signal host function
+//! they are fire and forget: sent once to each agent, without waiting for an answer or retrying
+//! an agent that can't be reached, and each arrives as a REMOTE_SIGNAL on the agent's signal bus
+//! signals sent within REMOTE_SIGNAL_BATCH_MS of each other go to an agent in a single message,
+//! payloads over REMOTE_SIGNAL_MAX_BYTES of JSON are refused
+
+use error::HolochainError;
+use network::{
+    direct_message::{DirectMessage, DirectMessenger}, Envelope,
+};
+use platform;
+use serde_json::{self, Value};
+use signal::{Signal, SignalBus};
+use std::{
+    collections::BTreeMap, fmt, mem, sync::{Arc, Mutex}, time::Duration,
+};
+
+/// name of the signal emitted for a remote signal received, its payload is the RemoteSignal
+pub const REMOTE_SIGNAL: &str = "remote_signal";
+
+/// max size of the JSON payload of a remote signal
+pub const REMOTE_SIGNAL_MAX_BYTES: usize = 1024;
+
+/// how long signals wait to be batched before going out
+pub const REMOTE_SIGNAL_BATCH_MS: u64 = 20;
+
+/// a signal a zome sent to another agent
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RemoteSignal {
+    /// address of the sending agent
+    pub from: String,
+    /// zome that sent it
+    pub zome: String,
+    pub payload: Value,
+}
+
+impl RemoteSignal {
+    /// the signal reporting it to the receiving agent, from the zome it was sent by
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            zome: self.zome.clone(),
+            name: REMOTE_SIGNAL.to_string(),
+            payload: serde_json::to_value(self).expect("RemoteSignal should serialize"),
+        }
+    }
+}
+
+/// emit the signals an agent sent on the bus
+pub fn receive(signals: &[RemoteSignal], bus: &SignalBus) {
+    for signal in signals {
+        bus.emit(&signal.to_signal());
+    }
+}
+
+#[derive(Default)]
+struct Batches {
+    /// signals waiting to go out by receiving agent
+    by_agent: BTreeMap<String, Vec<RemoteSignal>>,
+    /// true while a flush of the batches is on its way
+    flushing: bool,
+}
+
+/// the remote signals of an instance waiting to go out
+/// the sender is a cheap handle, clones share the same batches
+#[derive(Clone, Default)]
+pub struct RemoteSignalSender {
+    batches: Arc<Mutex<Batches>>,
+}
+
+impl PartialEq for RemoteSignalSender {
+    fn eq(&self, other: &RemoteSignalSender) -> bool {
+        Arc::ptr_eq(&self.batches, &other.batches)
+    }
+}
+
+impl fmt::Debug for RemoteSignalSender {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RemoteSignalSender")
+            .field("agents", &self.batches.lock().unwrap().by_agent.len())
+            .finish()
+    }
+}
+
+impl RemoteSignalSender {
+    /// batch the payload from the zome for each of the agents, it goes out
+    /// REMOTE_SIGNAL_BATCH_MS later
+    /// fails if the payload is too big or the messenger is not connected, but not if an agent
+    /// can't be reached
+    pub fn send(
+        &self,
+        messenger: &DirectMessenger,
+        zome: &str,
+        agents: &[String],
+        payload: Value,
+    ) -> Result<(), HolochainError> {
+        let size = payload.to_string().len();
+        if size > REMOTE_SIGNAL_MAX_BYTES {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "remote signal of {} bytes is over the {} bytes allowed",
+                size, REMOTE_SIGNAL_MAX_BYTES
+            )));
+        }
+        let from = messenger
+            .address()
+            .ok_or_else(|| HolochainError::new("not connected to a network"))?;
+        let signal = RemoteSignal {
+            from,
+            zome: zome.to_string(),
+            payload,
+        };
+        let start_flush = {
+            let mut batches = self.batches.lock().unwrap();
+            for agent in agents {
+                batches
+                    .by_agent
+                    .entry(agent.clone())
+                    .or_default()
+                    .push(signal.clone());
+            }
+            let start_flush = !batches.flushing && !batches.by_agent.is_empty();
+            batches.flushing |= start_flush;
+            start_flush
+        };
+        if start_flush {
+            let sender = self.clone();
+            let messenger = messenger.clone();
+            platform::spawn("remote_signals", move || {
+                platform::sleep(Duration::from_millis(REMOTE_SIGNAL_BATCH_MS));
+                sender.flush(&messenger);
+            });
+        }
+        Ok(())
+    }
+
+    /// how many signals wait to go out, over all agents
+    pub fn pending(&self) -> usize {
+        self.batches
+            .lock()
+            .unwrap()
+            .by_agent
+            .values()
+            .map(Vec::len)
+            .sum()
+    }
+
+    /// send each agent its batch in one message, once
+    /// returns how many agents the batches were delivered to
+    pub fn flush(&self, messenger: &DirectMessenger) -> usize {
+        let by_agent = {
+            let mut batches = self.batches.lock().unwrap();
+            batches.flushing = false;
+            mem::take(&mut batches.by_agent)
+        };
+        let network = match messenger.network() {
+            Some(network) => network,
+            None => return 0,
+        };
+        by_agent
+            .into_iter()
+            .filter(|(agent, signals)| {
+                let message = DirectMessage::RemoteSignals(signals.clone());
+                network.send(agent, Envelope::new(message)).is_ok()
+            })
+            .count()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use network::direct_message::MemoryNetwork;
+
+    fn agents(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    /// signals sent together reach each agent in one message, unreachable agents are skipped
+    fn batched() {
+        let network = MemoryNetwork::new();
+        let alice = DirectMessenger::default();
+        let _alice = alice.connect(&network, "alice");
+        let bob = network.connect("bob");
+        let sender = RemoteSignalSender::default();
+        sender
+            .send(&alice, "chat", &agents(&["bob", "carol"]), json!({"typing": true}))
+            .unwrap();
+        sender
+            .send(&alice, "chat", &agents(&["bob"]), json!({"typing": false}))
+            .unwrap();
+
+        let envelope = bob.recv_timeout(Duration::from_secs(1)).unwrap();
+        let signal = |typing| RemoteSignal {
+            from: "alice".to_string(),
+            zome: "chat".to_string(),
+            payload: json!({ "typing": typing }),
+        };
+        assert_eq!(
+            DirectMessage::RemoteSignals(vec![signal(true), signal(false)]),
+            envelope.message
+        );
+        assert_eq!(0, sender.pending());
+        assert!(bob.try_recv().is_err());
+        assert_eq!(0, sender.flush(&alice));
+    }
+
+    #[test]
+    /// big payloads and senders off the network are refused
+    fn refused() {
+        let alice = DirectMessenger::default();
+        let sender = RemoteSignalSender::default();
+        assert!(sender.send(&alice, "chat", &agents(&["bob"]), json!(1)).is_err());
+
+        let _alice = alice.connect(&MemoryNetwork::new(), "alice");
+        let big = json!("x".repeat(REMOTE_SIGNAL_MAX_BYTES));
+        assert!(sender.send(&alice, "chat", &agents(&["bob"]), big).is_err());
+        assert_eq!(0, sender.pending());
+        assert!(sender.send(&alice, "chat", &agents(&["bob"]), json!(1)).is_ok());
+    }
+
+    #[test]
+    /// received signals are emitted from the sending zome
+    fn received() {
+        let bus = SignalBus::default();
+        let signals = bus.subscribe();
+        let remote = RemoteSignal {
+            from: "alice".to_string(),
+            zome: "chat".to_string(),
+            payload: json!({"online": true}),
+        };
+        receive(&[remote], &bus);
+        let signal = signals.try_recv().unwrap();
+        assert_eq!("chat", signal.zome);
+        assert_eq!(REMOTE_SIGNAL, signal.name);
+        assert_eq!(
+            json!({"from": "alice", "zome": "chat", "payload": {"online": true}}),
+            signal.payload
+        );
+    }
+}
@@ -0,0 +1,725 @@
+//! direct messages are sealed to the key of the agent they are for, so nothing carrying them on
+//! the way can read them, only who they are for goes in the clear, see Sealed
+//! seals are the sealed boxes of libsodium: the message is boxed with X25519 and
+//! XSalsa20-Poly1305 between a key pair made for it alone and the recipient's key, and the
+//! public half of the one-off pair goes along, so nodes in other languages open them with
+//! crypto_box_seal_open
+//! agents have no keys of their own yet, see agent::keys, so a node makes a key pair when it
+//! connects and the network tells senders the public key of the agent at an address
+//! what is shared by a group of agents is encrypted with a secret key instead, see encrypt() and
+//! agent::groups
+//! key pairs can also be derived from a seed and agree on a key with other X25519 keys, for the
+//! keys zomes get from the keystore, see agent::keystore
+//! XSalsa20 also stretches a seed into as many bytes as wanted, see expand()
+//! keys to encrypt with are also stretched from passphrases with Argon2id, see stretch()
+//! secret keys and the keys agreed on and stretched are kept in SecBufs, see agent::secbuf
+//! what agents sign is signed with Ed25519 as in RFC 8032, see SigningKeyPair
+//! the primitives are those of audited crates, X25519 and Ed25519 of x25519-dalek and
+//! ed25519-dalek, XSalsa20-Poly1305 of crypto_secretbox, BLAKE2b of blake2 and Argon2id of argon2,
+//! put together here
+//! the way libsodium has them
+
+use agent::secbuf::{zeroize, SecBuf};
+use argon2::{Algorithm, Argon2, Params, Version};
+use blake2::{
+    digest::{Update, VariableOutput}, Blake2bVar,
+};
+use crypto_secretbox::{
+    aead::{AeadInPlace, KeyInit}, XSalsa20Poly1305,
+};
+use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use error::HolochainError;
+use rand::{self, Rng};
+use rust_base58::{FromBase58, ToBase58};
+use salsa20::{
+    cipher::{consts::U10, KeyIvInit, StreamCipher}, hsalsa, XSalsa20,
+};
+use std::fmt;
+use x25519_dalek::{PublicKey as X25519PublicKey, StaticSecret};
+
+/// bytes a seal adds to the message: the one-off public key and the Poly1305 tag
+pub const SEAL_BYTES: usize = 32 + 16;
+
+/// bytes encrypting with a secret key adds to the message: the random nonce and the Poly1305 tag
+pub const ENCRYPTION_BYTES: usize = 24 + 16;
+
+/// bytes of an Ed25519 signature
+pub const SIGNATURE_BYTES: usize = 64;
+
+/// default KiB of memory stretching a passphrase takes, see StretchParams
+/// with the default iterations and parallelism, the least OWASP recommends for Argon2id
+pub const STRETCH_DEFAULT_MEMORY_KIB: u32 = 19 * 1024;
+
+/// default passes over the memory when stretching a passphrase, see StretchParams
+pub const STRETCH_DEFAULT_ITERATIONS: u32 = 2;
+
+/// default lanes stretching a passphrase, see StretchParams
+pub const STRETCH_DEFAULT_PARALLELISM: u32 = 1;
+
+/// the cost of stretching a passphrase with Argon2id, see stretch()
+/// kept along with what the stretched key encrypts, so raising the defaults leaves what was
+/// encrypted before readable
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StretchParams {
+    /// KiB of memory every guess at the passphrase takes
+    pub memory_kib: u32,
+    /// passes over the memory
+    pub iterations: u32,
+    /// lanes of memory filled in parallel
+    pub parallelism: u32,
+}
+
+impl Default for StretchParams {
+    fn default() -> StretchParams {
+        StretchParams {
+            memory_kib: STRETCH_DEFAULT_MEMORY_KIB,
+            iterations: STRETCH_DEFAULT_ITERATIONS,
+            parallelism: STRETCH_DEFAULT_PARALLELISM,
+        }
+    }
+}
+
+/// a direct message on its way: who it is for in the clear, the envelope sealed to them
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Sealed {
+    /// address of the agent the message is for
+    pub to: String,
+    /// the envelope in the encoding the agent takes, sealed to its key
+    pub payload: Vec<u8>,
+}
+
+/// the X25519 public key of an agent, messages for it are sealed to it
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PublicKey(pub [u8; 32]);
+
+impl PublicKey {
+    /// the key in base58, an error if it isn't 32 bytes
+    pub fn from_base58(key: &str) -> Result<PublicKey, HolochainError> {
+        match key.from_base58() {
+            Ok(ref bytes) if bytes.len() == 32 => {
+                let mut public = [0; 32];
+                public.copy_from_slice(bytes);
+                Ok(PublicKey(public))
+            }
+            _ => Err(HolochainError::ErrorGeneric(format!(
+                "{} isn't an X25519 public key in base58",
+                key
+            ))),
+        }
+    }
+
+    pub fn to_base58(&self) -> String {
+        self.0.to_base58()
+    }
+}
+
+impl fmt::Debug for PublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "PublicKey({})", self.0.to_base58())
+    }
+}
+
+/// the key pair of a node messages for its agent are opened with
+#[derive(Clone)]
+pub struct KeyPair {
+    public: PublicKey,
+    secret: SecBuf,
+}
+
+impl PartialEq for KeyPair {
+    fn eq(&self, other: &KeyPair) -> bool {
+        self.public == other.public
+    }
+}
+
+/// only the public half shows
+impl fmt::Debug for KeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("KeyPair")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
+impl KeyPair {
+    /// a fresh random key pair
+    pub fn generate() -> KeyPair {
+        KeyPair::from_secret(SecBuf::random(32))
+    }
+
+    /// the key pair of an X25519 secret key
+    /// panics unless the secret key has 32 bytes
+    pub fn from_secret(secret: SecBuf) -> KeyPair {
+        let public = X25519PublicKey::from(&StaticSecret::from(*secret.as_key()));
+        KeyPair {
+            public: PublicKey(public.to_bytes()),
+            secret,
+        }
+    }
+
+    /// the key pair derived from a seed along a path, the same for the same seed and path,
+    /// the secret key is the BLAKE2b of both
+    pub fn derive(seed: &[u8; 32], path: &str) -> KeyPair {
+        KeyPair::from_secret(derive_secret(seed, path))
+    }
+
+    pub fn public(&self) -> PublicKey {
+        self.public
+    }
+
+    /// the key agreed with the holder of the other key, which gets the same one: the BLAKE2b of
+    /// the X25519 shared secret and both public keys, the smaller first
+    /// an error if the other key is of low order, so the secret would be known to anyone
+    pub fn agree(&self, other: &PublicKey) -> Result<SecBuf, HolochainError> {
+        let mut shared = x25519(self.secret.as_key(), &other.0);
+        if shared == [0; 32] {
+            return Err(HolochainError::ErrorGeneric(
+                "no key can be agreed with a key of low order".to_string(),
+            ));
+        }
+        let (first, second) = if self.public.0 < other.0 {
+            (self.public.0, other.0)
+        } else {
+            (other.0, self.public.0)
+        };
+        let mut input = SecBuf::new(96);
+        input[..32].copy_from_slice(&shared);
+        input[32..64].copy_from_slice(&first);
+        input[64..].copy_from_slice(&second);
+        zeroize(&mut shared);
+        Ok(SecBuf::from_vec(blake2b(&input, 32)))
+    }
+}
+
+/// the Ed25519 public key of a key pair an agent signs with, see SigningKeyPair
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SigningPublicKey(pub [u8; 32]);
+
+impl SigningPublicKey {
+    /// the key in base58, an error if it isn't 32 bytes
+    pub fn from_base58(key: &str) -> Result<SigningPublicKey, HolochainError> {
+        match key.from_base58() {
+            Ok(ref bytes) if bytes.len() == 32 => {
+                let mut public = [0; 32];
+                public.copy_from_slice(bytes);
+                Ok(SigningPublicKey(public))
+            }
+            _ => Err(HolochainError::ErrorGeneric(format!(
+                "{} isn't an Ed25519 public key in base58",
+                key
+            ))),
+        }
+    }
+
+    pub fn to_base58(&self) -> String {
+        self.0.to_base58()
+    }
+
+    /// true if the signature is the holder's of the key pair for the message
+    /// verified strictly: signatures with s not reduced mod L, which anyone can make from a valid
+    /// one, and keys of small order are refused, so a message has one signature per key
+    pub fn verify(&self, message: &[u8], signature: &[u8]) -> bool {
+        match (
+            VerifyingKey::from_bytes(&self.0),
+            Signature::from_slice(signature),
+        ) {
+            (Ok(key), Ok(signature)) => key.verify_strict(message, &signature).is_ok(),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for SigningPublicKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SigningPublicKey({})", self.0.to_base58())
+    }
+}
+
+/// an Ed25519 key pair, its secret key is the 32 byte seed of RFC 8032
+#[derive(Clone)]
+pub struct SigningKeyPair {
+    public: SigningPublicKey,
+    secret: SecBuf,
+}
+
+impl PartialEq for SigningKeyPair {
+    fn eq(&self, other: &SigningKeyPair) -> bool {
+        self.public == other.public
+    }
+}
+
+/// only the public half shows
+impl fmt::Debug for SigningKeyPair {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SigningKeyPair")
+            .field("public", &self.public)
+            .finish()
+    }
+}
+
+impl SigningKeyPair {
+    /// a fresh random key pair
+    pub fn generate() -> SigningKeyPair {
+        SigningKeyPair::from_secret(SecBuf::random(32))
+    }
+
+    /// the key pair of an Ed25519 secret key
+    /// panics unless the secret key has 32 bytes
+    pub fn from_secret(secret: SecBuf) -> SigningKeyPair {
+        let public = SigningKey::from_bytes(secret.as_key()).verifying_key();
+        SigningKeyPair {
+            public: SigningPublicKey(public.to_bytes()),
+            secret,
+        }
+    }
+
+    /// the key pair derived from a seed along a path, see derive_secret()
+    pub fn derive(seed: &[u8; 32], path: &str) -> SigningKeyPair {
+        SigningKeyPair::from_secret(derive_secret(seed, path))
+    }
+
+    pub fn public(&self) -> SigningPublicKey {
+        self.public
+    }
+
+    /// the signature of the message, SIGNATURE_BYTES
+    pub fn sign(&self, message: &[u8]) -> Vec<u8> {
+        SigningKey::from_bytes(self.secret.as_key())
+            .sign(message)
+            .to_bytes()
+            .to_vec()
+    }
+}
+
+/// a 32 byte secret derived from a seed along a path, the BLAKE2b of both, the same for the same
+/// seed and path
+pub fn derive_secret(seed: &[u8], path: &str) -> SecBuf {
+    let mut input = SecBuf::new(seed.len() + path.len());
+    input[..seed.len()].copy_from_slice(seed);
+    input[seed.len()..].copy_from_slice(path.as_bytes());
+    SecBuf::from_vec(blake2b(&input, 32))
+}
+
+/// the message sealed to the given key
+pub fn seal(message: &[u8], to: &PublicKey) -> Vec<u8> {
+    seal_with(message, to, &KeyPair::generate())
+}
+
+/// the message sealed to the given key with the given one-off key pair
+fn seal_with(message: &[u8], to: &PublicKey, ephemeral: &KeyPair) -> Vec<u8> {
+    let nonce = seal_nonce(&ephemeral.public, to);
+    let key = box_key(&ephemeral.secret, to);
+    let mut sealed = ephemeral.public.0.to_vec();
+    sealed.extend(secretbox(message, &nonce, key.as_key()));
+    sealed
+}
+
+/// the message sealed to the key pair, an error if it was sealed to another key or changed on
+/// the way
+pub fn open(sealed: &[u8], keys: &KeyPair) -> Result<Vec<u8>, HolochainError> {
+    if sealed.len() < SEAL_BYTES {
+        return Err(unopenable());
+    }
+    let mut ephemeral = [0; 32];
+    ephemeral.copy_from_slice(&sealed[..32]);
+    let ephemeral = PublicKey(ephemeral);
+    let nonce = seal_nonce(&ephemeral, &keys.public);
+    let key = box_key(&keys.secret, &ephemeral);
+    secretbox_open(&sealed[32..], &nonce, key.as_key()).ok_or_else(unopenable)
+}
+
+/// the message encrypted with a secret key, XSalsa20-Poly1305 with a random nonce put in front
+/// as libsodium's crypto_secretbox_easy has it
+pub fn encrypt(message: &[u8], key: &[u8; 32]) -> Vec<u8> {
+    let nonce = rand::thread_rng().gen::<[u8; 24]>();
+    let mut encrypted = nonce.to_vec();
+    encrypted.extend(secretbox(message, &nonce, key));
+    encrypted
+}
+
+/// the message encrypted with the secret key, an error if it was encrypted with another key or
+/// changed since
+pub fn decrypt(encrypted: &[u8], key: &[u8; 32]) -> Result<Vec<u8>, HolochainError> {
+    if encrypted.len() < ENCRYPTION_BYTES {
+        return Err(undecryptable());
+    }
+    let mut nonce = [0; 24];
+    nonce.copy_from_slice(&encrypted[..24]);
+    secretbox_open(&encrypted[24..], &nonce, key).ok_or_else(undecryptable)
+}
+
+/// len bytes of XSalsa20 keyed with the BLAKE2b of the seed, the same for the same seed, and
+/// the bytes for a shorter len are the start of the ones for a longer one
+pub fn expand(seed: &[u8], len: usize) -> Vec<u8> {
+    let mut key = [0; 32];
+    key.copy_from_slice(&blake2b(seed, 32));
+    let mut bytes = vec![0; len];
+    XSalsa20::new(&key.into(), &[0; 24].into()).apply_keystream(&mut bytes);
+    zeroize(&mut key);
+    bytes
+}
+
+/// a key from a passphrase and a salt of at least 8 bytes, Argon2id with the given cost, so every
+/// guess at the passphrase takes as much memory and time
+/// an error if the cost or the salt are out of the bounds of Argon2
+pub fn stretch(
+    passphrase: &[u8],
+    salt: &[u8],
+    params: &StretchParams,
+) -> Result<SecBuf, HolochainError> {
+    let unstretchable = |e: argon2::Error| {
+        HolochainError::ErrorGeneric(format!("the passphrase can't be stretched: {}", e))
+    };
+    let params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(32),
+    ).map_err(unstretchable)?;
+    let mut key = SecBuf::new(32);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase, salt, &mut key[..])
+        .map_err(unstretchable)?;
+    Ok(key)
+}
+
+fn undecryptable() -> HolochainError {
+    HolochainError::ErrorGeneric("the message can't be decrypted with the key".to_string())
+}
+
+fn unopenable() -> HolochainError {
+    HolochainError::ErrorGeneric("the message can't be opened with the agent's key".to_string())
+}
+
+/// the nonce of a seal, BLAKE2b of the one-off and the recipient's public key
+fn seal_nonce(ephemeral: &PublicKey, to: &PublicKey) -> [u8; 24] {
+    let mut input = ephemeral.0.to_vec();
+    input.extend_from_slice(&to.0);
+    let mut nonce = [0; 24];
+    nonce.copy_from_slice(&blake2b(&input, 24));
+    nonce
+}
+
+/// the key of crypto_box between a secret and a public key, HSalsa20 of their X25519
+fn box_key(secret: &SecBuf, public: &PublicKey) -> SecBuf {
+    let mut shared = x25519(secret.as_key(), &public.0);
+    let mut key = hsalsa::<U10>(&shared.into(), &[0; 16].into());
+    let boxed = SecBuf::from_slice(&key);
+    zeroize(&mut shared);
+    zeroize(&mut key);
+    boxed
+}
+
+/// crypto_secretbox_easy: the Poly1305 tag, then the message encrypted with XSalsa20
+fn secretbox(message: &[u8], nonce: &[u8; 24], key: &[u8; 32]) -> Vec<u8> {
+    let mut cipher = message.to_vec();
+    let tag = XSalsa20Poly1305::new(key.into())
+        .encrypt_in_place_detached(nonce.into(), &[], &mut cipher)
+        .expect("XSalsa20-Poly1305 encrypts any message that fits in memory");
+    let mut boxed = tag.to_vec();
+    boxed.extend(cipher);
+    boxed
+}
+
+fn secretbox_open(boxed: &[u8], nonce: &[u8; 24], key: &[u8; 32]) -> Option<Vec<u8>> {
+    if boxed.len() < 16 {
+        return None;
+    }
+    let (tag, cipher) = boxed.split_at(16);
+    let mut message = cipher.to_vec();
+    XSalsa20Poly1305::new(key.into())
+        .decrypt_in_place_detached(nonce.into(), &[], &mut message, tag.into())
+        .ok()?;
+    Some(message)
+}
+
+/// the X25519 of a secret scalar and a public point, all zero for a point of low order
+fn x25519(scalar: &[u8; 32], point: &[u8; 32]) -> [u8; 32] {
+    StaticSecret::from(*scalar)
+        .diffie_hellman(&X25519PublicKey::from(*point))
+        .to_bytes()
+}
+
+/// the BLAKE2b of the input, len bytes of it, up to 64
+fn blake2b(input: &[u8], len: usize) -> Vec<u8> {
+    let mut hasher = Blake2bVar::new(len).expect("BLAKE2b outputs 1 to 64 bytes");
+    hasher.update(input);
+    let mut hash = vec![0; len];
+    hasher
+        .finalize_variable(&mut hash)
+        .expect("the output has the length the hasher was made for");
+    hash
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn unhex(hex: &str) -> Vec<u8> {
+        (0..hex.len() / 2)
+            .map(|i| u8::from_str_radix(&hex[2 * i..2 * i + 2], 16).unwrap())
+            .collect()
+    }
+
+    fn key(hex: &str) -> SecBuf {
+        SecBuf::from_vec(unhex(hex))
+    }
+
+    /// the key pairs of alice and bob in RFC 7748
+    fn test_alice() -> KeyPair {
+        KeyPair::from_secret(key(
+            "77076d0a7318a57d3c16c17251b26645df4c2f87ebc0992ab177fba51db92c2a",
+        ))
+    }
+
+    fn test_bob() -> KeyPair {
+        KeyPair::from_secret(key(
+            "5dab087e624a8a4b79e17f8b83800ee66f3bb1292618b6fd1c2f8b27ff88e0eb",
+        ))
+    }
+
+    #[test]
+    /// keys and the shared secret of RFC 7748, and the first key of the NaCl box tests
+    fn x25519_vectors() {
+        let alice = test_alice();
+        let bob = test_bob();
+        assert_eq!(
+            unhex("8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a"),
+            alice.public().0.to_vec()
+        );
+        assert_eq!(
+            unhex("de9edb7d7b7dc1b4d35b61c2ece435373f8343c85b78674dadfc7e146f882b4f"),
+            bob.public().0.to_vec()
+        );
+        let shared = x25519(alice.secret.as_key(), &bob.public().0);
+        assert_eq!(shared, x25519(bob.secret.as_key(), &alice.public().0));
+        assert_eq!(
+            unhex("4a5d9d5ba4ce2de1728e3bf480350f25e07e21c947d19e3376f09b3c1e161742"),
+            shared.to_vec()
+        );
+        assert_eq!(
+            unhex("1b27556473e985d462cd51197a9a46c76009549eac6474f206c4ee0844f68389"),
+            box_key(&alice.secret, &bob.public()).to_vec()
+        );
+    }
+
+    #[test]
+    /// the box of the NaCl box tests, opened again
+    fn secretbox_vector() {
+        let key = box_key(&test_alice().secret, &test_bob().public());
+        let mut nonce = [0; 24];
+        nonce.copy_from_slice(&unhex("69696ee955b62b73cd62bda875fc73d68219e0036b7a0b37"));
+        let message = unhex(
+            "be075fc53c81f2d5cf141316ebeb0c7b5228c52a4c62cbd44b66849b64244ffce5ecbaaf33bd751a1a\
+             c728d45e6c61296cdc3c01233561f41db66cce314adb310e3be8250c46f06dceea3a7fa1348057e2f6\
+             556ad6b1318a024a838f21af1fde048977eb48f59ffd4924ca1c60902e52f0a089bc76897040e082f9\
+             37763848645e0705",
+        );
+        let boxed = secretbox(&message, &nonce, key.as_key());
+        assert_eq!(
+            unhex("f3ffc7703f9400e52a7dfb4b3d3305d98e993b9f48681273c29650ba32fc76ce"),
+            boxed[..32].to_vec()
+        );
+        assert_eq!(Some(message), secretbox_open(&boxed, &nonce, key.as_key()));
+    }
+
+    #[test]
+    /// the BLAKE2b vector of RFC 7693
+    fn hash_vectors() {
+        assert_eq!(
+            unhex(
+                "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1\
+                 7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923"
+            ),
+            blake2b(b"abc", 64)
+        );
+    }
+
+    #[test]
+    /// seals are those of crypto_box_seal, so libsodium opens them
+    fn seal_vector() {
+        let sealed = seal_with(b"hello bob", &test_bob().public(), &test_alice());
+        assert_eq!(
+            unhex(
+                "8520f0098930a754748b7ddcb43ef75a0dbf3a0d26381af4eba4a98eaa9b4e6a\
+                 09c1676bacadc3129046534d6e7c4406cf62c0aa87d8613eb2"
+            ),
+            sealed
+        );
+        assert_eq!(b"hello bob".to_vec(), open(&sealed, &test_bob()).unwrap());
+    }
+
+    #[test]
+    /// only the key sealed to opens a seal, and only as it was sealed
+    fn seal_open() {
+        let bob = KeyPair::generate();
+        let message: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        let sealed = seal(&message, &bob.public());
+        assert_eq!(message.len() + SEAL_BYTES, sealed.len());
+        assert_ne!(sealed, seal(&message, &bob.public()));
+        assert_eq!(message, open(&sealed, &bob).unwrap());
+
+        assert!(open(&sealed, &KeyPair::generate()).is_err());
+        let mut changed = sealed.clone();
+        changed[SEAL_BYTES + 500] ^= 1;
+        assert!(open(&changed, &bob).is_err());
+        assert!(open(&sealed[..SEAL_BYTES - 1], &bob).is_err());
+        assert_eq!(Vec::<u8>::new(), open(&seal(&[], &bob.public()), &bob).unwrap());
+    }
+
+    #[test]
+    /// only the key encrypted with decrypts, and only as it was encrypted
+    fn encrypt_decrypt() {
+        let key = rand::thread_rng().gen::<[u8; 32]>();
+        let encrypted = encrypt(b"for the group", &key);
+        assert_eq!(13 + ENCRYPTION_BYTES, encrypted.len());
+        assert_ne!(encrypted, encrypt(b"for the group", &key));
+        assert_eq!(b"for the group".to_vec(), decrypt(&encrypted, &key).unwrap());
+
+        assert!(decrypt(&encrypted, &[0; 32]).is_err());
+        let mut changed = encrypted.clone();
+        changed[ENCRYPTION_BYTES] ^= 1;
+        assert!(decrypt(&changed, &key).is_err());
+        assert!(decrypt(&encrypted[..ENCRYPTION_BYTES - 1], &key).is_err());
+    }
+
+    #[test]
+    /// both sides agree on the same key, which no third key gets
+    fn derive_agree() {
+        let seed = [7; 32];
+        let alice = KeyPair::derive(&seed, "chat/bob");
+        assert_eq!(alice, KeyPair::derive(&seed, "chat/bob"));
+        assert_ne!(alice, KeyPair::derive(&seed, "chat/carol"));
+        assert_ne!(alice, KeyPair::derive(&[8; 32], "chat/bob"));
+
+        let bob = test_bob();
+        let key = alice.agree(&bob.public()).unwrap();
+        assert_eq!(key, bob.agree(&alice.public()).unwrap());
+        assert_ne!(key, KeyPair::generate().agree(&bob.public()).unwrap());
+        assert!(alice.agree(&PublicKey([0; 32])).is_err());
+
+        let public = alice.public();
+        assert_eq!(Ok(public), PublicKey::from_base58(&public.to_base58()));
+        assert!(PublicKey::from_base58("not base58!").is_err());
+        assert!(PublicKey::from_base58(&[1; 31].to_base58()).is_err());
+    }
+
+    #[test]
+    /// the first two vectors of RFC 8032
+    fn ed25519_vectors() {
+        let vectors = [
+            (
+                "9d61b19deffd5a60ba844af492ec2cc44449c5697b326919703bac031cae7f60",
+                "d75a980182b10ab7d54bfed3c964073a0ee172f3daa62325af021a68f707511a",
+                "",
+                "e5564300c360ac729086e2cc806e828a84877f1eb8e5d974d873e06522490155\
+                 5fb8821590a33bacc61e39701cf9b46bd25bf5f0595bbe24655141438e7a100b",
+            ),
+            (
+                "4ccd089b28ff96da9db6c346ec114e0f5b8a319f35aba624da8cf6ed4fb8a6fb",
+                "3d4017c3e843895a92b70aa74d1b7ebc9c982ccf2ec4968cc0cd55f12af4660c",
+                "72",
+                "92a009a9f0d4cab8720e820b5f642540a2b27b5416503f8fb3762223ebdb69da\
+                 085ac1e43e15996e458f3613d0f11d8c387b2eaeb4302aeeb00d291612bb0c00",
+            ),
+        ];
+        for (secret, public, message, signature) in vectors.iter() {
+            let keys = SigningKeyPair::from_secret(key(secret));
+            assert_eq!(unhex(public), keys.public().0.to_vec());
+            let message = unhex(message);
+            assert_eq!(unhex(signature), keys.sign(&message));
+            assert!(keys.public().verify(&message, &unhex(signature)));
+        }
+    }
+
+    #[test]
+    /// signatures only check out for the message and key they were made with
+    fn sign_verify() {
+        let alice = SigningKeyPair::derive(&[7; 32], "root");
+        assert_eq!(alice, SigningKeyPair::derive(&[7; 32], "root"));
+        assert_ne!(alice, SigningKeyPair::derive(&[7; 32], "device/laptop"));
+        let signature = alice.sign(b"hello");
+        assert_eq!(SIGNATURE_BYTES, signature.len());
+        assert!(alice.public().verify(b"hello", &signature));
+        assert!(!alice.public().verify(b"hullo", &signature));
+        assert!(!SigningKeyPair::generate().public().verify(b"hello", &signature));
+        let mut forged = signature.clone();
+        forged[10] ^= 1;
+        assert!(!alice.public().verify(b"hello", &forged));
+        assert!(!alice.public().verify(b"hello", &signature[..63]));
+
+        let public = alice.public();
+        assert_eq!(Ok(public), SigningPublicKey::from_base58(&public.to_base58()));
+        assert!(SigningPublicKey::from_base58("not base58!").is_err());
+        assert!(!format!("{:?}", alice).contains("secret"));
+    }
+
+    #[test]
+    /// a valid signature with L added to s is refused, as are keys of small order
+    fn malleability() {
+        // the order of the base point, little endian
+        let l = unhex("edd3f55c1a631258d69cf7a2def9de1400000000000000000000000000000010");
+        let alice = SigningKeyPair::derive(&[7; 32], "root");
+        let signature = alice.sign(b"hello");
+        let mut malleated = signature.clone();
+        let mut carry = 0;
+        for i in 0..32 {
+            let sum = u16::from(signature[32 + i]) + u16::from(l[i]) + carry;
+            malleated[32 + i] = sum as u8;
+            carry = sum >> 8;
+        }
+        assert_eq!(0, carry);
+        assert!(alice.public().verify(b"hello", &signature));
+        assert!(!alice.public().verify(b"hello", &malleated));
+
+        // the identity verifies any message with the signature (identity, 0) unless refused
+        let mut identity = [0; 32];
+        identity[0] = 1;
+        let mut forged = identity.to_vec();
+        forged.extend_from_slice(&[0; 32]);
+        assert!(!SigningPublicKey(identity).verify(b"hello", &forged));
+    }
+
+    #[test]
+    /// the same seed stretches into the same bytes
+    fn expand_seed() {
+        let bytes = expand(b"round 1", 100);
+        assert_eq!(100, bytes.len());
+        assert_eq!(bytes, expand(b"round 1", 100));
+        assert_eq!(bytes[..10].to_vec(), expand(b"round 1", 10));
+        assert_ne!(bytes, expand(b"round 2", 100));
+        assert!(expand(b"", 0).is_empty());
+    }
+
+    /// a cheap cost of stretching a passphrase, to keep tests quick
+    pub fn test_stretch_params() -> StretchParams {
+        StretchParams {
+            memory_kib: 64,
+            iterations: 1,
+            parallelism: 1,
+        }
+    }
+
+    #[test]
+    /// passphrases stretch into the same key with the same salt and cost only
+    fn stretch_passphrase() {
+        let params = test_stretch_params();
+        let key = stretch(b"correct horse", b"saltsalt", &params).unwrap();
+        assert_eq!(32, key.len());
+        assert_eq!(key, stretch(b"correct horse", b"saltsalt", &params).unwrap());
+        assert_ne!(key, stretch(b"correct horse", b"pepperpepper", &params).unwrap());
+        assert_ne!(key, stretch(b"battery staple", b"saltsalt", &params).unwrap());
+        let costlier = StretchParams {
+            iterations: 2,
+            ..params
+        };
+        assert_ne!(key, stretch(b"correct horse", b"saltsalt", &costlier).unwrap());
+
+        assert!(stretch(b"correct horse", b"salt", &params).is_err());
+        let free = StretchParams {
+            memory_kib: 0,
+            ..params
+        };
+        assert!(stretch(b"correct horse", b"saltsalt", &free).is_err());
+    }
+}
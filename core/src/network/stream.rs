@@ -0,0 +1,498 @@
+//! chunked streaming of large entries for the DHT fetch protocol
+//! content is split into fixed size chunks that are each hashed in a ChunkManifest so that the
+//! receiving side can verify every chunk as it arrives and write it straight out to a sink,
+//! neither side ever holds the whole entry in memory
+//! the assembled content is finally checked against the entry address, which binds the
+//! (untrusted) manifest to what was actually asked for
+
+use error::HolochainError;
+use hash::{bytes_to_b58_hash, serializable_to_b58_hash};
+use multihash::Hash;
+use rust_base58::ToBase58;
+use serde_json;
+use sha2::{Digest, Sha256};
+use std::io::{Read, Seek, SeekFrom, Write};
+
+/// default number of bytes per chunk
+pub const STREAM_DEFAULT_CHUNK_SIZE: usize = 64 * 1024;
+/// largest chunk a manifest may ask for, chunks are held in memory one at a time
+pub const STREAM_MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+/// multihash prefix for a SHA2-256 digest, entry addresses are b58 encoded SHA2-256 multihashes
+const SHA2256_MULTIHASH_PREFIX: [u8; 2] = [0x12, 0x20];
+
+/// fill buf from reader, returning fewer bytes than buf.len() only at the end of the reader
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize, HolochainError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) => return Err(HolochainError::new(&e.to_string())),
+        }
+    }
+    Ok(filled)
+}
+
+/// describes how the content at an address is split into chunks
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChunkManifest {
+    pub address: String,
+    /// total size of the content in bytes
+    pub size: u64,
+    pub chunk_size: usize,
+    /// hash of every chunk in order
+    pub chunk_hashes: Vec<String>,
+}
+
+impl ChunkManifest {
+    /// build a manifest by reading through the content once, one chunk at a time
+    pub fn from_reader<R: Read>(
+        address: &str,
+        reader: &mut R,
+        chunk_size: usize,
+    ) -> Result<ChunkManifest, HolochainError> {
+        let chunk_size = chunk_size.clamp(1, STREAM_MAX_CHUNK_SIZE);
+        let mut buf = vec![0; chunk_size];
+        let mut size = 0;
+        let mut chunk_hashes = Vec::new();
+        loop {
+            let n = read_full(reader, &mut buf)?;
+            if n == 0 {
+                break;
+            }
+            size += n as u64;
+            chunk_hashes.push(bytes_to_b58_hash(&buf[..n], Hash::SHA2256));
+            if n < chunk_size {
+                break;
+            }
+        }
+        Ok(ChunkManifest {
+            address: address.to_string(),
+            size,
+            chunk_size,
+            chunk_hashes,
+        })
+    }
+
+    pub fn chunk_count(&self) -> usize {
+        self.chunk_hashes.len()
+    }
+
+    /// check a manifest received from a peer is well formed before any chunk is sized by it:
+    /// the chunk size is non zero and bounded, and there is a hash for every chunk of the size
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.chunk_size == 0 || self.chunk_size > STREAM_MAX_CHUNK_SIZE {
+            return Err(HolochainError::new(&format!(
+                "manifest for {} has a chunk size of {}",
+                self.address, self.chunk_size
+            )));
+        }
+        if self.chunk_count() as u64 != self.size.div_ceil(self.chunk_size as u64) {
+            return Err(HolochainError::new(&format!(
+                "manifest for {} has {} chunks for {} bytes in chunks of {}",
+                self.address,
+                self.chunk_count(),
+                self.size,
+                self.chunk_size
+            )));
+        }
+        Ok(())
+    }
+
+    /// expected length of the chunk at index, 0 past the end of the content
+    fn chunk_len(&self, index: usize) -> usize {
+        let offset = index as u64 * self.chunk_size as u64;
+        self.size
+            .checked_sub(offset)
+            .map_or(0, |rest| rest.min(self.chunk_size as u64) as usize)
+    }
+
+    /// hash of the manifest itself, used to make sure a resumed transfer is of the same content
+    pub fn hash(&self) -> String {
+        serializable_to_b58_hash(self, Hash::SHA2256)
+    }
+}
+
+/// a single piece of the content at an address
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub address: String,
+    pub index: usize,
+    pub data: Vec<u8>,
+}
+
+/// read a single chunk out of the content, e.g. to answer a GetChunk request
+pub fn read_chunk<R: Read + Seek>(
+    reader: &mut R,
+    manifest: &ChunkManifest,
+    index: usize,
+) -> Result<Chunk, HolochainError> {
+    if index >= manifest.chunk_count() {
+        return Err(HolochainError::new(&format!(
+            "chunk {} out of range for {}",
+            index, manifest.address
+        )));
+    }
+    reader
+        .seek(SeekFrom::Start(index as u64 * manifest.chunk_size as u64))
+        .map_err(|e| HolochainError::new(&e.to_string()))?;
+    let mut data = vec![0; manifest.chunk_len(index)];
+    let n = read_full(reader, &mut data)?;
+    data.truncate(n);
+    Ok(Chunk {
+        address: manifest.address.clone(),
+        index,
+        data,
+    })
+}
+
+/// everything needed to pick a transfer back up where it left off after a dropped connection
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ResumeToken {
+    pub address: String,
+    pub manifest_hash: String,
+    /// index of the first chunk not yet received
+    pub next_index: usize,
+}
+
+impl ResumeToken {
+    /// opaque string form of the token, e.g. for persisting alongside a partial download
+    pub fn to_token_string(&self) -> String {
+        serde_json::to_string(self).unwrap().as_bytes().to_base58()
+    }
+
+    pub fn from_token_string(s: &str) -> Result<ResumeToken, HolochainError> {
+        use rust_base58::FromBase58;
+        let bytes = s
+            .from_base58()
+            .map_err(|_| HolochainError::new("resume token is not valid base58"))?;
+        serde_json::from_slice(&bytes).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+}
+
+/// messages of the chunked DHT fetch protocol
+/// @TODO send these over the network once there is a transport
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum StreamMessage {
+    GetManifest(String),
+    Manifest(ChunkManifest),
+    GetChunk { address: String, index: usize },
+    Chunk(Chunk),
+    NotFound(String),
+}
+
+/// receiving side of a chunked transfer
+/// chunks must arrive in order, each is verified against the manifest before being written to
+/// the sink
+pub struct ChunkReceiver<W: Write> {
+    manifest: ChunkManifest,
+    sink: W,
+    next_index: usize,
+    /// running hash of everything received, checked against the address at the end
+    hasher: Sha256,
+}
+
+impl<W: Write> ChunkReceiver<W> {
+    /// a receiver for the content of the manifest, an error if the manifest is malformed
+    pub fn new(manifest: ChunkManifest, sink: W) -> Result<ChunkReceiver<W>, HolochainError> {
+        manifest.check()?;
+        Ok(ChunkReceiver {
+            manifest,
+            sink,
+            next_index: 0,
+            hasher: Sha256::default(),
+        })
+    }
+
+    /// pick up a transfer from a ResumeToken
+    /// the content received so far has to be read back to restore the running hash, it is
+    /// checked against the manifest as it is read
+    pub fn resume<R: Read>(
+        manifest: ChunkManifest,
+        token: &ResumeToken,
+        received: &mut R,
+        sink: W,
+    ) -> Result<ChunkReceiver<W>, HolochainError> {
+        manifest.check()?;
+        if token.address != manifest.address || token.manifest_hash != manifest.hash() {
+            return Err(HolochainError::new(
+                "resume token does not match the manifest",
+            ));
+        }
+        if token.next_index > manifest.chunk_count() {
+            return Err(HolochainError::new("resume token is past the last chunk"));
+        }
+
+        let mut receiver = ChunkReceiver::new(manifest, sink)?;
+        for index in 0..token.next_index {
+            let mut data = vec![0; receiver.manifest.chunk_len(index)];
+            let n = read_full(received, &mut data)?;
+            if n != data.len()
+                || bytes_to_b58_hash(&data, Hash::SHA2256) != receiver.manifest.chunk_hashes[index]
+            {
+                return Err(HolochainError::new(&format!(
+                    "previously received chunk {} is missing or corrupt",
+                    index
+                )));
+            }
+            receiver.hasher.input(&data);
+        }
+        receiver.next_index = token.next_index;
+        Ok(receiver)
+    }
+
+    /// the request for the next chunk, None once everything has been received
+    pub fn next_request(&self) -> Option<StreamMessage> {
+        if self.is_complete() {
+            None
+        } else {
+            Some(StreamMessage::GetChunk {
+                address: self.manifest.address.clone(),
+                index: self.next_index,
+            })
+        }
+    }
+
+    /// verify a chunk and write it to the sink
+    pub fn receive(&mut self, chunk: &Chunk) -> Result<(), HolochainError> {
+        if chunk.address != self.manifest.address {
+            return Err(HolochainError::new(&format!(
+                "chunk for {} received while fetching {}",
+                chunk.address, self.manifest.address
+            )));
+        }
+        if chunk.index != self.next_index {
+            return Err(HolochainError::new(&format!(
+                "expected chunk {} but received chunk {}",
+                self.next_index, chunk.index
+            )));
+        }
+        if chunk.data.len() != self.manifest.chunk_len(chunk.index)
+            || bytes_to_b58_hash(&chunk.data, Hash::SHA2256)
+                != self.manifest.chunk_hashes[chunk.index]
+        {
+            return Err(HolochainError::new(&format!(
+                "chunk {} failed verification",
+                chunk.index
+            )));
+        }
+
+        self.sink
+            .write_all(&chunk.data)
+            .map_err(|e| HolochainError::new(&e.to_string()))?;
+        self.hasher.input(&chunk.data);
+        self.next_index += 1;
+        Ok(())
+    }
+
+    /// token to resume from if the connection drops now
+    pub fn resume_token(&self) -> ResumeToken {
+        ResumeToken {
+            address: self.manifest.address.clone(),
+            manifest_hash: self.manifest.hash(),
+            next_index: self.next_index,
+        }
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.next_index >= self.manifest.chunk_count()
+    }
+
+    /// check the assembled content hashes to the address and hand back the sink
+    pub fn finish(mut self) -> Result<W, HolochainError> {
+        if !self.is_complete() {
+            return Err(HolochainError::new(&format!(
+                "transfer of {} finished early at chunk {} of {}",
+                self.manifest.address,
+                self.next_index,
+                self.manifest.chunk_count()
+            )));
+        }
+        let mut multihash = SHA2256_MULTIHASH_PREFIX.to_vec();
+        multihash.extend_from_slice(&self.hasher.result());
+        if multihash.to_base58() != self.manifest.address {
+            return Err(HolochainError::new(&format!(
+                "content received for {} does not match its address",
+                self.manifest.address
+            )));
+        }
+        self.sink
+            .flush()
+            .map_err(|e| HolochainError::new(&e.to_string()))?;
+        Ok(self.sink)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{read_chunk, ChunkManifest, ChunkReceiver, ResumeToken, StreamMessage};
+    use hash_table::entry::Entry;
+    use std::io::Cursor;
+
+    /// dummy large entry content
+    pub fn test_stream_content() -> String {
+        (0..100).map(|i| format!("line {}\n", i)).collect()
+    }
+
+    /// dummy manifest for test_stream_content() in small chunks
+    pub fn test_manifest() -> ChunkManifest {
+        let content = test_stream_content();
+        let address = Entry::new("testEntryType", &content).key();
+        ChunkManifest::from_reader(&address, &mut Cursor::new(content.as_bytes()), 64).unwrap()
+    }
+
+    /// drive a receiver to completion from the content
+    fn transfer(receiver: &mut ChunkReceiver<Vec<u8>>) {
+        let mut source = Cursor::new(test_stream_content().into_bytes());
+        let manifest = test_manifest();
+        while let Some(StreamMessage::GetChunk { index, .. }) = receiver.next_request() {
+            let chunk = read_chunk(&mut source, &manifest, index).unwrap();
+            receiver.receive(&chunk).unwrap();
+        }
+    }
+
+    #[test]
+    /// manifests cover all the content
+    fn manifest() {
+        let manifest = test_manifest();
+        let len = test_stream_content().len();
+        assert_eq!(len as u64, manifest.size);
+        // the last chunk is partial
+        assert!(64 * (manifest.chunk_count() - 1) < len);
+        assert!(len < 64 * manifest.chunk_count());
+
+        let empty = ChunkManifest::from_reader("", &mut Cursor::new(Vec::new()), 64).unwrap();
+        assert_eq!(0, empty.chunk_count());
+    }
+
+    #[test]
+    /// content round trips through chunks and is checked against the address
+    fn round_trip() {
+        let mut receiver = ChunkReceiver::new(test_manifest(), Vec::new()).unwrap();
+        transfer(&mut receiver);
+        assert!(receiver.is_complete());
+        assert_eq!(
+            test_stream_content().into_bytes(),
+            receiver.finish().unwrap()
+        );
+    }
+
+    #[test]
+    /// corrupt, out of order and foreign chunks are rejected
+    fn bad_chunks() {
+        let manifest = test_manifest();
+        let mut source = Cursor::new(test_stream_content().into_bytes());
+        let mut receiver = ChunkReceiver::new(manifest.clone(), Vec::new()).unwrap();
+
+        let mut chunk = read_chunk(&mut source, &manifest, 0).unwrap();
+        chunk.data[0] ^= 0xff;
+        assert!(receiver.receive(&chunk).is_err());
+
+        let chunk = read_chunk(&mut source, &manifest, 1).unwrap();
+        assert!(receiver.receive(&chunk).is_err());
+
+        let mut chunk = read_chunk(&mut source, &manifest, 0).unwrap();
+        chunk.address = "foo".to_string();
+        assert!(receiver.receive(&chunk).is_err());
+
+        assert!(read_chunk(&mut source, &manifest, manifest.chunk_count()).is_err());
+        assert!(receiver.finish().is_err());
+    }
+
+    #[test]
+    /// a manifest whose chunks hash fine but are not the content at the address is caught
+    fn wrong_address() {
+        let mut manifest = test_manifest();
+        manifest.address = Entry::new("testEntryType", "other").key();
+        let mut source = Cursor::new(test_stream_content().into_bytes());
+        let mut receiver = ChunkReceiver::new(manifest.clone(), Vec::new()).unwrap();
+        for index in 0..manifest.chunk_count() {
+            let chunk = read_chunk(&mut source, &manifest, index).unwrap();
+            receiver.receive(&chunk).unwrap();
+        }
+        assert!(receiver.finish().is_err());
+    }
+
+    #[test]
+    /// transfers can pick up where they left off
+    fn resume() {
+        let manifest = test_manifest();
+        let mut source = Cursor::new(test_stream_content().into_bytes());
+        let mut receiver = ChunkReceiver::new(manifest.clone(), Vec::new()).unwrap();
+        for index in 0..3 {
+            let chunk = read_chunk(&mut source, &manifest, index).unwrap();
+            receiver.receive(&chunk).unwrap();
+        }
+
+        // connection drops, all we have is the token and the partial content
+        let token = ResumeToken::from_token_string(&receiver.resume_token().to_token_string())
+            .unwrap();
+        assert_eq!(3, token.next_index);
+        assert!(receiver.finish().is_err());
+
+        let received = test_stream_content().into_bytes()[..3 * 64].to_vec();
+        let mut resumed = ChunkReceiver::resume(
+            manifest.clone(),
+            &token,
+            &mut Cursor::new(received.clone()),
+            received,
+        ).unwrap();
+        transfer(&mut resumed);
+        assert_eq!(
+            test_stream_content().into_bytes(),
+            resumed.finish().unwrap()
+        );
+
+        // tokens only resume the transfer they came from
+        let mut other = manifest.clone();
+        other.chunk_size = 32;
+        assert!(
+            ChunkReceiver::resume(other, &token, &mut Cursor::new(Vec::new()), Vec::new())
+                .is_err()
+        );
+
+        // and the partial content has to check out
+        assert!(
+            ChunkReceiver::resume(manifest, &token, &mut Cursor::new(vec![0; 3 * 64]), Vec::new())
+                .is_err()
+        );
+    }
+
+    #[test]
+    /// manifests with a chunk size or chunk count at odds with their size are refused before
+    /// any chunk is sized by them
+    fn bad_manifests() {
+        let manifest = test_manifest();
+        assert_eq!(Ok(()), manifest.check());
+        let empty = ChunkManifest::from_reader("", &mut Cursor::new(Vec::new()), 64).unwrap();
+        assert_eq!(Ok(()), empty.check());
+
+        let mut zero = manifest.clone();
+        zero.chunk_size = 0;
+        let mut huge = manifest.clone();
+        huge.chunk_size = usize::MAX;
+        // more chunks than the size has room for, their lengths would underflow
+        let mut extra = manifest.clone();
+        extra.chunk_hashes.push(manifest.chunk_hashes[0].clone());
+        let mut missing = manifest.clone();
+        missing.chunk_hashes.pop();
+        let mut oversized = manifest.clone();
+        oversized.size = u64::MAX;
+
+        for bad in &[zero, huge, extra, missing, oversized] {
+            assert!(bad.check().is_err());
+            assert!(ChunkReceiver::new(bad.clone(), Vec::new()).is_err());
+            let token = ResumeToken {
+                address: bad.address.clone(),
+                manifest_hash: bad.hash(),
+                next_index: bad.chunk_count(),
+            };
+            let received = test_stream_content().into_bytes();
+            assert!(
+                ChunkReceiver::resume(bad.clone(), &token, &mut Cursor::new(received), Vec::new())
+                    .is_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,479 @@
+//! envelopes of direct messages in the protocol buffers format of proto/network.proto, which is
+//! what nodes written in other languages implement
+//! the message types are generated from the schema by prost-build, see build.rs, what is here
+//! converts between them and DirectMessage
+//! every message of DirectMessage has a member in the Envelope oneof, the aspects, validations
+//! and signal payloads in them going as their JSON
+//! envelopes go between nodes sealed to the key of the agent they are for, see sealing, in a
+//! Sealed message with the protobuf bytes of the envelope as its sealed payload
+//! envelopes from nodes of later schema revisions are read as far as this revision knows them,
+//! fields it doesn't know are skipped and a message it doesn't know at all is an error
+
+use self::proto::{call_remote_result, envelope::Message as Kind, validation_package};
+use agent::{
+    groups::GroupSecret, secbuf::{zeroize, SecBuf},
+};
+use dht::aspect::Aspect;
+use error::HolochainError;
+use network::{
+    direct_message::{DirectMessage, RemoteCall}, remote_signal::RemoteSignal, sealing::Sealed,
+    Envelope,
+};
+use prost::Message;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
+use std::fmt::Display;
+use trace::TraceContext;
+use validation::{packages::Rejection, receipts::ValidationReceipt};
+
+/// the messages of proto/network.proto, as prost-build generates them
+#[allow(clippy::all)]
+mod proto {
+    include!(concat!(env!("OUT_DIR"), "/holochain.network.rs"));
+}
+
+/// the revision of proto/network.proto this is of
+pub const SCHEMA_REVISION: u32 = 5;
+
+fn to_error<E: Display>(error: E) -> HolochainError {
+    HolochainError::ErrorGeneric(format!("malformed envelope: {}", error))
+}
+
+/// the protobuf bytes of an envelope
+pub fn encode(envelope: &Envelope<DirectMessage>) -> Result<Vec<u8>, HolochainError> {
+    let mut envelope = to_proto(envelope)?;
+    let bytes = envelope.encode_to_vec();
+    if let Some(Kind::GroupSecret(ref mut secret)) = envelope.message {
+        zeroize(&mut secret.key);
+    }
+    Ok(bytes)
+}
+
+/// the envelope in protobuf bytes
+pub fn decode(bytes: &[u8]) -> Result<Envelope<DirectMessage>, HolochainError> {
+    from_proto(proto::Envelope::decode(bytes).map_err(to_error)?)
+}
+
+/// the protobuf bytes of a sealed message
+pub fn encode_sealed(sealed: &Sealed) -> Vec<u8> {
+    proto::Sealed {
+        to: sealed.to.clone(),
+        payload: sealed.payload.clone(),
+    }.encode_to_vec()
+}
+
+/// the sealed message in protobuf bytes
+pub fn decode_sealed(bytes: &[u8]) -> Result<Sealed, HolochainError> {
+    let sealed = proto::Sealed::decode(bytes).map_err(to_error)?;
+    Ok(Sealed {
+        to: sealed.to,
+        payload: sealed.payload,
+    })
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, HolochainError> {
+    serde_json::to_string(value).map_err(to_error)
+}
+
+fn from_json<T: DeserializeOwned>(json: &str) -> Result<T, HolochainError> {
+    serde_json::from_str(json).map_err(to_error)
+}
+
+/// the value in JSON, its default when the field was left out
+fn from_json_or_default<T: DeserializeOwned + Default>(json: &str) -> Result<T, HolochainError> {
+    if json.is_empty() {
+        Ok(T::default())
+    } else {
+        from_json(json)
+    }
+}
+
+fn to_aspects(aspects: &[Aspect]) -> Result<Vec<String>, HolochainError> {
+    aspects.iter().map(to_json).collect()
+}
+
+fn from_aspects(aspects: &[String]) -> Result<Vec<Aspect>, HolochainError> {
+    aspects.iter().map(|aspect| from_json(aspect)).collect()
+}
+
+fn to_proto(envelope: &Envelope<DirectMessage>) -> Result<proto::Envelope, HolochainError> {
+    let message = match envelope.message {
+        DirectMessage::CallRemote(ref call) => Kind::CallRemote(proto::CallRemote {
+            id: call.id.clone(),
+            from: call.from.clone(),
+            zome: call.zome.clone(),
+            capability: call.capability.clone(),
+            function: call.function.clone(),
+            cap_secret: call.cap_secret.clone(),
+            parameters: call.parameters.clone(),
+        }),
+        DirectMessage::CallRemoteResult(ref id, ref result) => {
+            Kind::CallRemoteResult(proto::CallRemoteResult {
+                id: id.clone(),
+                result: Some(match *result {
+                    Ok(ref ok) => call_remote_result::Result::Ok(ok.clone()),
+                    Err(ref err) => call_remote_result::Result::Err(err.clone()),
+                }),
+            })
+        }
+        DirectMessage::GetAspects {
+            ref id,
+            ref from,
+            ref address,
+        } => Kind::GetAspects(proto::GetAspects {
+            id: id.clone(),
+            from: from.clone(),
+            address: address.clone(),
+        }),
+        DirectMessage::GetAspectsResult(ref id, ref aspects) => {
+            Kind::GetAspectsResult(proto::GetAspectsResult {
+                id: id.clone(),
+                aspects: to_aspects(aspects)?,
+            })
+        }
+        DirectMessage::Publish {
+            ref from,
+            ref address,
+            ref aspects,
+        } => Kind::Publish(proto::Publish {
+            from: from.clone(),
+            address: address.clone(),
+            aspects: to_aspects(aspects)?,
+        }),
+        DirectMessage::ValidationReceipt(ref receipt) => {
+            Kind::ValidationReceipt(proto::ValidationReceipt {
+                address: receipt.address.clone(),
+                validator: receipt.validator.clone(),
+                validation: to_json(&receipt.validation)?,
+                timestamp: receipt.timestamp,
+            })
+        }
+        DirectMessage::RemoteSignals(ref signals) => Kind::RemoteSignals(proto::RemoteSignals {
+            signals: signals
+                .iter()
+                .map(|signal| proto::RemoteSignal {
+                    from: signal.from.clone(),
+                    zome: signal.zome.clone(),
+                    payload: signal.payload.to_string(),
+                })
+                .collect(),
+        }),
+        DirectMessage::Heartbeat(ref address) => Kind::Heartbeat(address.clone()),
+        DirectMessage::Goodbye(ref address) => Kind::Goodbye(address.clone()),
+        DirectMessage::Pruned {
+            ref from,
+            ref address,
+        } => Kind::Pruned(proto::Pruned {
+            from: from.clone(),
+            address: address.clone(),
+        }),
+        DirectMessage::GroupSecret(ref secret) => Kind::GroupSecret(proto::GroupSecret {
+            id: secret.id.clone(),
+            creator: secret.creator.clone(),
+            members: secret.members.clone(),
+            key: secret.key.to_vec(),
+        }),
+        DirectMessage::GetValidationPackage { ref id, ref from } => {
+            Kind::GetValidationPackage(proto::GetValidationPackage {
+                id: id.clone(),
+                from: from.clone(),
+            })
+        }
+        DirectMessage::ValidationPackage(ref id, ref package) => {
+            Kind::ValidationPackage(proto::ValidationPackage {
+                id: id.clone(),
+                result: Some(match *package {
+                    Ok(ref package) => validation_package::Result::Package(to_json(package)?),
+                    Err(ref err) => validation_package::Result::Err(err.clone()),
+                }),
+            })
+        }
+        DirectMessage::AgentRejected(ref rejection) => {
+            Kind::AgentRejected(proto::AgentRejected {
+                validator: rejection.validator.clone(),
+                reason: rejection.reason.clone(),
+            })
+        }
+    };
+    Ok(proto::Envelope {
+        trace: envelope.trace.as_ref().map(|trace| proto::TraceContext {
+            trace_id: trace.trace_id.clone(),
+            span_id: trace.span_id.clone(),
+        }),
+        message: Some(message),
+    })
+}
+
+fn from_proto(envelope: proto::Envelope) -> Result<Envelope<DirectMessage>, HolochainError> {
+    let message = match envelope.message {
+        Some(message) => message,
+        None => {
+            return Err(to_error(
+                "the envelope carries no message of a kind this node knows",
+            ))
+        }
+    };
+    let message = match message {
+        Kind::CallRemote(call) => DirectMessage::CallRemote(RemoteCall {
+            id: call.id,
+            from: call.from,
+            zome: call.zome,
+            capability: call.capability,
+            function: call.function,
+            cap_secret: call.cap_secret,
+            parameters: call.parameters,
+        }),
+        Kind::CallRemoteResult(result) => {
+            let outcome = match result.result {
+                Some(call_remote_result::Result::Ok(ok)) => Ok(ok),
+                Some(call_remote_result::Result::Err(err)) => Err(err),
+                None => return Err(to_error("the remote call result has no result")),
+            };
+            DirectMessage::CallRemoteResult(result.id, outcome)
+        }
+        Kind::GetAspects(get) => DirectMessage::GetAspects {
+            id: get.id,
+            from: get.from,
+            address: get.address,
+        },
+        Kind::GetAspectsResult(result) => {
+            DirectMessage::GetAspectsResult(result.id, from_aspects(&result.aspects)?)
+        }
+        Kind::Publish(publish) => DirectMessage::Publish {
+            aspects: from_aspects(&publish.aspects)?,
+            from: publish.from,
+            address: publish.address,
+        },
+        Kind::ValidationReceipt(receipt) => DirectMessage::ValidationReceipt(ValidationReceipt {
+            validation: from_json_or_default(&receipt.validation)?,
+            address: receipt.address,
+            validator: receipt.validator,
+            timestamp: receipt.timestamp,
+        }),
+        Kind::RemoteSignals(signals) => DirectMessage::RemoteSignals(
+            signals
+                .signals
+                .into_iter()
+                .map(|signal| {
+                    Ok(RemoteSignal {
+                        payload: from_json_or_default(&signal.payload)?,
+                        from: signal.from,
+                        zome: signal.zome,
+                    })
+                })
+                .collect::<Result<_, HolochainError>>()?,
+        ),
+        Kind::Heartbeat(address) => DirectMessage::Heartbeat(address),
+        Kind::Goodbye(address) => DirectMessage::Goodbye(address),
+        Kind::Pruned(pruned) => DirectMessage::Pruned {
+            from: pruned.from,
+            address: pruned.address,
+        },
+        Kind::GroupSecret(mut secret) => {
+            if secret.key.len() != 32 {
+                zeroize(&mut secret.key);
+                return Err(to_error("a group secret key has 32 bytes"));
+            }
+            DirectMessage::GroupSecret(GroupSecret {
+                id: secret.id,
+                creator: secret.creator,
+                members: secret.members,
+                key: SecBuf::from_vec(secret.key),
+            })
+        }
+        Kind::GetValidationPackage(get) => DirectMessage::GetValidationPackage {
+            id: get.id,
+            from: get.from,
+        },
+        Kind::ValidationPackage(package) => {
+            let result = match package.result {
+                Some(validation_package::Result::Package(package)) => Ok(from_json(&package)?),
+                Some(validation_package::Result::Err(err)) => Err(err),
+                None => Err(String::new()),
+            };
+            DirectMessage::ValidationPackage(package.id, result)
+        }
+        Kind::AgentRejected(rejection) => DirectMessage::AgentRejected(Rejection {
+            validator: rejection.validator,
+            reason: rejection.reason,
+        }),
+    };
+    Ok(Envelope {
+        trace: envelope.trace.map(|trace| TraceContext {
+            trace_id: trace.trace_id,
+            span_id: trace.span_id,
+        }),
+        message,
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use agent::groups::tests::test_group_secret;
+    use dht::HoldingValidation;
+    use hash_table::entry::tests::test_entry;
+    use holochain_dna::Dna;
+    use network::{
+        direct_message::tests::test_remote_call, sealing::{self, KeyPair},
+    };
+    use trace::tests::test_trace_context;
+    use validation::{packages::tests::test_package, receipts::tests::test_receipt};
+
+    /// one message of every kind
+    fn test_messages() -> Vec<DirectMessage> {
+        let secret = RemoteCall {
+            cap_secret: Some(String::new()),
+            ..test_remote_call()
+        };
+        vec![
+            DirectMessage::CallRemote(test_remote_call()),
+            DirectMessage::CallRemote(secret),
+            DirectMessage::CallRemoteResult("1".to_string(), Ok(String::new())),
+            DirectMessage::CallRemoteResult("2".to_string(), Err("refused".to_string())),
+            DirectMessage::GetAspects {
+                id: "3".to_string(),
+                from: "alice".to_string(),
+                address: test_entry().key(),
+            },
+            DirectMessage::GetAspectsResult(
+                "3".to_string(),
+                vec![Aspect::Content(test_entry()), Aspect::Delete],
+            ),
+            DirectMessage::Publish {
+                from: "alice".to_string(),
+                address: test_entry().key(),
+                aspects: vec![Aspect::Content(test_entry())],
+            },
+            DirectMessage::ValidationReceipt(ValidationReceipt {
+                validation: HoldingValidation::Invalid("too long".to_string()),
+                ..test_receipt("bob")
+            }),
+            DirectMessage::RemoteSignals(vec![RemoteSignal {
+                from: "alice".to_string(),
+                zome: "chat".to_string(),
+                payload: json!({"typing": true}),
+            }]),
+            DirectMessage::RemoteSignals(Vec::new()),
+            DirectMessage::Heartbeat("alice".to_string()),
+            DirectMessage::Goodbye(String::new()),
+            DirectMessage::Pruned {
+                from: "bob".to_string(),
+                address: test_entry().key(),
+            },
+            DirectMessage::GroupSecret(test_group_secret()),
+            DirectMessage::GetValidationPackage {
+                id: "4".to_string(),
+                from: "bob".to_string(),
+            },
+            DirectMessage::ValidationPackage("4".to_string(), Ok(test_package(&Dna::new(), None))),
+            DirectMessage::ValidationPackage("5".to_string(), Err("no genesis".to_string())),
+            DirectMessage::AgentRejected(Rejection {
+                validator: "bob".to_string(),
+                reason: "no proof".to_string(),
+            }),
+        ]
+    }
+
+    #[test]
+    /// every kind of message makes the trip, traced or not
+    fn roundtrip() {
+        for message in test_messages() {
+            let envelope = Envelope::new(message.clone());
+            assert_eq!(Ok(envelope.clone()), decode(&encode(&envelope).unwrap()));
+            let traced = Envelope::traced(message, test_trace_context());
+            assert_eq!(Ok(traced.clone()), decode(&encode(&traced).unwrap()));
+        }
+    }
+
+    #[test]
+    /// the revision here is the one of the schema the types are generated from
+    fn matches_schema() {
+        let schema = include_str!("../../proto/network.proto");
+        assert!(schema.contains(&format!("// revision {}", SCHEMA_REVISION)));
+    }
+
+    #[test]
+    /// sealed envelopes make the trip and open to the protobuf bytes of the envelope
+    fn sealed_roundtrip() {
+        let bob = KeyPair::generate();
+        let envelope = Envelope::new(DirectMessage::Goodbye("alice".to_string()));
+        let bytes = encode(&envelope).unwrap();
+        let sealed = Sealed {
+            to: "bob".to_string(),
+            payload: sealing::seal(&bytes, &bob.public()),
+        };
+        let received = decode_sealed(&encode_sealed(&sealed)).unwrap();
+        assert_eq!(sealed, received);
+        assert_eq!(Ok(envelope), decode(&sealing::open(&received.payload, &bob).unwrap()));
+        assert!(decode_sealed(&[0x0a, 0x05, b'b']).is_err());
+    }
+
+    #[test]
+    /// envelopes of revision 1 stay readable, byte for byte
+    fn reads_revision_1() {
+        // a traced heartbeat and a call with an empty cap secret, as revision 1 writes them
+        let heartbeat = [
+            0x0a, 0x06, 0x0a, 0x01, b't', 0x12, 0x01, b's', 0x4a, 0x05, b'a', b'l', b'i', b'c',
+            b'e',
+        ];
+        let envelope = Envelope::traced(
+            DirectMessage::Heartbeat("alice".to_string()),
+            TraceContext {
+                trace_id: "t".to_string(),
+                span_id: "s".to_string(),
+            },
+        );
+        assert_eq!(Ok(envelope.clone()), decode(&heartbeat));
+        assert_eq!(Ok(heartbeat.to_vec()), encode(&envelope));
+
+        let call = [
+            0x12, 0x0d, 0x0a, 0x01, b'1', 0x12, 0x03, b'b', b'o', b'b', 0x2a, 0x01, b'f', 0x32,
+            0x00,
+        ];
+        let mut expected = RemoteCall {
+            id: "1".to_string(),
+            from: "bob".to_string(),
+            zome: String::new(),
+            capability: String::new(),
+            function: "f".to_string(),
+            cap_secret: Some(String::new()),
+            parameters: String::new(),
+        };
+        let envelope = Envelope::new(DirectMessage::CallRemote(expected.clone()));
+        assert_eq!(Ok(envelope.clone()), decode(&call));
+        assert_eq!(Ok(call.to_vec()), encode(&envelope));
+
+        // without the secret the call is public
+        let public = [
+            0x12, 0x0b, 0x0a, 0x01, b'1', 0x12, 0x03, b'b', b'o', b'b', 0x2a, 0x01, b'f',
+        ];
+        expected.cap_secret = None;
+        let envelope = Envelope::new(DirectMessage::CallRemote(expected));
+        assert_eq!(Ok(envelope.clone()), decode(&public));
+        assert_eq!(Ok(public.to_vec()), encode(&envelope));
+    }
+
+    #[test]
+    /// fields of later revisions are skipped, messages of kinds not known yet are errors
+    fn reads_later_revisions() {
+        let envelope = Envelope::new(DirectMessage::Goodbye("bob".to_string()));
+        let mut bytes = encode(&envelope).unwrap();
+        // field 16 varint, field 17 string
+        bytes.extend_from_slice(&[0x80, 0x01, 0x01, 0x8a, 0x01, 0x01, b'x']);
+        assert_eq!(Ok(envelope), decode(&bytes));
+
+        // a call with field 20 string next to its id
+        let call = [
+            0x12, 0x09, 0x0a, 0x01, b'1', 0xa2, 0x01, 0x03, b'n', b'e', b'w',
+        ];
+        match decode(&call).unwrap().message {
+            DirectMessage::CallRemote(call) => assert_eq!("1", call.id),
+            message => panic!("unexpected {:?}", message),
+        }
+
+        // an empty message of the Envelope oneof at field 18
+        assert!(decode(&[0x92, 0x01, 0x00]).is_err());
+        assert!(decode(&[0x4a, 0x05, b'a']).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! zome calls committing at the same time could interleave their commits on the chain, so calls
+//! that may write go through the gate of an instance one at a time, while calls to functions their
+//! capability declares read_only run side by side, up to max_concurrent_reads of them if limited
+//! calls wait for their turn in the thread they run in, so the action loop is never held up
+//! a call that may write waiting on a remote call back into its own instance waits for itself
+//! until the remote call times out
+
+use std::{
+    fmt, sync::{Arc, Condvar, Mutex},
+};
+
+#[derive(Default)]
+struct Gate {
+    /// true while a call that may write is running
+    writing: bool,
+    /// read_only calls running
+    reading: usize,
+    max_reads: Option<usize>,
+}
+
+impl Gate {
+    fn admits(&self, read_only: bool) -> bool {
+        if read_only {
+            self.max_reads.map(|max| self.reading < max).unwrap_or(true)
+        } else {
+            !self.writing
+        }
+    }
+}
+
+/// the turns zome calls of an instance take
+/// the gate is a cheap handle, clones share the same turns
+#[derive(Clone, Default)]
+pub struct CallGate {
+    gate: Arc<(Mutex<Gate>, Condvar)>,
+}
+
+impl PartialEq for CallGate {
+    fn eq(&self, other: &CallGate) -> bool {
+        Arc::ptr_eq(&self.gate, &other.gate)
+    }
+}
+
+impl fmt::Debug for CallGate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gate = self.gate.0.lock().unwrap();
+        f.debug_struct("CallGate")
+            .field("writing", &gate.writing)
+            .field("reading", &gate.reading)
+            .field("max_reads", &gate.max_reads)
+            .finish()
+    }
+}
+
+/// the turn of a call, the next call waiting gets its turn once it is dropped
+pub struct Turn {
+    gate: CallGate,
+    read_only: bool,
+}
+
+impl Drop for Turn {
+    fn drop(&mut self) {
+        let (ref lock, ref turns) = *self.gate.gate;
+        let mut gate = lock.lock().unwrap();
+        if self.read_only {
+            gate.reading -= 1;
+        } else {
+            gate.writing = false;
+        }
+        turns.notify_all();
+    }
+}
+
+impl CallGate {
+    /// let at most max read_only calls run at once, any number if None
+    /// a max of 0 lets one through, so reads can't be shut out
+    pub fn set_max_reads(&self, max: Option<usize>) {
+        let (ref lock, ref turns) = *self.gate;
+        lock.lock().unwrap().max_reads = max.map(|max| max.max(1));
+        turns.notify_all();
+    }
+
+    /// wait for the turn of a call, read_only if it only reads
+    pub fn enter(&self, read_only: bool) -> Turn {
+        let (ref lock, ref turns) = *self.gate;
+        let mut gate = lock.lock().unwrap();
+        while !gate.admits(read_only) {
+            gate = turns.wait(gate).unwrap();
+        }
+        if read_only {
+            gate.reading += 1;
+        } else {
+            gate.writing = true;
+        }
+        Turn {
+            gate: self.clone(),
+            read_only,
+        }
+    }
+
+    pub fn max_reads(&self) -> Option<usize> {
+        self.gate.0.lock().unwrap().max_reads
+    }
+
+    /// the read_only calls running and whether a call that may write is
+    pub fn running(&self) -> (usize, bool) {
+        let gate = self.gate.0.lock().unwrap();
+        (gate.reading, gate.writing)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::{
+        sync::mpsc::channel, thread, time::Duration,
+    };
+
+    #[test]
+    /// calls that may write take turns, reads run alongside them up to the max
+    fn turns() {
+        let gate = CallGate::default();
+        let write = gate.enter(false);
+        let reads = vec![gate.enter(true), gate.enter(true)];
+        assert_eq!((2, true), gate.running());
+
+        let (sender, receiver) = channel();
+        let waiting = gate.clone();
+        let handle = thread::spawn(move || {
+            let _write = waiting.enter(false);
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(write);
+        assert!(receiver.recv_timeout(Duration::from_millis(1000)).is_ok());
+        handle.join().unwrap();
+        assert_eq!((2, false), gate.running());
+
+        gate.set_max_reads(Some(2));
+        let (sender, receiver) = channel();
+        let waiting = gate.clone();
+        let handle = thread::spawn(move || {
+            let _read = waiting.enter(true);
+            sender.send(()).unwrap();
+        });
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+        drop(reads);
+        assert!(receiver.recv_timeout(Duration::from_millis(1000)).is_ok());
+        handle.join().unwrap();
+        assert_eq!((0, false), gate.running());
+    }
+}
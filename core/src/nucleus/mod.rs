@@ -1,19 +1,60 @@
+pub mod call_gate;
+pub mod module_cache;
+#[cfg(feature = "native_zomes")]
+pub mod native_zome;
+pub mod random;
 pub mod ribosome;
+pub mod scheduler;
+pub mod scratch;
+pub mod traits;
 
+use agent::{
+    checkpoints::Checkpoints, groups::GroupSecrets, keystore::Keystore,
+    membrane::{self, AgentId}, transaction::Transaction, INIT_COMPLETE_ENTRY_TYPE,
+};
+use dht::{
+    retention::PruneLog, store::HoldingStore, subscriptions::Subscriptions,
+};
 use error::HolochainError;
+use hash_table::entry::Entry;
+use health::CallMonitor;
 use holochain_dna::{
     zome::capabilities::{ReservedCapabilityNames, ReservedFunctionNames}, Dna,
 };
+use index::SearchIndex;
 use instance::Observer;
+use limits::LimitExceeded;
+use logger::ZomeLogger;
+use network::{
+    connectivity::ConnectivityMonitor, direct_message::DirectMessenger,
+    outbox::{Outbox, Priority}, presence::Presence, remote_signal::RemoteSignalSender,
+};
+#[cfg(feature = "native_zomes")]
+use nucleus::native_zome::{NativeZome, NativeZomes};
+use nucleus::{
+    call_gate::CallGate, module_cache::ModuleCache, scheduler::Schedule, scratch::ScratchSpace,
+};
+use platform;
+use rand::{OsRng, Rng};
+use rust_base58::ToBase58;
+use signal::SignalBus;
 use snowflake;
 use state;
 use std::{
-    collections::HashMap, sync::{
+    collections::{BTreeMap, HashMap}, panic::{catch_unwind, AssertUnwindSafe}, sync::{
         mpsc::{channel, Sender}, Arc,
-    }, thread,
+    },
+};
+use trace::Tracer;
+use validation::{
+    packages::PackageVerdicts, rates::{self, AuthorRates}, receipts::ReceiptStore,
 };
 
-#[derive(Clone, Debug, PartialEq)]
+/// how often a zome call is run again when the chain head moved between its reads and commits,
+/// see ribosome::HeadMoved, before it fails with HolochainError::HeadMoved
+pub const HEAD_MOVED_RETRIES: usize = 3;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum NucleusStatus {
     New,
     Initializing,
@@ -27,19 +68,121 @@ impl Default for NucleusStatus {
     }
 }
 
-#[derive(Clone, Debug, PartialEq, Default)]
+/// serialized, the nucleus holds what it was set up with, the rest is rebuilt when it runs
+#[derive(Clone, Debug, PartialEq, Default, Serialize, Deserialize)]
 pub struct NucleusState {
     dna: Option<Dna>,
+    /// the agent the instance runs for and its membrane proof, see agent::membrane
+    agent_id: Option<AgentId>,
     status: NucleusStatus,
+    #[serde(skip)]
     ribosome_calls: HashMap<FunctionCall, Option<Result<String, HolochainError>>>,
+    #[serde(skip)]
+    module_cache: ModuleCache,
+    #[serde(skip)]
+    tracer: Tracer,
+    #[serde(skip)]
+    zome_logger: ZomeLogger,
+    /// recurring zome function calls by schedule_key()
+    schedules: BTreeMap<String, Schedule>,
+    #[serde(skip)]
+    signal_bus: SignalBus,
+    /// capabilities granted to remote callers by secret
+    cap_grants: HashMap<String, CapabilityGrant>,
+    #[serde(skip)]
+    messenger: DirectMessenger,
+    /// what is yet to get through to other nodes, e.g. publishes of commits made offline
+    #[serde(skip)]
+    outbox: Outbox,
+    /// the last connectivity seen, to signal when it changes
+    #[serde(skip)]
+    connectivity: ConnectivityMonitor,
+    /// where the DHT holdings are kept across restarts, if anywhere
+    #[serde(skip)]
+    holding_store: HoldingStore,
+    /// validation receipts from the holders of what was published
+    #[serde(skip)]
+    receipts: ReceiptStore,
+    /// secrets shared with the groups of the agent, see agent::groups
+    #[serde(skip)]
+    group_secrets: GroupSecrets,
+    /// the keys zomes derive, see agent::keystore
+    #[serde(skip)]
+    keystore: Keystore,
+    /// signals for other agents waiting to go out
+    #[serde(skip)]
+    remote_signals: RemoteSignalSender,
+    /// heartbeats sent and received, see network::presence
+    #[serde(skip)]
+    presence: Presence,
+    /// the checkpoints of the chain head published, see agent::checkpoints
+    #[serde(skip)]
+    checkpoints: Checkpoints,
+    /// what was pruned of the holdings, see dht::retention
+    #[serde(skip)]
+    prune_log: PruneLog,
+    /// the entries authors got held lately, see validation::rates
+    #[serde(skip)]
+    author_rates: AuthorRates,
+    /// the verdicts on the validation packages of the agents checked, see validation::packages
+    #[serde(skip)]
+    package_verdicts: PackageVerdicts,
+    #[serde(skip)]
+    scratch: ScratchSpace,
+    /// the content of the searchable entries committed and held, see index
+    #[serde(skip)]
+    search_index: SearchIndex,
+    /// who watches what for the aspects held of it, see dht::subscriptions
+    #[serde(skip)]
+    subscriptions: Subscriptions,
+    /// max pages the wasm memory of a zome call can grow to, see limits
+    max_wasm_pages: Option<u32>,
+    /// when the calls in flight were started, see health
+    #[serde(skip)]
+    call_monitor: CallMonitor,
+    /// the turns of zome calls, see call_gate
+    #[serde(skip)]
+    call_gate: CallGate,
+    /// zomes implemented in Rust, see native_zome
+    #[cfg(feature = "native_zomes")]
+    #[serde(skip)]
+    native_zomes: NativeZomes,
 }
 
 impl NucleusState {
     pub fn new() -> Self {
         NucleusState {
             dna: None,
+            agent_id: None,
             status: NucleusStatus::New,
             ribosome_calls: HashMap::new(),
+            module_cache: ModuleCache::default(),
+            tracer: Tracer::default(),
+            zome_logger: ZomeLogger::default(),
+            schedules: BTreeMap::new(),
+            signal_bus: SignalBus::default(),
+            cap_grants: HashMap::new(),
+            messenger: DirectMessenger::default(),
+            outbox: Outbox::default(),
+            connectivity: ConnectivityMonitor::default(),
+            holding_store: HoldingStore::default(),
+            receipts: ReceiptStore::default(),
+            group_secrets: GroupSecrets::default(),
+            keystore: Keystore::default(),
+            remote_signals: RemoteSignalSender::default(),
+            presence: Presence::default(),
+            checkpoints: Checkpoints::default(),
+            prune_log: PruneLog::default(),
+            author_rates: AuthorRates::default(),
+            package_verdicts: PackageVerdicts::default(),
+            scratch: ScratchSpace::default(),
+            search_index: SearchIndex::default(),
+            subscriptions: Subscriptions::default(),
+            max_wasm_pages: None,
+            call_monitor: CallMonitor::default(),
+            call_gate: CallGate::default(),
+            #[cfg(feature = "native_zomes")]
+            native_zomes: NativeZomes::default(),
         }
     }
 
@@ -53,6 +196,14 @@ impl NucleusState {
         }
     }
 
+    /// number of zome function calls started but without a result yet
+    pub fn calls_in_flight(&self) -> usize {
+        self.ribosome_calls
+            .values()
+            .filter(|result| result.is_none())
+            .count()
+    }
+
     pub fn has_initialized(&self) -> bool {
         self.status == NucleusStatus::Initialized
     }
@@ -71,6 +222,89 @@ impl NucleusState {
     pub fn status(&self) -> NucleusStatus {
         self.status.clone()
     }
+    #[cfg(feature = "native_zomes")]
+    pub fn native_zomes(&self) -> &NativeZomes {
+        &self.native_zomes
+    }
+
+    pub fn module_cache(&self) -> &ModuleCache {
+        &self.module_cache
+    }
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+    pub fn zome_logger(&self) -> &ZomeLogger {
+        &self.zome_logger
+    }
+    pub fn schedules(&self) -> &BTreeMap<String, Schedule> {
+        &self.schedules
+    }
+    pub fn agent_id(&self) -> Option<&AgentId> {
+        self.agent_id.as_ref()
+    }
+    pub fn signal_bus(&self) -> &SignalBus {
+        &self.signal_bus
+    }
+    pub fn cap_grants(&self) -> &HashMap<String, CapabilityGrant> {
+        &self.cap_grants
+    }
+    pub fn messenger(&self) -> &DirectMessenger {
+        &self.messenger
+    }
+    pub fn outbox(&self) -> &Outbox {
+        &self.outbox
+    }
+    pub fn connectivity(&self) -> &ConnectivityMonitor {
+        &self.connectivity
+    }
+    pub fn holding_store(&self) -> &HoldingStore {
+        &self.holding_store
+    }
+    pub fn receipts(&self) -> &ReceiptStore {
+        &self.receipts
+    }
+    pub fn group_secrets(&self) -> &GroupSecrets {
+        &self.group_secrets
+    }
+    pub fn keystore(&self) -> &Keystore {
+        &self.keystore
+    }
+    pub fn remote_signals(&self) -> &RemoteSignalSender {
+        &self.remote_signals
+    }
+    pub fn presence(&self) -> &Presence {
+        &self.presence
+    }
+    pub fn checkpoints(&self) -> &Checkpoints {
+        &self.checkpoints
+    }
+    pub fn prune_log(&self) -> &PruneLog {
+        &self.prune_log
+    }
+    pub fn author_rates(&self) -> &AuthorRates {
+        &self.author_rates
+    }
+    pub fn package_verdicts(&self) -> &PackageVerdicts {
+        &self.package_verdicts
+    }
+    pub fn scratch(&self) -> &ScratchSpace {
+        &self.scratch
+    }
+    pub fn search_index(&self) -> &SearchIndex {
+        &self.search_index
+    }
+    pub fn subscriptions(&self) -> &Subscriptions {
+        &self.subscriptions
+    }
+    pub fn max_wasm_pages(&self) -> Option<u32> {
+        self.max_wasm_pages
+    }
+    pub fn call_monitor(&self) -> &CallMonitor {
+        &self.call_monitor
+    }
+    pub fn call_gate(&self) -> &CallGate {
+        &self.call_gate
+    }
 }
 
 /// Struct holding data for requesting the execution of a Zome function (ExecutionZomeFunction Action)
@@ -81,6 +315,15 @@ pub struct FunctionCall {
     pub capability: String,
     pub function: String,
     pub parameters: String,
+    /// address of the agent calling remotely, None for local calls
+    pub caller: Option<String>,
+    /// secret of the capability grant the remote caller presented, if any
+    pub cap_secret: Option<String>,
+    /// seconds since the unix epoch when the call was made
+    pub requested_at: u64,
+    /// the priority what the call commits is published at, unless more urgent, see
+    /// network::outbox
+    pub priority: Priority,
 }
 
 impl FunctionCall {
@@ -91,6 +334,70 @@ impl FunctionCall {
             capability: capability.into(),
             function: function.into(),
             parameters: parameters.into(),
+            caller: None,
+            cap_secret: None,
+            requested_at: scheduler::unix_now(),
+            priority: Priority::default(),
+        }
+    }
+
+    /// the same call made remotely by caller, presenting the secret of a grant if any
+    pub fn with_caller(mut self, caller: &str, cap_secret: Option<String>) -> Self {
+        self.caller = Some(caller.to_string());
+        self.cap_secret = cap_secret;
+        self
+    }
+
+    /// the same call publishing what it commits at priority, e.g. Low for bulk imports so they
+    /// don't hold back what other calls commit
+    pub fn with_priority(mut self, priority: Priority) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// the context of the call once it started running at started_at
+    pub fn context(&self, started_at: u64) -> CallContext {
+        CallContext {
+            call_id: self.id.to_string(),
+            caller: self.caller.clone(),
+            cap_secret: self.cap_secret.clone(),
+            requested_at: self.requested_at,
+            started_at,
+        }
+    }
+}
+
+/// What zome code can know about the call it runs in, see the call_context host function
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CallContext {
+    /// unique among the calls of the instance
+    pub call_id: String,
+    /// address of the agent calling remotely, None for local calls
+    pub caller: Option<String>,
+    /// secret of the capability grant the remote caller presented, if any
+    pub cap_secret: Option<String>,
+    /// seconds since the unix epoch when the call was made and when it started running
+    pub requested_at: u64,
+    pub started_at: u64,
+}
+
+/// Lets remote callers presenting the secret call a capability that isn't public
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapabilityGrant {
+    pub secret: String,
+    pub zome: String,
+    pub capability: String,
+}
+
+impl CapabilityGrant {
+    /// grant with a new secret from the OS's generator, like agent::secbuf::SecBuf::random
+    /// panics if the OS has no randomness to give
+    pub fn new(zome: &str, capability: &str) -> Self {
+        let mut rng = OsRng::new().expect("the OS has no randomness for secrets");
+        CapabilityGrant {
+            secret: rng.gen::<[u8; 32]>().to_base58(),
+            zome: zome.to_string(),
+            capability: capability.to_string(),
         }
     }
 }
@@ -185,11 +492,38 @@ impl FunctionResult {
 #[allow(unknown_lints)]
 #[allow(large_enum_variant)]
 pub enum Action {
+    /// set the agent to initialize the application for, with its membrane proof
+    /// dispatched before InitApplication, DNAs gating agents check the proof then
+    SetAgentId(AgentId),
     InitApplication(Dna),
     ReturnInitializationResult(Option<String>),
     ExecuteZomeFunction(FunctionCall),
     ReturnZomeFunctionResult(FunctionResult),
     ValidateEntry(EntrySubmission),
+    /// set a recurring zome function call, replacing any with the same key
+    Schedule(Schedule),
+    /// remove the schedule with the given key
+    CancelSchedule(String),
+    /// call the function of the schedule with the given key for a tick, unless it already was
+    FireSchedule(String, u64),
+    /// let remote callers with the grant's secret call its capability
+    GrantCapability(CapabilityGrant),
+    /// remove the grant with the given secret
+    RevokeCapability(String),
+    /// ask for a transaction to be validated against the DNA and what the DHT holds, to be read
+    /// from the state once the action is reduced
+    ValidateTransaction(Transaction),
+    /// limit the pages the wasm memory of zome calls can grow to, None for no limit
+    SetWasmPageLimit(Option<u32>),
+    /// limit the read_only zome calls running at once, None for no limit
+    SetConcurrentReadLimit(Option<usize>),
+    /// signal that a resource limit of the instance was hit
+    ReportLimitExceeded(LimitExceeded),
+    /// fail every call in flight, e.g. when restoring a state saved while they were running
+    AbandonCalls,
+    /// swap in a new version of the DNA of an initialized instance, e.g. with its code or
+    /// properties changed during development, calls made from then on run it
+    ReloadDna(Dna),
 }
 
 /// Reduce ReturnInitializationResult Action
@@ -221,9 +555,46 @@ fn return_initialization_result(
         .expect("action channel to be open in reducer");
 }
 
+/// Call a lifecycle function of a zome, e.g. genesis(), and wait for it to finish
+/// Lifecycle functions return "" on success, anything else is the error
+/// It is fine for a zome not to have the lifecycle capability or the function
+pub fn call_lifecycle_function(
+    zome_name: &str,
+    function: &ReservedFunctionNames,
+    parameters: &str,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) -> Result<(), String> {
+    let call = FunctionCall::new(
+        zome_name.to_string(),
+        ReservedCapabilityNames::LifeCycle.as_str().to_string(),
+        function.as_str().to_string(),
+        parameters.to_string(),
+    );
+    let missing_export = format!(
+        "Function: Module doesn\'t have export {}_dispatch",
+        function.as_str()
+    );
+
+    match call_zome_and_wait_for_result(call, action_channel, observer_channel) {
+        // not okay if the function returned a value
+        Ok(ref s) if !s.is_empty() => Err(s.to_string()),
+        // its okay if hc_lifecycle or the function is not present
+        Ok(_)
+        | Err(HolochainError::CapabilityNotFound(_))
+        | Err(HolochainError::ZomeFunctionNotFound(_)) => Ok(()),
+        Err(HolochainError::ErrorGeneric(ref msg)) if *msg == missing_export => Ok(()),
+        // TODO - Create test for this edge case
+        // @see https://github.com/holochain/holochain-rust/issues/78
+        Err(err) => Err(err.to_string()),
+    }
+}
+
 /// Reduce InitApplication Action
-/// Initialize Nucleus by setting the DNA
-/// and sending ExecuteFunction Action of genesis of each zome
+/// Initialize Nucleus by setting the DNA,
+/// checking the agent's membrane proof and committing its AgentId first if the DNA is gated,
+/// sending ExecuteFunction Action of genesis then init of each zome
+/// and committing the InitComplete marker once they all succeeded
 fn reduce_ia(
     nucleus_state: &mut NucleusState,
     dna: &Dna,
@@ -242,47 +613,94 @@ fn reduce_ia(
             let action_channel = action_channel.clone();
             let observer_channel = observer_channel.clone();
             let dna_clone = dna.clone();
+            let module_cache = nucleus_state.module_cache.clone();
+            let agent_id = nucleus_state.agent_id.clone().unwrap_or_default();
+            #[allow(unused_mut)]
+            let mut zome_names: Vec<String> =
+                dna.zomes.iter().map(|zome| zome.name.clone()).collect();
+            // native zomes get the lifecycle calls too, after the zomes of the DNA
+            #[cfg(feature = "native_zomes")]
+            for name in nucleus_state.native_zomes.names() {
+                if !zome_names.contains(&name) {
+                    zome_names.push(name);
+                }
+            }
 
-            thread::spawn(move || {
-                //  Call each Zome's genesis() with an ExecuteZomeFunction Action
-                for zome in dna_clone.zomes {
-                    // Make ExecuteZomeFunction Action for genesis()
-                    let call = FunctionCall::new(
-                        zome.name,
-                        ReservedCapabilityNames::LifeCycle.as_str().to_string(),
-                        ReservedFunctionNames::Genesis.as_str().to_string(),
-                        "".to_string(),
-                    );
+            platform::spawn("precompile", move || {
+                // Compile every capability up front so the first calls don't pay for it
+                // Bad code is not an error here, it will surface when the capability is called
+                for zome in &dna_clone.zomes {
+                    for capability in &zome.capabilities {
+                        let _ = module_cache.precompile(&capability.code.code);
+                    }
+                }
 
-                    // Call Genesis and wait
-                    let call_result =
-                        call_zome_and_wait_for_result(call, &action_channel, &observer_channel);
+                // Fail fast on DNAs targeting a host API version this host doesn't support
+                if let Err(message) = ribosome::check_host_api(&dna_clone, &module_cache) {
+                    return_initialization_result(Some(message), &action_channel);
+                    return;
+                }
 
-                    // genesis returns a string
-                    // "" == success, otherwise error value
-                    match call_result {
-                        // not okay if genesis returned an value
-                        Ok(ref s) if s != "" => {
-                            // Send a failed ReturnInitializationResult Action
-                            return_initialization_result(Some(s.to_string()), &action_channel);
+                // Zomes must export every function of the traits they declare
+                if let Err(HolochainError::ErrorGeneric(message)) =
+                    traits::verify_traits(&dna_clone)
+                {
+                    return_initialization_result(Some(message), &action_channel);
+                    return;
+                }
 
-                            // Kill thread
-                            // TODO - Instead, Keep track of each zome's initialization.
-                            // @see https://github.com/holochain/holochain-rust/issues/78
-                            // Mark this one as failed and continue with other zomes
-                            return;
-                        }
-                        // its okay if hc_lifecycle or genesis not present
-                        Ok(_) | Err(HolochainError::CapabilityNotFound(_)) => { /* NA */ }
-                        Err(HolochainError::ErrorGeneric(ref msg))
-                            if msg == "Function: Module doesn\'t have export genesis_dispatch" =>
-                        { /* NA */ }
-                        // Init fails if something failed in genesis called
-                        Err(err) => {
-                            // TODO - Create test for this edge case
-                            // @see https://github.com/holochain/holochain-rust/issues/78
-                            // Send a failed ReturnInitializationResult Action
-                            return_initialization_result(Some(err.to_string()), &action_channel);
+                // Only agents with a membrane proof the DNA accepts may join it
+                let gated = membrane::is_gated(&dna_clone);
+                if gated {
+                    if let Err(refusal) = membrane::validate_agent(
+                        &dna_clone,
+                        &agent_id,
+                        &action_channel,
+                        &observer_channel,
+                    ) {
+                        return_initialization_result(Some(refusal), &action_channel);
+                        return;
+                    }
+                }
+
+                // Commits take from the rate buckets the DNA declares, genesis ones included
+                ::instance::dispatch_action(
+                    &action_channel,
+                    state::Action::Agent(::agent::Action::SetRateBuckets(rates::buckets(
+                        &dna_clone,
+                    ))),
+                );
+
+                // Hold back what the callbacks commit until they all succeeded
+                ::instance::dispatch_action(
+                    &action_channel,
+                    state::Action::Agent(::agent::Action::BeginStaging),
+                );
+                if gated {
+                    ::instance::dispatch_action_and_wait(
+                        &action_channel,
+                        &observer_channel,
+                        state::Action::Agent(::agent::Action::Commit(agent_id.to_entry())),
+                    );
+                }
+
+                // Call each Zome's genesis(), then once all succeeded each Zome's init()
+                for function in &[ReservedFunctionNames::Genesis, ReservedFunctionNames::Init] {
+                    for zome_name in &zome_names {
+                        if let Err(err) = call_lifecycle_function(
+                            zome_name,
+                            function,
+                            "",
+                            &action_channel,
+                            &observer_channel,
+                        ) {
+                            // Drop what earlier callbacks committed, then send a failed
+                            // ReturnInitializationResult Action
+                            ::instance::dispatch_action(
+                                &action_channel,
+                                state::Action::Agent(::agent::Action::AbortStaged),
+                            );
+                            return_initialization_result(Some(err), &action_channel);
 
                             // Kill thread
                             // TODO - Instead, Keep track of each zome's initialization.
@@ -292,6 +710,19 @@ fn reduce_ia(
                         }
                     }
                 }
+
+                // Record that init ran so it won't run again
+                let marker = Entry::new(INIT_COMPLETE_ENTRY_TYPE, &dna_clone.hash());
+                ::instance::dispatch_action_and_wait(
+                    &action_channel,
+                    &observer_channel,
+                    state::Action::Agent(::agent::Action::Commit(marker)),
+                );
+                ::instance::dispatch_action(
+                    &action_channel,
+                    state::Action::Agent(::agent::Action::CommitStaged),
+                );
+
                 // Send Succeeded ReturnInitializationResult Action
                 return_initialization_result(None, &action_channel);
             });
@@ -306,6 +737,107 @@ fn reduce_ia(
     }
 }
 
+/// The post_commit() call to make after a zome call committed entries, None if nothing was
+/// committed or the zome has no post_commit() in its lifecycle capability
+/// Entries committed by post_commit() itself don't trigger it again
+fn post_commit_call(
+    function_call: &FunctionCall,
+    lifecycle_code: Option<&[u8]>,
+    header_addresses: &[String],
+) -> Option<FunctionCall> {
+    let post_commit = ReservedFunctionNames::PostCommit.as_str();
+    let lifecycle = ReservedCapabilityNames::LifeCycle.as_str();
+    if header_addresses.is_empty()
+        || (function_call.capability == lifecycle && function_call.function == post_commit)
+    {
+        return None;
+    }
+    let exports = traits::wasm_exports(lifecycle_code?).ok()?;
+    if !exports.contains(&format!("{}_dispatch", post_commit)) {
+        return None;
+    }
+    Some(FunctionCall::new(
+        function_call.zome.clone(),
+        lifecycle.to_string(),
+        post_commit.to_string(),
+        json!({ "header_addresses": header_addresses }).to_string(),
+    ))
+}
+
+/// What the host functions of a call to the zome need, but for the trace and call context it only
+/// gets once it runs
+fn host_context(nucleus_state: &NucleusState, zome: &str) -> ribosome::HostContext {
+    let dna = nucleus_state.dna.as_ref();
+    ribosome::HostContext {
+        zome: zome.to_string(),
+        trace: None,
+        logger: nucleus_state.zome_logger.clone(),
+        properties: dna.map(|dna| dna.properties.clone()).unwrap_or_default(),
+        signals: nucleus_state.signal_bus.clone(),
+        messenger: nucleus_state.messenger.clone(),
+        outbox: nucleus_state.outbox.clone(),
+        receipts: nucleus_state.receipts.clone(),
+        group_secrets: nucleus_state.group_secrets.clone(),
+        keystore: nucleus_state.keystore.clone(),
+        remote_signals: nucleus_state.remote_signals.clone(),
+        presence: nucleus_state.presence.clone(),
+        scratch: nucleus_state.scratch.clone(),
+        search_index: nucleus_state.search_index.clone(),
+        max_wasm_pages: nucleus_state.max_wasm_pages,
+        call: CallContext::default(),
+        api_version: dna.map(|dna| dna.host_api_version),
+        address_format: dna.map(|dna| dna.address_format).unwrap_or_default(),
+        read_only: false,
+        priority: Priority::default(),
+    }
+}
+
+/// Reduce ExecuteZomeFunction Action for a native zome
+/// Call it in a seperate thread, once it is its turn at the call gate, and send the result in a
+/// ReturnZomeFunctionResult Action
+#[cfg(feature = "native_zomes")]
+fn reduce_native_call(
+    nucleus_state: &mut NucleusState,
+    zome: Arc<dyn NativeZome>,
+    fc: &FunctionCall,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) {
+    nucleus_state.ribosome_calls.insert(fc.clone(), None);
+    nucleus_state.call_monitor.started(fc);
+
+    let function_call = fc.clone();
+    let action_channel = action_channel.clone();
+    let observer_channel = observer_channel.clone();
+    let call_gate = nucleus_state.call_gate.clone();
+    let tracer = nucleus_state.tracer.clone();
+    let mut host = host_context(nucleus_state, &fc.zome);
+
+    platform::spawn("native_zome_call", move || {
+        // native zomes declare no read_only functions, so their calls may write
+        let _turn = call_gate.enter(false);
+        host.call = function_call.context(scheduler::unix_now());
+        host.priority = function_call.priority;
+        let mut span = tracer.span("zome_call");
+        span.tag("zome", &function_call.zome);
+        span.tag("capability", &function_call.capability);
+        span.tag("function", &function_call.function);
+        host.trace = Some((tracer.clone(), span.context()));
+        let result = native_zome::call(
+            &*zome,
+            &function_call,
+            &action_channel,
+            &observer_channel,
+            &host,
+        );
+        action_channel
+            .send(state::ActionWrapper::new(state::Action::Nucleus(
+                Action::ReturnZomeFunctionResult(FunctionResult::new(function_call, result)),
+            )))
+            .expect("action channel to be open in reducer");
+    });
+}
+
 /// Reduce ExecuteZomeFunction Action
 /// Execute an exposed Zome function in a seperate thread and send the result in
 /// a ReturnZomeFunctionResult Action on success or failure
@@ -315,6 +847,13 @@ fn reduce_ezf(
     action_channel: &Sender<state::ActionWrapper>,
     observer_channel: &Sender<Observer>,
 ) {
+    #[cfg(feature = "native_zomes")]
+    {
+        if let Some(zome) = nucleus_state.native_zomes.get(&fc.zome) {
+            return reduce_native_call(nucleus_state, zome, fc, action_channel, observer_channel);
+        }
+    }
+
     let function_call = fc.clone();
     let mut has_error = false;
     let mut result = FunctionResult::new(
@@ -324,34 +863,105 @@ fn reduce_ezf(
 
     if let Some(ref dna) = nucleus_state.dna {
         if let Some(ref zome) = dna.get_zome(&fc.zome) {
-            if let Some(ref wasm) = dna.get_capability(zome, &fc.capability) {
+            if let Some(capability) = zome
+                .capabilities
+                .iter()
+                .find(|capability| capability.name == fc.capability)
+            {
                 nucleus_state.ribosome_calls.insert(fc.clone(), None);
+                nucleus_state.call_monitor.started(fc);
 
                 let action_channel = action_channel.clone();
                 let tx_observer = observer_channel.clone();
-                let code = wasm.code.clone();
+                let code = capability.code.code.clone();
+                let read_only = capability.is_read_only(&fc.function);
+                let call_gate = nucleus_state.call_gate.clone();
+                let module_cache = nucleus_state.module_cache.clone();
+                let tracer = nucleus_state.tracer.clone();
+                let mut host = host_context(nucleus_state, &fc.zome);
+                host.read_only = read_only;
+                let lifecycle_code = dna
+                    .get_capability(zome, ReservedCapabilityNames::LifeCycle.as_str())
+                    .map(|wasm| wasm.code.clone());
 
-                thread::spawn(move || {
-                    let result: FunctionResult;
-                    match ribosome::call(
-                        &action_channel,
-                        &tx_observer,
-                        code,
-                        &function_call.function.clone(),
-                        Some(function_call.clone().parameters.into_bytes()),
-                    ) {
-                        Ok(runtime) => {
-                            result =
-                                FunctionResult::new(function_call, Ok(runtime.result.to_string()));
-                        }
+                platform::spawn("zome_call", move || {
+                    // Calls that may write wait for the ones running to finish, see call_gate
+                    let _turn = call_gate.enter(read_only);
+                    // a panicking call still returns a result, the caller would wait for it
+                    // forever otherwise
+                    let call = function_call.clone();
+                    let called = catch_unwind(AssertUnwindSafe(|| {
+                        host.call = function_call.context(scheduler::unix_now());
+                        host.priority = function_call.priority;
+                        let mut span = tracer.span("zome_call");
+                        span.tag("zome", &function_call.zome);
+                        span.tag("capability", &function_call.capability);
+                        span.tag("function", &function_call.function);
+                        host.trace = Some((tracer.clone(), span.context()));
+                        let module = match module_cache.get_or_compile(&code) {
+                            Ok(module) => module,
+                            Err(error) => {
+                                return (FunctionResult::new(function_call, Err(error)), None)
+                            }
+                        };
+                        // a call whose commit found the head moved since it read is run again on
+                        // the new head, up to HEAD_MOVED_RETRIES times
+                        let mut retries = 0;
+                        let called = loop {
+                            match ribosome::call_module(
+                                &action_channel,
+                                &tx_observer,
+                                &module,
+                                &function_call.function.clone(),
+                                Some(function_call.clone().parameters.into_bytes()),
+                                &host,
+                            ) {
+                                Err(ref error)
+                                    if ribosome::is_head_moved(error)
+                                        && retries < HEAD_MOVED_RETRIES =>
+                                {
+                                    retries += 1
+                                }
+                                called => break called,
+                            }
+                        };
+                        match called {
+                            Ok(runtime) => {
+                                let post_commit = post_commit_call(
+                                    &function_call,
+                                    lifecycle_code.as_ref().map(|code| &code[..]),
+                                    &runtime.committed,
+                                );
+                                let result = Ok(runtime.result.to_string());
+                                (FunctionResult::new(function_call, result), post_commit)
+                            }
 
-                        Err(ref error) => {
-                            result = FunctionResult::new(
-                                function_call,
-                                Err(HolochainError::ErrorGeneric(format!("{}", error))),
-                            );
+                            Err(ref error) if ribosome::is_head_moved(error) => (
+                                FunctionResult::new(function_call, Err(HolochainError::HeadMoved)),
+                                None,
+                            ),
+
+                            Err(ref error) => (
+                                FunctionResult::new(
+                                    function_call,
+                                    Err(HolochainError::ErrorGeneric(format!("{}", error))),
+                                ),
+                                None,
+                            ),
                         }
-                    }
+                    }));
+                    let (result, post_commit) = match called {
+                        Ok(called) => called,
+                        Err(_) => (
+                            FunctionResult::new(
+                                call,
+                                Err(HolochainError::ErrorGeneric(
+                                    "zome call panicked".to_string(),
+                                )),
+                            ),
+                            None,
+                        ),
+                    };
 
                     // Send ReturnResult Action
                     action_channel
@@ -359,6 +969,15 @@ fn reduce_ezf(
                             Action::ReturnZomeFunctionResult(result),
                         )))
                         .expect("action channel to be open in reducer");
+
+                    // Only now the caller has its result, run post_commit() without waiting on it
+                    // @TODO call it once the entries are published too
+                    if let Some(call) = post_commit {
+                        ::instance::dispatch_action(
+                            &action_channel,
+                            state::Action::Nucleus(Action::ExecuteZomeFunction(call)),
+                        );
+                    }
                 });
             } else {
                 has_error = true;
@@ -410,6 +1029,31 @@ fn reduce_ve(nucleus_state: &mut NucleusState, es: &EntrySubmission) {
     }
 }
 
+/// Reduce FireSchedule Action
+/// Record the tick on the schedule and call its function, at most once per tick, schedules once
+/// are removed after their first
+fn reduce_fs(
+    nucleus_state: &mut NucleusState,
+    key: &str,
+    tick: u64,
+    action_channel: &Sender<state::ActionWrapper>,
+) {
+    if let Some(schedule) = nucleus_state.schedules.get_mut(key) {
+        if schedule.last_tick >= Some(tick) {
+            return;
+        }
+        schedule.last_tick = Some(tick);
+        action_channel
+            .send(state::ActionWrapper::new(state::Action::Nucleus(
+                Action::ExecuteZomeFunction(schedule.call()),
+            )))
+            .expect("action channel to be open in reducer");
+    }
+    if nucleus_state.schedules.get(key).is_some_and(|schedule| schedule.once) {
+        nucleus_state.schedules.remove(key);
+    }
+}
+
 /// Reduce state of Nucleus according to action.
 /// Note: Can't block when dispatching action here because we are inside the reduce's mutex
 pub fn reduce(
@@ -423,6 +1067,10 @@ pub fn reduce(
             let mut new_nucleus_state: NucleusState = (*old_state).clone();
 
             match *nucleus_action {
+                Action::SetAgentId(ref agent_id) => {
+                    new_nucleus_state.agent_id = Some(agent_id.clone());
+                }
+
                 Action::ReturnInitializationResult(ref result) => {
                     reduce_rir(&mut new_nucleus_state, result);
                 }
@@ -445,11 +1093,71 @@ pub fn reduce(
                     new_nucleus_state
                         .ribosome_calls
                         .insert(result.call.clone(), Some(result.result.clone()));
+                    new_nucleus_state.call_monitor.finished(&result.call);
                 }
 
                 Action::ValidateEntry(ref es) => {
                     reduce_ve(&mut new_nucleus_state, es);
                 }
+
+                Action::Schedule(ref schedule) => {
+                    new_nucleus_state
+                        .schedules
+                        .insert(schedule.key(), schedule.clone());
+                }
+
+                Action::CancelSchedule(ref key) => {
+                    new_nucleus_state.schedules.remove(key);
+                }
+
+                Action::FireSchedule(ref key, tick) => {
+                    reduce_fs(&mut new_nucleus_state, key, tick, action_channel);
+                }
+
+                Action::GrantCapability(ref grant) => {
+                    new_nucleus_state
+                        .cap_grants
+                        .insert(grant.secret.clone(), grant.clone());
+                }
+
+                Action::RevokeCapability(ref secret) => {
+                    new_nucleus_state.cap_grants.remove(secret);
+                }
+
+                Action::ValidateTransaction(_) => {}
+
+                Action::SetWasmPageLimit(limit) => {
+                    new_nucleus_state.max_wasm_pages = limit;
+                }
+
+                Action::SetConcurrentReadLimit(limit) => {
+                    new_nucleus_state.call_gate.set_max_reads(limit);
+                }
+
+                Action::ReportLimitExceeded(ref exceeded) => {
+                    new_nucleus_state.signal_bus.emit(&exceeded.to_signal());
+                }
+
+                Action::AbandonCalls => {
+                    for result in new_nucleus_state.ribosome_calls.values_mut() {
+                        if result.is_none() {
+                            *result = Some(Err(HolochainError::new("the call was abandoned")));
+                        }
+                    }
+                    new_nucleus_state.call_monitor.clear();
+                }
+
+                Action::ReloadDna(ref dna) => {
+                    if new_nucleus_state.status == NucleusStatus::Initialized {
+                        new_nucleus_state.dna = Some(dna.clone());
+                        ::instance::dispatch_action(
+                            action_channel,
+                            state::Action::Agent(::agent::Action::SetRateBuckets(
+                                rates::buckets(dna),
+                            )),
+                        );
+                    }
+                }
             }
             Arc::new(new_nucleus_state)
         }
@@ -462,7 +1170,28 @@ mod tests {
     use super::{
         super::{nucleus::Action::*, state::Action::*}, *,
     };
-    use std::sync::mpsc::channel;
+    use limits;
+    use nucleus::module_cache::tests::test_module_code;
+    use parity_wasm::{self, builder};
+    use std::{sync::mpsc::channel, time::Duration};
+
+    /// wasm code exporting an empty post_commit_dispatch function
+    fn test_post_commit_code() -> Vec<u8> {
+        let module = builder::module()
+            .function()
+            .signature()
+            .build()
+            .body()
+            .build()
+            .build()
+            .export()
+            .field("post_commit_dispatch")
+            .internal()
+            .func(0)
+            .build()
+            .build();
+        parity_wasm::serialize(module).unwrap()
+    }
 
     #[test]
     fn can_instantiate_nucleus_state() {
@@ -495,6 +1224,223 @@ mod tests {
         assert_eq!(reduced_nucleus.status(), NucleusStatus::Initializing);
     }
 
+    #[test]
+    fn schedules_fire_at_most_once_per_tick() {
+        let schedule = scheduler::tests::test_schedule();
+        let nucleus = Arc::new(NucleusState::new());
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let reduce_action =
+            |nucleus, action| reduce(nucleus, &Nucleus(action), &sender, &tx_observer);
+
+        let nucleus = reduce_action(nucleus, Schedule(schedule.clone()));
+        assert_eq!(Some(&schedule), nucleus.schedules().get(&schedule.key()));
+
+        let nucleus = reduce_action(nucleus, FireSchedule(schedule.key(), 1));
+        let nucleus = reduce_action(nucleus, FireSchedule(schedule.key(), 1));
+        let nucleus = reduce_action(nucleus, FireSchedule(schedule.key(), 2));
+        let calls = receiver.try_iter().collect::<Vec<state::ActionWrapper>>();
+        assert_eq!(2, calls.len());
+        match calls[0].action {
+            Nucleus(ExecuteZomeFunction(ref call)) => assert_eq!("main", call.function),
+            _ => panic!("expected a zome function call"),
+        }
+        assert_eq!(Some(2), nucleus.schedules()[&schedule.key()].last_tick);
+
+        let nucleus = reduce_action(nucleus, CancelSchedule(schedule.key()));
+        assert!(nucleus.schedules().is_empty());
+        reduce_action(nucleus, FireSchedule(schedule.key(), 3));
+        assert_eq!(0, receiver.try_iter().count());
+    }
+
+    #[test]
+    fn schedules_once_removed_once_fired() {
+        let schedule = scheduler::Schedule::once("test_zome", "expire", "test_cap", "main", "", 30);
+        let nucleus = Arc::new(NucleusState::new());
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let reduce_action =
+            |nucleus, action| reduce(nucleus, &Nucleus(action), &sender, &tx_observer);
+
+        let nucleus = reduce_action(nucleus, Schedule(schedule.clone()));
+        let nucleus = reduce_action(nucleus, FireSchedule(schedule.key(), 1));
+        assert!(nucleus.schedules().is_empty());
+        reduce_action(nucleus, FireSchedule(schedule.key(), 2));
+        assert_eq!(1, receiver.try_iter().count());
+    }
+
+    #[test]
+    fn limits_exceeded_are_signalled() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let reduce_action =
+            |nucleus, action| reduce(nucleus, &Nucleus(action), &sender, &tx_observer);
+
+        let nucleus = reduce_action(Arc::new(NucleusState::new()), SetWasmPageLimit(Some(4)));
+        assert_eq!(Some(4), nucleus.max_wasm_pages());
+
+        let signals = nucleus.signal_bus().subscribe();
+        let exceeded = limits::tests::test_limit_exceeded();
+        reduce_action(nucleus, ReportLimitExceeded(exceeded.clone()));
+        assert_eq!(exceeded.to_signal(), signals.try_recv().unwrap());
+    }
+
+    #[test]
+    fn agent_id_is_set() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let agent_id = AgentId::new("alice", Some("invite code"));
+        let nucleus = reduce(
+            Arc::new(NucleusState::new()),
+            &Nucleus(SetAgentId(agent_id.clone())),
+            &sender,
+            &tx_observer,
+        );
+        assert_eq!(Some(&agent_id), nucleus.agent_id());
+        assert_eq!(None, NucleusState::new().agent_id());
+    }
+
+    #[test]
+    fn dna_is_reloaded_once_initialized() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let mut dna = Dna::new();
+        dna.properties = json!({"max_post_length": 280});
+        let reload = |nucleus: NucleusState| {
+            reduce(
+                Arc::new(nucleus),
+                &Nucleus(ReloadDna(dna.clone())),
+                &sender,
+                &tx_observer,
+            )
+        };
+        assert_eq!(None, reload(NucleusState::new()).dna());
+
+        let mut nucleus = NucleusState::new();
+        nucleus.dna = Some(Dna::new());
+        nucleus.status = NucleusStatus::Initialized;
+        assert_eq!(Some(dna.clone()), reload(nucleus).dna());
+    }
+
+    #[test]
+    fn calls_can_be_abandoned() {
+        let (sender, _receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let call = FunctionCall::new("test_zome", "test_cap", "main", "{}");
+        let mut nucleus = NucleusState::new();
+        nucleus.ribosome_calls.insert(call.clone(), None);
+        nucleus.call_monitor.started(&call);
+        assert_eq!(1, nucleus.calls_in_flight());
+
+        let nucleus = reduce(
+            Arc::new(nucleus),
+            &Nucleus(AbandonCalls),
+            &sender,
+            &tx_observer,
+        );
+        assert_eq!(0, nucleus.calls_in_flight());
+        assert!(nucleus.ribosome_call_result(&call).unwrap().is_err());
+        assert!(
+            nucleus
+                .call_monitor()
+                .over_budget(Duration::from_millis(0))
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn post_commit_follows_commits() {
+        let call = FunctionCall::new("test_zome", "test_cap", "main", "{}");
+        let code = test_post_commit_code();
+        let headers = vec!["QmHeader".to_string()];
+
+        let post_commit = post_commit_call(&call, Some(&code), &headers).unwrap();
+        assert_eq!("test_zome", post_commit.zome);
+        assert_eq!(
+            ReservedCapabilityNames::LifeCycle.as_str(),
+            post_commit.capability
+        );
+        assert_eq!("post_commit", post_commit.function);
+        assert_eq!(
+            r#"{"header_addresses":["QmHeader"]}"#,
+            post_commit.parameters
+        );
+
+        // nothing committed
+        assert_eq!(None, post_commit_call(&call, Some(&code), &[]));
+        // no lifecycle capability or no post_commit in it
+        assert_eq!(None, post_commit_call(&call, None, &headers));
+        assert_eq!(
+            None,
+            post_commit_call(&call, Some(&test_module_code()), &headers)
+        );
+        // commits made by post_commit itself
+        assert_eq!(None, post_commit_call(&post_commit, Some(&code), &headers));
+    }
+
+    #[test]
+    fn call_context_names_the_caller() {
+        let call = FunctionCall::new("test_zome", "test_cap", "main", "{}");
+        let context = call.context(call.requested_at);
+        assert_eq!(None, context.caller);
+        assert_eq!(None, context.cap_secret);
+        assert_eq!(call.requested_at, context.started_at);
+
+        let remote = call.clone().with_caller("bob", Some("secret".to_string()));
+        let remote_context = remote.context(remote.requested_at + 5);
+        assert_eq!(Some("bob".to_string()), remote_context.caller);
+        assert_eq!(Some("secret".to_string()), remote_context.cap_secret);
+        assert_eq!(context.call_id, remote_context.call_id);
+        assert_eq!(remote.requested_at + 5, remote_context.started_at);
+        assert_ne!(
+            context.call_id,
+            FunctionCall::new("test_zome", "test_cap", "main", "{}")
+                .context(0)
+                .call_id
+        );
+    }
+
+    #[test]
+    fn initialize_fails_on_unimplemented_trait() {
+        let dna = traits::tests::test_trait_dna(&["test", "missing"]);
+        let action = Nucleus(InitApplication(dna));
+        let nucleus = Arc::new(NucleusState::new());
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+
+        reduce(nucleus.clone(), &action, &sender, &tx_observer);
+        let result = receiver.recv().unwrap_or_else(|_| panic!("channel failed"));
+
+        assert_eq!(
+            Nucleus(ReturnInitializationResult(Some(
+                "zome 'test_zome' implements trait 'test_trait' but does not export function 'missing'"
+                    .to_string()
+            ))),
+            result.action
+        );
+    }
+
+    #[test]
+    fn initialize_fails_on_unsupported_host_api() {
+        let mut dna = Dna::new();
+        dna.host_api_version = 99;
+        let action = Nucleus(InitApplication(dna));
+        let nucleus = Arc::new(NucleusState::new());
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+
+        reduce(nucleus.clone(), &action, &sender, &tx_observer);
+        let result = receiver.recv().unwrap_or_else(|_| panic!("channel failed"));
+
+        assert_eq!(
+            Nucleus(ReturnInitializationResult(Some(
+                "the DNA targets host API version 99, this host supports versions 1 to 2"
+                    .to_string()
+            ))),
+            result.action
+        );
+    }
+
     #[test]
     fn can_reduce_return_init_result_action() {
         let dna = Dna::new();
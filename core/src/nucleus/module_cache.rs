@@ -0,0 +1,242 @@
+use error::HolochainError;
+use hash::bytes_to_b58_hash;
+use multihash::Hash;
+use std::{
+    collections::{HashMap, VecDeque}, fmt, sync::{Arc, Mutex},
+};
+use wasmi::Module;
+
+/// default number of compiled modules kept around by a ModuleCache
+pub const RIBOSOME_MODULE_CACHE_DEFAULT_SIZE: usize = 32;
+
+/// the key compiled modules are cached under
+pub fn code_hash(code: &[u8]) -> String {
+    bytes_to_b58_hash(code, Hash::SHA2256)
+}
+
+struct Inner {
+    capacity: usize,
+    modules: HashMap<String, Arc<Module>>,
+    /// least recently used first
+    recent: VecDeque<String>,
+    hits: usize,
+    misses: usize,
+}
+
+impl Inner {
+    fn touch(&mut self, hash: &str) {
+        self.recent.retain(|h| h != hash);
+        self.recent.push_back(hash.to_string());
+    }
+
+    fn evict(&mut self) {
+        while self.modules.len() > self.capacity {
+            match self.recent.pop_front() {
+                Some(hash) => {
+                    self.modules.remove(&hash);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+/// LRU cache of compiled and validated WASM modules keyed by the hash of their code
+/// compiling is the expensive part of a zome call so it should happen once per module, not once
+/// per call
+/// the cache is a cheap handle, clones share the same underlying modules
+#[derive(Clone)]
+pub struct ModuleCache {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl Default for ModuleCache {
+    fn default() -> Self {
+        ModuleCache::new(RIBOSOME_MODULE_CACHE_DEFAULT_SIZE)
+    }
+}
+
+impl PartialEq for ModuleCache {
+    fn eq(&self, other: &ModuleCache) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for ModuleCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("ModuleCache")
+            .field("capacity", &inner.capacity)
+            .field("modules", &inner.recent)
+            .field("hits", &inner.hits)
+            .field("misses", &inner.misses)
+            .finish()
+    }
+}
+
+impl ModuleCache {
+    /// build a new, empty ModuleCache holding at most capacity modules
+    /// a capacity of 0 disables caching
+    pub fn new(capacity: usize) -> ModuleCache {
+        ModuleCache {
+            inner: Arc::new(Mutex::new(Inner {
+                capacity,
+                modules: HashMap::new(),
+                recent: VecDeque::new(),
+                hits: 0,
+                misses: 0,
+            })),
+        }
+    }
+
+    /// the compiled module for the given code, compiling and caching it on a miss
+    pub fn get_or_compile(&self, code: &[u8]) -> Result<Arc<Module>, HolochainError> {
+        let hash = code_hash(code);
+        {
+            let mut inner = self.inner.lock().unwrap();
+            if let Some(module) = inner.modules.get(&hash).cloned() {
+                inner.hits += 1;
+                inner.touch(&hash);
+                return Ok(module);
+            }
+            inner.misses += 1;
+        }
+
+        // compile without holding the lock so other calls are not held up
+        let module = Arc::new(
+            Module::from_buffer(code)
+                .map_err(|e| HolochainError::ErrorGeneric(format!("{}", e)))?,
+        );
+
+        let mut inner = self.inner.lock().unwrap();
+        if inner.capacity > 0 {
+            inner.modules.insert(hash.clone(), Arc::clone(&module));
+            inner.touch(&hash);
+            inner.evict();
+        }
+        Ok(module)
+    }
+
+    /// compile and cache code ahead of the first call to it
+    pub fn precompile(&self, code: &[u8]) -> Result<(), HolochainError> {
+        self.get_or_compile(code).map(|_| ())
+    }
+
+    /// true if a compiled module for the given code hash is cached
+    pub fn contains(&self, hash: &str) -> bool {
+        self.inner.lock().unwrap().modules.contains_key(hash)
+    }
+
+    /// number of cached modules
+    pub fn len(&self) -> usize {
+        self.inner.lock().unwrap().modules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.lock().unwrap().capacity
+    }
+
+    /// change the max number of cached modules, evicting the least recently used as needed
+    pub fn set_capacity(&self, capacity: usize) {
+        let mut inner = self.inner.lock().unwrap();
+        inner.capacity = capacity;
+        inner.evict();
+    }
+
+    /// (hits, misses) since the cache was created
+    pub fn stats(&self) -> (usize, usize) {
+        let inner = self.inner.lock().unwrap();
+        (inner.hits, inner.misses)
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{code_hash, ModuleCache};
+
+    /// hand assembled module exporting memory and a `test_dispatch` function that returns 0
+    /// written out as bytes so it does not need wabt to build
+    #[rustfmt::skip]
+    pub fn test_module_code() -> Vec<u8> {
+        vec![
+            // magic and version
+            0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+            // type section: (func (param i32 i32) (result i32))
+            0x01, 0x07, 0x01, 0x60, 0x02, 0x7f, 0x7f, 0x01, 0x7f,
+            // function section: one function of type 0
+            0x03, 0x02, 0x01, 0x00,
+            // memory section: one memory of at least 1 page
+            0x05, 0x03, 0x01, 0x00, 0x01,
+            // export section: "memory" and "test_dispatch"
+            0x07, 0x1a, 0x02,
+            0x06, 0x6d, 0x65, 0x6d, 0x6f, 0x72, 0x79, 0x02, 0x00,
+            0x0d, 0x74, 0x65, 0x73, 0x74, 0x5f, 0x64, 0x69, 0x73, 0x70, 0x61, 0x74, 0x63, 0x68,
+            0x00, 0x00,
+            // code section: i32.const 0
+            0x0a, 0x06, 0x01, 0x04, 0x00, 0x41, 0x00, 0x0b,
+        ]
+    }
+
+    /// same as test_module_code() but with a different memory size so it hashes differently
+    pub fn test_module_code_b() -> Vec<u8> {
+        let mut code = test_module_code();
+        // bump the memory minimum from 1 to 2 pages
+        code[25] = 0x02;
+        code
+    }
+
+    #[test]
+    /// modules are compiled once then served from the cache
+    fn get_or_compile() {
+        let cache = ModuleCache::new(2);
+        assert!(cache.is_empty());
+
+        cache.get_or_compile(&test_module_code()).unwrap();
+        assert_eq!((0, 1), cache.stats());
+        assert!(cache.contains(&code_hash(&test_module_code())));
+
+        cache.get_or_compile(&test_module_code()).unwrap();
+        assert_eq!((1, 1), cache.stats());
+        assert_eq!(1, cache.len());
+    }
+
+    #[test]
+    /// invalid code is an error and is not cached
+    fn invalid_code() {
+        let cache = ModuleCache::default();
+        assert!(cache.get_or_compile(&[0x00, 0x01, 0x02]).is_err());
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    /// the least recently used module is evicted once over capacity
+    fn eviction() {
+        let cache = ModuleCache::new(1);
+        cache.precompile(&test_module_code()).unwrap();
+        cache.precompile(&test_module_code_b()).unwrap();
+
+        assert_eq!(1, cache.len());
+        assert!(!cache.contains(&code_hash(&test_module_code())));
+        assert!(cache.contains(&code_hash(&test_module_code_b())));
+
+        cache.set_capacity(0);
+        assert!(cache.is_empty());
+        cache.precompile(&test_module_code()).unwrap();
+        assert!(cache.is_empty());
+    }
+
+    #[test]
+    /// clones share the same cached modules
+    fn shared() {
+        let cache = ModuleCache::default();
+        let clone = cache.clone();
+        clone.precompile(&test_module_code()).unwrap();
+        assert_eq!(1, cache.len());
+        assert_eq!(cache, clone);
+        assert_ne!(cache, ModuleCache::default());
+    }
+}
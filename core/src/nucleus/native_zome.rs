@@ -0,0 +1,160 @@
+//! native zomes are zomes implemented in Rust rather than WASM, for system functionality like
+//! anchors or DPKI that shouldn't pay for the ribosome
+//! they are registered with the nucleus under their zome name and called like any other zome,
+//! lifecycle functions like genesis() included, taking turns at the call gate with the calls that
+//! may write and getting the host functions through the Runtime of their call
+//! a native zome shadows a zome of the DNA with the same name
+
+use error::HolochainError;
+use instance::Observer;
+use nucleus::{
+    ribosome::{HostContext, Runtime}, FunctionCall,
+};
+use state;
+use std::{
+    collections::BTreeMap, fmt, sync::{mpsc::Sender, Arc, RwLock},
+};
+
+/// a zome implemented in Rust, see the module docs
+pub trait NativeZome: Send + Sync {
+    /// call a function of a capability with its parameters, returning its result
+    /// capabilities the zome doesn't have are CapabilityNotFound and functions it doesn't have
+    /// ZomeFunctionNotFound, so lifecycle functions it doesn't implement are skipped as they are
+    /// for WASM zomes
+    /// what it commits through the runtime goes on the chain once it returned Ok
+    fn call(
+        &self,
+        runtime: &mut Runtime,
+        capability: &str,
+        function: &str,
+        parameters: &str,
+    ) -> Result<String, HolochainError>;
+}
+
+/// the native zomes of an instance by zome name
+/// the registry is a cheap handle, clones share the same zomes
+#[derive(Clone, Default)]
+pub struct NativeZomes {
+    zomes: Arc<RwLock<BTreeMap<String, Arc<dyn NativeZome>>>>,
+}
+
+impl PartialEq for NativeZomes {
+    fn eq(&self, other: &NativeZomes) -> bool {
+        Arc::ptr_eq(&self.zomes, &other.zomes)
+    }
+}
+
+impl fmt::Debug for NativeZomes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("NativeZomes")
+            .field("zomes", &self.names())
+            .finish()
+    }
+}
+
+impl NativeZomes {
+    /// register (or replace) the native zome called name
+    pub fn register(&self, name: &str, zome: Arc<dyn NativeZome>) {
+        self.zomes.write().unwrap().insert(name.to_string(), zome);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn NativeZome>> {
+        self.zomes.read().unwrap().get(name).cloned()
+    }
+
+    /// the names of the native zomes, sorted
+    pub fn names(&self) -> Vec<String> {
+        self.zomes.read().unwrap().keys().cloned().collect()
+    }
+}
+
+/// run a call to a native zome in the thread it is made from, putting what it committed on the
+/// chain once it returned
+pub fn call(
+    zome: &dyn NativeZome,
+    function_call: &FunctionCall,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+    host: &HostContext,
+) -> Result<String, HolochainError> {
+    let mut runtime = Runtime::without_wasm(action_channel, observer_channel, host);
+    let result = zome.call(
+        &mut runtime,
+        &function_call.capability,
+        &function_call.function,
+        &function_call.parameters,
+    )?;
+    runtime.complete()?;
+    Ok(result)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::entry::Entry;
+    use holochain_dna::Dna;
+    use instance::Instance;
+    use nucleus::{call_and_wait_for_result, Action::InitApplication, NucleusStatus};
+    use state::Action::Nucleus;
+    use std::{sync::Mutex, thread, time::Duration};
+
+    /// a native zome committing what count() is called with in "counter" entries, noting the
+    /// functions called
+    #[derive(Default)]
+    pub struct Counter {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl NativeZome for Counter {
+        fn call(
+            &self,
+            runtime: &mut Runtime,
+            capability: &str,
+            function: &str,
+            parameters: &str,
+        ) -> Result<String, HolochainError> {
+            self.calls.lock().unwrap().push(function.to_string());
+            match (capability, function) {
+                ("hc_lifecycle", "genesis") => Ok(String::new()),
+                ("main", "count") => {
+                    let hash = runtime.commit(&Entry::new("counter", parameters));
+                    Ok(runtime.get(&hash).unwrap().content().to_string())
+                }
+                ("hc_lifecycle", _) | ("main", _) => {
+                    Err(HolochainError::ZomeFunctionNotFound(function.to_string()))
+                }
+                _ => Err(HolochainError::CapabilityNotFound(capability.to_string())),
+            }
+        }
+    }
+
+    #[test]
+    /// native zomes are called like the zomes of the DNA and commit through their runtime
+    fn native_calls() {
+        let counter = Arc::new(Counter::default());
+        let mut instance = Instance::new();
+        instance.register_native_zome("counter", counter.clone());
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Nucleus(InitApplication(Dna::new())));
+        while instance.state().nucleus().status() == NucleusStatus::Initializing {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(NucleusStatus::Initialized, instance.state().nucleus().status());
+        assert_eq!(
+            vec!["genesis".to_string(), "init".to_string()],
+            *counter.calls.lock().unwrap()
+        );
+
+        let call = FunctionCall::new("counter", "main", "count", "1");
+        assert_eq!(Ok("1".to_string()), call_and_wait_for_result(call, &mut instance));
+        let top = instance.state().agent().top_pair().unwrap();
+        assert_eq!(&Entry::new("counter", "1"), top.entry());
+
+        let missing = FunctionCall::new("counter", "main", "missing", "");
+        assert_eq!(
+            Err(HolochainError::ZomeFunctionNotFound("missing".to_string())),
+            call_and_wait_for_result(missing, &mut instance)
+        );
+        assert_eq!(Some(top), instance.state().agent().top_pair());
+    }
+}
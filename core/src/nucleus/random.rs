@@ -0,0 +1,59 @@
+//! WASM has no entropy of its own, so zomes get random bytes from the host, e.g. for nonces and
+//! salts, from the operating system's generator
+//! zomes that need randomness every node running them agrees on, e.g. to shuffle in validation,
+//! get bytes stretched from a seed of their choosing instead, see network::sealing::expand()
+
+use error::HolochainError;
+use network::sealing;
+use rand::{OsRng, Rng};
+
+/// most bytes a zome gets in one call, so the JSON written back fits in its memory
+pub const MAX_RANDOM_BYTES: usize = 4096;
+
+fn check_len(len: usize) -> Result<(), HolochainError> {
+    if len > MAX_RANDOM_BYTES {
+        return Err(HolochainError::ErrorGeneric(format!(
+            "{} random bytes requested, at most {} are given at once",
+            len, MAX_RANDOM_BYTES
+        )));
+    }
+    Ok(())
+}
+
+/// len bytes from the operating system's generator
+pub fn random_bytes(len: usize) -> Result<Vec<u8>, HolochainError> {
+    check_len(len)?;
+    let mut rng = OsRng::new().map_err(|e| HolochainError::ErrorGeneric(e.to_string()))?;
+    let mut bytes = vec![0; len];
+    rng.fill_bytes(&mut bytes);
+    Ok(bytes)
+}
+
+/// len bytes stretched from the seed, the same for the same seed on every node
+pub fn seeded_random(seed: &str, len: usize) -> Result<Vec<u8>, HolochainError> {
+    check_len(len)?;
+    Ok(sealing::expand(seed.as_bytes(), len))
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    #[test]
+    fn random() {
+        let bytes = random_bytes(32).unwrap();
+        assert_eq!(32, bytes.len());
+        assert_ne!(bytes, random_bytes(32).unwrap());
+        assert!(random_bytes(0).unwrap().is_empty());
+        assert!(random_bytes(MAX_RANDOM_BYTES).is_ok());
+        assert!(random_bytes(MAX_RANDOM_BYTES + 1).is_err());
+    }
+
+    #[test]
+    fn seeded() {
+        let bytes = seeded_random("round 1", 32).unwrap();
+        assert_eq!(bytes, seeded_random("round 1", 32).unwrap());
+        assert_ne!(bytes, seeded_random("round 2", 32).unwrap());
+        assert!(seeded_random("round 1", MAX_RANDOM_BYTES + 1).is_err());
+    }
+}
@@ -5,13 +5,62 @@ extern crate wabt;
 use instance::Observer;
 use serde_json;
 use state;
-use std::sync::mpsc::Sender;
+use std::{
+    collections::BTreeMap, convert::TryFrom, fmt, ops::Range,
+    sync::{mpsc::{channel, Sender}, Arc},
+};
+use agent::{
+    blocks, checkpoints::{self, HeadVerification}, devices::{self, DEVICE_SIGNING_KEY_PATH},
+    groups::{GroupSecret, GroupSecrets}, keystore::Keystore, transaction::Transaction,
+};
+use anchors::Path;
+use dht::{
+    activity::{self, AgentActivity}, aspect::Aspect, entries::GetEntryOptions,
+    links::{GetLinksOptions, LinkPage}, read::{self, Read, ReadConsistency}, DhtState,
+};
+use error::HolochainError;
+use hash::cid::{self, Codec};
+use hash_table::{entry::Entry, field_index::IndexQuery, header::Header, pair::Pair};
+use index::{self, SearchIndex};
+use limits::{self, LimitExceeded, Resource};
+use holochain_dna::{AddressFormat, Dna, HOST_API_VERSION};
+use holochain_serialization::{
+    v2::{
+        AnchorInput, AnchorOutput, CallRemoteInput, CancelScheduleInput, CommitInput,
+        CommitOutput, CommitTransactionInput, CommitTransactionOutput, EmitSignalInput,
+        KvSetInput, LinkEndInput, QueryIndexInput, QueryIndexOutput, RemoteSignalInput,
+        ScheduleInput, ScheduleOnceInput, SearchInput, SearchOutput,
+    },
+    JsonString,
+};
+use logger::{LogLevel, ZomeLogMessage, ZomeLogger};
+use network::{
+    direct_message::DirectMessenger, outbox::{Outbox, Outgoing, Priority},
+    presence::Presence, remote_signal::RemoteSignalSender, sealing::PublicKey,
+};
+use nucleus::{
+    module_cache::ModuleCache, random, scheduler::{schedule_key, unix_now, Schedule},
+    scratch::ScratchSpace, CallContext, FunctionCall,
+};
+use rust_base58::ToBase58;
+use serde;
+use signal::{Signal, SignalBus};
+use trace::{TraceContext, Tracer};
+use validation::{links::Link, receipts::ReceiptStore};
 
 use wasmi::{
-    self, Error as InterpreterError, Externals, FuncInstance, FuncRef, ImportsBuilder, MemoryRef,
-    ModuleImportResolver, ModuleInstance, RuntimeArgs, RuntimeValue, Signature, Trap, ValueType,
+    self, memory_units::Pages, Error as InterpreterError, Externals, FuncInstance, FuncRef,
+    HostError, ImportsBuilder, MemoryInstance, MemoryRef, ModuleImportResolver, ModuleInstance,
+    RuntimeArgs, RuntimeValue, Signature, Trap, TrapKind, ValueType,
 };
 
+/// the first host API, with print and commit only
+pub const HOST_API_V1: u32 = 1;
+/// print replaced by log, along with every host function added until versions were declared
+pub const HOST_API_V2: u32 = 2;
+/// the oldest host API version DNAs may target
+pub const MIN_HOST_API_VERSION: u32 = HOST_API_V1;
+
 //--------------------------------------------------------------------------------------------------
 // HC API FUNCTION IMPLEMENTATIONS
 //--------------------------------------------------------------------------------------------------
@@ -19,141 +68,2290 @@ use wasmi::{
 /// Enumeration of all possible return codes that an HC API function can return
 #[repr(usize)]
 #[allow(non_camel_case_types)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum HcApiReturnCode {
     SUCCESS = 0,
     ERROR_SERDE_JSON,
+    ERROR_CALL_REMOTE,
+    ERROR_TRANSACTION,
+    ERROR_COMMIT,
+    ERROR_REMOTE_SIGNAL,
+    ERROR_NOT_INDEXED,
+    ERROR_GROUP_SECRET,
+    ERROR_KEYSTORE,
+    ERROR_RANDOM,
+    /// the output doesn't fit in the allocation of the guest, see write_output()
+    ERROR_OUTPUT_TOO_LARGE,
 }
 
 /// List of all the API functions available in Nucleus
 #[repr(usize)]
+#[allow(clippy::upper_case_acronyms, non_camel_case_types)]
 enum HcApiFuncIndex {
-    /// Print debug information in the console
-    /// print(...)
-    PRINT = 0,
+    /// Log a structured message to the host logger
+    /// log(level : String, target : String, payload : Json)
+    LOG = 0,
     /// Commit an entry to source chain
     /// commit(entry_type : String, entry_content : String) -> Hash
     COMMIT,
+    /// Get the value of a DNA property
+    /// property(name : String) -> Json
+    PROPERTY,
+    /// Call a function of the zome every interval, see nucleus::scheduler
+    /// schedule(id : String, capability : String, function : String, parameters : String,
+    ///          interval_secs : u64, jitter_secs : u64)
+    SCHEDULE,
+    /// Stop calling a function scheduled by the zome
+    /// cancel_schedule(id : String)
+    CANCEL_SCHEDULE,
+    /// Push an event to the UIs subscribed to the instance's signals
+    /// emit_signal(name : String, payload : Json)
+    EMIT_SIGNAL,
+    /// Call a zome function on another agent's node, see network::direct_message
+    /// call_remote(agent : String, zome : String, capability : String, function : String,
+    ///             cap_secret : Option<String>, parameters : String) -> String
+    CALL_REMOTE,
+    /// Commit the anchor at a path along with everything indexing it, see anchors
+    /// anchor(path : String) -> Hash
+    ANCHOR,
+    /// Get a page of the links from an entry, see dht::links
+    /// get_links(base : String, tag : Option<String>, tag_prefix : Option<String>,
+    ///           order : String, limit : Option<usize>, cursor : Option<String>) -> LinkPage
+    GET_LINKS,
+    /// Store a value in the instance's scratch space, see nucleus::scratch
+    /// kv_set(key : String, value : Json)
+    KV_SET,
+    /// Get a value from the instance's scratch space
+    /// kv_get(key : String) -> Json
+    KV_GET,
+    /// Commit several entries and links at once, see agent::transaction
+    /// commit_transaction(entries : Vec<Entry>, links : Vec<Link>) -> Vec<Hash>
+    COMMIT_TRANSACTION,
+    /// Get an entry from the DHT, or everything known about it, see dht::entries
+    /// get_entry(address : String, status_request : String, result_type : String)
+    ///     -> Option<Entry> | EntryDetails
+    GET_ENTRY,
+    /// Get the validation receipts holders sent for an entry the agent published, see
+    /// validation::receipts
+    /// get_validation_receipts(address : String) -> Vec<ValidationReceipt>
+    GET_VALIDATION_RECEIPTS,
+    /// Send a fire and forget signal to other agents, see network::remote_signal
+    /// remote_signal(agents : Vec<String>, payload : Json)
+    REMOTE_SIGNAL,
+    /// Get the agents seen online lately, see network::presence
+    /// get_online_agents() -> Vec<String>
+    GET_ONLINE_AGENTS,
+    /// Block an agent, dropping its messages and leaving its data out of filtered reads, see
+    /// agent::blocks
+    /// block_agent(address : String)
+    BLOCK_AGENT,
+    /// Lift the block of an agent, see agent::blocks
+    /// unblock_agent(address : String)
+    UNBLOCK_AGENT,
+    /// Get the context of the call, e.g. who is calling, see nucleus::CallContext
+    /// call_context() -> CallContext
+    CALL_CONTEXT,
+    /// Search the content of the searchable entries committed and held, see index
+    /// search(query : String, types : Vec<String>, offset : usize, limit : Option<usize>)
+    ///   -> (Vec<Hash>, Option<usize>)
+    SEARCH,
+    /// Look up the entries committed and held by the value of a field their type declares
+    /// indexed, see hash_table::field_index
+    /// query_index(entry_type : String, field : String, query : IndexQuery, offset : usize,
+    ///             limit : Option<usize>) -> (Vec<Hash>, Option<usize>)
+    QUERY_INDEX,
+    /// Get the headers an agent committed with their indices on its chain, as its neighborhood
+    /// holds them, see dht::activity
+    /// get_agent_activity(agent : String, from : u64, to : Option<u64>, limit : Option<u64>)
+    ///   -> AgentActivity
+    GET_AGENT_ACTIVITY,
+    /// Compare the freshest checkpoint of an agent to its chain as its neighborhood holds it, see
+    /// agent::checkpoints
+    /// verify_agent_head(agent : String) -> HeadVerification
+    VERIFY_AGENT_HEAD,
+    /// Create a secret shared with a group of agents, sent to each of them, see agent::groups
+    /// create_group_secret(members : Vec<String>) -> String
+    CREATE_GROUP_SECRET,
+    /// Encrypt a payload with a group secret known to the agent
+    /// group_encrypt(group : String, payload : String) -> String
+    GROUP_ENCRYPT,
+    /// Decrypt a payload encrypted with a group secret known to the agent
+    /// group_decrypt(group : String, ciphertext : String) -> String
+    GROUP_DECRYPT,
+    /// Get the public key of the key pair the keystore derives along a path, see agent::keystore
+    /// derive_key(path : String) -> String
+    DERIVE_KEY,
+    /// Get the key the key pair derived along a path agrees with another agent's public key
+    /// ecdh(path : String, public_key : String) -> String
+    ECDH,
+    /// Get random bytes from the host, see nucleus::random
+    /// random_bytes(n : usize) -> Vec<u8>
+    RANDOM_BYTES,
+    /// Get bytes stretched from a seed, the same on every node
+    /// seeded_random(seed : String, n : usize) -> Vec<u8>
+    SEEDED_RANDOM,
+    /// Get the host's clock, the one schedules and call contexts go by
+    /// sys_time() -> u64
+    SYS_TIME,
+    /// Call a function of the zome once after a delay, see nucleus::scheduler
+    /// schedule_once(id : String, capability : String, function : String, parameters : String,
+    ///               delay_secs : u64)
+    SCHEDULE_ONCE,
+    /// Print a number, the logging of the first host API, kept for DNAs targeting HOST_API_V1
+    /// print(value : i32)
+    PRINT,
     // Add new API function index here
     // ...
 }
 
+/// HcApiFuncIndex::LOG function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"level":"debug","target":"posts","payload":{"id":1}}"#
+/// Returns an HcApiReturnCode as I32
+fn invoke_log(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+    let message: ZomeLogMessage = match read_json_arg(runtime, args) {
+        Some(message) => message,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    runtime.host.logger.log(&runtime.host.zome, &message);
+    runtime.log_output.push(message);
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
 /// HcApiFuncIndex::PRINT function code
+/// args: [0] the number printed
+/// Shims the print of HOST_API_V1 as a log message at info level targeting "print"
 fn invoke_print(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
-    let arg: u32 = args.nth(0);
-    runtime.print_output.push(arg);
+    let value: i32 = args.nth(0);
+    let message = ZomeLogMessage {
+        level: LogLevel::Info,
+        target: "print".to_string(),
+        payload: json!(value),
+    };
+    runtime.host.logger.log(&runtime.host.zome, &message);
+    runtime.log_output.push(message);
     Ok(None)
 }
 
-/// Struct for input data received when Commit API function is invoked
-#[derive(Deserialize, Default, Debug)]
-struct CommitInputStruct {
-    entry_type_name: String,
-    entry_content: String,
+/// The priority the publish waits at in the outbox, that of the call unless it is more urgent
+/// of its own, see Priority::of()
+fn priority(runtime: &Runtime, publish: &Outgoing) -> Priority {
+    match Priority::of(publish) {
+        Priority::Normal => runtime.host.priority,
+        urgent => urgent,
+    }
+}
+
+/// Check the publishes of the entries fit in the outbox, see publish
+/// Headers don't change the priority of a publish, so the content tells it before the commit
+fn check_outbox_room(runtime: &Runtime, entries: &[Entry]) -> Result<(), LimitExceeded> {
+    let mut needed: BTreeMap<Priority, usize> = BTreeMap::new();
+    for entry in entries.iter().filter(|entry| !blocks::is_private(entry)) {
+        let content = Outgoing::Publish(entry.key(), vec![Aspect::Content(entry.clone())]);
+        *needed.entry(priority(runtime, &content)).or_default() += 1;
+    }
+    for (root, entry) in entries
+        .iter()
+        .filter_map(|entry| devices::root_of(entry).map(|root| (root, entry)))
+    {
+        let device = Outgoing::Publish(root, vec![Aspect::Device(entry.clone())]);
+        *needed.entry(priority(runtime, &device)).or_default() += 1;
+    }
+    if let Some(agent) = runtime.host.messenger.address() {
+        let activity = Outgoing::Publish(agent, Vec::new());
+        *needed.entry(priority(runtime, &activity)).or_default() += 1;
+    }
+    needed
+        .into_iter()
+        .try_for_each(|(priority, count)| runtime.host.outbox.room(priority, count))
+}
+
+/// Queue the content and header of every pair for publishing to the nodes holding the entry,
+/// and the headers to the nodes holding the activity of the agent, see dht::activity
+/// Headers go out signed by the device key of the keystore, unsigned while it is locked, and
+/// device bindings and revocations to the nodes holding the activity of their root key, see
+/// agent::devices
+/// Entries committed while staging, e.g. during genesis, aren't published, nor are private
+/// entries like blocks
+fn publish(runtime: &Runtime, pairs: &[Pair]) {
+    let queue = |publish: Outgoing| {
+        // a publish that can't be persisted still goes out while the instance runs, the room
+        // for it was checked before the commit
+        let _ = runtime
+            .host
+            .outbox
+            .queue_at(priority(runtime, &publish), publish);
+    };
+    let headers: Vec<Header> = pairs
+        .iter()
+        .map(|pair| {
+            runtime
+                .host
+                .keystore
+                .sign_header(DEVICE_SIGNING_KEY_PATH, pair.header())
+                .unwrap_or_else(|_| pair.header().clone())
+        })
+        .collect();
+    for (pair, header) in pairs.iter().zip(&headers) {
+        if blocks::is_private(pair.entry()) {
+            continue;
+        }
+        let aspects = vec![
+            Aspect::Content(pair.entry().clone()),
+            Aspect::Header(header.clone()),
+        ];
+        queue(Outgoing::Publish(pair.entry().key(), aspects));
+        if let Some(root) = devices::root_of(pair.entry()) {
+            queue(Outgoing::Publish(root, vec![Aspect::Device(pair.entry().clone())]));
+        }
+    }
+    // the neighborhood of the agent holds the headers of all of them, private ones too, as
+    // its activity
+    if let Some(agent) = runtime.host.messenger.address() {
+        let activity = headers.into_iter().map(Aspect::Activity).collect();
+        queue(Outgoing::Publish(agent, activity));
+    }
+}
+
+/// an entry address as the DNA has them expressed to zomes, see holochain_dna::AddressFormat
+/// zomes can pass addresses in either format, see cid::normalize()
+fn express(runtime: &Runtime, address: String) -> String {
+    match runtime.host.address_format {
+        AddressFormat::Multihash => address,
+        AddressFormat::Cid => cid::to_cid(&address, Codec::Raw).unwrap_or(address),
+    }
+}
+
+/// the links of a page with their addresses and targets as the DNA has them expressed
+fn express_links(runtime: &Runtime, mut page: LinkPage) -> LinkPage {
+    for link in &mut page.links {
+        link.address = express(runtime, link.address.clone());
+        link.target = express(runtime, link.target.clone());
+    }
+    page
+}
+
+/// Commit an entry as part of the zome call, returns the entry hash
+/// The entry waits in runtime.pending, where reads later in the call see it, until the call
+/// completes and flush puts everything pending on the chain at once
+fn commit_entry(runtime: &mut Runtime, entry: &Entry) -> String {
+    // Trace the commit as part of the zome call, if the zome call is traced
+    let mut commit_span = runtime
+        .host
+        .trace
+        .as_ref()
+        .map(|(tracer, parent)| tracer.child_of("commit", parent));
+    runtime.pending.stage(entry);
+
+    // Hash entry
+    let hash_str = entry.hash();
+    if let Some(ref mut span) = commit_span {
+        span.tag("entry_type", entry.entry_type());
+        span.tag("hash", &hash_str);
+    }
+    hash_str
 }
 
 /// HcApiFuncIndex::COMMIT function code
 /// args: [0] memory offset where complex argument is stored
 /// args: [1] memory length of complex argument soted in memory
 /// expected complex argument: r#"{"entry_type_name":"post","entry_content":"hello"}"#
+/// Writes r#"{"hash":"Qm..."}"# in place of the argument, a CID if the DNA has addresses
+/// expressed as such, see express()
+/// The entry only goes on the chain once the call completes, see commit_entry
 /// Returns an HcApiReturnCode as I32
 fn invoke_commit(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
     assert!(args.len() == 2);
 
-    // Read complex argument serialized in memory
-    // TODO - #65 use our Malloced data instead
-    let mem_offset: u32 = args.nth(0);
-    let mem_len: u32 = args.nth(1);
-    let bin_arg = runtime
-        .memory
-        .get(mem_offset, mem_len as usize)
-        .expect("Successfully retrieve the arguments");
-
-    // deserialize complex argument
-    let arg = String::from_utf8(bin_arg).unwrap();
-    let res_entry: Result<CommitInputStruct, _> = serde_json::from_str(&arg);
-    // Exit on error
-    if res_entry.is_err() {
-        // Return Error code in i32 format
-        return Ok(Some(RuntimeValue::I32(
-            HcApiReturnCode::ERROR_SERDE_JSON as i32,
-        )));
-    }
-
-    // Create Chain Entry
-    let entry_input = res_entry.unwrap();
-    let entry =
-        ::hash_table::entry::Entry::new(&entry_input.entry_type_name, &entry_input.entry_content);
-
-    // Create Commit Action
-    let action_commit = ::state::Action::Agent(::agent::Action::Commit(entry.clone()));
-
-    // Send Action and block for result
-    // TODO #97 - Dispatch with observer so we can check if the action did its job without errors
-    ::instance::dispatch_action_and_wait(
+    let input: CommitInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    let entry = Entry::new(&input.entry_type_name, &input.entry_content);
+    let hash = commit_entry(runtime, &entry);
+    let output = CommitOutput {
+        hash: express(runtime, hash),
+    };
+    let code = write_json(runtime, args, &output);
+
+    // Return code in i32 format
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// Stage the entries, then the links of a transaction
+fn stage_transaction(input: CommitTransactionInput) -> Result<Transaction, String> {
+    let mut transaction = Transaction::new();
+    let mut keys = Vec::new();
+    for entry in input.entries {
+        keys.push(transaction.stage(&Entry::new(&entry.entry_type_name, &entry.entry_content)));
+    }
+    let resolve = |end: LinkEndInput| match end {
+        LinkEndInput::Staged(position) => keys
+            .get(position)
+            .cloned()
+            .ok_or_else(|| format!("no entry staged at position {}", position)),
+        LinkEndInput::Address(address) => Ok(cid::normalize(&address)),
+    };
+    for link in input.links {
+        let base = resolve(link.base)?;
+        let target = resolve(link.target)?;
+        transaction.stage_link(&Link::new(&base, &target, &link.tag));
+    }
+    Ok(transaction)
+}
+
+/// Validate a transaction against the running DNA and what the DHT holds, along with what the
+/// zome committed earlier in the call, blocking until it is
+fn validate_transaction(runtime: &Runtime, transaction: &Transaction) -> Result<(), String> {
+    let (sender, receiver) = channel();
+    let wrapper = state::ActionWrapper::new(state::Action::Nucleus(
+        ::nucleus::Action::ValidateTransaction(transaction.clone()),
+    ));
+    let wrapper_clone = wrapper.clone();
+    let transaction = transaction.clone();
+    let pending = runtime.pending.clone();
+    ::instance::dispatch_wrapper_with_observer(
         &runtime.action_channel,
         &runtime.observer_channel,
-        action_commit.clone(),
-        // TODO #131 - add timeout argument and return error on timeout
-        // REDUX_DEFAULT_TIMEOUT_MS,
+        wrapper,
+        move |state: &state::State| {
+            if state.history.contains(&wrapper_clone) {
+                let dht = state.dht();
+                let result = match state.nucleus().dna() {
+                    Some(dna) => transaction.validate(&dna, |key| {
+                        pending.staged(key).cloned().or_else(|| dht.holding(key))
+                    }),
+                    None => Err("no DNA to validate the transaction against".to_string()),
+                };
+                sender.send(result).expect("local channel to be open");
+                true
+            } else {
+                false
+            }
+        },
     );
-    // TODO #97 - Return error if timeout or something failed
-    // return Err(_);
+    receiver.recv().expect("local channel to work")
+}
 
-    // Hash entry
-    let hash_str = entry.hash();
+/// Put everything the zome committed during the call on the chain, all of it or none, and block
+/// until it is
+/// The addresses of the headers the entries were committed under are recorded in
+/// runtime.committed
+/// Once the zome read, the commit expects the chain to still be at the head it read, see
+/// agent::HEAD_MOVED
+/// Calls of read_only functions only get to read the chain, whatever they committed fails them
+/// Nothing is committed while the outbox has no room for what it publishes, the call fails with
+/// the limit exceeded and a signal reports it, see limits
+fn flush(runtime: &mut Runtime) -> Result<(), String> {
+    if runtime.pending.is_empty() {
+        return Ok(());
+    }
+    if runtime.host.read_only {
+        return Err(READ_ONLY_COMMIT.to_string());
+    }
+    if let Err(exceeded) = check_outbox_room(runtime, runtime.pending.entries()) {
+        let message = exceeded.to_string();
+        ::instance::dispatch_action(
+            &runtime.action_channel,
+            state::Action::Nucleus(::nucleus::Action::ReportLimitExceeded(exceeded)),
+        );
+        return Err(message);
+    }
+    let mut transaction = runtime.pending.clone();
+    if let Some(ref head) = runtime.read_head {
+        transaction.expect_head(head.clone());
+    }
+    let action_commit =
+        ::state::Action::Agent(::agent::Action::CommitTransaction(transaction.clone()));
 
-    // Write Hash of Entry in memory in output format
-    let params_str = format!("{{\"hash\":\"{}\"}}", hash_str);
-    let mut params: Vec<_> = params_str.into_bytes();
-    params.push(0); // Add string terminate character (important)
+    // @TODO signing and publishing should get spans of their own under this one once they happen
+    let mut commit_span = runtime
+        .host
+        .trace
+        .as_ref()
+        .map(|(tracer, parent)| tracer.child_of("commit_transaction", parent));
+    let wrapper = match commit_span {
+        Some(ref mut span) => {
+            span.tag("entries", &transaction.entries().len().to_string());
+            state::ActionWrapper::traced(action_commit, span.context())
+        }
+        None => state::ActionWrapper::new(action_commit),
+    };
 
-    // TODO #65 - use our Malloc instead
+    // Send Action and block until it is reduced, reading back the headers they were committed
+    // under
+    let (sender, receiver) = channel();
+    let wrapper_clone = wrapper.clone();
+    ::instance::dispatch_wrapper_with_observer(
+        &runtime.action_channel,
+        &runtime.observer_channel,
+        wrapper,
+        move |state: &state::State| {
+            if state.history.contains(&wrapper_clone) {
+                let agent = state.agent();
+                sender
+                    .send((agent.last_commit(), agent.is_staging()))
+                    .expect("local channel to be open");
+                true
+            } else {
+                false
+            }
+        },
+    );
+    // TODO #131 - add timeout and return error on timeout
+    // REDUX_DEFAULT_TIMEOUT_MS,
+    let (pairs, staging) = receiver.recv().expect("local channel to work");
+    let pairs = pairs?;
+    runtime.pending = Transaction::new();
     runtime
-        .memory
-        .set(mem_offset, &params)
-        .expect("memory should be writable");
+        .committed
+        .extend(pairs.iter().map(|pair| pair.header().hash()));
+    if !staging {
+        publish(runtime, &pairs);
+    }
+    Ok(())
+}
 
-    // Return success in i32 format
-    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+/// HcApiFuncIndex::COMMIT_TRANSACTION function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"entries":[{"entry_type_name":"post","entry_content":"hello"}],
+///     "links":[{"base":0,"target":"Qm...","tag":"comments"}]}"#
+/// link ends are either the position of an entry in "entries" or an address
+/// Writes r#"{"hashes":[...]}"# in place of the argument, links last, or the reason nothing was
+/// committed and returns ERROR_TRANSACTION
+/// The entries go on the chain along with the others committed once the call completes, see
+/// commit_entry
+/// Returns an HcApiReturnCode as I32
+fn invoke_commit_transaction(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: CommitTransactionInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let result = stage_transaction(input).and_then(|transaction| {
+        validate_transaction(runtime, &transaction)?;
+        Ok(transaction
+            .entries()
+            .iter()
+            .map(|entry| commit_entry(runtime, entry))
+            .collect::<Vec<_>>())
+    });
+    match result {
+        Ok(hashes) => {
+            let hashes = hashes
+                .into_iter()
+                .map(|hash| express(runtime, hash))
+                .collect();
+            let code = write_json(runtime, args, &CommitTransactionOutput { hashes });
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(message) => {
+            // the zome learns the transaction failed even when the reason doesn't fit
+            let _ = write_output(runtime, args, &message);
+            Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_TRANSACTION as i32,
+            )))
+        }
+    }
+}
+
+/// HcApiFuncIndex::ANCHOR function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"path":"posts/2018/03"}"#
+/// Commits the anchors along the path and the links between them, then writes the address of the
+/// anchor at the path in place of the argument, e.g. to link entries from
+/// Returns an HcApiReturnCode as I32
+fn invoke_anchor(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: AnchorInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let path = Path::parse(&input.path);
+    for entry in path.index_entries() {
+        commit_entry(runtime, &entry);
+    }
+
+    let output = AnchorOutput {
+        address: express(runtime, path.address()),
+    };
+    let code = write_json(runtime, args, &output);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// Struct for input data received when GetLinks API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct GetLinksInputStruct {
+    base: String,
+    #[serde(flatten)]
+    options: GetLinksOptions,
+    /// how consistent the read is, the result comes with how it was answered if given
+    #[serde(default)]
+    consistency: Option<ReadConsistency>,
+    /// leave out what the agents blocked authored, see agent::blocks
+    #[serde(default)]
+    exclude_blocked: bool,
+}
+
+json_string_conversions!(GetLinksInputStruct);
+
+/// the DHT state once the action asking for what to read is reduced, along with the agents
+/// blocked then
+/// what the zome committed earlier in the call reads as held, and the head of the chain at the
+/// first read is recorded in runtime.read_head
+fn dht_after(runtime: &mut Runtime, action: ::dht::Action) -> (Arc<DhtState>, Vec<String>) {
+    let (sender, receiver) = channel();
+    let wrapper = state::ActionWrapper::new(state::Action::Dht(action));
+    let wrapper_clone = wrapper.clone();
+    ::instance::dispatch_wrapper_with_observer(
+        &runtime.action_channel,
+        &runtime.observer_channel,
+        wrapper,
+        move |state: &state::State| {
+            if state.history.contains(&wrapper_clone) {
+                let blocked = state.agent().blocked().clone();
+                sender
+                    .send((state.dht(), blocked, state.agent().head()))
+                    .expect("local channel to be open");
+                true
+            } else {
+                false
+            }
+        },
+    );
+    let (dht, mut blocked, head) = receiver.recv().expect("local channel to work");
+    for entry in runtime.pending.entries() {
+        blocks::apply(&mut blocked, entry);
+    }
+    let blocked = blocked.into_iter().collect();
+    if runtime.read_head.is_none() {
+        runtime.read_head = Some(head);
+    }
+    if runtime.pending.is_empty() {
+        (dht, blocked)
+    } else {
+        (Arc::new(dht.with_pending(runtime.pending.entries())), blocked)
+    }
+}
+
+/// HcApiFuncIndex::GET_LINKS function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"base":"Qm...","tag_prefix":"2018-","order":"newest_first","limit":20,"cursor":null}"#
+/// Writes the page of links in place of the argument, pass its "next" as cursor for the next page
+/// with "exclude_blocked" the links added by agents blocked are left out, as are the ones by
+/// "excluded_authors"
+/// with a "consistency" of "local", {"quorum":n}, "fastest" or "author" the page is written as
+/// "result" along with the "consistency", the "responders" that answered and "stats" on asking
+/// them, see dht::read
+/// Returns an HcApiReturnCode as I32
+fn invoke_get_links(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let mut input: GetLinksInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    input.base = cid::normalize(&input.base);
+    let (dht, blocked) = dht_after(runtime, ::dht::Action::GetLinks(input.base.clone()));
+    if input.exclude_blocked {
+        input.options.excluded_authors.extend(blocked);
+    }
+    let code = match input.consistency {
+        None => {
+            let result = express_links(runtime, dht.get_links(&input.base, &input.options));
+            write_json(runtime, args, &result)
+        }
+        Some(ref consistency) => {
+            let messenger = &runtime.host.messenger;
+            let (dht, meta) = read::read(&dht, messenger, &input.base, consistency);
+            let result = express_links(runtime, dht.get_links(&input.base, &input.options));
+            write_json(runtime, args, &Read { result, meta })
+        }
+    };
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// Struct for input data received when GetEntry API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct GetEntryInputStruct {
+    address: String,
+    #[serde(flatten)]
+    options: GetEntryOptions,
+    /// how consistent the read is, the result comes with how it was answered if given
+    #[serde(default)]
+    consistency: Option<ReadConsistency>,
+    /// leave out what the agents blocked authored, see agent::blocks
+    #[serde(default)]
+    exclude_blocked: bool,
+}
+
+json_string_conversions!(GetEntryInputStruct);
+
+/// HcApiFuncIndex::GET_ENTRY function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"address":"Qm...","status_request":"all","result_type":"details"}"#
+/// Writes the entry, null if there is none with the status asked for, or its details in place of
+/// the argument
+/// with "exclude_blocked" entries authored by agents blocked read as null, as do the ones by
+/// "excluded_authors"
+/// with a "consistency" of "local", {"quorum":n}, "fastest" or "author" they are written as
+/// "result" along with the "consistency", the "responders" that answered and "stats" on asking
+/// them, see dht::read
+/// Returns an HcApiReturnCode as I32
+fn invoke_get_entry(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let mut input: GetEntryInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    input.address = cid::normalize(&input.address);
+    let (dht, blocked) = dht_after(runtime, ::dht::Action::GetEntry(input.address.clone()));
+    if input.exclude_blocked {
+        input.options.excluded_authors.extend(blocked);
+    }
+    let code = match input.consistency {
+        None => {
+            let result = dht.get_entry(&input.address, &input.options);
+            write_json(runtime, args, &result)
+        }
+        Some(ref consistency) => {
+            let messenger = &runtime.host.messenger;
+            let (dht, meta) = read::read(&dht, messenger, &input.address, consistency);
+            let result = dht.get_entry(&input.address, &input.options);
+            write_json(runtime, args, &Read { result, meta })
+        }
+    };
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::PROPERTY function code
+/// args: [0] memory offset where the property name is stored
+/// args: [1] memory length of the property name
+/// the property value is written back at the same offset as JSON, null if it isn't set
+/// Returns an HcApiReturnCode as I32
+fn invoke_property(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    let name: String = match read_json_arg(runtime, args) {
+        Some(name) => name,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let value = runtime
+        .host
+        .properties
+        .get(&name)
+        .cloned()
+        .unwrap_or(serde_json::Value::Null);
+    let code = write_json(runtime, args, &value);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
 }
 
 //--------------------------------------------------------------------------------------------------
 // Wasm call
 //--------------------------------------------------------------------------------------------------
 
-pub const RESULT_OFFSET: u32 = 0;
+/// read and convert the complex argument of a host function from memory, plain strings like
+/// names and addresses come as they are
+/// None if the zome passed anything but the offset and length of an argument in its memory, or
+/// the argument doesn't convert
+fn read_json_arg<T>(runtime: &Runtime, args: &RuntimeArgs) -> Option<T>
+where
+    T: TryFrom<JsonString>,
+{
+    if args.len() != 2 {
+        return None;
+    }
+    let mem_offset: u32 = args.nth_checked(0).ok()?;
+    let mem_len: u32 = args.nth_checked(1).ok()?;
+    let bin_arg = runtime.memory.get(mem_offset, mem_len as usize).ok()?;
+    String::from_utf8(bin_arg)
+        .ok()
+        .and_then(|arg| T::try_from(JsonString::from(arg)).ok())
+}
 
-/// Object holding data to pass around to invoked API functions
-#[derive(Clone, Debug)]
-pub struct Runtime {
-    print_output: Vec<u32>,
-    pub result: String,
-    action_channel: Sender<state::ActionWrapper>,
-    observer_channel: Sender<Observer>,
-    memory: MemoryRef,
+/// write the output of a host function in place of its argument, null terminated
+/// the guest allocates a page for the argument and what the host writes back over it, so the
+/// output has to end before the end of the page the argument starts in
+/// ERROR_OUTPUT_TOO_LARGE and nothing written if it doesn't, or the page isn't in memory
+fn write_output(runtime: &Runtime, args: &RuntimeArgs, output: &str) -> HcApiReturnCode {
+    let mut bytes = output.as_bytes().to_vec();
+    bytes.push(0); // Add string terminate character (important)
+
+    let mem_offset: u32 = match args.nth_checked(0) {
+        Ok(mem_offset) => mem_offset,
+        Err(_) => return HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE,
+    };
+    let page_end = (u64::from(mem_offset) / OUTPUT_PAGE_SIZE + 1) * OUTPUT_PAGE_SIZE;
+    if u64::from(mem_offset) + bytes.len() as u64 > page_end {
+        return HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE;
+    }
+    match runtime.memory.set(mem_offset, &bytes) {
+        Ok(()) => HcApiReturnCode::SUCCESS,
+        Err(_) => HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE,
+    }
 }
 
-/// Executes an exposed function in a wasm binary
-pub fn call(
-    action_channel: &Sender<state::ActionWrapper>,
-    observer_channel: &Sender<Observer>,
-    wasm: Vec<u8>,
-    function_name: &str,
-    parameters: Option<Vec<u8>>,
-) -> Result<Runtime, InterpreterError> {
-    // Create wasm module from wasm binary
-    let module = wasmi::Module::from_buffer(wasm).unwrap();
+/// write the output of a host function as JSON in place of its argument, see write_output()
+fn write_json<T: serde::Serialize>(
+    runtime: &Runtime,
+    args: &RuntimeArgs,
+    output: &T,
+) -> HcApiReturnCode {
+    match JsonString::try_from_serialize(output) {
+        Ok(json) => write_output(runtime, args, json.as_str()),
+        Err(_) => HcApiReturnCode::ERROR_SERDE_JSON,
+    }
+}
 
-    // Describe invokable functions form within Zome
-    impl Externals for Runtime {
-        fn invoke_index(
-            &mut self,
-            index: usize,
-            args: RuntimeArgs,
-        ) -> Result<Option<RuntimeValue>, Trap> {
-            match index {
+/// HcApiFuncIndex::SCHEDULE function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"id":"digest","capability":"main","function":"send_digest","interval_secs":86400}"#
+/// a schedule with the same id replaces the zome's previous one
+/// Returns an HcApiReturnCode as I32
+fn invoke_schedule(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: ScheduleInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let schedule = Schedule::new(
+        &runtime.host.zome,
+        &input.id,
+        &input.capability,
+        &input.function,
+        &input.parameters,
+        input.interval_secs,
+        input.jitter_secs,
+    );
+
+    ::instance::dispatch_action_and_wait(
+        &runtime.action_channel,
+        &runtime.observer_channel,
+        state::Action::Nucleus(::nucleus::Action::Schedule(schedule)),
+    );
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::SCHEDULE_ONCE function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"id":"expire","capability":"main","function":"expire_invite","delay_secs":3600}"#
+/// the schedule is removed once the function is called, a schedule with the same id replaces the
+/// zome's previous one and cancel_schedule cancels it before it is due
+/// Returns an HcApiReturnCode as I32
+fn invoke_schedule_once(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: ScheduleOnceInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let schedule = Schedule::once(
+        &runtime.host.zome,
+        &input.id,
+        &input.capability,
+        &input.function,
+        &input.parameters,
+        input.delay_secs,
+    );
+
+    ::instance::dispatch_action_and_wait(
+        &runtime.action_channel,
+        &runtime.observer_channel,
+        state::Action::Nucleus(::nucleus::Action::Schedule(schedule)),
+    );
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::CANCEL_SCHEDULE function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"id":"digest"}"#
+/// Returns an HcApiReturnCode as I32
+fn invoke_cancel_schedule(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: CancelScheduleInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    ::instance::dispatch_action_and_wait(
+        &runtime.action_channel,
+        &runtime.observer_channel,
+        state::Action::Nucleus(::nucleus::Action::CancelSchedule(schedule_key(
+            &runtime.host.zome,
+            &input.id,
+        ))),
+    );
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::EMIT_SIGNAL function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"name":"new_post","payload":{"title":"hello"}}"#
+/// signals are fire and forget, it is not an error for nobody to be listening
+/// Returns an HcApiReturnCode as I32
+fn invoke_emit_signal(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: EmitSignalInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    runtime.host.signals.emit(&Signal {
+        zome: runtime.host.zome.clone(),
+        name: input.name,
+        payload: input.payload,
+    });
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::CALL_REMOTE function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"agent":"alice","zome":"chat","capability":"main","function":"inbox","parameters":"{}"}"#
+/// Blocks until the remote result arrives, then writes it in place of the argument, or the error
+/// the call failed with and returns ERROR_CALL_REMOTE
+/// Returns an HcApiReturnCode as I32
+fn invoke_call_remote(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: CallRemoteInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let call = FunctionCall::new(
+        input.zome,
+        input.capability,
+        input.function,
+        input.parameters,
+    );
+    let (code, output) = match runtime.host.messenger.call_remote(
+        &input.agent,
+        &call,
+        input.cap_secret,
+        runtime.host.messenger.config().direct_message_timeout(),
+    ) {
+        Ok(result) => (HcApiReturnCode::SUCCESS, result),
+        Err(HolochainError::ErrorGeneric(message)) => {
+            (HcApiReturnCode::ERROR_CALL_REMOTE, message)
+        }
+        Err(error) => (HcApiReturnCode::ERROR_CALL_REMOTE, format!("{:?}", error)),
+    };
+    let written = write_output(runtime, args, &output);
+    let code = if written == HcApiReturnCode::SUCCESS {
+        code
+    } else {
+        written
+    };
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::KV_SET function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"key":"draft","value":{"title":"hello"}}"#
+/// a null or missing value removes the key
+/// Returns an HcApiReturnCode as I32
+fn invoke_kv_set(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: KvSetInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    runtime
+        .host
+        .scratch
+        .set(&runtime.host.zome, &input.key, input.value);
+
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::KV_GET function code
+/// args: [0] memory offset where the key is stored
+/// args: [1] memory length of the key
+/// the value is written back at the same offset as JSON, null if the key isn't set
+/// Returns an HcApiReturnCode as I32
+fn invoke_kv_get(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    let key: String = match read_json_arg(runtime, args) {
+        Some(key) => key,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let value = runtime
+        .host
+        .scratch
+        .get(&runtime.host.zome, &key)
+        .unwrap_or(serde_json::Value::Null);
+    let code = write_json(runtime, args, &value);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::GET_VALIDATION_RECEIPTS function code
+/// args: [0] memory offset where the entry address is stored
+/// args: [1] memory length of the address
+/// the receipts are written back at the same offset as a JSON array
+/// Returns an HcApiReturnCode as I32
+fn invoke_get_validation_receipts(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    let address: String = match read_json_arg(runtime, args) {
+        Some(address) => address,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let receipts = runtime.host.receipts.receipts(&cid::normalize(&address));
+    let code = write_json(runtime, args, &receipts);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::REMOTE_SIGNAL function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"agents":["alice","bob"],"payload":{"typing":true}}"#
+/// the signal is batched and sent without waiting, agents that can't be reached miss it
+/// Returns ERROR_REMOTE_SIGNAL if the payload is too big or the agent is not on a network,
+/// otherwise an HcApiReturnCode as I32
+fn invoke_remote_signal(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: RemoteSignalInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let sent = runtime.host.remote_signals.send(
+        &runtime.host.messenger,
+        &runtime.host.zome,
+        &input.agents,
+        input.payload,
+    );
+
+    match sent {
+        Ok(()) => Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32))),
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_REMOTE_SIGNAL as i32,
+        ))),
+    }
+}
+
+/// HcApiFuncIndex::GET_ONLINE_AGENTS function code
+/// args: [0] memory offset where the result is written
+/// args: [1] memory length, unused as there is no argument
+/// the agents are written at the offset as a JSON array, empty while presence is off
+/// Returns an HcApiReturnCode as I32
+fn invoke_get_online_agents(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let agents = runtime
+        .host
+        .presence
+        .online_agents(&runtime.host.messenger.config());
+    let code = write_json(runtime, args, &agents);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::SYS_TIME function code
+/// args: [0] memory offset where the result is written
+/// args: [1] memory length, unused as there is no argument
+/// the seconds since the unix epoch are written at the offset as a JSON number, by the clock
+/// schedules and call contexts go by, for zomes to stop taking timestamps from their callers
+/// Returns an HcApiReturnCode as I32
+fn invoke_sys_time(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let code = write_json(runtime, args, &unix_now());
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::CALL_CONTEXT function code
+/// args: [0] memory offset where the result is written
+/// args: [1] memory length, unused as there is no argument
+/// the context is written at the offset as JSON, e.g.
+/// r#"{"call_id":"...","caller":"bob","cap_secret":null,"requested_at":...,"started_at":...}"#
+/// where caller is null for calls not made remotely
+/// Returns an HcApiReturnCode as I32
+fn invoke_call_context(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let code = write_json(runtime, args, &runtime.host.call);
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// HcApiFuncIndex::SEARCH function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"query":"cats dogs","types":["post"],"offset":0,"limit":10}"#
+/// a page of the addresses found is written back at the same offset along with the offset of the
+/// next page, e.g. r#"{"addresses":["Qm..."],"next":10}"#
+/// Returns an HcApiReturnCode as I32
+fn invoke_search(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: SearchInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let limit = input
+        .limit
+        .unwrap_or(index::SEARCH_DEFAULT_LIMIT)
+        .min(index::SEARCH_MAX_LIMIT);
+    let found = runtime.search(&input.query, &input.types);
+    let (addresses, next) = page_of(found, input.offset, limit);
+    let code = write_json(runtime, args, &SearchOutput { addresses, next });
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// the page of results from offset on, at most limit of them, with the offset of the next page
+/// if there are results past it
+fn page_of<T>(mut results: Vec<T>, offset: usize, limit: usize) -> (Vec<T>, Option<usize>) {
+    let end = offset.saturating_add(limit);
+    let next = if results.len() > end { Some(end) } else { None };
+    results.truncate(end);
+    let page = results.split_off(offset.min(results.len()));
+    (page, next)
+}
+
+/// HcApiFuncIndex::QUERY_INDEX function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument:
+/// r#"{"entry_type":"profile","field":"handle","query":{"equals":"alice"}}"#
+/// or with a range, both ends included and either left open with null, e.g.
+/// r#"{"entry_type":"profile","field":"age","query":{"range":{"from":18,"to":null}}}"#
+/// with an "offset" and a "limit" for the page of the addresses found, like search
+/// a page of the addresses found is written back at the same offset along with the offset of the
+/// next page, e.g. r#"{"addresses":["Qm..."],"next":null}"#
+/// Returns ERROR_NOT_INDEXED if the DNA doesn't declare the field indexed, otherwise an
+/// HcApiReturnCode as I32
+fn invoke_query_index(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: QueryIndexInput = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    let query: IndexQuery = match serde_json::from_value(input.query) {
+        Ok(query) => query,
+        Err(_) => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.query_index(&input.entry_type, &input.field, &query) {
+        Some(found) => {
+            let limit = input
+                .limit
+                .unwrap_or(index::QUERY_INDEX_DEFAULT_LIMIT)
+                .min(index::QUERY_INDEX_MAX_LIMIT);
+            let (addresses, next) = page_of(found, input.offset, limit);
+            let code = write_json(runtime, args, &QueryIndexOutput { addresses, next });
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        None => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_NOT_INDEXED as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when GetAgentActivity API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct GetAgentActivityInputStruct {
+    agent: String,
+    /// index of the first header to get
+    #[serde(default)]
+    from: u64,
+    /// index past the last header to get, None for up to the latest
+    #[serde(default)]
+    to: Option<u64>,
+    /// max number of headers, see activity::limited_range
+    #[serde(default)]
+    limit: Option<u64>,
+    /// how consistent the read is, the result comes with how it was answered if given
+    #[serde(default)]
+    consistency: Option<ReadConsistency>,
+}
+
+json_string_conversions!(GetAgentActivityInputStruct);
+
+/// the activity with the entry addresses as the DNA has them expressed
+fn express_activity(runtime: &Runtime, mut activity: AgentActivity) -> AgentActivity {
+    for header in &mut activity.headers {
+        header.entry = express(runtime, header.entry.clone());
+    }
+    activity
+}
+
+/// HcApiFuncIndex::GET_AGENT_ACTIVITY function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"agent":"alice","from":0,"to":10,"limit":10}"#
+/// Writes the activity back at the same offset, the headers held by index, the highest index,
+/// the indices the chain forked at and the headers not linking back to the first, e.g.
+/// r#"{"headers":[{"index":0,"hash":"Qm...",...}],"highest":0,"forks":[],"unlinked":[]}"#
+/// With a "consistency" the activity is read from the agent's neighborhood, see dht::read, and
+/// comes as "result" along with how it was answered
+/// There are at most limit headers from the index from, the next ones are from the index past
+/// the end of the range, the highest tells where the chain ends, and at most
+/// GET_AGENT_ACTIVITY_MAX_LIMIT of the unlinked hashes
+/// Returns an HcApiReturnCode as I32
+fn invoke_get_agent_activity(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: GetAgentActivityInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    let range = activity::limited_range(input.from, input.to, input.limit);
+    let action = ::dht::Action::GetAgentActivity(input.agent.clone());
+    let (dht, _) = dht_after(runtime, action);
+    let activity = |dht: &DhtState| {
+        let mut activity = express_activity(runtime, dht.get_agent_activity(&input.agent, &range));
+        activity
+            .unlinked
+            .truncate(activity::GET_AGENT_ACTIVITY_MAX_LIMIT as usize);
+        activity
+    };
+    let code = match input.consistency {
+        None => write_json(runtime, args, &activity(&dht)),
+        Some(ref consistency) => {
+            let messenger = &runtime.host.messenger;
+            let (dht, meta) = read::read(&dht, messenger, &input.agent, consistency);
+            let result = activity(&dht);
+            write_json(runtime, args, &Read { result, meta })
+        }
+    };
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// Struct for input data received when VerifyAgentHead API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct VerifyAgentHeadInputStruct {
+    agent: String,
+    /// how consistent the read is, the result comes with how it was answered if given
+    #[serde(default)]
+    consistency: Option<ReadConsistency>,
+}
+
+json_string_conversions!(VerifyAgentHeadInputStruct);
+
+/// HcApiFuncIndex::VERIFY_AGENT_HEAD function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"agent":"alice"}"#
+/// Writes what comparing the freshest checkpoint of the agent to its chain found back at the same
+/// offset, e.g. r#""unchecked""# or r#"{"rewritten":{"agent":"alice","head":"Qm...",...}}"#
+/// With a "consistency" the checkpoints and activity are read from the agent's neighborhood, see
+/// dht::read, and the result comes as "result" along with how it was answered
+/// Returns an HcApiReturnCode as I32
+fn invoke_verify_agent_head(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: VerifyAgentHeadInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+
+    let action = ::dht::Action::GetAgentActivity(input.agent.clone());
+    let (dht, _) = dht_after(runtime, action);
+    let code = match input.consistency {
+        None => {
+            let result = checkpoints::verify_agent_head(&dht, &input.agent);
+            write_json(runtime, args, &result)
+        }
+        Some(ref consistency) => {
+            let messenger = &runtime.host.messenger;
+            let (dht, meta) = read::read(&dht, messenger, &input.agent, consistency);
+            let result = checkpoints::verify_agent_head(&dht, &input.agent);
+            write_json(runtime, args, &Read { result, meta })
+        }
+    };
+
+    Ok(Some(RuntimeValue::I32(code as i32)))
+}
+
+/// Struct for input data received when CreateGroupSecret API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct CreateGroupSecretInputStruct {
+    members: Vec<String>,
+}
+
+json_string_conversions!(CreateGroupSecretInputStruct);
+
+/// HcApiFuncIndex::CREATE_GROUP_SECRET function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"members":["bob","carol"]}"#
+/// Writes the id of the secret back at the same offset, e.g. r#""3yZe7d...""#
+/// Returns ERROR_GROUP_SECRET if the agent is not on a network or the outbox has no room for the
+/// secret, otherwise an HcApiReturnCode as I32
+fn invoke_create_group_secret(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: CreateGroupSecretInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.create_group_secret(&input.members) {
+        Ok(id) => {
+            let code = write_json(runtime, args, &id);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_GROUP_SECRET as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when GroupEncrypt API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct GroupEncryptInputStruct {
+    group: String,
+    payload: String,
+}
+
+json_string_conversions!(GroupEncryptInputStruct);
+
+/// HcApiFuncIndex::GROUP_ENCRYPT function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"group":"3yZe7d...","payload":"the plan"}"#
+/// Writes the ciphertext back at the same offset as a JSON string
+/// Returns ERROR_GROUP_SECRET if the agent knows no secret with the id, otherwise an
+/// HcApiReturnCode as I32
+fn invoke_group_encrypt(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: GroupEncryptInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.group_encrypt(&input.group, &input.payload) {
+        Ok(ciphertext) => {
+            let code = write_json(runtime, args, &ciphertext);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_GROUP_SECRET as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when GroupDecrypt API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct GroupDecryptInputStruct {
+    group: String,
+    ciphertext: String,
+}
+
+json_string_conversions!(GroupDecryptInputStruct);
+
+/// HcApiFuncIndex::GROUP_DECRYPT function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"group":"3yZe7d...","ciphertext":"8Fq2..."}"#
+/// Writes the payload back at the same offset as a JSON string
+/// Returns ERROR_GROUP_SECRET if the agent knows no secret with the id or the ciphertext wasn't
+/// encrypted with it, otherwise an HcApiReturnCode as I32
+fn invoke_group_decrypt(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: GroupDecryptInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.group_decrypt(&input.group, &input.ciphertext) {
+        Ok(payload) => {
+            let code = write_json(runtime, args, &payload);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_GROUP_SECRET as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when DeriveKey API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct DeriveKeyInputStruct {
+    path: String,
+}
+
+json_string_conversions!(DeriveKeyInputStruct);
+
+/// HcApiFuncIndex::DERIVE_KEY function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"path":"chat/bob"}"#
+/// Writes the public key of the key pair derived along the path back at the same offset, in
+/// base58 as a JSON string, the secret key stays in the keystore
+/// Returns ERROR_KEYSTORE if the keystore is locked, otherwise an HcApiReturnCode as I32
+fn invoke_derive_key(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: DeriveKeyInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.derive_key(&input.path) {
+        Ok(key) => {
+            let code = write_json(runtime, args, &key);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_KEYSTORE as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when Ecdh API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct EcdhInputStruct {
+    path: String,
+    public_key: String,
+}
+
+json_string_conversions!(EcdhInputStruct);
+
+/// HcApiFuncIndex::ECDH function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"path":"chat/bob","public_key":"4LK8c..."}"#
+/// Writes the key the key pair derived along the path agrees with the public key back at the same
+/// offset, in base58 as a JSON string, the holder of the public key gets the same one
+/// Returns ERROR_KEYSTORE if the public key isn't an X25519 key in base58 or is of low order, or
+/// the keystore is locked, otherwise an HcApiReturnCode as I32
+fn invoke_ecdh(runtime: &mut Runtime, args: &RuntimeArgs) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: EcdhInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match runtime.ecdh(&input.path, &input.public_key) {
+        Ok(key) => {
+            let code = write_json(runtime, args, &key);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(
+            HcApiReturnCode::ERROR_KEYSTORE as i32,
+        ))),
+    }
+}
+
+/// Struct for input data received when RandomBytes API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct RandomBytesInputStruct {
+    n: usize,
+}
+
+json_string_conversions!(RandomBytesInputStruct);
+
+/// HcApiFuncIndex::RANDOM_BYTES function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"n":24}"#
+/// Writes n bytes from the operating system's generator back at the same offset as a JSON array
+/// Returns ERROR_RANDOM if more than random::MAX_RANDOM_BYTES are asked for or the host has no
+/// entropy, otherwise an HcApiReturnCode as I32
+fn invoke_random_bytes(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: RandomBytesInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match random::random_bytes(input.n) {
+        Ok(bytes) => {
+            let code = write_json(runtime, args, &bytes);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(HcApiReturnCode::ERROR_RANDOM as i32))),
+    }
+}
+
+/// Struct for input data received when SeededRandom API function is invoked
+#[derive(Serialize, Deserialize, Default, Debug)]
+struct SeededRandomInputStruct {
+    seed: String,
+    n: usize,
+}
+
+json_string_conversions!(SeededRandomInputStruct);
+
+/// HcApiFuncIndex::SEEDED_RANDOM function code
+/// args: [0] memory offset where complex argument is stored
+/// args: [1] memory length of complex argument stored in memory
+/// expected complex argument: r#"{"seed":"round 1","n":8}"#
+/// Writes n bytes stretched from the seed back at the same offset as a JSON array, the same for
+/// the same seed on every node
+/// Returns ERROR_RANDOM if more than random::MAX_RANDOM_BYTES are asked for, otherwise an
+/// HcApiReturnCode as I32
+fn invoke_seeded_random(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    assert!(args.len() == 2);
+
+    let input: SeededRandomInputStruct = match read_json_arg(runtime, args) {
+        Some(input) => input,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    match random::seeded_random(&input.seed, input.n) {
+        Ok(bytes) => {
+            let code = write_json(runtime, args, &bytes);
+            Ok(Some(RuntimeValue::I32(code as i32)))
+        }
+        Err(_) => Ok(Some(RuntimeValue::I32(HcApiReturnCode::ERROR_RANDOM as i32))),
+    }
+}
+
+/// commit the block entry for the agent at the address stored in memory
+fn commit_block(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+    block: fn(&str) -> Entry,
+) -> Result<Option<RuntimeValue>, Trap> {
+    let address: String = match read_json_arg(runtime, args) {
+        Some(address) => address,
+        None => {
+            return Ok(Some(RuntimeValue::I32(
+                HcApiReturnCode::ERROR_SERDE_JSON as i32,
+            )))
+        }
+    };
+    commit_entry(runtime, &block(&address));
+    Ok(Some(RuntimeValue::I32(HcApiReturnCode::SUCCESS as i32)))
+}
+
+/// HcApiFuncIndex::BLOCK_AGENT function code
+/// args: [0] memory offset where the agent address is stored
+/// args: [1] memory length of the agent address
+/// Commits a block entry for the agent, see commit_entry
+/// Returns an HcApiReturnCode as I32
+fn invoke_block_agent(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    commit_block(runtime, args, blocks::block_entry)
+}
+
+/// HcApiFuncIndex::UNBLOCK_AGENT function code
+/// args: [0] memory offset where the agent address is stored
+/// args: [1] memory length of the agent address
+/// Commits an unblock entry for the agent, see commit_entry
+/// Returns an HcApiReturnCode as I32
+fn invoke_unblock_agent(
+    runtime: &mut Runtime,
+    args: &RuntimeArgs,
+) -> Result<Option<RuntimeValue>, Trap> {
+    commit_block(runtime, args, blocks::unblock_entry)
+}
+
+pub const RESULT_OFFSET: u32 = 0;
+/// the size of the page a guest allocates for the argument of a host function and its output
+pub const OUTPUT_PAGE_SIZE: u64 = 65_536;
+
+/// why a call of a read_only function that committed failed
+pub const READ_ONLY_COMMIT: &str = "read_only functions cannot commit";
+
+/// What host functions know about the zome call they are invoked in
+#[derive(Clone, Debug, Default)]
+pub struct HostContext {
+    /// name of the zome being called
+    pub zome: String,
+    /// tracer and span of the zome call, for host functions to trace their work under
+    pub trace: Option<(Tracer, TraceContext)>,
+    /// where messages from the log host function go
+    pub logger: ZomeLogger,
+    /// properties of the running DNA, read by the property host function
+    /// validation run through the ribosome sees the same properties as zome calls
+    pub properties: serde_json::Value,
+    /// where signals from the emit_signal host function go
+    pub signals: SignalBus,
+    /// the instance's connection to other agents, for the call_remote host function
+    pub messenger: DirectMessenger,
+    /// where publishes of what the zome commits are queued
+    pub outbox: Outbox,
+    /// the receipts for what was published, for the get_validation_receipts host function
+    pub receipts: ReceiptStore,
+    /// the secrets of the agent's groups, for the group host functions
+    pub group_secrets: GroupSecrets,
+    /// the keys of the agent, for the derive_key and ecdh host functions
+    pub keystore: Keystore,
+    /// where signals from the remote_signal host function wait to go out
+    pub remote_signals: RemoteSignalSender,
+    /// heartbeats received, for the get_online_agents host function
+    pub presence: Presence,
+    /// the instance's scratch space, for the kv_set and kv_get host functions
+    pub scratch: ScratchSpace,
+    /// the instance's search index, for the search host function
+    pub search_index: SearchIndex,
+    /// max pages the zome's memory can grow to, see limits
+    pub max_wasm_pages: Option<u32>,
+    /// the call the zome runs in, for the call_context host function
+    pub call: CallContext,
+    /// the host API version the DNA targets, the current one if None
+    pub api_version: Option<u32>,
+    /// how entry addresses are expressed to the zome
+    pub address_format: AddressFormat,
+    /// whether the capability declares the function called read_only, see flush
+    pub read_only: bool,
+    /// the priority what the zome commits is published at, unless more urgent, see publish
+    pub priority: Priority,
+}
+
+/// Object holding data to pass around to invoked API functions
+#[derive(Clone, Debug)]
+pub struct Runtime {
+    /// every message the zome logged, whether or not it got past the log level
+    pub log_output: Vec<ZomeLogMessage>,
+    /// addresses of the headers of the entries the zome committed, in commit order
+    pub committed: Vec<String>,
+    pub result: String,
+    action_channel: Sender<state::ActionWrapper>,
+    observer_channel: Sender<Observer>,
+    memory: MemoryRef,
+    host: HostContext,
+    /// set once the memory went over max_wasm_pages
+    memory_exceeded: Option<LimitExceeded>,
+    /// what the zome committed so far in the call, see commit_entry
+    pending: Transaction,
+    /// the head of the chain when the zome first read, None until it reads
+    read_head: Option<Option<String>>,
+}
+
+impl Runtime {
+    /// the runtime of a call that doesn't run WASM, e.g. to a native zome, with an empty memory
+    /// the call gets the host functions through the methods below
+    pub fn without_wasm(
+        action_channel: &Sender<state::ActionWrapper>,
+        observer_channel: &Sender<Observer>,
+        host: &HostContext,
+    ) -> Runtime {
+        let memory = MemoryInstance::alloc(Pages(0), Some(Pages(0)))
+            .expect("an empty memory to be allocated");
+        Runtime {
+            log_output: vec![],
+            committed: vec![],
+            result: String::new(),
+            action_channel: action_channel.clone(),
+            observer_channel: observer_channel.clone(),
+            memory,
+            host: host.clone(),
+            memory_exceeded: None,
+            pending: Transaction::new(),
+            read_head: None,
+        }
+    }
+
+    /// the host the call runs in, e.g. for its logger, the DNA properties and the call context
+    pub fn host(&self) -> &HostContext {
+        &self.host
+    }
+
+    /// commit an entry as part of the call, returns the entry hash, see commit_entry
+    pub fn commit(&mut self, entry: &Entry) -> String {
+        commit_entry(self, entry)
+    }
+
+    /// the entry held at address as the call sees it, what it committed so far included
+    pub fn get(&mut self, address: &str) -> Option<Entry> {
+        let address = cid::normalize(address);
+        let (dht, _) = dht_after(self, ::dht::Action::GetEntry(address.clone()));
+        dht.holding(&address)
+    }
+
+    /// the activity of the agent held, with the headers in the range of indices, see
+    /// dht::activity
+    pub fn get_agent_activity(&mut self, agent: &str, range: &Range<u64>) -> AgentActivity {
+        let (dht, _) = dht_after(self, ::dht::Action::GetAgentActivity(agent.to_string()));
+        express_activity(self, dht.get_agent_activity(agent, range))
+    }
+
+    /// what comparing the freshest checkpoint held of the agent to its chain found, see
+    /// agent::checkpoints
+    pub fn verify_agent_head(&mut self, agent: &str) -> HeadVerification {
+        let (dht, _) = dht_after(self, ::dht::Action::GetAgentActivity(agent.to_string()));
+        checkpoints::verify_agent_head(&dht, agent)
+    }
+
+    /// create a secret shared with the members, queued for each of them in the outbox, returns
+    /// its id, an error if the agent is not on a network or the outbox has no room for it
+    pub fn create_group_secret(&self, members: &[String]) -> Result<String, HolochainError> {
+        let creator = self.host.messenger.address().ok_or_else(|| {
+            HolochainError::ErrorGeneric("the agent is not on a network".to_string())
+        })?;
+        let secret = GroupSecret::new(&creator, members);
+        secret.share(&self.host.outbox)?;
+        // the secret is known for as long as the instance runs even if it can't be persisted
+        let _ = self.host.group_secrets.add(secret.clone());
+        Ok(secret.id)
+    }
+
+    /// the payload encrypted with the group secret, see agent::groups
+    pub fn group_encrypt(&self, group: &str, payload: &str) -> Result<String, HolochainError> {
+        Ok(self.group_secret(group)?.encrypt(payload))
+    }
+
+    /// the payload decrypted with the group secret, an error if it was encrypted with another one
+    pub fn group_decrypt(&self, group: &str, ciphertext: &str) -> Result<String, HolochainError> {
+        self.group_secret(group)?.decrypt(ciphertext)
+    }
+
+    fn group_secret(&self, group: &str) -> Result<GroupSecret, HolochainError> {
+        self.host.group_secrets.get(group).ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("no group secret {} is known", group))
+        })
+    }
+
+    /// the public key of the key pair derived along the path, in base58, see agent::keystore
+    /// an error while the keystore is locked
+    pub fn derive_key(&self, path: &str) -> Result<String, HolochainError> {
+        Ok(self.host.keystore.derive_key(path)?.to_base58())
+    }
+
+    /// the key the key pair derived along the path agrees with the public key, both in base58,
+    /// an error if the public key isn't an X25519 key or is of low order, or the keystore is locked
+    pub fn ecdh(&self, path: &str, public_key: &str) -> Result<String, HolochainError> {
+        let public_key = PublicKey::from_base58(public_key)?;
+        let key = self.host.keystore.agree(path, &public_key)?;
+        Ok(key.to_base58())
+    }
+
+    /// addresses of the searchable entries committed and held with every word of the query, as
+    /// the DNA has them expressed, see index::SearchIndex::search
+    pub fn search(&self, query: &str, types: &[String]) -> Vec<String> {
+        self.host
+            .search_index
+            .search(query, types)
+            .into_iter()
+            .map(|address| express(self, address))
+            .collect()
+    }
+
+    /// addresses of the entries committed and held of entry_type with a value at the path of the
+    /// field matching the query, as the DNA has them expressed, or None if the DNA doesn't
+    /// declare the field indexed, see index::SearchIndex::query
+    pub fn query_index(
+        &self,
+        entry_type: &str,
+        field: &str,
+        query: &IndexQuery,
+    ) -> Option<Vec<String>> {
+        self.host
+            .search_index
+            .query(entry_type, field, query)
+            .map(|addresses| {
+                addresses
+                    .into_iter()
+                    .map(|address| express(self, address))
+                    .collect()
+            })
+    }
+
+    /// put what the call committed on the chain once it completed, see flush
+    pub fn complete(&mut self) -> Result<(), HolochainError> {
+        flush(self).map_err(|message| {
+            if message == ::agent::HEAD_MOVED {
+                HolochainError::HeadMoved
+            } else {
+                HolochainError::ErrorGeneric(message)
+            }
+        })
+    }
+
+    /// check the memory is within max_wasm_pages, signalling it the first time it isn't
+    fn check_memory(&mut self) -> Result<(), LimitExceeded> {
+        if let Some(ref exceeded) = self.memory_exceeded {
+            return Err(exceeded.clone());
+        }
+        let pages = self.memory.current_size().0 as u64;
+        let limit = self.host.max_wasm_pages.map(u64::from);
+        if let Err(exceeded) = limits::check(Resource::WasmPages, limit, pages) {
+            self.host.signals.emit(&exceeded.to_signal());
+            self.memory_exceeded = Some(exceeded.clone());
+            return Err(exceeded);
+        }
+        Ok(())
+    }
+}
+
+/// zome calls going over their memory limit trap with it
+impl HostError for LimitExceeded {}
+
+/// zome calls whose commits find the chain moved past the head they read fail with it, see
+/// agent::HEAD_MOVED, so they can be run again on the new head
+#[derive(Debug)]
+pub struct HeadMoved;
+
+impl fmt::Display for HeadMoved {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ::agent::HEAD_MOVED)
+    }
+}
+
+impl HostError for HeadMoved {}
+
+/// zome calls whose commits can't go on the chain fail with why, e.g. the chain is over its limit
+#[derive(Debug)]
+pub struct CommitFailed(pub String);
+
+impl fmt::Display for CommitFailed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl HostError for CommitFailed {}
+
+/// true for the error of a zome call that failed with HeadMoved
+pub fn is_head_moved(error: &InterpreterError) -> bool {
+    match *error {
+        InterpreterError::Host(ref host) => host.downcast_ref::<HeadMoved>().is_some(),
+        _ => false,
+    }
+}
+
+/// the host API version that added a host function
+fn introduced_in(field_name: &str) -> u32 {
+    match field_name {
+        "print" | "commit" => HOST_API_V1,
+        _ => HOST_API_V2,
+    }
+}
+
+/// Resolves the host functions zomes import for the host API version they target
+/// Functions are only there from the version that added them, and the ones replaced since are
+/// shimmed for the versions that had them
+struct RuntimeModuleImportResolver {
+    version: u32,
+}
+
+impl ModuleImportResolver for RuntimeModuleImportResolver {
+    fn resolve_func(
+        &self,
+        field_name: &str,
+        _signature: &Signature,
+    ) -> Result<FuncRef, InterpreterError> {
+        let since = introduced_in(field_name);
+        if self.version < since {
+            return Err(InterpreterError::Function(format!(
+                "host function {} needs host API version {}, the DNA targets {}",
+                field_name, since, self.version
+            )));
+        }
+        let func_ref = match field_name {
+            // the first host API printed numbers, they are logged now
+            "print" if self.version == HOST_API_V1 => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32][..], None),
+                HcApiFuncIndex::PRINT as usize,
+            ),
+            "log" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::LOG as usize,
+            ),
+            "commit" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::COMMIT as usize,
+            ),
+            "property" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::PROPERTY as usize,
+            ),
+            "schedule" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::SCHEDULE as usize,
+            ),
+            "cancel_schedule" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::CANCEL_SCHEDULE as usize,
+            ),
+            "emit_signal" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::EMIT_SIGNAL as usize,
+            ),
+            "call_remote" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::CALL_REMOTE as usize,
+            ),
+            "anchor" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::ANCHOR as usize,
+            ),
+            "get_links" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GET_LINKS as usize,
+            ),
+            "kv_set" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::KV_SET as usize,
+            ),
+            "kv_get" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::KV_GET as usize,
+            ),
+            "commit_transaction" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::COMMIT_TRANSACTION as usize,
+            ),
+            "get_entry" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GET_ENTRY as usize,
+            ),
+            "get_validation_receipts" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GET_VALIDATION_RECEIPTS as usize,
+            ),
+            "remote_signal" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::REMOTE_SIGNAL as usize,
+            ),
+            "get_online_agents" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GET_ONLINE_AGENTS as usize,
+            ),
+            "block_agent" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::BLOCK_AGENT as usize,
+            ),
+            "unblock_agent" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::UNBLOCK_AGENT as usize,
+            ),
+            "call_context" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::CALL_CONTEXT as usize,
+            ),
+            "search" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::SEARCH as usize,
+            ),
+            "query_index" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::QUERY_INDEX as usize,
+            ),
+            "get_agent_activity" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GET_AGENT_ACTIVITY as usize,
+            ),
+            "verify_agent_head" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::VERIFY_AGENT_HEAD as usize,
+            ),
+            "create_group_secret" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::CREATE_GROUP_SECRET as usize,
+            ),
+            "group_encrypt" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GROUP_ENCRYPT as usize,
+            ),
+            "group_decrypt" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::GROUP_DECRYPT as usize,
+            ),
+            "derive_key" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::DERIVE_KEY as usize,
+            ),
+            "ecdh" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::ECDH as usize,
+            ),
+            "random_bytes" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::RANDOM_BYTES as usize,
+            ),
+            "seeded_random" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::SEEDED_RANDOM as usize,
+            ),
+            "sys_time" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::SYS_TIME as usize,
+            ),
+            "schedule_once" => FuncInstance::alloc_host(
+                Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
+                HcApiFuncIndex::SCHEDULE_ONCE as usize,
+            ),
+            // Add API function here
+            // ....
+            _ => {
+                return Err(InterpreterError::Function(format!(
+                    "host module doesn't export function with name {}",
+                    field_name
+                )))
+            }
+        };
+        Ok(func_ref)
+    }
+}
+
+/// Check the host still supports the host API version the DNA targets, and the zomes only import
+/// host functions that version has
+/// Code that doesn't compile isn't checked here, it fails when called
+pub fn check_host_api(dna: &Dna, module_cache: &ModuleCache) -> Result<(), String> {
+    let version = dna.host_api_version;
+    if !(MIN_HOST_API_VERSION..=HOST_API_VERSION).contains(&version) {
+        return Err(format!(
+            "the DNA targets host API version {}, this host supports versions {} to {}",
+            version, MIN_HOST_API_VERSION, HOST_API_VERSION
+        ));
+    }
+    let resolver = RuntimeModuleImportResolver { version };
+    let mut imports = ImportsBuilder::new();
+    imports.push_resolver("env", &resolver);
+    for zome in &dna.zomes {
+        for capability in &zome.capabilities {
+            if let Ok(module) = module_cache.get_or_compile(&capability.code.code) {
+                ModuleInstance::new(&module, &imports)
+                    .map_err(|error| format!("zome '{}': {}", zome.name, error))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Executes an exposed function in a wasm binary
+pub fn call(
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+    wasm: Vec<u8>,
+    function_name: &str,
+    parameters: Option<Vec<u8>>,
+) -> Result<Runtime, InterpreterError> {
+    // Create wasm module from wasm binary
+    let module = wasmi::Module::from_buffer(wasm)?;
+
+    call_module(
+        action_channel,
+        observer_channel,
+        &module,
+        function_name,
+        parameters,
+        &HostContext::default(),
+    )
+}
+
+/// Executes an exposed function in an already compiled wasm module
+/// see ModuleCache for reusing compiled modules across calls
+pub fn call_module(
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+    module: &wasmi::Module,
+    function_name: &str,
+    parameters: Option<Vec<u8>>,
+    host: &HostContext,
+) -> Result<Runtime, InterpreterError> {
+    // Describe invokable functions form within Zome
+    impl Externals for Runtime {
+        fn invoke_index(
+            &mut self,
+            index: usize,
+            args: RuntimeArgs,
+        ) -> Result<Option<RuntimeValue>, Trap> {
+            // a zome over its memory limit doesn't get to do anything more
+            if let Err(exceeded) = self.check_memory() {
+                return Err(Trap::new(TrapKind::Host(Box::new(exceeded))));
+            }
+            match index {
+                index if index == HcApiFuncIndex::LOG as usize => invoke_log(self, &args),
+                index if index == HcApiFuncIndex::COMMIT as usize => invoke_commit(self, &args),
+                index if index == HcApiFuncIndex::PROPERTY as usize => {
+                    invoke_property(self, &args)
+                }
+                index if index == HcApiFuncIndex::SCHEDULE as usize => {
+                    invoke_schedule(self, &args)
+                }
+                index if index == HcApiFuncIndex::CANCEL_SCHEDULE as usize => {
+                    invoke_cancel_schedule(self, &args)
+                }
+                index if index == HcApiFuncIndex::EMIT_SIGNAL as usize => {
+                    invoke_emit_signal(self, &args)
+                }
+                index if index == HcApiFuncIndex::CALL_REMOTE as usize => {
+                    invoke_call_remote(self, &args)
+                }
+                index if index == HcApiFuncIndex::ANCHOR as usize => invoke_anchor(self, &args),
+                index if index == HcApiFuncIndex::GET_LINKS as usize => {
+                    invoke_get_links(self, &args)
+                }
+                index if index == HcApiFuncIndex::KV_SET as usize => invoke_kv_set(self, &args),
+                index if index == HcApiFuncIndex::KV_GET as usize => invoke_kv_get(self, &args),
+                index if index == HcApiFuncIndex::COMMIT_TRANSACTION as usize => {
+                    invoke_commit_transaction(self, &args)
+                }
+                index if index == HcApiFuncIndex::GET_ENTRY as usize => {
+                    invoke_get_entry(self, &args)
+                }
+                index if index == HcApiFuncIndex::GET_VALIDATION_RECEIPTS as usize => {
+                    invoke_get_validation_receipts(self, &args)
+                }
+                index if index == HcApiFuncIndex::REMOTE_SIGNAL as usize => {
+                    invoke_remote_signal(self, &args)
+                }
+                index if index == HcApiFuncIndex::GET_ONLINE_AGENTS as usize => {
+                    invoke_get_online_agents(self, &args)
+                }
+                index if index == HcApiFuncIndex::BLOCK_AGENT as usize => {
+                    invoke_block_agent(self, &args)
+                }
+                index if index == HcApiFuncIndex::UNBLOCK_AGENT as usize => {
+                    invoke_unblock_agent(self, &args)
+                }
+                index if index == HcApiFuncIndex::CALL_CONTEXT as usize => {
+                    invoke_call_context(self, &args)
+                }
+                index if index == HcApiFuncIndex::SEARCH as usize => invoke_search(self, &args),
+                index if index == HcApiFuncIndex::QUERY_INDEX as usize => {
+                    invoke_query_index(self, &args)
+                }
+                index if index == HcApiFuncIndex::GET_AGENT_ACTIVITY as usize => {
+                    invoke_get_agent_activity(self, &args)
+                }
+                index if index == HcApiFuncIndex::VERIFY_AGENT_HEAD as usize => {
+                    invoke_verify_agent_head(self, &args)
+                }
+                index if index == HcApiFuncIndex::CREATE_GROUP_SECRET as usize => {
+                    invoke_create_group_secret(self, &args)
+                }
+                index if index == HcApiFuncIndex::GROUP_ENCRYPT as usize => {
+                    invoke_group_encrypt(self, &args)
+                }
+                index if index == HcApiFuncIndex::GROUP_DECRYPT as usize => {
+                    invoke_group_decrypt(self, &args)
+                }
+                index if index == HcApiFuncIndex::DERIVE_KEY as usize => {
+                    invoke_derive_key(self, &args)
+                }
+                index if index == HcApiFuncIndex::ECDH as usize => invoke_ecdh(self, &args),
+                index if index == HcApiFuncIndex::RANDOM_BYTES as usize => {
+                    invoke_random_bytes(self, &args)
+                }
+                index if index == HcApiFuncIndex::SEEDED_RANDOM as usize => {
+                    invoke_seeded_random(self, &args)
+                }
+                index if index == HcApiFuncIndex::SYS_TIME as usize => invoke_sys_time(self, &args),
+                index if index == HcApiFuncIndex::SCHEDULE_ONCE as usize => {
+                    invoke_schedule_once(self, &args)
+                }
                 index if index == HcApiFuncIndex::PRINT as usize => invoke_print(self, &args),
-                index if index == HcApiFuncIndex::COMMIT as usize => invoke_commit(self, &args),
                 // Add API function code here
                 // ....
                 _ => panic!("unknown function index"),
@@ -161,44 +2359,16 @@ pub fn call(
         }
     }
 
-    // Define invokable functions form within Zome
-    struct RuntimeModuleImportResolver;
-    impl ModuleImportResolver for RuntimeModuleImportResolver {
-        fn resolve_func(
-            &self,
-            field_name: &str,
-            _signature: &Signature,
-        ) -> Result<FuncRef, InterpreterError> {
-            let func_ref = match field_name {
-                "print" => FuncInstance::alloc_host(
-                    Signature::new(&[ValueType::I32][..], None),
-                    HcApiFuncIndex::PRINT as usize,
-                ),
-                "commit" => FuncInstance::alloc_host(
-                    Signature::new(&[ValueType::I32, ValueType::I32][..], Some(ValueType::I32)),
-                    HcApiFuncIndex::COMMIT as usize,
-                ),
-                // Add API function here
-                // ....
-                _ => {
-                    return Err(InterpreterError::Function(format!(
-                        "host module doesn't export function with name {}",
-                        field_name
-                    )))
-                }
-            };
-            Ok(func_ref)
-        }
-    }
-
     // Create Imports with previously described Resolver
+    let resolver = RuntimeModuleImportResolver {
+        version: host.api_version.unwrap_or(HOST_API_VERSION),
+    };
     let mut imports = ImportsBuilder::new();
-    imports.push_resolver("env", &RuntimeModuleImportResolver);
+    imports.push_resolver("env", &resolver);
 
     // Create module instance from wasm module, and without starting it
-    let wasm_instance = ModuleInstance::new(&module, &imports)
-        .expect("Failed to instantiate module")
-        .assert_no_start();
+    // it fails for modules importing host functions the version they target doesn't have
+    let wasm_instance = ModuleInstance::new(module, &imports)?.assert_no_start();
 
     // get wasm memory reference from module
     let wasm_memory = wasm_instance
@@ -216,29 +2386,47 @@ pub fn call(
 
     // instantiate runtime struct for passing external state data over wasm but not to wasm
     let mut runtime = Runtime {
-        print_output: vec![],
+        log_output: vec![],
+        committed: vec![],
         result: String::new(),
         action_channel: action_channel.clone(),
         observer_channel: observer_channel.clone(),
         memory: wasm_memory.clone(),
+        host: host.clone(),
+        memory_exceeded: None,
+        pending: Transaction::new(),
+        read_head: None,
     };
+    let memory_error = |exceeded: LimitExceeded| InterpreterError::Memory(exceeded.to_string());
+    runtime.check_memory().map_err(memory_error)?;
 
     // invoke function in wasm instance
     // arguments are info for wasm on how to retrieve complex input arguments
     // which have been set in memory module
-    let i32_result_length: i32 = wasm_instance
-        .invoke_export(
-            format!("{}_dispatch", function_name).as_str(),
-            &[
-                RuntimeValue::I32(RESULT_OFFSET as i32),
-                RuntimeValue::I32(params.len() as i32),
-            ],
-            &mut runtime,
-        )?
+    // the memory may have grown past the limit since the last host function call
+    let returned = wasm_instance.invoke_export(
+        format!("{}_dispatch", function_name).as_str(),
+        &[
+            RuntimeValue::I32(RESULT_OFFSET as i32),
+            RuntimeValue::I32(params.len() as i32),
+        ],
+        &mut runtime,
+    );
+    runtime.check_memory().map_err(memory_error)?;
+    let i32_result_length: i32 = returned?
         .unwrap()
         .try_into()
         .unwrap();
 
+    // what the zome committed goes on the chain now it returned, a call that fails commits nothing
+    flush(&mut runtime).map_err(|message| {
+        if message == ::agent::HEAD_MOVED {
+            InterpreterError::Host(Box::new(HeadMoved))
+        } else {
+            InterpreterError::Host(Box::new(CommitFailed(message)))
+        }
+    })?;
+
     // retrieve invoked wasm function's result that got written in memory
     let result = wasm_memory
         .get(RESULT_OFFSET, i32_result_length as usize)
@@ -252,7 +2440,33 @@ pub fn call(
 mod tests {
     use self::wabt::Wat2Wasm;
     use super::*;
-    use std::sync::mpsc::channel;
+    use agent::{
+        checkpoints::Checkpoint, devices::{DeviceBinding, ROOT_KEY_PATH},
+    };
+    use dht::{
+        aspect::{self, Aspect}, entries::EntryDetails,
+        links::{tests::test_link, LinkPage, LinkResult},
+    };
+    use hash_table::{
+        provenance::tests::{test_agent_address, test_signing_keys}, status::EntryStatus,
+    };
+    use logger::{tests::TestLogger, LogLevel};
+    use network::{
+        direct_message::{
+            tests::{test_caller, test_responder}, DirectMessage, MemoryNetwork,
+        },
+        config::{NetworkConfig, PresenceConfig}, remote_signal::RemoteSignal,
+    };
+    use nucleus::Action;
+    use validation::{
+        links::tests::{test_comment, test_link_dna, test_post}, receipts::tests::test_receipt,
+    };
+    use std::{
+        sync::{
+            mpsc::{channel, Receiver}, Arc, Mutex,
+        },
+        thread, time::Duration,
+    };
 
     fn test_wasm() -> Vec<u8> {
         let wasm_binary = Wat2Wasm::new()
@@ -261,18 +2475,331 @@ mod tests {
             .convert(
                 r#"
                 (module
-                    (type (;0;) (func (result i32)))
-                    (type (;1;) (func (param i32)))
-                    (type (;2;) (func))
-                    (import "env" "print" (func $print (type 1)))
-                    (func (export "test_print_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
-                        i32.const 1337
-                        call $print
+                    (type (;0;) (func (param i32 i32) (result i32)))
+                    (type (;1;) (func))
+                    (import "env" "log" (func $log (type 0)))
+                    (import "env" "property" (func $property (type 0)))
+                    (import "env" "schedule" (func $schedule (type 0)))
+                    (import "env" "cancel_schedule" (func $cancel_schedule (type 0)))
+                    (import "env" "emit_signal" (func $emit_signal (type 0)))
+                    (import "env" "call_remote" (func $call_remote (type 0)))
+                    (import "env" "anchor" (func $anchor (type 0)))
+                    (import "env" "get_links" (func $get_links (type 0)))
+                    (import "env" "kv_set" (func $kv_set (type 0)))
+                    (import "env" "kv_get" (func $kv_get (type 0)))
+                    (import "env" "commit_transaction" (func $commit_transaction (type 0)))
+                    (import "env" "get_entry" (func $get_entry (type 0)))
+                    (import "env" "get_validation_receipts" (func $get_validation_receipts (type 0)))
+                    (import "env" "remote_signal" (func $remote_signal (type 0)))
+                    (import "env" "get_online_agents" (func $get_online_agents (type 0)))
+                    (import "env" "block_agent" (func $block_agent (type 0)))
+                    (import "env" "unblock_agent" (func $unblock_agent (type 0)))
+                    (import "env" "call_context" (func $call_context (type 0)))
+                    (func (export "test_log_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        i32.const 1024
+                        i32.const 56
+                        call $log
+                        drop
+                        i32.const 0)
+                    (func (export "test_log_out_of_bounds_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        i32.const -16
+                        i32.const 56
+                        call $log
+                        drop
+                        i32.const 0)
+                    (func (export "test_property_out_of_bounds_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        i32.const -16
+                        i32.const 56
+                        call $property
+                        drop
+                        i32.const 0)
+                    (func (export "test_property_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $property
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_schedule_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $schedule
+                        drop
+                        i32.const 0)
+                    (func (export "test_cancel_schedule_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $cancel_schedule
+                        drop
+                        i32.const 0)
+                    (func (export "test_emit_signal_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $emit_signal
+                        drop
+                        i32.const 0)
+                    (func (export "test_call_remote_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $call_remote
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_anchor_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $anchor
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_get_links_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $get_links
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_kv_set_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $kv_set
+                        drop
+                        i32.const 0)
+                    (func (export "test_kv_get_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $kv_get
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_commit_transaction_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $commit_transaction
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_get_entry_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $get_entry
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_remote_signal_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $remote_signal
+                        drop
+                        i32.const 0)
+                    (func (export "test_get_validation_receipts_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $get_validation_receipts
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_get_online_agents_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $get_online_agents
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func (export "test_block_agent_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $block_agent
+                        drop
                         i32.const 0)
-                    (func $rust_eh_personality (type 2))
+                    (func (export "test_unblock_agent_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        get_local $p0
+                        get_local $p1
+                        call $unblock_agent
+                        drop
+                        i32.const 0)
+                    (func (export "test_call_context_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                        (local $i i32)
+                        get_local $p0
+                        get_local $p1
+                        call $call_context
+                        drop
+                        get_local $p0
+                        set_local $i
+                        block
+                            loop
+                                get_local $i
+                                i32.load8_u
+                                i32.eqz
+                                br_if 1
+                                get_local $i
+                                i32.const 1
+                                i32.add
+                                set_local $i
+                                br 0
+                            end
+                        end
+                        get_local $i
+                        get_local $p0
+                        i32.sub)
+                    (func $rust_eh_personality (type 1))
                     (table (;0;) 1 1 anyfunc)
                     (memory (;0;) 17)
                     (global (;0;) (mut i32) (i32.const 1049600))
+                    (data (i32.const 1024) "{\"level\":\"info\",\"target\":\"test\",\"payload\":{\"answer\":42}}")
                     (export "memory" (memory 0))
                     (export "rust_eh_personality" (func $rust_eh_personality)))
             "#,
@@ -283,17 +2810,1042 @@ mod tests {
     }
 
     #[test]
-    fn test_print() {
+    fn test_log() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let logger = Arc::new(Mutex::new(TestLogger::default()));
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            ..Default::default()
+        };
+        host.logger.attach(logger.clone(), "test_instance");
+
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_log",
+            None,
+            &host,
+        ).expect("test_log should be callable");
+        assert_eq!(
+            vec![ZomeLogMessage {
+                level: LogLevel::Info,
+                target: "test".to_string(),
+                payload: json!({"answer": 42}),
+            }],
+            runtime.log_output
+        );
+        assert_eq!(1, logger.lock().unwrap().log.len());
+    }
+
+    #[test]
+    /// a log argument the zome doesn't have in its memory gets an error code back, not a panic
+    fn test_log_out_of_bounds() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_log_out_of_bounds",
+            None,
+            &HostContext::default(),
+        ).expect("test_log_out_of_bounds should be callable");
+        assert!(runtime.log_output.is_empty());
+    }
+
+    /// channels for host functions to dispatch on
+    /// each action is reduced into the State of the actions before it for the observers waiting on
+    /// it, and handed back through the receiver
+    fn test_dispatch_channels() -> (
+        Sender<state::ActionWrapper>,
+        Sender<Observer>,
+        Receiver<state::Action>,
+    ) {
+        let (action_channel, rx_action) = channel::<state::ActionWrapper>();
+        let (tx_observer, rx_observer) = channel::<Observer>();
+        let (tx_dispatched, rx_dispatched) = channel();
+        thread::spawn(move || {
+            let (sender, _receiver) = channel();
+            let (tx_unused, _observer) = channel();
+            let mut state = state::State::new();
+            let mut observers: Vec<Observer> = Vec::new();
+            for wrapper in rx_action {
+                state = state.reduce(wrapper.clone(), &sender, &tx_unused);
+                observers.extend(rx_observer.try_iter());
+                observers = observers
+                    .into_iter()
+                    .filter_map(|mut observer| {
+                        if (observer.sensor)(&state) {
+                            None
+                        } else {
+                            Some(observer)
+                        }
+                    })
+                    .collect();
+                if tx_dispatched.send(wrapper.action).is_err() {
+                    return;
+                }
+            }
+        });
+        (action_channel, tx_observer, rx_dispatched)
+    }
+
+    #[test]
+    fn test_schedule() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            ..Default::default()
+        };
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_schedule",
+            Some(
+                br#"{"id":"digest","capability":"main","function":"send_digest","interval_secs":60}"#
+                    .to_vec(),
+            ),
+            &host,
+        ).expect("test_schedule should be callable");
+        match dispatched.recv().unwrap() {
+            state::Action::Nucleus(Action::Schedule(schedule)) => {
+                assert_eq!("test_zome/digest", schedule.key());
+                assert_eq!("main", schedule.capability);
+                assert_eq!("send_digest", schedule.function);
+                assert_eq!(60, schedule.interval_secs);
+                assert_eq!(0, schedule.offset_secs);
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+
+        call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_cancel_schedule",
+            Some(br#"{"id":"digest"}"#.to_vec()),
+            &host,
+        ).expect("test_cancel_schedule should be callable");
+        assert_eq!(
+            state::Action::Nucleus(Action::CancelSchedule("test_zome/digest".to_string())),
+            dispatched.recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_anchor() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_anchor",
+            Some(br#"{"path":"posts/2018"}"#.to_vec()),
+            &HostContext::default(),
+        ).expect("test_anchor should be callable");
+        let path = Path::parse("posts/2018");
+        assert_eq!(json!({ "address": path.address() }).to_string(), runtime.result);
+        assert_eq!(5, runtime.committed.len());
+        // everything committed in the call goes on the chain at once
+        match dispatched.recv().unwrap() {
+            state::Action::Agent(::agent::Action::CommitTransaction(transaction)) => {
+                assert_eq!(&path.index_entries()[..], transaction.entries())
+            }
+            action => panic!("unexpected action {:?}", action),
+        }
+    }
+
+    #[test]
+    fn test_commit_transaction() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        ::instance::dispatch_action(
+            &action_channel,
+            state::Action::Nucleus(Action::InitApplication(test_link_dna())),
+        );
+        dispatched.recv().unwrap();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let commit = |links: serde_json::Value| {
+            let input = json!({
+                "entries": [
+                    {"entry_type_name": "post", "entry_content": "hello"},
+                    {"entry_type_name": "comment", "entry_content": "nice post"},
+                ],
+                "links": links,
+            });
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                "test_commit_transaction",
+                Some(input.to_string().into_bytes()),
+                &HostContext::default(),
+            ).expect("test_commit_transaction should be callable")
+        };
+
+        let runtime = commit(json!([{"base": 0, "target": 1, "tag": "comments"}]));
+        let link = Link::new(&test_post().key(), &test_comment().key(), "comments");
+        let hashes = vec![test_post().hash(), test_comment().hash(), link.to_entry().hash()];
+        assert_eq!(json!({ "hashes": hashes }).to_string(), runtime.result);
+        assert_eq!(3, runtime.committed.len());
+
+        // nothing is committed if a link doesn't validate
+        let runtime = commit(json!([{"base": 1, "target": 0, "tag": "comments"}]));
+        assert!(runtime.committed.is_empty());
+        let runtime = commit(json!([{"base": 0, "target": 5, "tag": "comments"}]));
+        assert_eq!("no entry staged at position 5", runtime.result);
+        assert!(runtime.committed.is_empty());
+        let runtime = commit(json!([{"base": 0, "target": "missing", "tag": "comments"}]));
+        assert_eq!("link target missing not found", runtime.result);
+    }
+
+    #[test]
+    fn test_get_links() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        for n in 0..3 {
+            let (_, link, meta) = test_link(n);
+            ::instance::dispatch_action(
+                &action_channel,
+                state::Action::Dht(::dht::Action::HoldLink(link, meta)),
+            );
+            dispatched.recv().unwrap();
+        }
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_get_links",
+            Some(br#"{"base":"base","order":"newest_first","limit":2}"#.to_vec()),
+            &HostContext::default(),
+        ).expect("test_get_links should be callable");
+        let page: LinkPage = serde_json::from_str(&runtime.result).unwrap();
+        assert_eq!(
+            vec!["target_2", "target_1"],
+            page.links
+                .iter()
+                .map(|link| link.target.as_str())
+                .collect::<Vec<&str>>()
+        );
+
+        let input = json!({
+            "base": "base",
+            "tag_prefix": "tag_",
+            "order": "newest_first",
+            "cursor": page.next,
+        });
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_get_links",
+            Some(input.to_string().into_bytes()),
+            &HostContext::default(),
+        ).expect("test_get_links should be callable");
+        let page: LinkPage = serde_json::from_str(&runtime.result).unwrap();
+        assert_eq!(1, page.links.len());
+        assert_eq!("target_0", page.links[0].target);
+        assert_eq!(None, page.next);
+    }
+
+    #[test]
+    fn test_get_entry() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        let entry = Entry::new("post", "hello");
+        for action in [
+            ::dht::Action::Hold(entry.clone()),
+            ::dht::Action::HoldAspect(entry.key(), Aspect::Delete),
+        ] {
+            ::instance::dispatch_action(&action_channel, state::Action::Dht(action));
+            dispatched.recv().unwrap();
+        }
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let get_entry = |input: serde_json::Value| {
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                "test_get_entry",
+                Some(input.to_string().into_bytes()),
+                &HostContext::default(),
+            ).expect("test_get_entry should be callable")
+                .result
+        };
+
+        assert_eq!("null", get_entry(json!({ "address": entry.key() })));
+        let deleted = json!({"address": entry.key(), "status_request": "deleted"});
+        assert_eq!(serde_json::to_string(&entry).unwrap(), get_entry(deleted));
+
+        let details: EntryDetails = serde_json::from_str(&get_entry(json!({
+            "address": entry.key(),
+            "result_type": "details",
+        }))).unwrap();
+        assert_eq!(Some(EntryStatus::Deleted), details.status);
+        assert_eq!(None, details.entry);
+
+        let never: EntryDetails = serde_json::from_str(&get_entry(json!({
+            "address": "nowhere",
+            "result_type": "details",
+        }))).unwrap();
+        assert_eq!(None, never.status);
+
+        // nobody else to ask, the read comes with how it was answered
+        let quorum = json!({"address": entry.key(), "status_request": "deleted", "consistency": {
+            "quorum": 3,
+        }});
+        let read: Read<Option<Entry>> = serde_json::from_str(&get_entry(quorum)).unwrap();
+        assert_eq!(Some(entry), read.result);
+        assert_eq!(ReadConsistency::Quorum(3), read.meta.consistency);
+        assert!(read.meta.responders.is_empty());
+        assert_eq!(0, read.meta.stats.asked);
+    }
+
+    #[test]
+    fn test_get_validation_receipts() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext::default();
+        host.receipts.add(test_receipt("bob")).unwrap();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let get_receipts = |address: &str| {
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                "test_get_validation_receipts",
+                Some(address.as_bytes().to_vec()),
+                &host,
+            ).expect("test_get_validation_receipts should be callable")
+                .result
+        };
+
+        assert_eq!("[]", get_receipts("nowhere"));
+        assert_eq!(
+            serde_json::to_string(&vec![test_receipt("bob")]).unwrap(),
+            get_receipts(&test_receipt("bob").address)
+        );
+    }
+
+    #[test]
+    fn test_remote_signal() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let network = MemoryNetwork::new();
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            ..Default::default()
+        };
+        let _alice = host.messenger.connect(&network, "alice");
+        let bob = network.connect("bob");
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_remote_signal",
+            Some(br#"{"agents":["bob"],"payload":{"typing":true}}"#.to_vec()),
+            &host,
+        ).expect("test_remote_signal should be callable");
+        let envelope = bob.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(
+            DirectMessage::RemoteSignals(vec![RemoteSignal {
+                from: "alice".to_string(),
+                zome: "test_zome".to_string(),
+                payload: json!({"typing": true}),
+            }]),
+            envelope.message
+        );
+    }
+
+    #[test]
+    fn test_get_online_agents() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext::default();
+        host.presence.received("bob", ::nucleus::scheduler::unix_now());
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let get_online_agents = || {
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                "test_get_online_agents",
+                Some(b"{}".to_vec()),
+                &host,
+            ).expect("test_get_online_agents should be callable")
+                .result
+        };
+
+        assert_eq!("[]", get_online_agents());
+        host.messenger.configure(&NetworkConfig {
+            presence: Some(PresenceConfig::default()),
+            ..NetworkConfig::default()
+        });
+        assert_eq!(r#"["bob"]"#, get_online_agents());
+    }
+
+    #[test]
+    fn test_call_context() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let call = FunctionCall::new("test_zome", "test_cap", "main", "{}")
+            .with_caller("bob", Some("secret".to_string()));
+        let host = HostContext {
+            call: call.context(call.requested_at + 1),
+            ..Default::default()
+        };
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_call_context",
+            Some(b"{}".to_vec()),
+            &host,
+        ).expect("test_call_context should be callable");
+        let context: CallContext = serde_json::from_str(&runtime.result).unwrap();
+        assert_eq!(host.call, context);
+        assert_eq!(Some("bob".to_string()), context.caller);
+        assert_eq!(Some("secret".to_string()), context.cap_secret);
+        assert_eq!(context.requested_at + 1, context.started_at);
+    }
+
+    #[test]
+    fn test_block_agent() {
+        let (action_channel, tx_observer, dispatched) = test_dispatch_channels();
+        let host = HostContext::default();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        for function in &["test_block_agent", "test_unblock_agent"] {
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                function,
+                Some(b"bob".to_vec()),
+                &host,
+            ).expect("block functions should be callable");
+        }
+
+        let committed = (0..2)
+            .flat_map(|_| match dispatched.recv().unwrap() {
+                state::Action::Agent(::agent::Action::CommitTransaction(transaction)) => {
+                    transaction.entries().to_vec()
+                }
+                action => panic!("unexpected action {:?}", action),
+            })
+            .collect::<Vec<Entry>>();
+        assert_eq!(vec![blocks::block_entry("bob"), blocks::unblock_entry("bob")], committed);
+        // blocks stay private
+        assert_eq!(0, host.outbox.depth());
+    }
+
+    #[test]
+    fn test_emit_signal() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            ..Default::default()
+        };
+        let signals = host.signals.subscribe();
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_emit_signal",
+            Some(br#"{"name":"new_post","payload":{"title":"hello"}}"#.to_vec()),
+            &host,
+        ).expect("test_emit_signal should be callable");
+        assert_eq!(
+            Signal {
+                zome: "test_zome".to_string(),
+                name: "new_post".to_string(),
+                payload: json!({"title": "hello"}),
+            },
+            signals.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_kv() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            ..Default::default()
+        };
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let call = |function: &str, parameters: &str| {
+            call_module(
+                &action_channel,
+                &tx_observer,
+                &module,
+                function,
+                Some(parameters.as_bytes().to_vec()),
+                &host,
+            ).expect("kv functions should be callable")
+                .result
+        };
+
+        assert_eq!("null", call("test_kv_get", "draft"));
+        call("test_kv_set", r#"{"key":"draft","value":{"title":"hello"}}"#);
+        assert_eq!(r#"{"title":"hello"}"#, call("test_kv_get", "draft"));
+        assert_eq!(
+            Some(json!({"title": "hello"})),
+            host.scratch.get("test_zome", "draft")
+        );
+        assert_eq!(None, host.scratch.get("other_zome", "draft"));
+
+        call("test_kv_set", r#"{"key":"draft"}"#);
+        assert_eq!("null", call("test_kv_get", "draft"));
+    }
+
+    #[test]
+    fn test_call_remote() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let network = MemoryNetwork::new();
+        test_responder(&network, "bob");
+        let host = HostContext {
+            zome: "test_zome".to_string(),
+            messenger: test_caller(&network, "alice"),
+            ..Default::default()
+        };
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+        let input = |agent: &str| {
+            let input = json!({
+                "agent": agent,
+                "zome": "chat",
+                "capability": "main",
+                "function": "inbox",
+                "parameters": "hi",
+            });
+            input.to_string().into_bytes()
+        };
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_call_remote",
+            Some(input("bob")),
+            &host,
+        ).expect("test_call_remote should be callable");
+        assert_eq!("hi from alice", runtime.result);
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_call_remote",
+            Some(input("carol")),
+            &host,
+        ).expect("test_call_remote should be callable");
+        assert_eq!("agent carol is not reachable", runtime.result);
+    }
+
+    #[test]
+    fn test_property() {
         let (action_channel, _) = channel::<::state::ActionWrapper>();
         let (tx_observer, _observer) = channel::<Observer>();
-        let runtime = call(
+        let host = HostContext {
+            properties: json!({"max_post_length": 280}),
+            ..Default::default()
+        };
+        let module = wasmi::Module::from_buffer(test_wasm()).unwrap();
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_property",
+            Some(b"max_post_length".to_vec()),
+            &host,
+        ).expect("test_property should be callable");
+        assert_eq!("280", runtime.result);
+
+        let runtime = call_module(
+            &action_channel,
+            &tx_observer,
+            &module,
+            "test_property",
+            Some(b"missing".to_vec()),
+            &host,
+        ).expect("test_property should be callable");
+        assert_eq!("null", runtime.result);
+
+        // a name the zome doesn't have in its memory gets an error code back, not a panic
+        let runtime = call_module(
             &action_channel,
             &tx_observer,
-            test_wasm(),
-            "test_print",
+            &module,
+            "test_property_out_of_bounds",
             None,
-        ).expect("test_print should be callable");
-        assert_eq!(runtime.print_output.len(), 1);
-        assert_eq!(runtime.print_output[0], 1337)
+            &host,
+        ).expect("test_property_out_of_bounds should be callable");
+        assert_eq!("", runtime.result);
+    }
+
+    #[test]
+    /// host output only goes in what is left of the page of its argument, an error code
+    /// and nothing written otherwise
+    fn write_output_bounds() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let mut runtime =
+            Runtime::without_wasm(&action_channel, &tx_observer, &HostContext::default());
+        runtime.memory = MemoryInstance::alloc(Pages(2), Some(Pages(2))).unwrap();
+        let write = |runtime: &Runtime, offset: i32| {
+            let args = [RuntimeValue::I32(offset), RuntimeValue::I32(0)];
+            write_output(runtime, &RuntimeArgs::from(&args[..]), "hello")
+        };
+        let page = OUTPUT_PAGE_SIZE as i32;
+
+        assert_eq!(HcApiReturnCode::SUCCESS, write(&runtime, 0));
+        assert_eq!(b"hello\0".to_vec(), runtime.memory.get(0, 6).unwrap());
+
+        // the null terminator has to fit too
+        assert_eq!(HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE, write(&runtime, page - 5));
+        assert_eq!(vec![0; 5], runtime.memory.get(page as u32 - 5, 5).unwrap());
+        assert_eq!(HcApiReturnCode::SUCCESS, write(&runtime, 2 * page - 6));
+
+        // past the end of memory
+        assert_eq!(HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE, write(&runtime, 2 * page));
+        assert_eq!(HcApiReturnCode::ERROR_OUTPUT_TOO_LARGE, write(&runtime, -16));
+    }
+
+    /// module with memory of at least 1 page, its test_dispatch function grows it by pages
+    fn test_growing_module(pages: i32) -> wasmi::Module {
+        use parity_wasm::{builder, elements};
+        let module = builder::module()
+            .memory()
+            .with_min(1)
+            .build()
+            .export()
+            .field("memory")
+            .internal()
+            .memory(0)
+            .build()
+            .function()
+            .signature()
+            .with_params(vec![elements::ValueType::I32, elements::ValueType::I32])
+            .with_return_type(Some(elements::ValueType::I32))
+            .build()
+            .body()
+            .with_instructions(elements::Instructions::new(vec![
+                elements::Instruction::I32Const(pages),
+                elements::Instruction::GrowMemory(0),
+                elements::Instruction::Drop,
+                elements::Instruction::I32Const(0),
+                elements::Instruction::End,
+            ]))
+            .build()
+            .build()
+            .export()
+            .field("test_dispatch")
+            .internal()
+            .func(0)
+            .build()
+            .build();
+        wasmi::Module::from_parity_wasm_module(module).unwrap()
+    }
+
+    /// a module importing one host function with the signature
+    fn test_importing_module(
+        field: &str,
+        params: Vec<parity_wasm::elements::ValueType>,
+        result: Option<parity_wasm::elements::ValueType>,
+    ) -> Vec<u8> {
+        use parity_wasm::builder;
+        let module = builder::module()
+            .with_signatures(vec![
+                builder::signature()
+                    .with_params(params)
+                    .with_return_type(result)
+                    .build_sig(),
+            ])
+            .import()
+            .path("env", field)
+            .external()
+            .func(0)
+            .build()
+            .build();
+        parity_wasm::serialize(module).unwrap()
+    }
+
+    #[test]
+    fn test_address_format() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let address = Entry::new("fooType", "bar").key();
+        let cid = "bafkreih43yvs5w5fnp2aqya7w4q75g24gogrb3sct2qe7lsvcg3i7p4pxe";
+
+        let runtime = Runtime::without_wasm(&action_channel, &tx_observer, &HostContext::default());
+        assert_eq!(address, express(&runtime, address.clone()));
+
+        let host = HostContext {
+            address_format: AddressFormat::Cid,
+            ..Default::default()
+        };
+        let runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        assert_eq!(cid, express(&runtime, address.clone()));
+        // what isn't an entry address stays as it is
+        assert_eq!("alice", express(&runtime, "alice".to_string()));
+        let page = LinkPage {
+            links: vec![LinkResult {
+                address: address.clone(),
+                target: address.clone(),
+                tag: "comments".to_string(),
+                author: "alice".to_string(),
+                timestamp: 1,
+            }],
+            next: None,
+        };
+        let page = express_links(&runtime, page);
+        assert_eq!(cid, page.links[0].address);
+        assert_eq!(cid, page.links[0].target);
+        assert_eq!("alice", page.links[0].author);
+    }
+
+    #[test]
+    /// results come a page at a time
+    fn pages() {
+        let results: Vec<usize> = (0..5).collect();
+        assert_eq!((vec![0, 1], Some(2)), page_of(results.clone(), 0, 2));
+        assert_eq!((vec![4], None), page_of(results.clone(), 4, 2));
+        assert_eq!((vec![3, 4], None), page_of(results.clone(), 3, 2));
+        assert_eq!((Vec::<usize>::new(), None), page_of(results.clone(), 9, 2));
+        assert_eq!((results.clone(), None), page_of(results, 0, usize::MAX));
+    }
+
+    #[test]
+    fn test_search() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let entry = Entry::new("post", "cats and dogs");
+        let dht = ::dht::tests::test_reduce(
+            ::dht::tests::test_dht_state(),
+            ::dht::Action::Hold(entry.clone()),
+        );
+        let host = HostContext {
+            address_format: AddressFormat::Cid,
+            ..Default::default()
+        };
+        host.search_index
+            .update(&::index::tests::test_search_dna(), &[], &dht);
+
+        let runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        let found = runtime.search("Dogs", &["post".to_string()]);
+        assert_eq!(vec![cid::to_cid(&entry.key(), Codec::Raw).unwrap()], found);
+        assert!(runtime.search("birds", &[]).is_empty());
+    }
+
+    #[test]
+    fn test_query_index() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let alice = ::hash_table::field_index::tests::test_profile("alice", 30);
+        let dht = ::dht::tests::test_reduce(
+            ::dht::tests::test_dht_state(),
+            ::dht::Action::Hold(alice.clone()),
+        );
+        let host = HostContext::default();
+        host.search_index
+            .update(&::index::tests::test_search_dna(), &[], &dht);
+
+        let runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        // queries come in as JSON
+        let query: IndexQuery = serde_json::from_str(r#"{"range":{"from":18,"to":null}}"#).unwrap();
+        assert_eq!(
+            Some(vec![alice.key()]),
+            runtime.query_index("profile", "about.age", &query)
+        );
+        let query: IndexQuery = serde_json::from_str(r#"{"equals":"bob"}"#).unwrap();
+        assert_eq!(
+            Some(Vec::new()),
+            runtime.query_index("profile", "handle", &query)
+        );
+        assert_eq!(None, runtime.query_index("profile", "name", &query));
+    }
+
+    #[test]
+    fn test_get_agent_activity() {
+        let (action_channel, tx_observer, _dispatched) = test_dispatch_channels();
+        let alice = test_agent_address("alice");
+        let headers = ::dht::activity::tests::test_activity_headers(3);
+        for header in &headers {
+            let aspect = Aspect::Activity(header.sign(&test_signing_keys("alice")));
+            let hold = ::dht::Action::HoldAspect(alice.clone(), aspect);
+            ::instance::dispatch_action(&action_channel, state::Action::Dht(hold));
+        }
+        let host = HostContext {
+            address_format: AddressFormat::Cid,
+            ..Default::default()
+        };
+
+        let mut runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        let activity = runtime.get_agent_activity(&alice, &(1..10));
+        assert_eq!(vec![1, 2], activity.headers.iter().map(|h| h.index).collect::<Vec<_>>());
+        assert_eq!(headers[1].hash(), activity.headers[0].hash);
+        // entry addresses come as the DNA has them expressed
+        assert_eq!(
+            cid::to_cid(headers[1].entry(), Codec::Raw).unwrap(),
+            activity.headers[0].entry
+        );
+        assert_eq!(Some(2), activity.highest);
+
+        let checkpoint = Checkpoint::new(&alice, &headers[2].hash(), 2, 100);
+        let hold = ::dht::Action::HoldAspect(alice.clone(), Aspect::Checkpoint(checkpoint));
+        ::instance::dispatch_action(&action_channel, state::Action::Dht(hold));
+        match runtime.verify_agent_head(&alice) {
+            HeadVerification::Verified(checkpoint) => assert_eq!(2, checkpoint.index),
+            verification => panic!("expected verified, got {:?}", verification),
+        }
+    }
+
+    #[test]
+    fn test_read_only() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext {
+            read_only: true,
+            ..Default::default()
+        };
+        let mut runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        // reading alone is fine
+        assert_eq!(Ok(()), runtime.complete());
+
+        commit_entry(&mut runtime, &Entry::new("fooType", "bar"));
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric(READ_ONLY_COMMIT.to_string())),
+            runtime.complete()
+        );
+        assert!(runtime.committed.is_empty());
+    }
+
+    #[test]
+    fn test_outbox_full() {
+        let (action_channel, rx_action) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext {
+            priority: Priority::Low,
+            ..Default::default()
+        };
+        host.outbox.set_max_depth(Some(1));
+        host.outbox
+            .queue_at(Priority::Low, Outgoing::Publish("import".to_string(), Vec::new()))
+            .unwrap();
+        let mut runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+
+        // what the call publishes waits behind the import, which has no room left
+        commit_entry(&mut runtime, &Entry::new("fooType", "bar"));
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric(
+                "outbox_depth limit of 1 exceeded, 2 requested".to_string()
+            )),
+            runtime.complete()
+        );
+        assert!(runtime.committed.is_empty());
+        match rx_action.try_recv().map(|wrapper| wrapper.action) {
+            Ok(state::Action::Nucleus(::nucleus::Action::ReportLimitExceeded(exceeded))) => {
+                assert_eq!(Resource::OutboxDepth, exceeded.resource)
+            }
+            action => panic!("expected the limit reported, got {:?}", action),
+        }
+        assert_eq!(Some(&1), host.outbox.metrics().refused.get(&Priority::Low));
+    }
+
+    #[test]
+    /// headers go out signed by the device key, its binding to the neighborhood of the root key
+    fn test_publish_signed() {
+        let (action_channel, tx_observer, _dispatched) = test_dispatch_channels();
+        let root = Keystore::default();
+        let host = HostContext {
+            keystore: root.device_keystore("phone").unwrap(),
+            ..Default::default()
+        };
+        let agent = root.signing_key(ROOT_KEY_PATH).unwrap().to_base58();
+        let _agent = host.messenger.connect(&MemoryNetwork::new(), &agent);
+        let phone = host.keystore.signing_key(DEVICE_SIGNING_KEY_PATH).unwrap();
+        let binding = DeviceBinding::new(&root, "phone", &phone).unwrap().to_entry();
+        let mut runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        commit_entry(&mut runtime, &binding);
+        commit_entry(&mut runtime, &Entry::new("post", "hello"));
+        assert_eq!(Ok(()), runtime.complete());
+
+        let published: Vec<(String, Aspect)> = host
+            .outbox
+            .pending()
+            .into_iter()
+            .flat_map(|outgoing| match outgoing {
+                Outgoing::Publish(base, aspects) => {
+                    aspects.into_iter().map(|aspect| (base.clone(), aspect)).collect()
+                }
+                _ => Vec::new(),
+            })
+            .collect();
+        let is_device = |aspect: &Aspect| matches!(*aspect, Aspect::Device(_));
+        let is_activity = |aspect: &Aspect| matches!(*aspect, Aspect::Activity(_));
+        assert!(published.iter().any(|(base, aspect)| *base == agent && is_device(aspect)));
+        assert_eq!(2, published.iter().filter(|(_, aspect)| is_activity(aspect)).count());
+        // the activity belongs at the root key once the binding is held there
+        let devices = aspect::devices(published.iter().map(|(_, aspect)| aspect));
+        for (base, aspect) in &published {
+            assert!(aspect.belongs_with(base, &devices), "{:?} at {}", aspect, base);
+        }
+        assert!(!published
+            .iter()
+            .filter(|(_, aspect)| is_activity(aspect))
+            .any(|(base, aspect)| aspect.belongs_at(base)));
+    }
+
+    #[test]
+    fn test_group_secret() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext::default();
+        let runtime = Runtime::without_wasm(&action_channel, &tx_observer, &host);
+        let members = vec!["bob".to_string()];
+        // a secret needs an agent to come from
+        assert!(runtime.create_group_secret(&members).is_err());
+
+        let network = MemoryNetwork::new();
+        let _alice = host.messenger.connect(&network, "alice");
+        let group = runtime.create_group_secret(&members).unwrap();
+        let secret = host.group_secrets.get(&group).unwrap();
+        assert_eq!(vec!["alice", "bob"], secret.members);
+        assert_eq!(
+            vec![Outgoing::Message("bob".to_string(), DirectMessage::GroupSecret(secret))],
+            host.outbox.pending()
+        );
+
+        let ciphertext = runtime.group_encrypt(&group, "the plan").unwrap();
+        assert_eq!(Ok("the plan".to_string()), runtime.group_decrypt(&group, &ciphertext));
+        assert!(runtime.group_encrypt("unknown", "the plan").is_err());
+        assert!(runtime.group_decrypt("unknown", &ciphertext).is_err());
+        let other = runtime.create_group_secret(&members).unwrap();
+        assert!(runtime.group_decrypt(&other, &ciphertext).is_err());
+    }
+
+    #[test]
+    fn test_keystore() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let alice = Runtime::without_wasm(&action_channel, &tx_observer, &HostContext::default());
+        let bob = Runtime::without_wasm(&action_channel, &tx_observer, &HostContext::default());
+        let alice_key = alice.derive_key("chat/bob").unwrap();
+        assert_eq!(Ok(alice_key.clone()), alice.derive_key("chat/bob"));
+        let bob_key = bob.derive_key("chat/alice").unwrap();
+
+        let key = alice.ecdh("chat/bob", &bob_key).unwrap();
+        assert_eq!(Ok(key), bob.ecdh("chat/alice", &alice_key));
+        assert!(alice.ecdh("chat/bob", "not a key").is_err());
+        assert!(alice.ecdh("chat/bob", &[0; 32].to_base58()).is_err());
+    }
+
+    #[test]
+    fn test_host_api_version() {
+        use holochain_dna::zome::{capabilities::Capability, Zome};
+        use parity_wasm::elements::ValueType::I32;
+        let dna_importing = |code: Vec<u8>, version: u32| {
+            let mut capability = Capability::new();
+            capability.code.code = code;
+            let mut zome = Zome::new();
+            zome.name = "test_zome".to_string();
+            zome.capabilities.push(capability);
+            let mut dna = Dna::new();
+            dna.host_api_version = version;
+            dna.zomes.push(zome);
+            dna
+        };
+        let print = test_importing_module("print", vec![I32], None);
+        let log = test_importing_module("log", vec![I32, I32], Some(I32));
+        let commit = test_importing_module("commit", vec![I32, I32], Some(I32));
+        let cache = ModuleCache::default();
+        let check = |code: &Vec<u8>, version| {
+            check_host_api(&dna_importing(code.clone(), version), &cache)
+        };
+
+        // the first host API printed, print is shimmed for it and gone since
+        assert_eq!(Ok(()), check(&print, HOST_API_V1));
+        assert!(check(&print, HOST_API_V2).is_err());
+        assert_eq!(Ok(()), check(&commit, HOST_API_V1));
+        assert_eq!(Ok(()), check(&commit, HOST_API_V2));
+        assert_eq!(Ok(()), check(&log, HOST_API_V2));
+        assert_eq!(
+            Err(
+                "zome 'test_zome': Function: host function log needs host API version 2, the DNA \
+                 targets 1"
+                    .to_string()
+            ),
+            check(&log, HOST_API_V1)
+        );
+
+        assert_eq!(
+            Err(
+                "the DNA targets host API version 0, this host supports versions 1 to 2"
+                    .to_string()
+            ),
+            check(&commit, 0)
+        );
+        assert!(check(&commit, HOST_API_VERSION + 1).is_err());
+    }
+
+    #[test]
+    fn test_memory_limit() {
+        let (action_channel, _) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<Observer>();
+        let host = HostContext {
+            max_wasm_pages: Some(2),
+            ..Default::default()
+        };
+        let signals = host.signals.subscribe();
+        let call = |module: &wasmi::Module| {
+            call_module(&action_channel, &tx_observer, module, "test", None, &host)
+        };
+
+        assert!(call(&test_growing_module(1)).is_ok());
+        assert!(signals.try_recv().is_err());
+
+        match call(&test_growing_module(2)) {
+            Err(InterpreterError::Memory(message)) => {
+                assert_eq!("wasm_pages limit of 2 exceeded, 3 requested", message)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        let signal = signals.try_recv().unwrap();
+        assert_eq!(limits::LIMIT_EXCEEDED_SIGNAL, signal.name);
+        assert_eq!(json!("wasm_pages"), signal.payload["resource"]);
+
+        let module_code = ::nucleus::module_cache::tests::test_module_code_b();
+        let module = wasmi::Module::from_buffer(module_code).unwrap();
+        let host = HostContext {
+            max_wasm_pages: Some(1),
+            ..Default::default()
+        };
+        assert!(call_module(&action_channel, &tx_observer, &module, "test", None, &host).is_err());
+    }
+
+    #[test]
+    fn test_head_moved() {
+        assert!(is_head_moved(&InterpreterError::Host(Box::new(HeadMoved))));
+        let failed = CommitFailed("over the limit".to_string());
+        assert!(!is_head_moved(&InterpreterError::Host(Box::new(failed))));
+        let trap = Trap::new(TrapKind::Host(Box::new(HeadMoved)));
+        assert!(!is_head_moved(&InterpreterError::Trap(trap)));
+        assert_eq!(::agent::HEAD_MOVED, HeadMoved.to_string());
     }
 }
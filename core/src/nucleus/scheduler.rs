@@ -0,0 +1,284 @@
+//! recurring zome function calls, e.g. sending a daily digest, and one-off ones after a delay,
+//! e.g. expiring an invitation
+//! schedules live in the nucleus state so they are persisted along with it and survive restarts
+//! the Scheduler only decides when a schedule is due, the nucleus reducer makes the call so a tick
+//! is never called twice however many times it is fired
+
+use nucleus::{Action, FunctionCall};
+use platform;
+use rand::{self, Rng};
+use state::{self, State};
+use std::{
+    sync::{mpsc::Sender, Arc, Condvar, Mutex, RwLock},
+    thread::JoinHandle, time::Duration,
+};
+
+/// how often the scheduler checks for due schedules by default
+pub const SCHEDULER_DEFAULT_RESOLUTION_MS: u64 = 1000;
+
+/// seconds since the unix epoch, the clock headers are timed by
+pub use hash_table::header::unix_now;
+
+/// a zome function called every interval_secs
+/// tick n is due n intervals after start, plus offset_secs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    /// name of the schedule, unique within its zome
+    pub id: String,
+    pub zome: String,
+    pub capability: String,
+    pub function: String,
+    pub parameters: String,
+    pub interval_secs: u64,
+    /// unix time ticks are counted from
+    pub start: u64,
+    /// random delay within the jitter the schedule was set with, so instances that set the same
+    /// schedule at the same time don't all call at once
+    pub offset_secs: u64,
+    /// last tick the function was called for
+    pub last_tick: Option<u64>,
+    /// whether the schedule is removed once its first tick is called
+    #[serde(default)]
+    pub once: bool,
+}
+
+impl Schedule {
+    /// a schedule starting now, delayed by a random offset of up to jitter_secs
+    pub fn new(
+        zome: &str,
+        id: &str,
+        capability: &str,
+        function: &str,
+        parameters: &str,
+        interval_secs: u64,
+        jitter_secs: u64,
+    ) -> Schedule {
+        Schedule {
+            id: id.to_string(),
+            zome: zome.to_string(),
+            capability: capability.to_string(),
+            function: function.to_string(),
+            parameters: parameters.to_string(),
+            interval_secs,
+            start: unix_now(),
+            offset_secs: rand::thread_rng().gen_range(0, jitter_secs + 1),
+            last_tick: None,
+            once: false,
+        }
+    }
+
+    /// a schedule calling the function once, delay_secs from now but a second at the earliest
+    pub fn once(
+        zome: &str,
+        id: &str,
+        capability: &str,
+        function: &str,
+        parameters: &str,
+        delay_secs: u64,
+    ) -> Schedule {
+        Schedule {
+            once: true,
+            ..Schedule::new(zome, id, capability, function, parameters, delay_secs.max(1), 0)
+        }
+    }
+
+    /// what the schedule is stored under, see schedule_key()
+    pub fn key(&self) -> String {
+        schedule_key(&self.zome, &self.id)
+    }
+
+    /// the tick to call the function for at unix time now, None if it is not due
+    /// ticks missed e.g. while the instance was down are not caught up on, only the latest one
+    /// is due
+    pub fn due_tick(&self, now: u64) -> Option<u64> {
+        if self.interval_secs == 0 || now < self.start + self.offset_secs {
+            return None;
+        }
+        let tick = (now - self.start - self.offset_secs) / self.interval_secs;
+        if tick == 0 || self.last_tick >= Some(tick) {
+            None
+        } else {
+            Some(tick)
+        }
+    }
+
+    /// the zome function call to make for a tick
+    pub fn call(&self) -> FunctionCall {
+        FunctionCall::new(
+            self.zome.clone(),
+            self.capability.clone(),
+            self.function.clone(),
+            self.parameters.clone(),
+        )
+    }
+}
+
+/// schedules are stored by zome and id
+pub fn schedule_key(zome: &str, id: &str) -> String {
+    format!("{}/{}", zome, id)
+}
+
+/// configuration for a Scheduler
+#[derive(Clone, Debug, PartialEq)]
+pub struct SchedulerConfig {
+    /// how often to check for due schedules, calls are late by up to this much
+    pub resolution: Duration,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            resolution: Duration::from_millis(SCHEDULER_DEFAULT_RESOLUTION_MS),
+        }
+    }
+}
+
+/// thread firing the schedules in the nucleus state as they fall due
+/// the thread stops when the Scheduler is dropped
+pub struct Scheduler {
+    /// set to true and signalled to stop the thread
+    stop: Arc<(Mutex<bool>, Condvar)>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub fn start(
+        config: &SchedulerConfig,
+        state: Arc<RwLock<State>>,
+        action_channel: Sender<state::ActionWrapper>,
+    ) -> Scheduler {
+        let stop = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stop = Arc::clone(&stop);
+        let resolution = config.resolution;
+
+        let handle = platform::spawn("scheduler", move || {
+            let (ref stopped, ref signal) = *thread_stop;
+            let mut stopped = stopped.lock().unwrap();
+            while !*stopped {
+                let now = unix_now();
+                let due = state
+                    .read()
+                    .unwrap()
+                    .nucleus()
+                    .schedules()
+                    .values()
+                    .filter_map(|schedule| {
+                        schedule.due_tick(now).map(|tick| (schedule.key(), tick))
+                    })
+                    .collect::<Vec<(String, u64)>>();
+                for (key, tick) in due {
+                    let action = state::Action::Nucleus(Action::FireSchedule(key, tick));
+                    if action_channel.send(state::ActionWrapper::new(action)).is_err() {
+                        return;
+                    }
+                }
+                stopped = signal.wait_timeout(stopped, resolution).unwrap().0;
+            }
+        });
+
+        Scheduler {
+            stop,
+            handle: Some(handle),
+        }
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        {
+            let (ref stopped, ref signal) = *self.stop;
+            *stopped.lock().unwrap() = true;
+            signal.notify_all();
+        }
+        if let Some(handle) = self.handle.take() {
+            handle.join().expect("scheduler should not panic");
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{schedule_key, unix_now, Schedule, Scheduler, SchedulerConfig};
+    use nucleus::Action;
+    use state::{self, State};
+    use std::{
+        sync::{mpsc::channel, Arc, RwLock}, time::Duration,
+    };
+
+    /// schedule calling main() every 10 seconds, started at unix time 1000 without jitter
+    pub fn test_schedule() -> Schedule {
+        let mut schedule = Schedule::new("test_zome", "every_10", "test_cap", "main", "{}", 10, 0);
+        schedule.start = 1000;
+        schedule
+    }
+
+    #[test]
+    /// ticks fall due every interval after the start and offset
+    fn due_tick() {
+        let mut schedule = test_schedule();
+        assert_eq!(None, schedule.due_tick(999));
+        assert_eq!(None, schedule.due_tick(1009));
+        assert_eq!(Some(1), schedule.due_tick(1010));
+        assert_eq!(Some(1), schedule.due_tick(1019));
+
+        schedule.last_tick = Some(1);
+        assert_eq!(None, schedule.due_tick(1019));
+        // missed ticks are skipped
+        assert_eq!(Some(5), schedule.due_tick(1055));
+
+        schedule.offset_secs = 5;
+        assert_eq!(None, schedule.due_tick(1024));
+        assert_eq!(Some(2), schedule.due_tick(1025));
+
+        schedule.interval_secs = 0;
+        assert_eq!(None, schedule.due_tick(2000));
+    }
+
+    #[test]
+    /// a schedule once is due a single tick after its delay
+    fn once() {
+        let mut schedule = Schedule::once("test_zome", "expire", "test_cap", "main", "{}", 30);
+        schedule.start = 1000;
+        assert!(schedule.once);
+        assert_eq!(None, schedule.due_tick(1029));
+        assert_eq!(Some(1), schedule.due_tick(1030));
+        assert_eq!(1, Schedule::once("zome", "id", "cap", "fn", "", 0).interval_secs);
+    }
+
+    #[test]
+    /// the offset stays within the jitter
+    fn jitter() {
+        for _ in 0..100 {
+            let schedule = Schedule::new("zome", "id", "cap", "fn", "", 60, 5);
+            assert!(schedule.offset_secs <= 5);
+        }
+        assert_eq!(0, Schedule::new("zome", "id", "cap", "fn", "", 60, 0).offset_secs);
+        assert_eq!(schedule_key("test_zome", "every_10"), test_schedule().key());
+    }
+
+    #[test]
+    /// the scheduler fires due schedules
+    fn scheduler_fires() {
+        let (sender, receiver) = channel::<state::ActionWrapper>();
+        let (tx_observer, _observer) = channel();
+        let mut schedule = test_schedule();
+        schedule.start = unix_now() - 15;
+        let state = State::new().reduce(
+            state::ActionWrapper::new(state::Action::Nucleus(Action::Schedule(schedule.clone()))),
+            &sender,
+            &tx_observer,
+        );
+
+        let config = SchedulerConfig {
+            resolution: Duration::from_millis(10),
+        };
+        let scheduler = Scheduler::start(&config, Arc::new(RwLock::new(state)), sender);
+        let fired = receiver.recv_timeout(Duration::from_millis(1000)).unwrap();
+        drop(scheduler);
+
+        assert_eq!(
+            state::Action::Nucleus(Action::FireSchedule(schedule.key(), 1)),
+            fired.action
+        );
+    }
+}
@@ -0,0 +1,120 @@
+//! scratch space is a key value store local to an instance for zomes to keep what doesn't belong
+//! on their chain, e.g. caches, drafts or session state
+//! nothing in it is hashed, signed or published and it doesn't outlive the instance
+//! each zome sees only its own keys
+
+use serde_json::Value;
+use std::{
+    collections::HashMap, fmt, sync::{Arc, Mutex},
+};
+
+/// the values each zome stored, by zome name then key
+/// the store is a cheap handle, clones share the same values
+#[derive(Clone, Default)]
+pub struct ScratchSpace {
+    zomes: Arc<Mutex<HashMap<String, HashMap<String, Value>>>>,
+}
+
+impl PartialEq for ScratchSpace {
+    fn eq(&self, other: &ScratchSpace) -> bool {
+        Arc::ptr_eq(&self.zomes, &other.zomes)
+    }
+}
+
+impl fmt::Debug for ScratchSpace {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScratchSpace")
+            .field("zomes", &self.zomes.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl ScratchSpace {
+    pub fn get(&self, zome: &str, key: &str) -> Option<Value> {
+        self.zomes
+            .lock()
+            .unwrap()
+            .get(zome)
+            .and_then(|values| values.get(key))
+            .cloned()
+    }
+
+    /// store the value under the key, returning the value it replaces if any
+    /// setting null removes the key
+    pub fn set(&self, zome: &str, key: &str, value: Value) -> Option<Value> {
+        let mut zomes = self.zomes.lock().unwrap();
+        if value.is_null() {
+            let values = zomes.get_mut(zome)?;
+            let previous = values.remove(key);
+            if values.is_empty() {
+                zomes.remove(zome);
+            }
+            previous
+        } else {
+            zomes
+                .entry(zome.to_string())
+                .or_default()
+                .insert(key.to_string(), value)
+        }
+    }
+
+    /// keys the zome stored values under, sorted
+    pub fn keys(&self, zome: &str) -> Vec<String> {
+        let mut keys = self
+            .zomes
+            .lock()
+            .unwrap()
+            .get(zome)
+            .map(|values| values.keys().cloned().collect::<Vec<String>>())
+            .unwrap_or_default();
+        keys.sort();
+        keys
+    }
+
+    /// drop every value of the zome
+    pub fn clear(&self, zome: &str) {
+        self.zomes.lock().unwrap().remove(zome);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::ScratchSpace;
+
+    #[test]
+    /// values can be set, replaced and removed
+    fn set_and_get() {
+        let scratch = ScratchSpace::default();
+        assert_eq!(None, scratch.get("posts", "draft"));
+
+        assert_eq!(None, scratch.set("posts", "draft", json!({"title": "hello"})));
+        assert_eq!(Some(json!({"title": "hello"})), scratch.get("posts", "draft"));
+        assert_eq!(
+            Some(json!({"title": "hello"})),
+            scratch.set("posts", "draft", json!("bye"))
+        );
+        assert_eq!(Some(json!("bye")), scratch.clone().get("posts", "draft"));
+
+        assert_eq!(Some(json!("bye")), scratch.set("posts", "draft", json!(null)));
+        assert_eq!(None, scratch.get("posts", "draft"));
+        assert_eq!(None, scratch.set("posts", "draft", json!(null)));
+    }
+
+    #[test]
+    /// zomes don't see each other's values
+    fn zomes() {
+        let scratch = ScratchSpace::default();
+        scratch.set("posts", "b", json!(2));
+        scratch.set("posts", "a", json!(1));
+        scratch.set("profile", "a", json!("other"));
+
+        assert_eq!(vec!["a".to_string(), "b".to_string()], scratch.keys("posts"));
+        assert_eq!(Some(json!(1)), scratch.get("posts", "a"));
+        assert_eq!(Some(json!("other")), scratch.get("profile", "a"));
+        assert_eq!(None, scratch.get("profile", "b"));
+
+        scratch.clear("posts");
+        assert!(scratch.keys("posts").is_empty());
+        assert_eq!(vec!["a".to_string()], scratch.keys("profile"));
+    }
+}
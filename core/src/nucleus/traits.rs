@@ -0,0 +1,102 @@
+use error::HolochainError;
+use holochain_dna::Dna;
+use parity_wasm::{
+    self, elements::{Internal, Module},
+};
+
+/// names of the functions a WASM module exports
+pub fn wasm_exports(code: &[u8]) -> Result<Vec<String>, HolochainError> {
+    let module: Module = parity_wasm::deserialize_buffer(code)
+        .map_err(|e| HolochainError::ErrorGeneric(format!("{}", e)))?;
+    Ok(module
+        .export_section()
+        .map(|section| {
+            section
+                .entries()
+                .iter()
+                .filter(|export| matches!(*export.internal(), Internal::Function(_)))
+                .map(|export| export.field().to_string())
+                .collect()
+        })
+        .unwrap_or_default())
+}
+
+/// checks every function of every trait a zome declares is exported by one of the zome's
+/// capabilities, so callers discovering the zome by trait can rely on it
+pub fn verify_traits(dna: &Dna) -> Result<(), HolochainError> {
+    for zome in &dna.zomes {
+        if zome.traits.is_empty() {
+            continue;
+        }
+        let mut exports = Vec::new();
+        for capability in &zome.capabilities {
+            exports.extend(wasm_exports(&capability.code.code)?);
+        }
+        for zome_trait in &zome.traits {
+            for function in &zome_trait.functions {
+                // zome functions are called through their dispatch export, see ribosome::call
+                let dispatch = format!("{}_dispatch", function.name);
+                if !exports.contains(&dispatch) {
+                    return Err(HolochainError::ErrorGeneric(format!(
+                        "zome '{}' implements trait '{}' but does not export function '{}'",
+                        zome.name, zome_trait.name, function.name
+                    )));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{verify_traits, wasm_exports};
+    use holochain_dna::{
+        zome::{
+            capabilities::{Capability, FnDeclaration}, traits::ZomeTrait, Zome,
+        },
+        Dna,
+    };
+    use nucleus::module_cache::tests::test_module_code;
+
+    /// dna with a zome implementing a trait of the given functions, with test_module_code()
+    pub fn test_trait_dna(functions: &[&str]) -> Dna {
+        let mut zome_trait = ZomeTrait::new();
+        zome_trait.name = "test_trait".to_string();
+        for name in functions {
+            let mut function = FnDeclaration::new();
+            function.name = name.to_string();
+            zome_trait.functions.push(function);
+        }
+
+        let mut capability = Capability::new();
+        capability.code.code = test_module_code();
+
+        let mut zome = Zome::new();
+        zome.name = "test_zome".to_string();
+        zome.capabilities.push(capability);
+        zome.traits.push(zome_trait);
+
+        let mut dna = Dna::new();
+        dna.zomes.push(zome);
+        dna
+    }
+
+    #[test]
+    /// exported function names are read from the module
+    fn exports() {
+        assert_eq!(
+            vec!["test_dispatch".to_string()],
+            wasm_exports(&test_module_code()).unwrap()
+        );
+        assert!(wasm_exports(&[0x00, 0x01]).is_err());
+    }
+
+    #[test]
+    /// traits must be backed by exported functions
+    fn verify() {
+        assert!(verify_traits(&Dna::new()).is_ok());
+        assert!(verify_traits(&test_trait_dna(&["test"])).is_ok());
+        assert!(verify_traits(&test_trait_dna(&["test", "missing"])).is_err());
+    }
+}
@@ -1,11 +1,13 @@
 extern {
-    fn print(i:i32);
+    fn log(offset: i32, length: i32) -> i32;
 }
 
+static MESSAGE: &'static str = r#"{"level":"info","target":"test","payload":{"answer":42}}"#;
+
 #[no_mangle]
-pub extern "C" fn test_print() -> i32 {
+pub extern "C" fn test_log() -> i32 {
     unsafe {
-        print(1337);
+        log(MESSAGE.as_ptr() as i32, MESSAGE.len() as i32);
     }
 
     return 0;
@@ -0,0 +1,107 @@
+//! persister keeping the state of an instance in an LMDB environment, the stores of the
+//! instance are kept in files next to it like with the FilePersister
+
+use super::{FilePersister, Persister};
+use error::HolochainError;
+use instance::Instance;
+use lmdb::{self, Environment, Transaction, WriteFlags};
+use serde_json;
+use state::State;
+use std::{fs, path::Path};
+
+/// the directory of the LMDB environment in the directory of the persister
+pub const LMDB_PERSISTER_ENV: &str = "state.lmdb";
+/// the key the state is kept under
+const LMDB_STATE_KEY: &str = "state";
+/// how big the environment can grow, the file only takes the space used
+const LMDB_MAP_SIZE: usize = 1 << 30;
+
+fn to_error(e: lmdb::Error) -> HolochainError {
+    HolochainError::new(&e.to_string())
+}
+
+/// persister keeping the state saved in an LMDB environment in a directory, see FilePersister
+/// for the other stores, the state is also kept in memory for restart() if writing it failed
+pub struct LmdbPersister {
+    files: FilePersister,
+    env: Environment,
+}
+
+impl Persister for LmdbPersister {
+    fn save(&mut self, state: &State) {
+        self.files.state = Some(state.clone());
+        // a state that couldn't be written is still kept in memory, the next save tries again
+        let _ = self.write_state(state);
+    }
+    fn load(&self) -> Result<Option<State>, HolochainError> {
+        if self.files.state.is_some() {
+            return Ok(self.files.state.clone());
+        }
+        let db = self.env.open_db(None).map_err(to_error)?;
+        let txn = self.env.begin_ro_txn().map_err(to_error)?;
+        match txn.get(db, &LMDB_STATE_KEY) {
+            Ok(bytes) => serde_json::from_slice(bytes)
+                .map(Some)
+                .map_err(|e| HolochainError::new(&e.to_string())),
+            Err(lmdb::Error::NotFound) => Ok(None),
+            Err(e) => Err(to_error(e)),
+        }
+    }
+    fn attach(&mut self, instance: &mut Instance) -> Result<(), HolochainError> {
+        self.files.attach(instance)
+    }
+    fn skip_keystore(&mut self) {
+        self.files.skip_keystore();
+    }
+}
+
+impl LmdbPersister {
+    /// fails if the environment can't be opened, e.g. another process has it
+    pub fn new(dir: &Path) -> Result<Self, HolochainError> {
+        let path = dir.join(LMDB_PERSISTER_ENV);
+        fs::create_dir_all(&path).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let env = Environment::new()
+            .set_map_size(LMDB_MAP_SIZE)
+            .open(&path)
+            .map_err(to_error)?;
+        Ok(LmdbPersister {
+            files: FilePersister::new(dir),
+            env,
+        })
+    }
+
+    /// the directory the LMDB environment and the stores are kept in
+    pub fn dir(&self) -> &Path {
+        self.files.dir()
+    }
+
+    fn write_state(&self, state: &State) -> Result<(), HolochainError> {
+        let json = serde_json::to_vec(state).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let db = self.env.open_db(None).map_err(to_error)?;
+        let mut txn = self.env.begin_rw_txn().map_err(to_error)?;
+        txn.put(db, &LMDB_STATE_KEY, &json, WriteFlags::empty())
+            .map_err(to_error)?;
+        txn.commit().map_err(to_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn can_roundtrip_through_lmdb() {
+        let dir = env::temp_dir().join(format!("holochain_lmdb_persister_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = LmdbPersister::new(&dir).unwrap();
+        assert_eq!(None, store.load().unwrap());
+
+        let state = State::new();
+        store.save(&state);
+        drop(store);
+        let loaded = LmdbPersister::new(&dir).unwrap().load().unwrap().unwrap();
+        assert_eq!(state.agent(), loaded.agent());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+#[cfg(feature = "lmdb")]
+pub mod lmdb;
+#[cfg(feature = "pickle")]
+pub mod pickle;
+
+use error::HolochainError;
+use instance::Instance;
+use serde_json;
+use state::State;
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+/// files the FilePersister keeps the stores of an instance in, inside its directory
+pub const FILE_PERSISTER_HOLDINGS: &str = "holdings.json";
+pub const FILE_PERSISTER_OUTBOX: &str = "outbox.json";
+pub const FILE_PERSISTER_RECEIPTS: &str = "receipts.json";
+pub const FILE_PERSISTER_GROUP_SECRETS: &str = "group_secrets.json";
+pub const FILE_PERSISTER_KEYSTORE: &str = "keystore.json";
+pub const FILE_PERSISTER_STATE: &str = "state.json";
+
+/// trait that defines the persistence functionality that holochain_core requires
+pub trait Persister {
+    fn save(&mut self, state: &State);
+    fn load(&self) -> Result<Option<State>, HolochainError>;
+
+    /// keep the stores of the instance that outlive the process wherever the persister keeps
+    /// them, picking up what was kept there, called as the instance starts
+    /// the default keeps nothing
+    fn attach(&mut self, _instance: &mut Instance) -> Result<(), HolochainError> {
+        Ok(())
+    }
+
+    /// leave the keystore out of what attach() keeps, for instances keeping it encrypted in a
+    /// file of their own, see Instance::persist_encrypted_keystore()
+    fn skip_keystore(&mut self) {}
+}
+
+#[derive(Default, Clone, PartialEq)]
+pub struct SimplePersister {
+    state: Option<State>,
+}
+
+impl Persister for SimplePersister {
+    fn save(&mut self, state: &State) {
+        self.state = Some(state.clone());
+    }
+    fn load(&self) -> Result<Option<State>, HolochainError> {
+        Ok(self.state.clone())
+    }
+}
+
+impl SimplePersister {
+    pub fn new() -> Self {
+        SimplePersister { state: None }
+    }
+}
+
+/// persister keeping the stores of an instance in files in a directory, so the state saved,
+/// DHT holdings, what waits in the outbox, validation receipts, group secrets and the keystore
+/// seed are still there after the process restarts, the directory is created if needed
+/// the state saved is also kept in memory, restart() still finds it if writing the file failed
+#[derive(Clone, PartialEq)]
+pub struct FilePersister {
+    dir: PathBuf,
+    state: Option<State>,
+    skip_keystore: bool,
+}
+
+impl Persister for FilePersister {
+    fn save(&mut self, state: &State) {
+        self.state = Some(state.clone());
+        // a state that couldn't be written is still kept in memory, the next save tries again
+        let _ = self.write_state(state);
+    }
+    fn load(&self) -> Result<Option<State>, HolochainError> {
+        if self.state.is_some() {
+            return Ok(self.state.clone());
+        }
+        match fs::read_to_string(self.dir.join(FILE_PERSISTER_STATE)) {
+            Ok(json) => serde_json::from_str(&json)
+                .map(Some)
+                .map_err(|e| HolochainError::new(&e.to_string())),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(HolochainError::new(&e.to_string())),
+        }
+    }
+    fn attach(&mut self, instance: &mut Instance) -> Result<(), HolochainError> {
+        fs::create_dir_all(&self.dir).map_err(|e| HolochainError::new(&e.to_string()))?;
+        if !self.skip_keystore {
+            instance.persist_keystore(&self.dir.join(FILE_PERSISTER_KEYSTORE))?;
+        }
+        instance.persist_holdings(&self.dir.join(FILE_PERSISTER_HOLDINGS))?;
+        instance.persist_outbox(&self.dir.join(FILE_PERSISTER_OUTBOX))?;
+        instance.persist_receipts(&self.dir.join(FILE_PERSISTER_RECEIPTS))?;
+        instance.persist_group_secrets(&self.dir.join(FILE_PERSISTER_GROUP_SECRETS))?;
+        Ok(())
+    }
+    fn skip_keystore(&mut self) {
+        self.skip_keystore = true;
+    }
+}
+
+impl FilePersister {
+    pub fn new(dir: &Path) -> Self {
+        FilePersister {
+            dir: dir.to_path_buf(),
+            state: None,
+            skip_keystore: false,
+        }
+    }
+
+    /// the directory the stores are kept in
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// replace the state file through a file next to it, so it is never left half written
+    fn write_state(&self, state: &State) -> Result<(), HolochainError> {
+        let to_error = |e: ::std::io::Error| HolochainError::new(&e.to_string());
+        let json = serde_json::to_string(state).map_err(|e| HolochainError::new(&e.to_string()))?;
+        fs::create_dir_all(&self.dir).map_err(to_error)?;
+        let path = self.dir.join(FILE_PERSISTER_STATE);
+        let temp = self.dir.join(format!("{}.tmp", FILE_PERSISTER_STATE));
+        let written = fs::File::create(&temp).and_then(|mut file| {
+            file.write_all(json.as_bytes())?;
+            file.sync_all()
+        });
+        written.and_then(|_| fs::rename(&temp, &path)).map_err(|e| {
+            let _ = fs::remove_file(&temp);
+            to_error(e)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash_table::entry::tests::test_entry;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    fn can_instantiate() {
+        let store = SimplePersister::new();
+        match store.load() {
+            Err(_) => assert!(false),
+            Ok(state) => match state {
+                None => assert!(true),
+                _ => assert!(false),
+            },
+        }
+    }
+
+    #[test]
+    fn can_roundtrip() {
+        let mut store = SimplePersister::new();
+
+        let state = State::new();
+
+        let action = ::state::Action::Agent(::agent::Action::Commit(test_entry()));
+        let (sender, _receiver) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<::instance::Observer>();
+        let new_state = state.reduce(::state::ActionWrapper::new(action), &sender, &tx_observer);
+
+        store.save(&new_state);
+
+        assert_eq!(store.load().unwrap().unwrap(), new_state);
+    }
+
+    #[test]
+    fn can_roundtrip_through_file() {
+        let dir = ::std::env::temp_dir()
+            .join(format!("holochain_file_persister_{}", ::std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = FilePersister::new(&dir);
+        assert_eq!(None, store.load().unwrap());
+
+        let state = State::new();
+        let action = ::state::Action::Agent(::agent::Action::Commit(test_entry()));
+        let (sender, _receiver) = channel::<::state::ActionWrapper>();
+        let (tx_observer, _observer) = channel::<::instance::Observer>();
+        let new_state = state.reduce(::state::ActionWrapper::new(action), &sender, &tx_observer);
+        store.save(&new_state);
+        assert!(dir.join(FILE_PERSISTER_STATE).exists());
+
+        // another persister on the directory, e.g. after the process restarted, reads the file
+        let loaded = FilePersister::new(&dir).load().unwrap().unwrap();
+        assert_eq!(new_state.agent(), loaded.agent());
+        assert_eq!(new_state.nucleus().dna(), loaded.nucleus().dna());
+        assert!(loaded.history.is_empty());
+
+        fs::write(dir.join(FILE_PERSISTER_STATE), "not json").unwrap();
+        assert!(FilePersister::new(&dir).load().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
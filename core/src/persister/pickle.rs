@@ -0,0 +1,104 @@
+//! persister keeping the state of an instance in a PickleDB file, a key-value store dumped to
+//! a single file, the stores of the instance are kept in files next to it like with the
+//! FilePersister
+
+use super::{FilePersister, Persister};
+use error::HolochainError;
+use instance::Instance;
+use pickledb::{PickleDb, PickleDbDumpPolicy, SerializationMethod};
+use state::State;
+use std::{fs, path::Path};
+
+/// the PickleDB file in the directory of the persister
+pub const PICKLE_PERSISTER_DB: &str = "state.db";
+/// the key the state is kept under
+const PICKLE_STATE_KEY: &str = "state";
+
+/// persister keeping the state saved in a PickleDB file in a directory, see FilePersister for
+/// the other stores, the state is also kept in memory for restart() if writing it failed
+#[derive(Clone, PartialEq)]
+pub struct PicklePersister {
+    files: FilePersister,
+}
+
+impl Persister for PicklePersister {
+    fn save(&mut self, state: &State) {
+        self.files.state = Some(state.clone());
+        // a state that couldn't be written is still kept in memory, the next save tries again
+        let _ = self.write_state(state);
+    }
+    fn load(&self) -> Result<Option<State>, HolochainError> {
+        if self.files.state.is_some() {
+            return Ok(self.files.state.clone());
+        }
+        let path = self.files.dir.join(PICKLE_PERSISTER_DB);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let db = PickleDb::load_read_only(&path, SerializationMethod::Json)
+            .map_err(|e| HolochainError::new(&e.to_string()))?;
+        if !db.exists(PICKLE_STATE_KEY) {
+            return Ok(None);
+        }
+        db.get(PICKLE_STATE_KEY)
+            .map(Some)
+            .ok_or_else(|| HolochainError::new("the state kept in the PickleDB file is invalid"))
+    }
+    fn attach(&mut self, instance: &mut Instance) -> Result<(), HolochainError> {
+        self.files.attach(instance)
+    }
+    fn skip_keystore(&mut self) {
+        self.files.skip_keystore();
+    }
+}
+
+impl PicklePersister {
+    pub fn new(dir: &Path) -> Self {
+        PicklePersister {
+            files: FilePersister::new(dir),
+        }
+    }
+
+    /// the directory the PickleDB file and the stores are kept in
+    pub fn dir(&self) -> &Path {
+        self.files.dir()
+    }
+
+    fn write_state(&self, state: &State) -> Result<(), HolochainError> {
+        let to_error = |e: ::pickledb::error::Error| HolochainError::new(&e.to_string());
+        fs::create_dir_all(self.dir()).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let path = self.dir().join(PICKLE_PERSISTER_DB);
+        let policy = PickleDbDumpPolicy::DumpUponRequest;
+        let mut db = if path.exists() {
+            PickleDb::load(&path, policy, SerializationMethod::Json).map_err(to_error)?
+        } else {
+            PickleDb::new(&path, policy, SerializationMethod::Json)
+        };
+        db.set(PICKLE_STATE_KEY, state).map_err(to_error)?;
+        db.dump().map_err(to_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[test]
+    fn can_roundtrip_through_pickle() {
+        let dir = env::temp_dir().join(format!("holochain_pickle_persister_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let mut store = PicklePersister::new(&dir);
+        assert_eq!(None, store.load().unwrap());
+
+        let state = State::new();
+        store.save(&state);
+        assert!(dir.join(PICKLE_PERSISTER_DB).exists());
+        let loaded = PicklePersister::new(&dir).load().unwrap().unwrap();
+        assert_eq!(state.agent(), loaded.agent());
+
+        fs::write(dir.join(PICKLE_PERSISTER_DB), "not json").unwrap();
+        assert!(PicklePersister::new(&dir).load().is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
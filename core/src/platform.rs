@@ -0,0 +1,140 @@
+//! what core needs from the platform it runs on: threads, sleeping and a place for data
+//! instances run on the native platform unless the host installs another one before creating
+//! them, e.g. a mobile host that has to set the stack size of threads, attach them to its VM or
+//! keep data in the directory the app is sandboxed to
+
+use std::{
+    io, path::PathBuf, sync::{Arc, RwLock}, thread::{self, JoinHandle}, time::Duration,
+};
+
+/// stack size of threads on mobile platforms, where the default can be as little as 512KiB
+pub const MOBILE_THREAD_STACK_SIZE: usize = 2 * 1024 * 1024;
+
+/// the work a thread does
+pub type Task = Box<dyn FnOnce() + Send>;
+
+pub trait Platform: Send + Sync {
+    /// run the task on a thread of its own, name says what it is for
+    fn spawn(&self, name: &str, task: Task) -> io::Result<JoinHandle<()>>;
+
+    fn sleep(&self, duration: Duration) {
+        thread::sleep(duration);
+    }
+
+    /// where instances keep their data unless configured otherwise, None to leave it to the
+    /// configuration
+    fn data_dir(&self) -> Option<PathBuf> {
+        None
+    }
+}
+
+/// plain std threads
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NativePlatform;
+
+impl Platform for NativePlatform {
+    fn spawn(&self, name: &str, task: Task) -> io::Result<JoinHandle<()>> {
+        thread::Builder::new().name(name.to_string()).spawn(task)
+    }
+}
+
+/// std threads with a bigger stack and data in the directory the host app is given
+#[derive(Clone, Debug, PartialEq)]
+pub struct MobilePlatform {
+    pub data_dir: PathBuf,
+    pub stack_size: usize,
+}
+
+impl MobilePlatform {
+    pub fn new(data_dir: PathBuf) -> MobilePlatform {
+        MobilePlatform {
+            data_dir,
+            stack_size: MOBILE_THREAD_STACK_SIZE,
+        }
+    }
+}
+
+impl Platform for MobilePlatform {
+    fn spawn(&self, name: &str, task: Task) -> io::Result<JoinHandle<()>> {
+        thread::Builder::new()
+            .name(name.to_string())
+            .stack_size(self.stack_size)
+            .spawn(task)
+    }
+
+    fn data_dir(&self) -> Option<PathBuf> {
+        Some(self.data_dir.clone())
+    }
+}
+
+static INSTALLED: RwLock<Option<Arc<dyn Platform>>> = RwLock::new(None);
+
+/// run on platform from now on, meant to be called once before any instance is created
+/// threads already running stay where they are
+pub fn install(platform: Arc<dyn Platform>) {
+    *INSTALLED.write().unwrap() = Some(platform);
+}
+
+/// the platform installed, native if none was
+pub fn current() -> Arc<dyn Platform> {
+    INSTALLED
+        .read()
+        .unwrap()
+        .clone()
+        .unwrap_or_else(|| Arc::new(NativePlatform))
+}
+
+/// run f on a thread of the current platform, panics like thread::spawn() if there is none to
+/// be had
+pub fn spawn<F>(name: &str, f: F) -> JoinHandle<()>
+where
+    F: FnOnce() + Send + 'static,
+{
+    current()
+        .spawn(name, Box::new(f))
+        .unwrap_or_else(|e| panic!("failed to spawn {} thread: {}", name, e))
+}
+
+pub fn sleep(duration: Duration) {
+    current().sleep(duration);
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+
+    #[test]
+    /// threads are named after what they are for
+    fn spawn_named() {
+        let (sender, receiver) = channel();
+        let handle = NativePlatform
+            .spawn(
+                "test",
+                Box::new(move || {
+                    sender
+                        .send(thread::current().name().map(str::to_string))
+                        .unwrap()
+                }),
+            )
+            .unwrap();
+        handle.join().unwrap();
+        assert_eq!(Some("test".to_string()), receiver.recv().unwrap());
+        assert_eq!(None, NativePlatform.data_dir());
+    }
+
+    #[test]
+    /// mobile threads get the bigger stack and data goes where the host says
+    fn mobile() {
+        let platform = MobilePlatform::new(PathBuf::from("/data/app"));
+        assert_eq!(MOBILE_THREAD_STACK_SIZE, platform.stack_size);
+        assert_eq!(Some(PathBuf::from("/data/app")), platform.data_dir());
+        let (sender, receiver) = channel();
+        platform
+            .spawn("test", Box::new(move || sender.send(()).unwrap()))
+            .unwrap()
+            .join()
+            .unwrap();
+        assert_eq!(Ok(()), receiver.recv());
+    }
+}
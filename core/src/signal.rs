@@ -0,0 +1,98 @@
+//! signals are app events zomes push to whoever is listening, e.g. UIs connected to the container,
+//! so they can update in real time instead of polling
+
+use serde_json::Value;
+use std::{
+    fmt, sync::{
+        mpsc::{channel, Receiver, Sender}, Arc, Mutex,
+    },
+};
+
+/// an event emitted by a zome through the emit_signal host function
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Signal {
+    /// zome the signal was emitted by
+    pub zome: String,
+    /// what happened, e.g. "new_post"
+    pub name: String,
+    #[serde(default)]
+    pub payload: Value,
+}
+
+/// fans signals out to every subscriber
+/// the bus is a cheap handle, clones share the same subscribers
+/// signals emitted while nobody is subscribed are dropped
+#[derive(Clone, Default)]
+pub struct SignalBus {
+    subscribers: Arc<Mutex<Vec<Sender<Signal>>>>,
+}
+
+impl PartialEq for SignalBus {
+    fn eq(&self, other: &SignalBus) -> bool {
+        Arc::ptr_eq(&self.subscribers, &other.subscribers)
+    }
+}
+
+impl fmt::Debug for SignalBus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("SignalBus")
+            .field("subscribers", &self.subscribers.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl SignalBus {
+    /// receive every signal emitted from now on
+    /// dropping the receiver unsubscribes
+    pub fn subscribe(&self) -> Receiver<Signal> {
+        let (sender, receiver) = channel();
+        self.subscribers.lock().unwrap().push(sender);
+        receiver
+    }
+
+    /// send a signal to every subscriber, returns how many it reached
+    pub fn emit(&self, signal: &Signal) -> usize {
+        let mut subscribers = self.subscribers.lock().unwrap();
+        subscribers.retain(|subscriber| subscriber.send(signal.clone()).is_ok());
+        subscribers.len()
+    }
+
+    /// drop every subscriber, their receivers end once they took the signals already sent
+    pub fn close(&self) {
+        self.subscribers.lock().unwrap().clear();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{Signal, SignalBus};
+
+    pub fn test_signal() -> Signal {
+        Signal {
+            zome: "test_zome".to_string(),
+            name: "new_post".to_string(),
+            payload: json!({"title": "hello"}),
+        }
+    }
+
+    #[test]
+    /// signals reach every subscriber until they unsubscribe
+    fn emit() {
+        let bus = SignalBus::default();
+        assert_eq!(0, bus.emit(&test_signal()));
+
+        let first = bus.subscribe();
+        let second = bus.clone().subscribe();
+        assert_eq!(2, bus.emit(&test_signal()));
+        assert_eq!(test_signal(), first.recv().unwrap());
+        assert_eq!(test_signal(), second.recv().unwrap());
+
+        drop(first);
+        assert_eq!(1, bus.emit(&test_signal()));
+
+        bus.close();
+        assert_eq!(test_signal(), second.recv().unwrap());
+        assert!(second.recv().is_err());
+        assert_eq!(0, bus.emit(&test_signal()));
+    }
+}
@@ -1,16 +1,19 @@
 use agent::AgentState;
+use dht::DhtState;
 use instance::Observer;
 use nucleus::NucleusState;
 use snowflake;
 use std::{
     collections::HashSet, hash::{Hash, Hasher}, sync::{mpsc::Sender, Arc},
 };
+use trace::TraceContext;
 
 #[derive(Clone, Debug, PartialEq)]
 #[allow(unknown_lints)]
 #[allow(large_enum_variant)]
 pub enum Action {
     Agent(::agent::Action),
+    Dht(::dht::Action),
     Network(::network::Action),
     Nucleus(::nucleus::Action),
 }
@@ -19,6 +22,8 @@ pub enum Action {
 pub struct ActionWrapper {
     pub action: Action,
     pub id: snowflake::ProcessUniqueId,
+    /// the trace the action was dispatched as part of, if any
+    pub trace: Option<TraceContext>,
 }
 
 impl ActionWrapper {
@@ -26,6 +31,15 @@ impl ActionWrapper {
         ActionWrapper {
             action: a,
             id: snowflake::ProcessUniqueId::new(),
+            trace: None,
+        }
+    }
+
+    /// wrap an action dispatched as part of a traced span of work
+    pub fn traced(a: Action, trace: TraceContext) -> Self {
+        ActionWrapper {
+            trace: Some(trace),
+            ..ActionWrapper::new(a)
         }
     }
 }
@@ -44,10 +58,16 @@ impl Hash for ActionWrapper {
     }
 }
 
-#[derive(Clone, PartialEq, Debug, Default)]
+/// serialized, a state holds the source chain and what the nucleus was set up with, see
+/// persister::FilePersister, the DHT holdings have a store of their own and the history only
+/// matters while the instance runs
+#[derive(Clone, PartialEq, Debug, Default, Serialize, Deserialize)]
 pub struct State {
     nucleus: Arc<NucleusState>,
     agent: Arc<AgentState>,
+    #[serde(skip)]
+    dht: Arc<DhtState>,
+    #[serde(skip)]
     pub history: HashSet<ActionWrapper>,
 }
 
@@ -56,6 +76,7 @@ impl State {
         State {
             nucleus: Arc::new(NucleusState::new()),
             agent: Arc::new(AgentState::new()),
+            dht: Arc::new(DhtState::new()),
             history: HashSet::new(),
         }
     }
@@ -78,6 +99,7 @@ impl State {
                 &action_wrapper.action,
                 action_channel,
             ),
+            dht: ::dht::reduce(Arc::clone(&self.dht), &action_wrapper.action, action_channel),
             history: self.history.clone(),
         };
 
@@ -92,4 +114,8 @@ impl State {
     pub fn agent(&self) -> Arc<AgentState> {
         Arc::clone(&self.agent)
     }
+
+    pub fn dht(&self) -> Arc<DhtState> {
+        Arc::clone(&self.dht)
+    }
 }
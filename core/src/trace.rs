@@ -0,0 +1,510 @@
+//! tracing of work that spans threads and nodes, e.g. a zome call through commit and on to
+//! validation by the nodes the entry is published to
+//! spans are spans of the tracing crate: each Tracer dispatches them to its own subscriber, a
+//! registry with a layer collecting them in memory, exportable in the JSON format the Jaeger UI
+//! loads traces from
+//! @TODO report straight to a Jaeger agent, e.g. with tracing-opentelemetry
+
+use rand::{self, Rng};
+use serde_json::Value;
+use std::{
+    collections::VecDeque, fmt, mem, sync::{Arc, Mutex},
+    time::{Instant, SystemTime, UNIX_EPOCH},
+};
+use tracing::{
+    self, dispatcher, field::{Field, Visit}, span::{Attributes, Id}, Dispatch, Event, Subscriber,
+};
+use tracing_subscriber::{
+    layer::{Context, SubscriberExt}, registry::LookupSpan, Layer, Registry,
+};
+
+/// default number of finished spans a Tracer keeps before dropping the oldest
+pub const TRACE_DEFAULT_CAPACITY: usize = 10_000;
+
+/// identifies a span within a trace
+/// this is what travels between threads and nodes so remote work joins the same trace
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub span_id: String,
+}
+
+/// a finished span
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct SpanRecord {
+    pub trace_id: String,
+    pub span_id: String,
+    pub parent_id: Option<String>,
+    pub name: String,
+    /// microseconds since the unix epoch
+    pub start: u64,
+    /// microseconds
+    pub duration: u64,
+    pub tags: Vec<(String, String)>,
+}
+
+fn new_id() -> String {
+    format!("{:016x}", rand::thread_rng().gen::<u64>())
+}
+
+fn micros_since_epoch() -> u64 {
+    let since = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    since.as_secs() * 1_000_000 + u64::from(since.subsec_micros())
+}
+
+struct Inner {
+    capacity: usize,
+    spans: VecDeque<SpanRecord>,
+}
+
+/// a span still open, kept in the extensions of its tracing span
+struct Open {
+    record: SpanRecord,
+    started: Instant,
+}
+
+/// fills in a record from the fields of a span started by a Tracer
+impl Visit for SpanRecord {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "operation" => self.name = value.to_string(),
+            "trace_id" => self.trace_id = value.to_string(),
+            "span_id" => self.span_id = value.to_string(),
+            "parent_id" => self.parent_id = Some(value.to_string()),
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value))
+    }
+}
+
+/// the key/value of a tag event, see Span::tag()
+#[derive(Default)]
+struct Tag {
+    key: Option<String>,
+    value: String,
+}
+
+impl Visit for Tag {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "tag" => self.key = Some(value.to_string()),
+            "value" => self.value = value.to_string(),
+            _ => (),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.record_str(field, &format!("{:?}", value))
+    }
+}
+
+/// the layer collecting the spans of a Tracer as they close
+struct Collector {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for Collector {
+    fn on_new_span(&self, attributes: &Attributes, id: &Id, ctx: Context<S>) {
+        let mut record = SpanRecord {
+            trace_id: String::new(),
+            span_id: String::new(),
+            parent_id: None,
+            name: String::new(),
+            start: micros_since_epoch(),
+            duration: 0,
+            tags: Vec::new(),
+        };
+        attributes.record(&mut record);
+        let span = match ctx.span(id) {
+            Some(span) => span,
+            None => return,
+        };
+        // spans of the tracing macros rather than a Tracer join the trace of their parent
+        if record.trace_id.is_empty() {
+            let parent = match span.parent() {
+                Some(parent) => parent,
+                None => return,
+            };
+            match parent.extensions().get::<Open>() {
+                Some(open) => {
+                    record.trace_id = open.record.trace_id.clone();
+                    record.parent_id = Some(open.record.span_id.clone());
+                }
+                None => return,
+            }
+            record.span_id = new_id();
+            record.name = attributes.metadata().name().to_string();
+        }
+        span.extensions_mut().insert(Open {
+            record,
+            started: Instant::now(),
+        });
+    }
+
+    fn on_event(&self, event: &Event, ctx: Context<S>) {
+        if let Some(span) = ctx.event_span(event) {
+            let mut tag = Tag::default();
+            event.record(&mut tag);
+            if let (Some(key), Some(open)) = (tag.key, span.extensions_mut().get_mut::<Open>()) {
+                open.record.tags.push((key, tag.value));
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<S>) {
+        let open = match ctx.span(&id) {
+            Some(span) => span.extensions_mut().remove::<Open>(),
+            None => None,
+        };
+        if let Some(Open { mut record, started }) = open {
+            let elapsed = started.elapsed();
+            record.duration = elapsed.as_secs() * 1_000_000 + u64::from(elapsed.subsec_micros());
+            let mut inner = self.inner.lock().unwrap();
+            if inner.capacity == 0 {
+                return;
+            }
+            while inner.spans.len() >= inner.capacity {
+                inner.spans.pop_front();
+            }
+            inner.spans.push_back(record);
+        }
+    }
+}
+
+/// collects finished spans
+/// the tracer is a cheap handle, clones share the same spans and subscriber
+/// a capacity of 0 disables tracing, spans are still handed out but not kept
+#[derive(Clone)]
+pub struct Tracer {
+    inner: Arc<Mutex<Inner>>,
+    dispatch: Dispatch,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer::new(TRACE_DEFAULT_CAPACITY)
+    }
+}
+
+impl PartialEq for Tracer {
+    fn eq(&self, other: &Tracer) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl fmt::Debug for Tracer {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let inner = self.inner.lock().unwrap();
+        f.debug_struct("Tracer")
+            .field("capacity", &inner.capacity)
+            .field("spans", &inner.spans.len())
+            .finish()
+    }
+}
+
+impl Tracer {
+    pub fn new(capacity: usize) -> Tracer {
+        let inner = Arc::new(Mutex::new(Inner {
+            capacity,
+            spans: VecDeque::new(),
+        }));
+        let collector = Collector {
+            inner: Arc::clone(&inner),
+        };
+        Tracer {
+            inner,
+            dispatch: Dispatch::new(Registry::default().with(collector)),
+        }
+    }
+
+    /// the subscriber the spans of the tracer are dispatched to
+    pub fn dispatch(&self) -> &Dispatch {
+        &self.dispatch
+    }
+
+    /// start a span at the root of a new trace
+    pub fn span(&self, name: &str) -> Span {
+        self.start(name, new_id(), None, None)
+    }
+
+    /// start a span continuing a trace started elsewhere, e.g. on another node
+    pub fn child_of(&self, name: &str, parent: &TraceContext) -> Span {
+        self.start(name, parent.trace_id.clone(), Some(&parent.span_id), None)
+    }
+
+    fn start(
+        &self,
+        name: &str,
+        trace_id: String,
+        parent_id: Option<&str>,
+        parent: Option<&tracing::Span>,
+    ) -> Span {
+        let span_id = new_id();
+        let span = dispatcher::with_default(&self.dispatch, || {
+            tracing::info_span!(
+                parent: parent.and_then(tracing::Span::id),
+                "span",
+                operation = name,
+                trace_id = trace_id.as_str(),
+                span_id = span_id.as_str(),
+                parent_id = parent_id,
+            )
+        });
+        Span {
+            tracer: self.clone(),
+            context: TraceContext { trace_id, span_id },
+            span,
+        }
+    }
+
+    /// copy of the finished spans, oldest first
+    pub fn spans(&self) -> Vec<SpanRecord> {
+        self.inner.lock().unwrap().spans.iter().cloned().collect()
+    }
+    /// finished spans of one trace, oldest first
+    pub fn trace(&self, trace_id: &str) -> Vec<SpanRecord> {
+        self.spans()
+            .into_iter()
+            .filter(|span| span.trace_id == trace_id)
+            .collect()
+    }
+
+    /// remove and return the finished spans, e.g. after exporting them
+    pub fn drain(&self) -> Vec<SpanRecord> {
+        self.inner.lock().unwrap().spans.drain(..).collect()
+    }
+
+    /// finished spans in the JSON format the Jaeger UI loads traces from
+    /// service is the name the spans show up under, e.g. the agent or instance id
+    pub fn to_jaeger(&self, service: &str) -> Value {
+        let mut trace_ids: Vec<String> = Vec::new();
+        let spans = self.spans();
+        for span in &spans {
+            if !trace_ids.contains(&span.trace_id) {
+                trace_ids.push(span.trace_id.clone());
+            }
+        }
+
+        let data = trace_ids
+            .iter()
+            .map(|trace_id| {
+                let trace_spans = spans
+                    .iter()
+                    .filter(|span| &span.trace_id == trace_id)
+                    .map(|span| {
+                        let references = match span.parent_id {
+                            Some(ref parent_id) => json!([{
+                                "refType": "CHILD_OF",
+                                "traceID": span.trace_id,
+                                "spanID": parent_id,
+                            }]),
+                            None => json!([]),
+                        };
+                        let tags = span
+                            .tags
+                            .iter()
+                            .map(|(key, value)| {
+                                json!({"key": key, "type": "string", "value": value})
+                            })
+                            .collect::<Vec<Value>>();
+                        json!({
+                            "traceID": span.trace_id,
+                            "spanID": span.span_id,
+                            "operationName": span.name,
+                            "references": references,
+                            "startTime": span.start,
+                            "duration": span.duration,
+                            "tags": tags,
+                            "processID": "p1",
+                        })
+                    })
+                    .collect::<Vec<Value>>();
+                json!({
+                    "traceID": trace_id,
+                    "spans": trace_spans,
+                    "processes": {"p1": {"serviceName": service, "tags": []}},
+                })
+            })
+            .collect::<Vec<Value>>();
+
+        json!({ "data": data })
+    }
+}
+
+/// a unit of traced work, a span of the tracing crate recorded by its Tracer when dropped
+pub struct Span {
+    tracer: Tracer,
+    context: TraceContext,
+    span: tracing::Span,
+}
+
+impl fmt::Debug for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Span")
+            .field("context", &self.context)
+            .field("span", &self.span)
+            .finish()
+    }
+}
+
+impl Span {
+    /// what to pass along for work done elsewhere to join this span's trace
+    pub fn context(&self) -> TraceContext {
+        self.context.clone()
+    }
+
+    pub fn tracer(&self) -> &Tracer {
+        &self.tracer
+    }
+
+    /// the span of the tracing crate, spans of the tracing macros opened in it while the
+    /// dispatch of the tracer is the default join its trace
+    pub fn span(&self) -> &tracing::Span {
+        &self.span
+    }
+
+    /// attach a key/value to the span, e.g. the address of the entry being committed
+    /// tags are events in the span, so the subscriber sees them in order
+    pub fn tag(&mut self, key: &str, value: &str) {
+        dispatcher::with_default(&self.tracer.dispatch, || {
+            tracing::info!(parent: &self.span, tag = key, value = value)
+        });
+    }
+
+    /// start a span for work done as part of this one
+    pub fn child(&self, name: &str) -> Span {
+        self.tracer.start(
+            name,
+            self.context.trace_id.clone(),
+            Some(&self.context.span_id),
+            Some(&self.span),
+        )
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        // the registry releases the parent of a closing span through the default dispatcher
+        let span = mem::replace(&mut self.span, tracing::Span::none());
+        dispatcher::with_default(&self.tracer.dispatch, || drop(span));
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{TraceContext, Tracer};
+    use tracing::{self, dispatcher};
+
+    /// dummy trace context as if received from another node
+    pub fn test_trace_context() -> TraceContext {
+        TraceContext {
+            trace_id: "00000000000000aa".to_string(),
+            span_id: "00000000000000bb".to_string(),
+        }
+    }
+
+    #[test]
+    /// spans are recorded when they finish, children join their parent's trace
+    fn spans() {
+        let tracer = Tracer::default();
+        let parent_context = {
+            let mut parent = tracer.span("zome_call");
+            parent.tag("function", "main");
+            {
+                let _child = parent.child("commit");
+            }
+            assert_eq!(1, tracer.spans().len());
+            parent.context()
+        };
+
+        let spans = tracer.spans();
+        assert_eq!(2, spans.len());
+        assert_eq!("commit", spans[0].name);
+        assert_eq!(Some(parent_context.span_id.clone()), spans[0].parent_id);
+        assert_eq!(parent_context.trace_id, spans[0].trace_id);
+        assert_eq!("zome_call", spans[1].name);
+        assert_eq!(None, spans[1].parent_id);
+        assert_eq!(
+            vec![("function".to_string(), "main".to_string())],
+            spans[1].tags
+        );
+
+        assert_eq!(2, tracer.trace(&parent_context.trace_id).len());
+        assert_eq!(2, tracer.drain().len());
+        assert!(tracer.spans().is_empty());
+    }
+
+    #[test]
+    /// remote work continues the trace it was handed
+    fn child_of() {
+        let tracer = Tracer::default();
+        tracer.child_of("validate", &test_trace_context());
+        let spans = tracer.spans();
+        assert_eq!(test_trace_context().trace_id, spans[0].trace_id);
+        assert_eq!(Some(test_trace_context().span_id), spans[0].parent_id);
+    }
+
+    #[test]
+    /// the oldest spans are dropped once over capacity, none are kept with a capacity of 0
+    fn capacity() {
+        let tracer = Tracer::new(1);
+        tracer.span("a");
+        tracer.span("b");
+        let spans = tracer.spans();
+        assert_eq!(1, spans.len());
+        assert_eq!("b", spans[0].name);
+
+        let disabled = Tracer::new(0);
+        disabled.span("a");
+        assert!(disabled.spans().is_empty());
+    }
+
+    #[test]
+    /// work instrumented with the tracing macros joins the trace of the span it runs in
+    fn instrumented() {
+        let tracer = Tracer::default();
+        let parent = tracer.span("zome_call");
+        let context = parent.context();
+        dispatcher::with_default(tracer.dispatch(), || {
+            let _entered = parent.span().enter();
+            let _ = tracing::info_span!("hash_entry").entered();
+        });
+        // outside of any Tracer span nothing is recorded
+        dispatcher::with_default(tracer.dispatch(), || tracing::info_span!("elsewhere"));
+        drop(parent);
+
+        let spans = tracer.trace(&context.trace_id);
+        assert_eq!(2, spans.len());
+        assert_eq!("hash_entry", spans[0].name);
+        assert_eq!(Some(context.span_id), spans[0].parent_id);
+        assert_eq!("zome_call", spans[1].name);
+    }
+
+    #[test]
+    /// spans export in the Jaeger JSON format
+    fn to_jaeger() {
+        let tracer = Tracer::default();
+        {
+            let parent = tracer.span("zome_call");
+            parent.child("commit");
+        }
+        let spans = tracer.spans();
+        let exported = tracer.to_jaeger("bob");
+
+        let trace = &exported["data"][0];
+        assert_eq!(json!(spans[0].trace_id), trace["traceID"]);
+        assert_eq!(json!("bob"), trace["processes"]["p1"]["serviceName"]);
+        assert_eq!(json!("commit"), trace["spans"][0]["operationName"]);
+        assert_eq!(json!("CHILD_OF"), trace["spans"][0]["references"][0]["refType"]);
+        assert_eq!(
+            json!(spans[1].span_id),
+            trace["spans"][0]["references"][0]["spanID"]
+        );
+        assert_eq!(json!([]), trace["spans"][1]["references"]);
+    }
+}
@@ -0,0 +1,228 @@
+use anchors::{validate_child_link, ANCHOR_CHILD_TAG, ANCHOR_ENTRY_TYPE};
+use hash_table::entry::Entry;
+use holochain_dna::Dna;
+use serde_json;
+use std::sync::Arc;
+use validation::{ValidationItem, Validator};
+
+/// entry type links are committed as
+pub const LINK_ENTRY_TYPE: &str = "%link";
+
+/// finds entries by key, e.g. from the source chain or what the DHT holds
+pub type EntryLookup = Arc<dyn Fn(&str) -> Option<Entry> + Send + Sync>;
+
+/// a tagged link from the entry at base to the entry at target, both addressed by entry key
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Link {
+    pub base: String,
+    pub target: String,
+    pub tag: String,
+}
+
+impl Link {
+    pub fn new(base: &str, target: &str, tag: &str) -> Link {
+        Link {
+            base: base.to_string(),
+            target: target.to_string(),
+            tag: tag.to_string(),
+        }
+    }
+
+    /// the link as an entry to commit
+    pub fn to_entry(&self) -> Entry {
+        Entry::new(
+            LINK_ENTRY_TYPE,
+            &serde_json::to_string(self).expect("Link should serialize"),
+        )
+    }
+
+    /// the link an entry holds, None if the entry is not a well formed link
+    pub fn from_entry(entry: &Entry) -> Option<Link> {
+        if entry.entry_type() != LINK_ENTRY_TYPE {
+            return None;
+        }
+        serde_json::from_str(entry.content()).ok()
+    }
+
+    /// a ValidationItem for the link, depending on its base and target
+    pub fn validation_item(&self) -> ValidationItem {
+        ValidationItem::new(
+            &self.to_entry(),
+            vec![self.base.clone(), self.target.clone()],
+        )
+    }
+}
+
+/// check a link is declared in the DNA for the types of the entries it links
+/// lookup finds entries by key, e.g. from the source chain or what the DHT holds
+pub fn validate_link<F>(dna: &Dna, link: &Link, lookup: F) -> Result<(), String>
+where
+    F: Fn(&str) -> Option<Entry>,
+{
+    let base = lookup(&link.base).ok_or_else(|| format!("link base {} not found", link.base))?;
+    let target =
+        lookup(&link.target).ok_or_else(|| format!("link target {} not found", link.target))?;
+    if base.entry_type() != ANCHOR_ENTRY_TYPE {
+        return dna.validate_link(base.entry_type(), &link.tag, target.entry_type());
+    }
+    // anchors are not declared in the DNA, entry types declare being linked from them instead
+    let linked_from_anchor = dna
+        .get_entry_type(target.entry_type())
+        .filter(|entry_type| entry_type.linked_from(ANCHOR_ENTRY_TYPE, &link.tag))
+        .is_some();
+    if link.tag == ANCHOR_CHILD_TAG {
+        validate_child_link(&base, &target)
+    } else if linked_from_anchor {
+        Ok(())
+    } else {
+        Err(format!(
+            "'{}' entries may not be linked from anchors with tag '{}'",
+            target.entry_type(),
+            link.tag
+        ))
+    }
+}
+
+/// Validator enforcing the link declarations of a DNA on link entries
+/// entries of other types are passed through as valid
+pub fn link_validator(dna: Dna, lookup: EntryLookup) -> Validator {
+    Arc::new(move |item: &ValidationItem| {
+        if item.entry.entry_type() != LINK_ENTRY_TYPE {
+            return Ok(());
+        }
+        let link = Link::from_entry(&item.entry)
+            .ok_or_else(|| format!("malformed link entry {}", item.address))?;
+        validate_link(&dna, &link, |key| lookup(key))
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{link_validator, validate_link, Link, LINK_ENTRY_TYPE};
+    use anchors::{Path, ANCHOR_CHILD_TAG};
+    use hash_table::entry::Entry;
+    use holochain_dna::Dna;
+    use std::sync::Arc;
+    use validation::ValidationItem;
+
+    /// dna where posts may link to comments with the tag "comments" and be linked from anchors
+    /// with the tag "posts"
+    pub fn test_link_dna() -> Dna {
+        Dna::new_from_json(
+            r#"{
+                "zomes": [
+                    {
+                        "name": "blog",
+                        "entry_types": [
+                            {
+                                "name": "post",
+                                "links_to": [
+                                    {"target_type": "comment", "tag": "comments"}
+                                ],
+                                "linked_from": [
+                                    {"base_type": "%anchor", "tag": "posts"}
+                                ]
+                            },
+                            {
+                                "name": "comment"
+                            }
+                        ]
+                    }
+                ]
+            }"#,
+        ).unwrap()
+    }
+
+    pub fn test_post() -> Entry {
+        Entry::new("post", "hello")
+    }
+
+    pub fn test_comment() -> Entry {
+        Entry::new("comment", "nice post")
+    }
+
+    fn test_lookup(key: &str) -> Option<Entry> {
+        vec![test_post(), test_comment()]
+            .into_iter()
+            .find(|entry| entry.key() == key)
+    }
+
+    #[test]
+    /// links round trip through entries
+    fn link_entry() {
+        let link = Link::new(&test_post().key(), &test_comment().key(), "comments");
+        let entry = link.to_entry();
+        assert_eq!(LINK_ENTRY_TYPE, entry.entry_type());
+        assert_eq!(Some(link.clone()), Link::from_entry(&entry));
+        assert_eq!(None, Link::from_entry(&test_post()));
+
+        let item = link.validation_item();
+        assert_eq!(vec![link.base, link.target], item.dependencies);
+    }
+
+    #[test]
+    /// only links declared in the dna are valid
+    fn links_are_validated() {
+        let dna = test_link_dna();
+        let comments = Link::new(&test_post().key(), &test_comment().key(), "comments");
+        assert_eq!(Ok(()), validate_link(&dna, &comments, test_lookup));
+
+        let wrong_tag = Link::new(&test_post().key(), &test_comment().key(), "likes");
+        assert!(validate_link(&dna, &wrong_tag, test_lookup).is_err());
+
+        let backwards = Link::new(&test_comment().key(), &test_post().key(), "comments");
+        assert!(validate_link(&dna, &backwards, test_lookup).is_err());
+
+        let missing = Link::new("missing", &test_comment().key(), "comments");
+        assert_eq!(
+            Err("link base missing not found".to_string()),
+            validate_link(&dna, &missing, test_lookup)
+        );
+    }
+
+    #[test]
+    /// anchors link to their children and to the entry types declaring it
+    fn anchor_links_are_validated() {
+        let parent = Path::parse("posts/2018");
+        let child = parent.child("03");
+        let lookup = |key: &str| {
+            vec![parent.to_entry(), child.to_entry()]
+                .into_iter()
+                .find(|entry| entry.key() == key)
+                .or_else(|| test_lookup(key))
+        };
+        let dna = test_link_dna();
+        let validate = |link: &Link| validate_link(&dna, link, lookup);
+
+        assert_eq!(
+            Ok(()),
+            validate(&Link::new(&parent.address(), &child.address(), ANCHOR_CHILD_TAG))
+        );
+        let backwards = Link::new(&child.address(), &parent.address(), ANCHOR_CHILD_TAG);
+        assert!(validate(&backwards).is_err());
+        assert_eq!(Ok(()), validate(&child.link_to(&test_post().key(), "posts")));
+        assert!(validate(&child.link_to(&test_post().key(), "comments")).is_err());
+        assert!(validate(&child.link_to(&test_comment().key(), "posts")).is_err());
+    }
+
+    #[test]
+    /// the link validator checks link entries and passes everything else
+    fn validator() {
+        let validator = link_validator(test_link_dna(), Arc::new(test_lookup));
+        let valid = Link::new(&test_post().key(), &test_comment().key(), "comments");
+        let invalid = Link::new(&test_post().key(), &test_comment().key(), "likes");
+
+        assert_eq!(Ok(()), validator(&valid.validation_item()));
+        assert!(validator(&invalid.validation_item()).is_err());
+        assert_eq!(
+            Ok(()),
+            validator(&ValidationItem::new(&test_post(), Vec::new()))
+        );
+        assert!(
+            validator(&ValidationItem::new(
+                &Entry::new(LINK_ENTRY_TYPE, "not json"),
+                Vec::new()
+            )).is_err()
+        );
+    }
+}
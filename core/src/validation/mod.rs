@@ -0,0 +1,91 @@
+//! the validation module holds the machinery for validating entries received from the network
+//! independently of the redux action loop
+
+pub mod links;
+pub mod packages;
+pub mod pool;
+pub mod rates;
+pub mod receipts;
+pub mod revalidation;
+pub mod timestamps;
+pub mod warrants;
+
+use hash_table::entry::Entry;
+use std::sync::Arc;
+use trace::TraceContext;
+
+/// function that decides whether an item is valid, returning the reason when it is not
+pub type Validator = Arc<dyn Fn(&ValidationItem) -> Result<(), String> + Send + Sync>;
+
+/// an Entry awaiting validation
+/// dependencies are the addresses of other items that must be validated first, e.g. the base and
+/// target of a link must be validated before the link itself
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationItem {
+    pub address: String,
+    pub entry: Entry,
+    pub dependencies: Vec<String>,
+    /// the trace of the publish the item arrived with, see network::Envelope
+    pub trace: Option<TraceContext>,
+}
+
+impl ValidationItem {
+    /// build a new ValidationItem for an Entry, addressed by the Entry key
+    pub fn new(entry: &Entry, dependencies: Vec<String>) -> ValidationItem {
+        ValidationItem {
+            address: entry.key(),
+            entry: entry.clone(),
+            dependencies,
+            trace: None,
+        }
+    }
+
+    /// validate the item as part of the trace it was published in
+    pub fn traced(self, trace: TraceContext) -> ValidationItem {
+        ValidationItem {
+            trace: Some(trace),
+            ..self
+        }
+    }
+}
+
+/// where a queued ValidationItem is at
+#[derive(Clone, Debug, PartialEq)]
+pub enum ValidationStatus {
+    /// all dependencies are in place, the item is waiting for a free worker
+    Pending,
+    /// parked until the dependency at the given address is validated or arrives
+    AwaitingDeps(String),
+}
+
+/// the outcome of validating a ValidationItem
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationResult {
+    pub address: String,
+    pub result: Result<(), String>,
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::ValidationItem;
+    use hash_table::entry::tests::{test_entry, test_entry_b};
+
+    /// dummy validation item without dependencies
+    pub fn test_validation_item() -> ValidationItem {
+        ValidationItem::new(&test_entry(), Vec::new())
+    }
+
+    /// dummy validation item that depends on test_validation_item()
+    pub fn test_dependent_validation_item() -> ValidationItem {
+        ValidationItem::new(&test_entry_b(), vec![test_validation_item().address])
+    }
+
+    #[test]
+    /// tests for ValidationItem::new()
+    fn new() {
+        let item = test_dependent_validation_item();
+        assert_eq!(test_entry_b().key(), item.address);
+        assert_eq!(test_entry_b(), item.entry);
+        assert_eq!(vec![test_entry().key()], item.dependencies);
+    }
+}
@@ -0,0 +1,210 @@
+//! agents joining the network of a gated DNA are checked before anything they publish is held:
+//! the holder asks the author's node for its validation package, the pairs it committed during
+//! genesis and the membrane proof it joined with, and checks it the way genesis checked it, see
+//! agent::membrane
+//! the verdict is cached by agent so each package is asked for and checked once, what rejected
+//! agents publish is refused and they are told why, a rejection their zomes get as a signal
+//! headers of commits don't follow each other yet, see agent::commit(), so the pairs are checked
+//! each on their own
+
+use agent::{membrane::{self, AgentId}, AgentState, INIT_COMPLETE_ENTRY_TYPE};
+use hash_table::pair::Pair;
+use holochain_dna::Dna;
+use instance::Observer;
+use serde_json;
+use signal::Signal;
+use state;
+use std::{
+    collections::HashMap, fmt, sync::{mpsc::Sender, Arc, Mutex},
+};
+
+/// name of the signal an agent gets when a node rejects its validation package
+pub const AGENT_REJECTED_SIGNAL: &str = "agent_rejected";
+
+/// what an agent committed during genesis and the proof it joined with
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidationPackage {
+    pub agent: String,
+    /// the pairs committed up to and including the InitComplete marker, in commit order
+    pub genesis: Vec<Pair>,
+    #[serde(default)]
+    pub membrane_proof: Option<String>,
+}
+
+impl ValidationPackage {
+    /// the package of the agent, None until its genesis completed
+    pub fn of(agent: &AgentState, agent_id: &AgentId) -> Option<ValidationPackage> {
+        if !agent.init_complete() {
+            return None;
+        }
+        Some(ValidationPackage {
+            agent: agent_id.address.clone(),
+            genesis: agent.genesis().to_vec(),
+            membrane_proof: agent_id.membrane_proof.clone(),
+        })
+    }
+
+    /// check the package is the genesis of the agent for the DNA, returning the AgentId its
+    /// membrane proof is to be validated with if the DNA is gated
+    pub fn check(&self, agent: &str, dna: &Dna) -> Result<Option<AgentId>, String> {
+        if self.agent != agent {
+            return Err(format!("the package of {} came for {}", self.agent, agent));
+        }
+        if let Some(pair) = self.genesis.iter().find(|pair| !pair.validate()) {
+            return Err(format!("the genesis pair {} is not valid", pair.key()));
+        }
+        match self.genesis.last().map(Pair::entry) {
+            Some(marker)
+                if marker.entry_type() == INIT_COMPLETE_ENTRY_TYPE
+                    && marker.content() == dna.hash() => {}
+            _ => return Err(format!("{} did not complete genesis of the DNA", agent)),
+        }
+        if !membrane::is_gated(dna) {
+            return Ok(None);
+        }
+        let expected = AgentId {
+            address: agent.to_string(),
+            membrane_proof: self.membrane_proof.clone(),
+        };
+        match self.genesis.first().and_then(|pair| AgentId::from_entry(pair.entry())) {
+            Some(ref agent_id) if *agent_id == expected => Ok(Some(expected)),
+            _ => Err(format!("the genesis of {} doesn't start with its AgentId", agent)),
+        }
+    }
+
+    /// check the package and have validate_agent accept its membrane proof, see check()
+    pub fn verify(
+        &self,
+        agent: &str,
+        dna: &Dna,
+        action_channel: &Sender<state::ActionWrapper>,
+        observer_channel: &Sender<Observer>,
+    ) -> Result<(), String> {
+        match self.check(agent, dna)? {
+            Some(agent_id) => {
+                membrane::validate_agent(dna, &agent_id, action_channel, observer_channel)
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// a node's word that it rejected the validation package of the agent it is sent to
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rejection {
+    /// agent that checked the package
+    pub validator: String,
+    pub reason: String,
+}
+
+impl Rejection {
+    /// the signal reporting it, from no zome in particular
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            zome: String::new(),
+            name: AGENT_REJECTED_SIGNAL.to_string(),
+            payload: serde_json::to_value(self).expect("Rejection should serialize"),
+        }
+    }
+}
+
+/// the verdicts on the validation packages checked, by agent
+/// the verdicts are a cheap handle, clones share the same verdicts
+#[derive(Clone, Default)]
+pub struct PackageVerdicts {
+    verdicts: Arc<Mutex<HashMap<String, Result<(), String>>>>,
+}
+
+impl PartialEq for PackageVerdicts {
+    fn eq(&self, other: &PackageVerdicts) -> bool {
+        Arc::ptr_eq(&self.verdicts, &other.verdicts)
+    }
+}
+
+impl fmt::Debug for PackageVerdicts {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PackageVerdicts")
+            .field("agents", &self.verdicts.lock().unwrap().len())
+            .finish()
+    }
+}
+
+impl PackageVerdicts {
+    /// the verdict on the package of the agent, None until it was checked
+    pub fn get(&self, agent: &str) -> Option<Result<(), String>> {
+        self.verdicts.lock().unwrap().get(agent).cloned()
+    }
+
+    /// keep the verdict on the package of the agent
+    pub fn record(&self, agent: &str, verdict: Result<(), String>) {
+        self.verdicts
+            .lock()
+            .unwrap()
+            .insert(agent.to_string(), verdict);
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use chain::tests::test_chain;
+    use hash_table::entry::Entry;
+
+    fn pair(entry: &Entry) -> Pair {
+        Pair::new(&test_chain(), entry)
+    }
+
+    /// the package of alice's genesis for the DNA, with the AgentId entry if proven
+    pub fn test_package(dna: &Dna, membrane_proof: Option<&str>) -> ValidationPackage {
+        let mut genesis = Vec::new();
+        if membrane_proof.is_some() {
+            genesis.push(pair(&AgentId::new("alice", membrane_proof).to_entry()));
+        }
+        genesis.push(pair(&Entry::new("post", "hello")));
+        genesis.push(pair(&Entry::new(INIT_COMPLETE_ENTRY_TYPE, &dna.hash())));
+        ValidationPackage {
+            agent: "alice".to_string(),
+            genesis,
+            membrane_proof: membrane_proof.map(str::to_string),
+        }
+    }
+
+    #[test]
+    /// packages are the agent's complete genesis of the DNA
+    fn check() {
+        let dna = Dna::new();
+        let package = test_package(&dna, None);
+        assert_eq!(Ok(None), package.check("alice", &dna));
+        assert!(package.check("bob", &dna).is_err());
+
+        let mut incomplete = package.clone();
+        incomplete.genesis.pop();
+        assert!(incomplete.check("alice", &dna).is_err());
+
+        let mut other = Dna::new();
+        other.name = "other".to_string();
+        assert!(package.check("alice", &other).is_err());
+    }
+
+    #[test]
+    /// the verdicts are shared by clones
+    fn verdicts() {
+        let verdicts = PackageVerdicts::default();
+        assert_eq!(None, verdicts.get("alice"));
+        verdicts.clone().record("alice", Ok(()));
+        verdicts.record("bob", Err("no proof".to_string()));
+        assert_eq!(Some(Ok(())), verdicts.get("alice"));
+        assert_eq!(Some(Err("no proof".to_string())), verdicts.get("bob"));
+    }
+
+    #[test]
+    fn rejection_signal() {
+        let rejection = Rejection {
+            validator: "bob".to_string(),
+            reason: "no proof".to_string(),
+        };
+        let signal = rejection.to_signal();
+        assert_eq!(AGENT_REJECTED_SIGNAL, signal.name);
+        assert_eq!(json!({"validator": "bob", "reason": "no proof"}), signal.payload);
+    }
+}
@@ -0,0 +1,840 @@
+use platform;
+use std::{
+    collections::{HashMap, HashSet, VecDeque}, sync::{
+        mpsc::{channel, Receiver, Sender}, Arc, Condvar, Mutex,
+    },
+    thread::JoinHandle, time::{Duration, Instant},
+};
+use trace::Tracer;
+use validation::{ValidationItem, ValidationResult, ValidationStatus, Validator};
+
+pub const VALIDATION_POOL_DEFAULT_WORKERS: usize = 4;
+pub const VALIDATION_POOL_DEFAULT_QUEUE_SIZE: usize = 256;
+pub const VALIDATION_POOL_DEFAULT_AWAITING_SIZE: usize = 256;
+pub const VALIDATION_POOL_DEFAULT_DEPENDENCY_TIMEOUT_MS: u64 = 60_000;
+pub const VALIDATION_POOL_DEFAULT_VALIDATED_CAPACITY: usize = 4096;
+/// how often idle workers wake up to check parked items for timeouts
+const VALIDATION_POOL_TICK_MS: u64 = 50;
+
+/// configuration for a ValidationPool
+#[derive(Clone, Debug, PartialEq)]
+pub struct ValidationPoolConfig {
+    /// number of worker threads validating in parallel
+    pub workers: usize,
+    /// max items queued ready for validation before submit() blocks
+    pub queue_size: usize,
+    /// max items waiting on dependencies, past it the items waiting longest are given up on so
+    /// they cannot starve new work
+    pub awaiting_size: usize,
+    /// how long an item may wait in limbo for a dependency before it is given up on
+    pub dependency_timeout: Duration,
+    /// max results of finished items kept for their dependents, the least recently used are
+    /// forgotten first
+    pub validated_capacity: usize,
+    /// traced items are validated in a span of their trace, recorded by this tracer
+    pub tracer: Option<Tracer>,
+}
+
+impl Default for ValidationPoolConfig {
+    fn default() -> Self {
+        ValidationPoolConfig {
+            workers: VALIDATION_POOL_DEFAULT_WORKERS,
+            queue_size: VALIDATION_POOL_DEFAULT_QUEUE_SIZE,
+            awaiting_size: VALIDATION_POOL_DEFAULT_AWAITING_SIZE,
+            dependency_timeout: Duration::from_millis(
+                VALIDATION_POOL_DEFAULT_DEPENDENCY_TIMEOUT_MS,
+            ),
+            validated_capacity: VALIDATION_POOL_DEFAULT_VALIDATED_CAPACITY,
+            tracer: None,
+        }
+    }
+}
+
+/// diagnostic snapshot of an item sitting in the validation queue
+#[derive(Clone, Debug, PartialEq)]
+pub struct LimboItem {
+    pub address: String,
+    pub status: ValidationStatus,
+    /// how long the item has been in its current status
+    pub waiting: Duration,
+}
+
+/// a ValidationItem in the queue along with where it is at
+struct Queued {
+    item: ValidationItem,
+    status: ValidationStatus,
+    since: Instant,
+}
+
+/// state shared between the pool handle and its workers
+struct Shared {
+    queue: VecDeque<Queued>,
+    /// address => true if valid, for the items that finished validation most recently used
+    validated: HashMap<String, bool>,
+    validated_capacity: usize,
+    /// addresses of validated, least recently used first
+    recent: VecDeque<String>,
+    /// addresses that are already held elsewhere so do not need validating here, kept while a
+    /// queued item depends on them
+    arrived: HashSet<String>,
+    shutdown: bool,
+}
+
+/// the first dependency of an item that has neither finished validation nor arrived
+fn missing_dependency(
+    validated: &HashMap<String, bool>,
+    arrived: &HashSet<String>,
+    item: &ValidationItem,
+) -> Option<String> {
+    item.dependencies
+        .iter()
+        .find(|dependency| !validated.contains_key(*dependency) && !arrived.contains(*dependency))
+        .cloned()
+}
+
+/// the status of an item given what has been validated and what has arrived so far
+fn status(
+    validated: &HashMap<String, bool>,
+    arrived: &HashSet<String>,
+    item: &ValidationItem,
+) -> ValidationStatus {
+    match missing_dependency(validated, arrived, item) {
+        None => ValidationStatus::Pending,
+        Some(dependency) => ValidationStatus::AwaitingDeps(dependency),
+    }
+}
+
+/// the first dependency of an item that failed validation
+fn failed_dependency(validated: &HashMap<String, bool>, item: &ValidationItem) -> Option<String> {
+    item.dependencies
+        .iter()
+        .find(|dependency| validated.get(*dependency) == Some(&false))
+        .cloned()
+}
+
+impl Shared {
+    /// take the first queued item whose dependencies are all in place
+    /// the status of every queued item is brought up to date along the way, items that are still
+    /// missing a dependency are parked as AwaitingDeps
+    /// items with a failed dependency are never ready, see reject_failed()
+    fn next_ready(&mut self) -> Option<ValidationItem> {
+        let mut ready = None;
+        for (position, queued) in self.queue.iter_mut().enumerate() {
+            let status = status(&self.validated, &self.arrived, &queued.item);
+            if status != queued.status {
+                queued.status = status;
+                queued.since = Instant::now();
+            }
+            if ready.is_none()
+                && queued.status == ValidationStatus::Pending
+                && failed_dependency(&self.validated, &queued.item).is_none()
+            {
+                ready = Some(position);
+            }
+        }
+        let item = ready
+            .and_then(|position| self.queue.remove(position))
+            .map(|queued| queued.item)?;
+        self.prune_arrived(&item);
+        for dependency in &item.dependencies {
+            if self.validated.contains_key(dependency) {
+                self.touch(dependency);
+            }
+        }
+        Some(item)
+    }
+
+    /// forget the arrived dependencies of an item leaving the queue that nothing queued still
+    /// depends on
+    fn prune_arrived(&mut self, item: &ValidationItem) {
+        for dependency in &item.dependencies {
+            if self.arrived.contains(dependency) && !self.awaited(dependency) {
+                self.arrived.remove(dependency);
+            }
+        }
+    }
+
+    /// fail every queued item with a dependency that failed validation, without running it
+    /// through the validator
+    /// the failures are recorded in turn, so whatever depends on a failed item fails too
+    fn reject_failed(&mut self) -> Vec<ValidationResult> {
+        let mut rejected = Vec::new();
+        while let Some((position, dependency)) = self
+            .queue
+            .iter()
+            .enumerate()
+            .filter_map(|(position, queued)| {
+                failed_dependency(&self.validated, &queued.item).map(|d| (position, d))
+            })
+            .next()
+        {
+            let queued = self.queue.remove(position).unwrap();
+            self.prune_arrived(&queued.item);
+            self.touch(&dependency);
+            self.record(&queued.item.address, false);
+            rejected.push(ValidationResult {
+                address: queued.item.address,
+                result: Err(format!("dependency {} failed validation", dependency)),
+            });
+        }
+        rejected
+    }
+
+    /// number of queued items waiting on a dependency
+    fn awaiting_count(&self) -> usize {
+        self.queue
+            .iter()
+            .filter(|queued| queued.status != ValidationStatus::Pending)
+            .count()
+    }
+
+    /// number of queued items ready for validation, these are what the queue size limits
+    fn pending_count(&self) -> usize {
+        self.queue.len() - self.awaiting_count()
+    }
+
+    /// true if a queued item depends on the address
+    fn awaited(&self, address: &str) -> bool {
+        self.queue
+            .iter()
+            .any(|queued| queued.item.dependencies.iter().any(|d| d == address))
+    }
+
+    fn touch(&mut self, address: &str) {
+        self.recent.retain(|a| a != address);
+        self.recent.push_back(address.to_string());
+    }
+
+    /// keep the result of a finished item, forgetting the least recently used past the capacity
+    /// items submitted after the result of their dependency is forgotten wait for it to arrive
+    fn record(&mut self, address: &str, valid: bool) {
+        self.validated.insert(address.to_string(), valid);
+        self.touch(address);
+        while self.validated.len() > self.validated_capacity {
+            match self.recent.pop_front() {
+                Some(address) => {
+                    self.validated.remove(&address);
+                }
+                None => break,
+            }
+        }
+    }
+
+    /// drop every item that has been awaiting a dependency for longer than the timeout, then the
+    /// items awaiting longest while more than awaiting_size are left
+    fn expire(&mut self, timeout: Duration, awaiting_size: usize) -> Vec<ValidationResult> {
+        let (expired, kept): (Vec<Queued>, Vec<Queued>) =
+            self.queue.drain(..).partition(|queued| match queued.status {
+                ValidationStatus::AwaitingDeps(_) => queued.since.elapsed() > timeout,
+                ValidationStatus::Pending => false,
+            });
+        self.queue = kept.into_iter().collect();
+        let mut results: Vec<ValidationResult> = expired
+            .into_iter()
+            .map(|queued| self.give_up(queued, "timed out awaiting dependency"))
+            .collect();
+
+        while self.awaiting_count() > awaiting_size {
+            let longest = self
+                .queue
+                .iter()
+                .enumerate()
+                .filter(|(_, queued)| queued.status != ValidationStatus::Pending)
+                .min_by_key(|(_, queued)| queued.since)
+                .map(|(position, _)| position);
+            match longest.and_then(|position| self.queue.remove(position)) {
+                Some(queued) => results.push(
+                    self.give_up(queued, "too many items awaiting, gave up on dependency"),
+                ),
+                None => break,
+            }
+        }
+        results
+    }
+
+    /// the failed result of an item taken off the queue while awaiting a dependency
+    fn give_up(&mut self, queued: Queued, reason: &str) -> ValidationResult {
+        self.prune_arrived(&queued.item);
+        ValidationResult {
+            address: queued.item.address,
+            result: Err(match queued.status {
+                ValidationStatus::AwaitingDeps(dependency) => format!("{} {}", reason, dependency),
+                ValidationStatus::Pending => unreachable!(),
+            }),
+        }
+    }
+
+    /// put an item at the back of the queue
+    fn push(&mut self, item: ValidationItem) {
+        let status = status(&self.validated, &self.arrived, &item);
+        self.queue.push_back(Queued {
+            item,
+            status,
+            since: Instant::now(),
+        });
+    }
+}
+
+struct Inner {
+    shared: Mutex<Shared>,
+    /// signalled whenever new work may be ready
+    work: Condvar,
+    /// signalled whenever the queue shrinks
+    not_full: Condvar,
+    queue_size: usize,
+    awaiting_size: usize,
+    dependency_timeout: Duration,
+    tracer: Option<Tracer>,
+}
+
+/// bounded pool of worker threads validating independent items in parallel
+/// items are only handed to a worker once all of their dependencies have been validated or have
+/// arrived, and an item fails without being run through the validator if any of its dependencies
+/// failed
+/// items missing a dependency wait in limbo as AwaitingDeps until it shows up or they time out,
+/// they do not count towards the queue size but have a cap of their own
+pub struct ValidationPool {
+    inner: Arc<Inner>,
+    workers: Vec<JoinHandle<()>>,
+    results: Receiver<ValidationResult>,
+}
+
+impl ValidationPool {
+    /// start a new pool with config.workers threads running the validator
+    pub fn new(config: &ValidationPoolConfig, validator: Validator) -> ValidationPool {
+        let inner = Arc::new(Inner {
+            shared: Mutex::new(Shared {
+                queue: VecDeque::new(),
+                validated: HashMap::new(),
+                validated_capacity: config.validated_capacity.max(1),
+                recent: VecDeque::new(),
+                arrived: HashSet::new(),
+                shutdown: false,
+            }),
+            work: Condvar::new(),
+            not_full: Condvar::new(),
+            queue_size: config.queue_size.max(1),
+            awaiting_size: config.awaiting_size,
+            dependency_timeout: config.dependency_timeout,
+            tracer: config.tracer.clone(),
+        });
+        let (tx_result, rx_result) = channel();
+
+        let workers = (0..config.workers.max(1))
+            .map(|_| {
+                let inner = Arc::clone(&inner);
+                let validator = Arc::clone(&validator);
+                let tx_result = tx_result.clone();
+                platform::spawn("validation", move || work(&inner, &validator, &tx_result))
+            })
+            .collect();
+
+        ValidationPool {
+            inner,
+            workers,
+            results: rx_result,
+        }
+    }
+
+    /// queue an item for validation, blocking while the queue is full
+    pub fn submit(&self, item: ValidationItem) {
+        let mut shared = self.inner.shared.lock().unwrap();
+        while shared.pending_count() >= self.inner.queue_size {
+            shared = self.inner.not_full.wait(shared).unwrap();
+        }
+        shared.push(item);
+        self.inner.work.notify_all();
+    }
+
+    /// queue an item for validation without blocking
+    /// the item is handed back if the queue is full so the caller can apply backpressure upstream
+    #[allow(clippy::result_large_err)]
+    pub fn try_submit(&self, item: ValidationItem) -> Result<(), ValidationItem> {
+        let mut shared = self.inner.shared.lock().unwrap();
+        if shared.pending_count() >= self.inner.queue_size {
+            return Err(item);
+        }
+        shared.push(item);
+        self.inner.work.notify_all();
+        Ok(())
+    }
+
+    /// number of items queued and not yet picked up by a worker
+    pub fn queued(&self) -> usize {
+        self.inner.shared.lock().unwrap().queue.len()
+    }
+
+    /// let the pool know that the data at an address is held, e.g. it was fetched from the DHT
+    /// items queued awaiting it are retried, it is forgotten once none are left, so items
+    /// submitted after that wait for it to arrive again
+    pub fn dependency_arrived(&self, address: &str) {
+        let mut shared = self.inner.shared.lock().unwrap();
+        if shared.awaited(address) {
+            shared.arrived.insert(address.to_string());
+            self.inner.work.notify_all();
+        }
+    }
+
+    /// diagnostic listing of everything still queued, including items stuck in limbo
+    pub fn limbo(&self) -> Vec<LimboItem> {
+        self.inner
+            .shared
+            .lock()
+            .unwrap()
+            .queue
+            .iter()
+            .map(|queued| LimboItem {
+                address: queued.item.address.clone(),
+                status: queued.status.clone(),
+                waiting: queued.since.elapsed(),
+            })
+            .collect()
+    }
+
+    /// receiver for the results of validation, in order of completion
+    pub fn results(&self) -> &Receiver<ValidationResult> {
+        &self.results
+    }
+}
+
+impl Drop for ValidationPool {
+    fn drop(&mut self) {
+        {
+            let mut shared = self.inner.shared.lock().unwrap();
+            shared.shutdown = true;
+        }
+        self.inner.work.notify_all();
+        self.inner.not_full.notify_all();
+        for worker in self.workers.drain(..) {
+            worker.join().expect("validation worker should not panic");
+        }
+    }
+}
+
+/// worker loop, runs until the pool shuts down and nothing is ready to validate
+fn work(inner: &Inner, validator: &Validator, tx_result: &Sender<ValidationResult>) {
+    loop {
+        let item = {
+            let mut shared = inner.shared.lock().unwrap();
+            loop {
+                let mut given_up = shared.reject_failed();
+                given_up.extend(shared.expire(inner.dependency_timeout, inner.awaiting_size));
+                if !given_up.is_empty() {
+                    inner.not_full.notify_all();
+                }
+                for result in given_up {
+                    let _ = tx_result.send(result);
+                }
+                if let Some(item) = shared.next_ready() {
+                    break item;
+                }
+                if shared.shutdown {
+                    return;
+                }
+                shared = if shared.queue.is_empty() {
+                    inner.work.wait(shared).unwrap()
+                } else {
+                    // parked items need checking for timeouts even if nothing else happens
+                    inner
+                        .work
+                        .wait_timeout(shared, Duration::from_millis(VALIDATION_POOL_TICK_MS))
+                        .unwrap()
+                        .0
+                };
+            }
+        };
+        inner.not_full.notify_all();
+
+        let mut span = match (&inner.tracer, &item.trace) {
+            (Some(tracer), Some(trace)) => Some(tracer.child_of("validate", trace)),
+            _ => None,
+        };
+
+        let result = validator(&item);
+
+        if let Some(ref mut span) = span {
+            span.tag("address", &item.address);
+            span.tag("valid", &result.is_ok().to_string());
+        }
+        drop(span);
+
+        {
+            let mut shared = inner.shared.lock().unwrap();
+            shared.record(&item.address, result.is_ok());
+        }
+        // dependents of this item may now be ready
+        inner.work.notify_all();
+
+        // nobody listening for results is not a reason to stop validating
+        let _ = tx_result.send(ValidationResult {
+            address: item.address,
+            result,
+        });
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::{ValidationPool, ValidationPoolConfig};
+    use hash_table::entry::Entry;
+    use std::{
+        sync::{mpsc::channel, Arc, Mutex}, thread::sleep, time::Duration,
+    };
+    use trace::{tests::test_trace_context, Tracer};
+    use validation::{
+        tests::{test_dependent_validation_item, test_validation_item}, ValidationItem,
+        ValidationResult, ValidationStatus, Validator,
+    };
+
+    /// validator that accepts everything
+    pub fn test_validator() -> Validator {
+        Arc::new(|_: &ValidationItem| Ok(()))
+    }
+
+    /// receive the next result, failing the test rather than hanging
+    fn next_result(pool: &ValidationPool) -> ValidationResult {
+        pool.results()
+            .recv_timeout(Duration::from_millis(1000))
+            .expect("validation result should arrive")
+    }
+
+    #[test]
+    /// items are validated and results reported
+    fn validate() {
+        let validator: Validator = Arc::new(|item: &ValidationItem| {
+            if item.entry.content() == "bad" {
+                Err("bad content".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), validator);
+
+        let good = ValidationItem::new(&Entry::new("t", "good"), Vec::new());
+        let bad = ValidationItem::new(&Entry::new("t", "bad"), Vec::new());
+        pool.submit(good.clone());
+        pool.submit(bad.clone());
+
+        let mut results = vec![next_result(&pool), next_result(&pool)];
+        results.sort_by(|a, b| a.address.cmp(&b.address));
+        let mut expected = vec![
+            ValidationResult {
+                address: good.address,
+                result: Ok(()),
+            },
+            ValidationResult {
+                address: bad.address,
+                result: Err("bad content".to_string()),
+            },
+        ];
+        expected.sort_by(|a, b| a.address.cmp(&b.address));
+        assert_eq!(expected, results);
+    }
+
+    #[test]
+    /// dependents are held back until their dependencies are validated
+    fn dependency_order() {
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let order_clone = Arc::clone(&order);
+        let validator: Validator = Arc::new(move |item: &ValidationItem| {
+            order_clone.lock().unwrap().push(item.address.clone());
+            Ok(())
+        });
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), validator);
+
+        // submit the dependent first, it must still be validated last
+        let dependency = test_validation_item();
+        let dependent = test_dependent_validation_item();
+        pool.submit(dependent.clone());
+        assert_eq!(1, pool.queued());
+        pool.submit(dependency.clone());
+
+        assert_eq!(dependency.address, next_result(&pool).address);
+        assert_eq!(dependent.address, next_result(&pool).address);
+        assert_eq!(
+            vec![dependency.address, dependent.address],
+            *order.lock().unwrap()
+        );
+    }
+
+    #[test]
+    /// dependents of invalid items fail without running the validator
+    fn failed_dependency() {
+        let validator: Validator = Arc::new(|item: &ValidationItem| {
+            if item.dependencies.is_empty() {
+                Err("nope".to_string())
+            } else {
+                Ok(())
+            }
+        });
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), validator);
+
+        let dependency = test_validation_item();
+        let dependent = test_dependent_validation_item();
+        pool.submit(dependency.clone());
+        pool.submit(dependent.clone());
+
+        assert_eq!(Err("nope".to_string()), next_result(&pool).result);
+        assert_eq!(
+            Err(format!("dependency {} failed validation", dependency.address)),
+            next_result(&pool).result
+        );
+    }
+
+    #[test]
+    /// items depending on an invalid item, directly or not, are rejected without ever being
+    /// handed to the validator
+    fn failed_dependency_rejects_dependents() {
+        let validated = Arc::new(Mutex::new(Vec::new()));
+        let validated_clone = Arc::clone(&validated);
+        let validator: Validator = Arc::new(move |item: &ValidationItem| {
+            validated_clone.lock().unwrap().push(item.address.clone());
+            Err("nope".to_string())
+        });
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), validator);
+
+        let dependency = test_validation_item();
+        let dependent = test_dependent_validation_item();
+        let transitive =
+            ValidationItem::new(&Entry::new("t", "transitive"), vec![dependent.address.clone()]);
+        pool.submit(dependent.clone());
+        pool.submit(transitive.clone());
+        pool.submit(dependency.clone());
+
+        let mut results = vec![next_result(&pool), next_result(&pool), next_result(&pool)];
+        results.sort_by(|a, b| a.address.cmp(&b.address));
+        let mut expected = vec![
+            ValidationResult {
+                address: dependency.address.clone(),
+                result: Err("nope".to_string()),
+            },
+            ValidationResult {
+                address: dependent.address.clone(),
+                result: Err(format!("dependency {} failed validation", dependency.address)),
+            },
+            ValidationResult {
+                address: transitive.address,
+                result: Err(format!("dependency {} failed validation", dependent.address)),
+            },
+        ];
+        expected.sort_by(|a, b| a.address.cmp(&b.address));
+        assert_eq!(expected, results);
+        assert_eq!(vec![dependency.address], *validated.lock().unwrap());
+        assert!(pool.limbo().is_empty());
+    }
+
+    #[test]
+    /// try_submit() hands items back when the queue is full
+    fn backpressure() {
+        // block the only worker until we say so
+        let (tx_release, rx_release) = channel::<()>();
+        let rx_release = Mutex::new(rx_release);
+        let validator: Validator = Arc::new(move |_: &ValidationItem| {
+            rx_release.lock().unwrap().recv().unwrap();
+            Ok(())
+        });
+        let config = ValidationPoolConfig {
+            workers: 1,
+            queue_size: 1,
+            ..Default::default()
+        };
+        let pool = ValidationPool::new(&config, validator);
+
+        let first = ValidationItem::new(&Entry::new("t", "1"), Vec::new());
+        let second = ValidationItem::new(&Entry::new("t", "2"), Vec::new());
+        let third = ValidationItem::new(&Entry::new("t", "3"), Vec::new());
+
+        // first is picked up by the worker, second fills the queue
+        pool.submit(first);
+        while pool.queued() > 0 {
+            sleep(Duration::from_millis(1));
+        }
+        assert_eq!(Ok(()), pool.try_submit(second));
+        assert_eq!(Err(third.clone()), pool.try_submit(third.clone()));
+
+        for _ in 0..2 {
+            tx_release.send(()).unwrap();
+            next_result(&pool);
+        }
+        assert_eq!(Ok(()), pool.try_submit(third));
+        tx_release.send(()).unwrap();
+        next_result(&pool);
+    }
+
+    #[test]
+    /// items missing a dependency are parked in limbo until it arrives
+    fn awaiting_deps() {
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), test_validator());
+
+        let dependent = test_dependent_validation_item();
+        pool.submit(dependent.clone());
+
+        // wait for a worker to notice the item is missing its dependency
+        let expected_status = ValidationStatus::AwaitingDeps(test_validation_item().address);
+        while pool.limbo()[0].status != expected_status {
+            sleep(Duration::from_millis(1));
+        }
+        let limbo = pool.limbo();
+        assert_eq!(1, limbo.len());
+        assert_eq!(dependent.address, limbo[0].address);
+        assert!(pool.results().try_recv().is_err());
+
+        // the dependency turning up (without needing validation here) releases the item
+        pool.dependency_arrived(&test_validation_item().address);
+        let result = next_result(&pool);
+        assert_eq!(dependent.address, result.address);
+        assert_eq!(Ok(()), result.result);
+        assert!(pool.limbo().is_empty());
+    }
+
+    #[test]
+    /// items awaiting dependencies do not fill the queue, past their own cap the items awaiting
+    /// longest are given up on
+    fn awaiting_size() {
+        let config = ValidationPoolConfig {
+            queue_size: 1,
+            awaiting_size: 1,
+            ..Default::default()
+        };
+        let pool = ValidationPool::new(&config, test_validator());
+
+        let first = ValidationItem::new(&Entry::new("t", "1"), vec!["a".to_string()]);
+        let second = ValidationItem::new(&Entry::new("t", "2"), vec!["b".to_string()]);
+        assert_eq!(Ok(()), pool.try_submit(first.clone()));
+        sleep(Duration::from_millis(1));
+        assert_eq!(Ok(()), pool.try_submit(second.clone()));
+
+        let result = next_result(&pool);
+        assert_eq!(first.address, result.address);
+        assert_eq!(
+            Err("too many items awaiting, gave up on dependency a".to_string()),
+            result.result
+        );
+        let limbo = pool.limbo();
+        assert_eq!(1, limbo.len());
+        assert_eq!(second.address, limbo[0].address);
+
+        // ready work still gets through
+        let ready = test_validation_item();
+        assert_eq!(Ok(()), pool.try_submit(ready.clone()));
+        assert_eq!(ready.address, next_result(&pool).address);
+    }
+
+    #[test]
+    /// a pass over the queue brings every item up to date, not just the items before the first
+    /// ready one
+    fn statuses() {
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), test_validator());
+        let dependent = test_dependent_validation_item();
+        let mut shared = pool.inner.shared.lock().unwrap();
+        let ready = test_validation_item();
+        shared.push(ready.clone());
+        shared.push(dependent.clone());
+
+        assert_eq!(Some(ready), shared.next_ready());
+        assert_eq!(
+            ValidationStatus::AwaitingDeps(test_validation_item().address),
+            shared.queue[0].status
+        );
+    }
+
+    #[test]
+    /// arrived dependencies are only kept while something queued depends on them
+    fn arrived_pruned() {
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), test_validator());
+        let dependency = test_validation_item().address;
+
+        // nothing is waiting for it
+        pool.dependency_arrived(&dependency);
+        assert!(pool.inner.shared.lock().unwrap().arrived.is_empty());
+
+        pool.submit(test_dependent_validation_item());
+        pool.dependency_arrived(&dependency);
+        assert_eq!(Ok(()), next_result(&pool).result);
+        assert!(pool.inner.shared.lock().unwrap().arrived.is_empty());
+    }
+
+    #[test]
+    /// items awaiting a dependency for too long are given up on
+    fn dependency_timeout() {
+        let config = ValidationPoolConfig {
+            dependency_timeout: Duration::from_millis(10),
+            ..Default::default()
+        };
+        let pool = ValidationPool::new(&config, test_validator());
+
+        let dependent = test_dependent_validation_item();
+        pool.submit(dependent.clone());
+
+        let result = next_result(&pool);
+        assert_eq!(dependent.address, result.address);
+        assert_eq!(
+            Err(format!(
+                "timed out awaiting dependency {}",
+                test_validation_item().address
+            )),
+            result.result
+        );
+        assert!(pool.limbo().is_empty());
+    }
+
+    #[test]
+    /// traced items are validated in a span of the trace they were published in
+    fn traced() {
+        let tracer = Tracer::default();
+        let config = ValidationPoolConfig {
+            tracer: Some(tracer.clone()),
+            ..Default::default()
+        };
+        let pool = ValidationPool::new(&config, test_validator());
+
+        pool.submit(test_validation_item().traced(test_trace_context()));
+        next_result(&pool);
+
+        let spans = tracer.trace(&test_trace_context().trace_id);
+        assert_eq!(1, spans.len());
+        assert_eq!("validate", spans[0].name);
+        assert_eq!(Some(test_trace_context().span_id), spans[0].parent_id);
+        assert!(
+            spans[0]
+                .tags
+                .contains(&("valid".to_string(), "true".to_string()))
+        );
+    }
+
+    #[test]
+    /// only the results of the most recently used items are kept for their dependents
+    fn validated_capacity() {
+        let config = ValidationPoolConfig {
+            workers: 1,
+            validated_capacity: 2,
+            ..Default::default()
+        };
+        let pool = ValidationPool::new(&config, test_validator());
+
+        let dependency = test_validation_item();
+        let other = ValidationItem::new(&Entry::new("t", "other"), Vec::new());
+        let dependent = test_dependent_validation_item();
+        for item in &[&dependency, &other, &dependent] {
+            pool.submit((*item).clone());
+            next_result(&pool);
+        }
+
+        // the dependent using the result of its dependency made other the least recently used
+        let shared = pool.inner.shared.lock().unwrap();
+        assert_eq!(2, shared.validated.len());
+        assert!(shared.validated.contains_key(&dependency.address));
+        assert!(shared.validated.contains_key(&dependent.address));
+        assert!(!shared.validated.contains_key(&other.address));
+        assert_eq!(shared.validated.len(), shared.recent.len());
+    }
+
+    #[test]
+    /// dropping the pool stops the workers
+    fn shutdown() {
+        let pool = ValidationPool::new(&ValidationPoolConfig::default(), test_validator());
+        pool.submit(test_validation_item());
+        next_result(&pool);
+        drop(pool);
+    }
+}
@@ -0,0 +1,299 @@
+//! per author rate limits keep a hostile agent from flooding the nodes holding its entries
+//! holders count the entries of each author they take from publishes, and refuse the ones that
+//! take the author over the author_rates of the network config with an invalid receipt
+//! headers carry no usable time yet, so entries count from when they arrived rather than from
+//! when they were committed, and the author is the first agent that signed the header, or the
+//! publisher for entries coming without one
+//! entry types of the DNA may also declare a rate bucket, the weight of their entries each agent
+//! may commit within an interval, which agents check when committing and holders when taking the
+//! entries of an author
+
+use dht::aspect::Aspect;
+use hash_table::entry::Entry;
+use holochain_dna::{zome::entry_types::RateBucket, Dna};
+use network::config::AuthorRateLimits;
+use std::{
+    collections::{BTreeMap, VecDeque}, fmt, sync::{Arc, Mutex},
+};
+
+const MINUTE_SECS: u64 = 60;
+const HOUR_SECS: u64 = 60 * MINUTE_SECS;
+
+/// the entries of each author taken within the last hour, as seconds since the unix epoch when
+/// they arrived and bytes of content, oldest first
+type Arrivals = BTreeMap<String, VecDeque<(u64, u64)>>;
+
+/// the rate buckets the entry types of a DNA declare, with the weight of their entries, by entry
+/// type name, entry types without one are left out
+pub type Buckets = BTreeMap<String, (u64, RateBucket)>;
+
+/// the rate buckets declared in the DNA
+pub fn buckets(dna: &Dna) -> Buckets {
+    dna.zomes
+        .iter()
+        .flat_map(|zome| zome.entry_types.iter())
+        .filter_map(|entry_type| {
+            entry_type
+                .rate_bucket
+                .clone()
+                .map(|bucket| (entry_type.name.clone(), (entry_type.weight, bucket)))
+        })
+        .collect()
+}
+
+/// what an agent took from the rate buckets lately, as seconds since the unix epoch when and the
+/// weight taken, oldest first, by entry type name
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct BucketUse {
+    taken: BTreeMap<String, VecDeque<(u64, u64)>>,
+}
+
+impl BucketUse {
+    /// take the weight of an entry of entry_type from its bucket at now, unless the bucket is
+    /// used up, then the reason is returned and nothing is taken
+    /// entry types without a bucket take nothing
+    pub fn take(&mut self, entry_type: &str, buckets: &Buckets, now: u64) -> Result<(), String> {
+        let (weight, bucket) = match buckets.get(entry_type) {
+            Some(declared) => declared,
+            None => return Ok(()),
+        };
+        let recent = self.taken.entry(entry_type.to_string()).or_default();
+        while recent
+            .front()
+            .map(|(at, _)| now.saturating_sub(*at) >= bucket.interval_secs)
+            .unwrap_or(false)
+        {
+            recent.pop_front();
+        }
+        let used = recent.iter().map(|(_, weight)| weight).sum::<u64>();
+        if used + weight > bucket.capacity {
+            return Err(format!(
+                "'{}' entries are over their rate bucket of {} per {} seconds",
+                entry_type, bucket.capacity, bucket.interval_secs
+            ));
+        }
+        recent.push_back((now, *weight));
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Rates {
+    arrivals: Arrivals,
+    buckets: BTreeMap<String, BucketUse>,
+}
+
+/// the entries authors got held by an instance lately
+/// the rates are a cheap handle, clones share the same counts
+#[derive(Clone, Default)]
+pub struct AuthorRates {
+    rates: Arc<Mutex<Rates>>,
+}
+
+impl PartialEq for AuthorRates {
+    fn eq(&self, other: &AuthorRates) -> bool {
+        Arc::ptr_eq(&self.rates, &other.rates)
+    }
+}
+
+impl fmt::Debug for AuthorRates {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AuthorRates")
+            .field("authors", &self.rates.lock().unwrap().arrivals.len())
+            .finish()
+    }
+}
+
+impl AuthorRates {
+    /// count an entry of the author arriving at now and take it from the rate bucket of its
+    /// type, unless it takes the author over the limits or the bucket, then the reason is
+    /// returned and it isn't counted
+    pub fn admit(
+        &self,
+        author: &str,
+        entry: &Entry,
+        now: u64,
+        limits: &AuthorRateLimits,
+        buckets: &Buckets,
+    ) -> Result<(), String> {
+        let mut rates = self.rates.lock().unwrap();
+        let bytes = entry.content().len() as u64;
+        if limits.is_limited() {
+            check_limits(&mut rates.arrivals, author, bytes, now, limits)?;
+        }
+        rates
+            .buckets
+            .entry(author.to_string())
+            .or_default()
+            .take(entry.entry_type(), buckets, now)
+            .map_err(|reason| format!("{}: {}", author, reason))?;
+        if limits.is_limited() {
+            rates
+                .arrivals
+                .entry(author.to_string())
+                .or_default()
+                .push_back((now, bytes));
+        }
+        Ok(())
+    }
+}
+
+/// check an entry of the author with bytes of content arriving at now keeps it within the limits
+fn check_limits(
+    arrivals: &mut Arrivals,
+    author: &str,
+    bytes: u64,
+    now: u64,
+    limits: &AuthorRateLimits,
+) -> Result<(), String> {
+    let recent = arrivals.entry(author.to_string()).or_default();
+    while recent
+        .front()
+        .map(|(at, _)| now.saturating_sub(*at) >= HOUR_SECS)
+        .unwrap_or(false)
+    {
+        recent.pop_front();
+    }
+    let last_minute = recent
+        .iter()
+        .filter(|(at, _)| now.saturating_sub(*at) < MINUTE_SECS)
+        .count() as u64;
+    if let Some(max) = limits.max_entries_per_minute {
+        if last_minute + 1 > max {
+            return Err(format!("{} is over {} entries per minute", author, max));
+        }
+    }
+    let last_hour = recent.iter().map(|(_, bytes)| bytes).sum::<u64>();
+    if let Some(max) = limits.max_bytes_per_hour {
+        if last_hour + bytes > max {
+            return Err(format!("{} is over {} bytes per hour", author, max));
+        }
+    }
+    Ok(())
+}
+
+/// the author of what is published with the aspects, the first agent signing a header among
+/// them, if any did
+pub fn author(aspects: &[Aspect]) -> Option<String> {
+    aspects
+        .iter()
+        .filter_map(|aspect| match *aspect {
+            Aspect::Header(ref header) | Aspect::Activity(ref header) => {
+                header.provenances().first()
+            }
+            _ => None,
+        })
+        .map(|provenance| provenance.source().to_string())
+        .next()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::{
+        entry::tests::test_entry, header::tests::test_header, provenance::Provenance,
+    };
+    use holochain_dna::zome::{entry_types::EntryType, Zome};
+
+    fn sized(bytes: usize) -> Entry {
+        Entry::new("test", &"x".repeat(bytes))
+    }
+
+    fn test_buckets() -> Buckets {
+        let bucket = RateBucket {
+            capacity: 10,
+            interval_secs: 60,
+        };
+        let mut buckets = Buckets::new();
+        buckets.insert("vote".to_string(), (4, bucket));
+        buckets
+    }
+
+    #[test]
+    /// authors get so many entries and bytes taken in a window
+    fn limited() {
+        let rates = AuthorRates::default();
+        let limits = AuthorRateLimits {
+            max_entries_per_minute: Some(2),
+            max_bytes_per_hour: Some(100),
+        };
+        let none = Buckets::new();
+        assert_eq!(Ok(()), rates.admit("bob", &sized(10), 0, &limits, &none));
+        assert_eq!(Ok(()), rates.admit("bob", &sized(10), 30, &limits, &none));
+        assert!(rates.admit("bob", &sized(10), 59, &limits, &none).is_err());
+        assert_eq!(Ok(()), rates.admit("carol", &sized(10), 59, &limits, &none));
+        assert_eq!(Ok(()), rates.admit("bob", &sized(10), 60, &limits, &none));
+
+        assert!(rates.admit("bob", &sized(71), 600, &limits, &none).is_err());
+        assert_eq!(Ok(()), rates.admit("bob", &sized(70), 600, &limits, &none));
+        assert!(rates.admit("bob", &sized(1), 3599, &limits, &none).is_err());
+        // the first arrivals are an hour old
+        assert_eq!(Ok(()), rates.admit("bob", &sized(10), 3600, &limits, &none));
+
+        let unlimited = AuthorRates::default();
+        for _ in 0..10 {
+            let limits = AuthorRateLimits::default();
+            assert_eq!(Ok(()), unlimited.admit("bob", &sized(1000), 0, &limits, &none));
+        }
+    }
+
+    #[test]
+    /// entries of a bucketed type take their weight until the bucket is used up for the interval
+    fn bucket_taken() {
+        let buckets = test_buckets();
+        let mut bucket_use = BucketUse::default();
+        assert_eq!(Ok(()), bucket_use.take("vote", &buckets, 0));
+        assert_eq!(Ok(()), bucket_use.take("vote", &buckets, 10));
+        assert_eq!(
+            Err("'vote' entries are over their rate bucket of 10 per 60 seconds".to_string()),
+            bucket_use.take("vote", &buckets, 59)
+        );
+        for _ in 0..10 {
+            assert_eq!(Ok(()), bucket_use.take("post", &buckets, 59));
+        }
+        // the first vote was taken a minute ago
+        assert_eq!(Ok(()), bucket_use.take("vote", &buckets, 60));
+        assert!(bucket_use.take("vote", &buckets, 69).is_err());
+    }
+
+    #[test]
+    /// holders take the entries of each author from their own buckets
+    fn author_buckets() {
+        let rates = AuthorRates::default();
+        let limits = AuthorRateLimits::default();
+        let buckets = test_buckets();
+        let vote = Entry::new("vote", "yes");
+        for _ in 0..2 {
+            assert_eq!(Ok(()), rates.admit("bob", &vote, 0, &limits, &buckets));
+        }
+        assert!(rates.admit("bob", &vote, 0, &limits, &buckets).is_err());
+        assert_eq!(Ok(()), rates.admit("carol", &vote, 0, &limits, &buckets));
+    }
+
+    #[test]
+    /// the buckets are those the entry types of the DNA declare
+    fn dna_buckets() {
+        let mut dna = Dna::new();
+        let mut zome = Zome::default();
+        let mut vote = EntryType::new();
+        vote.name = "vote".to_string();
+        vote.weight = 4;
+        vote.rate_bucket = test_buckets().get("vote").map(|(_, bucket)| bucket.clone());
+        zome.entry_types.push(vote);
+        zome.entry_types.push(EntryType::new());
+        dna.zomes.push(zome);
+        assert_eq!(test_buckets(), buckets(&dna));
+    }
+
+    #[test]
+    /// the author is the first to sign a header published
+    fn published_author() {
+        let signed = test_header().with_provenance(Provenance::new("bob", "signature"));
+        let content = Aspect::Content(test_entry());
+        assert_eq!(None, author(&[content.clone(), Aspect::Header(test_header())]));
+        assert_eq!(
+            Some("bob".to_string()),
+            author(&[content, Aspect::Header(signed)])
+        );
+    }
+}
@@ -0,0 +1,251 @@
+//! validation receipts are what holders send back to the author of what was published to them,
+//! saying they hold it and what came of validating it, so authors can tell how far their commits
+//! got
+//! the author keeps them in a receipt store by entry address, one receipt per holder, which can be
+//! persisted to a JSON file rewritten every time a receipt arrives
+//! holders that prune an entry tell its author, which withdraws their receipt, see dht::retention
+
+use dht::HoldingValidation;
+use error::HolochainError;
+use network::outbox::Outgoing;
+use serde_json;
+use state::State;
+use std::{
+    collections::BTreeMap, fmt, fs, path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// a holder's word that it holds the entry at address
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ValidationReceipt {
+    pub address: String,
+    /// agent holding the entry
+    pub validator: String,
+    pub validation: HoldingValidation,
+    /// seconds since the unix epoch when the validator integrated the entry
+    pub timestamp: u64,
+}
+
+#[derive(Default)]
+struct Receipts {
+    /// receipts by entry address, sorted by validator
+    by_address: BTreeMap<String, Vec<ValidationReceipt>>,
+    /// file the receipts are persisted to, if any
+    path: Option<PathBuf>,
+}
+
+impl Receipts {
+    /// keep the receipt, replacing the one from the same validator for the same address
+    fn add(&mut self, receipt: ValidationReceipt) {
+        let receipts = self.by_address.entry(receipt.address.clone()).or_default();
+        receipts.retain(|kept| kept.validator != receipt.validator);
+        receipts.push(receipt);
+        receipts.sort_by(|a, b| a.validator.cmp(&b.validator));
+    }
+
+    /// forget the receipt of the validator for the entry at address, if any
+    fn withdraw(&mut self, address: &str, validator: &str) {
+        let mut emptied = false;
+        if let Some(receipts) = self.by_address.get_mut(address) {
+            receipts.retain(|kept| kept.validator != validator);
+            emptied = receipts.is_empty();
+        }
+        if emptied {
+            self.by_address.remove(address);
+        }
+    }
+
+    fn save(&self) -> Result<(), HolochainError> {
+        match self.path {
+            Some(ref path) => {
+                let json = serde_json::to_string(&self.by_address)
+                    .map_err(|e| HolochainError::new(&e.to_string()))?;
+                fs::write(path, json).map_err(|e| HolochainError::new(&e.to_string()))
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// the validation receipts an author received
+/// the store is a cheap handle, clones share the same receipts
+#[derive(Clone, Default)]
+pub struct ReceiptStore {
+    receipts: Arc<Mutex<Receipts>>,
+}
+
+impl PartialEq for ReceiptStore {
+    fn eq(&self, other: &ReceiptStore) -> bool {
+        Arc::ptr_eq(&self.receipts, &other.receipts)
+    }
+}
+
+impl fmt::Debug for ReceiptStore {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let receipts = self.receipts.lock().unwrap();
+        f.debug_struct("ReceiptStore")
+            .field("addresses", &receipts.by_address.len())
+            .field("path", &receipts.path)
+            .finish()
+    }
+}
+
+impl ReceiptStore {
+    /// persist the receipts to the file from now on, adding the ones kept in it
+    pub fn persist(&self, path: &Path) -> Result<(), HolochainError> {
+        let persisted: BTreeMap<String, Vec<ValidationReceipt>> = if path.exists() {
+            let json = fs::read_to_string(path).map_err(|e| HolochainError::new(&e.to_string()))?;
+            serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))?
+        } else {
+            BTreeMap::new()
+        };
+        let mut receipts = self.receipts.lock().unwrap();
+        for receipt in persisted.into_values().flatten() {
+            receipts.add(receipt);
+        }
+        receipts.path = Some(path.to_path_buf());
+        receipts.save()
+    }
+
+    /// keep a receipt, it is kept even if it can't be persisted, the error says why it wasn't
+    pub fn add(&self, receipt: ValidationReceipt) -> Result<(), HolochainError> {
+        let mut receipts = self.receipts.lock().unwrap();
+        receipts.add(receipt);
+        receipts.save()
+    }
+
+    /// forget the receipt of the validator for the entry at address, e.g. once the validator
+    /// pruned it, the error says why that couldn't be persisted
+    pub fn withdraw(&self, address: &str, validator: &str) -> Result<(), HolochainError> {
+        let mut receipts = self.receipts.lock().unwrap();
+        receipts.withdraw(address, validator);
+        receipts.save()
+    }
+
+    /// the receipts for the entry at address, by validator
+    pub fn receipts(&self, address: &str) -> Vec<ValidationReceipt> {
+        self.receipts
+            .lock()
+            .unwrap()
+            .by_address
+            .get(address)
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+/// how far the publish of an entry got
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PublishStatus {
+    pub address: String,
+    /// true while a publish of the entry waits in the outbox
+    pub pending: bool,
+    /// how many holders sent a receipt for it
+    pub receipts: usize,
+}
+
+/// how far the publish of the entry at address got for the instance with the state
+pub fn publish_status(state: &State, address: &str) -> PublishStatus {
+    let nucleus = state.nucleus();
+    let pending = nucleus
+        .outbox()
+        .pending()
+        .iter()
+        .any(|outgoing| match *outgoing {
+            Outgoing::Publish(ref published, _) => published == address,
+            Outgoing::Message(..) => false,
+        });
+    PublishStatus {
+        address: address.to_string(),
+        pending,
+        receipts: nucleus.receipts().receipts(address).len(),
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use hash_table::entry::tests::test_entry;
+    use network::outbox::tests::test_publish;
+    use std::{env, process};
+
+    /// receipt from the validator for test_entry
+    pub fn test_receipt(validator: &str) -> ValidationReceipt {
+        ValidationReceipt {
+            address: test_entry().key(),
+            validator: validator.to_string(),
+            validation: HoldingValidation::Unvalidated,
+            timestamp: 1,
+        }
+    }
+
+    #[test]
+    /// one receipt is kept per validator
+    fn add() {
+        let store = ReceiptStore::default();
+        store.add(test_receipt("bob")).unwrap();
+        store.add(test_receipt("alice")).unwrap();
+        let valid = ValidationReceipt {
+            validation: HoldingValidation::Valid,
+            ..test_receipt("bob")
+        };
+        store.add(valid.clone()).unwrap();
+        assert_eq!(
+            vec![test_receipt("alice"), valid],
+            store.receipts(&test_entry().key())
+        );
+        assert!(store.receipts("nowhere").is_empty());
+    }
+
+    #[test]
+    /// holders that pruned an entry no longer count
+    fn withdraw() {
+        let store = ReceiptStore::default();
+        store.add(test_receipt("bob")).unwrap();
+        store.add(test_receipt("carol")).unwrap();
+        store.withdraw(&test_entry().key(), "bob").unwrap();
+        assert_eq!(vec![test_receipt("carol")], store.receipts(&test_entry().key()));
+        store.withdraw(&test_entry().key(), "carol").unwrap();
+        store.withdraw("nowhere", "carol").unwrap();
+        assert!(store.receipts(&test_entry().key()).is_empty());
+    }
+
+    #[test]
+    /// receipts survive in their file
+    fn persist() {
+        let path = env::temp_dir().join(format!("holochain_receipts_test_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        let store = ReceiptStore::default();
+        store.persist(&path).unwrap();
+        store.add(test_receipt("bob")).unwrap();
+
+        let restarted = ReceiptStore::default();
+        restarted.add(test_receipt("alice")).unwrap();
+        restarted.persist(&path).unwrap();
+        assert_eq!(2, restarted.receipts(&test_entry().key()).len());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(ReceiptStore::default().persist(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    /// the status counts receipts and tells whether the publish is still waiting
+    fn status() {
+        let state = State::new();
+        let address = test_entry().key();
+        let status = |state: &State| publish_status(state, &address);
+        assert!(!status(&state).pending);
+
+        state.nucleus().outbox().queue(test_publish()).unwrap();
+        state.nucleus().receipts().add(test_receipt("bob")).unwrap();
+        assert_eq!(
+            PublishStatus {
+                address: address.clone(),
+                pending: true,
+                receipts: 1,
+            },
+            status(&state)
+        );
+    }
+}
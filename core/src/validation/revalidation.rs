@@ -0,0 +1,147 @@
+//! revalidation checks what a node holds again once the rules it was validated by change, e.g.
+//! when a DNA is reloaded during development with new validation code or properties
+//! held entries that fail under the new rules are quarantined rather than dropped, so they can be
+//! looked into and are released again should the rules change back
+//! entries found invalid before stay invalid, they failed under the rules they arrived with
+
+use agent::membrane::{self, AgentId};
+use dht::{self, DhtState, HoldingValidation};
+use holochain_dna::Dna;
+use instance::Observer;
+use state;
+use std::{
+    collections::BTreeMap, sync::{mpsc::Sender, Arc},
+};
+use validation::{
+    links::{link_validator, EntryLookup}, ValidationItem, Validator,
+};
+
+/// what changed when held entries were validated again
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RevalidationReport {
+    /// how many held entries were validated again
+    pub checked: usize,
+    /// addresses of the entries newly failing, with the reason
+    pub quarantined: BTreeMap<String, String>,
+    /// addresses of quarantined entries passing again, sorted
+    pub released: Vec<String>,
+}
+
+impl RevalidationReport {
+    /// the actions recording the changes in the DHT state
+    pub fn actions(&self) -> Vec<dht::Action> {
+        let quarantined = self.quarantined.iter().map(|(address, reason)| {
+            dht::Action::SetValidation(
+                address.clone(),
+                HoldingValidation::Quarantined(reason.clone()),
+            )
+        });
+        let released = self
+            .released
+            .iter()
+            .map(|address| dht::Action::SetValidation(address.clone(), HoldingValidation::Valid));
+        quarantined.chain(released).collect()
+    }
+}
+
+/// run every held entry through the validator, reporting the entries whose validation changed
+pub fn revalidate(dht: &DhtState, validator: &Validator) -> RevalidationReport {
+    let mut report = RevalidationReport::default();
+    for address in dht.held_addresses() {
+        let (entry, integration) = match (dht.holding(&address), dht.integration(&address)) {
+            (Some(entry), Some(integration)) => (entry, integration),
+            _ => continue,
+        };
+        report.checked += 1;
+        let item = ValidationItem::new(&entry, Vec::new());
+        match (validator(&item), integration.validation) {
+            (Ok(()), HoldingValidation::Quarantined(_)) => report.released.push(address),
+            (Err(reason), HoldingValidation::Unvalidated)
+            | (Err(reason), HoldingValidation::Valid) => {
+                report.quarantined.insert(address, reason);
+            }
+            _ => (),
+        }
+    }
+    report
+}
+
+/// Validator for what a node holds under the rules of the DNA: link entries against its link
+/// declarations and, if the DNA gates agents, AgentId entries through validate_agent
+/// validate_agent is a zome call, so the validator has to run off the action loop
+pub fn dna_validator(
+    dna: Dna,
+    lookup: EntryLookup,
+    action_channel: &Sender<state::ActionWrapper>,
+    observer_channel: &Sender<Observer>,
+) -> Validator {
+    let links = link_validator(dna.clone(), lookup);
+    let gated = membrane::is_gated(&dna);
+    let action_channel = action_channel.clone();
+    let observer_channel = observer_channel.clone();
+    Arc::new(move |item: &ValidationItem| {
+        links(item)?;
+        match AgentId::from_entry(&item.entry) {
+            Some(ref agent_id) if gated => {
+                membrane::validate_agent(&dna, agent_id, &action_channel, &observer_channel)
+            }
+            _ => Ok(()),
+        }
+    })
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::entry::tests::{test_entry_a, test_entry_b};
+
+    #[test]
+    /// newly failing entries are quarantined and released once they pass again
+    fn quarantined_and_released() {
+        let a = test_entry_a().key();
+        let b = test_entry_b().key();
+        let mut dht = test_reduce(test_dht_state(), Action::Hold(test_entry_a()));
+        dht = test_reduce(dht, Action::Hold(test_entry_b()));
+        let refuse_a: Validator = Arc::new(move |item: &ValidationItem| {
+            if item.address == test_entry_a().key() {
+                Err("no more a".to_string())
+            } else {
+                Ok(())
+            }
+        });
+
+        let report = revalidate(&dht, &refuse_a);
+        assert_eq!(2, report.checked);
+        assert_eq!(Some(&"no more a".to_string()), report.quarantined.get(&a));
+        assert!(report.released.is_empty());
+        for action in report.actions() {
+            dht = test_reduce(dht, action);
+        }
+        assert_eq!(
+            HoldingValidation::Quarantined("no more a".to_string()),
+            dht.integration(&a).unwrap().validation
+        );
+        assert_eq!(Some(test_entry_a()), dht.holding(&a));
+        // nothing changes while the rules stay the same
+        let unchanged = revalidate(&dht, &refuse_a);
+        assert!(unchanged.quarantined.is_empty() && unchanged.released.is_empty());
+
+        let accept: Validator = Arc::new(|_: &ValidationItem| Ok(()));
+        let report = revalidate(&dht, &accept);
+        assert!(report.quarantined.is_empty());
+        assert_eq!(vec![a.clone()], report.released);
+        assert_eq!(
+            vec![Action::SetValidation(a, HoldingValidation::Valid)],
+            report.actions()
+        );
+
+        // entries found invalid before stay so
+        let invalid = HoldingValidation::Invalid("bad".to_string());
+        dht = test_reduce(dht, Action::SetValidation(b.clone(), invalid));
+        let refuse: Validator = Arc::new(|_: &ValidationItem| Err("no".to_string()));
+        assert!(!revalidate(&dht, &refuse).quarantined.contains_key(&b));
+    }
+}
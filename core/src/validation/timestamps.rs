@@ -0,0 +1,141 @@
+//! the times of the headers of a chain only move forward, and no header is timed ahead of the
+//! clock of the node checking it, both within the skew clocks are allowed, see
+//! AgentState::max_clock_skew_secs()
+//! commits breaking the order fail, aspects published with headers breaking either are refused
+//! like those of warranted authors, see network::direct_message
+//! headers are timed by the clock of the node writing them, see Header::link(), those without a
+//! time, or one that isn't ISO8601, fail both checks
+
+use dht::{aspect::Aspect, DhtState};
+use hash_table::header::Header;
+
+/// seconds clocks are allowed to be apart by default
+pub const DEFAULT_MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// seconds since the unix epoch the header is timed at, failing for headers without a time
+fn timestamp(header: &Header) -> Result<u64, String> {
+    header
+        .timestamp()
+        .ok_or_else(|| format!("header {} has no time", header.hash()))
+}
+
+/// check the header isn't timed before the header it follows by more than skew_secs
+pub fn check_order(header: &Header, previous: &Header, skew_secs: u64) -> Result<(), String> {
+    if timestamp(header)? + skew_secs < timestamp(previous)? {
+        Err(format!(
+            "header {} is timed {} before the header it follows, at {}",
+            header.hash(),
+            header.time(),
+            previous.time()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// check the header isn't timed after now by more than skew_secs
+pub fn check_not_ahead(header: &Header, now: u64, skew_secs: u64) -> Result<(), String> {
+    if timestamp(header)? > now + skew_secs {
+        Err(format!(
+            "header {} is timed {}, ahead of the clock",
+            header.hash(),
+            header.time()
+        ))
+    } else {
+        Ok(())
+    }
+}
+
+/// check the headers of the aspects published at address against now and, for the activity of
+/// the agent at address, against the headers they follow, held or published along
+pub fn check_aspects(
+    dht: &DhtState,
+    address: &str,
+    aspects: &[Aspect],
+    now: u64,
+    skew_secs: u64,
+) -> Result<(), String> {
+    let mut activity = None;
+    for aspect in aspects {
+        match *aspect {
+            Aspect::Header(ref header) => check_not_ahead(header, now, skew_secs)?,
+            Aspect::Activity(ref header) => {
+                check_not_ahead(header, now, skew_secs)?;
+                let held = activity.get_or_insert_with(|| {
+                    let mut held = dht.activity(address);
+                    held.extend(aspects.iter().filter_map(|aspect| match *aspect {
+                        Aspect::Activity(ref header) => Some(header.clone()),
+                        _ => None,
+                    }));
+                    held
+                });
+                let previous = header
+                    .next()
+                    .and_then(|next| held.iter().find(|held| held.signed_hash() == next));
+                if let Some(previous) = previous {
+                    check_order(header, previous, skew_secs)?;
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::tests::{test_dht_state, test_reduce};
+    use hash_table::{
+        entry::Entry, header::tests::{test_header, test_untimed_header},
+        provenance::tests::{test_agent_address, test_signing_keys},
+    };
+
+    /// the first header and the one following it, timed at first and second
+    fn test_timed_headers(first: u64, second: u64) -> (Header, Header) {
+        let previous = Header::link(&Entry::new("post", "first"), None, None).with_time(first);
+        let header = Header::link(&Entry::new("post", "second"), Some(previous.hash()), None)
+            .with_time(second);
+        (previous, header)
+    }
+
+    #[test]
+    /// headers can go back in time by the skew at most
+    fn order() {
+        let (previous, header) = test_timed_headers(1000, 1000);
+        assert_eq!(Ok(()), check_order(&header, &previous, 0));
+        let (previous, header) = test_timed_headers(1000, 940);
+        assert_eq!(Ok(()), check_order(&header, &previous, 60));
+        assert!(check_order(&header, &previous, 59).is_err());
+        // headers without a time fail
+        assert!(check_order(&test_untimed_header(), &previous, 0).is_err());
+        assert!(check_order(&header, &test_untimed_header(), 60).is_err());
+    }
+
+    #[test]
+    /// headers can be ahead of the clock by the skew at most
+    fn ahead() {
+        let header = test_header().with_time(1060);
+        assert_eq!(Ok(()), check_not_ahead(&header, 1000, 60));
+        assert!(check_not_ahead(&header, 1000, 59).is_err());
+        assert!(check_not_ahead(&test_untimed_header(), 1000, 60).is_err());
+    }
+
+    #[test]
+    /// activity is checked against the headers it follows, held or published along
+    fn aspects() {
+        let alice = test_agent_address("alice");
+        let (previous, header) = test_timed_headers(1000, 900);
+        let activity = |header: &Header| Aspect::Activity(header.sign(&test_signing_keys("alice")));
+        let dht = test_dht_state();
+        assert!(check_aspects(&dht, &alice, &[activity(&previous), activity(&header)], 1000, 60)
+            .is_err());
+        assert_eq!(Ok(()), check_aspects(&dht, &alice, &[activity(&header)], 1000, 60));
+        let dht = test_reduce(dht, ::dht::Action::HoldAspect(alice.clone(), activity(&previous)));
+        assert!(check_aspects(&dht, &alice, &[activity(&header)], 1000, 60).is_err());
+        assert_eq!(Ok(()), check_aspects(&dht, &alice, &[activity(&header)], 1000, 100));
+
+        let ahead = Aspect::Header(test_header().with_time(2000));
+        assert!(check_aspects(&dht, "Qm", &[ahead], 1000, 60).is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! warrants are evidence that an agent forked its chain: two different headers it signed on top
+//! of the same header, i.e. claiming the same index, see dht::activity
+//! nodes holding the activity of an agent check every header they hold against the others, warrant
+//! the forks they find and send the warrants to every peer they know, nodes holding a warrant of
+//! an agent refuse what it publishes from then on
+//! a warrant is checked on its own, without the chain, so any node can hold it wherever it
+//! arrives, only headers the agent signed count as evidence since unsigned ones could come from
+//! anyone
+
+use dht::{aspect::Aspect, subscriptions::held_since, DhtState};
+use hash_table::header::Header;
+use network::direct_message::DirectMessage;
+use state::{self, State};
+use std::sync::mpsc::Sender;
+
+/// evidence that the agent forked its chain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Warrant {
+    pub agent: String,
+    /// the headers the agent signed on top of the same one, ordered by signed hash so every node
+    /// warranting the fork issues the same warrant
+    pub headers: (Header, Header),
+}
+
+impl Warrant {
+    /// the warrant of the agent for the two headers if they are evidence of a fork
+    pub fn fork(agent: &str, a: &Header, b: &Header) -> Option<Warrant> {
+        let headers = if a.signed_hash() <= b.signed_hash() {
+            (a.clone(), b.clone())
+        } else {
+            (b.clone(), a.clone())
+        };
+        let warrant = Warrant {
+            agent: agent.to_string(),
+            headers,
+        };
+        if warrant.is_valid() {
+            Some(warrant)
+        } else {
+            None
+        }
+    }
+
+    /// true if the headers are different, on top of the same header and signed by the agent
+    /// signatures are verified, forged ones would warrant agents who never forked
+    /// headers are compared without their provenances, anyone can add theirs to a header the
+    /// agent signed and that doesn't make it a different one
+    pub fn is_valid(&self) -> bool {
+        let (ref a, ref b) = self.headers;
+        let signed = |header: &Header| {
+            header
+                .provenances()
+                .first()
+                .map(|provenance| provenance.source() == self.agent)
+                .unwrap_or(false) && header.verify_provenances()
+        };
+        a.signed_hash() != b.signed_hash() && a.next() == b.next() && signed(a) && signed(b)
+    }
+}
+
+/// the warrants of the forks revealed by the activity new holds that old didn't, leaving out the
+/// ones already held
+pub fn detect(old: &DhtState, new: &DhtState) -> Vec<Warrant> {
+    let mut warrants = Vec::new();
+    for (agent, aspect) in held_since(old, new) {
+        let header = match aspect {
+            Aspect::Activity(header) => header,
+            _ => continue,
+        };
+        let held = new.warrants(&agent);
+        for other in new.activity(&agent) {
+            if other.next() != header.next() {
+                continue;
+            }
+            if let Some(warrant) = Warrant::fork(&agent, &header, &other) {
+                if !held.contains(&warrant) && !warrants.contains(&warrant) {
+                    warrants.push(warrant);
+                }
+            }
+        }
+    }
+    warrants
+}
+
+/// warrant the forks the activity held since old reveals, holding the warrants and sending them
+/// to every peer known, returns the warrants issued
+/// peers that can't be reached right away miss out, the nodes holding the activity warrant the
+/// fork themselves
+pub fn issue(
+    state: &State,
+    old: &DhtState,
+    action_channel: &Sender<state::ActionWrapper>,
+) -> Vec<Warrant> {
+    let dht = state.dht();
+    let warrants = detect(old, &dht);
+    let messenger = state.nucleus().messenger().clone();
+    let me = messenger.address();
+    for warrant in &warrants {
+        let address = warrant.agent.clone();
+        let aspect = Aspect::Warrant(warrant.clone());
+        ::instance::dispatch_action(
+            action_channel,
+            state::Action::Dht(::dht::Action::HoldAspect(address.clone(), aspect.clone())),
+        );
+        let me = match me {
+            Some(ref me) => me,
+            None => continue,
+        };
+        for peer in dht.peers().into_iter().filter(|peer| peer.id != *me) {
+            let publish = DirectMessage::Publish {
+                from: me.clone(),
+                address: address.clone(),
+                aspects: vec![aspect.clone()],
+            };
+            let _ = messenger.send(&peer.id, publish);
+        }
+    }
+    warrants
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use dht::{
+        activity::tests::test_activity_headers, tests::{test_dht_state, test_reduce}, Action,
+    };
+    use hash_table::{
+        entry::Entry, provenance::{tests::{test_agent_address, test_signing_keys}, Provenance},
+    };
+
+    /// two headers alice signed on top of the same one, see test_agent_address()
+    pub fn test_fork() -> (Header, Header) {
+        let first = test_activity_headers(1).remove(0);
+        let signed = |entry: &Entry| {
+            Header::link(entry, Some(first.hash()), None).sign(&test_signing_keys("alice"))
+        };
+        (
+            signed(&Entry::new("post", "hello")),
+            signed(&Entry::new("post", "goodbye")),
+        )
+    }
+
+    fn hold(dht: DhtState, agent: &str, header: &Header) -> DhtState {
+        let aspect = Aspect::Activity(header.clone());
+        test_reduce(dht, Action::HoldAspect(agent.to_string(), aspect))
+    }
+
+    #[test]
+    /// only different headers the agent signed on top of the same one are evidence
+    fn fork() {
+        let alice = test_agent_address("alice");
+        let (a, b) = test_fork();
+        let warrant = Warrant::fork(&alice, &a, &b).unwrap();
+        assert_eq!(Some(warrant.clone()), Warrant::fork(&alice, &b, &a));
+        assert!(warrant.is_valid());
+
+        assert_eq!(None, Warrant::fork(&alice, &a, &a));
+        assert_eq!(None, Warrant::fork(&test_agent_address("bob"), &a, &b));
+        let unsigned = test_activity_headers(2);
+        let on_top = Header::link(&Entry::new("post", "other"), Some(unsigned[0].hash()), None);
+        assert_eq!(None, Warrant::fork(&alice, &unsigned[1], &on_top));
+        let elsewhere = test_activity_headers(1)[0].sign(&test_signing_keys("alice"));
+        assert_eq!(None, Warrant::fork(&alice, &a, &elsewhere));
+
+        // bob framing alice with headers signed in alice's name
+        let framed = |entry: &Entry| {
+            let header = Header::link(entry, Some(unsigned[0].hash()), None);
+            let bobs = header.sign(&test_signing_keys("bob")).provenances()[0].clone();
+            header.with_provenance(Provenance::new(&alice, bobs.signature()))
+        };
+        let (c, d) = (framed(&Entry::new("post", "hi")), framed(&Entry::new("post", "bye")));
+        assert_eq!(None, Warrant::fork(&alice, &c, &d));
+
+        // bob countersigning one of alice's headers doesn't fork it
+        let countersigned = a.sign(&test_signing_keys("bob"));
+        assert_ne!(a.hash(), countersigned.hash());
+        assert_eq!(None, Warrant::fork(&alice, &a, &countersigned));
+    }
+
+    #[test]
+    /// forks are warranted once, when the second header is held
+    fn detect_forks() {
+        let alice = test_agent_address("alice");
+        let (a, b) = test_fork();
+        let dht = hold(test_dht_state(), &alice, &a);
+        assert!(detect(&test_dht_state(), &dht).is_empty());
+
+        let forked = hold(dht.clone(), &alice, &b);
+        let warrants = detect(&dht, &forked);
+        assert_eq!(vec![Warrant::fork(&alice, &a, &b).unwrap()], warrants);
+
+        let aspect = Aspect::Warrant(warrants[0].clone());
+        let warranted = test_reduce(forked, Action::HoldAspect(alice.clone(), aspect));
+        assert_eq!(warrants, warranted.warrants(&alice));
+        assert!(detect(&dht, &warranted).is_empty());
+    }
+
+    #[test]
+    /// warrants are held at the address of the agent they are about, if they are valid
+    fn held_at_agent() {
+        let alice = test_agent_address("alice");
+        let (a, b) = test_fork();
+        let warrant = Warrant::fork(&alice, &a, &b).unwrap();
+        assert!(Aspect::Warrant(warrant.clone()).belongs_at(&alice));
+        assert!(!Aspect::Warrant(warrant.clone()).belongs_at(&test_agent_address("bob")));
+        let forged = Warrant {
+            agent: alice.clone(),
+            headers: (a.clone(), a),
+        };
+        assert!(!Aspect::Warrant(forged).belongs_at(&alice));
+    }
+}
@@ -0,0 +1,166 @@
+//! the agents a container hosts, created at runtime through the admin API so a hosting provider
+//! can onboard users without editing the config and restarting, see Container::create_agent()
+//! every agent has a keystore of its own, see holochain_core::agent::keystore, whose seed is kept
+//! in a file named after the agent in the agents directory of the storage root, so agents last
+//! across restarts and the instances attached to an agent derive its keys
+//! the seed is encrypted with the agent's own passphrase, the keystore is only unlocked while an
+//! instance of the agent runs, so the agents are listed from an index next to the keystores
+
+use holochain_core::{agent::keystore::Keystore, error::HolochainError};
+use serde_json;
+use std::{
+    collections::BTreeMap, fs, path::{Path, PathBuf},
+};
+
+/// directory under the storage root the keystores of the agents are kept in
+pub const AGENTS_DIR: &str = "agents";
+
+/// path the public key agents are listed with is derived along, see Keystore::derive_key()
+pub const AGENT_KEY_PATH: &str = "agent";
+
+/// file in the agents directory listing the agents, see AgentInfo
+pub const AGENTS_INDEX: &str = "agents.json";
+
+/// an agent hosted by the container
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AgentInfo {
+    /// the address of the agent on the network
+    pub name: String,
+    /// base58 public key derived along AGENT_KEY_PATH
+    pub public_key: String,
+}
+
+/// names are file names and network addresses, so they are kept to unreserved characters
+fn check_name(name: &str) -> Result<(), HolochainError> {
+    let unreserved = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if !name.is_empty() && name.chars().all(unreserved) {
+        Ok(())
+    } else {
+        Err(HolochainError::new(&format!(
+            "agent name '{}' has to be letters, digits, '-' and '_'",
+            name
+        )))
+    }
+}
+
+/// the agents of a container by name, with the directory their keystores are kept in
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AgentRegistry {
+    /// agents can only be created with a directory to keep their keys in
+    dir: Option<PathBuf>,
+    agents: BTreeMap<String, AgentInfo>,
+}
+
+impl AgentRegistry {
+    /// the agents listed in the index in dir, none if it doesn't exist yet, it is created with
+    /// the first agent
+    pub fn load(dir: &Path) -> Result<AgentRegistry, HolochainError> {
+        let mut registry = AgentRegistry {
+            dir: Some(dir.to_path_buf()),
+            agents: BTreeMap::new(),
+        };
+        let index = dir.join(AGENTS_INDEX);
+        if !index.exists() {
+            return Ok(registry);
+        }
+        let json = fs::read_to_string(index).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let agents: Vec<AgentInfo> =
+            serde_json::from_str(&json).map_err(|e| HolochainError::new(&e.to_string()))?;
+        for info in agents {
+            check_name(&info.name)?;
+            registry.agents.insert(info.name.clone(), info);
+        }
+        Ok(registry)
+    }
+
+    /// the file the keystore of the agent is kept in
+    pub fn keystore_path(&self, name: &str) -> Option<PathBuf> {
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.keystore", name)))
+    }
+
+    fn save(&self, dir: &Path) -> Result<(), HolochainError> {
+        let json = serde_json::to_string_pretty(&self.agents())
+            .map_err(|e| HolochainError::new(&e.to_string()))?;
+        fs::write(dir.join(AGENTS_INDEX), json).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+
+    /// create an agent with a fresh keystore, encrypted with the passphrase
+    /// fails if the name is taken or not a valid name, or there is no directory for the keys
+    pub fn create(&mut self, name: &str, passphrase: &str) -> Result<AgentInfo, HolochainError> {
+        check_name(name)?;
+        if self.agents.contains_key(name) {
+            return Err(HolochainError::new(&format!(
+                "there already is an agent '{}'",
+                name
+            )));
+        }
+        let path = self.keystore_path(name).ok_or_else(|| {
+            HolochainError::new("the container has no storage root to keep agent keys in")
+        })?;
+        let dir = path.parent().unwrap_or_else(|| Path::new("")).to_path_buf();
+        fs::create_dir_all(&dir).map_err(|e| HolochainError::new(&e.to_string()))?;
+        let keystore = Keystore::default();
+        let info = AgentInfo {
+            name: name.to_string(),
+            public_key: keystore.derive_key(AGENT_KEY_PATH)?.to_base58(),
+        };
+        keystore.persist_encrypted(&path, passphrase)?;
+        self.agents.insert(name.to_string(), info.clone());
+        self.save(&dir)?;
+        Ok(info)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&AgentInfo> {
+        self.agents.get(name)
+    }
+
+    /// all agents, sorted by name
+    pub fn agents(&self) -> Vec<AgentInfo> {
+        self.agents.values().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use sandbox::tests::test_root;
+
+    #[test]
+    fn can_create_agents() {
+        let dir = test_root("agents").join(AGENTS_DIR);
+        let mut registry = AgentRegistry::load(&dir).unwrap();
+        assert!(registry.agents().is_empty());
+
+        let bob = registry.create("bob", "bob's passphrase").unwrap();
+        let alice = registry.create("alice", "alice's passphrase").unwrap();
+        assert_ne!(alice.public_key, bob.public_key);
+        assert_eq!(vec![alice.clone(), bob.clone()], registry.agents());
+        assert_eq!(Some(&bob), registry.get("bob"));
+        assert!(registry.create("bob", "bob's passphrase").is_err());
+
+        // the agents are still there after a restart, each keystore opens with its own passphrase
+        assert_eq!(
+            vec![alice, bob.clone()],
+            AgentRegistry::load(&dir).unwrap().agents()
+        );
+        let path = registry.keystore_path("bob").unwrap();
+        let keystore = Keystore::default();
+        assert!(keystore.persist_encrypted(&path, "alice's passphrase").is_err());
+        keystore.persist_encrypted(&path, "bob's passphrase").unwrap();
+        assert_eq!(
+            bob.public_key,
+            keystore.derive_key(AGENT_KEY_PATH).unwrap().to_base58()
+        );
+    }
+
+    #[test]
+    fn can_not_create_agents_without_dir_or_name() {
+        assert!(AgentRegistry::default().create("bob", "secret").is_err());
+        let mut registry = AgentRegistry::load(&test_root("agent_names")).unwrap();
+        for name in &["", "../bob", "bob.json", "bob carol"] {
+            assert!(registry.create(name, "secret").is_err(), "{}", name);
+        }
+    }
+}
@@ -0,0 +1,612 @@
+//! configuration for container applications running a set of holochain instances
+//! configs are JSON, e.g.
+//!
+//! ```json
+//! {
+//!     "storage_root": "/var/lib/holochain",
+//!     "network": {
+//!         "direct_message_timeout_ms": 5000,
+//!         "retry": { "attempts": 5, "initial_delay_ms": 100, "backoff": "exponential" }
+//!     },
+//!     "watchdog": { "heartbeat_timeout_ms": 5000, "call_budget_ms": 30000 },
+//!     "dna_sources": ["https://store.example.org/dna/"],
+//!     "interfaces": [
+//!         {
+//!             "id": "ui",
+//!             "auth": { "type": "token", "tokens": ["f9c3d1e0"] },
+//!             "instances": ["app"],
+//!             "allowed_origins": ["https://app.example.org"],
+//!             "rate_limit": { "calls_per_second": 20, "burst": 50 },
+//!             "encodings": ["msgpack"]
+//!         },
+//!         {
+//!             "id": "admin",
+//!             "admin": true,
+//!             "auth": { "type": "challenge", "secret": "7b2e44a1" }
+//!         },
+//!         {
+//!             "id": "backend",
+//!             "driver": "grpc",
+//!             "auth": { "type": "token", "tokens": ["5d0a7c3b"] }
+//!         }
+//!     ],
+//!     "instances": [
+//!         {
+//!             "id": "app",
+//!             "dna": "app.hcpkg",
+//!             "agent": "bob",
+//!             "storage": "file:",
+//!             "logging": {
+//!                 "level": "warn",
+//!                 "zomes": { "blog": "debug" }
+//!             },
+//!             "limits": {
+//!                 "max_wasm_pages": 256,
+//!                 "max_chain_bytes": 104857600,
+//!                 "max_dht_bytes": 1073741824,
+//!                 "max_concurrent_reads": 16,
+//!                 "max_outbox_depth": 10000
+//!             }
+//!         },
+//!         { "id": "team", "dna": "app.hcpkg", "uuid": "4b1c9e02", "agent": "bob" }
+//!     ]
+//! }
+//! ```
+//!
+//! storage is "memory:", "file:" or the scheme of a backend registered with the StorageRegistry,
+//! "lmdb:" and "pickle:" are refused until they exist, see storage
+//! with a storage root, storage without a path is kept in a directory derived from the DNA hash
+//! and agent of the instance, and configured paths must stay inside the root, see sandbox
+//! instances can also reference their DNA by hash, as "hash:" followed by it, and it is fetched
+//! from the dna_sources and checked to hash to it, see holochain_dna::resolver
+//! the network section applies to every instance, see Holochain::set_network_config()
+//! an instance with a uuid runs its DNA with it instead, so the same code forms a network of its
+//! own, like "team" above
+//! interfaces authenticate every connection, see interface, and speak JSON-RPC on a WebSocket
+//! unless their driver is "grpc", like "backend" above, see holochain_core_api_grpc
+//! the watchdog section says when instances count as wedged, see watchdog
+
+use holochain_agent::Agent;
+use holochain_dna::resolver::DnaResolver;
+use holochain_core::{
+    context::Context, error::HolochainError, limits::ResourceLimits,
+    logger::{LogLevel, SimpleLogger, ZomeLogger}, network::config::NetworkConfig, platform,
+};
+use interface::InterfaceConfiguration;
+use serde_json;
+use sandbox;
+use std::{
+    collections::{HashMap, HashSet}, path::{Path, PathBuf}, sync::{Arc, Mutex},
+};
+use storage::{StorageRegistry, StorageUri, STORAGE_DEFAULT_URI};
+use watchdog::WatchdogConfig;
+
+fn default_storage() -> String {
+    STORAGE_DEFAULT_URI.to_string()
+}
+
+/// top level container configuration
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Configuration {
+    /// directory every instance keeps its storage in, the data directory of the platform if not
+    /// set, storage is not sandboxed if the platform has none either
+    #[serde(default)]
+    pub storage_root: Option<String>,
+    /// timeouts and retries of the instances' network traffic
+    #[serde(default)]
+    pub network: NetworkConfig,
+    /// when instances count as wedged and are restarted
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    /// how clients reach the instances, there are none if not set
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceConfiguration>,
+    /// where DNAs instances reference by hash are fetched from, in order, see
+    /// holochain_dna::resolver
+    #[serde(default)]
+    pub dna_sources: Vec<String>,
+    #[serde(default)]
+    pub instances: Vec<InstanceConfiguration>,
+}
+
+impl Configuration {
+    /// parse a configuration from JSON and check it is consistent
+    pub fn from_json(json: &str) -> Result<Configuration, HolochainError> {
+        let config: Configuration =
+            serde_json::from_str(json).map_err(|e| HolochainError::new(&e.to_string()))?;
+        config.check_consistency()?;
+        Ok(config)
+    }
+
+    /// resolver for the DNAs instances reference by hash, asking the dna_sources and caching what
+    /// it fetched in the dna directory of the storage root
+    pub fn dna_resolver(&self) -> DnaResolver {
+        let cache_dir = self.root().map(|root| root.join("dna"));
+        let mut resolver = DnaResolver::new(cache_dir.as_deref());
+        for source in &self.dna_sources {
+            resolver.add_source(source);
+        }
+        resolver
+    }
+
+    /// the storage root, the data directory of the platform if none is configured
+    pub(crate) fn root(&self) -> Option<PathBuf> {
+        self.storage_root
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| platform::current().data_dir())
+    }
+
+    /// the configuration of the instance with the given id
+    pub fn instance(&self, id: &str) -> Option<&InstanceConfiguration> {
+        self.instances.iter().find(|instance| instance.id == id)
+    }
+
+    /// the configuration of the interface with the given id
+    pub fn interface(&self, id: &str) -> Option<&InterfaceConfiguration> {
+        self.interfaces.iter().find(|interface| interface.id == id)
+    }
+
+    /// checks that the network and watchdog settings make sense, instance ids are unique, storage
+    /// URIs are well formed and no two instances are configured to use the same storage, and
+    /// that interfaces have unique ids, credentials, well formed origins and only expose
+    /// configured instances
+    pub fn check_consistency(&self) -> Result<(), HolochainError> {
+        self.network.check()?;
+        self.watchdog.check()?;
+        self.check_interfaces()?;
+        let mut ids = HashSet::new();
+        let mut storage = HashMap::new();
+        for instance in &self.instances {
+            if !ids.insert(instance.id.clone()) {
+                return Err(HolochainError::new(&format!(
+                    "instance id '{}' is used more than once",
+                    instance.id
+                )));
+            }
+            let uri = StorageUri::parse(&instance.storage)?;
+            uri.check_supported()?;
+            if uri.scheme == "memory" {
+                continue;
+            }
+            // storage derived from the DNA and agent is only shared by instances running the same
+            // DNA file as the same agent, other sharing needs the DNA hash to tell
+            let key = if uri.path.is_empty() {
+                (uri.scheme, format!("{}\n{}", instance.dna, instance.agent))
+            } else {
+                (uri.scheme, uri.path)
+            };
+            if let Some(other) = storage.insert(key, instance.id.clone()) {
+                return Err(shared_storage(&other, &instance.id));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_interfaces(&self) -> Result<(), HolochainError> {
+        let mut ids = HashSet::new();
+        for interface in &self.interfaces {
+            if !ids.insert(interface.id.clone()) {
+                return Err(HolochainError::new(&format!(
+                    "interface id '{}' is used more than once",
+                    interface.id
+                )));
+            }
+            interface.check()?;
+            let exposed = interface.instances.iter().flat_map(|ids| ids.iter());
+            for id in exposed {
+                if self.instance(id).is_none() {
+                    return Err(HolochainError::new(&format!(
+                        "interface '{}' exposes unknown instance '{}'",
+                        interface.id, id
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// the storage of every instance by instance id, dna_hashes has the hash of the DNA each one
+    /// runs
+    /// with a storage root, storage without a path gets the instance's directory under it and
+    /// configured paths have to stay inside it
+    /// without a storage root the platform's data directory is used as one, if it has one
+    /// fails if two instances would use the same storage
+    pub fn storage(
+        &self,
+        dna_hashes: &HashMap<String, String>,
+    ) -> Result<HashMap<String, StorageUri>, HolochainError> {
+        let root = self.root();
+        let root = root.as_deref();
+        let mut storage = HashMap::new();
+        let mut used: HashMap<StorageUri, String> = HashMap::new();
+        for instance in &self.instances {
+            let dna_hash = dna_hashes.get(&instance.id).ok_or_else(|| {
+                HolochainError::new(&format!("no DNA hash for instance '{}'", instance.id))
+            })?;
+            let uri = instance.storage_uri(root, dna_hash)?;
+            if !uri.path.is_empty() {
+                if let Some(other) = used.insert(uri.clone(), instance.id.clone()) {
+                    return Err(shared_storage(&other, &instance.id));
+                }
+            }
+            storage.insert(instance.id.clone(), uri);
+        }
+        Ok(storage)
+    }
+}
+
+fn shared_storage(first: &str, second: &str) -> HolochainError {
+    HolochainError::new(&format!(
+        "instances '{}' and '{}' use the same storage",
+        first, second
+    ))
+}
+
+/// configuration of a single instance
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InstanceConfiguration {
+    pub id: String,
+    /// path of the DNA package to run, or "hash:" followed by the hash of a DNA to fetch from
+    /// the dna_sources
+    pub dna: String,
+    /// run the DNA with this uuid instead of its own, so the same code forms a network of its
+    /// own, see Dna::hash()
+    #[serde(default)]
+    pub uuid: Option<String>,
+    pub agent: String,
+    /// storage URI resolved through a StorageRegistry, defaults to memory
+    #[serde(default = "default_storage")]
+    pub storage: String,
+    #[serde(default)]
+    pub logging: LoggingConfiguration,
+    /// what the instance may use of the host, nothing is limited if not set
+    /// see Holochain::set_resource_limits()
+    #[serde(default)]
+    pub limits: ResourceLimits,
+}
+
+impl InstanceConfiguration {
+    /// where the instance running the DNA with the given hash keeps its storage
+    /// without a root the configured storage is used as is
+    pub fn storage_uri(
+        &self,
+        root: Option<&Path>,
+        dna_hash: &str,
+    ) -> Result<StorageUri, HolochainError> {
+        let mut uri = StorageUri::parse(&self.storage)?;
+        let root = match root {
+            Some(root) if uri.scheme != "memory" => root,
+            _ => return Ok(uri),
+        };
+        let path = if uri.path.is_empty() {
+            sandbox::instance_dir(root, dna_hash, &self.agent)
+        } else {
+            PathBuf::from(&uri.path)
+        };
+        uri.path = sandbox::confine(root, &path)?
+            .to_string_lossy()
+            .to_string();
+        Ok(uri)
+    }
+
+    /// build the Context for this instance, resolving its storage through the registry
+    /// see Configuration::storage() for sandboxed storage
+    pub fn context(&self, registry: &StorageRegistry) -> Result<Context, HolochainError> {
+        self.context_with_storage(registry, &StorageUri::parse(&self.storage)?)
+    }
+
+    /// build the Context for this instance with the given storage
+    pub fn context_with_storage(
+        &self,
+        registry: &StorageRegistry,
+        storage: &StorageUri,
+    ) -> Result<Context, HolochainError> {
+        Ok(Context {
+            agent: Agent::from_string(&self.agent),
+            logger: Arc::new(Mutex::new(SimpleLogger {})),
+            persister: registry.resolve_uri(storage)?,
+        })
+    }
+}
+
+/// which messages logged by zomes reach the host logger
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LoggingConfiguration {
+    /// level for zomes without one of their own, info if not set
+    #[serde(default)]
+    pub level: Option<LogLevel>,
+    /// levels by zome name
+    #[serde(default)]
+    pub zomes: HashMap<String, LogLevel>,
+}
+
+impl LoggingConfiguration {
+    /// set the configured levels on an instance's ZomeLogger
+    pub fn apply(&self, zome_logger: &ZomeLogger) {
+        if let Some(level) = self.level {
+            zome_logger.set_default_level(level);
+        }
+        for (zome, level) in &self.zomes {
+            zome_logger.set_zome_level(zome, *level);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_core::persister::SimplePersister;
+    use sandbox::tests::test_root;
+
+    fn test_config_json() -> &'static str {
+        r#"{
+            "instances": [
+                {
+                    "id": "app",
+                    "dna": "app.hcpkg",
+                    "agent": "bob"
+                },
+                {
+                    "id": "other",
+                    "dna": "other.hcpkg",
+                    "uuid": "4b1c9e02",
+                    "agent": "jane",
+                    "storage": "test:///data/other",
+                    "logging": {
+                        "level": "warn",
+                        "zomes": { "blog": "trace" }
+                    },
+                    "limits": {
+                        "max_wasm_pages": 64,
+                        "max_chain_bytes": 4096,
+                        "max_concurrent_reads": 8,
+                        "max_outbox_depth": 100
+                    }
+                }
+            ]
+        }"#
+    }
+
+    #[test]
+    fn can_parse_config() {
+        let config = Configuration::from_json(test_config_json()).unwrap();
+        assert_eq!(2, config.instances.len());
+
+        let app = config.instance("app").unwrap();
+        assert_eq!("app.hcpkg".to_string(), app.dna);
+        assert_eq!(STORAGE_DEFAULT_URI.to_string(), app.storage);
+        assert_eq!(None, app.uuid);
+        assert_eq!(
+            Some("4b1c9e02".to_string()),
+            config.instance("other").unwrap().uuid
+        );
+        assert_eq!(
+            "test:///data/other".to_string(),
+            config.instance("other").unwrap().storage
+        );
+        assert_eq!(None, config.instance("missing"));
+        assert_eq!(ResourceLimits::default(), app.limits);
+        assert_eq!(
+            ResourceLimits {
+                max_wasm_pages: Some(64),
+                max_chain_bytes: Some(4096),
+                max_dht_bytes: None,
+                max_concurrent_reads: Some(8),
+                max_outbox_depth: Some(100),
+            },
+            config.instance("other").unwrap().limits
+        );
+
+        assert_eq!(
+            Configuration::default(),
+            Configuration::from_json("{}").unwrap()
+        );
+    }
+
+    #[test]
+    fn fails_on_inconsistent_config() {
+        assert!(Configuration::from_json("not json").is_err());
+        assert!(
+            Configuration::from_json(
+                r#"{"instances": [
+                    {"id": "app", "dna": "a.hcpkg", "agent": "bob"},
+                    {"id": "app", "dna": "b.hcpkg", "agent": "jane"}
+                ]}"#
+            ).is_err()
+        );
+        assert!(
+            Configuration::from_json(
+                r#"{"instances": [
+                    {"id": "app", "dna": "a.hcpkg", "agent": "bob", "storage": "/no/scheme"}
+                ]}"#
+            ).is_err()
+        );
+        // lmdb storage needs the lmdb feature
+        #[cfg(not(feature = "lmdb"))]
+        assert!(
+            Configuration::from_json(
+                r#"{"instances": [
+                    {"id": "app", "dna": "a.hcpkg", "agent": "bob", "storage": "lmdb:"}
+                ]}"#
+            ).is_err()
+        );
+    }
+
+    #[test]
+    fn can_configure_network() {
+        let config = Configuration::from_json(
+            r#"{"network": {"direct_message_timeout_ms": 500, "retry": {"attempts": 1}}}"#,
+        ).unwrap();
+        assert_eq!(500, config.network.direct_message_timeout_ms);
+        assert_eq!(1, config.network.retry.attempts);
+        assert_eq!(
+            NetworkConfig::default(),
+            Configuration::from_json(test_config_json()).unwrap().network
+        );
+
+        let invalid = |network: &str| {
+            Configuration::from_json(&format!(r#"{{"network": {}}}"#, network)).is_err()
+        };
+        assert!(invalid(r#"{"direct_message_timeout_ms": 0}"#));
+        assert!(invalid(r#"{"retry": {"attempts": 0}}"#));
+        assert!(invalid(r#"{"retry": {"initial_delay_ms": 5000, "max_delay_ms": 100}}"#));
+        assert!(invalid(r#"{"retry": {"backoff": "random"}}"#));
+    }
+
+    #[test]
+    fn can_configure_watchdog() {
+        let config =
+            Configuration::from_json(r#"{"watchdog": {"call_budget_ms": 1000}}"#).unwrap();
+        assert_eq!(1000, config.watchdog.call_budget_ms);
+        assert_eq!(
+            WatchdogConfig::default().heartbeat_timeout_ms,
+            config.watchdog.heartbeat_timeout_ms
+        );
+        assert!(
+            Configuration::from_json(r#"{"watchdog": {"heartbeat_timeout_ms": 10}}"#).is_err()
+        );
+    }
+
+    #[test]
+    fn can_configure_interfaces() {
+        let config = Configuration::from_json(
+            r#"{
+                "interfaces": [
+                    {"id": "ui", "auth": {"type": "token", "tokens": ["a"]}, "instances": ["app"]},
+                    {"id": "admin", "admin": true, "auth": {"type": "challenge", "secret": "s"}}
+                ],
+                "instances": [{"id": "app", "dna": "app.hcpkg", "agent": "bob"}]
+            }"#,
+        ).unwrap();
+        let ui = config.interface("ui").unwrap();
+        assert!(!ui.admin);
+        assert_eq!(Some(vec!["app".to_string()]), ui.instances);
+        assert!(ui.allowed_origins.is_empty());
+        assert_eq!(None, ui.rate_limit);
+        let admin = config.interface("admin").unwrap();
+        assert!(admin.admin);
+        assert!(!admin.allow_remote);
+        assert_eq!(None, admin.instances);
+        assert_eq!(None, config.interface("missing"));
+
+        let invalid = |interfaces: &str| {
+            Configuration::from_json(&format!(
+                r#"{{"interfaces": {}, "instances": [{{"id": "app", "dna": "a", "agent": "b"}}]}}"#,
+                interfaces
+            )).is_err()
+        };
+        assert!(invalid(r#"[{"id": "ui"}]"#));
+        assert!(invalid(r#"[{"id": "ui", "auth": {"type": "none"}}]"#));
+        assert!(invalid(r#"[{"id": "ui", "auth": {"type": "token", "tokens": []}}]"#));
+        assert!(invalid(r#"[{"id": "ui", "auth": {"type": "challenge", "secret": ""}}]"#));
+        assert!(invalid(
+            r#"[{"id": "ui", "auth": {"type": "token", "tokens": ["a"]}, "instances": ["x"]}]"#
+        ));
+        assert!(invalid(
+            r#"[{"id": "ui", "auth": {"type": "token", "tokens": ["a"]},
+                "allowed_origins": ["/"]}]"#
+        ));
+        assert!(invalid(
+            r#"[{"id": "ui", "auth": {"type": "token", "tokens": ["a"]},
+                "rate_limit": {"calls_per_second": 10, "burst": 0}}]"#
+        ));
+        assert!(invalid(
+            r#"[{"id": "ui", "auth": {"type": "token", "tokens": ["a"]}},
+                {"id": "ui", "auth": {"type": "token", "tokens": ["b"]}}]"#
+        ));
+    }
+
+    #[test]
+    fn instances_can_mix_backends() {
+        let config = Configuration::from_json(test_config_json()).unwrap();
+        let mut registry = StorageRegistry::default();
+
+        // the test backend is not registered yet
+        assert!(config.instance("app").unwrap().context(&registry).is_ok());
+        assert!(config.instance("other").unwrap().context(&registry).is_err());
+
+        registry.register(
+            "test",
+            Box::new(|_| Ok(Arc::new(Mutex::new(SimplePersister::new())))),
+        );
+        let context = config.instance("other").unwrap().context(&registry).unwrap();
+        assert_eq!(Agent::from_string("jane"), context.agent);
+    }
+
+    #[test]
+    fn fails_on_shared_storage() {
+        let config = |storage: &[(&str, &str, &str)]| {
+            let instances = storage
+                .iter()
+                .enumerate()
+                .map(|(i, (dna, agent, storage))| {
+                    json!({"id": i.to_string(), "dna": dna, "agent": agent, "storage": storage})
+                })
+                .collect::<Vec<_>>();
+            Configuration::from_json(&json!({ "instances": instances }).to_string())
+        };
+        assert!(config(&[("a.hcpkg", "bob", "memory:"), ("a.hcpkg", "bob", "memory:")]).is_ok());
+        assert!(config(&[("a.hcpkg", "bob", "file:"), ("a.hcpkg", "jane", "file:")]).is_ok());
+        assert!(config(&[("a.hcpkg", "bob", "file:"), ("a.hcpkg", "bob", "file:")]).is_err());
+        assert!(
+            config(&[("a.hcpkg", "bob", "file://app"), ("b.hcpkg", "jane", "file://app")]).is_err()
+        );
+    }
+
+    #[test]
+    fn can_sandbox_storage() {
+        let root = test_root("config");
+        let canonical = root.canonicalize().unwrap();
+        let config = |storage: &str| {
+            Configuration::from_json(
+                &json!({
+                    "storage_root": root,
+                    "instances": [
+                        {"id": "app", "dna": "app.hcpkg", "agent": "bob", "storage": "file:"},
+                        {"id": "other", "dna": "other.hcpkg", "agent": "bob", "storage": storage},
+                    ]
+                }).to_string(),
+            ).unwrap()
+        };
+        let hashes = |app: &str, other: &str| {
+            vec![("app", app), ("other", other)]
+                .into_iter()
+                .map(|(id, hash)| (id.to_string(), hash.to_string()))
+                .collect::<HashMap<String, String>>()
+        };
+
+        let storage = config("file://data/other").storage(&hashes("QmA", "QmB")).unwrap();
+        let path = |id: &str| PathBuf::from(&storage[id].path);
+        assert_eq!(canonical.join("QmA").join("bob"), path("app"));
+        assert_eq!(canonical.join("data/other"), path("other"));
+        assert_eq!(
+            StorageUri::parse(STORAGE_DEFAULT_URI).unwrap(),
+            config("memory:").storage(&hashes("QmA", "QmA")).unwrap()["other"]
+        );
+
+        // the same DNA run by the same agent from another file
+        assert!(config("file:").storage(&hashes("QmA", "QmA")).is_err());
+        assert!(config("file:").storage(&hashes("QmA", "QmB")).is_ok());
+        // paths leaving the root
+        assert!(config("file://../other").storage(&hashes("QmA", "QmB")).is_err());
+        assert!(config("file:///etc").storage(&hashes("QmA", "QmB")).is_err());
+        // the derived directory of another instance
+        assert!(config("file://QmA/bob").storage(&hashes("QmA", "QmB")).is_err());
+        assert!(config("file:").storage(&HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn can_configure_zome_log_levels() {
+        let config = Configuration::from_json(test_config_json()).unwrap();
+        assert_eq!(
+            LoggingConfiguration::default(),
+            config.instance("app").unwrap().logging
+        );
+
+        let zome_logger = ZomeLogger::default();
+        config.instance("other").unwrap().logging.apply(&zome_logger);
+        assert_eq!(LogLevel::Trace, zome_logger.level("blog"));
+        assert_eq!(LogLevel::Warn, zome_logger.level("chat"));
+    }
+}
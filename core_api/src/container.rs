@@ -0,0 +1,688 @@
+//! a container runs a set of holochain instances side by side and lets them find each other,
+//! e.g. by the zome traits their DNAs declare
+//!
+//! instances can be cloned at runtime with a DNA differing in its uuid or properties, see
+//! clone_instance(), giving a new chain in a network of its own that runs the same code, which is
+//! how apps make private spaces or channels
+//!
+//! agents are created at runtime too, and instances added for them, see create_agent() and
+//! add_agent_instance()
+
+use agents::{AgentInfo, AgentRegistry, AGENTS_DIR};
+use config::{Configuration, InstanceConfiguration};
+use dump::StateDump;
+use holochain_core::{
+    context::Context, error::HolochainError, network::config::NetworkConfig, platform,
+    signal::Signal,
+};
+use holochain_dna::{resolver::DnaResolver, Dna};
+use serde_json;
+use std::{
+    collections::{BTreeMap, HashMap}, fs, path::PathBuf, sync::{
+        mpsc::{channel, Receiver}, Arc,
+    },
+    time::{Duration, Instant},
+};
+use storage::StorageRegistry;
+use watchdog::WatchdogConfig;
+use Holochain;
+
+/// a signal along with the id of the instance it was emitted in
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct InstanceSignal {
+    pub instance_id: String,
+    pub signal: Signal,
+}
+
+/// how the DNA of a clone differs from the DNA of the instance it is cloned from
+/// a clone with neither set gets a new random uuid, so it is always in a network of its own
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CloneChanges {
+    #[serde(default)]
+    pub uuid: Option<String>,
+    /// replace the properties of the DNA
+    #[serde(default)]
+    pub properties: Option<serde_json::Value>,
+}
+
+impl CloneChanges {
+    /// the DNA of the clone, with the same zomes and code as the DNA cloned
+    pub fn apply(&self, dna: &Dna) -> Dna {
+        let mut clone = dna.clone();
+        match (&self.uuid, &self.properties) {
+            (None, None) => clone.uuid = Dna::new().uuid,
+            (uuid, properties) => {
+                if let Some(uuid) = uuid {
+                    clone.uuid = uuid.clone();
+                }
+                if let Some(properties) = properties {
+                    clone.properties = properties.clone();
+                }
+            }
+        }
+        clone
+    }
+}
+
+/// the instances run by a container application, by instance id
+#[derive(Default)]
+pub struct Container {
+    instances: HashMap<String, Holochain>,
+    /// the agents instances can be added for at runtime
+    agents: AgentRegistry,
+    /// the storage root of the configuration, if any
+    root: Option<PathBuf>,
+    /// the network config of the configuration, for instances added at runtime
+    network: NetworkConfig,
+}
+
+impl Container {
+    pub fn new() -> Container {
+        Container::default()
+    }
+
+    /// build the instances of a configuration, not started yet
+    /// load_dna loads the DNA at the path an instance is configured with, e.g. dna_from_file()
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn from_config<F>(
+        config: &Configuration,
+        registry: &StorageRegistry,
+        load_dna: F,
+    ) -> Result<Container, HolochainError>
+    where
+        F: Fn(&str) -> Result<Dna, HolochainError>,
+    {
+        let mut dnas = HashMap::new();
+        let mut dna_hashes = HashMap::new();
+        for instance in &config.instances {
+            let mut dna = load_dna(&instance.dna)?;
+            if let Some(ref uuid) = instance.uuid {
+                dna.uuid = uuid.clone();
+            }
+            dna_hashes.insert(instance.id.clone(), dna.hash());
+            dnas.insert(instance.id.clone(), dna);
+        }
+        let storage = config.storage(&dna_hashes)?;
+
+        let mut container = Container::new();
+        container.root = config.root();
+        if let Some(ref root) = container.root {
+            container.agents = AgentRegistry::load(&root.join(AGENTS_DIR))?;
+        }
+        container.network = config.network.clone();
+        for instance in &config.instances {
+            let context = instance.context_with_storage(registry, &storage[&instance.id])?;
+            let dna = dnas.remove(&instance.id).expect("every instance has a DNA loaded");
+            let mut hc = Holochain::new(dna, Arc::new(context))?;
+            instance.logging.apply(&hc.zome_logger());
+            hc.set_resource_limits(&instance.limits);
+            hc.set_network_config(&config.network);
+            container.add_instance(&instance.id, hc);
+        }
+        Ok(container)
+    }
+
+    /// add an instance under the given id, returning the instance it replaces if any
+    pub fn add_instance(&mut self, id: &str, instance: Holochain) -> Option<Holochain> {
+        self.instances.insert(id.to_string(), instance)
+    }
+
+    /// remove and return the instance with the given id
+    pub fn remove_instance(&mut self, id: &str) -> Option<Holochain> {
+        self.instances.remove(id)
+    }
+
+    pub fn instance(&self, id: &str) -> Option<&Holochain> {
+        self.instances.get(id)
+    }
+
+    pub fn instance_mut(&mut self, id: &str) -> Option<&mut Holochain> {
+        self.instances.get_mut(id)
+    }
+
+    /// add a clone of the instance with the given id under clone_id, running a DNA with the
+    /// changes on a chain of its own kept with the context, the admin/instance/clone call
+    /// the clone has the network config of the instance and isn't started yet
+    /// fails if there is no such instance, clone_id is taken or the clone fails to initialize
+    pub fn clone_instance(
+        &mut self,
+        id: &str,
+        clone_id: &str,
+        changes: &CloneChanges,
+        context: Arc<Context>,
+    ) -> Result<Dna, HolochainError> {
+        if self.instances.contains_key(clone_id) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "there already is an instance '{}'",
+                clone_id
+            )));
+        }
+        let (dna, network_config) = match self.instances.get(id) {
+            Some(instance) => (
+                instance.dna(),
+                instance.instance.state().nucleus().messenger().config(),
+            ),
+            None => {
+                return Err(HolochainError::ErrorGeneric(format!(
+                    "there is no instance '{}' to clone",
+                    id
+                )))
+            }
+        };
+        let dna = match dna {
+            Some(dna) => changes.apply(&dna),
+            None => return Err(HolochainError::new("the instance has no DNA to clone")),
+        };
+        let mut clone = Holochain::new(dna.clone(), context)?;
+        clone.set_network_config(&network_config);
+        self.add_instance(clone_id, clone);
+        Ok(dna)
+    }
+
+    /// create an agent with keys of its own, encrypted with its passphrase, the
+    /// admin/agent/create call, see agents
+    /// fails if there is no storage root to keep its keys in or the name is taken
+    pub fn create_agent(
+        &mut self,
+        name: &str,
+        passphrase: &str,
+    ) -> Result<AgentInfo, HolochainError> {
+        self.agents.create(name, passphrase)
+    }
+
+    /// the agents of the container, sorted by name, the admin/agent/list call
+    pub fn agents(&self) -> Vec<AgentInfo> {
+        self.agents.agents()
+    }
+
+    /// add an instance configured as in a config file, running the DNA for one of the agents of
+    /// the container, the admin/instance/add call
+    /// the instance derives the agent's keys, has the network config of the configuration and
+    /// isn't started yet, its keystore is unlocked with the agent's passphrase until it stops, see
+    /// Holochain::unlock_keystore()
+    /// fails if the agent isn't one of the container's, the id is taken, the passphrase isn't the
+    /// agent's or the instance fails to initialize
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn add_agent_instance(
+        &mut self,
+        config: &InstanceConfiguration,
+        mut dna: Dna,
+        registry: &StorageRegistry,
+        passphrase: &str,
+    ) -> Result<(), HolochainError> {
+        if self.instances.contains_key(&config.id) {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "there already is an instance '{}'",
+                config.id
+            )));
+        }
+        let keystore = match self.agents.get(&config.agent) {
+            Some(agent) => self.agents.keystore_path(&agent.name),
+            None => None,
+        };
+        let keystore = keystore.ok_or_else(|| {
+            HolochainError::ErrorGeneric(format!("there is no agent '{}'", config.agent))
+        })?;
+        if let Some(ref uuid) = config.uuid {
+            dna.uuid = uuid.clone();
+        }
+        let storage = config.storage_uri(self.root.as_deref(), &dna.hash())?;
+        let context = config.context_with_storage(registry, &storage)?;
+        // the keystore is kept encrypted with the passphrase, not with the stores of the instance
+        context.persister.lock().unwrap().skip_keystore();
+        let mut hc = Holochain::new(dna, Arc::new(context))?;
+        hc.persist_encrypted_keystore(&keystore, passphrase)?;
+        config.logging.apply(&hc.zome_logger());
+        hc.set_resource_limits(&config.limits);
+        hc.set_network_config(&self.network);
+        self.add_instance(&config.id, hc);
+        Ok(())
+    }
+
+    /// ids of all instances, sorted
+    pub fn instance_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.instances.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// shut every instance down, see Holochain::shutdown(), within timeout overall
+    /// fails naming the instances that still had calls in flight, they are shut down regardless
+    pub fn shutdown(&mut self, timeout: Duration) -> Result<(), HolochainError> {
+        let deadline = Instant::now() + timeout;
+        let mut busy = Vec::new();
+        for id in self.instance_ids() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            let instance = self.instances.get_mut(&id).expect("id was just listed");
+            if instance.shutdown(left).is_err() {
+                busy.push(id);
+            }
+        }
+        if busy.is_empty() {
+            Ok(())
+        } else {
+            Err(HolochainError::ErrorGeneric(format!(
+                "instances {} still had calls in flight",
+                busy.join(", ")
+            )))
+        }
+    }
+
+    /// dump the state of the instance with the given id for debugging, see dump
+    /// this exposes everything the instance holds, so it is only for admin interfaces
+    pub fn dump_state(&self, id: &str) -> Option<StateDump> {
+        self.instances.get(id).map(|instance| instance.dump_state())
+    }
+
+    /// have the watchdog check on every instance: healthy ones are checkpointed, wedged ones are
+    /// restarted from their last checkpoint and an "instance_restarted" signal with the
+    /// diagnosis is emitted in them
+    /// meant to be called regularly, returns the signals emitted
+    pub fn check_health(&mut self, config: &WatchdogConfig) -> Vec<InstanceSignal> {
+        let mut signals = Vec::new();
+        for id in self.instance_ids() {
+            let instance = self.instances.get_mut(&id).expect("id was just listed");
+            let mut diagnosis = match config.diagnose(&instance.health(config.call_budget())) {
+                Some(diagnosis) => diagnosis,
+                None => {
+                    instance.checkpoint();
+                    continue;
+                }
+            };
+            diagnosis.restarted = instance.restart().is_ok();
+            let signal = diagnosis.to_signal();
+            instance.instance.state().nucleus().signal_bus().emit(&signal);
+            signals.push(InstanceSignal {
+                instance_id: id,
+                signal,
+            });
+        }
+        signals
+    }
+
+    /// receive the signals emitted in any of the current instances from now on, e.g. for an
+    /// interface to push them to connected UIs
+    /// instances added later are not subscribed to
+    pub fn signals(&self) -> Receiver<InstanceSignal> {
+        let (sender, receiver) = channel();
+        for (id, instance) in &self.instances {
+            let instance_id = id.clone();
+            let signals = instance.signals();
+            let sender = sender.clone();
+            platform::spawn("signal_forwarder", move || {
+                for signal in signals {
+                    let forwarded = InstanceSignal {
+                        instance_id: instance_id.clone(),
+                        signal,
+                    };
+                    if sender.send(forwarded).is_err() {
+                        return;
+                    }
+                }
+            });
+        }
+        receiver
+    }
+
+    /// the zomes implementing the named trait, by id of the instance running them
+    /// instances without such a zome are left out
+    pub fn instances_implementing(&self, trait_name: &str) -> BTreeMap<String, Vec<String>> {
+        self.instances
+            .iter()
+            .filter_map(|(id, instance)| {
+                let zomes = instance
+                    .dna()
+                    .map(|dna| zomes_implementing(&dna, trait_name))
+                    .unwrap_or_default();
+                if zomes.is_empty() {
+                    None
+                } else {
+                    Some((id.clone(), zomes))
+                }
+            })
+            .collect()
+    }
+}
+
+/// prefix of the DNA an instance is configured with when it references it by hash
+pub const DNA_HASH_PREFIX: &str = "hash:";
+
+/// load the DNA an instance is configured with, resolving DNAs referenced by hash with the
+/// resolver, e.g. Configuration::dna_resolver(), and loading the others from file
+pub fn dna_from_resolver<'a>(
+    resolver: &'a DnaResolver,
+) -> impl Fn(&str) -> Result<Dna, HolochainError> + 'a {
+    move |dna: &str| match dna.strip_prefix(DNA_HASH_PREFIX) {
+        Some(hash) => resolver.resolve(hash).map_err(HolochainError::ErrorGeneric),
+        None => dna_from_file(dna),
+    }
+}
+
+/// load a DNA from a JSON file
+pub fn dna_from_file(path: &str) -> Result<Dna, HolochainError> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| HolochainError::ErrorGeneric(format!("couldn't read {}: {}", path, e)))?;
+    Dna::new_from_json(&json).map_err(|e| HolochainError::new(&e.to_string()))
+}
+
+fn zomes_implementing(dna: &Dna, trait_name: &str) -> Vec<String> {
+    dna.get_zomes_implementing(trait_name)
+        .iter()
+        .map(|zome| zome.name.clone())
+        .collect()
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use agents::AGENT_KEY_PATH;
+    use holochain_agent::Agent;
+    use holochain_core::{
+        context::Context, logger::SimpleLogger,
+        persister::{SimplePersister, FILE_PERSISTER_HOLDINGS, FILE_PERSISTER_KEYSTORE},
+    };
+    use holochain_dna::zome::{traits::ZomeTrait, Zome};
+    use sandbox::{instance_dir, tests::test_root};
+    use watchdog::INSTANCE_RESTARTED_SIGNAL;
+    use std::{
+        sync::{Arc, Mutex}, thread, time::Duration,
+    };
+
+    /// dna with one zome per (zome name, trait names) pair, the traits have no functions so
+    /// the zomes need no code
+    fn test_dna(zomes: &[(&str, &[&str])]) -> Dna {
+        let mut dna = Dna::new();
+        for (zome_name, trait_names) in zomes {
+            let mut zome = Zome::new();
+            zome.name = zome_name.to_string();
+            for trait_name in trait_names.iter() {
+                let mut zome_trait = ZomeTrait::new();
+                zome_trait.name = trait_name.to_string();
+                zome.traits.push(zome_trait);
+            }
+            dna.zomes.push(zome);
+        }
+        dna
+    }
+
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub fn test_instance(dna: Dna) -> Holochain {
+        let context = Context {
+            agent: Agent::from_string("bob"),
+            logger: Arc::new(Mutex::new(SimpleLogger {})),
+            persister: Arc::new(Mutex::new(SimplePersister::new())),
+        };
+        Holochain::new(dna, Arc::new(context)).unwrap()
+    }
+
+    #[test]
+    fn can_add_and_remove_instances() {
+        let mut container = Container::new();
+        assert!(container.add_instance("b", test_instance(Dna::new())).is_none());
+        assert!(container.add_instance("a", test_instance(Dna::new())).is_none());
+        assert!(container.add_instance("a", test_instance(Dna::new())).is_some());
+        assert_eq!(vec!["a".to_string(), "b".to_string()], container.instance_ids());
+
+        assert!(container.instance("a").is_some());
+        container.instance_mut("a").unwrap().start().unwrap();
+        assert!(container.instance("a").unwrap().active());
+
+        assert!(container.remove_instance("a").is_some());
+        assert!(container.instance("a").is_none());
+        assert_eq!(vec!["b".to_string()], container.instance_ids());
+    }
+
+    #[test]
+    fn can_build_from_config() {
+        let config = Configuration::from_json(
+            r#"{
+                "instances": [
+                    {"id": "app", "dna": "app.json", "agent": "bob"},
+                    {"id": "other", "dna": "other.json", "uuid": "forked", "agent": "jane"}
+                ]
+            }"#,
+        ).unwrap();
+        let load_dna = |path: &str| {
+            let mut dna = Dna::new();
+            dna.name = path.to_string();
+            Ok(dna)
+        };
+        let container =
+            Container::from_config(&config, &StorageRegistry::default(), load_dna).unwrap();
+        assert_eq!(vec!["app".to_string(), "other".to_string()], container.instance_ids());
+        assert_eq!("other.json", container.instance("other").unwrap().dna().unwrap().name);
+        assert_eq!("forked", container.instance("other").unwrap().dna().unwrap().uuid);
+        assert!(!container.instance("app").unwrap().active());
+
+        let missing = Container::from_config(&config, &StorageRegistry::default(), dna_from_file);
+        assert!(missing.is_err());
+    }
+
+    #[test]
+    fn can_clone_instances() {
+        let mut container = Container::new();
+        let dna = test_dna(&[("messages", &["messaging"])]);
+        container.add_instance("chat", test_instance(dna.clone()));
+        let context = || test_instance(Dna::new()).context;
+
+        let space = CloneChanges {
+            properties: Some(json!({"space": "friends"})),
+            ..CloneChanges::default()
+        };
+        let cloned = container
+            .clone_instance("chat", "friends", &space, context())
+            .unwrap();
+        assert_eq!(dna.uuid, cloned.uuid);
+        assert_eq!(json!({"space": "friends"}), cloned.properties);
+        assert_eq!(dna.zomes, cloned.zomes);
+        assert_ne!(dna.hash(), cloned.hash());
+        assert_eq!(Some(cloned), container.instance("friends").unwrap().dna());
+        assert!(!container.instance("friends").unwrap().active());
+
+        let fresh = container
+            .clone_instance("chat", "other", &CloneChanges::default(), context())
+            .unwrap();
+        assert_ne!(dna.uuid, fresh.uuid);
+        assert_eq!(dna.properties, fresh.properties);
+        assert_eq!(
+            vec!["chat", "friends", "other"],
+            container.instance_ids()
+        );
+
+        assert!(container.clone_instance("chat", "other", &space, context()).is_err());
+        assert!(container.clone_instance("missing", "new", &space, context()).is_err());
+    }
+
+    #[test]
+    fn can_add_instances_for_agents() {
+        let root = test_root("container_agents");
+        let config = Configuration {
+            storage_root: Some(root.to_string_lossy().to_string()),
+            ..Configuration::default()
+        };
+        let registry = StorageRegistry::default();
+        let mut container = Container::from_config(&config, &registry, dna_from_file).unwrap();
+        assert!(container.agents().is_empty());
+        let bob = container.create_agent("bob", "bob's passphrase").unwrap();
+        assert_eq!(vec![bob.clone()], container.agents());
+
+        let instance = |id: &str, agent: &str| InstanceConfiguration {
+            id: id.to_string(),
+            dna: "app.json".to_string(),
+            uuid: None,
+            agent: agent.to_string(),
+            storage: "file:".to_string(),
+            logging: Default::default(),
+            limits: Default::default(),
+        };
+        let dna = Dna::new();
+        container
+            .add_agent_instance(&instance("app", "bob"), dna.clone(), &registry, "bob's passphrase")
+            .unwrap();
+        // the seed is only kept encrypted, not with the other stores of the instance
+        let dir = instance_dir(&root, &dna.hash(), "bob");
+        assert!(dir.join(FILE_PERSISTER_HOLDINGS).exists());
+        assert!(!dir.join(FILE_PERSISTER_KEYSTORE).exists());
+        let keystore = container
+            .instance("app")
+            .unwrap()
+            .instance
+            .state()
+            .nucleus()
+            .keystore()
+            .clone();
+        assert!(!container.instance("app").unwrap().active());
+
+        // the keys are gone once the instance stops, until unlocked with the passphrase again
+        container.instance_mut("app").unwrap().start().unwrap();
+        let keys = keystore.derive_key(AGENT_KEY_PATH).unwrap();
+        assert_eq!(bob.public_key, keys.to_base58());
+        container.instance_mut("app").unwrap().stop().unwrap();
+        assert!(keystore.is_locked());
+        assert!(container.instance_mut("app").unwrap().start().is_err());
+        let app = container.instance_mut("app").unwrap();
+        assert!(app.unlock_keystore("carol's passphrase").is_err());
+        app.unlock_keystore("bob's passphrase").unwrap();
+        app.start().unwrap();
+
+        let add = |container: &mut Container, id: &str, agent: &str, passphrase: &str| {
+            container.add_agent_instance(&instance(id, agent), Dna::new(), &registry, passphrase)
+        };
+        assert!(add(&mut container, "app", "bob", "bob's passphrase").is_err());
+        assert!(add(&mut container, "other", "carol", "carol's passphrase").is_err());
+        assert!(add(&mut container, "guessed", "bob", "carol's passphrase").is_err());
+
+        // agents are still there when the container is built again
+        let restarted = Container::from_config(&config, &registry, dna_from_file).unwrap();
+        assert_eq!(vec![bob], restarted.agents());
+        assert!(Container::new().create_agent("bob", "secret").is_err());
+    }
+
+    #[test]
+    fn can_resolve_dnas_by_hash() {
+        let dna = test_dna(&[("messages", &["messaging"])]);
+        let root = test_root("dna_by_hash");
+        let store = root.join("store");
+        fs::create_dir_all(&store).unwrap();
+        fs::write(store.join(dna.hash()), dna.to_json().unwrap()).unwrap();
+
+        let config = Configuration::from_json(&format!(
+            r#"{{
+                "storage_root": "{}",
+                "dna_sources": ["file://{}/"],
+                "instances": [{{"id": "app", "dna": "hash:{}", "agent": "bob"}}]
+            }}"#,
+            root.display(),
+            store.display(),
+            dna.hash()
+        )).unwrap();
+        let resolver = config.dna_resolver();
+        let load_dna = dna_from_resolver(&resolver);
+        let container =
+            Container::from_config(&config, &StorageRegistry::default(), &load_dna).unwrap();
+        assert_eq!(Some(dna.clone()), container.instance("app").unwrap().dna());
+        assert!(root.join("dna").join(format!("{}.json", dna.hash())).exists());
+
+        assert!(load_dna(&format!("hash:{}", Dna::new().hash())).is_err());
+        assert!(load_dna("missing.json").is_err());
+    }
+
+    #[test]
+    fn can_shutdown_all_instances() {
+        let mut container = Container::new();
+        container.add_instance("a", test_instance(Dna::new()));
+        container.add_instance("b", test_instance(Dna::new()));
+        container.instance_mut("a").unwrap().start().unwrap();
+
+        assert_eq!(Ok(()), container.shutdown(Duration::from_millis(100)));
+        assert!(!container.instance("a").unwrap().active());
+        assert!(!container.instance("b").unwrap().active());
+    }
+
+    #[test]
+    fn can_dump_instance_state() {
+        let mut container = Container::new();
+        container.add_instance("a", test_instance(Dna::new()));
+        let dump = container.dump_state("a").unwrap();
+        assert_eq!(None, dump.network_address);
+        assert!(container.dump_state("b").is_none());
+    }
+
+    #[test]
+    fn can_restart_wedged_instances() {
+        let mut container = Container::new();
+        container.add_instance("a", test_instance(Dna::new()));
+        container.add_instance("b", test_instance(Dna::new()));
+        let config = WatchdogConfig {
+            heartbeat_timeout_ms: 500,
+            ..WatchdogConfig::default()
+        };
+        assert!(container.check_health(&config).is_empty());
+
+        let signals = container.signals();
+        // the loop stops within REDUX_LOOP_TIMEOUT_MS, beating one last time
+        container.instance_mut("b").unwrap().instance.stop_action_loop();
+        thread::sleep(Duration::from_millis(1000));
+        let emitted = container.check_health(&config);
+        assert_eq!(1, emitted.len());
+        assert_eq!("b", emitted[0].instance_id);
+        assert_eq!(INSTANCE_RESTARTED_SIGNAL, emitted[0].signal.name);
+        assert_eq!(json!(true), emitted[0].signal.payload["restarted"]);
+        assert_eq!(
+            emitted[0],
+            signals.recv_timeout(Duration::from_millis(1000)).unwrap()
+        );
+
+        assert!(container.check_health(&config).is_empty());
+    }
+
+    #[test]
+    fn can_find_instances_implementing_trait() {
+        let mut container = Container::new();
+        container.add_instance(
+            "chat",
+            test_instance(test_dna(&[("messages", &["messaging"]), ("profile", &[])])),
+        );
+        container.add_instance(
+            "mail",
+            test_instance(test_dna(&[("inbox", &["messaging", "search"])])),
+        );
+        container.add_instance("empty", test_instance(Dna::new()));
+
+        let found = container.instances_implementing("messaging");
+        assert_eq!(2, found.len());
+        assert_eq!(Some(&vec!["messages".to_string()]), found.get("chat"));
+        assert_eq!(Some(&vec!["inbox".to_string()]), found.get("mail"));
+
+        let found = container.instances_implementing("search");
+        assert_eq!(vec!["mail".to_string()], found.keys().cloned().collect::<Vec<_>>());
+
+        assert!(container.instances_implementing("missing").is_empty());
+    }
+
+    #[test]
+    fn can_receive_signals_from_all_instances() {
+        let mut container = Container::new();
+        container.add_instance("a", test_instance(Dna::new()));
+        container.add_instance("b", test_instance(Dna::new()));
+        let signals = container.signals();
+
+        let signal = Signal {
+            zome: "test_zome".to_string(),
+            name: "new_post".to_string(),
+            payload: json!({"title": "hello"}),
+        };
+        for id in &["a", "b"] {
+            let instance = container.instance(id).unwrap();
+            instance.instance.state().nucleus().signal_bus().emit(&signal);
+        }
+
+        let mut received = (0..2)
+            .map(|_| signals.recv_timeout(Duration::from_millis(1000)).unwrap())
+            .collect::<Vec<InstanceSignal>>();
+        received.sort_by(|a, b| a.instance_id.cmp(&b.instance_id));
+        assert_eq!("a", received[0].instance_id);
+        assert_eq!("b", received[1].instance_id);
+        assert_eq!(signal, received[1].signal);
+    }
+}
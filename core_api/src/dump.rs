@@ -0,0 +1,197 @@
+//! state dumps for debugging: a JSON report of what an instance has on its chain, holds for the
+//! DHT, has waiting in validation limbo and knows of its peers
+//! dumps are taken through the admin API, see Holochain::dump_state(), and loaded back with
+//! StateDump::from_json() to be looked into offline, away from the node they were taken on
+
+use holochain_core::{
+    dht::{self, DhtStats}, error::HolochainError, state::State,
+    validation::{pool::ValidationPool, ValidationStatus},
+};
+use serde_json;
+
+/// summary of the source chain
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ChainDump {
+    /// hash of the header on top of the chain, None if nothing was committed
+    pub top_hash: Option<String>,
+    /// entry type of the entry on top of the chain
+    pub top_entry_type: Option<String>,
+    /// bytes of entry content committed
+    pub bytes: u64,
+    /// whether commits are staged, see agent::Action::BeginStaging
+    pub staging: bool,
+    pub init_complete: bool,
+}
+
+/// what is held for the DHT
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DhtDump {
+    pub stats: DhtStats,
+    /// addresses of the held entries, sorted
+    pub holdings: Vec<String>,
+}
+
+/// an item waiting in the validation queue
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LimboDump {
+    pub address: String,
+    /// address of the dependency the item is parked on, None if it is waiting for a worker
+    pub awaiting: Option<String>,
+    pub waiting_ms: u64,
+}
+
+/// a peer the DHT knows of
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PeerDump {
+    pub id: String,
+    /// the storage arc of the peer
+    pub center: u32,
+    pub half_length: u32,
+    pub last_seen_secs: u64,
+}
+
+/// the full state of an instance as of when it was dumped
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StateDump {
+    pub chain: ChainDump,
+    pub dht: DhtDump,
+    /// empty unless the validation pool is started
+    pub limbo: Vec<LimboDump>,
+    /// peers the DHT knows of, sorted by id
+    pub peers: Vec<PeerDump>,
+    /// address the instance is on the network under, None if it isn't connected
+    pub network_address: Option<String>,
+    /// agents direct messages were exchanged with, sorted
+    pub messaged: Vec<String>,
+}
+
+impl StateDump {
+    /// take a dump of the state and, if there is one, the validation pool
+    pub fn new(state: &State, validation_pool: Option<&ValidationPool>) -> StateDump {
+        let agent = state.agent();
+        let top_pair = agent.top_pair();
+        let mut peers: Vec<PeerDump> = state
+            .dht()
+            .peers()
+            .into_iter()
+            .map(|peer| PeerDump {
+                id: peer.id,
+                center: peer.arc.center,
+                half_length: peer.arc.half_length,
+                last_seen_secs: peer.last_seen.elapsed().as_secs(),
+            })
+            .collect();
+        peers.sort_by(|a, b| a.id.cmp(&b.id));
+        let limbo = validation_pool
+            .map(|pool| pool.limbo())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|item| LimboDump {
+                address: item.address,
+                awaiting: match item.status {
+                    ValidationStatus::Pending => None,
+                    ValidationStatus::AwaitingDeps(dependency) => Some(dependency),
+                },
+                waiting_ms: item.waiting.as_millis() as u64,
+            })
+            .collect();
+        let messenger = state.nucleus().messenger().clone();
+
+        StateDump {
+            chain: ChainDump {
+                top_hash: top_pair.as_ref().map(|pair| pair.key()),
+                top_entry_type: top_pair
+                    .as_ref()
+                    .map(|pair| pair.header().entry_type().to_string()),
+                bytes: agent.chain_bytes(),
+                staging: agent.is_staging(),
+                init_complete: agent.init_complete(),
+            },
+            dht: DhtDump {
+                stats: dht::stats(&state.dht()),
+                holdings: state.dht().held_addresses(),
+            },
+            limbo,
+            peers,
+            network_address: messenger.address(),
+            messaged: messenger.peers(),
+        }
+    }
+
+    /// load a dump, e.g. from a file written by an operator
+    pub fn from_json(json: &str) -> Result<StateDump, HolochainError> {
+        serde_json::from_str(json).map_err(|e| HolochainError::new(&e.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("StateDump should serialize")
+    }
+
+    /// the things worth looking at first, one per line
+    pub fn summary(&self) -> Vec<String> {
+        let mut summary = vec![
+            format!(
+                "chain: top {}, {} bytes{}",
+                self.chain.top_hash.as_ref().map_or("none", String::as_str),
+                self.chain.bytes,
+                if self.chain.staging { ", staging" } else { "" }
+            ),
+            format!(
+                "dht: {} entries held, {} bytes, arc coverage {:.2}",
+                self.dht.holdings.len(),
+                self.dht.stats.storage_bytes,
+                self.dht.stats.arc_coverage
+            ),
+            format!(
+                "peers: {} known, {} stale",
+                self.peers.len(),
+                self.dht.stats.stale_peers
+            ),
+            format!("limbo: {} items", self.limbo.len()),
+        ];
+        for item in &self.limbo {
+            if let Some(ref dependency) = item.awaiting {
+                summary.push(format!(
+                    "  {} waiting on {} for {}ms",
+                    item.address, dependency, item.waiting_ms
+                ));
+            }
+        }
+        summary
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core::{
+        dht::StorageArc, hash_table::entry::Entry, instance::Instance, state::Action::Dht,
+    };
+
+    #[test]
+    fn can_dump_state() {
+        let mut instance = Instance::new();
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Dht(dht::Action::Hold(Entry::new("post", "a"))));
+        instance.dispatch_and_wait(Dht(dht::Action::PeerSeen(
+            "alice".to_string(),
+            StorageArc::new(0, 1),
+        )));
+
+        let dump = StateDump::new(&instance.state(), None);
+        assert_eq!(vec![Entry::new("post", "a").key()], dump.dht.holdings);
+        assert_eq!(Some(&1), dump.dht.stats.holdings_by_type.get("post"));
+        assert_eq!(1, dump.peers.len());
+        assert_eq!("alice", dump.peers[0].id);
+        assert!(dump.limbo.is_empty());
+        assert_eq!(None, dump.network_address);
+    }
+
+    #[test]
+    fn can_import_dump() {
+        let dump = StateDump::new(&Instance::new().state(), None);
+        assert_eq!(Ok(dump.clone()), StateDump::from_json(&dump.to_json()));
+        assert!(StateDump::from_json("{}").is_err());
+        assert_eq!("limbo: 0 items", dump.summary()[3]);
+    }
+}
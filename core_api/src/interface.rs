@@ -0,0 +1,744 @@
+//! interfaces are how UIs and other clients reach the instances of a container, e.g. over
+//! JSON-RPC on a WebSocket
+//! every interface authenticates connections before they can call anything:
+//! - "token" connections present one of the interface's static tokens
+//! - "challenge" connections are sent a random nonce and answer it signed with the interface's
+//!   shared secret, see sign_challenge(), so the secret itself never goes over the wire
+//!
+//! admin interfaces only accept connections from localhost unless allow_remote is set, and any
+//! interface can be scoped to some of the container's instances
+//!
+//! browsers tell the origin of the page opening a connection, those from origins not in the
+//! interface's allow-list are rejected and the allowed ones get CORS headers, see cors_headers()
+//! clients that aren't browsers send no origin and are left to authentication
+//!
+//! with a rate_limit, each connection can only make so many calls, see rate_limit
+//!
+//! connections talk JSON unless the client offers an encoding the interface also speaks, e.g.
+//! MessagePack, see Connection::negotiate()
+//!
+//! clients can watch addresses or entry types of instances through their connection, the
+//! subscriptions last until the connection is dropped, see Connection::subscribe()
+//!
+//! interfaces speak JSON-RPC on a WebSocket, or with the "grpc" driver serve the Conductor
+//! service of holochain_core_api_grpc
+
+use container::{Container, InstanceSignal};
+use holochain_core::{dht::subscriptions::Target, error::HolochainError, signal::Signal};
+use holochain_serialization::Encoding;
+use rand::{OsRng, Rng};
+use rate_limit::{RateLimit, RateLimited, TokenBucket};
+use rust_base58::ToBase58;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{net::IpAddr, sync::mpsc::Receiver, time::Instant};
+
+/// origin in an allow-list allowing any origin
+pub const ANY_ORIGIN: &str = "*";
+
+/// how connections to an interface authenticate
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum AuthConfiguration {
+    /// the connection presents one of the tokens
+    #[serde(rename = "token")]
+    Token { tokens: Vec<String> },
+    /// the connection signs a nonce with the shared secret
+    #[serde(rename = "challenge")]
+    Challenge { secret: String },
+}
+
+impl AuthConfiguration {
+    pub fn check(&self) -> Result<(), HolochainError> {
+        let usable = match *self {
+            AuthConfiguration::Token { ref tokens } => {
+                !tokens.is_empty() && tokens.iter().all(|token| !token.is_empty())
+            }
+            AuthConfiguration::Challenge { ref secret } => !secret.is_empty(),
+        };
+        if usable {
+            Ok(())
+        } else {
+            Err(HolochainError::new("interface tokens and secrets can't be empty"))
+        }
+    }
+}
+
+/// how clients talk to an interface
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum InterfaceDriver {
+    /// JSON-RPC on a WebSocket
+    #[default]
+    #[serde(rename = "websocket")]
+    WebSocket,
+    /// the Conductor service of holochain_core_api_grpc
+    #[serde(rename = "grpc")]
+    Grpc,
+}
+
+/// configuration of a single interface
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InterfaceConfiguration {
+    pub id: String,
+    /// whether the interface can manage the container rather than only call instances
+    #[serde(default)]
+    pub admin: bool,
+    /// let an admin interface accept connections from other hosts than localhost
+    #[serde(default)]
+    pub allow_remote: bool,
+    pub auth: AuthConfiguration,
+    /// ids of the instances the interface gives access to, all of them if not set
+    #[serde(default)]
+    pub instances: Option<Vec<String>>,
+    /// origins browser pages connecting can come from, e.g. "https://app.example.org", none if
+    /// not set
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    /// calls each connection can make, not limited if not set
+    #[serde(default)]
+    pub rate_limit: Option<RateLimit>,
+    /// encodings connections can switch to besides JSON, e.g. "msgpack"
+    #[serde(default)]
+    pub encodings: Vec<Encoding>,
+    /// WebSocket unless set
+    #[serde(default)]
+    pub driver: InterfaceDriver,
+}
+
+/// checks an allow-list entry is "*" or a bare scheme://host[:port] as browsers send it
+fn check_origin(origin: &str) -> Result<(), HolochainError> {
+    if origin == ANY_ORIGIN {
+        return Ok(());
+    }
+    let well_formed = match origin.find("://") {
+        Some(i) => {
+            let (scheme, host) = (&origin[..i], &origin[i + 3..]);
+            !scheme.is_empty()
+                && scheme.bytes().all(|b| b.is_ascii_alphanumeric() || b"+-.".contains(&b))
+                && !host.is_empty()
+                && !host.contains(&['/', '?', '#', '@'][..])
+        }
+        None => false,
+    };
+    if well_formed {
+        Ok(())
+    } else {
+        Err(HolochainError::new(&format!(
+            "allowed origin '{}' is not of the form scheme://host[:port]",
+            origin
+        )))
+    }
+}
+
+impl InterfaceConfiguration {
+    /// whether connections from peer are accepted at all
+    pub fn accepts_peer(&self, peer: &IpAddr) -> bool {
+        !self.admin || self.allow_remote || peer.is_loopback()
+    }
+
+    /// checks the credentials, allowed origins and rate limit are usable
+    pub fn check(&self) -> Result<(), HolochainError> {
+        self.auth.check()?;
+        if self.rate_limit.is_some_and(|limit| !limit.is_valid()) {
+            return Err(HolochainError::new(&format!(
+                "rate limit of interface '{}' has to allow calls",
+                self.id
+            )));
+        }
+        for origin in &self.allowed_origins {
+            check_origin(origin)?;
+        }
+        Ok(())
+    }
+
+    /// whether a connection with the given Origin header is accepted
+    /// connections without one don't come from a browser page and are accepted, the token or
+    /// challenge still authenticates them
+    pub fn allows_origin(&self, origin: Option<&str>) -> bool {
+        match origin {
+            Some(origin) => self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == ANY_ORIGIN || allowed.eq_ignore_ascii_case(origin)),
+            None => true,
+        }
+    }
+
+    /// the CORS headers answering a request with the given Origin header, none for requests
+    /// without one or from origins not allowed
+    pub fn cors_headers(&self, origin: Option<&str>) -> Vec<(String, String)> {
+        let origin = match origin {
+            Some(origin) if self.allows_origin(Some(origin)) => origin,
+            _ => return Vec::new(),
+        };
+        vec![
+            ("Access-Control-Allow-Origin", origin),
+            ("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+            ("Access-Control-Allow-Headers", "Authorization, Content-Type"),
+            ("Vary", "Origin"),
+        ].into_iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    /// whether the interface gives access to the instance with the given id
+    pub fn exposes(&self, instance_id: &str) -> bool {
+        match self.instances {
+            Some(ref ids) => ids.iter().any(|id| id == instance_id),
+            None => true,
+        }
+    }
+
+    /// start authenticating a new connection from peer, e.g. on a WebSocket upgrade, with the
+    /// Origin header of the request if any
+    /// fails for peers and origins the interface doesn't accept
+    pub fn connect(
+        &self,
+        peer: &IpAddr,
+        origin: Option<&str>,
+    ) -> Result<Connection<'_>, HolochainError> {
+        if !self.accepts_peer(peer) {
+            return Err(HolochainError::new(&format!(
+                "admin interface '{}' only accepts connections from localhost",
+                self.id
+            )));
+        }
+        if !self.allows_origin(origin) {
+            return Err(HolochainError::new(&format!(
+                "interface '{}' doesn't accept connections from origin {}",
+                self.id,
+                origin.unwrap_or_default()
+            )));
+        }
+        let nonce = match self.auth {
+            AuthConfiguration::Challenge { .. } => {
+                let mut rng = OsRng::new().map_err(|e| HolochainError::new(&e.to_string()))?;
+                Some(rng.gen::<[u8; 32]>().to_base58())
+            }
+            AuthConfiguration::Token { .. } => None,
+        };
+        Ok(Connection {
+            interface: self,
+            nonce,
+            authenticated: false,
+            encoding: Encoding::Json,
+            bucket: self
+                .rate_limit
+                .map(|limit| TokenBucket::new(limit, Instant::now())),
+            subscriptions: Vec::new(),
+        })
+    }
+
+    /// a new connection from peer answering a challenge sent on another one, for transports
+    /// where every call comes on its own, e.g. gRPC
+    /// the nonce has to be one the interface sent, whoever resumes is to check that
+    pub fn resume(
+        &self,
+        peer: &IpAddr,
+        origin: Option<&str>,
+        nonce: &str,
+    ) -> Result<Connection<'_>, HolochainError> {
+        let mut connection = self.connect(peer, origin)?;
+        if connection.nonce.is_some() {
+            connection.nonce = Some(nonce.to_string());
+        }
+        Ok(connection)
+    }
+}
+
+/// the answer to a challenge: the HMAC-SHA256 of the nonce keyed with the shared secret
+pub fn sign_challenge(secret: &str, nonce: &str) -> String {
+    hmac_sha256(secret.as_bytes(), nonce.as_bytes()).to_base58()
+}
+
+/// HMAC of RFC 2104, like the HMAC-SHA512 of holochain_core's mnemonics
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut block = [0; 64];
+    if key.len() > block.len() {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let mut inner = Sha256::default();
+    inner.input(&block.iter().map(|byte| byte ^ 0x36).collect::<Vec<u8>>());
+    inner.input(message);
+    let mut outer = Sha256::default();
+    outer.input(&block.iter().map(|byte| byte ^ 0x5c).collect::<Vec<u8>>());
+    outer.input(&inner.result());
+    outer.result().to_vec()
+}
+
+/// compare without giving away through timing how much of a guess was right
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a
+            .bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// a connection to an interface, nothing can be called through it until it is authenticated
+#[derive(Debug)]
+pub struct Connection<'a> {
+    interface: &'a InterfaceConfiguration,
+    nonce: Option<String>,
+    authenticated: bool,
+    encoding: Encoding,
+    bucket: Option<TokenBucket>,
+    /// what the connection watches, by id of the instance, see subscribe()
+    subscriptions: Vec<(String, Receiver<Signal>)>,
+}
+
+impl<'a> Connection<'a> {
+    /// the nonce to send the client for challenge authentication
+    pub fn challenge(&self) -> Option<&str> {
+        self.nonce.as_deref()
+    }
+
+    /// authenticate with a token or the signed challenge, depending on the interface
+    /// a challenge can only be answered once, failing consumes it
+    pub fn authenticate(&mut self, credential: &str) -> Result<(), HolochainError> {
+        let valid = match self.interface.auth {
+            AuthConfiguration::Token { ref tokens } => {
+                // check every token so timing doesn't tell which one was close
+                tokens
+                    .iter()
+                    .fold(false, |valid, token| same(token, credential) | valid)
+            }
+            AuthConfiguration::Challenge { ref secret } => match self.nonce.take() {
+                Some(nonce) => same(&sign_challenge(secret, &nonce), credential),
+                None => false,
+            },
+        };
+        self.authenticated = valid;
+        if valid {
+            Ok(())
+        } else {
+            Err(HolochainError::new(&format!(
+                "authentication to interface '{}' failed",
+                self.interface.id
+            )))
+        }
+    }
+
+    pub fn authenticated(&self) -> bool {
+        self.authenticated
+    }
+
+    /// switch to the first of the encodings the client offers, most preferred first, that the
+    /// interface speaks, JSON if there is none, and give back the one to tell the client
+    pub fn negotiate(&mut self, offered: &[Encoding]) -> Encoding {
+        self.encoding = Encoding::negotiate(offered, &self.interface.encodings);
+        self.encoding
+    }
+
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    /// a message to the client in the encoding of the connection
+    pub fn encode<T: Serialize>(&self, message: &T) -> Result<Vec<u8>, HolochainError> {
+        self.encoding
+            .encode(message)
+            .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))
+    }
+
+    /// a message from the client in the encoding of the connection
+    pub fn decode<T: DeserializeOwned>(&self, bytes: &[u8]) -> Result<T, HolochainError> {
+        self.encoding
+            .decode(bytes)
+            .map_err(|e| HolochainError::ErrorGeneric(e.to_string()))
+    }
+
+    /// check the connection may call the instance with the given id
+    pub fn authorize(&self, instance_id: &str) -> Result<(), HolochainError> {
+        self.check_authenticated()?;
+        if self.interface.exposes(instance_id) {
+            Ok(())
+        } else {
+            Err(HolochainError::new(&format!(
+                "instance '{}' is not accessible through interface '{}'",
+                instance_id, self.interface.id
+            )))
+        }
+    }
+
+    /// check the connection may manage the container
+    pub fn authorize_admin(&self) -> Result<(), HolochainError> {
+        self.check_authenticated()?;
+        if self.interface.admin {
+            Ok(())
+        } else {
+            Err(HolochainError::new(&format!(
+                "interface '{}' is not an admin interface",
+                self.interface.id
+            )))
+        }
+    }
+
+    /// count a call against the connection's rate limit, e.g. before every zome call
+    /// clients calling too often are told when to retry
+    pub fn throttle(&mut self) -> Result<(), RateLimited> {
+        match self.bucket {
+            Some(ref mut bucket) => bucket.take(Instant::now()),
+            None => Ok(()),
+        }
+    }
+
+    /// watch an address or entry type of the instance with the given id for as long as the
+    /// connection is open, see dht::subscriptions
+    pub fn subscribe(
+        &mut self,
+        container: &Container,
+        instance_id: &str,
+        target: Target,
+    ) -> Result<(), HolochainError> {
+        self.authorize(instance_id)?;
+        let instance = container.instance(instance_id).ok_or_else(|| {
+            HolochainError::new(&format!("instance '{}' is not running", instance_id))
+        })?;
+        self.subscriptions
+            .push((instance_id.to_string(), instance.subscribe(target)));
+        Ok(())
+    }
+
+    /// the signals of the subscriptions received since last asked, e.g. to push them to the
+    /// client
+    pub fn notifications(&self) -> Vec<InstanceSignal> {
+        self.subscriptions
+            .iter()
+            .flat_map(|(instance_id, signals)| {
+                signals.try_iter().map(move |signal| InstanceSignal {
+                    instance_id: instance_id.clone(),
+                    signal,
+                })
+            })
+            .collect()
+    }
+
+    fn check_authenticated(&self) -> Result<(), HolochainError> {
+        if self.authenticated {
+            Ok(())
+        } else {
+            Err(HolochainError::new("connection is not authenticated"))
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use container::tests::test_instance;
+    use holochain_core::{
+        dht, hash_table::entry::Entry, state::Action::Dht,
+    };
+    use holochain_dna::Dna;
+    use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+    pub fn test_interface(auth: AuthConfiguration) -> InterfaceConfiguration {
+        InterfaceConfiguration {
+            id: "ui".to_string(),
+            admin: false,
+            allow_remote: false,
+            auth,
+            instances: Some(vec!["app".to_string()]),
+            allowed_origins: vec!["https://app.example.org".to_string()],
+            rate_limit: None,
+            encodings: Vec::new(),
+            driver: InterfaceDriver::WebSocket,
+        }
+    }
+
+    pub fn test_tokens() -> AuthConfiguration {
+        AuthConfiguration::Token {
+            tokens: vec!["first".to_string(), "second".to_string()],
+        }
+    }
+
+    fn localhost() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))
+    }
+
+    fn remote() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(10, 0, 0, 7))
+    }
+
+    #[test]
+    fn can_authenticate_with_token() {
+        let interface = test_interface(test_tokens());
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        assert_eq!(None, connection.challenge());
+        assert!(connection.authorize("app").is_err());
+
+        assert!(connection.authenticate("wrong").is_err());
+        assert!(connection.authenticate("secon").is_err());
+        assert!(!connection.authenticated());
+        assert_eq!(Ok(()), connection.authenticate("second"));
+        assert!(connection.authenticated());
+        assert_eq!(Ok(()), connection.authorize("app"));
+    }
+
+    #[test]
+    fn can_authenticate_with_challenge() {
+        let interface = test_interface(AuthConfiguration::Challenge {
+            secret: "shared".to_string(),
+        });
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        let nonce = connection.challenge().unwrap().to_string();
+        assert_ne!(
+            Some(nonce.as_str()),
+            interface.connect(&remote(), None).unwrap().challenge()
+        );
+
+        assert_eq!(Ok(()), connection.authenticate(&sign_challenge("shared", &nonce)));
+        assert_eq!(None, connection.challenge());
+
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        let nonce = connection.challenge().unwrap().to_string();
+        assert!(connection.authenticate(&sign_challenge("guess", &nonce)).is_err());
+        // the challenge is used up
+        assert!(connection.authenticate(&sign_challenge("shared", &nonce)).is_err());
+        assert!(connection.authorize("app").is_err());
+
+        // the answer goes on another connection
+        let mut resumed = interface.resume(&remote(), None, &nonce).unwrap();
+        assert_eq!(Ok(()), resumed.authenticate(&sign_challenge("shared", &nonce)));
+        let tokens = test_interface(test_tokens());
+        assert_eq!(None, tokens.resume(&remote(), None, &nonce).unwrap().challenge());
+    }
+
+    #[test]
+    fn hmac_sha256_matches_rfc_4231() {
+        let expected = [
+            0x5b, 0xdc, 0xc1, 0x46, 0xbf, 0x60, 0x75, 0x4e, 0x6a, 0x04, 0x24, 0x26, 0x08, 0x95,
+            0x75, 0xc7, 0x5a, 0x00, 0x3f, 0x08, 0x9d, 0x27, 0x39, 0x83, 0x9d, 0xec, 0x58, 0xb9,
+            0x64, 0xec, 0x38, 0x43,
+        ];
+        assert_eq!(
+            expected.to_vec(),
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?")
+        );
+        // keys longer than a block are hashed first
+        let long = [0xaa; 131];
+        let expected = [
+            0x60, 0xe4, 0x31, 0x59, 0x1e, 0xe0, 0xb6, 0x7f, 0x0d, 0x8a, 0x26, 0xaa, 0xcb, 0xf5,
+            0xb7, 0x7f, 0x8e, 0x0b, 0xc6, 0x21, 0x37, 0x28, 0xc5, 0x14, 0x05, 0x46, 0x04, 0x0f,
+            0x0e, 0xe3, 0x7f, 0x54,
+        ];
+        assert_eq!(
+            expected.to_vec(),
+            hmac_sha256(&long, b"Test Using Larger Than Block-Size Key - Hash Key First")
+        );
+    }
+
+    #[test]
+    fn can_subscribe_while_connected() {
+        let mut container = Container::new();
+        container.add_instance("app", test_instance(Dna::new()));
+        container.add_instance("other", test_instance(Dna::new()));
+        let interface = test_interface(test_tokens());
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        let posts = || Target::EntryType("post".to_string());
+        assert!(connection.subscribe(&container, "app", posts()).is_err());
+
+        connection.authenticate("first").unwrap();
+        assert!(connection.subscribe(&container, "other", posts()).is_err());
+        assert_eq!(Ok(()), connection.subscribe(&container, "app", posts()));
+        let hold = |container: &mut Container, content: &str| {
+            let instance = &mut container.instance_mut("app").unwrap().instance;
+            instance.dispatch_and_wait(Dht(dht::Action::Hold(Entry::new("post", content))))
+        };
+        hold(&mut container, "hello");
+        let notifications = connection.notifications();
+        assert_eq!(1, notifications.len());
+        assert_eq!("app", notifications[0].instance_id);
+        assert!(connection.notifications().is_empty());
+
+        // the subscriptions go with the connection
+        let state = container.instance("app").unwrap().instance.state().clone();
+        let subscriptions = state.nucleus().subscriptions().clone();
+        drop(connection);
+        hold(&mut container, "bye");
+        assert_eq!(0, subscriptions.len());
+    }
+
+    #[test]
+    fn can_scope_instances() {
+        let interface = test_interface(test_tokens());
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        connection.authenticate("first").unwrap();
+        assert_eq!(Ok(()), connection.authorize("app"));
+        assert!(connection.authorize("other").is_err());
+        assert!(connection.authorize_admin().is_err());
+
+        let interface = InterfaceConfiguration {
+            instances: None,
+            ..test_interface(test_tokens())
+        };
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        connection.authenticate("first").unwrap();
+        assert_eq!(Ok(()), connection.authorize("other"));
+    }
+
+    #[test]
+    fn admin_interfaces_are_local_by_default() {
+        let admin = InterfaceConfiguration {
+            admin: true,
+            ..test_interface(test_tokens())
+        };
+        assert!(admin.connect(&remote(), None).is_err());
+        assert!(admin.accepts_peer(&IpAddr::V6(Ipv6Addr::LOCALHOST)));
+
+        let mut connection = admin.connect(&localhost(), None).unwrap();
+        assert!(connection.authorize_admin().is_err());
+        connection.authenticate("first").unwrap();
+        assert_eq!(Ok(()), connection.authorize_admin());
+
+        let remote_admin = InterfaceConfiguration {
+            allow_remote: true,
+            ..admin
+        };
+        assert!(remote_admin.connect(&remote(), None).is_ok());
+    }
+
+    #[test]
+    fn can_allow_origins() {
+        let interface = test_interface(test_tokens());
+        let origin = Some("https://app.example.org");
+        assert!(interface.connect(&remote(), origin).is_ok());
+        assert!(interface.connect(&remote(), Some("HTTPS://APP.example.org")).is_ok());
+        assert!(interface.connect(&remote(), Some("https://evil.example.org")).is_err());
+        assert!(interface.connect(&remote(), Some("http://app.example.org")).is_err());
+        assert!(interface.connect(&remote(), Some("null")).is_err());
+
+        let headers = interface.cors_headers(origin);
+        assert!(headers.contains(&(
+            "Access-Control-Allow-Origin".to_string(),
+            "https://app.example.org".to_string()
+        )));
+        assert!(headers.contains(&("Vary".to_string(), "Origin".to_string())));
+        assert!(interface.cors_headers(Some("https://evil.example.org")).is_empty());
+        assert!(interface.cors_headers(None).is_empty());
+
+        let closed = InterfaceConfiguration {
+            allowed_origins: Vec::new(),
+            ..test_interface(test_tokens())
+        };
+        assert!(closed.connect(&remote(), origin).is_err());
+        let open = InterfaceConfiguration {
+            allowed_origins: vec![ANY_ORIGIN.to_string()],
+            ..test_interface(test_tokens())
+        };
+        assert!(open.connect(&remote(), Some("https://evil.example.org")).is_ok());
+    }
+
+    #[test]
+    fn can_rate_limit_connections() {
+        let interface = InterfaceConfiguration {
+            rate_limit: Some(RateLimit {
+                calls_per_second: 1,
+                burst: 2,
+            }),
+            ..test_interface(test_tokens())
+        };
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        assert_eq!(Ok(()), connection.throttle());
+        assert_eq!(Ok(()), connection.throttle());
+        let limited = connection.throttle().unwrap_err();
+        assert!(limited.retry_after_ms > 0 && limited.retry_after_ms <= 1000);
+
+        // every connection has a bucket of its own
+        let mut other = interface.connect(&remote(), None).unwrap();
+        assert_eq!(Ok(()), other.throttle());
+
+        let interface = test_interface(test_tokens());
+        let mut unlimited = interface.connect(&remote(), None).unwrap();
+        for _ in 0..100 {
+            assert_eq!(Ok(()), unlimited.throttle());
+        }
+
+        let stalled = InterfaceConfiguration {
+            rate_limit: Some(RateLimit {
+                calls_per_second: 0,
+                burst: 2,
+            }),
+            ..test_interface(test_tokens())
+        };
+        assert!(stalled.check().is_err());
+    }
+
+    #[test]
+    fn can_negotiate_encodings() {
+        let interface = InterfaceConfiguration {
+            encodings: vec![Encoding::MessagePack],
+            ..test_interface(test_tokens())
+        };
+        let mut connection = interface.connect(&remote(), None).unwrap();
+        assert_eq!(Encoding::Json, connection.encoding());
+        assert_eq!(
+            Encoding::MessagePack,
+            connection.negotiate(&[Encoding::MessagePack, Encoding::Json])
+        );
+        let message = json!({"method": "app/blog/main/create_post", "params": {"title": "hi"}});
+        let bytes = connection.encode(&message).unwrap();
+        assert!(bytes.len() < message.to_string().len());
+        assert_eq!(Ok(message), connection.decode(&bytes));
+        assert!(connection
+            .decode::<serde_json::Value>(br#"{"method": "info/instances"}"#)
+            .is_err());
+
+        let json_only = test_interface(test_tokens());
+        let mut connection = json_only.connect(&remote(), None).unwrap();
+        assert_eq!(Encoding::Json, connection.negotiate(&[Encoding::MessagePack]));
+        let configured: InterfaceConfiguration = serde_json::from_str(
+            r#"{"id": "ui", "auth": {"type": "token", "tokens": ["a"]}, "encodings": ["msgpack"]}"#,
+        ).unwrap();
+        assert_eq!(vec![Encoding::MessagePack], configured.encodings);
+    }
+
+    #[test]
+    /// interfaces are WebSocket unless configured otherwise
+    fn can_configure_driver() {
+        let configured: InterfaceConfiguration =
+            serde_json::from_str(r#"{"id": "ui", "auth": {"type": "token", "tokens": ["a"]}}"#)
+                .unwrap();
+        assert_eq!(InterfaceDriver::WebSocket, configured.driver);
+        let configured: InterfaceConfiguration = serde_json::from_str(
+            r#"{"id": "ui", "auth": {"type": "token", "tokens": ["a"]}, "driver": "grpc"}"#,
+        ).unwrap();
+        assert_eq!(InterfaceDriver::Grpc, configured.driver);
+        assert_eq!(Ok(()), configured.check());
+    }
+
+    #[test]
+    fn fails_on_malformed_origins() {
+        let interface = |origin: &str| InterfaceConfiguration {
+            allowed_origins: vec![origin.to_string()],
+            ..test_interface(test_tokens())
+        };
+        assert_eq!(Ok(()), interface("*").check());
+        assert_eq!(Ok(()), interface("http://localhost:8888").check());
+        assert!(interface("app.example.org").check().is_err());
+        assert!(interface("https://app.example.org/").check().is_err());
+        assert!(interface("https://").check().is_err());
+        assert!(interface("https://user@app.example.org").check().is_err());
+    }
+
+    #[test]
+    fn fails_on_empty_credentials() {
+        assert_eq!(Ok(()), test_tokens().check());
+        assert!(AuthConfiguration::Token { tokens: vec![] }.check().is_err());
+        assert!(
+            AuthConfiguration::Token {
+                tokens: vec!["".to_string()],
+            }.check()
+                .is_err()
+        );
+        assert!(
+            AuthConfiguration::Challenge {
+                secret: String::new(),
+            }.check()
+                .is_err()
+        );
+    }
+}
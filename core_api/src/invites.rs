@@ -0,0 +1,201 @@
+//! public keys and invites to join an instance go from device to device and friend to friend as
+//! compact strings, short enough for a QR code and safe to paste: a prefix saying what the
+//! string holds, then base58 of the payload and a checksum of it, see encode()
+//! importing a string checks the prefix and the checksum, so a misread or mangled code is
+//! refused instead of giving a wrong key, and what it holds, see import_key() and Invite
+//! an invite names the DNA to run by hash, with the uuid of the network if it has its own, the
+//! dna_sources it can be fetched from and the agents to reach first on the network, see
+//! Invite::instance_config()
+
+use config::InstanceConfiguration;
+use container::DNA_HASH_PREFIX;
+use holochain_core::{error::HolochainError, network::sealing::PublicKey};
+use rust_base58::{FromBase58, ToBase58};
+use serde_json;
+use sha2::{Digest, Sha256};
+use storage::STORAGE_DEFAULT_URI;
+
+/// prefix of the strings holding a public key
+pub const KEY_PREFIX: &str = "hckey:";
+/// prefix of the strings holding an invite
+pub const INVITE_PREFIX: &str = "hcinvite:";
+/// bytes of the checksum after the payload
+const CHECKSUM_BYTES: usize = 4;
+
+fn checksum(prefix: &str, payload: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha256::default();
+    hasher.input(prefix.as_bytes());
+    hasher.input(payload);
+    hasher.result()[..CHECKSUM_BYTES].to_vec()
+}
+
+fn invalid(reason: &str) -> HolochainError {
+    HolochainError::new(&format!("invalid code: {}", reason))
+}
+
+/// the prefix followed by base58 of the payload and its checksum
+pub fn encode(prefix: &str, payload: &[u8]) -> String {
+    let mut bytes = payload.to_vec();
+    bytes.extend(checksum(prefix, payload));
+    format!("{}{}", prefix, bytes.to_base58())
+}
+
+/// the payload of a string made by encode() with the prefix, an error if it has another prefix
+/// or the checksum doesn't match
+/// whitespace around it, e.g. from a scanner, is left out
+pub fn decode(prefix: &str, code: &str) -> Result<Vec<u8>, HolochainError> {
+    let encoded = code
+        .trim()
+        .strip_prefix(prefix)
+        .ok_or_else(|| invalid(&format!("it doesn't start with '{}'", prefix)))?;
+    let mut bytes = encoded
+        .from_base58()
+        .map_err(|_| invalid("it isn't base58"))?;
+    if bytes.len() < CHECKSUM_BYTES {
+        return Err(invalid("it is too short"));
+    }
+    let sum = bytes.split_off(bytes.len() - CHECKSUM_BYTES);
+    if sum == checksum(prefix, &bytes) {
+        Ok(bytes)
+    } else {
+        Err(invalid("the checksum doesn't match"))
+    }
+}
+
+/// the string holding the base58 public key, e.g. of AgentInfo, an error if it isn't a key
+pub fn export_key(public_key: &str) -> Result<String, HolochainError> {
+    Ok(encode(KEY_PREFIX, &PublicKey::from_base58(public_key)?.0))
+}
+
+/// the base58 public key held by a string made by export_key()
+pub fn import_key(code: &str) -> Result<String, HolochainError> {
+    let bytes = decode(KEY_PREFIX, code)?;
+    if bytes.len() == 32 {
+        Ok(bytes.to_base58())
+    } else {
+        Err(invalid("it doesn't hold a 32 byte key"))
+    }
+}
+
+/// what it takes to join the network of an instance
+/// fields have one letter names in the string, so it fits in smaller QR codes
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Invite {
+    /// hash of the DNA, see Dna::hash()
+    #[serde(rename = "d")]
+    pub dna_hash: String,
+    /// the uuid the instance runs the DNA with, see InstanceConfiguration::uuid
+    #[serde(rename = "u", default, skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// where the DNA can be fetched from by its hash, see Configuration::dna_sources
+    #[serde(rename = "s", default, skip_serializing_if = "Vec::is_empty")]
+    pub dna_sources: Vec<String>,
+    /// addresses of agents on the network to reach first
+    #[serde(rename = "b", default, skip_serializing_if = "Vec::is_empty")]
+    pub bootstrap: Vec<String>,
+    /// base58 public key of the agent inviting, to check who the invite is from
+    #[serde(rename = "i", default, skip_serializing_if = "Option::is_none")]
+    pub inviter: Option<String>,
+}
+
+impl Invite {
+    pub fn new(dna_hash: &str) -> Invite {
+        Invite {
+            dna_hash: dna_hash.to_string(),
+            ..Invite::default()
+        }
+    }
+
+    /// the string holding the invite
+    pub fn to_code(&self) -> String {
+        let json = serde_json::to_string(self).expect("Invite should serialize");
+        encode(INVITE_PREFIX, json.as_bytes())
+    }
+
+    /// the invite held by a string made by to_code(), an error if it doesn't name a DNA or its
+    /// inviter key isn't a key
+    pub fn from_code(code: &str) -> Result<Invite, HolochainError> {
+        let bytes = decode(INVITE_PREFIX, code)?;
+        let invite: Invite =
+            serde_json::from_slice(&bytes).map_err(|_| invalid("it doesn't hold an invite"))?;
+        if invite.dna_hash.is_empty() {
+            return Err(invalid("the invite names no DNA"));
+        }
+        if let Some(ref inviter) = invite.inviter {
+            PublicKey::from_base58(inviter)?;
+        }
+        Ok(invite)
+    }
+
+    /// the configuration of an instance of the agent joining with the invite, running the DNA
+    /// fetched by its hash, the dna_sources of the invite go to the container's configuration
+    pub fn instance_config(&self, id: &str, agent: &str) -> InstanceConfiguration {
+        InstanceConfiguration {
+            id: id.to_string(),
+            dna: format!("{}{}", DNA_HASH_PREFIX, self.dna_hash),
+            uuid: self.uuid.clone(),
+            agent: agent.to_string(),
+            storage: STORAGE_DEFAULT_URI.to_string(),
+            logging: Default::default(),
+            limits: Default::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use holochain_core::network::sealing::KeyPair;
+
+    #[test]
+    /// keys come back as they went, misread codes don't
+    fn keys() {
+        let key = KeyPair::generate().public().to_base58();
+        let code = export_key(&key).unwrap();
+        assert!(code.starts_with(KEY_PREFIX));
+        assert_eq!(Ok(key.clone()), import_key(&format!(" {}\n", code)));
+        assert!(export_key("not a key").is_err());
+
+        // one character off, the wrong prefix, cut short
+        let mut misread = code.clone();
+        let last = misread.pop();
+        misread.push(if last == Some('1') { '2' } else { '1' });
+        assert!(import_key(&misread).is_err());
+        assert!(import_key(&code.replacen(KEY_PREFIX, INVITE_PREFIX, 1)).is_err());
+        assert!(import_key(&code[..code.len() - 8]).is_err());
+        assert!(import_key(KEY_PREFIX).is_err());
+    }
+
+    #[test]
+    fn invites() {
+        let mut invite = Invite::new("QmChat");
+        invite.uuid = Some("friends".to_string());
+        invite.dna_sources = vec!["https://example.com/dnas".to_string()];
+        invite.bootstrap = vec!["alice".to_string()];
+        invite.inviter = Some(KeyPair::generate().public().to_base58());
+        let code = invite.to_code();
+        assert!(code.starts_with(INVITE_PREFIX));
+        assert_eq!(Ok(invite.clone()), Invite::from_code(&code));
+
+        let config = invite.instance_config("chat", "bob");
+        assert_eq!("hash:QmChat", config.dna);
+        assert_eq!(Some("friends".to_string()), config.uuid);
+        assert_eq!("bob", config.agent);
+
+        // the fields left out take no room
+        let bare = Invite::new("QmChat");
+        assert!(bare.to_code().len() < code.len());
+        assert_eq!(Ok(bare), Invite::from_code(&Invite::new("QmChat").to_code()));
+    }
+
+    #[test]
+    fn invalid_invites() {
+        assert!(Invite::from_code(&Invite::new("").to_code()).is_err());
+        let mut invite = Invite::new("QmChat");
+        invite.inviter = Some("alice".to_string());
+        assert!(Invite::from_code(&invite.to_code()).is_err());
+        assert!(Invite::from_code(&encode(INVITE_PREFIX, b"not json")).is_err());
+        assert!(Invite::from_code(&export_key(&KeyPair::generate().public().to_base58()).unwrap())
+            .is_err());
+    }
+}
@@ -1,5 +1,8 @@
 //! holochain_core_api provides a library for container applications to instantiate and run holochain applications.
 //!
+//! Holochain is the supported entry point for embedders, e.g. Electron apps or services: new(),
+//! start(), call(), stop() and state() stay put while the actions and reducers behind them change.
+//!
 //! # Examples
 //!
 //! ``` rust
@@ -53,34 +56,146 @@
 extern crate holochain_agent;
 extern crate holochain_core;
 extern crate holochain_dna;
+extern crate holochain_serialization;
+extern crate rand;
+extern crate rust_base58;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+extern crate sha2;
+#[cfg(test)]
+extern crate tempfile;
 #[cfg(test)]
 extern crate test_utils;
 
+pub mod agents;
+pub mod config;
+pub mod container;
+pub mod dump;
+pub mod interface;
+pub mod invites;
+pub mod rate_limit;
+pub mod sandbox;
+pub mod storage;
+pub mod watchdog;
+
+use dump::StateDump;
 use holochain_core::{
-    context::Context, error::HolochainError, instance::Instance,
-    nucleus::{call_and_wait_for_result, Action::*, FunctionCall, NucleusStatus},
+    agent::{self, membrane::AgentId}, anchors::{self, Path}, context::Context,
+    dht::{self, subscriptions::Target, DhtStats}, error::HolochainError, health::Health,
+    instance::Instance, limits::ResourceLimits, logger::ZomeLogger,
+    network::{
+        config::NetworkConfig, connectivity::NetworkInfo, direct_message::MemoryNetwork,
+        outbox::OutboxMetrics,
+    },
+    nucleus::{
+        call_and_wait_for_result, scheduler::{Schedule, SchedulerConfig}, Action::*,
+        CapabilityGrant, FunctionCall, NucleusStatus,
+    },
+    signal::Signal,
     state::{Action::*, State},
+    validation::{
+        receipts::{PublishStatus, ValidationReceipt}, revalidation::RevalidationReport,
+    },
 };
-use holochain_dna::Dna;
+use holochain_dna::{zome::entry_types::EntryType, Dna};
 use std::{
-    sync::{mpsc::channel, Arc}, time::Duration,
+    path,
+    sync::{
+        mpsc::{channel, Receiver}, Arc,
+    },
+    time::Duration,
 };
 
 /// contains a Holochain application instance
 pub struct Holochain {
     instance: Instance,
-    #[allow(dead_code)]
     context: Arc<Context>,
     active: bool,
 }
 
 impl Holochain {
     /// create a new Holochain instance
+    /// an instance the persister kept the state of, e.g. in a file before the process ended,
+    /// picks up from it instead of initializing again, failing if the state is of another DNA
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate holochain_agent;
+    /// # extern crate holochain_core;
+    /// # extern crate holochain_core_api;
+    /// # extern crate holochain_dna;
+    /// # use holochain_agent::Agent;
+    /// # use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
+    /// # use holochain_core_api::Holochain;
+    /// # use holochain_dna::Dna;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// # let context = Arc::new(Context {
+    /// #     agent: Agent::from_string("bob"),
+    /// #     logger: Arc::new(Mutex::new(SimpleLogger {})),
+    /// #     persister: Arc::new(Mutex::new(SimplePersister::new())),
+    /// # });
+    /// let hc = Holochain::new(Dna::new(), context).expect("couldn't instantiate the app");
+    /// assert!(!hc.active());
+    /// # }
+    /// ```
     pub fn new(dna: Dna, context: Arc<Context>) -> Result<Self, HolochainError> {
-        let mut instance = Instance::new();
+        Holochain::init(dna, context, None)
+    }
+
+    /// create a new Holochain instance for an agent joining with a membrane proof, e.g. an invite
+    /// the instance fails to initialize if the DNA defines validate_agent and it refuses the proof
+    pub fn new_with_membrane_proof(
+        dna: Dna,
+        context: Arc<Context>,
+        membrane_proof: &str,
+    ) -> Result<Self, HolochainError> {
+        Holochain::init(dna, context, Some(membrane_proof))
+    }
+
+    fn init(
+        dna: Dna,
+        context: Arc<Context>,
+        membrane_proof: Option<&str>,
+    ) -> Result<Self, HolochainError> {
         let name = dna.name.clone();
+        // an instance initialized before, e.g. by a process that ended, picks up from its state
+        let saved = context.persister.lock().unwrap().load()?;
+        if let Some(state) = saved.filter(|state| state.nucleus().has_initialized()) {
+            if state.nucleus().dna() != Some(dna) {
+                return Err(HolochainError::new("the state kept is of another DNA"));
+            }
+            let mut instance = Instance::from_state(state);
+            instance
+                .state()
+                .nucleus()
+                .zome_logger()
+                .attach(context.logger.clone(), &name);
+            instance.start_action_loop();
+            context.persister.lock().unwrap().attach(&mut instance)?;
+            context.log(&format!("{} resumed", name))?;
+            return Ok(Holochain {
+                instance,
+                context,
+                active: false,
+            });
+        }
+
+        let mut instance = Instance::new();
+        instance
+            .state()
+            .nucleus()
+            .zome_logger()
+            .attach(context.logger.clone(), &name);
         let action = Nucleus(InitApplication(dna));
         instance.start_action_loop();
+        context.persister.lock().unwrap().attach(&mut instance)?;
+        let agent_id = AgentId::new(&context.agent.address(), membrane_proof);
+        instance.dispatch_and_wait(Nucleus(SetAgentId(agent_id)));
 
         let (sender, receiver) = channel();
 
@@ -118,24 +233,188 @@ impl Holochain {
     }
 
     /// activate the Holochain instance
+    /// scheduled zome functions are only called while the instance is active
+    /// a keystore kept in a file is unlocked first, starting fails if it can't be, one encrypted
+    /// with a passphrase has to be unlocked with it after a stop, see unlock_keystore()
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate holochain_agent;
+    /// # extern crate holochain_core;
+    /// # extern crate holochain_core_api;
+    /// # extern crate holochain_dna;
+    /// # use holochain_agent::Agent;
+    /// # use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
+    /// # use holochain_core_api::Holochain;
+    /// # use holochain_dna::Dna;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// # let context = Arc::new(Context {
+    /// #     agent: Agent::from_string("bob"),
+    /// #     logger: Arc::new(Mutex::new(SimpleLogger {})),
+    /// #     persister: Arc::new(Mutex::new(SimplePersister::new())),
+    /// # });
+    /// # let mut hc = Holochain::new(Dna::new(), context).unwrap();
+    /// hc.start().expect("couldn't start the app");
+    /// assert!(hc.active());
+    /// // starting twice fails
+    /// assert!(hc.start().is_err());
+    /// # }
+    /// ```
     pub fn start(&mut self) -> Result<(), HolochainError> {
         if self.active {
             return Err(HolochainError::InstanceActive);
         }
+        self.instance.state().nucleus().keystore().unlock()?;
+        self.instance.start_scheduler(&SchedulerConfig::default());
         self.active = true;
         Ok(())
     }
 
     /// deactivate the Holochain instance
+    /// a keystore kept in a file is locked, its key material zeroized until the next start
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate holochain_agent;
+    /// # extern crate holochain_core;
+    /// # extern crate holochain_core_api;
+    /// # extern crate holochain_dna;
+    /// # use holochain_agent::Agent;
+    /// # use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
+    /// # use holochain_core_api::Holochain;
+    /// # use holochain_dna::Dna;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// # let context = Arc::new(Context {
+    /// #     agent: Agent::from_string("bob"),
+    /// #     logger: Arc::new(Mutex::new(SimpleLogger {})),
+    /// #     persister: Arc::new(Mutex::new(SimplePersister::new())),
+    /// # });
+    /// # let mut hc = Holochain::new(Dna::new(), context).unwrap();
+    /// hc.start().unwrap();
+    /// hc.stop().expect("couldn't stop the app");
+    /// assert!(!hc.active());
+    /// # }
+    /// ```
     pub fn stop(&mut self) -> Result<(), HolochainError> {
         if !self.active {
             return Err(HolochainError::InstanceNotActive);
         }
+        self.instance.stop_scheduler();
+        self.instance.state().nucleus().keystore().lock();
         self.active = false;
         Ok(())
     }
 
+    /// stop the instance for good: stop taking calls, wait up to timeout for the calls in flight
+    /// and staged commits to finish, save the state, leave the network and end the signals
+    /// fails if the instance wasn't idle in time, the rest is done regardless
+    pub fn shutdown(&mut self, timeout: Duration) -> Result<(), HolochainError> {
+        if self.active {
+            self.stop()?;
+        }
+        self.instance.close_network();
+        let drained = self.instance.wait_until_idle(timeout);
+        self.context
+            .persister
+            .lock()
+            .unwrap()
+            .save(&self.instance.state());
+        self.instance.leave_network();
+        self.instance.state().nucleus().signal_bus().close();
+        if drained {
+            Ok(())
+        } else {
+            Err(HolochainError::ErrorGeneric(format!(
+                "calls still in flight after {}ms",
+                timeout.as_millis()
+            )))
+        }
+    }
+
+    /// how the instance is doing, zome calls running longer than call_budget count as stuck
+    /// see watchdog
+    pub fn health(&self, call_budget: Duration) -> Health {
+        self.instance.health(call_budget)
+    }
+
+    /// save the state for restart() to pick up from, unless the instance is busy changing it
+    /// returns whether it was saved
+    pub fn checkpoint(&self) -> bool {
+        match self.instance.snapshot() {
+            Some(state) => {
+                self.context.persister.lock().unwrap().save(&state);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// replace a wedged instance with one picking up from the state last saved, e.g. by
+    /// checkpoint()
+    /// the action loop of the wedged instance is stopped and the calls it had in flight and
+    /// commits it had staged are abandoned, the new one rejoins the network and is active if the
+    /// wedged one was
+    pub fn restart(&mut self) -> Result<(), HolochainError> {
+        let state = self
+            .context
+            .persister
+            .lock()
+            .unwrap()
+            .load()?
+            .ok_or_else(|| HolochainError::new("no saved state to restart from"))?;
+        self.instance.stop_action_loop();
+        self.instance.stop_scheduler();
+
+        let network = state.nucleus().messenger().network();
+        let mut instance = Instance::from_state(state);
+        instance.start_action_loop();
+        instance.dispatch_and_wait(Nucleus(AbandonCalls));
+        if instance.state().agent().is_staging() {
+            instance.dispatch_and_wait(Agent(agent::Action::AbortStaged));
+        }
+        if let Some(network) = network {
+            instance.join_network(&network, &self.context.agent.address());
+        }
+        if self.active {
+            instance.start_scheduler(&SchedulerConfig::default());
+        }
+        self.instance = instance;
+        Ok(())
+    }
+
     /// call a function in a zome
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate holochain_agent;
+    /// # extern crate holochain_core;
+    /// # extern crate holochain_core_api;
+    /// # extern crate holochain_dna;
+    /// # use holochain_agent::Agent;
+    /// # use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
+    /// # use holochain_core_api::Holochain;
+    /// # use holochain_dna::Dna;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// # let context = Arc::new(Context {
+    /// #     agent: Agent::from_string("bob"),
+    /// #     logger: Arc::new(Mutex::new(SimpleLogger {})),
+    /// #     persister: Arc::new(Mutex::new(SimplePersister::new())),
+    /// # });
+    /// # let mut hc = Holochain::new(Dna::new(), context).unwrap();
+    /// // calls are only taken while the app is active
+    /// assert!(hc.call("test_zome", "test_cap", "main", "{}").is_err());
+    ///
+    /// hc.start().unwrap();
+    /// // the empty DNA has no zomes to call
+    /// assert!(hc.call("test_zome", "test_cap", "main", "{}").is_err());
+    /// # }
+    /// ```
     pub fn call<T: Into<String>>(
         &mut self,
         zome: T,
@@ -152,25 +431,284 @@ impl Holochain {
         call_and_wait_for_result(call, &mut self.instance)
     }
 
+    /// call a function in a zome of another agent's instance, over the network joined
+    /// cap_secret is the secret of a grant from the other agent, needed unless the capability is
+    /// public
+    pub fn call_remote<T: Into<String>>(
+        &self,
+        agent: &str,
+        zome: T,
+        cap: T,
+        fn_name: T,
+        cap_secret: Option<String>,
+        params: T,
+    ) -> Result<String, HolochainError> {
+        if !self.active {
+            return Err(HolochainError::InstanceNotActive);
+        }
+
+        let call = FunctionCall::new(zome.into(), cap.into(), fn_name.into(), params.into());
+
+        let messenger = self.instance.state().nucleus().messenger().clone();
+        messenger.call_remote(
+            agent,
+            &call,
+            cap_secret,
+            messenger.config().direct_message_timeout(),
+        )
+    }
+
+    /// connect the instance to a network under its agent's address, so other agents can call it
+    /// the instance joins the space of the network for the hash of its DNA, so it only talks to
+    /// instances running the same DNA, the uuid included
+    pub fn join_network(&mut self, network: &MemoryNetwork) {
+        let address = self.context.agent.address();
+        let seed = self.dna().map(|dna| dna.hash()).unwrap_or_default();
+        self.instance.join_network(&network.space(&seed), &address);
+    }
+
+    /// set the timeouts and retries of the instance's network traffic
+    pub fn set_network_config(&mut self, config: &NetworkConfig) {
+        self.instance.set_network_config(config);
+    }
+
+    /// disconnect the instance from the network joined, saying goodbye to the agents talked to
+    pub fn leave_network(&mut self) {
+        self.instance.leave_network();
+    }
+
+    /// where the instance stands on the network: its connectivity, peers, arc and when it last
+    /// heard from a peer, changes of connectivity are signalled as "connectivity_changed" too
+    pub fn network_info(&self) -> NetworkInfo {
+        self.instance.network_info()
+    }
+
+    /// the agents that told the instance they are online lately, as long as presence is on in the
+    /// network config
+    pub fn get_online_agents(&self) -> Vec<String> {
+        self.instance.online_agents()
+    }
+
+    /// how many publishes and messages of the instance are waiting for the network, e.g. to show
+    /// whether what was committed offline is synced yet
+    pub fn outbox_depth(&self) -> usize {
+        self.instance.outbox_depth()
+    }
+
+    /// how many publishes and messages of the instance wait at each priority, and how many were
+    /// refused for want of room, see ResourceLimits::max_outbox_depth
+    pub fn outbox_metrics(&self) -> OutboxMetrics {
+        self.instance.outbox_metrics()
+    }
+
+    /// keep what is waiting for the network in the file, so it is sent after a restart too
+    pub fn persist_outbox(&self, path: &path::Path) -> Result<(), HolochainError> {
+        self.instance.persist_outbox(path)
+    }
+
+    /// keep what the instance holds for the DHT in the file, so it is held again after a restart
+    /// what was kept there is held again right away, unless the file turns out corrupted
+    /// returns how many addresses were kept
+    pub fn persist_holdings(&mut self, path: &path::Path) -> Result<usize, HolochainError> {
+        self.instance.persist_holdings(path)
+    }
+
+    /// the validation receipts the holders of an entry the instance published sent for it
+    pub fn get_validation_receipts(&self, address: &str) -> Vec<ValidationReceipt> {
+        self.instance.validation_receipts(address)
+    }
+
+    /// how far the publish of an entry got: whether it is still waiting for the network and how
+    /// many holders sent a receipt for it
+    pub fn publish_status(&self, address: &str) -> PublishStatus {
+        self.instance.publish_status(address)
+    }
+
+    /// keep the validation receipts received in the file, so they are still there after a restart
+    pub fn persist_receipts(&self, path: &path::Path) -> Result<(), HolochainError> {
+        self.instance.persist_receipts(path)
+    }
+
+    /// take the keys zomes derive from the keystore kept in the file, or keep the instance's in
+    /// it, so they are the same after a restart, see holochain_core::agent::keystore
+    pub fn persist_keystore(&self, path: &path::Path) -> Result<(), HolochainError> {
+        self.instance.persist_keystore(path)
+    }
+
+    /// like persist_keystore(), but the file keeps the seed encrypted with the agent's own
+    /// passphrase, fails if it doesn't decrypt the file
+    /// the passphrase isn't kept: the keystore is locked as the instance stops and only unlocks
+    /// with the passphrase again, see unlock_keystore()
+    pub fn persist_encrypted_keystore(
+        &self,
+        path: &path::Path,
+        passphrase: &str,
+    ) -> Result<(), HolochainError> {
+        self.instance.persist_encrypted_keystore(path, passphrase)
+    }
+
+    /// unlock the keystore encrypted with the passphrase, to start the instance again after it
+    /// was stopped
+    pub fn unlock_keystore(&self, passphrase: &str) -> Result<(), HolochainError> {
+        self.instance.unlock_keystore(passphrase)
+    }
+
+    /// let other agents call a capability that isn't public, returns the secret they need to
+    /// pass to call_remote
+    pub fn grant_capability(&mut self, zome: &str, cap: &str) -> String {
+        let grant = CapabilityGrant::new(zome, cap);
+        let secret = grant.secret.clone();
+        self.instance.dispatch_and_wait(Nucleus(GrantCapability(grant)));
+        secret
+    }
+
+    /// stop letting other agents call with a secret from grant_capability
+    pub fn revoke_capability(&mut self, secret: &str) {
+        self.instance.dispatch_and_wait(Nucleus(RevokeCapability(secret.to_string())));
+    }
+
+    /// cap what the instance may use of the host, e.g. from its configuration
+    /// going over a limit fails what would have, and a "resource_limit_exceeded" signal says which
+    pub fn set_resource_limits(&mut self, limits: &ResourceLimits) {
+        self.instance.set_resource_limits(limits);
+    }
+
     /// checks to see if an instance is active
     pub fn active(&self) -> bool {
         self.active
     }
 
-    /// return
+    /// a copy of the current state of the instance, e.g. to inspect what is on its chain
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate holochain_agent;
+    /// # extern crate holochain_core;
+    /// # extern crate holochain_core_api;
+    /// # extern crate holochain_dna;
+    /// # use holochain_agent::Agent;
+    /// # use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
+    /// # use holochain_core_api::Holochain;
+    /// # use holochain_dna::Dna;
+    /// # use std::sync::{Arc, Mutex};
+    /// # fn main() {
+    /// # let context = Arc::new(Context {
+    /// #     agent: Agent::from_string("bob"),
+    /// #     logger: Arc::new(Mutex::new(SimpleLogger {})),
+    /// #     persister: Arc::new(Mutex::new(SimplePersister::new())),
+    /// # });
+    /// # let mut hc = Holochain::new(Dna::new(), context).unwrap();
+    /// let state = hc.state().unwrap();
+    /// assert!(state.nucleus().has_initialized());
+    /// # }
+    /// ```
     pub fn state(&mut self) -> Result<State, HolochainError> {
         Ok(self.instance.state().clone())
     }
+
+    /// summary of what the instance holds for the DHT and what it knows of its peers
+    pub fn dht_stats(&self) -> DhtStats {
+        dht::stats(&self.instance.state().dht())
+    }
+
+    /// the full state of the instance for debugging, see dump
+    pub fn dump_state(&self) -> StateDump {
+        StateDump::new(&self.instance.state(), self.instance.validation_pool())
+    }
+
+    /// receive the signals the instance's zomes emit from now on, e.g. to push them to a UI
+    pub fn signals(&self) -> Receiver<Signal> {
+        self.instance.state().nucleus().signal_bus().subscribe()
+    }
+
+    /// receive a signal for every aspect of the address or the entries of the type the instance
+    /// holds from now on, see dht::subscriptions
+    /// dropping the receiver unsubscribes
+    pub fn subscribe(&self, target: Target) -> Receiver<Signal> {
+        self.instance.state().nucleus().subscriptions().subscribe(target)
+    }
+
+    /// the anchors under the anchor at a path, e.g. "posts/2018" to find the months with posts
+    pub fn anchor_children(&self, path: &str) -> Vec<String> {
+        anchors::children(&self.instance.state().dht(), &Path::parse(path))
+            .iter()
+            .map(|child| child.to_string())
+            .collect()
+    }
+
+    /// keys of the entries linked from the anchor at a path with the tag
+    pub fn anchor_targets(&self, path: &str, tag: &str) -> Vec<String> {
+        anchors::targets(&self.instance.state().dht(), &Path::parse(path), tag)
+    }
+
+    /// the recurring zome function calls the instance's zomes have scheduled
+    pub fn schedules(&self) -> Vec<Schedule> {
+        self.instance
+            .state()
+            .nucleus()
+            .schedules()
+            .values()
+            .cloned()
+            .collect()
+    }
+
+    /// the DNA the instance runs, None until it is initialized
+    pub fn dna(&self) -> Option<Dna> {
+        self.instance.state().nucleus().dna()
+    }
+
+    /// run a new version of the DNA without restarting, e.g. with its zome code or properties
+    /// changed during development
+    /// what the instance holds is validated again under it, the report says which entries fail
+    /// since and are quarantined, and which quarantined ones pass again
+    pub fn reload_dna(&mut self, dna: Dna) -> Result<RevalidationReport, HolochainError> {
+        self.instance.reload_dna(dna)
+    }
+
+    /// definition of an entry type in the running DNA, including the links it declares, e.g. for
+    /// UIs to build forms from
+    pub fn entry_type(&self, name: &str) -> Option<EntryType> {
+        self.instance
+            .state()
+            .nucleus()
+            .dna()
+            .and_then(|dna| dna.get_entry_type(name).cloned())
+    }
+
+    /// where messages zomes log go, e.g. to set per zome log levels
+    pub fn zome_logger(&self) -> ZomeLogger {
+        self.instance.state().nucleus().zome_logger().clone()
+    }
+
+    /// traces of zome calls and the work they caused, in the JSON format the Jaeger UI loads
+    /// service is the name to show the instance under
+    pub fn traces(&self, service: &str) -> serde_json::Value {
+        self.instance.tracer().to_jaeger(service)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use holochain_agent::Agent as HCAgent;
-    use holochain_core::{context::Context, logger::Logger, persister::SimplePersister};
-    use holochain_dna::zome::capabilities::ReservedCapabilityNames;
+    use holochain_core::{
+        context::Context, hash_table::entry::Entry, logger::{Logger, SimpleLogger},
+        network::{
+            config::PresenceConfig, connectivity::Connectivity, direct_message::DirectMessage,
+            Envelope,
+        },
+        persister::{
+            FilePersister, Persister, SimplePersister, FILE_PERSISTER_HOLDINGS,
+            FILE_PERSISTER_KEYSTORE, FILE_PERSISTER_STATE,
+        },
+    };
+    use holochain_dna::zome::{
+        capabilities::{FnDeclaration, ReservedCapabilityNames}, entry_types::LinkedFrom,
+        traits::ZomeTrait, Zome,
+    };
     use std::{
-        fmt, sync::{Arc, Mutex},
+        env, fmt, fs, process, sync::{mpsc::TryRecvError, Arc, Mutex}, thread::sleep,
     };
     use test_utils::{create_test_dna_with_wasm, create_test_dna_with_wat, create_wasm_from_file};
 
@@ -215,7 +753,7 @@ mod tests {
 
         match result {
             Ok(hc) => {
-                assert_eq!(hc.instance.state().nucleus().dna(), Some(dna));
+                assert_eq!(hc.dna(), Some(dna));
                 assert!(!hc.active);
                 assert_eq!(hc.context.agent, agent);
                 assert!(hc.instance.state().nucleus().has_initialized());
@@ -258,6 +796,32 @@ mod tests {
         };
     }
 
+    #[test]
+    fn fails_instantiate_if_trait_not_implemented() {
+        let mut zome_trait = ZomeTrait::new();
+        zome_trait.name = "messaging".to_string();
+        let mut function = FnDeclaration::new();
+        function.name = "send".to_string();
+        zome_trait.functions.push(function);
+
+        let mut zome = Zome::new();
+        zome.name = "test_zome".to_string();
+        zome.traits.push(zome_trait);
+        let mut dna = Dna::new();
+        dna.zomes.push(zome);
+
+        let (context, _test_logger) = test_context(HCAgent::from_string("bob"));
+        let result = Holochain::new(dna, context).map(|_| ());
+
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric(
+                "zome 'test_zome' implements trait 'messaging' but does not export function 'send'"
+                    .to_string()
+            )),
+            result
+        );
+    }
+
     #[test]
     fn fails_instantiate_if_genesis_times_out() {
         let mut dna = create_test_dna_with_wat(
@@ -330,6 +894,76 @@ mod tests {
         assert!(!hc.active());
     }
 
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn can_shutdown() {
+        let persister = Arc::new(Mutex::new(SimplePersister::new()));
+        let context = Arc::new(Context {
+            agent: HCAgent::from_string("bob"),
+            logger: Arc::new(Mutex::new(SimpleLogger {})),
+            persister: persister.clone(),
+        });
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let network = MemoryNetwork::new();
+        hc.join_network(&network);
+        hc.start().unwrap();
+        let signals = hc.signals();
+
+        assert_eq!(Ok(()), hc.shutdown(Duration::from_millis(100)));
+        assert!(!hc.active());
+        // the signals of going offline come through, then they end
+        signals.try_iter().for_each(drop);
+        assert_eq!(Err(TryRecvError::Disconnected), signals.try_recv());
+        assert_eq!(
+            Err(HolochainError::InstanceNotActive),
+            hc.call("test_zome", "test_cap", "main", "")
+        );
+        let goodbye = Envelope::new(DirectMessage::Goodbye("alice".to_string()));
+        let space = network.space(&hc.dna().unwrap().hash());
+        assert!(space.send("bob", goodbye).is_err());
+        assert_eq!(Some(hc.state().unwrap()), persister.lock().unwrap().load().unwrap());
+    }
+
+    #[test]
+    fn can_restart_from_checkpoint() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        assert!(hc.restart().is_err());
+
+        let network = MemoryNetwork::new();
+        hc.join_network(&network);
+        hc.start().unwrap();
+        hc.instance.dispatch_and_wait(Agent(agent::Action::Commit(Entry::new("post", "a"))));
+        assert!(hc.checkpoint());
+        let checkpointed = hc.state().unwrap();
+
+        hc.instance.dispatch_and_wait(Agent(agent::Action::BeginStaging));
+        hc.instance.dispatch_and_wait(Agent(agent::Action::Commit(Entry::new("post", "b"))));
+        assert!(hc.checkpoint());
+        hc.instance.stop_action_loop();
+        sleep(Duration::from_millis(1000));
+        assert!(hc.health(Duration::from_millis(1000)).heartbeat_age > Duration::from_millis(500));
+
+        assert_eq!(Ok(()), hc.restart());
+        assert!(hc.active());
+        assert!(hc.health(Duration::from_millis(1000)).heartbeat_age < Duration::from_millis(400));
+        let state = hc.state().unwrap();
+        assert!(!state.agent().is_staging());
+        assert_eq!(checkpointed.agent().top_pair(), state.agent().top_pair());
+        let goodbye = Envelope::new(DirectMessage::Goodbye("alice".to_string()));
+        let space = network.space(&hc.dna().unwrap().hash());
+        assert!(space.send("bob", goodbye).is_ok());
+    }
+
+    #[test]
+    fn fails_shutdown_with_calls_in_flight() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        hc.instance
+            .dispatch_and_wait(Agent(holochain_core::agent::Action::BeginStaging));
+        assert!(hc.shutdown(Duration::from_millis(20)).is_err());
+    }
+
     #[test]
     fn can_call() {
         let wat = r#"
@@ -385,6 +1019,301 @@ mod tests {
         };
     }
 
+    #[test]
+    fn can_get_dht_stats() {
+        let dna = Dna::new();
+        let agent = HCAgent::from_string("bob");
+        let (context, _) = test_context(agent.clone());
+        let hc = Holochain::new(dna.clone(), context).unwrap();
+
+        let stats = hc.dht_stats();
+        assert!(stats.holdings_by_type.is_empty());
+        assert_eq!(1.0, stats.arc_coverage);
+        assert_eq!(0, stats.peers);
+    }
+
+    #[test]
+    fn can_gate_with_membrane_proof() {
+        let dna = |refusal: &str| {
+            let wat = format!(
+                r#"
+            (module
+                (memory (;0;) 17)
+                (func (export "validate_agent_dispatch") (param $p0 i32) (param $p1 i32) (result i32)
+                    i32.const {}
+                )
+                (data (i32.const 0)
+                    "{}"
+                )
+                (export "memory" (memory 0))
+            )
+        "#,
+                refusal.len(),
+                refusal
+            );
+            create_test_dna_with_wat(
+                "test_zome".to_string(),
+                ReservedCapabilityNames::LifeCycle.as_str().to_string(),
+                Some(&wat),
+            )
+        };
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let refused = Holochain::new_with_membrane_proof(dna("no invite"), context, "forged");
+        assert!(refused.is_err());
+
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new_with_membrane_proof(dna(""), context, "invite").unwrap();
+        let state = hc.state().unwrap();
+        let agent_id = state.nucleus().agent_id().cloned().unwrap();
+        assert_eq!(Some("invite".to_string()), agent_id.membrane_proof);
+        assert_eq!(HCAgent::from_string("bob").address(), agent_id.address);
+    }
+
+    #[test]
+    fn can_get_network_info() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        assert_eq!(Connectivity::Offline, hc.network_info().connectivity);
+
+        hc.join_network(&MemoryNetwork::new());
+        let info = hc.network_info();
+        assert_eq!(Connectivity::Bootstrapping, info.connectivity);
+        assert_eq!(Some(HCAgent::from_string("bob").address()), info.address);
+        assert!(info.peers.is_empty());
+        assert_eq!(None, info.last_gossip_secs);
+        assert_eq!(1.0, info.arc_size);
+    }
+
+    #[test]
+    fn can_get_online_agents() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let network = MemoryNetwork::new();
+        hc.join_network(&network);
+        let heartbeat = Envelope::new(DirectMessage::Heartbeat("alice".to_string()));
+        network
+            .space(&hc.dna().unwrap().hash())
+            .send(&HCAgent::from_string("bob").address(), heartbeat)
+            .unwrap();
+        hc.set_network_config(&NetworkConfig {
+            presence: Some(PresenceConfig::default()),
+            ..NetworkConfig::default()
+        });
+        for _ in 0..100 {
+            if !hc.get_online_agents().is_empty() {
+                break;
+            }
+            sleep(Duration::from_millis(10));
+        }
+        assert_eq!(vec!["alice".to_string()], hc.get_online_agents());
+
+        hc.set_network_config(&NetworkConfig::default());
+        assert!(hc.get_online_agents().is_empty());
+    }
+
+    #[test]
+    fn can_reload_dna() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let mut dna = Dna::new();
+        dna.properties = json!({"max_post_length": 280});
+        let report = hc.reload_dna(dna.clone()).unwrap();
+        assert!(report.quarantined.is_empty());
+        assert_eq!(Some(dna), hc.dna());
+    }
+
+    #[test]
+    fn can_persist_outbox() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let hc = Holochain::new(Dna::new(), context).unwrap();
+        assert_eq!(0, hc.outbox_depth());
+
+        let path = env::temp_dir().join(format!("hc_outbox_{}.json", process::id()));
+        fs::write(&path, "not json").unwrap();
+        assert!(hc.persist_outbox(&path).is_err());
+        fs::write(&path, "[]").unwrap();
+        assert_eq!(Ok(()), hc.persist_outbox(&path));
+        assert_eq!(0, hc.outbox_depth());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn can_persist_holdings() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let path = env::temp_dir().join(format!("hc_holdings_{}.json", process::id()));
+        let _ = fs::remove_file(&path);
+        assert_eq!(Ok(0), hc.persist_holdings(&path));
+        assert!(path.exists());
+
+        fs::write(&path, "not json").unwrap();
+        assert!(hc.persist_holdings(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn can_keep_stores_in_file_storage() {
+        let dir = env::temp_dir().join(format!("hc_file_storage_{}", process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let context = |dir: &path::Path| {
+            Arc::new(Context {
+                agent: HCAgent::from_string("bob"),
+                logger: Arc::new(Mutex::new(SimpleLogger {})),
+                persister: Arc::new(Mutex::new(FilePersister::new(dir))),
+            })
+        };
+        let dna = Dna::new();
+        let mut hc = Holochain::new(dna.clone(), context(&dir)).unwrap();
+        assert!(dir.join(FILE_PERSISTER_HOLDINGS).exists());
+        assert!(dir.join(FILE_PERSISTER_KEYSTORE).exists());
+        let key = hc.state().unwrap().nucleus().keystore().derive_key("app");
+        assert!(key.is_ok());
+        hc.instance.dispatch_and_wait(Agent(agent::Action::Commit(Entry::new("post", "a"))));
+        let top_pair = hc.state().unwrap().agent().top_pair();
+        hc.shutdown(Duration::from_millis(500)).unwrap();
+        assert!(dir.join(FILE_PERSISTER_STATE).exists());
+
+        // a new instance on the same directory picks up what the first one kept
+        let mut other = Holochain::new(dna, context(&dir)).unwrap();
+        assert_eq!(key, other.state().unwrap().nucleus().keystore().derive_key("app"));
+        assert_eq!(top_pair, other.state().unwrap().agent().top_pair());
+        assert!(other.state().unwrap().nucleus().has_initialized());
+
+        assert!(Holochain::new(Dna::new(), context(&dir)).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn can_get_validation_receipts() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let hc = Holochain::new(Dna::new(), context).unwrap();
+        assert!(hc.get_validation_receipts("nowhere").is_empty());
+        let status = hc.publish_status("nowhere");
+        assert!(!status.pending);
+        assert_eq!(0, status.receipts);
+
+        let path = env::temp_dir().join(format!("hc_receipts_{}.json", process::id()));
+        fs::write(&path, "not json").unwrap();
+        assert!(hc.persist_receipts(&path).is_err());
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn can_dump_state() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        hc.join_network(&MemoryNetwork::new());
+        hc.instance.dispatch_and_wait(Agent(agent::Action::Commit(Entry::new("post", "a"))));
+
+        let dump = hc.dump_state();
+        let top_pair = hc.state().unwrap().agent().top_pair().unwrap();
+        assert_eq!(Some(top_pair.key()), dump.chain.top_hash);
+        assert_eq!(Some("post".to_string()), dump.chain.top_entry_type);
+        assert_eq!(Some("bob".to_string()), dump.network_address);
+    }
+
+    #[test]
+    fn can_get_schedules() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        assert!(hc.schedules().is_empty());
+
+        let schedule = Schedule::new("test_zome", "digest", "main", "send_digest", "{}", 60, 0);
+        hc.instance.dispatch_and_wait(Nucleus(
+            holochain_core::nucleus::Action::Schedule(schedule.clone()),
+        ));
+        assert_eq!(vec![schedule], hc.schedules());
+
+        hc.start().unwrap();
+        hc.stop().unwrap();
+    }
+
+    #[test]
+    fn can_subscribe_to_signals() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let hc = Holochain::new(Dna::new(), context).unwrap();
+        let signals = hc.signals();
+
+        let signal = Signal {
+            zome: "test_zome".to_string(),
+            name: "new_post".to_string(),
+            payload: json!({"title": "hello"}),
+        };
+        hc.instance.state().nucleus().signal_bus().emit(&signal);
+        assert_eq!(signal, signals.try_recv().unwrap());
+    }
+
+    #[test]
+    fn can_subscribe_to_entry_types() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let posts = hc.subscribe(Target::EntryType("post".to_string()));
+
+        let post = Entry::new("post", "hello");
+        hc.instance
+            .dispatch_and_wait(Dht(holochain_core::dht::Action::Hold(post.clone())));
+        hc.instance
+            .dispatch_and_wait(Dht(holochain_core::dht::Action::Hold(Entry::new(
+                "comment", "hi",
+            ))));
+        let signal = posts.recv_timeout(Duration::from_millis(1000)).unwrap();
+        assert_eq!("aspect_held", signal.name);
+        assert_eq!(json!(post.key()), signal.payload["address"]);
+        assert!(posts.try_recv().is_err());
+    }
+
+    #[test]
+    fn can_set_resource_limits() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let signals = hc.signals();
+        hc.set_resource_limits(&ResourceLimits {
+            max_dht_bytes: Some(0),
+            ..ResourceLimits::default()
+        });
+
+        hc.instance.dispatch_and_wait(Dht(holochain_core::dht::Action::Hold(Entry::new(
+            "post",
+            "hello",
+        ))));
+        assert_eq!(0, hc.dht_stats().storage_bytes);
+        let signal = signals.recv_timeout(Duration::from_millis(1000)).unwrap();
+        assert_eq!("resource_limit_exceeded", signal.name);
+        assert_eq!(json!("dht_bytes"), signal.payload["resource"]);
+    }
+
+    #[test]
+    fn can_get_entry_type() {
+        let mut dna = Dna::new();
+        let mut zome = holochain_dna::zome::Zome::new();
+        let mut entry_type = EntryType::new();
+        entry_type.name = "post".to_string();
+        entry_type.linked_from.push(LinkedFrom {
+            base_type: "profile".to_string(),
+            tag: "posts".to_string(),
+        });
+        zome.entry_types.push(entry_type.clone());
+        dna.zomes.push(zome);
+
+        let agent = HCAgent::from_string("bob");
+        let (context, _) = test_context(agent.clone());
+        let hc = Holochain::new(dna, context).unwrap();
+
+        assert_eq!(Some(entry_type), hc.entry_type("post"));
+        assert_eq!(None, hc.entry_type("comment"));
+    }
+
+    #[test]
+    fn can_get_traces() {
+        let dna = Dna::new();
+        let agent = HCAgent::from_string("bob");
+        let (context, _) = test_context(agent.clone());
+        let hc = Holochain::new(dna.clone(), context).unwrap();
+
+        assert_eq!(json!({ "data": [] }), hc.traces("bob"));
+    }
+
     #[test]
     fn can_call_test() {
         let wasm = create_wasm_from_file(
@@ -413,6 +1342,92 @@ mod tests {
         };
     }
 
+    #[test]
+    fn can_query_anchors() {
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut hc = Holochain::new(Dna::new(), context).unwrap();
+        let post = Entry::new("post", "hello");
+        let march = Path::parse("posts/2018/03");
+        let mut entries = march.index_entries();
+        entries.extend(Path::parse("posts/2018/04").index_entries());
+        entries.push(march.link_to(&post.key(), "posts").to_entry());
+        for entry in entries {
+            hc.instance.dispatch_and_wait(Dht(dht::Action::Hold(entry)));
+        }
+
+        assert_eq!(vec!["posts".to_string()], hc.anchor_children(""));
+        assert_eq!(
+            vec!["posts/2018/03".to_string(), "posts/2018/04".to_string()],
+            hc.anchor_children("posts/2018")
+        );
+        assert_eq!(vec![post.key()], hc.anchor_targets("posts/2018/03", "posts"));
+        assert!(hc.anchor_targets("posts/2018/04", "posts").is_empty());
+    }
+
+    #[test]
+    fn can_call_remote() {
+        let wasm = create_wasm_from_file(
+            "wasm-test/round_trip/target/wasm32-unknown-unknown/debug/round_trip.wasm",
+        );
+        let dna = create_test_dna_with_wasm("test_zome".to_string(), "test_cap".to_string(), wasm);
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut bob = Holochain::new(dna.clone(), context).unwrap();
+        let (context, _) = test_context(HCAgent::from_string("alice"));
+        let mut alice = Holochain::new(dna.clone(), context).unwrap();
+        alice.start().expect("couldn't start");
+
+        let network = MemoryNetwork::new();
+        bob.join_network(&network);
+        alice.join_network(&network);
+
+        let params = r#"{"input_int_val":2,"input_str_val":"fish"}"#;
+        let call = |secret: Option<String>| {
+            alice.call_remote("bob", "test_zome", "test_cap", "test", secret, params)
+        };
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric(
+                "Capability 'test_cap' of Zome 'test_zome' is not granted to alice".to_string()
+            )),
+            call(None)
+        );
+
+        let secret = bob.grant_capability("test_zome", "test_cap");
+        assert_eq!(
+            Ok(r#"{"input_int_val_plus2":4,"input_str_val_plus_dog":"fish.puppy"}"#.to_string()),
+            call(Some(secret.clone()))
+        );
+
+        bob.revoke_capability(&secret);
+        assert!(call(Some(secret)).is_err());
+
+        bob.leave_network();
+        assert_eq!(
+            Err(HolochainError::ErrorGeneric("agent bob is not reachable".to_string())),
+            call(None)
+        );
+    }
+
+    #[test]
+    fn can_fork_networks_by_uuid() {
+        let dna = Dna::new();
+        let mut forked = dna.clone();
+        forked.uuid = "forked".to_string();
+        let (context, _) = test_context(HCAgent::from_string("bob"));
+        let mut bob = Holochain::new(dna.clone(), context).unwrap();
+        let (context, _) = test_context(HCAgent::from_string("alice"));
+        let mut alice = Holochain::new(forked.clone(), context).unwrap();
+
+        let network = MemoryNetwork::new();
+        bob.join_network(&network);
+        alice.join_network(&network);
+
+        let hello = || Envelope::new(DirectMessage::Heartbeat("carol".to_string()));
+        assert!(network.space(&dna.hash()).send("bob", hello()).is_ok());
+        assert!(network.space(&dna.hash()).send("alice", hello()).is_err());
+        assert!(network.space(&forked.hash()).send("alice", hello()).is_ok());
+        assert!(network.space(&forked.hash()).send("bob", hello()).is_err());
+    }
+
     #[test]
     fn can_call_commit() {
         // Setup the holochain instance
@@ -426,7 +1441,7 @@ mod tests {
 
         // Run the holochain instance
         hc.start().expect("couldn't start");
-        assert_eq!(hc.state().unwrap().history.len(), 4);
+        assert_eq!(hc.state().unwrap().history.len(), 8);
 
         // Call the exposed wasm function that calls the Commit API function
         let result = hc.call("test_zome", "test_cap", "test", r#"{}"#);
@@ -443,6 +1458,6 @@ mod tests {
         };
 
         // Check in holochain instance's history that the commit event has been processed
-        assert_eq!(hc.state().unwrap().history.len(), 7);
+        assert_eq!(hc.state().unwrap().history.len(), 11);
     }
 }
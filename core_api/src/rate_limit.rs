@@ -0,0 +1,145 @@
+//! rate limiting of the zome calls made through an interface connection, so a buggy UI can't
+//! saturate the ribosome
+//! every connection gets a token bucket: a call takes a token, tokens come back at a steady rate
+//! and a connection that was quiet can make up to burst calls at once
+
+use std::{fmt, time::Instant};
+
+/// how many calls a connection can make
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// calls a connection can make over time
+    pub calls_per_second: u32,
+    /// calls a connection can make at once
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn is_valid(&self) -> bool {
+        self.calls_per_second > 0 && self.burst > 0
+    }
+}
+
+/// the error clients get when calling too often
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RateLimited {
+    /// how long until the next call is let through
+    pub retry_after_ms: u64,
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "rate limited, retry after {}ms", self.retry_after_ms)
+    }
+}
+
+/// a call in the units tokens are counted in, fine enough for a microsecond's refill
+const TOKEN: u64 = 1_000_000;
+
+/// the calls left to a connection
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    limit: RateLimit,
+    tokens: u64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    /// a full bucket
+    pub fn new(limit: RateLimit, now: Instant) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: u64::from(limit.burst) * TOKEN,
+            refilled: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        if now > self.refilled {
+            let micros = now.duration_since(self.refilled).as_micros();
+            let refill = micros.saturating_mul(u128::from(self.limit.calls_per_second));
+            let full = u64::from(self.limit.burst) * TOKEN;
+            self.tokens = (u128::from(self.tokens) + refill).min(u128::from(full)) as u64;
+            self.refilled = now;
+        }
+    }
+
+    /// take a token for a call at now, or tell how long until there is one
+    pub fn take(&mut self, now: Instant) -> Result<(), RateLimited> {
+        self.refill(now);
+        if self.tokens >= TOKEN {
+            self.tokens -= TOKEN;
+            return Ok(());
+        }
+        let rate = u64::from(self.limit.calls_per_second).max(1);
+        let micros = (TOKEN - self.tokens).div_ceil(rate);
+        Err(RateLimited {
+            retry_after_ms: micros.div_ceil(1000),
+        })
+    }
+
+    /// whether the bucket filled up again by now, it is no different from a new one then
+    pub fn is_full(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        self.tokens == u64::from(self.limit.burst) * TOKEN
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// 10 calls per second, 3 at once
+    pub fn test_rate_limit() -> RateLimit {
+        RateLimit {
+            calls_per_second: 10,
+            burst: 3,
+        }
+    }
+
+    #[test]
+    fn can_burst_then_throttle() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(test_rate_limit(), start);
+        for _ in 0..3 {
+            assert_eq!(Ok(()), bucket.take(start));
+        }
+        assert_eq!(Err(RateLimited { retry_after_ms: 100 }), bucket.take(start));
+
+        let later = start + Duration::from_millis(40);
+        assert_eq!(Err(RateLimited { retry_after_ms: 60 }), bucket.take(later));
+        assert_eq!(Ok(()), bucket.take(start + Duration::from_millis(100)));
+        assert!(bucket.take(start + Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn can_not_save_up_past_burst() {
+        let start = Instant::now();
+        let mut bucket = TokenBucket::new(test_rate_limit(), start);
+        assert!(bucket.is_full(start));
+        bucket.take(start).unwrap();
+        assert!(!bucket.is_full(start));
+        let later = start + Duration::from_secs(60);
+        assert!(bucket.is_full(later));
+        for _ in 0..3 {
+            assert_eq!(Ok(()), bucket.take(later));
+        }
+        assert!(bucket.take(later).is_err());
+    }
+
+    #[test]
+    fn can_serialize_rate_limited() {
+        let limited = RateLimited { retry_after_ms: 250 };
+        assert_eq!("rate limited, retry after 250ms", limited.to_string());
+        assert_eq!(
+            json!({"retry_after_ms": 250}),
+            ::serde_json::to_value(&limited).unwrap()
+        );
+        assert!(test_rate_limit().is_valid());
+        assert!(!RateLimit {
+            calls_per_second: 0,
+            burst: 3,
+        }.is_valid());
+    }
+}
@@ -0,0 +1,158 @@
+//! keeps the storage of every instance of a container in a directory of its own under the
+//! container's storage root, so no instance can read another's
+//! directories are derived from the DNA an instance runs and its agent, configured paths are
+//! checked to stay inside the root once symlinks are followed
+
+use holochain_core::error::HolochainError;
+use std::path::{Component, Path, PathBuf};
+
+/// escape anything but unreserved characters so a name is a single, harmless path component
+/// distinct names stay distinct, e.g. "a/b" is "a%2Fb" and ".." is "%2E%2E"
+fn path_component(name: &str) -> String {
+    let mut component = String::new();
+    for (i, byte) in name.bytes().enumerate() {
+        let unreserved = byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_';
+        if unreserved || (byte == b'.' && i > 0 && name != "..") {
+            component.push(byte as char);
+        } else {
+            component.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    component
+}
+
+/// directory of the instance running the DNA with the given hash as agent, under root
+pub fn instance_dir(root: &Path, dna_hash: &str, agent: &str) -> PathBuf {
+    root.join(path_component(dna_hash)).join(path_component(agent))
+}
+
+/// resolve "." and ".." without touching the file system
+fn normalize(path: &Path) -> Result<PathBuf, HolochainError> {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(HolochainError::new(&format!(
+                        "storage path {} leaves the file system root",
+                        path.display()
+                    )));
+                }
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    Ok(normalized)
+}
+
+/// canonical form of a path that might not exist yet: its deepest existing ancestor is
+/// canonicalized, following symlinks, and the rest appended
+fn canonicalize(path: &Path) -> Result<PathBuf, HolochainError> {
+    let path = normalize(path)?;
+    let mut existing = path.as_path();
+    let mut rest = Vec::new();
+    while !existing.exists() {
+        match (existing.file_name(), existing.parent()) {
+            (Some(name), Some(parent)) => {
+                rest.push(name.to_os_string());
+                existing = parent;
+            }
+            _ => break,
+        }
+    }
+    let mut canonical = existing
+        .canonicalize()
+        .map_err(|e| HolochainError::new(&format!("{}: {}", existing.display(), e)))?;
+    for name in rest.iter().rev() {
+        canonical.push(name);
+    }
+    Ok(canonical)
+}
+
+/// the canonical form of path, relative paths being relative to root, if it stays inside root
+pub fn confine(root: &Path, path: &Path) -> Result<PathBuf, HolochainError> {
+    let root = root.canonicalize().map_err(|e| {
+        HolochainError::new(&format!("storage root {}: {}", root.display(), e))
+    })?;
+    let confined = canonicalize(&root.join(path))?;
+    if confined.starts_with(&root) && confined != root {
+        Ok(confined)
+    } else {
+        Err(HolochainError::new(&format!(
+            "storage path {} is not inside the storage root {}",
+            path.display(),
+            root.display()
+        )))
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use std::{env, fs};
+
+    /// an empty directory of its own in the system temp dir
+    pub fn test_root(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!(
+            "holochain_sandbox_{}_{}",
+            name,
+            ::std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        root
+    }
+
+    #[test]
+    fn can_derive_instance_dirs() {
+        let root = Path::new("/var/lib/holochain");
+        assert_eq!(
+            Path::new("/var/lib/holochain/QmDna/bob"),
+            instance_dir(root, "QmDna", "bob")
+        );
+        assert_eq!(
+            Path::new("/var/lib/holochain/QmDna/%2E%2E"),
+            instance_dir(root, "QmDna", "..")
+        );
+        assert_eq!(
+            Path::new("/var/lib/holochain/QmDna/%2Ehidden%20a%2Fb"),
+            instance_dir(root, "QmDna", ".hidden a/b")
+        );
+        assert_ne!(
+            instance_dir(root, "QmDna", "a/b"),
+            instance_dir(root, "QmDna", "a%2Fb")
+        );
+    }
+
+    #[test]
+    fn can_confine_paths() {
+        let root = test_root("confine");
+        let canonical = root.canonicalize().unwrap();
+
+        assert_eq!(canonical.join("app"), confine(&root, Path::new("app")).unwrap());
+        assert_eq!(
+            canonical.join("app/db"),
+            confine(&root, Path::new("./app/../app/db")).unwrap()
+        );
+        assert_eq!(
+            canonical.join("app"),
+            confine(&root, &root.join("app")).unwrap()
+        );
+        assert!(confine(&root, Path::new("../other")).is_err());
+        assert!(confine(&root, Path::new("/etc")).is_err());
+        assert!(confine(&root, Path::new(".")).is_err());
+        assert!(confine(&root.join("missing"), Path::new("app")).is_err());
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn can_not_escape_through_symlinks() {
+        let root = test_root("symlinks");
+        let outside = test_root("symlinks_outside");
+        ::std::os::unix::fs::symlink(&outside, root.join("link")).unwrap();
+
+        assert!(confine(&root, Path::new("link")).is_err());
+        assert!(confine(&root, Path::new("link/app")).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+//! registry of storage backends that instance persisters are resolved from by URI
+//! new persistence implementations are added by registering a factory for their URI scheme,
+//! e.g. `file:///path/to/store` resolves through the factory registered for `file`
+//! `lmdb:///path` and `pickle:///path` are built with the lmdb and pickle features
+
+#[cfg(feature = "lmdb")]
+use holochain_core::persister::lmdb::LmdbPersister;
+#[cfg(feature = "pickle")]
+use holochain_core::persister::pickle::PicklePersister;
+use holochain_core::{
+    error::HolochainError, persister::{FilePersister, Persister, SimplePersister},
+};
+use std::{
+    collections::HashMap, path::Path, sync::{Arc, Mutex},
+};
+
+/// storage used by instances that do not configure any
+pub const STORAGE_DEFAULT_URI: &str = "memory:";
+
+/// the persistent backends built with a feature of the same name, by scheme
+const STORAGE_FEATURE_SCHEMES: [(&str, bool); 2] = [
+    ("lmdb", cfg!(feature = "lmdb")),
+    ("pickle", cfg!(feature = "pickle")),
+];
+
+/// a parsed storage URI of the form `scheme:` or `scheme://path`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct StorageUri {
+    pub scheme: String,
+    pub path: String,
+}
+
+impl StorageUri {
+    pub fn parse(uri: &str) -> Result<StorageUri, HolochainError> {
+        let mut parts = uri.splitn(2, ':');
+        let scheme = parts.next().unwrap_or_default();
+        let rest = parts
+            .next()
+            .ok_or_else(|| HolochainError::new(&format!("storage uri '{}' has no scheme", uri)))?;
+        if scheme.is_empty()
+            || !scheme
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '+' || c == '-' || c == '.')
+        {
+            return Err(HolochainError::new(&format!(
+                "storage uri '{}' has an invalid scheme",
+                uri
+            )));
+        }
+        let path = rest.strip_prefix("//").unwrap_or(rest);
+        Ok(StorageUri {
+            scheme: scheme.to_lowercase(),
+            path: path.to_string(),
+        })
+    }
+
+    /// checks the scheme isn't one of the persistent backends left out of the build, so configs
+    /// naming them are refused up front rather than when the instance starts
+    pub fn check_supported(&self) -> Result<(), HolochainError> {
+        let built = STORAGE_FEATURE_SCHEMES
+            .iter()
+            .find(|(scheme, _)| *scheme == self.scheme)
+            .is_none_or(|(_, built)| *built);
+        if built {
+            Ok(())
+        } else {
+            Err(HolochainError::new(&format!(
+                "unsupported storage scheme '{}', build with the '{}' feature or use '{}' or \
+                 'file://'",
+                self.scheme, self.scheme, STORAGE_DEFAULT_URI
+            )))
+        }
+    }
+}
+
+/// builds a persister for a storage URI
+pub type StorageFactory =
+    Box<dyn Fn(&StorageUri) -> Result<Arc<Mutex<dyn Persister>>, HolochainError> + Send + Sync>;
+
+/// maps URI schemes to the factories that build persisters for them
+/// the default registry knows `memory:` and `file://`, which keeps the stores of the instance
+/// in the directory at the path, see holochain_core::persister::FilePersister, and `lmdb://` and
+/// `pickle://` when built with their features, which keep the state in an LMDB environment or
+/// a PickleDB file in the directory instead
+pub struct StorageRegistry {
+    factories: HashMap<String, StorageFactory>,
+}
+
+impl Default for StorageRegistry {
+    fn default() -> Self {
+        let mut registry = StorageRegistry::new();
+        registry.register(
+            "memory",
+            Box::new(|_| Ok(Arc::new(Mutex::new(SimplePersister::new())))),
+        );
+        registry.register(
+            "file",
+            Box::new(|uri| {
+                if uri.path.is_empty() {
+                    return Err(HolochainError::new("file storage needs a path"));
+                }
+                Ok(Arc::new(Mutex::new(FilePersister::new(Path::new(&uri.path)))))
+            }),
+        );
+        #[cfg(feature = "lmdb")]
+        registry.register(
+            "lmdb",
+            Box::new(|uri| {
+                if uri.path.is_empty() {
+                    return Err(HolochainError::new("lmdb storage needs a path"));
+                }
+                Ok(Arc::new(Mutex::new(LmdbPersister::new(Path::new(&uri.path))?)))
+            }),
+        );
+        #[cfg(feature = "pickle")]
+        registry.register(
+            "pickle",
+            Box::new(|uri| {
+                if uri.path.is_empty() {
+                    return Err(HolochainError::new("pickle storage needs a path"));
+                }
+                Ok(Arc::new(Mutex::new(PicklePersister::new(Path::new(&uri.path)))))
+            }),
+        );
+        registry
+    }
+}
+
+impl StorageRegistry {
+    /// an empty registry, see StorageRegistry::default() for one with the built in backends
+    pub fn new() -> StorageRegistry {
+        StorageRegistry {
+            factories: HashMap::new(),
+        }
+    }
+
+    /// add (or replace) the factory for a URI scheme
+    pub fn register(&mut self, scheme: &str, factory: StorageFactory) {
+        self.factories.insert(scheme.to_lowercase(), factory);
+    }
+
+    /// the registered URI schemes, sorted
+    pub fn schemes(&self) -> Vec<String> {
+        let mut schemes = self.factories.keys().cloned().collect::<Vec<String>>();
+        schemes.sort();
+        schemes
+    }
+
+    /// build a persister for a storage URI
+    pub fn resolve(&self, uri: &str) -> Result<Arc<Mutex<dyn Persister>>, HolochainError> {
+        self.resolve_uri(&StorageUri::parse(uri)?)
+    }
+
+    /// build a persister for an already parsed storage URI
+    pub fn resolve_uri(
+        &self,
+        uri: &StorageUri,
+    ) -> Result<Arc<Mutex<dyn Persister>>, HolochainError> {
+        match self.factories.get(&uri.scheme) {
+            Some(factory) => factory(uri),
+            None => Err(HolochainError::new(&format!(
+                "no storage backend registered for scheme '{}'",
+                uri.scheme
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_core::state::State;
+    use tempfile;
+
+    #[test]
+    fn can_parse_uris() {
+        assert_eq!(
+            StorageUri {
+                scheme: "memory".to_string(),
+                path: "".to_string(),
+            },
+            StorageUri::parse("memory:").unwrap()
+        );
+        assert_eq!(
+            StorageUri {
+                scheme: "file".to_string(),
+                path: "/tmp/holochain".to_string(),
+            },
+            StorageUri::parse("file:///tmp/holochain").unwrap()
+        );
+        assert_eq!(
+            "lmdb".to_string(),
+            StorageUri::parse("LMDB:///data").unwrap().scheme
+        );
+        assert!(StorageUri::parse("/tmp/holochain").is_err());
+        assert!(StorageUri::parse(":foo").is_err());
+        assert!(StorageUri::parse("fi le:foo").is_err());
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "lmdb", feature = "pickle")))]
+    fn fails_on_unsupported_schemes() {
+        for uri in &["LMDB:///data", "pickle:"] {
+            assert!(StorageUri::parse(uri).unwrap().check_supported().is_err());
+        }
+        assert_eq!(Ok(()), StorageUri::parse("memory:").unwrap().check_supported());
+        assert_eq!(Ok(()), StorageUri::parse("file:///tmp/holochain").unwrap().check_supported());
+        assert_eq!(Ok(()), StorageUri::parse("test://foo").unwrap().check_supported());
+    }
+
+    #[test]
+    fn can_resolve_memory() {
+        let registry = StorageRegistry::default();
+        assert!(registry.schemes().contains(&"file".to_string()));
+        assert!(registry.schemes().contains(&"memory".to_string()));
+
+        let persister = registry.resolve(STORAGE_DEFAULT_URI).unwrap();
+        let state = State::new();
+        persister.lock().unwrap().save(&state);
+        assert_eq!(Some(state), persister.lock().unwrap().load().unwrap());
+
+        // every resolve is a separate store
+        let other = registry.resolve(STORAGE_DEFAULT_URI).unwrap();
+        assert_eq!(None, other.lock().unwrap().load().unwrap());
+    }
+
+    #[test]
+    fn can_resolve_file() {
+        let registry = StorageRegistry::default();
+        assert!(registry.resolve("file:").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let persister = registry
+            .resolve(&format!("file://{}", dir.path().display()))
+            .unwrap();
+        let state = State::new();
+        persister.lock().unwrap().save(&state);
+        assert_eq!(Some(state), persister.lock().unwrap().load().unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "lmdb")]
+    fn can_resolve_lmdb() {
+        let registry = StorageRegistry::default();
+        assert_eq!(Ok(()), StorageUri::parse("lmdb:").unwrap().check_supported());
+        assert!(registry.resolve("lmdb:").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let uri = format!("lmdb://{}", dir.path().display());
+        let state = State::new();
+        registry.resolve(&uri).unwrap().lock().unwrap().save(&state);
+        // a new store on the same path, e.g. after the process restarted, finds the state
+        let persister = registry.resolve(&uri).unwrap();
+        assert!(persister.lock().unwrap().load().unwrap().is_some());
+    }
+
+    #[test]
+    #[cfg(feature = "pickle")]
+    fn can_resolve_pickle() {
+        let registry = StorageRegistry::default();
+        assert_eq!(Ok(()), StorageUri::parse("pickle:").unwrap().check_supported());
+        assert!(registry.resolve("pickle:").is_err());
+
+        let dir = tempfile::tempdir().unwrap();
+        let uri = format!("pickle://{}", dir.path().display());
+        let state = State::new();
+        registry.resolve(&uri).unwrap().lock().unwrap().save(&state);
+        let persister = registry.resolve(&uri).unwrap();
+        assert!(persister.lock().unwrap().load().unwrap().is_some());
+    }
+
+    #[test]
+    fn can_register_backends() {
+        let mut registry = StorageRegistry::default();
+        assert!(registry.resolve("test://foo").is_err());
+
+        let (sender, receiver) = ::std::sync::mpsc::channel();
+        let sender = Mutex::new(sender);
+        registry.register(
+            "test",
+            Box::new(move |uri| {
+                sender.lock().unwrap().send(uri.path.clone()).unwrap();
+                Ok(Arc::new(Mutex::new(SimplePersister::new())))
+            }),
+        );
+        assert!(registry.schemes().contains(&"test".to_string()));
+
+        registry.resolve("test://foo").unwrap();
+        assert_eq!("foo".to_string(), receiver.recv().unwrap());
+    }
+}
@@ -0,0 +1,139 @@
+//! the watchdog supervises the instances of a container, restarting those that are wedged
+//! an instance is wedged when its action loop hasn't come around for heartbeat_timeout_ms or a
+//! zome call has been running for longer than call_budget_ms
+//! wedged instances are restarted from the state last checkpointed while they were healthy and an
+//! "instance_restarted" signal tells operators what they were stuck doing, see
+//! Container::check_health()
+
+use holochain_core::{
+    error::HolochainError, health::Health, instance::REDUX_LOOP_TIMEOUT_MS, signal::Signal,
+};
+use serde_json;
+use std::time::Duration;
+
+/// name of the signal emitted when a wedged instance is restarted, its payload is the Diagnosis
+pub const INSTANCE_RESTARTED_SIGNAL: &str = "instance_restarted";
+pub const WATCHDOG_DEFAULT_HEARTBEAT_TIMEOUT_MS: u64 = 5000;
+pub const WATCHDOG_DEFAULT_CALL_BUDGET_MS: u64 = 30000;
+
+/// when instances count as wedged
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    /// how long the action loop of an instance may go without coming around
+    pub heartbeat_timeout_ms: u64,
+    /// how long a zome call may run
+    pub call_budget_ms: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        WatchdogConfig {
+            heartbeat_timeout_ms: WATCHDOG_DEFAULT_HEARTBEAT_TIMEOUT_MS,
+            call_budget_ms: WATCHDOG_DEFAULT_CALL_BUDGET_MS,
+        }
+    }
+}
+
+impl WatchdogConfig {
+    pub fn heartbeat_timeout(&self) -> Duration {
+        Duration::from_millis(self.heartbeat_timeout_ms)
+    }
+
+    pub fn call_budget(&self) -> Duration {
+        Duration::from_millis(self.call_budget_ms)
+    }
+
+    /// checks healthy instances won't be taken for wedged, idle action loops come around every
+    /// REDUX_LOOP_TIMEOUT_MS
+    pub fn check(&self) -> Result<(), HolochainError> {
+        if self.heartbeat_timeout_ms <= REDUX_LOOP_TIMEOUT_MS {
+            return Err(HolochainError::new(&format!(
+                "watchdog heartbeat_timeout_ms has to be more than {}",
+                REDUX_LOOP_TIMEOUT_MS
+            )));
+        }
+        if self.call_budget_ms == 0 {
+            return Err(HolochainError::new(
+                "watchdog call_budget_ms has to be more than 0",
+            ));
+        }
+        Ok(())
+    }
+
+    /// what is wrong with an instance, None if it is healthy
+    pub fn diagnose(&self, health: &Health) -> Option<Diagnosis> {
+        if health.is_wedged(self.heartbeat_timeout()) {
+            Some(Diagnosis {
+                heartbeat_age_ms: health.heartbeat_age.as_millis() as u64,
+                backtrace: health.backtrace(),
+                restarted: false,
+            })
+        } else {
+            None
+        }
+    }
+}
+
+/// why an instance was taken for wedged
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Diagnosis {
+    /// time since the action loop last came around
+    pub heartbeat_age_ms: u64,
+    /// what the instance was stuck doing, see Health::backtrace()
+    pub backtrace: Vec<String>,
+    /// false if there was no checkpoint to restart the instance from
+    pub restarted: bool,
+}
+
+impl Diagnosis {
+    /// the signal reporting it, from no zome in particular
+    pub fn to_signal(&self) -> Signal {
+        Signal {
+            zome: String::new(),
+            name: INSTANCE_RESTARTED_SIGNAL.to_string(),
+            payload: serde_json::to_value(self).expect("Diagnosis should serialize"),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    fn test_health(heartbeat_age_ms: u64) -> Health {
+        Health {
+            heartbeat_age: Duration::from_millis(heartbeat_age_ms),
+            reducing: None,
+            stuck_calls: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn can_diagnose_wedged_instances() {
+        let config = WatchdogConfig::default();
+        assert_eq!(None, config.diagnose(&test_health(REDUX_LOOP_TIMEOUT_MS)));
+
+        let diagnosis = config.diagnose(&test_health(6000)).unwrap();
+        assert_eq!(6000, diagnosis.heartbeat_age_ms);
+        assert!(diagnosis.backtrace.is_empty());
+
+        let signal = diagnosis.to_signal();
+        assert_eq!(INSTANCE_RESTARTED_SIGNAL, signal.name);
+        assert_eq!(
+            json!({"heartbeat_age_ms": 6000, "backtrace": [], "restarted": false}),
+            signal.payload
+        );
+    }
+
+    #[test]
+    fn fails_on_watchdog_too_eager() {
+        assert_eq!(Ok(()), WatchdogConfig::default().check());
+        let config = |heartbeat_timeout_ms, call_budget_ms| WatchdogConfig {
+            heartbeat_timeout_ms,
+            call_budget_ms,
+        };
+        assert!(config(REDUX_LOOP_TIMEOUT_MS, 1000).check().is_err());
+        assert!(config(5000, 0).check().is_err());
+    }
+}
@@ -11,11 +11,26 @@
 extern "C" {
 #endif
 
+// only in builds with the mobile feature, call it before anything else
+extern bool holochain_platform_init(const char* data_dir);
+
 typedef void Holochain;
 extern Holochain *holochain_new(Dna*);
+extern Holochain *holochain_new_from_json(const char* dna_json, const char* agent);
+extern void holochain_destroy(Holochain*);
 extern bool holochain_start(Holochain*);
 extern bool holochain_stop(Holochain*);
 extern char* holochain_call(Holochain*, const char* zome, const char* capability, const char* function, const char* parameters);
+extern void holochain_string_free(char* s);
+
+typedef void Container;
+typedef void SignalReceiver;
+extern Container *holochain_container_new_from_json(const char* config_json);
+extern void holochain_container_destroy(Container*);
+extern char* holochain_container_call(Container*, const char* instance_id, const char* zome, const char* capability, const char* function, const char* parameters);
+extern SignalReceiver *holochain_container_signals(Container*);
+extern char* holochain_signals_next(SignalReceiver*, uint64_t timeout_ms);
+extern void holochain_signals_free(SignalReceiver*);
 
 #ifdef __cplusplus
 }
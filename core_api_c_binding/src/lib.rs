@@ -1,19 +1,46 @@
+//! This crate is an ffi wrapper to embed holochain instances through the core_api facade, e.g.
+//! from Node.js/Electron or mobile apps.
+//!
+//! Everything crossing the boundary is a C string, DNAs and zome call parameters and results are
+//! JSON. Panics are caught at the boundary and reported like any other failure: a null pointer or
+//! false.
+//!
+//! A container runs the instances of a configuration side by side, e.g. for a test harness or an
+//! Electron app driving several agents, see holochain_container_new_from_json().
+//!
+//! Remember to destroy instances, containers and signal subscriptions and free returned strings,
+//! the declarations are in include/core_api_c_binding.h.
+//!
+//! Built with the mobile feature, e.g. `make android` or `make ios`, the static library can be
+//! linked into Android apps, behind the app's JNI glue, and iOS apps. These have to call
+//! holochain_platform_init() with the app's data directory before anything else.
+
 extern crate holochain_agent;
 extern crate holochain_core;
 extern crate holochain_core_api;
 extern crate holochain_dna;
+extern crate serde_json;
 
+#[cfg(feature = "mobile")]
+use holochain_core::platform::{self, MobilePlatform};
 use holochain_core::context::Context;
-use holochain_core_api::Holochain;
+use holochain_core_api::{
+    config::Configuration, container::{dna_from_resolver, Container, InstanceSignal},
+    storage::StorageRegistry, Holochain,
+};
 use holochain_dna::Dna;
 use std::sync::Arc;
 
 use holochain_agent::Agent;
 use holochain_core::{logger::Logger, persister::SimplePersister};
 use std::{
-    ffi::{CStr, CString}, os::raw::c_char, sync::Mutex,
+    ffi::{CStr, CString}, os::raw::c_char, panic::{catch_unwind, AssertUnwindSafe},
+    sync::{mpsc::Receiver, Mutex}, time::Duration,
 };
 
+/// how long calls in flight get to finish when an instance is destroyed
+const DESTROY_TIMEOUT_MS: u64 = 5000;
+
 #[derive(Clone, Debug)]
 struct NullLogger {}
 
@@ -21,25 +48,106 @@ impl Logger for NullLogger {
     fn log(&mut self, _msg: String) {}
 }
 
-#[no_mangle]
-pub unsafe extern "C" fn holochain_new(ptr: *mut Dna) -> *mut Holochain {
-    let agent = Agent::from_string("c_bob");
+/// run f, taking a panic for the failure value instead of unwinding into foreign code
+fn catch_panic<T, F: FnOnce() -> T>(failure: T, f: F) -> T {
+    catch_unwind(AssertUnwindSafe(f)).unwrap_or(failure)
+}
 
+fn to_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+#[allow(clippy::arc_with_non_send_sync)]
+fn new_instance(dna: Dna, agent: &str) -> *mut Holochain {
     let context = Arc::new(Context {
-        agent,
+        agent: Agent::from_string(agent),
         logger: Arc::new(Mutex::new(NullLogger {})),
         persister: Arc::new(Mutex::new(SimplePersister::new())),
     });
 
-    assert!(!ptr.is_null());
+    match Holochain::new(dna, context) {
+        Ok(hc) => Box::into_raw(Box::new(hc)),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// run instances the way mobile apps need them to: threads get a stack big enough for wasm and
+/// storage goes in data_dir, the directory the app is given for its data, unless configured
+/// otherwise
+/// call it once before creating instances or containers, false if data_dir is null
+///
+/// # Safety
+///
+/// data_dir has to be a null terminated string
+#[cfg(feature = "mobile")]
+#[no_mangle]
+pub unsafe extern "C" fn holochain_platform_init(data_dir: *const c_char) -> bool {
+    if data_dir.is_null() {
+        return false;
+    }
+    let data_dir = CStr::from_ptr(data_dir).to_string_lossy().into_owned();
+    platform::install(Arc::new(MobilePlatform::new(data_dir.into())));
+    true
+}
+
+/// create an instance of the DNA, which is consumed, null if it fails to initialize
+///
+/// # Safety
+///
+/// ptr has to be a Dna from holochain_dna_create() not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_new(ptr: *mut Dna) -> *mut Holochain {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
     let dna = Box::from_raw(ptr);
+    catch_panic(std::ptr::null_mut(), || new_instance(*dna, "c_bob"))
+}
 
-    match Holochain::new(*dna, context) {
-        Ok(hc) => Box::into_raw(Box::new(hc)),
+/// create an instance of the DNA in dna_json for the agent, null if the DNA doesn't parse or
+/// fails to initialize
+///
+/// # Safety
+///
+/// dna_json and agent have to be null terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn holochain_new_from_json(
+    dna_json: *const c_char,
+    agent: *const c_char,
+) -> *mut Holochain {
+    if dna_json.is_null() || agent.is_null() {
+        return std::ptr::null_mut();
+    }
+    let dna_json = CStr::from_ptr(dna_json).to_string_lossy().into_owned();
+    let agent = CStr::from_ptr(agent).to_string_lossy().into_owned();
+    catch_panic(std::ptr::null_mut(), || match Dna::new_from_json(&dna_json) {
+        Ok(dna) => new_instance(dna, &agent),
         Err(_) => std::ptr::null_mut(),
+    })
+}
+
+/// shut the instance down, waiting a while for calls in flight, and free it
+///
+/// # Safety
+///
+/// ptr has to be an instance from this library not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_destroy(ptr: *mut Holochain) {
+    if ptr.is_null() {
+        return;
     }
+    let mut holochain = Box::from_raw(ptr);
+    catch_panic((), move || {
+        let _ = holochain.shutdown(Duration::from_millis(DESTROY_TIMEOUT_MS));
+    })
 }
 
+/// # Safety
+///
+/// ptr has to be an instance from this library
 #[no_mangle]
 pub unsafe extern "C" fn holochain_start(ptr: *mut Holochain) -> bool {
     let holochain = {
@@ -49,9 +157,12 @@ pub unsafe extern "C" fn holochain_start(ptr: *mut Holochain) -> bool {
         &mut *ptr
     };
 
-    holochain.start().is_ok()
+    catch_panic(false, || holochain.start().is_ok())
 }
 
+/// # Safety
+///
+/// ptr has to be an instance from this library
 #[no_mangle]
 pub unsafe extern "C" fn holochain_stop(ptr: *mut Holochain) -> bool {
     let holochain = {
@@ -61,11 +172,17 @@ pub unsafe extern "C" fn holochain_stop(ptr: *mut Holochain) -> bool {
         &mut *ptr
     };
 
-    holochain.stop().is_ok()
+    catch_panic(false, || holochain.stop().is_ok())
 }
 
 type CStrPtr = *mut c_char;
 
+/// call a zome function with JSON parameters, returning its JSON result or an error message
+/// null if the call panicked or an argument is null
+///
+/// # Safety
+///
+/// ptr has to be an instance from this library and the rest null terminated strings
 #[no_mangle]
 pub unsafe extern "C" fn holochain_call(
     ptr: *mut Holochain,
@@ -89,22 +206,272 @@ pub unsafe extern "C" fn holochain_call(
     let function = CStr::from_ptr(function).to_string_lossy().into_owned();
     let parameters = CStr::from_ptr(parameters).to_string_lossy().into_owned();
 
-    match holochain.call(
-        zome.as_str(),
-        capability.as_str(),
-        function.as_str(),
-        parameters.as_str(),
-    ) {
-        Ok(string_result) => match CString::new(string_result) {
-            Ok(s) => s.into_raw(),
-            Err(_) => std::ptr::null_mut(),
-        },
-        Err(holochain_error) => match CString::new(format!(
-            "Error calling zome function: {:?}",
-            holochain_error
-        )) {
-            Ok(s) => s.into_raw(),
+    catch_panic(std::ptr::null_mut(), || {
+        match holochain.call(
+            zome.as_str(),
+            capability.as_str(),
+            function.as_str(),
+            parameters.as_str(),
+        ) {
+            Ok(string_result) => to_c_string(string_result),
+            Err(holochain_error) => to_c_string(format!(
+                "Error calling zome function: {:?}",
+                holochain_error
+            )),
+        }
+    })
+}
+
+/// free a string returned by this library
+///
+/// # Safety
+///
+/// s has to be a string from this library not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_string_free(s: *mut c_char) {
+    if s.is_null() {
+        return;
+    }
+    drop(CString::from_raw(s));
+}
+
+/// build and start the instances of a container configuration given as JSON, the DNA of each
+/// instance is loaded from the JSON file at its configured path
+/// null if the configuration is inconsistent or an instance fails to initialize
+///
+/// # Safety
+///
+/// config_json has to be a null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn holochain_container_new_from_json(
+    config_json: *const c_char,
+) -> *mut Container {
+    if config_json.is_null() {
+        return std::ptr::null_mut();
+    }
+    let config_json = CStr::from_ptr(config_json).to_string_lossy().into_owned();
+    catch_panic(std::ptr::null_mut(), || {
+        let config = match Configuration::from_json(&config_json) {
+            Ok(config) => config,
+            Err(_) => return std::ptr::null_mut(),
+        };
+        let resolver = config.dna_resolver();
+        let load_dna = dna_from_resolver(&resolver);
+        let mut container =
+            match Container::from_config(&config, &StorageRegistry::default(), load_dna) {
+                Ok(container) => container,
+                Err(_) => return std::ptr::null_mut(),
+            };
+        for id in container.instance_ids() {
+            if let Some(instance) = container.instance_mut(&id) {
+                if instance.start().is_err() {
+                    return std::ptr::null_mut();
+                }
+            }
+        }
+        Box::into_raw(Box::new(container))
+    })
+}
+
+/// shut every instance down, waiting a while for calls in flight, and free the container
+///
+/// # Safety
+///
+/// ptr has to be a container from this library not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_container_destroy(ptr: *mut Container) {
+    if ptr.is_null() {
+        return;
+    }
+    let mut container = Box::from_raw(ptr);
+    catch_panic((), move || {
+        let _ = container.shutdown(Duration::from_millis(DESTROY_TIMEOUT_MS));
+    })
+}
+
+/// like holochain_call() on the container's instance with the given id
+/// null if there is no such instance
+///
+/// # Safety
+///
+/// ptr has to be a container from this library and the rest null terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn holochain_container_call(
+    ptr: *mut Container,
+    instance_id: *const c_char,
+    zome: CStrPtr,
+    capability: CStrPtr,
+    function: CStrPtr,
+    parameters: CStrPtr,
+) -> CStrPtr {
+    if ptr.is_null() || instance_id.is_null() {
+        return std::ptr::null_mut();
+    }
+    let container = &mut *ptr;
+    let instance_id = CStr::from_ptr(instance_id).to_string_lossy().into_owned();
+    match container.instance_mut(&instance_id) {
+        Some(holochain) => holochain_call(holochain, zome, capability, function, parameters),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// subscribe to the signals emitted in the container's instances from now on
+///
+/// # Safety
+///
+/// ptr has to be a container from this library
+#[no_mangle]
+pub unsafe extern "C" fn holochain_container_signals(
+    ptr: *mut Container,
+) -> *mut Receiver<InstanceSignal> {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    let container = &*ptr;
+    catch_panic(std::ptr::null_mut(), || {
+        Box::into_raw(Box::new(container.signals()))
+    })
+}
+
+/// wait up to timeout_ms for the next signal, as JSON with the instance_id and signal
+/// null if there was none in time
+///
+/// # Safety
+///
+/// ptr has to be a subscription from holochain_container_signals()
+#[no_mangle]
+pub unsafe extern "C" fn holochain_signals_next(
+    ptr: *mut Receiver<InstanceSignal>,
+    timeout_ms: u64,
+) -> *mut c_char {
+    if ptr.is_null() {
+        return std::ptr::null_mut();
+    }
+    let signals = &*ptr;
+    catch_panic(std::ptr::null_mut(), || {
+        match signals.recv_timeout(Duration::from_millis(timeout_ms)) {
+            Ok(signal) => match serde_json::to_string(&signal) {
+                Ok(json) => to_c_string(json),
+                Err(_) => std::ptr::null_mut(),
+            },
             Err(_) => std::ptr::null_mut(),
-        },
+        }
+    })
+}
+
+/// stop receiving signals
+///
+/// # Safety
+///
+/// ptr has to be a subscription from holochain_container_signals() not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_signals_free(ptr: *mut Receiver<InstanceSignal>) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(Box::from_raw(ptr));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // comprehensive tests are handled in the C++ Qt unit test framework
+    // there are a couple here to make iterating within this file faster
+
+    #[test]
+    fn can_create_call_and_destroy() {
+        let dna_json = CString::new(Dna::new().to_json().unwrap()).unwrap();
+        let agent = CString::new("bob").unwrap();
+        let zome = CString::new("test_zome").unwrap();
+        let cap = CString::new("test_cap").unwrap();
+        let function = CString::new("main").unwrap();
+        let parameters = CString::new("{}").unwrap();
+        unsafe {
+            let hc = holochain_new_from_json(dna_json.as_ptr(), agent.as_ptr());
+            assert!(!hc.is_null());
+            assert!(holochain_start(hc));
+
+            let result = holochain_call(
+                hc,
+                zome.as_ptr() as CStrPtr,
+                cap.as_ptr() as CStrPtr,
+                function.as_ptr() as CStrPtr,
+                parameters.as_ptr() as CStrPtr,
+            );
+            let message = CStr::from_ptr(result).to_string_lossy().into_owned();
+            assert!(message.starts_with("Error calling zome function"));
+            holochain_string_free(result);
+
+            holochain_destroy(hc);
+        }
+    }
+
+    #[test]
+    fn fails_on_bad_arguments() {
+        let not_json = CString::new("not json").unwrap();
+        let agent = CString::new("bob").unwrap();
+        unsafe {
+            assert!(holochain_new_from_json(not_json.as_ptr(), agent.as_ptr()).is_null());
+            assert!(holochain_new_from_json(std::ptr::null(), agent.as_ptr()).is_null());
+            assert!(!holochain_start(std::ptr::null_mut()));
+            holochain_destroy(std::ptr::null_mut());
+        }
+    }
+
+    #[test]
+    fn can_run_a_container() {
+        let dna_path = std::env::temp_dir().join("holochain_c_binding_container_dna.json");
+        std::fs::write(&dna_path, Dna::new().to_json().unwrap()).unwrap();
+        let config = CString::new(format!(
+            r#"{{"instances": [{{"id": "app", "dna": {:?}, "agent": "bob"}}]}}"#,
+            dna_path.to_string_lossy()
+        )).unwrap();
+        let app = CString::new("app").unwrap();
+        let other = CString::new("other").unwrap();
+        let zome = CString::new("test_zome").unwrap();
+        let parameters = CString::new("{}").unwrap();
+        unsafe {
+            let container = holochain_container_new_from_json(config.as_ptr());
+            assert!(!container.is_null());
+            let call = |instance_id: &CString| {
+                holochain_container_call(
+                    container,
+                    instance_id.as_ptr(),
+                    zome.as_ptr() as CStrPtr,
+                    zome.as_ptr() as CStrPtr,
+                    zome.as_ptr() as CStrPtr,
+                    parameters.as_ptr() as CStrPtr,
+                )
+            };
+            let result = call(&app);
+            assert!(!result.is_null());
+            holochain_string_free(result);
+            assert!(call(&other).is_null());
+
+            let signals = holochain_container_signals(container);
+            assert!(holochain_signals_next(signals, 10).is_null());
+            holochain_signals_free(signals);
+            holochain_container_destroy(container);
+        }
+    }
+
+    #[cfg(feature = "mobile")]
+    #[test]
+    fn can_init_platform() {
+        let data_dir = CString::new("/data/app").unwrap();
+        unsafe {
+            assert!(!holochain_platform_init(std::ptr::null()));
+            assert!(holochain_platform_init(data_dir.as_ptr()));
+        }
+        assert_eq!(
+            Some(std::path::PathBuf::from("/data/app")),
+            platform::current().data_dir()
+        );
+    }
+
+    #[test]
+    fn can_catch_panics() {
+        assert!(!catch_panic(false, || panic!("across the boundary")));
     }
 }
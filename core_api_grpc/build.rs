@@ -0,0 +1,5 @@
+fn main() {
+    let protoc = protoc_bin_vendored::protoc_bin_path().expect("protoc for this platform");
+    std::env::set_var("PROTOC", protoc);
+    tonic_build::compile_protos("proto/conductor.proto").expect("proto/conductor.proto compiles");
+}
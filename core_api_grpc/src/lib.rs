@@ -0,0 +1,1031 @@
+//! the gRPC server of interfaces with the "grpc" driver, see holochain_core_api::interface
+//! it serves the Conductor service of proto/conductor.proto: zome calls, the signals of instances
+//! streamed by the server and, on admin interfaces, managing the container, the same calls as on
+//! JSON-RPC
+//!
+//! the server runs on threads of its own but instances stay on the thread running the container,
+//! so the calls it takes wait for that thread to answer them, see GrpcServer::handle()
+//!
+//! every call comes on its own, so challenge interfaces take the nonce of a Challenge call along
+//! with its signature as credential, as "nonce:signature", from the peer the nonce was sent to
+//! a nonce answers a single call, within CHALLENGE_TIMEOUT, and a peer only has
+//! MAX_PEER_CHALLENGES pending, so it can't crowd out the challenges of others
+//!
+//! zome calls are rate limited by peer, whatever the credential
+
+// the service answers with tonic's Status, however big it is
+#![allow(clippy::result_large_err)]
+
+pub mod methods;
+
+/// the messages and service of proto/conductor.proto
+#[allow(clippy::all)]
+pub mod proto {
+    tonic::include_proto!("holochain.conductor");
+}
+
+use crate::{
+    methods::{streams, GrpcMethod, AUTHORIZATION_METADATA},
+    proto::conductor_server::{Conductor, ConductorServer},
+};
+use holochain_core::{context::Context, error::HolochainError, platform, signal::Signal};
+use holochain_core_api::{
+    agents::AgentInfo, config::InstanceConfiguration,
+    container::{CloneChanges, Container, InstanceSignal},
+    interface::{AuthConfiguration, InterfaceConfiguration, InterfaceDriver},
+    rate_limit::TokenBucket, storage::StorageRegistry,
+};
+use holochain_dna::Dna;
+use std::{
+    collections::{HashMap, VecDeque}, net::{IpAddr, SocketAddr},
+    sync::{mpsc as std_mpsc, Arc, Mutex}, thread::JoinHandle, time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, oneshot};
+use tokio_stream::wrappers::{ReceiverStream, TcpListenerStream};
+use tonic::{transport::Server, Request, Response, Status};
+
+/// challenges sent that can be answered, no more are sent until some are answered or time out
+pub const MAX_CHALLENGES: usize = 1024;
+
+/// challenges a peer can have pending, its oldest is forgotten first
+pub const MAX_PEER_CHALLENGES: usize = 8;
+
+/// how long a challenge can be answered for
+pub const CHALLENGE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// peers with a rate limit bucket, the ones with a full bucket are forgotten when there are more
+pub const MAX_PEERS: usize = 1024;
+
+/// signals a Signals stream can fall behind by, the ones after are dropped until it catches up
+pub const SIGNAL_BUFFER: usize = 64;
+
+/// what admin calls need from the application running the container
+pub trait Admin {
+    /// the DNA at the path an instance is configured with, e.g. container::dna_from_file()
+    fn load_dna(&self, path: &str) -> Result<Dna, HolochainError>;
+
+    /// the storage backends instances added can use
+    fn registry(&self) -> &StorageRegistry;
+
+    /// the context of a clone of the instance with the given id, with a chain of its own
+    fn clone_context(
+        &self,
+        instance_id: &str,
+        clone_id: &str,
+    ) -> Result<Context, HolochainError>;
+}
+
+type Reply<T> = oneshot::Sender<Result<T, HolochainError>>;
+
+/// a call for the thread running the container to answer
+enum Call {
+    Zome(proto::CallZomeRequest, Reply<String>),
+    ListInstances(Reply<Vec<String>>),
+    CreateAgent(proto::CreateAgentRequest, Reply<AgentInfo>),
+    ListAgents(Reply<Vec<AgentInfo>>),
+    AddInstance(proto::AddInstanceRequest, Reply<String>),
+    CloneInstance(proto::CloneInstanceRequest, Reply<String>),
+}
+
+/// a challenge sent, to be answered by the peer within CHALLENGE_TIMEOUT
+struct SentChallenge {
+    peer: IpAddr,
+    nonce: String,
+    sent: Instant,
+}
+
+/// an open Signals stream
+struct SignalStream {
+    instance_ids: Vec<String>,
+    sender: mpsc::Sender<Result<proto::InstanceSignal, Status>>,
+}
+
+/// what the service and the server share
+struct Shared {
+    interface: InterfaceConfiguration,
+    calls: Mutex<std_mpsc::Sender<Call>>,
+    /// challenges pending, the latest last
+    challenges: Mutex<VecDeque<SentChallenge>>,
+    /// the rate limit buckets, by peer
+    buckets: Mutex<HashMap<IpAddr, TokenBucket>>,
+    streams: Mutex<Vec<SignalStream>>,
+    /// why the server stopped serving, if it failed
+    failure: Mutex<Option<String>>,
+}
+
+/// the message of the error, its Display doesn't give one
+fn message(error: &HolochainError) -> String {
+    match error {
+        HolochainError::ErrorGeneric(message) => message.clone(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn unauthenticated(error: HolochainError) -> Status {
+    Status::unauthenticated(message(&error))
+}
+
+fn denied(error: HolochainError) -> Status {
+    Status::permission_denied(message(&error))
+}
+
+fn failed(error: HolochainError) -> Status {
+    Status::unknown(message(&error))
+}
+
+fn stopped() -> Status {
+    Status::unavailable("the container is not answering calls anymore")
+}
+
+fn peer<T>(request: &Request<T>) -> Result<IpAddr, Status> {
+    request
+        .remote_addr()
+        .map(|address| address.ip())
+        .ok_or_else(|| Status::unauthenticated("the address of the peer is unknown"))
+}
+
+/// the credential in the metadata of the request, empty if there is none
+fn credential<T>(request: &Request<T>) -> &str {
+    request
+        .metadata()
+        .get(AUTHORIZATION_METADATA)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+}
+
+fn agent_info(agent: AgentInfo) -> proto::AgentInfo {
+    proto::AgentInfo {
+        name: agent.name,
+        public_key: agent.public_key,
+    }
+}
+
+/// the Conductor service, answering from the thread running the container
+struct Service {
+    shared: Arc<Shared>,
+}
+
+impl Service {
+    /// authenticate the request with its credential and authorize it for the method, the
+    /// instance_id being the one a zome call is for
+    fn authorize<T>(
+        &self,
+        request: &Request<T>,
+        method: GrpcMethod,
+        instance_id: Option<&str>,
+    ) -> Result<(), Status> {
+        let interface = &self.shared.interface;
+        let peer = peer(request)?;
+        let connection = match interface.auth {
+            AuthConfiguration::Challenge { .. } => {
+                let (nonce, signature) = credential(request).split_once(':').unwrap_or_default();
+                if !self.take_challenge(&peer, nonce) {
+                    return Err(Status::unauthenticated(
+                        "the nonce was not sent to this peer, was answered or timed out, ask for a \
+                         new challenge",
+                    ));
+                }
+                let mut connection = interface.resume(&peer, None, nonce).map_err(denied)?;
+                connection.authenticate(signature).map_err(unauthenticated)?;
+                connection
+            }
+            AuthConfiguration::Token { .. } => {
+                let mut connection = interface.connect(&peer, None).map_err(denied)?;
+                connection
+                    .authenticate(credential(request))
+                    .map_err(unauthenticated)?;
+                connection
+            }
+        };
+        method.authorize(&connection, instance_id).map_err(denied)
+    }
+
+    /// forget the challenge sent to peer with the nonce, returns whether it was pending
+    /// a challenge is answered once, whether rightly or not
+    fn take_challenge(&self, peer: &IpAddr, nonce: &str) -> bool {
+        let mut challenges = self.shared.challenges.lock().unwrap();
+        let now = Instant::now();
+        challenges.retain(|challenge| now.duration_since(challenge.sent) < CHALLENGE_TIMEOUT);
+        match challenges
+            .iter()
+            .position(|challenge| challenge.peer == *peer && challenge.nonce == nonce)
+        {
+            Some(index) => challenges.remove(index).is_some(),
+            None => false,
+        }
+    }
+
+    /// count a zome call against the rate limit of the peer
+    fn throttle(&self, peer: &IpAddr) -> Result<(), Status> {
+        let limit = match self.shared.interface.rate_limit {
+            Some(limit) => limit,
+            None => return Ok(()),
+        };
+        let now = Instant::now();
+        let mut buckets = self.shared.buckets.lock().unwrap();
+        if !buckets.contains_key(peer) && buckets.len() >= MAX_PEERS {
+            // a full bucket is what a peer gets anyway
+            buckets.retain(|_, bucket| !bucket.is_full(now));
+            if buckets.len() >= MAX_PEERS {
+                return Err(Status::resource_exhausted(
+                    "too many peers are calling, retry later",
+                ));
+            }
+        }
+        buckets
+            .entry(*peer)
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .take(now)
+            .map_err(|limited| Status::resource_exhausted(limited.to_string()))
+    }
+
+    /// have the thread running the container answer the call
+    async fn ask<T>(
+        &self,
+        call: Call,
+        answer: oneshot::Receiver<Result<T, HolochainError>>,
+    ) -> Result<T, Status> {
+        self.shared
+            .calls
+            .lock()
+            .unwrap()
+            .send(call)
+            .map_err(|_| stopped())?;
+        answer.await.map_err(|_| stopped())?.map_err(failed)
+    }
+}
+
+#[tonic::async_trait]
+impl Conductor for Service {
+    async fn challenge(
+        &self,
+        request: Request<proto::ChallengeRequest>,
+    ) -> Result<Response<proto::ChallengeResponse>, Status> {
+        let interface = &self.shared.interface;
+        let peer = peer(&request)?;
+        let connection = interface.connect(&peer, None).map_err(denied)?;
+        let nonce = match connection.challenge() {
+            Some(nonce) => nonce.to_string(),
+            None => {
+                return Err(Status::failed_precondition(format!(
+                    "interface '{}' authenticates with tokens",
+                    interface.id
+                )))
+            }
+        };
+        let mut challenges = self.shared.challenges.lock().unwrap();
+        let now = Instant::now();
+        challenges.retain(|challenge| now.duration_since(challenge.sent) < CHALLENGE_TIMEOUT);
+        let pending = challenges.iter().filter(|challenge| challenge.peer == peer).count();
+        if pending >= MAX_PEER_CHALLENGES {
+            let oldest = challenges
+                .iter()
+                .position(|challenge| challenge.peer == peer)
+                .expect("the peer has challenges pending");
+            challenges.remove(oldest);
+        } else if challenges.len() >= MAX_CHALLENGES {
+            return Err(Status::resource_exhausted(
+                "too many challenges are pending, retry later",
+            ));
+        }
+        challenges.push_back(SentChallenge {
+            peer,
+            nonce: nonce.clone(),
+            sent: now,
+        });
+        Ok(Response::new(proto::ChallengeResponse { nonce }))
+    }
+
+    async fn call_zome(
+        &self,
+        request: Request<proto::CallZomeRequest>,
+    ) -> Result<Response<proto::CallZomeResponse>, Status> {
+        let instance_id = request.get_ref().instance_id.clone();
+        self.authorize(&request, GrpcMethod::CallZome, Some(&instance_id))?;
+        self.throttle(&peer(&request)?)?;
+        let (reply, answer) = oneshot::channel();
+        let result = self.ask(Call::Zome(request.into_inner(), reply), answer).await?;
+        Ok(Response::new(proto::CallZomeResponse { result }))
+    }
+
+    type SignalsStream = ReceiverStream<Result<proto::InstanceSignal, Status>>;
+
+    async fn signals(
+        &self,
+        request: Request<proto::SignalsRequest>,
+    ) -> Result<Response<Self::SignalsStream>, Status> {
+        self.authorize(&request, GrpcMethod::Signals, None)?;
+        let interface = &self.shared.interface;
+        let instance_ids = request.into_inner().instance_ids;
+        if let Some(hidden) = instance_ids.iter().find(|id| !interface.exposes(id)) {
+            return Err(Status::permission_denied(format!(
+                "instance '{}' is not accessible through interface '{}'",
+                hidden, interface.id
+            )));
+        }
+        let (sender, receiver) = mpsc::channel(SIGNAL_BUFFER);
+        self.shared.streams.lock().unwrap().push(SignalStream {
+            instance_ids,
+            sender,
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn list_instances(
+        &self,
+        request: Request<proto::ListInstancesRequest>,
+    ) -> Result<Response<proto::ListInstancesResponse>, Status> {
+        self.authorize(&request, GrpcMethod::ListInstances, None)?;
+        let (reply, answer) = oneshot::channel();
+        let instance_ids = self
+            .ask(Call::ListInstances(reply), answer)
+            .await?
+            .into_iter()
+            .filter(|id| self.shared.interface.exposes(id))
+            .collect();
+        Ok(Response::new(proto::ListInstancesResponse { instance_ids }))
+    }
+
+    async fn create_agent(
+        &self,
+        request: Request<proto::CreateAgentRequest>,
+    ) -> Result<Response<proto::AgentInfo>, Status> {
+        self.authorize(&request, GrpcMethod::CreateAgent, None)?;
+        let (reply, answer) = oneshot::channel();
+        let agent = self
+            .ask(Call::CreateAgent(request.into_inner(), reply), answer)
+            .await?;
+        Ok(Response::new(agent_info(agent)))
+    }
+
+    async fn list_agents(
+        &self,
+        request: Request<proto::ListAgentsRequest>,
+    ) -> Result<Response<proto::ListAgentsResponse>, Status> {
+        self.authorize(&request, GrpcMethod::ListAgents, None)?;
+        let (reply, answer) = oneshot::channel();
+        let agents = self.ask(Call::ListAgents(reply), answer).await?;
+        Ok(Response::new(proto::ListAgentsResponse {
+            agents: agents.into_iter().map(agent_info).collect(),
+        }))
+    }
+
+    async fn add_instance(
+        &self,
+        request: Request<proto::AddInstanceRequest>,
+    ) -> Result<Response<proto::AddInstanceResponse>, Status> {
+        self.authorize(&request, GrpcMethod::AddInstance, None)?;
+        let (reply, answer) = oneshot::channel();
+        self.ask(Call::AddInstance(request.into_inner(), reply), answer)
+            .await?;
+        Ok(Response::new(proto::AddInstanceResponse {}))
+    }
+
+    async fn clone_instance(
+        &self,
+        request: Request<proto::CloneInstanceRequest>,
+    ) -> Result<Response<proto::CloneInstanceResponse>, Status> {
+        self.authorize(&request, GrpcMethod::CloneInstance, None)?;
+        let (reply, answer) = oneshot::channel();
+        let dna_hash = self
+            .ask(Call::CloneInstance(request.into_inner(), reply), answer)
+            .await?;
+        Ok(Response::new(proto::CloneInstanceResponse { dna_hash }))
+    }
+}
+
+fn json<T: serde::de::DeserializeOwned>(json: &str) -> Result<T, HolochainError> {
+    serde_json::from_str(json).map_err(|e| HolochainError::ErrorGeneric(e.to_string()))
+}
+
+/// add the instance of the request, returns its id
+fn add_instance(
+    container: &mut Container,
+    admin: &dyn Admin,
+    request: &proto::AddInstanceRequest,
+) -> Result<String, HolochainError> {
+    let config: InstanceConfiguration = json(&request.config)?;
+    let dna = admin.load_dna(&config.dna)?;
+    container.add_agent_instance(&config, dna, admin.registry(), &request.passphrase)?;
+    Ok(config.id)
+}
+
+/// clone the instance of the request, returns the hash of the DNA of the clone
+#[allow(clippy::arc_with_non_send_sync)]
+fn clone_instance(
+    container: &mut Container,
+    admin: &dyn Admin,
+    request: &proto::CloneInstanceRequest,
+) -> Result<String, HolochainError> {
+    let changes: CloneChanges = if request.changes.is_empty() {
+        CloneChanges::default()
+    } else {
+        json(&request.changes)?
+    };
+    let context = admin.clone_context(&request.instance_id, &request.clone_id)?;
+    let dna = container.clone_instance(
+        &request.instance_id,
+        &request.clone_id,
+        &changes,
+        Arc::new(context),
+    )?;
+    Ok(dna.hash())
+}
+
+/// a gRPC server of an interface, serving until stopped or dropped
+pub struct GrpcServer {
+    shared: Arc<Shared>,
+    address: SocketAddr,
+    calls: std_mpsc::Receiver<Call>,
+    /// the signals of the instances, by id of the instance
+    signals: Vec<(String, std_mpsc::Receiver<Signal>)>,
+    shutdown: Option<oneshot::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl GrpcServer {
+    /// serve the interface at address, port 0 for any that is free, see address(), with the
+    /// signals of the instances of the container, the ones added through the server included
+    /// fails for interfaces with another driver and addresses that can't be listened at
+    pub fn start(
+        interface: &InterfaceConfiguration,
+        address: SocketAddr,
+        container: &Container,
+    ) -> Result<GrpcServer, HolochainError> {
+        if interface.driver != InterfaceDriver::Grpc {
+            return Err(HolochainError::ErrorGeneric(format!(
+                "interface '{}' doesn't have the \"grpc\" driver",
+                interface.id
+            )));
+        }
+        interface.check()?;
+        let io = |e: std::io::Error| HolochainError::ErrorGeneric(e.to_string());
+        let listener = std::net::TcpListener::bind(address).map_err(io)?;
+        listener.set_nonblocking(true).map_err(io)?;
+        let address = listener.local_addr().map_err(io)?;
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .enable_io()
+            .build()
+            .map_err(io)?;
+
+        let (calls, calls_received) = std_mpsc::channel();
+        let shared = Arc::new(Shared {
+            interface: interface.clone(),
+            calls: Mutex::new(calls),
+            challenges: Mutex::new(VecDeque::new()),
+            buckets: Mutex::new(HashMap::new()),
+            streams: Mutex::new(Vec::new()),
+            failure: Mutex::new(None),
+        });
+        let service = ConductorServer::new(Service {
+            shared: shared.clone(),
+        });
+        let (shutdown, shut_down) = oneshot::channel::<()>();
+        let serving = shared.clone();
+        let thread = platform::spawn("grpc_server", move || {
+            let served = runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                Server::builder()
+                    .add_service(service)
+                    .serve_with_incoming_shutdown(TcpListenerStream::new(listener), async {
+                        shut_down.await.ok();
+                    })
+                    .await
+                    .map_err(|e| std::io::Error::other(e.to_string()))
+            });
+            if let Err(e) = served {
+                *serving.failure.lock().unwrap() = Some(e.to_string());
+            }
+        });
+
+        let signals = container
+            .instance_ids()
+            .into_iter()
+            .filter_map(|id| {
+                let signals = container.instance(&id)?.signals();
+                Some((id, signals))
+            })
+            .collect();
+        Ok(GrpcServer {
+            shared,
+            address,
+            calls: calls_received,
+            signals,
+            shutdown: Some(shutdown),
+            thread: Some(thread),
+        })
+    }
+
+    /// the address served at
+    pub fn address(&self) -> SocketAddr {
+        self.address
+    }
+
+    /// answer the calls taken since last called and push the signals emitted since down the
+    /// Signals streams, returns how many calls were answered
+    /// meant to be called regularly on the thread running the container, e.g. in its main loop,
+    /// zome calls block it until they return
+    pub fn handle(&mut self, container: &mut Container, admin: &dyn Admin) -> usize {
+        let calls: Vec<Call> = self.calls.try_iter().collect();
+        let answered = calls.len();
+        for call in calls {
+            // the client may be gone by the time there is an answer, it is dropped then
+            match call {
+                Call::Zome(request, reply) => {
+                    let result = match container.instance_mut(&request.instance_id) {
+                        Some(instance) => instance.call(
+                            request.zome,
+                            request.capability,
+                            request.function,
+                            request.params,
+                        ),
+                        None => Err(HolochainError::ErrorGeneric(format!(
+                            "instance '{}' is not running",
+                            request.instance_id
+                        ))),
+                    };
+                    reply.send(result).ok();
+                }
+                Call::ListInstances(reply) => {
+                    reply.send(Ok(container.instance_ids())).ok();
+                }
+                Call::CreateAgent(request, reply) => {
+                    let agent = container.create_agent(&request.name, &request.passphrase);
+                    reply.send(agent).ok();
+                }
+                Call::ListAgents(reply) => {
+                    reply.send(Ok(container.agents())).ok();
+                }
+                Call::AddInstance(request, reply) => {
+                    let added = add_instance(container, admin, &request);
+                    if let Ok(ref instance_id) = added {
+                        self.subscribe(container, instance_id);
+                    }
+                    reply.send(added).ok();
+                }
+                Call::CloneInstance(request, reply) => {
+                    let cloned = clone_instance(container, admin, &request);
+                    if cloned.is_ok() {
+                        self.subscribe(container, &request.clone_id);
+                    }
+                    reply.send(cloned).ok();
+                }
+            }
+        }
+        self.forward_signals();
+        answered
+    }
+
+    /// receive the signals of the instance added with the given id
+    fn subscribe(&mut self, container: &Container, instance_id: &str) {
+        if let Some(instance) = container.instance(instance_id) {
+            self.signals
+                .push((instance_id.to_string(), instance.signals()));
+        }
+    }
+
+    fn forward_signals(&self) {
+        let mut open = self.shared.streams.lock().unwrap();
+        open.retain(|stream| !stream.sender.is_closed());
+        for (instance_id, signals) in &self.signals {
+            for signal in signals.try_iter() {
+                let signal = InstanceSignal {
+                    instance_id: instance_id.clone(),
+                    signal,
+                };
+                let message = proto::InstanceSignal {
+                    instance_id: signal.instance_id.clone(),
+                    zome: signal.signal.zome.clone(),
+                    name: signal.signal.name.clone(),
+                    payload: signal.signal.payload.to_string(),
+                };
+                for stream in open.iter() {
+                    if streams(&self.shared.interface, &stream.instance_ids, &signal) {
+                        // streams that fell behind miss the signal
+                        stream.sender.try_send(Ok(message.clone())).ok();
+                    }
+                }
+            }
+        }
+    }
+
+    /// stop serving, open streams end
+    /// fails if the server had stopped serving before, telling why
+    pub fn stop(mut self) -> Result<(), HolochainError> {
+        self.shut_down();
+        match self.shared.failure.lock().unwrap().take() {
+            Some(failure) => Err(HolochainError::ErrorGeneric(failure)),
+            None => Ok(()),
+        }
+    }
+
+    fn shut_down(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+        self.shared.streams.lock().unwrap().clear();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+
+impl Drop for GrpcServer {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::proto::conductor_client::ConductorClient;
+    use holochain_agent::Agent;
+    use holochain_core::{logger::SimpleLogger, persister::SimplePersister};
+    use holochain_core_api::{interface::sign_challenge, rate_limit::RateLimit, Holochain};
+    use std::{future::Future, thread};
+    use tonic::{transport::Channel, Code};
+
+    pub fn test_interface(auth: AuthConfiguration) -> InterfaceConfiguration {
+        InterfaceConfiguration {
+            id: "backend".to_string(),
+            admin: false,
+            allow_remote: false,
+            auth,
+            instances: Some(vec!["app".to_string()]),
+            allowed_origins: Vec::new(),
+            rate_limit: None,
+            encodings: Vec::new(),
+            driver: InterfaceDriver::Grpc,
+        }
+    }
+
+    pub fn test_tokens() -> AuthConfiguration {
+        AuthConfiguration::Token {
+            tokens: vec!["first".to_string(), "second".to_string()],
+        }
+    }
+
+    fn test_context() -> Context {
+        Context {
+            agent: Agent::from_string("bob"),
+            logger: Arc::new(Mutex::new(SimpleLogger {})),
+            persister: Arc::new(Mutex::new(SimplePersister::new())),
+        }
+    }
+
+    #[allow(clippy::arc_with_non_send_sync)]
+    fn test_container() -> Container {
+        let mut container = Container::new();
+        for id in &["app", "other"] {
+            let instance = Holochain::new(Dna::new(), Arc::new(test_context())).unwrap();
+            container.add_instance(id, instance);
+        }
+        container
+    }
+
+    #[derive(Default)]
+    struct TestAdmin {
+        registry: StorageRegistry,
+    }
+
+    impl Admin for TestAdmin {
+        fn load_dna(&self, _path: &str) -> Result<Dna, HolochainError> {
+            Ok(Dna::new())
+        }
+
+        fn registry(&self) -> &StorageRegistry {
+            &self.registry
+        }
+
+        fn clone_context(&self, _: &str, _: &str) -> Result<Context, HolochainError> {
+            Ok(test_context())
+        }
+    }
+
+    fn localhost() -> SocketAddr {
+        "127.0.0.1:0".parse().unwrap()
+    }
+
+    /// the message with the credential in its metadata
+    fn with<T>(credential: &str, message: T) -> Request<T> {
+        let mut request = Request::new(message);
+        let value = credential.parse().unwrap();
+        request.metadata_mut().insert(AUTHORIZATION_METADATA, value);
+        request
+    }
+
+    /// run the client on a thread of its own, connected to the server
+    fn client<T, C, F>(server: &GrpcServer, client: C) -> thread::JoinHandle<T>
+    where
+        T: Send + 'static,
+        C: FnOnce(ConductorClient<Channel>) -> F + Send + 'static,
+        F: Future<Output = T>,
+    {
+        let url = format!("http://{}", server.address());
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Runtime::new().unwrap();
+            runtime.block_on(async move {
+                client(ConductorClient::connect(url).await.unwrap()).await
+            })
+        })
+    }
+
+    /// handle the calls of the client until it is done
+    fn serve<T>(
+        server: &mut GrpcServer,
+        container: &mut Container,
+        client: thread::JoinHandle<T>,
+    ) -> T {
+        while !client.is_finished() {
+            server.handle(container, &TestAdmin::default());
+            thread::sleep(Duration::from_millis(5));
+        }
+        client.join().unwrap()
+    }
+
+    #[test]
+    /// calls are authenticated and authorized like on JSON-RPC
+    fn serves_calls() {
+        let mut container = test_container();
+        let interface = test_interface(test_tokens());
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let calls = client(&server, |mut client| async move {
+            let unauthenticated = client
+                .list_instances(proto::ListInstancesRequest {})
+                .await
+                .unwrap_err()
+                .code();
+            let wrong = client
+                .list_instances(with("third", proto::ListInstancesRequest {}))
+                .await
+                .unwrap_err()
+                .code();
+            let instances = client
+                .list_instances(with("first", proto::ListInstancesRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .instance_ids;
+            let call = |instance_id: &str| proto::CallZomeRequest {
+                instance_id: instance_id.to_string(),
+                zome: "blog".to_string(),
+                capability: "main".to_string(),
+                function: "create_post".to_string(),
+                params: "{}".to_string(),
+            };
+            let inactive = client.call_zome(with("first", call("app"))).await;
+            let hidden = client.call_zome(with("first", call("other"))).await;
+            let admin = client
+                .list_agents(with("first", proto::ListAgentsRequest {}))
+                .await;
+            let challenge = client.challenge(proto::ChallengeRequest {}).await;
+            (
+                unauthenticated,
+                wrong,
+                instances,
+                inactive.unwrap_err().code(),
+                hidden.unwrap_err().code(),
+                admin.unwrap_err().code(),
+                challenge.unwrap_err().code(),
+            )
+        });
+        let calls = serve(&mut server, &mut container, calls);
+        assert_eq!(Code::Unauthenticated, calls.0);
+        assert_eq!(Code::Unauthenticated, calls.1);
+        assert_eq!(vec!["app".to_string()], calls.2);
+        // the instance isn't started
+        assert_eq!(Code::Unknown, calls.3);
+        assert_eq!(Code::PermissionDenied, calls.4);
+        assert_eq!(Code::PermissionDenied, calls.5);
+        assert_eq!(Code::FailedPrecondition, calls.6);
+        assert_eq!(Ok(()), server.stop());
+    }
+
+    #[test]
+    /// challenge interfaces take a nonce the server sent with its signature
+    fn authenticates_with_challenges() {
+        let mut container = test_container();
+        let interface = test_interface(AuthConfiguration::Challenge {
+            secret: "shared".to_string(),
+        });
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let calls = client(&server, |mut client| async move {
+            let nonce = client
+                .challenge(proto::ChallengeRequest {})
+                .await
+                .unwrap()
+                .into_inner()
+                .nonce;
+            let answer = |nonce: &str, secret: &str| {
+                let credential = format!("{}:{}", nonce, sign_challenge(secret, nonce));
+                with(&credential, proto::ListInstancesRequest {})
+            };
+            let answered = client.list_instances(answer(&nonce, "shared")).await;
+            let replayed = client.list_instances(answer(&nonce, "shared")).await;
+            let nonce = client
+                .challenge(proto::ChallengeRequest {})
+                .await
+                .unwrap()
+                .into_inner()
+                .nonce;
+            let guessed = client.list_instances(answer(&nonce, "guess")).await;
+            // a wrong answer uses the challenge up too
+            let retried = client.list_instances(answer(&nonce, "shared")).await;
+            let made_up = client.list_instances(answer("made up", "shared")).await;
+            (
+                answered.is_ok(),
+                replayed.unwrap_err().code(),
+                guessed.unwrap_err().code(),
+                retried.unwrap_err().code(),
+                made_up.unwrap_err().code(),
+            )
+        });
+        let (answered, replayed, guessed, retried, made_up) =
+            serve(&mut server, &mut container, calls);
+        assert!(answered);
+        assert_eq!(Code::Unauthenticated, replayed);
+        assert_eq!(Code::Unauthenticated, guessed);
+        assert_eq!(Code::Unauthenticated, retried);
+        assert_eq!(Code::Unauthenticated, made_up);
+    }
+
+    #[test]
+    /// a peer asking for challenges only pushes out its own
+    fn bounds_challenges_by_peer() {
+        let mut container = test_container();
+        let interface = test_interface(AuthConfiguration::Challenge {
+            secret: "shared".to_string(),
+        });
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let other: IpAddr = "127.0.0.2".parse().unwrap();
+        server.shared.challenges.lock().unwrap().push_back(SentChallenge {
+            peer: other,
+            nonce: "other's".to_string(),
+            sent: Instant::now(),
+        });
+        let calls = client(&server, |mut client| async move {
+            let mut nonces = Vec::new();
+            for _ in 0..=MAX_PEER_CHALLENGES {
+                let challenge = client.challenge(proto::ChallengeRequest {}).await;
+                nonces.push(challenge.unwrap().into_inner().nonce);
+            }
+            nonces
+        });
+        let nonces = serve(&mut server, &mut container, calls);
+        let pending: Vec<(IpAddr, String)> = server
+            .shared
+            .challenges
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|challenge| (challenge.peer, challenge.nonce.clone()))
+            .collect();
+        assert_eq!(MAX_PEER_CHALLENGES + 1, pending.len());
+        assert_eq!((other, "other's".to_string()), pending[0]);
+        // the first nonce sent to the client was forgotten
+        assert!(pending.iter().all(|(_, nonce)| *nonce != nonces[0]));
+        assert_eq!(nonces[MAX_PEER_CHALLENGES], pending[MAX_PEER_CHALLENGES].1);
+    }
+
+    #[test]
+    fn rate_limits_zome_calls() {
+        let mut container = test_container();
+        let interface = InterfaceConfiguration {
+            rate_limit: Some(RateLimit {
+                calls_per_second: 1,
+                burst: 1,
+            }),
+            ..test_interface(test_tokens())
+        };
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let calls = client(&server, |mut client| async move {
+            let call = || proto::CallZomeRequest {
+                instance_id: "app".to_string(),
+                ..Default::default()
+            };
+            let first = client.call_zome(with("first", call())).await;
+            let second = client.call_zome(with("first", call())).await;
+            let other = client.call_zome(with("second", call())).await;
+            (
+                first.unwrap_err().code(),
+                second.unwrap_err().code(),
+                other.unwrap_err().code(),
+            )
+        });
+        let (first, second, other) = serve(&mut server, &mut container, calls);
+        assert_eq!(Code::Unknown, first);
+        assert_eq!(Code::ResourceExhausted, second);
+        // the bucket is the peer's, another credential doesn't get it a fresh one
+        assert_eq!(Code::ResourceExhausted, other);
+        assert_eq!(1, server.shared.buckets.lock().unwrap().len());
+    }
+
+    #[test]
+    /// streams carry the signals of the instances the interface exposes
+    fn streams_signals() {
+        let mut container = test_container();
+        let interface = test_interface(test_tokens());
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let (open, opened) = std_mpsc::channel();
+        let stream = client(&server, move |mut client| async move {
+            let hidden = client
+                .signals(with("first", proto::SignalsRequest {
+                    instance_ids: vec!["other".to_string()],
+                }))
+                .await
+                .unwrap_err()
+                .code();
+            let mut signals = client
+                .signals(with("first", proto::SignalsRequest::default()))
+                .await
+                .unwrap()
+                .into_inner();
+            open.send(()).unwrap();
+            (hidden, signals.message().await.unwrap().unwrap())
+        });
+        let emit = |container: &mut Container, instance_id: &str| {
+            let signal = Signal {
+                zome: "blog".to_string(),
+                name: "new_post".to_string(),
+                payload: serde_json::json!({"title": "hi"}),
+            };
+            let instance = container.instance_mut(instance_id).unwrap();
+            instance.state().unwrap().nucleus().signal_bus().emit(&signal);
+        };
+        while opened.try_recv().is_err() {
+            server.handle(&mut container, &TestAdmin::default());
+            thread::sleep(Duration::from_millis(5));
+        }
+        emit(&mut container, "other");
+        emit(&mut container, "app");
+        let (hidden, signal) = serve(&mut server, &mut container, stream);
+        assert_eq!(Code::PermissionDenied, hidden);
+        assert_eq!("app", signal.instance_id);
+        assert_eq!("new_post", signal.name);
+        assert_eq!(r#"{"title":"hi"}"#, signal.payload);
+    }
+
+    #[test]
+    /// admin interfaces manage the container
+    fn manages_container() {
+        let mut container = test_container();
+        let interface = InterfaceConfiguration {
+            admin: true,
+            instances: None,
+            ..test_interface(test_tokens())
+        };
+        let mut server = GrpcServer::start(&interface, localhost(), &container).unwrap();
+        let calls = client(&server, |mut client| async move {
+            let agents = client
+                .list_agents(with("first", proto::ListAgentsRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .agents;
+            // there is no storage root to keep the keys of agents in
+            let created = client
+                .create_agent(with("first", proto::CreateAgentRequest {
+                    name: "carol".to_string(),
+                    passphrase: "carol's passphrase".to_string(),
+                }))
+                .await
+                .unwrap_err()
+                .code();
+            let cloned = client
+                .clone_instance(with("first", proto::CloneInstanceRequest {
+                    instance_id: "app".to_string(),
+                    clone_id: "copy".to_string(),
+                    changes: r#"{"uuid": "copied"}"#.to_string(),
+                }))
+                .await
+                .unwrap()
+                .into_inner()
+                .dna_hash;
+            let malformed = client
+                .add_instance(with("first", proto::AddInstanceRequest {
+                    config: "{".to_string(),
+                    passphrase: String::new(),
+                }))
+                .await
+                .unwrap_err()
+                .code();
+            let instances = client
+                .list_instances(with("first", proto::ListInstancesRequest {}))
+                .await
+                .unwrap()
+                .into_inner()
+                .instance_ids;
+            (agents, created, cloned, malformed, instances)
+        });
+        let (agents, created, cloned, malformed, instances) =
+            serve(&mut server, &mut container, calls);
+        assert!(agents.is_empty());
+        assert_eq!(Code::Unknown, created);
+        let copy = container.instance("copy").unwrap().dna().unwrap();
+        assert_eq!("copied", copy.uuid);
+        assert_eq!(copy.hash(), cloned);
+        assert_eq!(Code::Unknown, malformed);
+        assert_eq!(vec!["app", "copy", "other"], instances);
+    }
+}
@@ -0,0 +1,210 @@
+//! the methods of the Conductor service and what a connection needs to call each of them
+//! every request carries the credential of the connection in its "authorization" metadata, the
+//! connection authenticates with it before the call is authorized, see authorize()
+
+use holochain_core::error::HolochainError;
+use holochain_core_api::{
+    container::InstanceSignal, interface::{Connection, InterfaceConfiguration},
+};
+
+/// full name of the service in proto/conductor.proto
+pub const GRPC_SERVICE: &str = "holochain.conductor.Conductor";
+
+/// metadata key requests carry the credential of the connection in
+pub const AUTHORIZATION_METADATA: &str = "authorization";
+
+/// the methods of the service
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GrpcMethod {
+    Challenge,
+    CallZome,
+    /// streams InstanceSignals, see streams()
+    Signals,
+    ListInstances,
+    CreateAgent,
+    ListAgents,
+    AddInstance,
+    CloneInstance,
+}
+
+const METHODS: [GrpcMethod; 8] = [
+    GrpcMethod::Challenge,
+    GrpcMethod::CallZome,
+    GrpcMethod::Signals,
+    GrpcMethod::ListInstances,
+    GrpcMethod::CreateAgent,
+    GrpcMethod::ListAgents,
+    GrpcMethod::AddInstance,
+    GrpcMethod::CloneInstance,
+];
+
+impl GrpcMethod {
+    /// the name in proto/conductor.proto
+    pub fn name(self) -> &'static str {
+        match self {
+            GrpcMethod::Challenge => "Challenge",
+            GrpcMethod::CallZome => "CallZome",
+            GrpcMethod::Signals => "Signals",
+            GrpcMethod::ListInstances => "ListInstances",
+            GrpcMethod::CreateAgent => "CreateAgent",
+            GrpcMethod::ListAgents => "ListAgents",
+            GrpcMethod::AddInstance => "AddInstance",
+            GrpcMethod::CloneInstance => "CloneInstance",
+        }
+    }
+
+    /// the path requests for the method are sent to, e.g. "/holochain.conductor.Conductor/CallZome"
+    pub fn path(self) -> String {
+        format!("/{}/{}", GRPC_SERVICE, self.name())
+    }
+
+    /// the method requests sent to the path are for, None for paths of no method of the service
+    pub fn from_path(path: &str) -> Option<GrpcMethod> {
+        METHODS
+            .iter()
+            .cloned()
+            .find(|method| method.path() == path)
+    }
+
+    /// the JSON-RPC method the method does the same as, None for the ones JSON-RPC does
+    /// otherwise, e.g. zome calls, which JSON-RPC names after the zome function
+    pub fn json_rpc_method(self) -> Option<&'static str> {
+        match self {
+            GrpcMethod::ListInstances => Some("info/instances"),
+            GrpcMethod::CreateAgent => Some("admin/agent/create"),
+            GrpcMethod::ListAgents => Some("admin/agent/list"),
+            GrpcMethod::AddInstance => Some("admin/instance/add"),
+            GrpcMethod::CloneInstance => Some("admin/instance/clone"),
+            GrpcMethod::Challenge | GrpcMethod::CallZome | GrpcMethod::Signals => None,
+        }
+    }
+
+    /// true for the methods only admin interfaces serve
+    pub fn is_admin(self) -> bool {
+        matches!(
+            self,
+            GrpcMethod::CreateAgent
+                | GrpcMethod::ListAgents
+                | GrpcMethod::AddInstance
+                | GrpcMethod::CloneInstance
+        )
+    }
+
+    /// true for the methods answering with a stream
+    pub fn is_streaming(self) -> bool {
+        self == GrpcMethod::Signals
+    }
+
+    /// check the connection may call the method, the instance_id being the one a zome call is
+    /// for, Challenge needs no authentication
+    pub fn authorize(
+        self,
+        connection: &Connection,
+        instance_id: Option<&str>,
+    ) -> Result<(), HolochainError> {
+        match self {
+            GrpcMethod::Challenge => Ok(()),
+            GrpcMethod::CallZome => match instance_id {
+                Some(instance_id) => connection.authorize(instance_id),
+                None => Err(HolochainError::new("zome calls have to name an instance")),
+            },
+            _ if self.is_admin() => connection.authorize_admin(),
+            _ if connection.authenticated() => Ok(()),
+            _ => Err(HolochainError::new("connection is not authenticated")),
+        }
+    }
+}
+
+/// whether the signal goes down a Signals stream of the interface asking for the signals of the
+/// instances with the given ids, all the interface exposes if there are none
+/// the connection opening the stream is authorized before, see authorize()
+pub fn streams(
+    interface: &InterfaceConfiguration,
+    instance_ids: &[String],
+    signal: &InstanceSignal,
+) -> bool {
+    (instance_ids.is_empty() || instance_ids.contains(&signal.instance_id))
+        && interface.exposes(&signal.instance_id)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::tests::{test_interface, test_tokens};
+    use holochain_core::signal::Signal;
+    use serde_json::json;
+    use std::net::{IpAddr, Ipv4Addr};
+
+    fn localhost() -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1))
+    }
+
+    fn test_grpc_interface(admin: bool) -> InterfaceConfiguration {
+        InterfaceConfiguration {
+            admin,
+            ..test_interface(test_tokens())
+        }
+    }
+
+    fn signal(instance_id: &str) -> InstanceSignal {
+        InstanceSignal {
+            instance_id: instance_id.to_string(),
+            signal: Signal {
+                zome: "blog".to_string(),
+                name: "new_post".to_string(),
+                payload: json!({}),
+            },
+        }
+    }
+
+    #[test]
+    fn paths() {
+        for method in METHODS.iter() {
+            assert_eq!(Some(*method), GrpcMethod::from_path(&method.path()));
+        }
+        assert_eq!(
+            "/holochain.conductor.Conductor/CallZome",
+            GrpcMethod::CallZome.path()
+        );
+        assert_eq!(None, GrpcMethod::from_path("/holochain.conductor.Conductor/Shutdown"));
+        assert_eq!(None, GrpcMethod::from_path("CallZome"));
+        assert!(GrpcMethod::Signals.is_streaming());
+        assert!(!GrpcMethod::CallZome.is_streaming());
+        assert_eq!(
+            Some("admin/agent/create"),
+            GrpcMethod::CreateAgent.json_rpc_method()
+        );
+    }
+
+    #[test]
+    /// calls are authorized like on JSON-RPC, admin methods on admin interfaces only
+    fn authorize() {
+        let interface = test_grpc_interface(false);
+        let mut connection = interface.connect(&localhost(), None).unwrap();
+        assert_eq!(Ok(()), GrpcMethod::Challenge.authorize(&connection, None));
+        assert!(GrpcMethod::ListInstances.authorize(&connection, None).is_err());
+        assert!(GrpcMethod::CallZome.authorize(&connection, Some("app")).is_err());
+
+        connection.authenticate("first").unwrap();
+        assert_eq!(Ok(()), GrpcMethod::CallZome.authorize(&connection, Some("app")));
+        assert!(GrpcMethod::CallZome.authorize(&connection, Some("other")).is_err());
+        assert!(GrpcMethod::CallZome.authorize(&connection, None).is_err());
+        assert_eq!(Ok(()), GrpcMethod::ListInstances.authorize(&connection, None));
+        assert!(GrpcMethod::CreateAgent.authorize(&connection, None).is_err());
+
+        let admin = test_grpc_interface(true);
+        let mut connection = admin.connect(&localhost(), None).unwrap();
+        connection.authenticate("second").unwrap();
+        assert_eq!(Ok(()), GrpcMethod::AddInstance.authorize(&connection, None));
+    }
+
+    #[test]
+    /// streams carry the signals of the instances asked for and exposed
+    fn signal_streams() {
+        let interface = test_grpc_interface(false);
+        assert!(streams(&interface, &[], &signal("app")));
+        assert!(!streams(&interface, &[], &signal("other")));
+        assert!(!streams(&interface, &["team".to_string()], &signal("app")));
+        assert!(!streams(&interface, &["other".to_string()], &signal("other")));
+    }
+}
@@ -0,0 +1,45 @@
+// Verifies a chain client side with the core light client module.
+//
+// Build the module with `make wasm_light_client`, then either
+//   node verify_chain.js <module.wasm> <chain.json>
+// or, in a browser, load this file and call verifyChain() with the module's bytes, e.g. from
+// fetch(), and a chain as written by Chain::to_json().
+
+async function verifyChain (wasmBytes, chainJson) {
+  const { instance } = await WebAssembly.instantiate(wasmBytes, {})
+  const hc = instance.exports
+
+  // write the chain into the module's memory as a null terminated string
+  const bytes = new TextEncoder().encode(chainJson)
+  const ptr = hc.holochain_alloc(bytes.length + 1)
+  const memory = new Uint8Array(hc.memory.buffer)
+  memory.set(bytes, ptr)
+  memory[ptr + bytes.length] = 0
+
+  const result = hc.holochain_verify_chain(ptr)
+  hc.holochain_free(ptr, bytes.length + 1)
+
+  // read the result back, the memory may have grown in the meantime
+  const out = new Uint8Array(hc.memory.buffer)
+  let end = result
+  while (out[end] !== 0) end++
+  const verification = JSON.parse(new TextDecoder().decode(out.subarray(result, end)))
+  hc.holochain_string_free(result)
+  return verification
+}
+
+if (typeof module !== 'undefined' && require.main === module) {
+  const fs = require('fs')
+  const [wasmPath, chainPath] = process.argv.slice(2)
+  verifyChain(fs.readFileSync(wasmPath), fs.readFileSync(chainPath, 'utf8')).then(verification => {
+    if (verification.error) {
+      console.log(`couldn't load the chain: ${verification.error}`)
+    } else if (verification.problems.length === 0) {
+      console.log(`valid, ${verification.checked} headers checked`)
+    } else {
+      verification.problems.forEach(problem => console.log(problem))
+    }
+  })
+} else if (typeof module !== 'undefined') {
+  module.exports = { verifyChain }
+}
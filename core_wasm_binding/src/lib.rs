@@ -0,0 +1,110 @@
+//! This crate exposes what core can do without running an instance to JavaScript, built for
+//! wasm32-unknown-unknown, e.g. for browser light clients verifying chains client side.
+//!
+//! Strings cross the boundary as null terminated UTF-8 in the module's memory: JavaScript
+//! allocates room with holochain_alloc(), writes the string and passes the pointer. Returned
+//! strings are JSON and have to be freed with holochain_string_free().
+//!
+//! See example/verify_chain.js.
+
+extern crate holochain_core;
+#[macro_use]
+extern crate serde_json;
+
+use holochain_core::chain::verify::{chain_from_json, verify};
+use std::{
+    ffi::{CStr, CString}, mem, os::raw::c_char,
+};
+
+fn to_c_string(s: String) -> *mut c_char {
+    match CString::new(s) {
+        Ok(s) => s.into_raw(),
+        Err(_) => std::ptr::null_mut(),
+    }
+}
+
+/// room in the module's memory for len bytes, e.g. a string with its null terminator
+#[no_mangle]
+pub extern "C" fn holochain_alloc(len: usize) -> *mut u8 {
+    let mut buffer = Vec::with_capacity(len);
+    let ptr = buffer.as_mut_ptr();
+    mem::forget(buffer);
+    ptr
+}
+
+/// give back room from holochain_alloc()
+///
+/// # Safety
+///
+/// ptr has to be from holochain_alloc() called with the same len, not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_free(ptr: *mut u8, len: usize) {
+    if ptr.is_null() {
+        return;
+    }
+    drop(Vec::from_raw_parts(ptr, 0, len));
+}
+
+/// free a string returned by this module
+///
+/// # Safety
+///
+/// s has to be a string from this module not used again afterwards
+#[no_mangle]
+pub unsafe extern "C" fn holochain_string_free(s: *mut c_char) {
+    if s.is_null() {
+        return;
+    }
+    drop(CString::from_raw(s));
+}
+
+/// verify a chain given as JSON, top to bottom as written by Chain::to_json()
+/// returns the Verification as JSON, or {"error": ...} if the chain doesn't parse
+///
+/// # Safety
+///
+/// chain_json has to be a null terminated string
+#[no_mangle]
+pub unsafe extern "C" fn holochain_verify_chain(chain_json: *const c_char) -> *mut c_char {
+    if chain_json.is_null() {
+        return std::ptr::null_mut();
+    }
+    let chain_json = CStr::from_ptr(chain_json).to_string_lossy();
+    let result = match chain_from_json(&chain_json) {
+        Ok(chain) => serde_json::to_value(verify(&chain)).expect("Verification should serialize"),
+        Err(error) => json!({ "error": format!("{:?}", error) }),
+    };
+    to_c_string(result.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    unsafe fn verify_chain(json: &str) -> serde_json::Value {
+        let json = CString::new(json).unwrap();
+        let result = holochain_verify_chain(json.as_ptr());
+        let value = serde_json::from_str(&CStr::from_ptr(result).to_string_lossy()).unwrap();
+        holochain_string_free(result);
+        value
+    }
+
+    #[test]
+    fn can_verify_chain() {
+        unsafe {
+            assert_eq!(
+                json!({"checked": 0, "unsigned": 0, "problems": []}),
+                verify_chain("[]")
+            );
+            assert!(verify_chain("not a chain")["error"].is_string());
+            assert!(holochain_verify_chain(std::ptr::null()).is_null());
+        }
+    }
+
+    #[test]
+    fn can_alloc_and_free() {
+        let ptr = holochain_alloc(16);
+        assert!(!ptr.is_null());
+        unsafe { holochain_free(ptr, 16) };
+    }
+}
@@ -25,11 +25,16 @@ extern crate serde;
 #[macro_use]
 extern crate serde_json;
 extern crate base64;
+extern crate multihash;
+extern crate rust_base58;
 extern crate uuid;
 
+pub mod resolver;
 pub mod wasm;
 pub mod zome;
 
+use multihash::{encode, Hash};
+use rust_base58::ToBase58;
 use uuid::Uuid;
 
 /// serde helper, provides a default empty object
@@ -37,6 +42,49 @@ fn _def_empty_object() -> serde_json::Value {
     json!({})
 }
 
+/// copy of a json value with object keys sorted so equal values serialize the same way
+fn canonical_json(value: &serde_json::Value) -> serde_json::Value {
+    match *value {
+        serde_json::Value::Object(ref map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            serde_json::Value::Object(
+                keys.into_iter()
+                    .map(|key| (key.clone(), canonical_json(&map[key])))
+                    .collect(),
+            )
+        }
+        serde_json::Value::Array(ref values) => {
+            serde_json::Value::Array(values.iter().map(canonical_json).collect())
+        }
+        ref other => other.clone(),
+    }
+}
+
+/// The version of the host API, the host functions zomes import, new DNAs target
+/// Hosts run DNAs targeting older versions with shims for what changed since, if they still
+/// support them
+pub const HOST_API_VERSION: u32 = 2;
+
+/// serde helper, DNAs from before they declared a host API version target the one current then
+fn _def_host_api_version() -> u32 {
+    2
+}
+
+/// How the addresses of entries are expressed to zomes, they are stored as multihashes either way
+/// and zomes can pass either form
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum AddressFormat {
+    /// base58 multihashes, e.g. "Qm..."
+    #[default]
+    #[serde(rename = "multihash")]
+    Multihash,
+    /// CIDv1 of the entry content as a raw IPFS block, e.g. "bafkrei...", so entries can be
+    /// looked up on IPFS and content pinned there referenced
+    #[serde(rename = "cid")]
+    Cid,
+}
+
 /// serde helper, provides a default newly generated v4 uuid
 fn _def_new_uuid() -> String {
     Uuid::new_v4().to_string()
@@ -65,6 +113,14 @@ pub struct Dna {
     #[serde(default)]
     pub dna_spec_version: String,
 
+    /// Which version of the host API do the zomes target?
+    #[serde(default = "_def_host_api_version")]
+    pub host_api_version: u32,
+
+    /// How entry addresses are expressed to the zomes.
+    #[serde(default)]
+    pub address_format: AddressFormat,
+
     /// Any arbitrary application properties can be included in this object.
     #[serde(default = "_def_empty_object")]
     pub properties: serde_json::Value,
@@ -83,6 +139,8 @@ impl Default for Dna {
             version: String::from(""),
             uuid: _def_new_uuid(),
             dna_spec_version: String::from("2.0"),
+            host_api_version: HOST_API_VERSION,
+            address_format: AddressFormat::default(),
             properties: _def_empty_object(),
             zomes: Vec::new(),
         }
@@ -152,6 +210,40 @@ impl Dna {
         serde_json::to_string_pretty(self)
     }
 
+    /// The b58 SHA256 multihash identifying this dna.
+    ///
+    /// Everything in the dna is hashed, including the properties, so the same code run with
+    /// different properties makes a distinct network.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # #[macro_use]
+    /// # extern crate serde_json;
+    /// # extern crate holochain_dna;
+    /// # fn main() {
+    /// use holochain_dna::Dna;
+    ///
+    /// let dna = Dna::new();
+    /// let mut other = dna.clone();
+    /// other.properties = json!({"max_post_length": 280});
+    ///
+    /// assert_ne!(dna.hash(), other.hash());
+    /// # }
+    /// ```
+    pub fn hash(&self) -> String {
+        let value = serde_json::to_value(self).expect("dna should serialize");
+        let json = serde_json::to_string(&canonical_json(&value)).expect("dna should serialize");
+        encode(Hash::SHA2256, json.as_bytes())
+            .expect("SHA256 should encode")
+            .to_base58()
+    }
+
+    /// Return the value of a top-level property, if it is set
+    pub fn get_property(&self, name: &str) -> Option<&serde_json::Value> {
+        self.properties.get(name)
+    }
+
     /// Return a Zome
     pub fn get_zome(&self, zome_name: &str) -> Option<&zome::Zome> {
         self.zomes.iter().find(|z| z.name == zome_name)
@@ -200,6 +292,46 @@ impl Dna {
             .find(|et| et.name == entry_type_name)?;
         Some(&entry_type.validation)
     }
+
+    /// Return the zomes implementing the named trait
+    pub fn get_zomes_implementing(&self, trait_name: &str) -> Vec<&zome::Zome> {
+        self.zomes
+            .iter()
+            .filter(|z| z.implements(trait_name))
+            .collect()
+    }
+
+    /// Return the definition of an entry type, from whichever zome defines it
+    pub fn get_entry_type(&self, entry_type_name: &str) -> Option<&zome::entry_types::EntryType> {
+        self.zomes
+            .iter()
+            .flat_map(|z| z.entry_types.iter())
+            .find(|et| et.name == entry_type_name)
+    }
+
+    /// Check a link from an entry of base_type to one of target_type with tag is declared,
+    /// either in the "links_to" of the base type or the "linked_from" of the target type
+    pub fn validate_link(
+        &self,
+        base_type: &str,
+        tag: &str,
+        target_type: &str,
+    ) -> Result<(), String> {
+        let base = self
+            .get_entry_type(base_type)
+            .ok_or_else(|| format!("unknown entry type '{}'", base_type))?;
+        let target = self
+            .get_entry_type(target_type)
+            .ok_or_else(|| format!("unknown entry type '{}'", target_type))?;
+        if base.links_to(target_type, tag) || target.linked_from(base_type, tag) {
+            Ok(())
+        } else {
+            Err(format!(
+                "'{}' entries may not link to '{}' entries with tag '{}'",
+                base_type, target_type, tag
+            ))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -240,6 +372,8 @@ mod tests {
                 "version": "test",
                 "uuid": "00000000-0000-0000-0000-000000000000",
                 "dna_spec_version": "2.0",
+                "host_api_version": 2,
+                "address_format": "multihash",
                 "properties": {
                     "test": "test"
                 },
@@ -266,7 +400,18 @@ mod tests {
                                             "code": "AAECAw=="
                                         }
                                     }
-                                ]
+                                ],
+                                "linked_from": [
+                                    {
+                                        "base_type": "test",
+                                        "tag": "test"
+                                    }
+                                ],
+                                "weight": 1,
+                                "rate_bucket": null,
+                                "searchable": false,
+                                "indexes": [],
+                                "retention": "forever"
                             }
                         ],
                         "capabilities": [
@@ -281,13 +426,30 @@ mod tests {
                                         "signature": {
                                             "inputs": [],
                                             "outputs": []
-                                        }
+                                        },
+                                        "read_only": false
                                     }
                                 ],
                                 "code": {
                                     "code": "AAECAw=="
                                 }
                             }
+                        ],
+                        "traits": [
+                            {
+                                "name": "test",
+                                "description": "test",
+                                "functions": [
+                                    {
+                                        "name": "test",
+                                        "signature": {
+                                            "inputs": [],
+                                            "outputs": []
+                                        },
+                                        "read_only": false
+                                    }
+                                ]
+                            }
                         ]
                     }
                 ]
@@ -301,6 +463,8 @@ mod tests {
         let serialized = dna.to_json().unwrap().replace(char::is_whitespace, "");
 
         assert_eq!(fixture, serialized);
+        assert_eq!(1, dna.get_zomes_implementing("test").len());
+        assert!(dna.get_zomes_implementing("other").is_empty());
     }
 
     #[test]
@@ -353,6 +517,25 @@ mod tests {
         assert!(dna.uuid.len() > 0);
     }
 
+    #[test]
+    fn parse_host_api_version() {
+        let dna = Dna::new_from_json(r#"{"host_api_version": 1}"#).unwrap();
+        assert_eq!(1, dna.host_api_version);
+
+        // DNAs from before they declared it target the version current then
+        let dna = Dna::new_from_json(r#"{}"#).unwrap();
+        assert_eq!(2, dna.host_api_version);
+        assert_eq!(HOST_API_VERSION, Dna::new().host_api_version);
+    }
+
+    #[test]
+    fn parse_address_format() {
+        let dna = Dna::new_from_json(r#"{"address_format": "cid"}"#).unwrap();
+        assert_eq!(AddressFormat::Cid, dna.address_format);
+        assert_eq!(AddressFormat::Multihash, Dna::new().address_format);
+        assert!(Dna::new_from_json(r#"{"address_format": "cidv0"}"#).is_err());
+    }
+
     #[test]
     fn parse_with_defaults_zome() {
         let dna = Dna::new_from_json(
@@ -526,7 +709,8 @@ mod tests {
                                         "signature": {
                                             "inputs": [],
                                             "outputs": []
-                                        }
+                                        },
+                                        "read_only": false
                                     }
                                 ],
                                 "code": {
@@ -545,4 +729,74 @@ mod tests {
         let fail = dna.get_wasm_for_capability("non existant zome", "test capability");
         assert_eq!(None, fail);
     }
+
+    #[test]
+    fn hash_includes_properties() {
+        let dna = Dna::new_from_json(
+            r#"{
+                "uuid": "00000000-0000-0000-0000-000000000000",
+                "properties": {"a": 1, "b": {"c": 2, "d": 3}}
+            }"#,
+        ).unwrap();
+
+        // the order properties are written in does not matter
+        let reordered = Dna::new_from_json(
+            r#"{
+                "properties": {"b": {"d": 3, "c": 2}, "a": 1},
+                "uuid": "00000000-0000-0000-0000-000000000000"
+            }"#,
+        ).unwrap();
+        assert_eq!(dna.hash(), reordered.hash());
+
+        let mut other = dna.clone();
+        other.properties = json!({"a": 2, "b": {"c": 2, "d": 3}});
+        assert_ne!(dna.hash(), other.hash());
+
+        assert_eq!(Some(&json!(1)), dna.get_property("a"));
+        assert_eq!(None, dna.get_property("z"));
+    }
+
+    #[test]
+    fn validate_link() {
+        let dna = Dna::new_from_json(
+            r#"{
+                "zomes": [
+                    {
+                        "name": "blog",
+                        "entry_types": [
+                            {
+                                "name": "post",
+                                "links_to": [
+                                    {"target_type": "comment", "tag": "comments"}
+                                ]
+                            },
+                            {
+                                "name": "comment"
+                            }
+                        ]
+                    },
+                    {
+                        "name": "profiles",
+                        "entry_types": [
+                            {
+                                "name": "profile",
+                                "linked_from": [
+                                    {"base_type": "post", "tag": "author"}
+                                ]
+                            }
+                        ]
+                    }
+                ]
+            }"#,
+        ).unwrap();
+
+        assert_eq!("profile", dna.get_entry_type("profile").unwrap().name);
+        assert_eq!(None, dna.get_entry_type("missing"));
+
+        assert_eq!(Ok(()), dna.validate_link("post", "comments", "comment"));
+        assert_eq!(Ok(()), dna.validate_link("post", "author", "profile"));
+        assert!(dna.validate_link("post", "author", "comment").is_err());
+        assert!(dna.validate_link("comment", "comments", "post").is_err());
+        assert!(dna.validate_link("missing", "comments", "comment").is_err());
+    }
 }
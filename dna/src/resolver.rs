@@ -0,0 +1,205 @@
+//! holochain_dna::resolver fetches DNAs by their hash, e.g. from a hApp store or other registry.
+//!
+//! A resolver asks its sources in turn for the DNA with a hash, checks what comes back hashes to
+//! it, and keeps it in its cache directory so it is only fetched once.
+//! Sources are locations the hash is appended to, e.g. `https://store.example.org/dna/`, fetched
+//! with the fetcher registered for their scheme. Only `file` is built in, fetchers for other
+//! schemes, e.g. `https`, are registered by the application embedding the resolver.
+
+use std::{
+    collections::HashMap, fs, path::{Path, PathBuf},
+};
+use Dna;
+
+/// fetches the DNA bundle, as JSON, at a location
+pub type Fetcher = Box<dyn Fn(&str) -> Result<String, String> + Send + Sync>;
+
+/// Resolves DNAs by hash from its sources, see the module docs.
+pub struct DnaResolver {
+    sources: Vec<String>,
+    fetchers: HashMap<String, Fetcher>,
+    cache_dir: Option<PathBuf>,
+}
+
+impl Default for DnaResolver {
+    /// A resolver without sources or cache that can fetch `file` locations.
+    fn default() -> Self {
+        let mut resolver = DnaResolver {
+            sources: Vec::new(),
+            fetchers: HashMap::new(),
+            cache_dir: None,
+        };
+        resolver.register(
+            "file",
+            Box::new(|location| {
+                let path = location.trim_start_matches("file://");
+                fs::read_to_string(path).map_err(|e| format!("couldn't read {}: {}", path, e))
+            }),
+        );
+        resolver
+    }
+}
+
+impl DnaResolver {
+    /// A resolver caching the DNAs it fetched in cache_dir, if given.
+    pub fn new(cache_dir: Option<&Path>) -> Self {
+        DnaResolver {
+            cache_dir: cache_dir.map(Path::to_path_buf),
+            ..Default::default()
+        }
+    }
+
+    /// Add (or replace) the fetcher for a location scheme.
+    pub fn register(&mut self, scheme: &str, fetcher: Fetcher) {
+        self.fetchers.insert(scheme.to_lowercase(), fetcher);
+    }
+
+    /// Add a source to ask for DNAs, after the ones added before.
+    pub fn add_source(&mut self, source: &str) {
+        self.sources.push(source.to_string());
+    }
+
+    /// Fetch the DNA at a location, failing unless it has the expected hash.
+    pub fn fetch(&self, location: &str, hash: &str) -> Result<Dna, String> {
+        let scheme = location
+            .split("://")
+            .next()
+            .filter(|scheme| *scheme != location)
+            .unwrap_or("file")
+            .to_lowercase();
+        let fetcher = self
+            .fetchers
+            .get(&scheme)
+            .ok_or_else(|| format!("no fetcher for '{}' locations", scheme))?;
+        let json = fetcher(location)?;
+        let dna = verify(&json, hash).map_err(|e| format!("{}: {}", location, e))?;
+        self.cache(hash, &json)?;
+        Ok(dna)
+    }
+
+    /// The DNA with the hash, from the cache or else the first source that has it.
+    pub fn resolve(&self, hash: &str) -> Result<Dna, String> {
+        if let Some(dna) = self.cached(hash) {
+            return Ok(dna);
+        }
+        let mut failures = Vec::new();
+        for source in &self.sources {
+            match self.fetch(&format!("{}{}", source, hash), hash) {
+                Ok(dna) => return Ok(dna),
+                Err(failure) => failures.push(failure),
+            }
+        }
+        Err(format!(
+            "couldn't resolve DNA {}: {}",
+            hash,
+            if failures.is_empty() {
+                "there are no sources".to_string()
+            } else {
+                failures.join(", ")
+            }
+        ))
+    }
+
+    fn cache_path(&self, hash: &str) -> Option<PathBuf> {
+        self.cache_dir
+            .as_ref()
+            .map(|dir| dir.join(format!("{}.json", hash)))
+    }
+
+    /// The DNA with the hash if it is cached and still hashes to it.
+    fn cached(&self, hash: &str) -> Option<Dna> {
+        let json = fs::read_to_string(self.cache_path(hash)?).ok()?;
+        verify(&json, hash).ok()
+    }
+
+    fn cache(&self, hash: &str, json: &str) -> Result<(), String> {
+        let path = match self.cache_path(hash) {
+            Some(path) => path,
+            None => return Ok(()),
+        };
+        let write = |path: &Path| {
+            fs::create_dir_all(path.parent().expect("cache paths are in the cache dir"))?;
+            fs::write(path, json)
+        };
+        write(&path).map_err(|e| format!("couldn't cache {}: {}", path.display(), e))
+    }
+}
+
+/// The DNA of a bundle, if it has the expected hash.
+pub fn verify(json: &str, hash: &str) -> Result<Dna, String> {
+    let dna = Dna::new_from_json(json).map_err(|e| format!("not a DNA: {}", e))?;
+    if dna.hash() == hash {
+        Ok(dna)
+    } else {
+        Err(format!("expected DNA {} but got {}", hash, dna.hash()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        env, sync::{Arc, Mutex},
+    };
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("holochain_dna_resolver_{}", name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn resolves_from_sources() {
+        let dna = Dna::new();
+        let hash = dna.hash();
+        let json = dna.to_json().unwrap();
+        let fetched = Arc::new(Mutex::new(Vec::new()));
+
+        let cache_dir = test_dir("sources");
+        let mut resolver = DnaResolver::new(Some(&cache_dir));
+        let log = fetched.clone();
+        resolver.register(
+            "https",
+            Box::new(move |location| {
+                log.lock().unwrap().push(location.to_string());
+                if location.starts_with("https://store.example.org/") {
+                    Ok(json.clone())
+                } else {
+                    Err(format!("{} not found", location))
+                }
+            }),
+        );
+        resolver.add_source("https://mirror.example.org/");
+        resolver.add_source("https://store.example.org/");
+
+        assert_eq!(Ok(dna.clone()), resolver.resolve(&hash));
+        assert_eq!(2, fetched.lock().unwrap().len());
+        assert_eq!(
+            format!("https://store.example.org/{}", hash),
+            fetched.lock().unwrap()[1]
+        );
+
+        // cached from now on
+        assert_eq!(Ok(dna), resolver.resolve(&hash));
+        assert_eq!(2, fetched.lock().unwrap().len());
+        assert!(cache_dir.join(format!("{}.json", hash)).exists());
+
+        assert!(DnaResolver::default().resolve(&hash).is_err());
+    }
+
+    #[test]
+    fn fails_on_mismatched_hash() {
+        let dna = Dna::new();
+        let dir = test_dir("mismatch");
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("dna.json");
+        fs::write(&path, dna.to_json().unwrap()).unwrap();
+        let location = path.to_string_lossy().to_string();
+
+        let resolver = DnaResolver::default();
+        assert_eq!(Ok(dna.clone()), resolver.fetch(&location, &dna.hash()));
+        assert!(resolver.fetch(&location, &Dna::new().hash()).is_err());
+        assert!(resolver.fetch("ftp://example.org/dna", &dna.hash()).is_err());
+        assert!(verify("{", &dna.hash()).is_err());
+    }
+}
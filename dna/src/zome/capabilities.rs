@@ -40,9 +40,25 @@ pub enum ReservedFunctionNames {
     /// genesis() -> bool
     /// Must be in LifeCycle Capability
     Genesis,
+    /// init() -> String
+    /// Must be in LifeCycle Capability
+    /// Called once after genesis on the first run, may commit bootstrap entries
+    /// "" == success, otherwise the error that fails initialization
+    Init,
+    /// post_commit(header_addresses : Vec<String>)
+    /// Must be in LifeCycle Capability
+    /// Called in the background after a zome call committed entries, with the addresses of their
+    /// headers, e.g. to send notifications or add links to them
+    PostCommit,
     /// receive(from : String, message : String) -> String
     /// Must be in Communication Capability
     Receive,
+    /// validate_agent(address : String, membrane_proof : Option<String>) -> String
+    /// Must be in LifeCycle Capability
+    /// Called before genesis and by the nodes the agent's AgentId entry is published to, to
+    /// gate who may join the network
+    /// "" == the agent may join, otherwise why it may not
+    ValidateAgent,
 }
 
 impl FromStr for ReservedFunctionNames {
@@ -50,7 +66,10 @@ impl FromStr for ReservedFunctionNames {
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
             "genesis" => Ok(ReservedFunctionNames::Genesis),
+            "init" => Ok(ReservedFunctionNames::Init),
+            "post_commit" => Ok(ReservedFunctionNames::PostCommit),
             "receive" => Ok(ReservedFunctionNames::Receive),
+            "validate_agent" => Ok(ReservedFunctionNames::ValidateAgent),
             _ => Err("Cannot convert string to ReservedFunctionNames"),
         }
     }
@@ -60,7 +79,10 @@ impl ReservedFunctionNames {
     pub fn as_str(&self) -> &'static str {
         match *self {
             ReservedFunctionNames::Genesis => "genesis",
+            ReservedFunctionNames::Init => "init",
+            ReservedFunctionNames::PostCommit => "post_commit",
             ReservedFunctionNames::Receive => "receive",
+            ReservedFunctionNames::ValidateAgent => "validate_agent",
         }
     }
 }
@@ -143,6 +165,9 @@ pub struct FnDeclaration {
     #[serde(default)]
     pub name: String,
     pub signature: FnSignature,
+    /// True if the function only reads, so calls to it can run alongside other calls.
+    #[serde(default)]
+    pub read_only: bool,
 }
 
 impl Default for FnDeclaration {
@@ -154,6 +179,7 @@ impl Default for FnDeclaration {
                 inputs: Vec::new(),
                 outputs: Vec::new(),
             },
+            read_only: false,
         }
     }
 }
@@ -202,6 +228,13 @@ impl Capability {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// True if this capability declares function read_only.
+    pub fn is_read_only(&self, function: &str) -> bool {
+        self.fn_declarations
+            .iter()
+            .any(|declaration| declaration.name == function && declaration.read_only)
+    }
 }
 
 #[cfg(test)]
@@ -234,7 +267,8 @@ mod tests {
                                     "type": "string"
                                 }
                             ]
-                        }
+                        },
+                        "read_only": true
                     }
                 ],
                 "code": {
@@ -251,9 +285,14 @@ mod tests {
         let output = FnParameter::new("hash", "string");
         fn_dec.signature.inputs.push(input);
         fn_dec.signature.outputs.push(output);
+        fn_dec.read_only = true;
         cap.fn_declarations.push(fn_dec);
         cap.code.code = vec![0, 1, 2, 3];
 
         assert_eq!(fixture, cap);
+        assert!(cap.is_read_only("test"));
+        assert!(!cap.is_read_only("other"));
+        cap.fn_declarations[0].read_only = false;
+        assert!(!cap.is_read_only("test"));
     }
 }
@@ -54,6 +54,74 @@ impl LinksTo {
     }
 }
 
+/// An individual object in a "linked_from" array.
+/// Declares that entries of base_type may link to this entry type with tag, without the base
+/// type having to declare it in its "links_to".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LinkedFrom {
+    /// The base_type of this linked_from entry
+    #[serde(default)]
+    pub base_type: String,
+
+    /// The tag of this linked_from entry
+    #[serde(default)]
+    pub tag: String,
+}
+
+impl Default for LinkedFrom {
+    /// Provide defaults for a "linked_from" object.
+    fn default() -> Self {
+        LinkedFrom {
+            base_type: String::from(""),
+            tag: String::from(""),
+        }
+    }
+}
+
+impl LinkedFrom {
+    /// Allow sane defaults for `LinkedFrom::new()`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+/// The "rate_bucket" of an entry type.
+/// Each agent may commit at most capacity weight of entries of the type within any
+/// interval_secs, nodes holding the entries refuse the ones going over it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct RateBucket {
+    /// The weight of entries an agent may commit within interval_secs.
+    pub capacity: u64,
+
+    /// The length of the window capacity applies to, in seconds.
+    pub interval_secs: u64,
+}
+
+/// The "retention" of an entry type.
+/// How long nodes holding entries of the type for the network keep them, each holder prunes
+/// the ones it holds past it.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Retention {
+    /// Held for as long as they are in the holder's arc.
+    Forever,
+    /// Held for the given number of days after the holder first held them.
+    Days(u64),
+    /// Held while links from them are, i.e. until every link made from them is removed.
+    WhileLinked,
+}
+
+impl Default for Retention {
+    /// Default zome entry_type retention is "forever"
+    fn default() -> Self {
+        Retention::Forever
+    }
+}
+
+fn default_weight() -> u64 {
+    1
+}
+
 /// Represents an individual object in the "zome" "entry_types" array.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct EntryType {
@@ -73,9 +141,34 @@ pub struct EntryType {
     #[serde(default)]
     pub validation: DnaWasm,
 
-    /// An array of entry_types associated with this zome.
+    /// An array of links entries of this type may make to other entries.
     #[serde(default)]
     pub links_to: Vec<LinksTo>,
+
+    /// An array of links other entries may make to entries of this type.
+    #[serde(default)]
+    pub linked_from: Vec<LinkedFrom>,
+
+    /// How much each entry of this type takes from the rate_bucket.
+    #[serde(default = "default_weight")]
+    pub weight: u64,
+
+    /// The bucket limiting how many entries of this type each agent commits, none if unlimited.
+    #[serde(default)]
+    pub rate_bucket: Option<RateBucket>,
+
+    /// Whether the content of entries of this type is indexed for searching.
+    #[serde(default)]
+    pub searchable: bool,
+
+    /// The paths of the fields of the JSON content of entries of this type indexed for lookups
+    /// by value, e.g. "handle" or "profile.handle".
+    #[serde(default)]
+    pub indexes: Vec<String>,
+
+    /// How long nodes holding entries of this type keep them.
+    #[serde(default)]
+    pub retention: Retention,
 }
 
 impl Default for EntryType {
@@ -87,6 +180,12 @@ impl Default for EntryType {
             sharing: Sharing::Public,
             validation: DnaWasm::new(),
             links_to: Vec::new(),
+            linked_from: Vec::new(),
+            weight: default_weight(),
+            rate_bucket: None,
+            searchable: false,
+            indexes: Vec::new(),
+            retention: Retention::Forever,
         }
     }
 }
@@ -96,6 +195,20 @@ impl EntryType {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// True if this entry type declares links to target_type with tag.
+    pub fn links_to(&self, target_type: &str, tag: &str) -> bool {
+        self.links_to
+            .iter()
+            .any(|link| link.target_type == target_type && link.tag == tag)
+    }
+
+    /// True if this entry type declares links from base_type with tag.
+    pub fn linked_from(&self, base_type: &str, tag: &str) -> bool {
+        self.linked_from
+            .iter()
+            .any(|link| link.base_type == base_type && link.tag == tag)
+    }
 }
 
 #[cfg(test)]
@@ -121,7 +234,21 @@ mod tests {
                             "code": "AAECAw=="
                         }
                     }
-                ]
+                ],
+                "linked_from": [
+                    {
+                        "base_type": "other",
+                        "tag": "test"
+                    }
+                ],
+                "weight": 5,
+                "rate_bucket": {
+                    "capacity": 100,
+                    "interval_secs": 3600
+                },
+                "searchable": true,
+                "indexes": ["handle", "profile.age"],
+                "retention": {"days": 30}
             }"#,
         ).unwrap();
 
@@ -138,6 +265,57 @@ mod tests {
 
         entry.links_to.push(link);
 
+        let mut linked_from = LinkedFrom::new();
+        linked_from.base_type = String::from("other");
+        linked_from.tag = String::from("test");
+
+        entry.linked_from.push(linked_from);
+
+        entry.weight = 5;
+        entry.rate_bucket = Some(RateBucket {
+            capacity: 100,
+            interval_secs: 3600,
+        });
+        entry.searchable = true;
+        entry.indexes = vec!["handle".to_string(), "profile.age".to_string()];
+        entry.retention = Retention::Days(30);
+
         assert_eq!(fixture, entry);
     }
+
+    #[test]
+    fn unlimited_by_default() {
+        let entry: EntryType = serde_json::from_str(r#"{"name": "test"}"#).unwrap();
+        assert_eq!(1, entry.weight);
+        assert_eq!(None, entry.rate_bucket);
+        assert_eq!(EntryType::new().weight, entry.weight);
+        assert!(!entry.searchable);
+        assert!(entry.indexes.is_empty());
+        assert_eq!(Retention::Forever, entry.retention);
+
+        let anchor: EntryType =
+            serde_json::from_str(r#"{"name": "anchor", "retention": "while_linked"}"#).unwrap();
+        assert_eq!(Retention::WhileLinked, anchor.retention);
+    }
+
+    #[test]
+    fn link_declarations() {
+        let mut entry = EntryType::new();
+        entry.links_to.push(LinksTo {
+            target_type: String::from("post"),
+            tag: String::from("authored"),
+            ..LinksTo::new()
+        });
+        entry.linked_from.push(LinkedFrom {
+            base_type: String::from("post"),
+            tag: String::from("author"),
+        });
+
+        assert!(entry.links_to("post", "authored"));
+        assert!(!entry.links_to("post", "author"));
+        assert!(!entry.links_to("comment", "authored"));
+
+        assert!(entry.linked_from("post", "author"));
+        assert!(!entry.linked_from("post", "authored"));
+    }
 }
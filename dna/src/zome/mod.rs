@@ -2,6 +2,7 @@
 
 pub mod capabilities;
 pub mod entry_types;
+pub mod traits;
 
 /// Enum for "zome" "config" "error_handling" property.
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -65,6 +66,10 @@ pub struct Zome {
     /// An array of capabilities associated with this zome.
     #[serde(default)]
     pub capabilities: Vec<capabilities::Capability>,
+
+    /// An array of traits this zome implements.
+    #[serde(default)]
+    pub traits: Vec<traits::ZomeTrait>,
 }
 
 impl Default for Zome {
@@ -76,6 +81,7 @@ impl Default for Zome {
             config: Config::new(),
             entry_types: Vec::new(),
             capabilities: Vec::new(),
+            traits: Vec::new(),
         }
     }
 }
@@ -85,6 +91,11 @@ impl Zome {
     pub fn new() -> Self {
         Default::default()
     }
+
+    /// True if this zome declares it implements the named trait.
+    pub fn implements(&self, trait_name: &str) -> bool {
+        self.traits.iter().any(|t| t.name == trait_name)
+    }
 }
 
 #[cfg(test)]
@@ -112,5 +123,6 @@ mod tests {
         zome.config.error_handling = ErrorHandling::ThrowErrors;
 
         assert_eq!(fixture, zome);
+        assert!(!zome.implements("chat"));
     }
 }
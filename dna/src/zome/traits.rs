@@ -0,0 +1,79 @@
+//! holochain_dna::zome::traits is a set of structs for working with holochain dna.
+
+use zome::capabilities::FnDeclaration;
+
+/// Represents an individual object in the "zome" "traits" array.
+/// A trait is a named interface, a set of functions any zome implementing it exposes, so apps
+/// can find and call each other by what they do rather than by name.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct ZomeTrait {
+    /// The name of this trait.
+    #[serde(default)]
+    pub name: String,
+
+    /// A description of this trait.
+    #[serde(default)]
+    pub description: String,
+
+    /// The functions a zome implementing this trait exposes.
+    #[serde(default)]
+    pub functions: Vec<FnDeclaration>,
+}
+
+impl Default for ZomeTrait {
+    /// Provide defaults for a "traits" object.
+    fn default() -> Self {
+        ZomeTrait {
+            name: String::from(""),
+            description: String::from(""),
+            functions: Vec::new(),
+        }
+    }
+}
+
+impl ZomeTrait {
+    /// Allow sane defaults for `ZomeTrait::new()`.
+    pub fn new() -> Self {
+        Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json;
+    use zome::capabilities::FnParameter;
+
+    #[test]
+    fn build_and_compare() {
+        let fixture: ZomeTrait = serde_json::from_str(
+            r#"{
+                "name": "chat",
+                "description": "test",
+                "functions": [
+                    {
+                        "name": "post_message",
+                        "signature": {
+                            "inputs": [{"name": "text", "type": "string"}],
+                            "outputs": []
+                        }
+                    }
+                ]
+            }"#,
+        ).unwrap();
+
+        let mut zome_trait = ZomeTrait::new();
+        zome_trait.name = String::from("chat");
+        zome_trait.description = String::from("test");
+
+        let mut function = FnDeclaration::new();
+        function.name = String::from("post_message");
+        function.signature.inputs.push(FnParameter {
+            name: String::from("text"),
+            parameter_type: String::from("string"),
+        });
+        zome_trait.functions.push(function);
+
+        assert_eq!(fixture, zome_trait);
+    }
+}
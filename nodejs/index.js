@@ -0,0 +1,22 @@
+// Loads the native module built by `make nodejs` (or `npm run build`), see src/lib.rs.
+//
+//   const { Conductor, withConductor } = require('@holochain/holochain-nodejs')
+//   const conductor = new Conductor({ instances: [{ id: 'app', dna: 'app.json', agent: 'alice' }] })
+//   conductor.onSignal(({ instance_id, signal }) => console.log(instance_id, signal))
+//   const result = conductor.call('app', 'blog', 'main', 'create_post', { title: 'hello' })
+//   conductor.shutdown()
+
+const { Conductor } = require('./index.node')
+
+// runs f with a conductor of the configuration and shuts it down once f is done, even if it
+// throws, e.g. for a scenario of a test suite
+async function withConductor (config, f) {
+  const conductor = new Conductor(config)
+  try {
+    return await f(conductor)
+  } finally {
+    conductor.shutdown()
+  }
+}
+
+module.exports = { Conductor, withConductor }
@@ -0,0 +1,222 @@
+//! Node.js module running holochain instances natively, for JavaScript test suites and Electron
+//! apps, build it with `make nodejs`, see index.js for how it is loaded
+//!
+//! a Conductor is built from a container configuration given as an object, see
+//! holochain_core_api::config, and starts its instances right away, it takes zome calls and hands
+//! the signals emitted in its instances to the callbacks given to onSignal()
+//!
+//! zome calls run on the JavaScript thread, like the instances, so they block it until they
+//! return, signals come through the event loop
+
+use holochain_core::error::HolochainError;
+use holochain_core_api::{
+    config::Configuration,
+    container::{dna_from_resolver, Container, InstanceSignal},
+    storage::StorageRegistry,
+};
+use napi::{
+    threadsafe_function::{ErrorStrategy, ThreadsafeFunction, ThreadsafeFunctionCallMode},
+    Env, JsFunction,
+};
+use napi_derive::napi;
+use serde_json::Value;
+use std::{sync::mpsc::Receiver, thread, time::Duration};
+
+/// how long calls in flight get to finish when a conductor is shut down
+const SHUTDOWN_TIMEOUT_MS: u64 = 5000;
+
+fn to_napi_error(error: HolochainError) -> napi::Error {
+    napi::Error::from_reason(format!("{:?}", error))
+}
+
+/// build and start the instances of a container configuration, the DNA of each instance is
+/// loaded from the JSON file at its configured path
+pub fn start_container(config: &Value) -> Result<Container, HolochainError> {
+    let config = Configuration::from_json(&config.to_string())?;
+    let resolver = config.dna_resolver();
+    let mut container =
+        Container::from_config(&config, &StorageRegistry::default(), dna_from_resolver(&resolver))?;
+    for id in container.instance_ids() {
+        if let Some(instance) = container.instance_mut(&id) {
+            instance.start()?;
+        }
+    }
+    Ok(container)
+}
+
+/// call a zome function of the container's instance with the given id, the parameters are
+/// passed as JSON and the result is parsed as JSON, it is taken as a string if it isn't any
+pub fn call(
+    container: &mut Container,
+    instance_id: &str,
+    zome: &str,
+    capability: &str,
+    function: &str,
+    parameters: &Value,
+) -> Result<Value, HolochainError> {
+    let instance = container
+        .instance_mut(instance_id)
+        .ok_or_else(|| HolochainError::new(&format!("no instance '{}'", instance_id)))?;
+    let result = instance.call(zome, capability, function, &parameters.to_string())?;
+    Ok(serde_json::from_str(&result).unwrap_or(Value::String(result)))
+}
+
+/// hand every signal received to f as JSON with the instance_id and signal, on a thread of its
+/// own, until the instances are shut down
+pub fn forward_signals<F>(signals: Receiver<InstanceSignal>, f: F) -> thread::JoinHandle<()>
+where
+    F: Fn(Value) + Send + 'static,
+{
+    thread::spawn(move || {
+        for signal in signals {
+            if let Ok(signal) = serde_json::to_value(&signal) {
+                f(signal);
+            }
+        }
+    })
+}
+
+/// the instances of a container configuration, running until shutdown()
+#[napi]
+pub struct Conductor {
+    container: Option<Container>,
+}
+
+#[napi]
+impl Conductor {
+    /// build and start the instances of the configuration, throws if it is inconsistent or an
+    /// instance fails to initialize
+    #[napi(constructor)]
+    pub fn new(config: Value) -> napi::Result<Conductor> {
+        Ok(Conductor {
+            container: Some(start_container(&config).map_err(to_napi_error)?),
+        })
+    }
+
+    fn container(&mut self) -> napi::Result<&mut Container> {
+        self.container
+            .as_mut()
+            .ok_or_else(|| napi::Error::from_reason("the conductor is shut down"))
+    }
+
+    /// the ids of the instances, sorted
+    #[napi]
+    pub fn instance_ids(&mut self) -> napi::Result<Vec<String>> {
+        Ok(self.container()?.instance_ids())
+    }
+
+    /// call a zome function of the instance with the given id, throws if there is no such
+    /// instance or the call fails
+    #[napi]
+    pub fn call(
+        &mut self,
+        instance_id: String,
+        zome: String,
+        capability: String,
+        function: String,
+        parameters: Value,
+    ) -> napi::Result<Value> {
+        call(
+            self.container()?,
+            &instance_id,
+            &zome,
+            &capability,
+            &function,
+            &parameters,
+        ).map_err(to_napi_error)
+    }
+
+    /// have callback called with the signals emitted in the instances from now on, as objects
+    /// with the instance_id and signal, it doesn't keep Node.js from exiting
+    #[napi]
+    pub fn on_signal(&mut self, env: Env, callback: JsFunction) -> napi::Result<()> {
+        let signals = self.container()?.signals();
+        let mut callback: ThreadsafeFunction<Value, ErrorStrategy::Fatal> =
+            callback.create_threadsafe_function(0, |cx| Ok(vec![cx.value]))?;
+        callback.unref(&env)?;
+        forward_signals(signals, move |signal| {
+            callback.call(signal, ThreadsafeFunctionCallMode::NonBlocking);
+        });
+        Ok(())
+    }
+
+    /// shut every instance down, waiting a while for calls in flight, the conductor can't be
+    /// used afterwards
+    #[napi]
+    pub fn shutdown(&mut self) -> napi::Result<()> {
+        match self.container.take() {
+            Some(mut container) => container
+                .shutdown(Duration::from_millis(SHUTDOWN_TIMEOUT_MS))
+                .map_err(to_napi_error),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use holochain_core::signal::Signal;
+    use holochain_dna::Dna;
+    use serde_json::json;
+    use std::{env, fs, process, sync::mpsc::channel};
+
+    fn test_config(name: &str) -> Value {
+        let dna_path = env::temp_dir().join(format!("{}_{}.json", name, process::id()));
+        fs::write(&dna_path, Dna::new().to_json().unwrap()).unwrap();
+        json!({"instances": [{"id": "app", "dna": dna_path, "agent": "bob"}]})
+    }
+
+    #[test]
+    fn can_start_and_call() {
+        let mut container = start_container(&test_config("holochain_nodejs_call")).unwrap();
+        assert_eq!(vec!["app".to_string()], container.instance_ids());
+        assert!(container.instance("app").unwrap().active());
+
+        let call = |container: &mut Container, instance_id| {
+            call(container, instance_id, "test_zome", "test_cap", "main", &json!({}))
+        };
+        // there is no zome in the DNA
+        assert!(call(&mut container, "app").is_err());
+        assert_eq!(
+            Err(HolochainError::new("no instance 'other'")),
+            call(&mut container, "other")
+        );
+        assert_eq!(Ok(()), container.shutdown(Duration::from_millis(100)));
+    }
+
+    #[test]
+    fn fails_on_bad_configs() {
+        assert!(start_container(&json!("not a config")).is_err());
+        assert!(
+            start_container(&json!({"instances": [{"id": "app", "dna": "/no/dna.json"}]}))
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn can_forward_signals() {
+        let mut container = start_container(&test_config("holochain_nodejs_signals")).unwrap();
+        let (sender, receiver) = channel();
+        let forwarder = forward_signals(container.signals(), move |signal| {
+            sender.send(signal).unwrap();
+        });
+
+        let signal = Signal {
+            zome: "blog".to_string(),
+            name: "new_post".to_string(),
+            payload: json!({"title": "hello"}),
+        };
+        let app = container.instance_mut("app").unwrap();
+        app.state().unwrap().nucleus().signal_bus().emit(&signal);
+        assert_eq!(
+            json!({"instance_id": "app", "signal": signal}),
+            receiver.recv_timeout(Duration::from_millis(1000)).unwrap()
+        );
+
+        // the forwarder stops with the instances
+        container.shutdown(Duration::from_millis(100)).unwrap();
+        drop(container);
+        forwarder.join().unwrap();
+    }
+}
@@ -0,0 +1,40 @@
+// Run with `npm test` once the module is built, see package.json.
+
+const assert = require('node:assert')
+const fs = require('node:fs')
+const os = require('node:os')
+const path = require('node:path')
+const { test } = require('node:test')
+const { Conductor, withConductor } = require('..')
+
+// a configuration with an instance of a DNA without zomes for each agent
+function testConfig (...agents) {
+  const dna = path.join(os.tmpdir(), `holochain_nodejs_test_${process.pid}.json`)
+  fs.writeFileSync(dna, JSON.stringify({ name: 'test', zomes: {} }))
+  return { instances: agents.map(agent => ({ id: agent, dna, agent })) }
+}
+
+test('runs the instances of the configuration', async () => {
+  await withConductor(testConfig('alice', 'bob'), conductor => {
+    assert.deepStrictEqual(conductor.instanceIds(), ['alice', 'bob'])
+  })
+})
+
+test('throws on bad configurations', () => {
+  assert.throws(() => new Conductor('not a config'))
+  assert.throws(() => new Conductor({ instances: [{ id: 'app', dna: '/no/dna.json' }] }))
+})
+
+test('throws on failed calls', async () => {
+  await withConductor(testConfig('alice'), conductor => {
+    assert.throws(() => conductor.call('alice', 'blog', 'main', 'create_post', {}))
+    assert.throws(() => conductor.call('carol', 'blog', 'main', 'create_post', {}), /no instance/)
+  })
+})
+
+test('can not be used after shutdown', () => {
+  const conductor = new Conductor(testConfig('alice'))
+  conductor.onSignal(() => {})
+  conductor.shutdown()
+  assert.throws(() => conductor.instanceIds(), /shut down/)
+})
@@ -0,0 +1,70 @@
+//! compares the encodings on payloads like the ones going over the network and interfaces: the
+//! bytes they take and how long a round trip through them takes
+//!
+//! cargo run --release -p holochain_serialization --example encodings
+
+#[macro_use]
+extern crate serde_json;
+extern crate holochain_serialization;
+
+use holochain_serialization::encoding::ENCODINGS;
+use serde_json::Value;
+use std::time::Instant;
+
+const ROUNDS: u32 = 10_000;
+
+/// a publish of an entry with its header and a few links, as a node sends it to holders
+fn publish() -> Value {
+    let hash = "QmYfyyHKk5hhRfMf8s5gGyaQmHCRmbnByZGSdQTpsmMRnj";
+    json!({
+        "trace": null,
+        "message": {"Publish": {
+            "from": "alice",
+            "address": hash,
+            "aspects": (0..8).map(|i| json!({
+                "kind": if i == 0 { "Entry" } else { "Link" },
+                "address": hash,
+                "header": {
+                    "entry_type": "post",
+                    "timestamp": 1_538_000_000 + i,
+                    "link": hash,
+                    "entry_hash": hash,
+                    "signature": "3xNqAfzBmWNrDW7sQXGhk2b6QvX4M7WzGbZ8Y1d5nE9Rc",
+                },
+                "content": {"title": "hello", "body": "x".repeat(64), "tags": ["a", "b"]},
+            })).collect::<Vec<Value>>(),
+        }},
+    })
+}
+
+/// a zome call as an interface client makes it
+fn zome_call() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": 42,
+        "method": "app/blog/main/create_post",
+        "params": {"title": "hello", "content": "world", "timestamp": 1_538_000_000},
+    })
+}
+
+fn main() {
+    for (name, payload) in [("publish", publish()), ("zome call", zome_call())] {
+        println!("{}", name);
+        for encoding in ENCODINGS {
+            let bytes = encoding.encode(&payload).unwrap();
+            let start = Instant::now();
+            for _ in 0..ROUNDS {
+                let bytes = encoding.encode(&payload).unwrap();
+                let decoded: Value = encoding.decode(&bytes).unwrap();
+                assert_eq!(payload, decoded);
+            }
+            let per_round = start.elapsed() / ROUNDS;
+            println!(
+                "  {:12} {:6} bytes {:8.1} us per round trip",
+                format!("{:?}", encoding),
+                bytes.len(),
+                per_round.as_secs_f64() * 1e6
+            );
+        }
+    }
+}
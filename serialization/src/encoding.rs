@@ -0,0 +1,82 @@
+//! how payloads are put on the wire, e.g. network envelopes and interface messages
+//! JSON is always understood, MessagePack is used between ends that both speak it, see
+//! Encoding::negotiate()
+
+use msgpack;
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json;
+use SerializationError;
+
+/// every encoding, all of them are spoken by this build
+pub const ENCODINGS: &[Encoding] = &[Encoding::Json, Encoding::MessagePack];
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Encoding {
+    #[default]
+    #[serde(rename = "json")]
+    Json,
+    #[serde(rename = "msgpack")]
+    MessagePack,
+}
+
+impl Encoding {
+    /// the encoding to use between an end preferring the encodings in the given order and one
+    /// speaking the accepted ones, JSON if they have none other in common
+    pub fn negotiate(preferred: &[Encoding], accepted: &[Encoding]) -> Encoding {
+        preferred
+            .iter()
+            .find(|encoding| **encoding == Encoding::Json || accepted.contains(encoding))
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    pub fn encode<T: Serialize>(self, value: &T) -> Result<Vec<u8>, SerializationError> {
+        match self {
+            Encoding::Json => serde_json::to_vec(value),
+            Encoding::MessagePack => msgpack::to_vec(value),
+        }
+    }
+
+    pub fn decode<T: DeserializeOwned>(self, bytes: &[u8]) -> Result<T, SerializationError> {
+        match self {
+            Encoding::Json => serde_json::from_slice(bytes),
+            Encoding::MessagePack => msgpack::from_slice(bytes),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v2::RemoteSignalInput;
+
+    #[test]
+    fn negotiate() {
+        use self::Encoding::{Json, MessagePack};
+        assert_eq!(MessagePack, Encoding::negotiate(&[MessagePack], &[MessagePack]));
+        assert_eq!(Json, Encoding::negotiate(&[MessagePack], &[]));
+        assert_eq!(Json, Encoding::negotiate(&[Json, MessagePack], &[MessagePack]));
+        assert_eq!(Json, Encoding::negotiate(&[], &[MessagePack]));
+    }
+
+    #[test]
+    /// MessagePack decodes to what JSON does and takes less room
+    fn encode() {
+        let input = RemoteSignalInput {
+            agents: vec!["alice".to_string(), "bob".to_string()],
+            payload: json!({"typing": true, "since": 1538000000, "chars": [1, 2, 3]}),
+        };
+        let json = Encoding::Json.encode(&input).unwrap();
+        let msgpack = Encoding::MessagePack.encode(&input).unwrap();
+        assert!(msgpack.len() < json.len());
+        assert_eq!(
+            Encoding::Json.decode::<RemoteSignalInput>(&json).unwrap(),
+            Encoding::MessagePack.decode::<RemoteSignalInput>(&msgpack).unwrap()
+        );
+        assert!(Encoding::MessagePack.decode::<RemoteSignalInput>(&json).is_err());
+        assert_eq!(
+            Encoding::MessagePack,
+            serde_json::from_str(r#""msgpack""#).unwrap()
+        );
+    }
+}
@@ -0,0 +1,184 @@
+//! holochain_serialization is what the host and the zomes it runs, through the HDK, agree on
+//! about the JSON they pass each other.
+//!
+//! `JsonString` is JSON text on its way across, with `TryFrom` conversions to and from the structs
+//! it carries, see `json_string_conversions!`. The modules named after host API versions hold the
+//! wire structs of the host functions as of that version, see holochain_dna::HOST_API_VERSION.
+//!
+//! Payloads going over the network or to clients can also be MessagePack, when both ends speak
+//! it, see `Encoding`. Hashes are always taken of the JSON.
+//!
+//! # Examples
+//!
+//! ```
+//! use holochain_serialization::{v2::CommitInput, JsonString};
+//! use std::convert::TryFrom;
+//!
+//! let input = CommitInput {
+//!     entry_type_name: "post".to_string(),
+//!     entry_content: "hello".to_string(),
+//! };
+//!
+//! let json = JsonString::try_from(input.clone()).unwrap();
+//! assert_eq!(r#"{"entry_type_name":"post","entry_content":"hello"}"#, json.as_str());
+//! assert_eq!(Ok(input), CommitInput::try_from(json).map_err(|e| e.to_string()));
+//! ```
+
+#[macro_use]
+extern crate serde_derive;
+extern crate serde;
+#[cfg_attr(test, macro_use)]
+extern crate serde_json;
+
+use serde::{de::DeserializeOwned, Serialize};
+use std::fmt;
+
+/// why JSON didn't convert
+pub type SerializationError = serde_json::Error;
+
+/// JSON text passed between the host and a zome
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JsonString(String);
+
+impl JsonString {
+    /// JSON null, e.g. for a property that isn't set
+    pub fn null() -> JsonString {
+        JsonString::from("null")
+    }
+
+    /// the JSON of any value that serializes, for types without conversions of their own
+    pub fn try_from_serialize<T: Serialize>(value: &T) -> Result<JsonString, SerializationError> {
+        serde_json::to_string(value).map(JsonString)
+    }
+
+    /// the value the JSON is of, for types without conversions of their own
+    pub fn try_deserialize<T: DeserializeOwned>(&self) -> Result<T, SerializationError> {
+        serde_json::from_str(&self.0)
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for JsonString {
+    fn from(json: String) -> JsonString {
+        JsonString(json)
+    }
+}
+
+impl From<&str> for JsonString {
+    fn from(json: &str) -> JsonString {
+        JsonString(json.to_string())
+    }
+}
+
+impl From<serde_json::Value> for JsonString {
+    fn from(value: serde_json::Value) -> JsonString {
+        JsonString(value.to_string())
+    }
+}
+
+impl From<JsonString> for String {
+    fn from(json: JsonString) -> String {
+        json.0
+    }
+}
+
+impl fmt::Display for JsonString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// `TryFrom` conversions between a type that serializes and `JsonString`, both ways.
+///
+/// ```
+/// #[macro_use]
+/// extern crate holochain_serialization;
+/// #[macro_use]
+/// extern crate serde_derive;
+///
+/// use holochain_serialization::JsonString;
+/// use std::convert::TryFrom;
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Post {
+///     title: String,
+/// }
+///
+/// json_string_conversions!(Post);
+///
+/// # fn main() {
+/// let json = JsonString::from(r#"{"title":"hello"}"#);
+/// assert_eq!("hello", Post::try_from(json).unwrap().title);
+/// # }
+/// ```
+#[macro_export]
+macro_rules! json_string_conversions {
+    ($type:ty) => {
+        impl ::std::convert::TryFrom<$crate::JsonString> for $type {
+            type Error = $crate::SerializationError;
+            fn try_from(json: $crate::JsonString) -> Result<Self, Self::Error> {
+                json.try_deserialize()
+            }
+        }
+
+        impl ::std::convert::TryFrom<$type> for $crate::JsonString {
+            type Error = $crate::SerializationError;
+            fn try_from(value: $type) -> Result<Self, Self::Error> {
+                $crate::JsonString::try_from_serialize(&value)
+            }
+        }
+    };
+}
+
+pub mod encoding;
+pub mod msgpack;
+// after the macro, which the wire structs use
+pub mod v2;
+
+pub use encoding::Encoding;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Post {
+        title: String,
+    }
+
+    json_string_conversions!(Post);
+
+    #[test]
+    fn converts_both_ways() {
+        let post = Post {
+            title: "hello".to_string(),
+        };
+        let json = JsonString::try_from(post).unwrap();
+        assert_eq!(r#"{"title":"hello"}"#, json.to_string());
+        assert_eq!(
+            Post {
+                title: "hello".to_string(),
+            },
+            Post::try_from(json).unwrap()
+        );
+        assert!(Post::try_from(JsonString::from("{")).is_err());
+        assert!(Post::try_from(JsonString::null()).is_err());
+    }
+
+    #[test]
+    fn converts_plain_values() {
+        let json = JsonString::try_from_serialize(&vec!["alice", "bob"]).unwrap();
+        assert_eq!(r#"["alice","bob"]"#, json.as_str());
+        assert_eq!(
+            Ok(vec!["alice".to_string(), "bob".to_string()]),
+            json.try_deserialize::<Vec<String>>()
+                .map_err(|e| e.to_string())
+        );
+        assert_eq!("null", JsonString::from(serde_json::Value::Null).as_str());
+        assert_eq!("null".to_string(), String::from(JsonString::null()));
+    }
+}
@@ -0,0 +1,345 @@
+//! MessagePack, the compact binary alternative to JSON text on the network and interfaces
+//! values go through serde_json::Value, so anything that serializes to JSON encodes the same way
+//! here and decodes back to what JSON would have given, map keys being strings
+//! integers take the smallest format holding them, other numbers are 64 bit floats
+//! MessagePack is never hashed, addresses are always of the canonical JSON
+
+use serde::{de::DeserializeOwned, de::Error, Serialize};
+use serde_json::{self, Map, Number, Value};
+use SerializationError;
+
+/// how deep arrays and maps may nest when decoding, as serde_json allows, so untrusted bytes
+/// can't run the decoder out of stack
+pub const MSGPACK_MAX_DEPTH: usize = 128;
+
+/// the MessagePack bytes of any value that serializes
+pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, SerializationError> {
+    let value = serde_json::to_value(value)?;
+    let mut bytes = Vec::new();
+    write_value(&mut bytes, &value);
+    Ok(bytes)
+}
+
+/// the value MessagePack bytes are of, all the bytes have to be of that one value
+pub fn from_slice<T: DeserializeOwned>(bytes: &[u8]) -> Result<T, SerializationError> {
+    let mut reader = Reader {
+        bytes,
+        position: 0,
+        depth: 0,
+    };
+    let value = reader.value()?;
+    if reader.position != bytes.len() {
+        return Err(SerializationError::custom(format!(
+            "{} bytes left after the MessagePack value",
+            bytes.len() - reader.position
+        )));
+    }
+    serde_json::from_value(value)
+}
+
+fn write_value(bytes: &mut Vec<u8>, value: &Value) {
+    match *value {
+        Value::Null => bytes.push(0xc0),
+        Value::Bool(false) => bytes.push(0xc2),
+        Value::Bool(true) => bytes.push(0xc3),
+        Value::Number(ref number) => write_number(bytes, number),
+        Value::String(ref string) => write_str(bytes, string),
+        Value::Array(ref values) => {
+            write_header(bytes, values.len(), 0x90, 0x0f, 0xdc);
+            for value in values {
+                write_value(bytes, value);
+            }
+        }
+        Value::Object(ref map) => {
+            write_header(bytes, map.len(), 0x80, 0x0f, 0xde);
+            for (key, value) in map {
+                write_str(bytes, key);
+                write_value(bytes, value);
+            }
+        }
+    }
+}
+
+fn write_number(bytes: &mut Vec<u8>, number: &Number) {
+    if let Some(n) = number.as_u64() {
+        if n <= 0x7f {
+            bytes.push(n as u8);
+        } else if n <= u64::from(u8::MAX) {
+            bytes.extend_from_slice(&[0xcc, n as u8]);
+        } else if n <= u64::from(u16::MAX) {
+            bytes.push(0xcd);
+            bytes.extend_from_slice(&(n as u16).to_be_bytes());
+        } else if n <= u64::from(u32::MAX) {
+            bytes.push(0xce);
+            bytes.extend_from_slice(&(n as u32).to_be_bytes());
+        } else {
+            bytes.push(0xcf);
+            bytes.extend_from_slice(&n.to_be_bytes());
+        }
+    } else if let Some(n) = number.as_i64() {
+        // negative, as_u64 takes the others
+        if n >= -32 {
+            bytes.push(n as u8);
+        } else if n >= i64::from(i8::MIN) {
+            bytes.extend_from_slice(&[0xd0, n as u8]);
+        } else if n >= i64::from(i16::MIN) {
+            bytes.push(0xd1);
+            bytes.extend_from_slice(&(n as i16).to_be_bytes());
+        } else if n >= i64::from(i32::MIN) {
+            bytes.push(0xd2);
+            bytes.extend_from_slice(&(n as i32).to_be_bytes());
+        } else {
+            bytes.push(0xd3);
+            bytes.extend_from_slice(&n.to_be_bytes());
+        }
+    } else {
+        bytes.push(0xcb);
+        bytes.extend_from_slice(&number.as_f64().unwrap_or_default().to_be_bytes());
+    }
+}
+
+fn write_str(bytes: &mut Vec<u8>, string: &str) {
+    if string.len() < 32 {
+        bytes.push(0xa0 | string.len() as u8);
+    } else if string.len() <= usize::from(u8::MAX) {
+        bytes.extend_from_slice(&[0xd9, string.len() as u8]);
+    } else if string.len() <= usize::from(u16::MAX) {
+        bytes.push(0xda);
+        bytes.extend_from_slice(&(string.len() as u16).to_be_bytes());
+    } else {
+        bytes.push(0xdb);
+        bytes.extend_from_slice(&(string.len() as u32).to_be_bytes());
+    }
+    bytes.extend_from_slice(string.as_bytes());
+}
+
+/// the length of an array or map, in the fixed format up to fixed_max, then in 16 bits after
+/// marker, then in 32 bits after the marker following it
+fn write_header(bytes: &mut Vec<u8>, len: usize, fixed: u8, fixed_max: usize, marker: u8) {
+    if len <= fixed_max {
+        bytes.push(fixed | len as u8);
+    } else if len <= usize::from(u16::MAX) {
+        bytes.push(marker);
+        bytes.extend_from_slice(&(len as u16).to_be_bytes());
+    } else {
+        bytes.push(marker + 1);
+        bytes.extend_from_slice(&(len as u32).to_be_bytes());
+    }
+}
+
+fn float(n: f64) -> Result<Value, SerializationError> {
+    Number::from_f64(n)
+        .map(Value::Number)
+        .ok_or_else(|| SerializationError::custom("MessagePack float is not a JSON number"))
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    /// arrays and maps the reader is inside of
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], SerializationError> {
+        if self.bytes.len() - self.position < len {
+            return Err(SerializationError::custom("MessagePack value ends early"));
+        }
+        let taken = &self.bytes[self.position..self.position + len];
+        self.position += len;
+        Ok(taken)
+    }
+
+    fn uint(&mut self, len: usize) -> Result<u64, SerializationError> {
+        Ok(self
+            .take(len)?
+            .iter()
+            .fold(0, |n, byte| (n << 8) | u64::from(*byte)))
+    }
+
+    /// the two's complement integer of len bytes
+    fn int(&mut self, len: usize) -> Result<i64, SerializationError> {
+        let shift = 64 - 8 * len as u32;
+        Ok(((self.uint(len)? << shift) as i64) >> shift)
+    }
+
+    fn string(&mut self, len: usize) -> Result<String, SerializationError> {
+        String::from_utf8(self.take(len)?.to_vec())
+            .map_err(|_| SerializationError::custom("MessagePack string is not UTF-8"))
+    }
+
+    /// go one array or map deeper, failing past MSGPACK_MAX_DEPTH
+    fn descend(&mut self) -> Result<(), SerializationError> {
+        if self.depth >= MSGPACK_MAX_DEPTH {
+            return Err(SerializationError::custom(format!(
+                "MessagePack nests deeper than {}",
+                MSGPACK_MAX_DEPTH
+            )));
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn array(&mut self, len: usize) -> Result<Value, SerializationError> {
+        self.descend()?;
+        let array = (0..len)
+            .map(|_| self.value())
+            .collect::<Result<Vec<Value>, _>>()
+            .map(Value::Array);
+        self.depth -= 1;
+        array
+    }
+
+    fn map(&mut self, len: usize) -> Result<Value, SerializationError> {
+        self.descend()?;
+        let map = self.entries(len);
+        self.depth -= 1;
+        map
+    }
+
+    fn entries(&mut self, len: usize) -> Result<Value, SerializationError> {
+        let mut map = Map::new();
+        for _ in 0..len {
+            let key = match self.value()? {
+                Value::String(key) => key,
+                _ => {
+                    return Err(SerializationError::custom(
+                        "MessagePack map keys have to be strings",
+                    ))
+                }
+            };
+            let value = self.value()?;
+            map.insert(key, value);
+        }
+        Ok(Value::Object(map))
+    }
+
+    fn value(&mut self) -> Result<Value, SerializationError> {
+        let marker = self.take(1)?[0];
+        match marker {
+            0x00..=0x7f => Ok(Value::from(marker)),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f)),
+            0x90..=0x9f => self.array(usize::from(marker & 0x0f)),
+            0xa0..=0xbf => self.string(usize::from(marker & 0x1f)).map(Value::String),
+            0xc0 => Ok(Value::Null),
+            0xc2 => Ok(Value::Bool(false)),
+            0xc3 => Ok(Value::Bool(true)),
+            0xca => {
+                let bits = self.uint(4)? as u32;
+                float(f64::from(f32::from_bits(bits)))
+            }
+            0xcb => {
+                let bits = self.uint(8)?;
+                float(f64::from_bits(bits))
+            }
+            0xcc => self.uint(1).map(Value::from),
+            0xcd => self.uint(2).map(Value::from),
+            0xce => self.uint(4).map(Value::from),
+            0xcf => self.uint(8).map(Value::from),
+            0xd0 => self.int(1).map(Value::from),
+            0xd1 => self.int(2).map(Value::from),
+            0xd2 => self.int(4).map(Value::from),
+            0xd3 => self.int(8).map(Value::from),
+            0xd9 => {
+                let len = self.uint(1)? as usize;
+                self.string(len).map(Value::String)
+            }
+            0xda => {
+                let len = self.uint(2)? as usize;
+                self.string(len).map(Value::String)
+            }
+            0xdb => {
+                let len = self.uint(4)? as usize;
+                self.string(len).map(Value::String)
+            }
+            0xdc => {
+                let len = self.uint(2)? as usize;
+                self.array(len)
+            }
+            0xdd => {
+                let len = self.uint(4)? as usize;
+                self.array(len)
+            }
+            0xde => {
+                let len = self.uint(2)? as usize;
+                self.map(len)
+            }
+            0xdf => {
+                let len = self.uint(4)? as usize;
+                self.map(len)
+            }
+            0xe0..=0xff => Ok(Value::from(i64::from(marker as i8))),
+            _ => Err(SerializationError::custom(format!(
+                "MessagePack format 0x{:x} is not supported",
+                marker
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use v2::CommitInput;
+
+    #[test]
+    fn formats() {
+        let bytes = |value: Value| to_vec(&value).unwrap();
+        assert_eq!(vec![0xc0], bytes(Value::Null));
+        assert_eq!(vec![0x7f], bytes(json!(127)));
+        assert_eq!(vec![0xcc, 0x80], bytes(json!(128)));
+        assert_eq!(vec![0xcd, 0x01, 0x00], bytes(json!(256)));
+        assert_eq!(vec![0xe0], bytes(json!(-32)));
+        assert_eq!(vec![0xd0, 0xdf], bytes(json!(-33)));
+        assert_eq!(vec![0xd1, 0xff, 0x00], bytes(json!(-256)));
+        assert_eq!(vec![0xa2, b'h', b'i'], bytes(json!("hi")));
+        assert_eq!(vec![0x92, 0xc3, 0xc2], bytes(json!([true, false])));
+        assert_eq!(vec![0x81, 0xa1, b'a', 0x01], bytes(json!({"a": 1})));
+        assert_eq!(0xd9, bytes(json!("x".repeat(32)))[0]);
+        assert_eq!(vec![0xda, 0x01, 0x00], bytes(json!("x".repeat(256)))[..3].to_vec());
+        assert_eq!(vec![0xdc, 0x00, 0x10], bytes(json!(vec![0; 16]))[..3].to_vec());
+    }
+
+    #[test]
+    fn roundtrip() {
+        let values = vec![
+            json!(null),
+            json!({"a": [1, -1, 300, -300, 70000, -70000, 5000000000u64, -5000000000i64]}),
+            json!({"max": u64::MAX, "min": i64::MIN, "float": 1.5, "text": "x".repeat(70000)}),
+            json!(vec![json!({}); 20]),
+        ];
+        for value in values {
+            assert_eq!(value, from_slice::<Value>(&to_vec(&value).unwrap()).unwrap());
+        }
+        let input = CommitInput {
+            entry_type_name: "post".to_string(),
+            entry_content: "hello".to_string(),
+        };
+        assert_eq!(input, from_slice(&to_vec(&input).unwrap()).unwrap());
+    }
+
+    #[test]
+    fn rejects_malformed() {
+        assert!(from_slice::<Value>(&[]).is_err());
+        assert!(from_slice::<Value>(&[0xa2, b'h']).is_err());
+        assert!(from_slice::<Value>(&[0x01, 0x02]).is_err());
+        assert!(from_slice::<Value>(&[0x81, 0x01, 0x01]).is_err());
+        assert!(from_slice::<Value>(&[0xc1]).is_err());
+        assert!(from_slice::<Value>(&[0xa1, 0xff]).is_err());
+    }
+
+    #[test]
+    fn rejects_deep_nesting() {
+        let nested = |depth: usize| {
+            let mut bytes = vec![0x91; depth];
+            bytes.push(0xc0);
+            bytes
+        };
+        assert!(from_slice::<Value>(&nested(MSGPACK_MAX_DEPTH)).is_ok());
+        assert!(from_slice::<Value>(&nested(MSGPACK_MAX_DEPTH + 1)).is_err());
+        let mut maps = [0x81, 0xa1, b'a'].repeat(MSGPACK_MAX_DEPTH + 1);
+        maps.push(0xc0);
+        assert!(from_slice::<Value>(&maps).is_err());
+        assert!(from_slice::<Value>(&vec![0x91; 1_000_000]).is_err());
+    }
+}
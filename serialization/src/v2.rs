@@ -0,0 +1,238 @@
+//! the wire structs of host API version 2, the one current when DNAs started declaring the version
+//! they target
+//! host functions taking or giving back a single string or an array, e.g. property or
+//! get_online_agents, have no struct of their own, nor do the ones passing structs of core, e.g.
+//! get_entry
+//! a version changing the shape of a struct gets a module of its own, version 1 only had commit,
+//! which takes CommitInput as it is here
+
+use serde_json;
+
+/// what commit is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitInput {
+    pub entry_type_name: String,
+    pub entry_content: String,
+}
+
+/// what commit writes back
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitOutput {
+    pub hash: String,
+}
+
+/// end of a link committed in a transaction, either the position of an entry staged in the same
+/// transaction or the address of an entry committed before
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum LinkEndInput {
+    Staged(usize),
+    Address(String),
+}
+
+/// link committed in a transaction
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TransactionLinkInput {
+    pub base: LinkEndInput,
+    pub target: LinkEndInput,
+    pub tag: String,
+}
+
+/// what commit_transaction is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitTransactionInput {
+    #[serde(default)]
+    pub entries: Vec<CommitInput>,
+    #[serde(default)]
+    pub links: Vec<TransactionLinkInput>,
+}
+
+/// what commit_transaction writes back when it committed, the hashes of the entries, then the
+/// links
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CommitTransactionOutput {
+    pub hashes: Vec<String>,
+}
+
+/// what anchor is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnchorInput {
+    pub path: String,
+}
+
+/// what anchor writes back, the address of the anchor at the path
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct AnchorOutput {
+    pub address: String,
+}
+
+/// what schedule is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleInput {
+    pub id: String,
+    pub capability: String,
+    pub function: String,
+    #[serde(default)]
+    pub parameters: String,
+    pub interval_secs: u64,
+    #[serde(default)]
+    pub jitter_secs: u64,
+}
+
+/// what schedule_once is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ScheduleOnceInput {
+    pub id: String,
+    pub capability: String,
+    pub function: String,
+    #[serde(default)]
+    pub parameters: String,
+    pub delay_secs: u64,
+}
+
+/// what cancel_schedule is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CancelScheduleInput {
+    pub id: String,
+}
+
+/// what emit_signal is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct EmitSignalInput {
+    pub name: String,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+/// what call_remote is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct CallRemoteInput {
+    pub agent: String,
+    pub zome: String,
+    pub capability: String,
+    pub function: String,
+    #[serde(default)]
+    pub cap_secret: Option<String>,
+    #[serde(default)]
+    pub parameters: String,
+}
+
+/// what kv_set is called with, a null value removes the key
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct KvSetInput {
+    pub key: String,
+    #[serde(default)]
+    pub value: serde_json::Value,
+}
+
+/// what remote_signal is called with
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RemoteSignalInput {
+    pub agents: Vec<String>,
+    #[serde(default)]
+    pub payload: serde_json::Value,
+}
+
+/// what search is called with, no types for every type indexed
+/// the addresses found come a page at a time, from offset on, at most limit of them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchInput {
+    pub query: String,
+    #[serde(default)]
+    pub types: Vec<String>,
+    #[serde(default)]
+    pub offset: usize,
+    /// core::index::SEARCH_DEFAULT_LIMIT if not set, core::index::SEARCH_MAX_LIMIT at most
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// what search writes back, the addresses of the entries found, best matches first
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct SearchOutput {
+    pub addresses: Vec<String>,
+    /// the offset of the next page, None if this is the last
+    #[serde(default)]
+    pub next: Option<usize>,
+}
+
+/// what query_index is called with, the query as in core::hash_table::field_index::IndexQuery,
+/// e.g. {"equals":"alice"} or {"range":{"from":18,"to":null}}
+/// the addresses found come a page at a time, from offset on, at most limit of them
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryIndexInput {
+    pub entry_type: String,
+    pub field: String,
+    pub query: serde_json::Value,
+    #[serde(default)]
+    pub offset: usize,
+    /// core::index::QUERY_INDEX_DEFAULT_LIMIT if not set, core::index::QUERY_INDEX_MAX_LIMIT at
+    /// most
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// what query_index writes back, the addresses of the entries found by the value of the field
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct QueryIndexOutput {
+    pub addresses: Vec<String>,
+    /// the offset of the next page, None if this is the last
+    #[serde(default)]
+    pub next: Option<usize>,
+}
+
+json_string_conversions!(CommitInput);
+json_string_conversions!(CommitOutput);
+json_string_conversions!(CommitTransactionInput);
+json_string_conversions!(CommitTransactionOutput);
+json_string_conversions!(AnchorInput);
+json_string_conversions!(AnchorOutput);
+json_string_conversions!(ScheduleInput);
+json_string_conversions!(ScheduleOnceInput);
+json_string_conversions!(CancelScheduleInput);
+json_string_conversions!(EmitSignalInput);
+json_string_conversions!(CallRemoteInput);
+json_string_conversions!(KvSetInput);
+json_string_conversions!(RemoteSignalInput);
+json_string_conversions!(SearchInput);
+json_string_conversions!(SearchOutput);
+json_string_conversions!(QueryIndexInput);
+json_string_conversions!(QueryIndexOutput);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+    use JsonString;
+
+    #[test]
+    fn defaults() {
+        let json = JsonString::from(
+            r#"{"entries":[{"entry_type_name":"post","entry_content":"hi"}],
+                "links":[{"base":0,"target":"Qm","tag":"comments"}]}"#,
+        );
+        let input = CommitTransactionInput::try_from(json).unwrap();
+        assert_eq!(1, input.entries.len());
+        assert_eq!(LinkEndInput::Staged(0), input.links[0].base);
+        assert_eq!(LinkEndInput::Address("Qm".to_string()), input.links[0].target);
+        assert_eq!(
+            CommitTransactionInput::default(),
+            CommitTransactionInput::try_from(JsonString::from("{}")).unwrap()
+        );
+
+        let json = JsonString::from(r#"{"key":"draft"}"#);
+        assert_eq!(
+            serde_json::Value::Null,
+            KvSetInput::try_from(json).unwrap().value
+        );
+
+        let json = JsonString::from(r#"{"query":"hello"}"#);
+        let input = SearchInput::try_from(json).unwrap();
+        assert!(input.types.is_empty());
+        assert_eq!((0, None), (input.offset, input.limit));
+
+        let json = JsonString::from(r#"{"entry_type":"profile","field":"age","query":null}"#);
+        let input = QueryIndexInput::try_from(json).unwrap();
+        assert_eq!((0, None), (input.offset, input.limit));
+    }
+}
@@ -2,20 +2,171 @@ extern crate holochain_agent;
 extern crate holochain_core;
 extern crate holochain_core_api;
 extern crate holochain_dna;
+extern crate libc;
 
 use holochain_agent::Agent;
-use holochain_core::{context::Context, logger::SimpleLogger, persister::SimplePersister};
-use holochain_core_api::*;
+use holochain_core::{
+    agent::{keystore::Keystore, mnemonic},
+    chain::{diff::diff, explorer::Explorer, verify::chain_from_json},
+    context::Context, fixtures::{self, TestVectors}, logger::SimpleLogger,
+    persister::SimplePersister,
+};
+use holochain_core_api::{
+    agents::AGENT_KEY_PATH, dump::StateDump, invites::{self, Invite}, *,
+};
 use holochain_dna::Dna;
 use std::{
-    env, sync::{Arc, Mutex},
+    env, fs, io::{self, BufRead, Write}, sync::{
+        atomic::{AtomicBool, Ordering}, Arc, Mutex,
+    },
+    thread, time::Duration,
 };
 
+/// how long calls in flight get to finish on shutdown
+const SHUTDOWN_TIMEOUT_MS: u64 = 5000;
+
+static TERMINATED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn terminate(_signal: libc::c_int) {
+    TERMINATED.store(true, Ordering::SeqCst);
+}
+
+/// shut down gracefully on SIGTERM and SIGINT instead of dying mid call
+#[cfg(unix)]
+fn handle_termination() {
+    let handler = terminate as extern "C" fn(libc::c_int) as libc::sighandler_t;
+    unsafe {
+        libc::signal(libc::SIGTERM, handler);
+        libc::signal(libc::SIGINT, handler);
+    }
+}
+
+#[cfg(not(unix))]
+fn handle_termination() {}
+
 fn usage() {
-    println!("Usage: holochain_test_bin <identity>");
+    println!("Usage: holochain_test_bin <identity> [--dump-state <file>]");
+    println!("       holochain_test_bin --import-dump <file>");
+    println!("       holochain_test_bin --explore <chain file>");
+    println!("       holochain_test_bin --diff <chain file> <chain file>");
+    println!("       holochain_test_bin --write-test-vectors <file>");
+    println!("       holochain_test_bin --check-test-vectors <file>");
+    println!("       holochain_test_bin --keygen --mnemonic");
+    println!("       holochain_test_bin --keygen <dna hash> <keystore file>");
+    println!("       holochain_test_bin --keygen --export-key <public key>");
+    println!("       holochain_test_bin --keygen --invite <dna hash> [<bootstrap agent>..]");
+    println!("       holochain_test_bin --keygen --import <code>");
     std::process::exit(1);
 }
 
+/// load a state dump taken with --dump-state and print what is worth looking at first
+fn import_dump(path: &str) {
+    let json = fs::read_to_string(path).expect("couldn't read the dump");
+    let dump = StateDump::from_json(&json).expect("couldn't load the dump");
+    for line in dump.summary() {
+        println!("{}", line);
+    }
+}
+
+/// walk a chain saved as JSON at a prompt, type help for the commands
+fn explore(path: &str) {
+    let json = fs::read_to_string(path).expect("couldn't read the chain");
+    let mut explorer = Explorer::from_json(&json).expect("couldn't load the chain");
+    let stdin = io::stdin();
+    print!("> ");
+    io::stdout().flush().unwrap();
+    for line in stdin.lock().lines() {
+        let line = line.expect("couldn't read the command");
+        if line.trim() == "quit" {
+            return;
+        }
+        match explorer.run(&line) {
+            Ok(output) => println!("{}", output),
+            Err(err) => println!("error: {:?}", err),
+        }
+        print!("> ");
+        io::stdout().flush().unwrap();
+    }
+}
+
+/// compare two chains saved as JSON, e.g. a chain and its backup, one difference per line
+fn diff_chains(a: &str, b: &str) {
+    let load = |path: &str| {
+        let json = fs::read_to_string(path).expect("couldn't read the chain");
+        chain_from_json(&json).expect("couldn't load the chain")
+    };
+    let diff = diff(&load(a), &load(b));
+    if diff.is_empty() {
+        println!("the chains are the same");
+    }
+    for line in diff.summary() {
+        println!("{}", line);
+    }
+}
+
+/// check test vectors, e.g. published by another implementation, agree with this one
+fn check_test_vectors(path: &str) {
+    let json = fs::read_to_string(path).expect("couldn't read the test vectors");
+    let vectors = TestVectors::from_json(&json).expect("couldn't load the test vectors");
+    let problems = fixtures::verify(&vectors);
+    if problems.is_empty() {
+        println!(
+            "all {} test vectors agree",
+            vectors.entries.len() + vectors.headers.len() + vectors.signatures.len()
+        );
+    }
+    for problem in problems {
+        println!("{}", problem);
+    }
+}
+
+/// write the keystore of the DNA recovered from a BIP39 phrase, read from stdin with its
+/// passphrase on the next line, and print the agent's public key
+fn recover_keystore(dna_hash: &str, path: &str) {
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    let mut read = |prompt: &str| {
+        println!("{}", prompt);
+        lines
+            .next()
+            .and_then(Result::ok)
+            .unwrap_or_default()
+    };
+    let phrase = read("Phrase:");
+    let passphrase = read("Passphrase (empty for none):");
+    let keystore = Keystore::from_mnemonic(&phrase, &passphrase, &mnemonic::dna_path(dna_hash))
+        .expect("couldn't recover the keys");
+    keystore
+        .persist(path.as_ref())
+        .expect("couldn't write the keystore");
+    let key = keystore
+        .derive_key(AGENT_KEY_PATH)
+        .expect("the keystore was just unlocked");
+    println!("Wrote the keystore of {}", key.to_base58());
+}
+
+/// print the public key or the invite held by a code made with --export-key or --invite
+fn import_code(code: &str) {
+    if code.trim().starts_with(invites::KEY_PREFIX) {
+        println!("{}", invites::import_key(code).expect("couldn't import the key"));
+        return;
+    }
+    let invite = Invite::from_code(code).expect("couldn't import the invite");
+    println!("DNA: {}", invite.dna_hash);
+    if let Some(uuid) = invite.uuid {
+        println!("uuid: {}", uuid);
+    }
+    for source in invite.dna_sources {
+        println!("DNA source: {}", source);
+    }
+    for agent in invite.bootstrap {
+        println!("bootstrap agent: {}", agent);
+    }
+    if let Some(inviter) = invite.inviter {
+        println!("invited by: {}", inviter);
+    }
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
 
@@ -23,12 +174,77 @@ fn main() {
         usage();
     }
 
+    if args[1] == "--import-dump" {
+        if args.len() < 3 {
+            usage();
+        }
+        import_dump(&args[2]);
+        return;
+    }
+
+    if args[1] == "--explore" {
+        if args.len() < 3 {
+            usage();
+        }
+        explore(&args[2]);
+        return;
+    }
+
+    if args[1] == "--diff" {
+        if args.len() < 4 {
+            usage();
+        }
+        diff_chains(&args[2], &args[3]);
+        return;
+    }
+
+    if args[1] == "--write-test-vectors" || args[1] == "--check-test-vectors" {
+        match args.get(2) {
+            Some(file) if args[1] == "--check-test-vectors" => check_test_vectors(file),
+            Some(file) => fs::write(file, fixtures::generate().to_json() + "\n")
+                .expect("couldn't write the test vectors"),
+            None => usage(),
+        }
+        return;
+    }
+
+    if args[1] == "--keygen" {
+        match (args.get(2), args.get(3)) {
+            (Some(flag), None) if flag == "--mnemonic" => {
+                let phrase = mnemonic::generate(24).expect("24 words is a BIP39 length");
+                println!("{}", phrase.as_str().expect("phrases are words"));
+                println!("Write the phrase down, it recovers the keys of every DNA");
+            }
+            (Some(flag), Some(key)) if flag == "--export-key" => {
+                println!("{}", invites::export_key(key).expect("couldn't export the key"));
+            }
+            (Some(flag), Some(dna_hash)) if flag == "--invite" => {
+                let mut invite = Invite::new(dna_hash);
+                invite.bootstrap = args[4..].to_vec();
+                println!("{}", invite.to_code());
+            }
+            (Some(flag), Some(code)) if flag == "--import" => import_code(code),
+            (Some(dna_hash), Some(file)) => recover_keystore(dna_hash, file),
+            _ => usage(),
+        }
+        return;
+    }
+
     let identity = &args[1];
 
     if identity == "" {
         usage();
     }
 
+    let dump_file = match args.get(2).map(String::as_str) {
+        Some("--dump-state") => match args.get(3) {
+            Some(file) => Some(file.clone()),
+            None => return usage(),
+        },
+        Some(_) => return usage(),
+        None => None,
+    };
+
     //let dna = holochain_dna::from_package_file("mydna.hcpkg");
     let dna = Dna::new();
     let agent = Agent::from_string(identity);
@@ -56,7 +272,24 @@ fn main() {
         // ...
     }
 
-    // stop the app
-    hc.stop().expect("couldn't stop the app");
-    println!("Stopped the app..");
+    // run until told to terminate
+    handle_termination();
+    println!("Running, send SIGTERM to shut down..");
+    while !TERMINATED.load(Ordering::SeqCst) {
+        thread::sleep(Duration::from_millis(100));
+    }
+
+    // dump the state for debugging before it goes
+    if let Some(file) = dump_file {
+        match fs::write(&file, hc.dump_state().to_json()) {
+            Ok(()) => println!("Dumped the state to {}", file),
+            Err(err) => println!("Couldn't dump the state to {}: {}", file, err),
+        }
+    }
+
+    // shut down the app
+    match hc.shutdown(Duration::from_millis(SHUTDOWN_TIMEOUT_MS)) {
+        Ok(()) => println!("Shut down the app.."),
+        Err(err) => println!("Shut down the app, but: {:?}", err),
+    }
 }